@@ -0,0 +1,646 @@
+use dbus::blocking::{stdintf::org_freedesktop_dbus::Properties, Connection};
+use dbus::channel::Token;
+use dbus::message::MatchRule;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::client::NmClient;
+use crate::device::{DeviceState, DeviceStateReason};
+use crate::error::nm_error;
+use crate::nm::{
+    ACTIVE_CONNECTION_INTERFACE, DEVICE_INTERFACE, NM_BUS_NAME, NM_INTERFACE, NM_PATH,
+    NO_OBJECT_PATH,
+};
+use crate::settings::ConnectionSettings;
+use crate::{secret, security};
+use crate::{
+    ActiveConnectionInfo, ApSecurity, Connectivity, DbusTimeouts, DeviceStateEvent,
+    DisconnectOutcome, IpInfo, PinnedAccessPoint, ProfileChange, WifiConfigError,
+};
+
+/// Default time to wait for an activation to complete.
+pub(crate) const DEFAULT_ACTIVATION_TIMEOUT: Duration = Duration::from_secs(30);
+/// Default time to wait for a hidden network, where association takes noticeably longer.
+pub(crate) const DEFAULT_HIDDEN_ACTIVATION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// `NM_MANAGER_ERROR_CONNECTION_NOT_ACTIVE`, returned when deactivating an inactive
+/// connection.
+const CONNECTION_NOT_ACTIVE_ERROR: &str = "org.freedesktop.NetworkManager.ConnectionNotActive";
+
+/// The profile and active connection set up by [`connect`](crate::connect).
+///
+/// Keeps the object paths NetworkManager returned, so exactly this connection can later be
+/// torn down with [`deactivate`] or removed with [`delete`](crate::delete), without
+/// searching by SSID.
+///
+/// With [`Backend::WpaSupplicant`](crate::Backend::WpaSupplicant), `settings_path` is the
+/// wpa_supplicant network object, `active_path` the interface object and `uuid` is
+/// empty. With [`Backend::Iwd`](crate::Backend::Iwd), `settings_path` is the known
+/// network (or the network, if iwd didn't save it) and `active_path` the station.
+/// [`deactivate`] and [`delete`](crate::delete) only work with NetworkManager.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ActivationHandle {
+    /// D-Bus object path of the saved profile (`Settings.Connection`).
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::nm::serialize_path"))]
+    pub settings_path: dbus::Path<'static>,
+    /// D-Bus object path of the active connection (`Connection.Active`).
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::nm::serialize_path"))]
+    pub active_path: dbus::Path<'static>,
+    /// Interface name of the Wi-Fi device the connection was activated on, e.g. `wlan0`.
+    pub interface: String,
+    /// With [`DeviceSelection::All`](crate::DeviceSelection::All), the activations of the
+    /// same profile on the other devices; empty otherwise.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    pub additional: Vec<DeviceActivation>,
+    /// Profile UUID (`connection.uuid`), as read back from NetworkManager.
+    pub uuid: String,
+    /// Whether the profile was newly created or an existing one was updated.
+    pub change: ProfileChange,
+    /// The access point security found for [`Security::Auto`](crate::Security::Auto);
+    /// `None` if the security was given explicitly, or if the SSID wasn't visible and
+    /// WPA-PSK was assumed.
+    pub detected_security: Option<ApSecurity>,
+    /// The result of NetworkManager's connectivity check once the connection was up, with
+    /// [`ConnectOptions::verify_connectivity`](crate::ConnectOptions::verify_connectivity);
+    /// `None` otherwise.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub connectivity: Option<Connectivity>,
+    /// The login page of the captive portal when [`connectivity`](Self::connectivity) is
+    /// [`Connectivity::Portal`] and the network announced it; see
+    /// [`WifiStatus::portal_url`](crate::WifiStatus::portal_url).
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub portal_url: Option<String>,
+    /// The addresses, gateways and DNS servers of the connection once it was up, from
+    /// [`connect_and_wait`](crate::connect_and_wait) through NetworkManager; `None`
+    /// otherwise, or if they couldn't be read. See [`ip_info`](crate::ip_info) for
+    /// connections that weren't waited for.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub ip: Option<IpInfo>,
+    /// The connection once it was up, from [`connect_and_wait`](crate::connect_and_wait)
+    /// through NetworkManager; `None` otherwise, or if it couldn't be read. See
+    /// [`active_connection`](crate::active_connection) for the same later on.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub active: Option<ActiveConnectionInfo>,
+    /// The access point the connection was pinned to with
+    /// [`ConnectOptions::pin_strongest_ap`](crate::ConnectOptions::pin_strongest_ap);
+    /// `None` otherwise, or if no access point broadcast the SSID.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub access_point: Option<PinnedAccessPoint>,
+}
+
+/// The profile of an [`ActivationHandle`] activated on one more device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DeviceActivation {
+    /// Interface name of the device.
+    pub interface: String,
+    /// D-Bus object path of the active connection on it.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::nm::serialize_path"))]
+    pub active_path: dbus::Path<'static>,
+}
+
+/// What [`connect_and_wait`](crate::connect_and_wait) undid after a failed activation;
+/// see [`WifiConfigError::RolledBack`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Rollback {
+    /// Whether the profile the attempt created was deleted.
+    pub profile_deleted: bool,
+    /// Whether the saved profile the attempt updated got its previous settings and
+    /// secrets back.
+    pub profile_restored: bool,
+    /// The profile that was active on the device before the attempt, if any.
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "crate::nm::serialize_optional_path")
+    )]
+    pub previous_profile: Option<dbus::Path<'static>>,
+    /// Whether NetworkManager accepted re-activating
+    /// [`previous_profile`](Self::previous_profile). It isn't waited for.
+    pub restored: bool,
+}
+
+/// The profile active on `device_path` right now, to restore after a failed attempt.
+///
+/// Best effort: a failure to read it is logged and treated as none.
+pub(crate) fn active_profile(
+    conn: &Connection,
+    device_path: &dbus::Path,
+    timeouts: DbusTimeouts,
+) -> Option<dbus::Path<'static>> {
+    let result = (|| -> Result<Option<dbus::Path<'static>>, dbus::Error> {
+        let active_path: dbus::Path<'static> = conn
+            .with_proxy(NM_BUS_NAME, device_path, timeouts.query)
+            .get(DEVICE_INTERFACE, "ActiveConnection")?;
+        if &*active_path == NO_OBJECT_PATH {
+            return Ok(None);
+        }
+        conn.with_proxy(NM_BUS_NAME, &active_path, timeouts.query)
+            .get(ACTIVE_CONNECTION_INTERFACE, "Connection")
+            .map(Some)
+    })();
+    result.unwrap_or_else(|e| {
+        log::debug!("can't read the active profile of {}: {}", device_path, e);
+        None
+    })
+}
+
+/// A saved profile's settings and secrets from before an attempt updated it, to write
+/// back if the attempt fails. The secrets are wiped when it is dropped.
+pub(crate) struct ProfileSnapshot {
+    path: dbus::Path<'static>,
+    settings: ConnectionSettings,
+}
+
+impl Drop for ProfileSnapshot {
+    fn drop(&mut self) {
+        secret::wipe_settings(&mut self.settings);
+    }
+}
+
+/// Snapshots the saved profile at `path`, whose `GetSettings` result is `settings`,
+/// together with its secrets.
+///
+/// Best effort: if a secret section can't be read, e.g. because the caller may not see
+/// the secrets, the failure is logged and `None` returned, since writing the settings
+/// back without them would drop the stored password.
+pub(crate) fn snapshot_profile(
+    client: &dyn NmClient,
+    path: dbus::Path<'static>,
+    mut settings: ConnectionSettings,
+) -> Option<ProfileSnapshot> {
+    let mut sections: Vec<&str> = security::SECRETS
+        .iter()
+        .map(|(section, _, _)| *section)
+        .filter(|section| settings.contains_key(*section))
+        .collect();
+    sections.dedup();
+    for section in sections {
+        match client.get_secrets(&path, section) {
+            Ok(mut secrets) => {
+                if let Some(values) = secrets.remove(section) {
+                    settings.entry(section.into()).or_default().extend(values);
+                }
+            }
+            Err(e) => {
+                log::info!(
+                    "can't read the {} secrets of {}; it won't be restored on failure: {}",
+                    section,
+                    path,
+                    e
+                );
+                secret::wipe_settings(&mut settings);
+                return None;
+            }
+        }
+    }
+    Some(ProfileSnapshot { path, settings })
+}
+
+/// Undoes the failed activation of `handle` on `device_path`: deletes its profile if it
+/// was created, writes `snapshot` back if its profile was updated, and re-activates
+/// `previous_profile`.
+///
+/// Returns `error` wrapped in [`WifiConfigError::RolledBack`], or as it is if there was
+/// nothing to undo. Failures along the way are logged, not returned, so `error` stays
+/// the reported cause.
+pub(crate) fn roll_back(
+    client: &dyn NmClient,
+    handle: &ActivationHandle,
+    device_path: &dbus::Path,
+    previous_profile: Option<dbus::Path<'static>>,
+    snapshot: Option<ProfileSnapshot>,
+    error: WifiConfigError,
+) -> WifiConfigError {
+    // Deleting the profile also ends its activation on every device.
+    let created = matches!(
+        handle.change,
+        ProfileChange::Created | ProfileChange::CreatedSeparate
+    );
+    let profile_deleted = created
+        && match client.delete_connection(&handle.settings_path) {
+            Ok(()) => {
+                log::info!("deleted profile {} after: {}", handle.settings_path, error);
+                true
+            }
+            Err(e) => {
+                log::warn!("can't delete profile {}: {}", handle.settings_path, e);
+                false
+            }
+        };
+    let updated = matches!(
+        handle.change,
+        ProfileChange::Updated | ProfileChange::UpdatedUnverified
+    );
+    let profile_restored = match snapshot {
+        Some(mut snapshot) if updated && snapshot.path == handle.settings_path => {
+            let settings = std::mem::take(&mut snapshot.settings);
+            match client.update(&snapshot.path, settings) {
+                Ok(()) => {
+                    log::info!("restored profile {} after: {}", snapshot.path, error);
+                    true
+                }
+                Err(e) => {
+                    log::warn!("can't restore profile {}: {}", snapshot.path, e);
+                    false
+                }
+            }
+        }
+        _ => false,
+    };
+    // The failed profile itself is only worth re-activating with its old settings back.
+    let previous_profile =
+        previous_profile.filter(|path| *path != handle.settings_path || profile_restored);
+    let restored = previous_profile.as_ref().is_some_and(|previous| {
+        let specific_object = dbus::Path::new("/").unwrap();
+        match client.activate_connection(previous, device_path, &specific_object) {
+            Ok(_) => {
+                log::info!("re-activated profile {}", previous);
+                true
+            }
+            Err(e) => {
+                log::warn!("can't re-activate profile {}: {}", previous, e);
+                false
+            }
+        }
+    });
+    if !profile_deleted && !profile_restored && previous_profile.is_none() {
+        return error;
+    }
+    WifiConfigError::RolledBack {
+        error: Box::new(error),
+        rollback: Rollback {
+            profile_deleted,
+            profile_restored,
+            previous_profile,
+            restored,
+        },
+    }
+}
+
+/// Final state of an activation attempt, as reported by [`wait_for_activation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ActivationOutcome {
+    /// The terminal device state: `Activated`, `Failed` or `Disconnected`.
+    pub state: DeviceState,
+    /// The reason NetworkManager gave for entering that state.
+    pub reason: DeviceStateReason,
+}
+
+impl ActivationOutcome {
+    /// Returns `true` if the device ended up connected.
+    pub fn is_activated(&self) -> bool {
+        self.state == DeviceState::Activated
+    }
+}
+
+/// Waits for the activation running on `device_path` to finish.
+///
+/// Subscribes to `StateChanged` on `org.freedesktop.NetworkManager.Device` and processes
+/// signals until the device reaches a terminal state or `timeout` passes. If the device is
+/// already `ACTIVATED` or `FAILED` when called, that state is returned right away, so this
+/// can be attached after [`connect`](crate::connect) has returned.
+///
+/// A `DISCONNECTED` state only counts as terminal once an activation stage (`PREPARE`
+/// through `SECONDARIES`) has been observed, since devices pass through it while switching
+/// networks.
+///
+/// # Errors
+///
+/// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+/// - [`WifiConfigError::NetworkManagerUnavailable`] if NetworkManager isn't running.
+/// - [`WifiConfigError::DeviceQueryFailed`] if subscribing or reading the device fails.
+/// - [`WifiConfigError::ActivationTimeout`] if no terminal state arrived in time.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// let device = wifi_config::list_wifi_devices()?.remove(0);
+/// let outcome = wifi_config::wait_for_activation(&device.path, Duration::from_secs(30))?;
+/// println!("{:?} ({:?})", outcome.state, outcome.reason);
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn wait_for_activation(
+    device_path: &dbus::Path,
+    timeout: Duration,
+) -> Result<ActivationOutcome, WifiConfigError> {
+    let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+    let watcher = StateChangeWatcher::new(&conn, device_path)?;
+
+    let device_proxy = conn.with_proxy(NM_BUS_NAME, device_path, DbusTimeouts::default().query);
+    let (state, reason): (u32, u32) = device_proxy
+        .get(DEVICE_INTERFACE, "StateReason")
+        .map_err(nm_error(WifiConfigError::DeviceQueryFailed))?;
+    let current = ActivationOutcome {
+        state: DeviceState::from(state),
+        reason: DeviceStateReason::from(reason),
+    };
+    match current.state {
+        DeviceState::Activated | DeviceState::Failed => Ok(current),
+        state => watcher.wait(timeout, is_activating(state)),
+    }
+}
+
+/// Deactivates the connection brought up by [`connect`](crate::connect).
+///
+/// Calls `DeactivateConnection` with [`ActivationHandle::active_path`], and with those of
+/// the [`additional`](ActivationHandle::additional) activations. The profile stays saved,
+/// so NetworkManager may autoconnect it again later; use [`delete`](crate::delete) to
+/// remove it.
+///
+/// Returns [`DisconnectOutcome::AlreadyDisconnected`] if none of the connections was
+/// still active, e.g. because the device moved on to another network.
+///
+/// # Errors
+///
+/// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+/// - [`WifiConfigError::NetworkManagerUnavailable`] if NetworkManager isn't running.
+/// - [`WifiConfigError::DeactivateFailed`] if NetworkManager refuses.
+///
+/// # Example
+///
+/// ```no_run
+/// let handle = wifi_config::send_wifi_to_network_manager("MyHomeWiFi", "supersecret123")?;
+/// wifi_config::deactivate(&handle)?;
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn deactivate(handle: &ActivationHandle) -> Result<DisconnectOutcome, WifiConfigError> {
+    let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+    deactivate_on(&conn, handle, DbusTimeouts::default())
+}
+
+/// [`deactivate`] over `conn`.
+pub(crate) fn deactivate_on(
+    conn: &Connection,
+    handle: &ActivationHandle,
+    timeouts: DbusTimeouts,
+) -> Result<DisconnectOutcome, WifiConfigError> {
+    let proxy = conn.with_proxy(NM_BUS_NAME, NM_PATH, timeouts.query);
+    let active_paths = std::iter::once(&handle.active_path).chain(
+        handle
+            .additional
+            .iter()
+            .map(|activation| &activation.active_path),
+    );
+    let mut outcome = DisconnectOutcome::AlreadyDisconnected;
+    for active_path in active_paths {
+        let result: Result<(), _> =
+            proxy.method_call(NM_INTERFACE, "DeactivateConnection", (active_path,));
+        match result {
+            Ok(()) => outcome = DisconnectOutcome::Disconnected,
+            Err(e) if e.name() == Some(CONNECTION_NOT_ACTIVE_ERROR) => {}
+            Err(e) => return Err(nm_error(WifiConfigError::DeactivateFailed)(e)),
+        }
+    }
+    Ok(outcome)
+}
+
+/// Returns `true` for the intermediate states of an activation.
+pub(crate) fn is_activating(state: DeviceState) -> bool {
+    matches!(
+        state,
+        DeviceState::Prepare
+            | DeviceState::Config
+            | DeviceState::NeedAuth
+            | DeviceState::IpConfig
+            | DeviceState::IpCheck
+            | DeviceState::Secondaries
+    )
+}
+
+/// Collects `StateChanged` signals of one device for as long as it lives.
+pub(crate) struct StateChangeWatcher<'a> {
+    conn: &'a Connection,
+    token: Token,
+    changes: Arc<Mutex<VecDeque<DeviceStateEvent>>>,
+}
+
+impl<'a> StateChangeWatcher<'a> {
+    /// Subscribes to `StateChanged` on `device_path`.
+    pub(crate) fn new(
+        conn: &'a Connection,
+        device_path: &dbus::Path,
+    ) -> Result<Self, WifiConfigError> {
+        let changes = Arc::new(Mutex::new(VecDeque::new()));
+        let sink = Arc::clone(&changes);
+        let rule = MatchRule::new_signal(DEVICE_INTERFACE, "StateChanged")
+            .with_path(device_path.clone().into_static());
+        let token = conn
+            .add_match(
+                rule,
+                move |(new_state, old_state, reason): (u32, u32, u32), _, _| {
+                    sink.lock().unwrap().push_back(DeviceStateEvent {
+                        old: DeviceState::from(old_state),
+                        new: DeviceState::from(new_state),
+                        reason: DeviceStateReason::from(reason),
+                    });
+                    true
+                },
+            )
+            .map_err(nm_error(WifiConfigError::DeviceQueryFailed))?;
+        Ok(StateChangeWatcher {
+            conn,
+            token,
+            changes,
+        })
+    }
+
+    /// Processes signals until a terminal state arrives or `timeout` passes.
+    ///
+    /// `activating` tells whether the activation is already known to be in progress.
+    pub(crate) fn wait(
+        &self,
+        timeout: Duration,
+        activating: bool,
+    ) -> Result<ActivationOutcome, WifiConfigError> {
+        self.wait_reporting(timeout, activating, &mut |_| {})
+    }
+
+    /// Like [`wait`](Self::wait), passing every state change to `progress` as it arrives.
+    pub(crate) fn wait_reporting(
+        &self,
+        timeout: Duration,
+        mut activating: bool,
+        progress: &mut dyn FnMut(&DeviceStateEvent),
+    ) -> Result<ActivationOutcome, WifiConfigError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            while let Some(change) = self.changes.lock().unwrap().pop_front() {
+                log::debug!("device state {:?} ({:?})", change.new, change.reason);
+                progress(&change);
+                let outcome = ActivationOutcome {
+                    state: change.new,
+                    reason: change.reason,
+                };
+                match change.new {
+                    DeviceState::Activated | DeviceState::Failed => return Ok(outcome),
+                    DeviceState::Disconnected if activating => return Ok(outcome),
+                    state => activating |= is_activating(state),
+                }
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(WifiConfigError::ActivationTimeout(timeout));
+            }
+            self.conn
+                .process(remaining)
+                .map_err(nm_error(WifiConfigError::DeviceQueryFailed))?;
+        }
+    }
+}
+
+impl Drop for StateChangeWatcher<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = self.conn.remove_match(self.token) {
+            log::warn!("failed to remove the StateChanged match: {}", e);
+        }
+    }
+}
+
+/// Maps the reason of a failed activation to an error.
+pub(crate) fn activation_error(reason: DeviceStateReason) -> WifiConfigError {
+    if reason.is_auth_failure() {
+        WifiConfigError::AuthenticationFailed(reason)
+    } else {
+        WifiConfigError::ConnectionFailed(reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::{device_path, MockDevice, MockNmClient};
+    use crate::settings::build_connection_settings;
+    use crate::ConnectOptions;
+
+    fn handle(client: &MockNmClient, change: ProfileChange) -> ActivationHandle {
+        let settings = build_connection_settings(b"Office", &ConnectOptions::default());
+        let specific_object = dbus::Path::new("/").unwrap();
+        let (settings_path, active_path) = client
+            .add_and_activate(settings, &device_path(1), &specific_object)
+            .unwrap();
+        ActivationHandle {
+            settings_path,
+            active_path,
+            interface: "wlan0".into(),
+            additional: Vec::new(),
+            uuid: "generated-uuid".into(),
+            change,
+            detected_security: None,
+            connectivity: None,
+            portal_url: None,
+            ip: None,
+            active: None,
+            access_point: None,
+        }
+    }
+
+    #[test]
+    fn deletes_the_new_profile_and_restores_the_previous_one() {
+        let client = MockNmClient::with_devices(vec![MockDevice::new(1, "wlan0", 2)]);
+        let home = build_connection_settings(b"Home", &ConnectOptions::default());
+        let previous = client.save(home, "0b7cb1a4-5f7c-4d36-9d0e-6a1f3c2e8b90");
+        let handle = handle(&client, ProfileChange::Created);
+
+        let timeout = WifiConfigError::ActivationTimeout(Duration::from_secs(30));
+        let error = roll_back(
+            &client,
+            &handle,
+            &device_path(1),
+            Some(previous.clone()),
+            None,
+            timeout,
+        );
+        let WifiConfigError::RolledBack { error, rollback } = error else {
+            panic!("expected RolledBack");
+        };
+        assert!(matches!(*error, WifiConfigError::ActivationTimeout(_)));
+        assert_eq!(
+            rollback,
+            Rollback {
+                profile_deleted: true,
+                profile_restored: false,
+                previous_profile: Some(previous.clone()),
+                restored: true,
+            }
+        );
+        assert_eq!(
+            client.calls()[1..],
+            [
+                format!("Delete {}", handle.settings_path),
+                format!("ActivateConnection {} {}", previous, device_path(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn restores_and_reactivates_the_updated_profile() {
+        let client = MockNmClient::with_devices(vec![MockDevice::new(1, "wlan0", 2)]);
+        let old = ConnectOptions::builder().password("oldpassword").build();
+        let saved = client.save(
+            build_connection_settings(b"Office", &old),
+            "0b7cb1a4-5f7c-4d36-9d0e-6a1f3c2e8b90",
+        );
+        let existing = client.get_settings(&saved).unwrap();
+        let snapshot = snapshot_profile(&client, saved.clone(), existing).unwrap();
+
+        let new = ConnectOptions::builder().password("newpassword").build();
+        client
+            .update(&saved, build_connection_settings(b"Office", &new))
+            .unwrap();
+        let mut handle = handle(&client, ProfileChange::Updated);
+        handle.settings_path = saved.clone();
+
+        let failed = WifiConfigError::AuthenticationFailed(DeviceStateReason::from(7));
+        let error = roll_back(
+            &client,
+            &handle,
+            &device_path(1),
+            Some(saved.clone()),
+            Some(snapshot),
+            failed,
+        );
+        let WifiConfigError::RolledBack { rollback, .. } = error else {
+            panic!("expected RolledBack");
+        };
+        assert_eq!(
+            rollback,
+            Rollback {
+                profile_deleted: false,
+                profile_restored: true,
+                previous_profile: Some(saved.clone()),
+                restored: true,
+            }
+        );
+        let calls = client.calls();
+        assert_eq!(
+            calls[calls.len() - 2..],
+            [
+                format!("Update {}", saved),
+                format!("ActivateConnection {} {}", saved, device_path(1)),
+            ]
+        );
+        let psk = client
+            .get_secrets(&saved, "802-11-wireless-security")
+            .unwrap()
+            .remove("802-11-wireless-security")
+            .and_then(|section| dbus::arg::prop_cast::<String>(&section, "psk").cloned());
+        assert_eq!(psk.as_deref(), Some("oldpassword"));
+    }
+
+    #[test]
+    fn keeps_updated_profiles_without_a_snapshot() {
+        let client = MockNmClient::with_devices(vec![MockDevice::new(1, "wlan0", 2)]);
+        let handle = handle(&client, ProfileChange::Updated);
+        let failed = WifiConfigError::ConnectionFailed(DeviceStateReason::from(0));
+        let error = roll_back(&client, &handle, &device_path(1), None, None, failed);
+        assert!(matches!(error, WifiConfigError::ConnectionFailed(_)));
+        assert_eq!(client.methods(), ["AddAndActivateConnection"]);
+    }
+}
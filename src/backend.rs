@@ -0,0 +1,1500 @@
+use dbus::arg::{prop_cast, Variant};
+use dbus::blocking::Connection;
+use std::time::{Duration, SystemTime};
+
+use crate::client::{DbusNmClient, NmClient};
+use crate::nm::{DBUS_TIMEOUT, NM_BUS_NAME, NO_OBJECT_PATH};
+use crate::{
+    activation, connectivity, device, ip_info, iwd, profiles, radio, scan, secret, settings,
+    status, version, wpa_supplicant,
+};
+use crate::{
+    AccessPointInfo, ActivationHandle, ActiveConnectionInfo, ApSecurity, ConnectOptions,
+    DbusTimeouts, DeviceActivation, DeviceSelection, DeviceState, DeviceStateEvent, Ipv4Config,
+    Ipv6Config, PinnedAccessPoint, ProfileChange, SecretStorage, Security, WifiConfigError,
+    WifiDevice, WifiManager, WifiStatus, WirelessMode,
+};
+
+/// Well-known bus name of the message bus itself (`NameHasOwner`, ...).
+const DBUS_BUS_NAME: &str = "org.freedesktop.DBus";
+/// Object path of the message bus.
+const DBUS_PATH: &str = "/org/freedesktop/DBus";
+
+/// The service [`connect`](crate::connect) configures the network through.
+///
+/// Set it in [`ConnectOptions::backend`], or use [`Backend::detect`] to pick whichever
+/// service is running. [`scan_networks`](Self::scan_networks) and
+/// [`active_connection`](Self::active_connection) work with every backend; everything else
+/// (profiles, hotspots, disconnecting, ...) always talks to NetworkManager.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Backend {
+    /// NetworkManager (`org.freedesktop.NetworkManager`), which supports every option.
+    #[default]
+    NetworkManager,
+    /// wpa_supplicant on its own (`fi.w1.wpa_supplicant1`), for systems without
+    /// NetworkManager.
+    ///
+    /// Only associates with the network: addresses have to come from a separate DHCP
+    /// client, and options that need NetworkManager (IP and DNS settings, band, MAC
+    /// policy, ...) are rejected with [`WifiConfigError::UnsupportedByBackend`]. See
+    /// [`ActivationHandle`] for how the handle's fields are used.
+    WpaSupplicant,
+    /// iwd (`net.connman.iwd`), which replaces NetworkManager on some distributions.
+    ///
+    /// The password is handed to iwd by a short-lived agent, and iwd saves the network
+    /// as a known network. A known network keeps its saved password unless iwd rejects
+    /// it, and the new one replaces it only once it connected. As with [`Backend::WpaSupplicant`], options that need
+    /// NetworkManager are rejected, and so are WEP and enterprise networks.
+    Iwd,
+}
+
+impl Backend {
+    /// Picks the backend whose service owns its bus name: NetworkManager if it runs,
+    /// then iwd, then wpa_supplicant.
+    ///
+    /// NetworkManager is checked first because it often runs on top of wpa_supplicant or
+    /// iwd. If none of them runs, [`Backend::NetworkManager`] is returned, so the
+    /// following call fails with [`WifiConfigError::NetworkManagerUnavailable`].
+    ///
+    /// # Errors
+    ///
+    /// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+    /// - [`WifiConfigError::DeviceQueryFailed`] if the bus doesn't answer `NameHasOwner`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use wifi_config::Backend;
+    ///
+    /// for ap in Backend::detect()?.scan_networks(false)? {
+    ///     println!("{} {}%", ap.ssid_lossy, ap.strength);
+    /// }
+    /// # Ok::<(), wifi_config::WifiConfigError>(())
+    /// ```
+    pub fn detect() -> Result<Backend, WifiConfigError> {
+        let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+        let proxy = conn.with_proxy(DBUS_BUS_NAME, DBUS_PATH, DBUS_TIMEOUT);
+        for (bus_name, backend) in [
+            (NM_BUS_NAME, Backend::NetworkManager),
+            (iwd::IWD_BUS_NAME, Backend::Iwd),
+            (wpa_supplicant::WPA_BUS_NAME, Backend::WpaSupplicant),
+        ] {
+            let (owned,): (bool,) = proxy
+                .method_call(DBUS_BUS_NAME, "NameHasOwner", (bus_name,))
+                .map_err(WifiConfigError::DeviceQueryFailed)?;
+            if owned {
+                return Ok(backend);
+            }
+        }
+        Ok(Backend::NetworkManager)
+    }
+
+    /// Lists the networks visible to the first usable Wi-Fi device, from the service's cached
+    /// scan results or, with `fresh`, after a new scan.
+    ///
+    /// With NetworkManager this is [`scan_networks`](crate::scan_networks). wpa_supplicant
+    /// also reports every BSSID, but has no raw NetworkManager flags (they are `0`). iwd
+    /// reports one entry per network rather than per access point, with an empty
+    /// `hw_address` and a `frequency` of `0`.
+    ///
+    /// # Errors
+    ///
+    /// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+    /// - [`WifiConfigError::NoWifiDevice`] if the service has no Wi-Fi device.
+    /// - [`WifiConfigError::ScanFailed`] if the networks can't be listed or read.
+    /// - [`WifiConfigError::UnsupportedByBackend`] for a `fresh` scan with iwd or
+    ///   wpa_supplicant.
+    pub fn scan_networks(self, fresh: bool) -> Result<Vec<AccessPointInfo>, WifiConfigError> {
+        self.implementation()
+            .scan_networks(fresh, DbusTimeouts::default())
+    }
+
+    /// Reports the connection up on the first usable Wi-Fi device; `Ok(None)` if nothing.
+    ///
+    /// With NetworkManager this is [`active_connection`](crate::active_connection). iwd and
+    /// wpa_supplicant have no profile UUIDs and don't say since when the connection is up,
+    /// so `uuid` is empty and `started_at` is when the status was read; they don't know
+    /// the IP configuration, MTU, radio or connectivity either, so `ip4` and `mtu` are
+    /// `None`, `connection_id` is the network name, and iwd doesn't report the BSSID.
+    ///
+    /// # Errors
+    ///
+    /// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+    /// - [`WifiConfigError::NoWifiDevice`] if the service has no Wi-Fi device.
+    /// - [`WifiConfigError::DeviceQueryFailed`] if a property can't be read.
+    pub fn active_connection(self) -> Result<Option<ActiveConnectionInfo>, WifiConfigError> {
+        self.implementation()
+            .active_connection(DbusTimeouts::default())
+    }
+
+    /// Reports what the first usable Wi-Fi device is connected to; `Ok(None)` if nothing.
+    ///
+    /// Deprecated: [`active_connection`](Self::active_connection) reports the connection
+    /// in the same shape [`connect_and_wait`](crate::connect_and_wait) does.
+    ///
+    /// With NetworkManager this is [`current_status`](crate::current_status). The other
+    /// services don't know the IP configuration, so `ipv4_address` is `None`, and
+    /// `connection_id` is the network name; iwd doesn't report the BSSID and frequency
+    /// either.
+    ///
+    /// # Errors
+    ///
+    /// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+    /// - [`WifiConfigError::NoWifiDevice`] if the service has no Wi-Fi device.
+    /// - [`WifiConfigError::DeviceQueryFailed`] if a property can't be read.
+    #[deprecated(note = "use `Backend::active_connection`")]
+    pub fn current_status(self) -> Result<Option<WifiStatus>, WifiConfigError> {
+        self.implementation()
+            .current_status(DbusTimeouts::default())
+    }
+
+    /// The implementation of this backend.
+    pub(crate) fn implementation(self) -> &'static dyn WifiBackend {
+        match self {
+            Backend::NetworkManager => &NetworkManagerBackend,
+            Backend::WpaSupplicant => &wpa_supplicant::WpaSupplicantBackend,
+            Backend::Iwd => &iwd::IwdBackend,
+        }
+    }
+}
+
+/// What a [`Backend`] implements.
+///
+/// The connect methods are called with options that already passed
+/// [`ConnectOptions::validate`].
+pub(crate) trait WifiBackend {
+    /// Hands the network to the service and returns once it accepted it.
+    fn connect(
+        &self,
+        ssid: &[u8],
+        options: &ConnectOptions,
+    ) -> Result<ActivationHandle, WifiConfigError>;
+
+    /// Like [`connect`](Self::connect), but returns once the connection is up, passing
+    /// the device's state changes to `progress` meanwhile if the service reports them.
+    fn connect_and_wait(
+        &self,
+        ssid: &[u8],
+        options: &ConnectOptions,
+        progress: &mut dyn FnMut(&DeviceStateEvent),
+    ) -> Result<ActivationHandle, WifiConfigError>;
+
+    /// See [`Backend::scan_networks`].
+    fn scan_networks(
+        &self,
+        fresh: bool,
+        timeouts: DbusTimeouts,
+    ) -> Result<Vec<AccessPointInfo>, WifiConfigError>;
+
+    /// See [`Backend::current_status`].
+    fn current_status(&self, timeouts: DbusTimeouts)
+        -> Result<Option<WifiStatus>, WifiConfigError>;
+
+    /// See [`Backend::active_connection`].
+    fn active_connection(
+        &self,
+        timeouts: DbusTimeouts,
+    ) -> Result<Option<ActiveConnectionInfo>, WifiConfigError> {
+        Ok(self
+            .current_status(timeouts)?
+            .map(ActiveConnectionInfo::from_status))
+    }
+}
+
+/// Fails with [`WifiConfigError::UnsupportedByBackend`] if `options` use anything only
+/// NetworkManager can apply, or any of the `extra` options `backend` doesn't support,
+/// rather than silently ignoring it.
+pub(crate) fn check_supported(
+    backend: &str,
+    options: &ConnectOptions,
+    extra: &[(bool, &str)],
+) -> Result<(), WifiConfigError> {
+    let network_manager_only = [
+        (options.device_path.is_some(), "device paths"),
+        (options.ipv4 != Ipv4Config::default(), "IPv4 settings"),
+        (
+            options.ipv6 != Ipv6Config::default() || options.ipv6_privacy.is_some(),
+            "IPv6 settings",
+        ),
+        (!options.dhcp.is_empty(), "DHCP options"),
+        (
+            !options.dns.is_empty() || !options.dns_search.is_empty(),
+            "DNS settings",
+        ),
+        (options.metered.is_some(), "metered connections"),
+        (
+            options.secret_storage != SecretStorage::System,
+            "secret storage flags",
+        ),
+        (!options.permissions.is_empty(), "profile permissions"),
+        (
+            options.device_selection == DeviceSelection::All,
+            "activations on every device",
+        ),
+        (options.zone.is_some(), "firewall zones"),
+        (options.enable_radio, "enabling the radio"),
+        (options.verify_connectivity, "connectivity checks"),
+        (
+            options.connection_id.is_some() || options.uuid.is_some() || options.stable_uuid,
+            "profile ids and UUIDs",
+        ),
+        (options.retry.attempts > 1, "retries"),
+        (
+            options.mode != WirelessMode::Infrastructure,
+            "non-infrastructure modes",
+        ),
+        (
+            options.band.is_some() || options.channel.is_some(),
+            "band and channel locks",
+        ),
+        (
+            options.mac_policy.is_some() || options.scan_mac_randomization.is_some(),
+            "MAC address policies",
+        ),
+        (options.powersave.is_some(), "power saving settings"),
+        (options.mtu.is_some(), "MTU overrides"),
+        (options.cipher_policy.is_some(), "cipher policies"),
+        (options.min_strength.is_some(), "signal strength gates"),
+        (options.pin_strongest_ap, "pinned access points"),
+        (!options.extra_settings.is_empty(), "extra settings"),
+    ];
+    match network_manager_only
+        .iter()
+        .chain(extra)
+        .find(|(set, _)| *set)
+    {
+        Some((_, what)) => Err(WifiConfigError::UnsupportedByBackend(format!(
+            "{} need NetworkManager; the {} backend can't apply them",
+            what, backend
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Fails with [`WifiConfigError::UnsupportedByBackend`] for a fresh scan, which only the
+/// NetworkManager backend can request.
+pub(crate) fn check_cached_scan(backend: &str, fresh: bool) -> Result<(), WifiConfigError> {
+    if fresh {
+        return Err(WifiConfigError::UnsupportedByBackend(format!(
+            "fresh scans need NetworkManager; the {} backend only lists cached results",
+            backend
+        )));
+    }
+    Ok(())
+}
+
+/// [`Backend::NetworkManager`].
+struct NetworkManagerBackend;
+
+impl WifiBackend for NetworkManagerBackend {
+    fn connect(
+        &self,
+        ssid: &[u8],
+        options: &ConnectOptions,
+    ) -> Result<ActivationHandle, WifiConfigError> {
+        WifiManager::new()?.connect_validated(ssid, options)
+    }
+
+    fn connect_and_wait(
+        &self,
+        ssid: &[u8],
+        options: &ConnectOptions,
+        progress: &mut dyn FnMut(&DeviceStateEvent),
+    ) -> Result<ActivationHandle, WifiConfigError> {
+        let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+        connect_and_wait_on(&conn, ssid, options, progress)
+    }
+
+    fn scan_networks(
+        &self,
+        fresh: bool,
+        timeouts: DbusTimeouts,
+    ) -> Result<Vec<AccessPointInfo>, WifiConfigError> {
+        WifiManager::new()?.with_timeouts(timeouts).scan(fresh)
+    }
+
+    #[allow(deprecated)]
+    fn current_status(
+        &self,
+        timeouts: DbusTimeouts,
+    ) -> Result<Option<WifiStatus>, WifiConfigError> {
+        WifiManager::new()?.with_timeouts(timeouts).status()
+    }
+
+    fn active_connection(
+        &self,
+        timeouts: DbusTimeouts,
+    ) -> Result<Option<ActiveConnectionInfo>, WifiConfigError> {
+        WifiManager::new()?
+            .with_timeouts(timeouts)
+            .active_connection()
+    }
+}
+
+/// [`NetworkManagerBackend`]'s `connect_and_wait` over `conn`.
+pub(crate) fn connect_and_wait_on(
+    conn: &Connection,
+    ssid: &[u8],
+    options: &ConnectOptions,
+    progress: &mut dyn FnMut(&DeviceStateEvent),
+) -> Result<ActivationHandle, WifiConfigError> {
+    let client = DbusNmClient::new(conn, options.timeouts).with_retry(options.retry);
+    radio::ensure_radio(&client, options.enable_radio, options.interface.as_deref())?;
+    let devices = find_devices(&client, options)?;
+    let (options, detected_security, connected) = prepare(&client, &devices, ssid, options)?;
+    let (mut handle, started_at) = match connected {
+        Some(handle) => (handle, None),
+        None => {
+            let handle = activate_and_wait(conn, &client, &devices, ssid, &options, progress)?;
+            (handle, Some(SystemTime::now()))
+        }
+    };
+    handle.detected_security = detected_security;
+    match ip_info::wait_for_ip_info(
+        conn,
+        options.timeouts,
+        &handle.active_path,
+        ip_info::IP_INFO_WAIT,
+    ) {
+        Ok(ip) => handle.ip = Some(ip),
+        Err(e) => log::warn!("can't read the addresses of {}: {}", handle.interface, e),
+    }
+    let ip4 = handle
+        .ip
+        .as_ref()
+        .and_then(|ip| ip.ipv4_addresses.first())
+        .map(|&(address, _)| address);
+    match status::read_active_connection(&client, &devices[0], &handle.active_path, ip4, started_at)
+    {
+        Ok(active) => handle.active = Some(active),
+        Err(e) => log::warn!("can't read the connection on {}: {}", handle.interface, e),
+    }
+    if options.verify_connectivity {
+        let connectivity = client.check_connectivity()?;
+        log::info!("connectivity after activation: {:?}", connectivity);
+        handle.connectivity = Some(connectivity);
+        if connectivity.is_portal() {
+            handle.portal_url = connectivity::portal_url(&client, &devices[0].path);
+        }
+    }
+    Ok(handle)
+}
+
+/// Provisions the profile for `ssid` on `devices` and waits until it is up on all of them,
+/// rolling back on failure unless [`ConnectOptions::keep_on_failure`] is set.
+fn activate_and_wait(
+    conn: &Connection,
+    client: &DbusNmClient,
+    devices: &[WifiDevice],
+    ssid: &[u8],
+    options: &ConnectOptions,
+    progress: &mut dyn FnMut(&DeviceStateEvent),
+) -> Result<ActivationHandle, WifiConfigError> {
+    // Subscribe first so no state change between activation and waiting is missed.
+    let watchers = devices
+        .iter()
+        .map(|device| activation::StateChangeWatcher::new(conn, &device.path))
+        .collect::<Result<Vec<_>, _>>()?;
+    let (previous_profile, snapshot) = if options.keep_on_failure {
+        (None, None)
+    } else {
+        (
+            activation::active_profile(conn, &devices[0].path, options.timeouts),
+            snapshot_saved(client, ssid, options)?,
+        )
+    };
+    let mut handle = provision_pinned(client, &devices[0], ssid, options)?;
+    activate_on_others(client, &mut handle, &devices[1..])?;
+
+    let timeout = options.activation_timeout();
+    let activated = match wait_activated(&watchers[0], timeout, progress) {
+        Err(error) => match handle.access_point.as_mut() {
+            Some(ap) if ap.pinned && vanished(client, &devices[0].path, ap) => {
+                log::warn!(
+                    "access point {} disappeared while activating ({}); activating without it",
+                    ap.bssid,
+                    error
+                );
+                ap.pinned = false;
+                let unpinned = dbus::Path::new(NO_OBJECT_PATH).unwrap();
+                client
+                    .activate_connection(&handle.settings_path, &devices[0].path, &unpinned)
+                    .and_then(|active_path| {
+                        handle.active_path = active_path;
+                        wait_activated(&watchers[0], timeout, progress)
+                    })
+            }
+            _ => Err(error),
+        },
+        Ok(()) => Ok(()),
+    }
+    .and_then(|()| {
+        watchers[1..]
+            .iter()
+            .try_for_each(|watcher| wait_activated(watcher, timeout, progress))
+    });
+    match activated {
+        Ok(()) => Ok(handle),
+        Err(error) if options.keep_on_failure => Err(error),
+        Err(error) => Err(activation::roll_back(
+            client,
+            &handle,
+            &devices[0].path,
+            previous_profile,
+            snapshot,
+            error,
+        )),
+    }
+}
+
+/// Snapshots the saved profile [`provision`] would update for `ssid`, so a failed
+/// attempt can write it back; `None` if a new profile would be added.
+fn snapshot_saved(
+    client: &dyn NmClient,
+    ssid: &[u8],
+    options: &ConnectOptions,
+) -> Result<Option<activation::ProfileSnapshot>, WifiConfigError> {
+    if options.allow_duplicates {
+        return Ok(None);
+    }
+    let saved =
+        profiles::find_saved_connection(client, ssid, options.profile_uuid(ssid).as_deref())?;
+    Ok(saved.and_then(|(path, settings)| activation::snapshot_profile(client, path, settings)))
+}
+
+/// Waits up to `timeout` for the device `watcher` watches to activate.
+fn wait_activated(
+    watcher: &activation::StateChangeWatcher,
+    timeout: Duration,
+    progress: &mut dyn FnMut(&DeviceStateEvent),
+) -> Result<(), WifiConfigError> {
+    let outcome = watcher.wait_reporting(timeout, false, progress)?;
+    if outcome.is_activated() {
+        Ok(())
+    } else {
+        Err(activation::activation_error(outcome.reason))
+    }
+}
+
+/// [`NetworkManagerBackend`]'s `connect` through `client`, on `devices` as picked by
+/// [`find_devices`].
+pub(crate) fn connect_on(
+    client: &dyn NmClient,
+    devices: &[WifiDevice],
+    ssid: &[u8],
+    options: &ConnectOptions,
+) -> Result<ActivationHandle, WifiConfigError> {
+    let (options, detected_security, connected) = prepare(client, devices, ssid, options)?;
+    let mut handle = match connected {
+        Some(handle) => handle,
+        None => {
+            let mut handle = provision_pinned(client, &devices[0], ssid, &options)?;
+            activate_on_others(client, &mut handle, &devices[1..])?;
+            handle
+        }
+    };
+    handle.detected_security = detected_security;
+    Ok(handle)
+}
+
+/// What `connect` and `connect_and_wait` do before activating anything: resolves the
+/// security, refuses what NetworkManager's version can't do and looks for the
+/// connection [`already_connected`].
+///
+/// Returns the resolved options, the detected access point security and the handle of
+/// the connection that is already up, if any.
+fn prepare(
+    client: &dyn NmClient,
+    devices: &[WifiDevice],
+    ssid: &[u8],
+    options: &ConnectOptions,
+) -> Result<(ConnectOptions, Option<ApSecurity>, Option<ActivationHandle>), WifiConfigError> {
+    let (options, detected_security) = resolve_security(client, &devices[0].path, ssid, options)?;
+    let mut requested = settings::build_connection_settings(ssid, &options);
+    let supported = version::check_supported(&requested, client.version()?);
+    secret::wipe_settings(&mut requested);
+    supported?;
+    let connected = already_connected(client, devices, ssid, &options)?;
+    Ok((options, detected_security, connected))
+}
+
+/// The connection already up on the only device in `devices`, if it is the one `options`
+/// ask for on `ssid` and [`ConnectOptions::force`] isn't set.
+pub(crate) fn already_connected(
+    client: &dyn NmClient,
+    devices: &[WifiDevice],
+    ssid: &[u8],
+    options: &ConnectOptions,
+) -> Result<Option<ActivationHandle>, WifiConfigError> {
+    let [device] = devices else {
+        return Ok(None);
+    };
+    if options.force || options.allow_duplicates || device.state != DeviceState::Activated {
+        return Ok(None);
+    }
+    let object = |props: &dbus::arg::PropMap, name: &str| {
+        prop_cast::<dbus::Path<'static>>(props, name)
+            .filter(|path| &***path != NO_OBJECT_PATH)
+            .cloned()
+    };
+    let Some(active_path) = object(&client.device_properties(&device.path)?, "ActiveConnection")
+    else {
+        return Ok(None);
+    };
+    let Some(access_point) = object(
+        &client.wireless_properties(&device.path)?,
+        "ActiveAccessPoint",
+    ) else {
+        return Ok(None);
+    };
+    let ap_props = client.access_point_properties(&access_point)?;
+    let ap_ssid = prop_cast::<Vec<u8>>(&ap_props, "Ssid").map_or(&[][..], Vec::as_slice);
+    if ap_ssid != ssid {
+        return Ok(None);
+    }
+
+    let settings_path = client.active_connection_profile(&active_path)?;
+    let active = client.get_settings(&settings_path)?;
+    let uuid = profiles::connection_uuid(&active);
+    if !profiles::is_requested_connection(client, &settings_path, ssid, options, active)? {
+        return Ok(None);
+    }
+    log::info!(
+        "already connected to {} with profile {}; leaving it up",
+        String::from_utf8_lossy(ssid),
+        settings_path
+    );
+    Ok(Some(ActivationHandle {
+        settings_path,
+        active_path,
+        interface: device.interface.clone(),
+        additional: Vec::new(),
+        uuid,
+        change: ProfileChange::AlreadyConnected,
+        detected_security: None,
+        connectivity: None,
+        portal_url: None,
+        ip: None,
+        active: None,
+        access_point: None,
+    }))
+}
+
+/// Locates the Wi-Fi devices selected by `options`; more than one only with
+/// [`DeviceSelection::All`].
+pub(crate) fn find_devices(
+    client: &dyn NmClient,
+    options: &ConnectOptions,
+) -> Result<Vec<WifiDevice>, WifiConfigError> {
+    let devices = device::select_wifi_devices(
+        device::wifi_devices(client)?,
+        options.device_path.as_ref(),
+        options.interface.as_deref(),
+        options.device_selection,
+    )?;
+    for device in &devices {
+        log::debug!("using Wi-Fi device {} ({})", device.path, device.interface);
+    }
+    Ok(devices)
+}
+
+/// Activates the profile of `handle` on `devices` as well, for [`DeviceSelection::All`].
+fn activate_on_others(
+    client: &dyn NmClient,
+    handle: &mut ActivationHandle,
+    devices: &[WifiDevice],
+) -> Result<(), WifiConfigError> {
+    let specific_object = dbus::Path::new("/").unwrap();
+    for device in devices {
+        let active_path =
+            client.activate_connection(&handle.settings_path, &device.path, &specific_object)?;
+        log::info!(
+            "also activated profile {} on {}",
+            handle.settings_path,
+            device.interface
+        );
+        handle.additional.push(DeviceActivation {
+            interface: device.interface.clone(),
+            active_path,
+        });
+    }
+    Ok(())
+}
+
+/// Checks [`ConnectOptions::min_strength`] and resolves [`Security::Auto`] against the
+/// access points visible to `device_path`.
+pub(crate) fn resolve_security(
+    client: &dyn NmClient,
+    device_path: &dbus::Path,
+    ssid: &[u8],
+    options: &ConnectOptions,
+) -> Result<(ConnectOptions, Option<ApSecurity>), WifiConfigError> {
+    let auto = matches!(options.security, Security::Auto(_));
+    if !auto && options.min_strength.is_none() {
+        return Ok((options.clone(), None));
+    }
+    let access_points = scan::access_points(client, device_path)?;
+    scan::check_strength(options, &access_points, ssid)?;
+    if !auto {
+        return Ok((options.clone(), None));
+    }
+    let resolved = scan::resolve_auto_security(options, &access_points, ssid)?;
+    log::debug!(
+        "access point security of {}: {:?}",
+        String::from_utf8_lossy(ssid),
+        resolved.1
+    );
+    Ok(resolved)
+}
+
+/// With [`ConnectOptions::pin_strongest_ap`], scans on `device` and picks the strongest
+/// access point broadcasting `ssid` to pin the activation to.
+fn pick_access_point(
+    client: &dyn NmClient,
+    device: &WifiDevice,
+    ssid: &[u8],
+    options: &ConnectOptions,
+) -> Result<Option<PinnedAccessPoint>, WifiConfigError> {
+    if !options.pin_strongest_ap || options.mode != WirelessMode::Infrastructure {
+        return Ok(None);
+    }
+    if let Err(e) = client.request_scan(&device.path) {
+        log::warn!(
+            "can't scan on {}; using the cached results: {}",
+            device.interface,
+            e
+        );
+    }
+    let access_points = scan::access_points(client, &device.path)?;
+    let Some((ap, bssid)) = scan::strongest_access_point(&access_points, ssid, options.bssid)
+    else {
+        log::info!(
+            "no access point broadcasts {}; not pinning the connection",
+            String::from_utf8_lossy(ssid)
+        );
+        return Ok(None);
+    };
+    log::info!(
+        "pinning the connection to {} ({}%), the strongest of {} access points",
+        bssid,
+        ap.strength,
+        access_points.iter().filter(|ap| ap.ssid == ssid).count()
+    );
+    Ok(Some(PinnedAccessPoint {
+        path: ap.path.clone(),
+        bssid,
+        strength: ap.strength,
+        pinned: true,
+    }))
+}
+
+/// Whether `access_point` is no longer visible to `device_path`. A failure to list the
+/// access points is logged and taken as still visible.
+fn vanished(
+    client: &dyn NmClient,
+    device_path: &dbus::Path,
+    access_point: &PinnedAccessPoint,
+) -> bool {
+    match client.access_points(device_path) {
+        Ok(paths) => !paths.contains(&access_point.path),
+        Err(e) => {
+            log::warn!("can't list the access points of {}: {}", device_path, e);
+            false
+        }
+    }
+}
+
+/// [`provision`], pinned to the access point [`pick_access_point`] picks. If
+/// NetworkManager refuses the activation and the access point is gone, provisions
+/// again without the pin.
+fn provision_pinned(
+    client: &dyn NmClient,
+    device: &WifiDevice,
+    ssid: &[u8],
+    options: &ConnectOptions,
+) -> Result<ActivationHandle, WifiConfigError> {
+    let mut access_point = pick_access_point(client, device, ssid, options)?;
+    let unpinned = dbus::Path::new(NO_OBJECT_PATH).unwrap();
+    let specific_object = access_point
+        .as_ref()
+        .map_or_else(|| unpinned.clone(), |ap| ap.path.clone());
+    let mut handle = match provision(client, device, ssid, options, &specific_object) {
+        Err(WifiConfigError::ActivationFailed(reason)) => match access_point.as_mut() {
+            Some(ap) if vanished(client, &device.path, ap) => {
+                log::warn!(
+                    "access point {} disappeared ({}); activating without it",
+                    ap.bssid,
+                    reason
+                );
+                ap.pinned = false;
+                provision(client, device, ssid, options, &unpinned)?
+            }
+            _ => return Err(WifiConfigError::ActivationFailed(reason)),
+        },
+        result => result?,
+    };
+    handle.access_point = access_point;
+    Ok(handle)
+}
+
+/// Builds the settings and activates them on `device`, on the access point
+/// `specific_object` unless that is `/`.
+///
+/// Updates the saved profile for `ssid` if there is one (unless
+/// `options.allow_duplicates` is set) and it doesn't already match, otherwise adds a
+/// new profile.
+fn provision(
+    client: &dyn NmClient,
+    device: &WifiDevice,
+    ssid: &[u8],
+    options: &ConnectOptions,
+    specific_object: &dbus::Path,
+) -> Result<ActivationHandle, WifiConfigError> {
+    let device_path = &device.path;
+    let mut connection_settings = settings::build_connection_settings(ssid, options);
+    version::fit_settings(client, &mut connection_settings)?;
+    log::debug!(
+        "settings for {}: {}",
+        String::from_utf8_lossy(ssid),
+        settings::setting_keys(&connection_settings)
+    );
+
+    if !options.allow_duplicates {
+        let saved =
+            profiles::find_saved_connection(client, ssid, options.profile_uuid(ssid).as_deref())?;
+        if let Some((settings_path, existing)) = saved {
+            let uuid = profiles::connection_uuid(&existing);
+            let (change, active_path) = profiles::update_and_activate(
+                client,
+                &settings_path,
+                existing,
+                connection_settings,
+                options.connection_id.is_some(),
+                device_path,
+                specific_object,
+            )?;
+            log::info!("updated and activated profile {}", settings_path);
+            return Ok(ActivationHandle {
+                settings_path,
+                active_path,
+                interface: device.interface.clone(),
+                additional: Vec::new(),
+                uuid,
+                change,
+                detected_security: None,
+                connectivity: None,
+                portal_url: None,
+                ip: None,
+                active: None,
+                access_point: None,
+            });
+        }
+    }
+    let uuid = settings::assign_uuid(&mut connection_settings);
+    let (settings_path, active_path, uuid, change) =
+        match client.add_and_activate(connection_settings, device_path, specific_object) {
+            Ok((settings_path, active_path)) => {
+                (settings_path, active_path, uuid, ProfileChange::Created)
+            }
+            Err(WifiConfigError::ProfileExists(e)) => {
+                log::info!(
+                    "a profile with UUID {} already exists: {}",
+                    uuid,
+                    e.message().unwrap_or_default()
+                );
+                resolve_conflict(client, device_path, specific_object, ssid, options, uuid)?
+            }
+            Err(e) => return Err(e),
+        };
+    log::info!("added and activated profile {}", settings_path);
+    Ok(ActivationHandle {
+        settings_path,
+        active_path,
+        interface: device.interface.clone(),
+        additional: Vec::new(),
+        uuid,
+        change,
+        detected_security: None,
+        connectivity: None,
+        portal_url: None,
+        ip: None,
+        active: None,
+        access_point: None,
+    })
+}
+
+/// Activates a profile on `device_path` (and `specific_object`) after adding one with
+/// `uuid` failed because a profile with that UUID exists: updates that one, or with
+/// [`ConnectOptions::isolate_profile`] adds a separate profile.
+///
+/// Returns the paths of the profile and its active connection, its UUID and what
+/// happened to it.
+fn resolve_conflict(
+    client: &dyn NmClient,
+    device_path: &dbus::Path,
+    specific_object: &dbus::Path,
+    ssid: &[u8],
+    options: &ConnectOptions,
+    uuid: String,
+) -> Result<
+    (
+        dbus::Path<'static>,
+        dbus::Path<'static>,
+        String,
+        ProfileChange,
+    ),
+    WifiConfigError,
+> {
+    let mut new = settings::build_connection_settings(ssid, options);
+    version::fit_settings(client, &mut new)?;
+    if !options.isolate_profile {
+        let settings_path = client.connection_by_uuid(&uuid)?;
+        let existing = client.get_settings(&settings_path)?;
+        let rename = options.connection_id.is_some();
+        let (_, active_path) = profiles::update_and_activate(
+            client,
+            &settings_path,
+            existing,
+            new,
+            rename,
+            device_path,
+            specific_object,
+        )?;
+        return Ok((
+            settings_path,
+            active_path,
+            uuid,
+            ProfileChange::AdoptedExisting,
+        ));
+    }
+    let connection = new.entry("connection".into()).or_default();
+    connection.remove("uuid");
+    let id = prop_cast::<String>(connection, "id")
+        .cloned()
+        .unwrap_or_default();
+    let id = format!("{} (wifi-config)", id);
+    connection.insert("id".into(), Variant(Box::new(id)));
+    let uuid = settings::assign_uuid(&mut new);
+    let (settings_path, active_path) =
+        client.add_and_activate(new, device_path, specific_object)?;
+    Ok((
+        settings_path,
+        active_path,
+        uuid,
+        ProfileChange::CreatedSeparate,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::{device_path, MockDevice, MockNmClient};
+    use crate::settings::build_connection_settings;
+    use crate::{NmVersion, PowerSave};
+    use dbus::arg::{RefArg, Variant};
+    use std::sync::Mutex;
+
+    /// Keeps every log message, so tests can check what the library logs.
+    struct CapturingLogger(Mutex<Vec<String>>);
+
+    impl log::Log for CapturingLogger {
+        fn enabled(&self, _metadata: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            self.0.lock().unwrap().push(record.args().to_string());
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: CapturingLogger = CapturingLogger(Mutex::new(Vec::new()));
+
+    fn connect_with(
+        client: &dyn NmClient,
+        ssid: &[u8],
+        options: &ConnectOptions,
+    ) -> Result<ActivationHandle, WifiConfigError> {
+        connect_on(client, &find_devices(client, options)?, ssid, options)
+    }
+
+    fn wpa_options() -> ConnectOptions {
+        ConnectOptions {
+            security: Security::WpaPsk("supersecret123".into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn fails_without_wifi_device() {
+        let client = MockNmClient::with_devices(vec![MockDevice::new(1, "eth0", 1)]);
+        assert!(matches!(
+            connect_with(&client, b"MyHomeWiFi", &wpa_options()),
+            Err(WifiConfigError::NoWifiDevice)
+        ));
+        assert!(!client
+            .methods()
+            .contains(&"AddAndActivateConnection".into()));
+    }
+
+    #[test]
+    fn activates_on_selected_device() {
+        let client = MockNmClient::with_devices(vec![
+            MockDevice::new(1, "eth0", 1),
+            MockDevice::new(2, "wlan0", 2),
+            MockDevice::new(3, "wlan1", 2),
+        ]);
+        let options = ConnectOptions {
+            interface: Some("wlan1".into()),
+            ..wpa_options()
+        };
+        let handle = connect_with(&client, b"MyHomeWiFi", &options).unwrap();
+
+        assert_eq!(handle.change, ProfileChange::Created);
+        assert_eq!(handle.uuid.len(), 36);
+        let connections = client.connections.borrow();
+        assert_eq!(profiles::connection_uuid(&connections[0].1), handle.uuid);
+        assert!(client
+            .calls()
+            .contains(&format!("AddAndActivateConnection {}", device_path(3))));
+    }
+
+    #[test]
+    fn defaults_to_first_wifi_device() {
+        let client = MockNmClient::with_devices(vec![
+            MockDevice::new(1, "eth0", 1),
+            MockDevice::new(2, "wlan0", 2),
+            MockDevice::new(3, "wlan1", 2),
+        ]);
+        connect_with(&client, b"MyHomeWiFi", &wpa_options()).unwrap();
+        assert!(client
+            .calls()
+            .contains(&format!("AddAndActivateConnection {}", device_path(2))));
+    }
+
+    #[test]
+    fn activates_on_every_usable_device() {
+        let client = MockNmClient::with_devices(vec![
+            MockDevice::new(2, "wlan0", 2),
+            MockDevice::new(3, "wlan1", 2),
+        ]);
+        let options = ConnectOptions {
+            device_selection: DeviceSelection::All,
+            ..wpa_options()
+        };
+        let handle = connect_with(&client, b"MyHomeWiFi", &options).unwrap();
+
+        assert_eq!(handle.interface, "wlan0");
+        assert_eq!(handle.additional.len(), 1);
+        assert_eq!(handle.additional[0].interface, "wlan1");
+        assert!(client.calls().contains(&format!(
+            "ActivateConnection {} {}",
+            handle.settings_path,
+            device_path(3)
+        )));
+        let connections = client.connections.borrow();
+        let multi_connect = &connections[0].1["connection"]["multi-connect"];
+        assert_eq!(multi_connect.0.as_i64(), Some(3));
+    }
+
+    #[test]
+    fn refuses_weak_signals_before_creating_a_profile() {
+        let device = MockDevice::new(2, "wlan0", 2).with_access_point(b"MyHomeWiFi", 0);
+        let client = MockNmClient::with_devices(vec![device]);
+        let options = ConnectOptions {
+            min_strength: Some(80),
+            ..wpa_options()
+        };
+        assert!(matches!(
+            connect_with(&client, b"MyHomeWiFi", &options),
+            Err(WifiConfigError::SignalTooWeak {
+                found: 70,
+                required: 80
+            })
+        ));
+        assert!(client.connections.borrow().is_empty());
+
+        let options = ConnectOptions {
+            min_strength: Some(70),
+            ..wpa_options()
+        };
+        connect_with(&client, b"MyHomeWiFi", &options).unwrap();
+        assert_eq!(client.connections.borrow().len(), 1);
+    }
+
+    #[test]
+    fn pins_the_strongest_access_point_unless_it_vanishes() {
+        let campus = || {
+            let mut device = MockDevice::new(2, "wlan0", 2)
+                .with_access_point(b"Campus", 0)
+                .with_access_point(b"Campus", 0)
+                .with_access_point(b"Other", 0);
+            let aps = [
+                (40u8, "02:00:00:00:00:01"),
+                (85, "02:00:00:00:00:02"),
+                (99, "02:00:00:00:00:03"),
+            ];
+            for ((_, properties), (strength, bssid)) in device.access_points.iter_mut().zip(aps) {
+                properties.insert("Strength".into(), Variant(Box::new(strength)));
+                properties.insert("HwAddress".into(), Variant(Box::new(bssid.to_string())));
+            }
+            device
+        };
+        let strongest = campus().access_points[1].0.clone();
+        let options = ConnectOptions {
+            pin_strongest_ap: true,
+            ..wpa_options()
+        };
+
+        let client = MockNmClient::with_devices(vec![campus()]);
+        let handle = connect_with(&client, b"Campus", &options).unwrap();
+        assert_eq!(
+            handle.access_point,
+            Some(PinnedAccessPoint {
+                path: strongest.clone(),
+                bssid: "02:00:00:00:00:02".parse().unwrap(),
+                strength: 85,
+                pinned: true,
+            })
+        );
+        let calls = client.calls();
+        let position = |call: String| calls.iter().position(|c| *c == call).unwrap();
+        assert!(
+            position(format!("RequestScan {}", device_path(2)))
+                < position(format!(
+                    "AddAndActivateConnection {} {}",
+                    device_path(2),
+                    strongest
+                ))
+        );
+
+        let client = MockNmClient::with_devices(vec![campus()]);
+        let bssid = ConnectOptions {
+            bssid: Some("02:00:00:00:00:01".parse().unwrap()),
+            ..options.clone()
+        };
+        let handle = connect_with(&client, b"Campus", &bssid).unwrap();
+        assert_eq!(handle.access_point.unwrap().strength, 40);
+
+        let client = MockNmClient {
+            vanishing: vec![strongest.clone()],
+            ..MockNmClient::with_devices(vec![campus()])
+        };
+        let handle = connect_with(&client, b"Campus", &options).unwrap();
+        assert_eq!(handle.change, ProfileChange::Created);
+        let access_point = handle.access_point.unwrap();
+        assert_eq!(access_point.path, strongest);
+        assert!(!access_point.pinned);
+        assert_eq!(client.connections.borrow().len(), 1);
+        let calls = client.calls();
+        assert!(calls.contains(&format!(
+            "AddAndActivateConnection {} {}",
+            device_path(2),
+            strongest
+        )));
+        assert!(calls.contains(&format!("AddAndActivateConnection {}", device_path(2))));
+
+        let client = MockNmClient::with_devices(vec![campus()]);
+        let handle = connect_with(&client, b"Campus", &wpa_options()).unwrap();
+        assert_eq!(handle.access_point, None);
+        assert!(!client.methods().contains(&"RequestScan".into()));
+    }
+
+    #[test]
+    fn propagates_activation_error() {
+        let client = MockNmClient {
+            activation_error: Some("secrets were required".into()),
+            ..MockNmClient::with_devices(vec![MockDevice::new(2, "wlan0", 2)])
+        };
+        match connect_with(&client, b"MyHomeWiFi", &wpa_options()) {
+            Err(WifiConfigError::ActivationFailed(reason)) => {
+                assert_eq!(reason, "secrets were required")
+            }
+            other => panic!("expected ActivationFailed, got {:?}", other.map(|_| ())),
+        }
+        assert!(client.connections.borrow().is_empty());
+    }
+
+    #[test]
+    fn sends_settings_with_nm_variant_types() {
+        let client = MockNmClient::with_devices(vec![MockDevice::new(2, "wlan0", 2)]);
+        connect_with(&client, b"MyHomeWiFi", &wpa_options()).unwrap();
+
+        let connections = client.connections.borrow();
+        let (_, settings) = &connections[0];
+        let signature = |section: &str, key: &str| settings[section][key].0.signature().to_string();
+        assert_eq!(signature("connection", "type"), "s");
+        assert_eq!(signature("802-11-wireless", "ssid"), "ay");
+        assert_eq!(signature("802-11-wireless", "mode"), "s");
+        assert_eq!(signature("802-11-wireless-security", "key-mgmt"), "s");
+        assert_eq!(signature("802-11-wireless-security", "psk"), "s");
+        assert_eq!(
+            settings["802-11-wireless"]["ssid"]
+                .0
+                .as_iter()
+                .unwrap()
+                .count(),
+            b"MyHomeWiFi".len()
+        );
+    }
+
+    #[test]
+    fn saves_powersave_as_u32() {
+        let client = MockNmClient::with_devices(vec![MockDevice::new(2, "wlan0", 2)]);
+        let options = ConnectOptions {
+            powersave: Some(PowerSave::Disable),
+            ..wpa_options()
+        };
+        let handle = connect_with(&client, b"MyHomeWiFi", &options).unwrap();
+
+        let settings = client.get_settings(&handle.settings_path).unwrap();
+        let powersave = &settings["802-11-wireless"]["powersave"];
+        assert_eq!(powersave.0.signature().to_string(), "u");
+        assert_eq!(powersave.0.as_any().downcast_ref::<u32>(), Some(&2));
+    }
+
+    #[test]
+    fn saves_the_mtu_and_rejects_tiny_ones() {
+        let client = MockNmClient::with_devices(vec![MockDevice::new(2, "wlan0", 2)]);
+        let options = ConnectOptions {
+            mtu: Some(1400),
+            ..wpa_options()
+        };
+        let handle = connect_with(&client, b"MyHomeWiFi", &options).unwrap();
+        let settings = client.get_settings(&handle.settings_path).unwrap();
+        let mtu = &settings["802-11-wireless"]["mtu"];
+        assert_eq!(mtu.0.signature().to_string(), "u");
+        assert_eq!(mtu.0.as_any().downcast_ref::<u32>(), Some(&1400));
+
+        let tiny = ConnectOptions {
+            mtu: Some(100),
+            ..wpa_options()
+        };
+        assert!(matches!(
+            tiny.validate(b"MyHomeWiFi"),
+            Err(WifiConfigError::InvalidWirelessConfig(_))
+        ));
+    }
+
+    #[test]
+    fn updates_saved_profile_instead_of_adding() {
+        let client = MockNmClient::with_devices(vec![MockDevice::new(2, "wlan0", 2)]);
+        let old_password = ConnectOptions {
+            security: Security::WpaPsk("oldpassword".into()),
+            ..Default::default()
+        };
+        let saved = client.save(
+            settings::build_connection_settings(b"MyHomeWiFi", &old_password),
+            "saved-uuid",
+        );
+        let handle = connect_with(&client, b"MyHomeWiFi", &wpa_options()).unwrap();
+
+        assert_eq!(handle.change, ProfileChange::Updated);
+        assert_eq!(handle.settings_path, saved);
+        assert_eq!(handle.uuid, "saved-uuid");
+        let methods = client.methods();
+        assert!(methods.contains(&"Update".into()));
+        assert!(methods.contains(&"ActivateConnection".into()));
+        assert!(!methods.contains(&"AddAndActivateConnection".into()));
+        assert_eq!(client.connections.borrow().len(), 1);
+    }
+
+    #[test]
+    fn skips_the_update_when_the_profile_already_matches() {
+        let mut client = MockNmClient::with_devices(vec![MockDevice::new(2, "wlan0", 2)]);
+        let saved = client.save(
+            settings::build_connection_settings(b"MyHomeWiFi", &wpa_options()),
+            "saved-uuid",
+        );
+        let handle = connect_with(&client, b"MyHomeWiFi", &wpa_options()).unwrap();
+        assert_eq!(handle.change, ProfileChange::Unchanged);
+        assert_eq!(handle.settings_path, saved);
+        let methods = client.methods();
+        assert!(methods.contains(&"GetSecrets".into()));
+        assert!(!methods.contains(&"Update".into()));
+        assert!(methods.contains(&"ActivateConnection".into()));
+
+        // Without access to the secrets it can't tell, so it updates and says so.
+        client.secrets_denied = true;
+        let handle = connect_with(&client, b"MyHomeWiFi", &wpa_options()).unwrap();
+        assert_eq!(handle.change, ProfileChange::UpdatedUnverified);
+        assert!(client.methods().contains(&"Update".into()));
+    }
+
+    #[test]
+    fn fits_the_request_to_the_network_manager_version() {
+        let mut client = MockNmClient::with_devices(vec![MockDevice::new(2, "wlan0", 2)]);
+        client.version = Some(NmVersion::new(1, 14, 0));
+        let sae = ConnectOptions {
+            security: Security::Wpa3Sae("supersecret123".into()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            connect_with(&client, b"MyHomeWiFi", &sae),
+            Err(WifiConfigError::UnsupportedByNetworkManager { feature, .. })
+                if feature == "WPA3-SAE"
+        ));
+        assert!(!client
+            .methods()
+            .contains(&"AddAndActivateConnection".into()));
+
+        client.version = Some(NmVersion::new(0, 9, 10));
+        let manual = ConnectOptions {
+            ipv4: Ipv4Config::Manual {
+                address: [192, 168, 1, 50].into(),
+                prefix: 24,
+                gateway: None,
+            },
+            ..wpa_options()
+        };
+        connect_with(&client, b"MyHomeWiFi", &manual).unwrap();
+        let connections = client.connections.borrow();
+        let ipv4 = &connections.last().unwrap().1["ipv4"];
+        assert!(ipv4.contains_key("addresses") && !ipv4.contains_key("address-data"));
+    }
+
+    #[test]
+    fn takes_over_or_sidesteps_a_profile_that_exists_unseen() {
+        let uuid = "0b7cb1a4-5f7c-4d36-9d0e-6a1f3c2e8b90";
+        let options = ConnectOptions {
+            uuid: Some(uuid.into()),
+            ..wpa_options()
+        };
+        // Another tool saves the profile after the lookup, so the first Add fails.
+        let client_with_conflict = || {
+            let mut client = MockNmClient::with_devices(vec![MockDevice::new(2, "wlan0", 2)]);
+            let other = client.save(
+                settings::build_connection_settings(b"MyHomeWiFi", &ConnectOptions::default()),
+                uuid,
+            );
+            client.unlisted.push(other.clone());
+            (client, other)
+        };
+
+        let (client, other) = client_with_conflict();
+        let handle = connect_with(&client, b"MyHomeWiFi", &options).unwrap();
+        assert_eq!(handle.change, ProfileChange::AdoptedExisting);
+        assert_eq!(handle.settings_path, other);
+        assert_eq!(handle.uuid, uuid);
+        let methods = client.methods();
+        let conflict = methods
+            .iter()
+            .position(|method| method == "AddAndActivateConnection")
+            .unwrap();
+        assert_eq!(
+            methods[conflict + 1..],
+            [
+                "Version",
+                "GetConnectionByUuid",
+                "GetSettings",
+                "Update",
+                "ActivateConnection"
+            ]
+        );
+        assert_eq!(client.connections.borrow().len(), 1);
+
+        let (client, other) = client_with_conflict();
+        let isolated = ConnectOptions {
+            isolate_profile: true,
+            ..options
+        };
+        let handle = connect_with(&client, b"MyHomeWiFi", &isolated).unwrap();
+        assert_eq!(handle.change, ProfileChange::CreatedSeparate);
+        assert_ne!(handle.settings_path, other);
+        assert_ne!(handle.uuid, uuid);
+        let connections = client.connections.borrow();
+        assert_eq!(connections.len(), 2);
+        let connection = &connections[1].1["connection"];
+        assert_eq!(
+            connection["id"].0.as_str(),
+            Some("MyHomeWiFi (wifi-config)")
+        );
+        assert_eq!(connection["uuid"].0.as_str(), Some(handle.uuid.as_str()));
+    }
+
+    #[test]
+    fn updates_profile_by_uuid_and_renames_it() {
+        let client = MockNmClient::with_devices(vec![MockDevice::new(2, "wlan0", 2)]);
+        client.save(
+            settings::build_connection_settings(b"MyHomeWiFi", &wpa_options()),
+            "0b7cb1a4-5f7c-4d36-9d0e-6a1f3c2e8b90",
+        );
+        let saved = client.save(
+            settings::build_connection_settings(b"Other", &wpa_options()),
+            "6f1e2d3c-4b5a-4978-8695-a4b3c2d1e0f9",
+        );
+        let options = ConnectOptions {
+            connection_id: Some("Office".into()),
+            uuid: Some("6f1e2d3c-4b5a-4978-8695-a4b3c2d1e0f9".into()),
+            ..wpa_options()
+        };
+        let handle = connect_with(&client, b"MyHomeWiFi", &options).unwrap();
+
+        assert_eq!(handle.change, ProfileChange::Updated);
+        assert_eq!(handle.settings_path, saved);
+        let connections = client.connections.borrow();
+        let connection = &connections[1].1["connection"];
+        assert_eq!(connection["id"].0.as_str(), Some("Office"));
+        assert!(profiles::matches_ssid(&connections[1].1, b"MyHomeWiFi"));
+    }
+
+    #[test]
+    fn converges_on_the_profile_with_the_stable_uuid() {
+        let client = MockNmClient::with_devices(vec![MockDevice::new(2, "wlan0", 2)]);
+        client.save(
+            settings::build_connection_settings(b"MyHomeWiFi", &wpa_options()),
+            "0b7cb1a4-5f7c-4d36-9d0e-6a1f3c2e8b90",
+        );
+        let options = ConnectOptions {
+            stable_uuid: true,
+            ..wpa_options()
+        };
+        let stable = crate::stable_uuid_for("MyHomeWiFi").to_string();
+
+        let created = connect_with(&client, b"MyHomeWiFi", &options).unwrap();
+        assert_eq!(created.change, ProfileChange::Created);
+        assert_eq!(created.uuid, stable);
+
+        let again = connect_with(&client, b"MyHomeWiFi", &options).unwrap();
+        assert_eq!(again.change, ProfileChange::Unchanged);
+        assert_eq!(again.settings_path, created.settings_path);
+        assert_eq!(again.uuid, stable);
+        assert_eq!(client.connections.borrow().len(), 2);
+
+        let pinned = ConnectOptions {
+            interface: Some("wlan0".into()),
+            ..options
+        };
+        let handle = connect_with(&client, b"MyHomeWiFi", &pinned).unwrap();
+        assert_eq!(handle.change, ProfileChange::Created);
+        assert_eq!(
+            handle.uuid,
+            crate::stable_uuid_for_interface("MyHomeWiFi", "wlan0").to_string()
+        );
+    }
+
+    /// A client whose only device is up on `MyHomeWiFi` with a saved WPA-PSK profile,
+    /// and the paths of that active connection and profile.
+    fn connected_client() -> (MockNmClient, dbus::Path<'static>, dbus::Path<'static>) {
+        let mut device = MockDevice::new(1, "wlan0", 2).with_access_point(b"MyHomeWiFi", 0x188);
+        let active = dbus::Path::new("/org/freedesktop/NetworkManager/ActiveConnection/7").unwrap();
+        device
+            .properties
+            .insert("State".into(), Variant(Box::new(100u32)));
+        device
+            .properties
+            .insert("ActiveConnection".into(), Variant(Box::new(active.clone())));
+        device.wireless_properties.insert(
+            "ActiveAccessPoint".into(),
+            Variant(Box::new(device.access_points[0].0.clone())),
+        );
+        let mut client = MockNmClient::with_devices(vec![device]);
+        let profile = client.save(
+            build_connection_settings(b"MyHomeWiFi", &wpa_options()),
+            "6f1e2d3c-4b5a-4978-8695-a4b3c2d1e0f9",
+        );
+        client
+            .active_profiles
+            .push((active.clone(), profile.clone()));
+        (client, active, profile)
+    }
+
+    #[test]
+    fn leaves_the_connection_alone_when_already_connected() {
+        let (client, active, profile) = connected_client();
+        let handle = connect_with(&client, b"MyHomeWiFi", &wpa_options()).unwrap();
+        assert_eq!(handle.change, ProfileChange::AlreadyConnected);
+        assert_eq!(handle.settings_path, profile);
+        assert_eq!(handle.active_path, active);
+        assert_eq!(handle.uuid, "6f1e2d3c-4b5a-4978-8695-a4b3c2d1e0f9");
+        let methods = client.methods();
+        assert!(methods.iter().any(|method| method == "GetSecrets"));
+        assert!(!methods
+            .iter()
+            .any(|method| method == "Update" || method.contains("Activate")));
+
+        let other_password = ConnectOptions {
+            security: Security::WpaPsk("anothersecret".into()),
+            ..Default::default()
+        };
+        let forced = ConnectOptions {
+            force: true,
+            ..wpa_options()
+        };
+        for options in [other_password, forced] {
+            let handle = connect_with(&client, b"MyHomeWiFi", &options).unwrap();
+            assert_eq!(handle.change, ProfileChange::Updated);
+        }
+        let handle = connect_with(&client, b"Office", &wpa_options()).unwrap();
+        assert_eq!(handle.change, ProfileChange::Created);
+    }
+
+    #[test]
+    fn reconnects_unless_the_active_profile_is_known_to_match() {
+        let (client, _, _) = connected_client();
+        let hidden = ConnectOptions {
+            hidden: true,
+            ..wpa_options()
+        };
+        let handle = connect_with(&client, b"MyHomeWiFi", &hidden).unwrap();
+        assert_eq!(handle.change, ProfileChange::Updated);
+
+        let (mut client, _, _) = connected_client();
+        client.secrets_denied = true;
+        let handle = connect_with(&client, b"MyHomeWiFi", &wpa_options()).unwrap();
+        assert_eq!(handle.change, ProfileChange::UpdatedUnverified);
+        assert!(client
+            .methods()
+            .iter()
+            .any(|method| method == "ActivateConnection"));
+    }
+
+    #[test]
+    fn resolves_auto_security_from_access_point() {
+        let client = MockNmClient::with_devices(vec![
+            MockDevice::new(2, "wlan0", 2).with_access_point(b"MyHomeWiFi", 0x188)
+        ]);
+        let options = ConnectOptions {
+            security: Security::Auto(Some("supersecret123".into())),
+            ..Default::default()
+        };
+        let handle = connect_with(&client, b"MyHomeWiFi", &options).unwrap();
+
+        assert_eq!(handle.detected_security, Some(ApSecurity::WpaPsk));
+        let connections = client.connections.borrow();
+        let key_mgmt = &connections[0].1["802-11-wireless-security"]["key-mgmt"];
+        assert_eq!(key_mgmt.0.as_str(), Some("wpa-psk"));
+    }
+
+    #[test]
+    fn never_logs_the_psk() {
+        const PSK: &str = "correct-horse-battery";
+        // The logger can only be installed once per test binary.
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(log::LevelFilter::Trace);
+
+        let client = MockNmClient::with_devices(vec![MockDevice::new(2, "wlan0", 2)]);
+        let options = ConnectOptions {
+            security: Security::WpaPsk(PSK.into()),
+            ..Default::default()
+        };
+        // Once to add the profile, once to update it.
+        connect_with(&client, b"MyHomeWiFi", &options).unwrap();
+        connect_with(&client, b"MyHomeWiFi", &options).unwrap();
+
+        let messages = LOGGER.0.lock().unwrap();
+        assert!(messages
+            .iter()
+            .any(|message| message.contains("802-11-wireless-security.psk")));
+        assert!(messages
+            .iter()
+            .any(|message| message.contains(&*device_path(2))));
+        assert!(!messages.iter().any(|message| message.contains(PSK)));
+    }
+}
@@ -0,0 +1,597 @@
+use std::net::IpAddr;
+use std::time::Duration;
+
+use crate::{
+    ActivationHandle, Backend, Band, CipherPolicy, ConnectOptions, DbusTimeouts, DeviceSelection,
+    DhcpOptions, DryRun, ExtraSettingValue, Ipv4Config, Ipv6Config, Ipv6Privacy, MacAddr,
+    MacPolicy, Metered, PendingConnection, PowerSave, RetryPolicy, SavedProfile,
+    ScanMacRandomization, Secret, SecretStorage, Security, WifiConfigError, WirelessMode,
+};
+
+/// A validated Wi-Fi connection, ready to be sent to NetworkManager.
+///
+/// Created with [`WifiConnection::builder`]; [`build`](WifiConnectionBuilder::build) runs
+/// all client-side checks, so a `WifiConnection` only fails on the D-Bus side.
+///
+/// Hidden WPA2 network on a specific adapter:
+///
+/// ```no_run
+/// use wifi_config::WifiConnection;
+///
+/// WifiConnection::builder("MySsid")
+///     .password("supersecret123")
+///     .hidden(true)
+///     .interface("wlan0")
+///     .build()?
+///     .connect()?;
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "crate::json::UncheckedConnection")
+)]
+pub struct WifiConnection {
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "crate::json::ssid::serialize")
+    )]
+    ssid: Vec<u8>,
+    options: ConnectOptions,
+}
+
+impl WifiConnection {
+    /// Starts building a connection to `ssid`. Without credentials the network is open.
+    ///
+    /// Accepts a `&str` as well as raw bytes for SSIDs that aren't valid UTF-8.
+    pub fn builder(ssid: impl Into<Vec<u8>>) -> WifiConnectionBuilder {
+        WifiConnectionBuilder {
+            ssid: ssid.into(),
+            options: ConnectOptions::default(),
+        }
+    }
+
+    /// The raw SSID bytes.
+    pub fn ssid(&self) -> &[u8] {
+        &self.ssid
+    }
+
+    /// The options the connection was built with.
+    pub fn options(&self) -> &ConnectOptions {
+        &self.options
+    }
+
+    /// Sends the connection to NetworkManager; see [`connect`](crate::connect).
+    pub fn connect(self) -> Result<ActivationHandle, WifiConfigError> {
+        crate::connect(&self.ssid, &self.options)
+    }
+
+    /// Sends the connection and waits until it is up; see
+    /// [`connect_and_wait`](crate::connect_and_wait).
+    pub fn connect_and_wait(self) -> Result<ActivationHandle, WifiConfigError> {
+        crate::connect_and_wait(&self.ssid, &self.options)
+    }
+
+    /// Saves the connection as a profile without activating it; see
+    /// [`save_connection`](crate::save_connection).
+    pub fn save(self) -> Result<SavedProfile, WifiConfigError> {
+        crate::save_connection(&self.ssid, &self.options)
+    }
+
+    /// Starts connecting without waiting; see [`start_connect`](crate::start_connect).
+    pub fn start_connect(self) -> Result<PendingConnection, WifiConfigError> {
+        crate::start_connect(&self.ssid, &self.options)
+    }
+
+    /// Shows the settings [`connect`](Self::connect) would send, without sending them;
+    /// see [`dry_run`](crate::dry_run).
+    pub fn dry_run(&self, show_secrets: bool) -> Result<DryRun, WifiConfigError> {
+        crate::dry_run(&self.ssid, &self.options, show_secrets)
+    }
+
+    /// Reads a connection from a JSON profile, as written by [`to_json`](Self::to_json).
+    ///
+    /// A profile is an object with the envelope `version` (currently 1), the `ssid` (a
+    /// string, or an array of bytes for SSIDs that aren't UTF-8) and the `options`, whose
+    /// fields are those of [`ConnectOptions`]:
+    ///
+    /// ```json
+    /// {
+    ///   "version": 1,
+    ///   "ssid": "Office",
+    ///   "options": {
+    ///     "security": { "wpa_psk": "env:OFFICE_PSK" },
+    ///     "hidden": true,
+    ///     "ipv4": { "manual": { "address": "192.168.1.50", "prefix": 24, "gateway": null } },
+    ///     "activation_timeout": 45
+    ///   }
+    /// }
+    /// ```
+    ///
+    /// Enum values are written in snake case, durations in seconds. Missing options take
+    /// their defaults and unknown ones are ignored, so profiles keep working across
+    /// releases; `device_path` is never read or written. A password given as
+    /// `"env:VAR"` is read from the environment variable `VAR`, so the profile itself
+    /// needn't hold it.
+    ///
+    /// Requires the `serde` feature.
+    ///
+    /// # Errors
+    ///
+    /// - [`WifiConfigError::InvalidConfigFile`] if the JSON is malformed, the version
+    ///   isn't 1, a referenced environment variable isn't set, or the connection fails
+    ///   the checks of [`build`](WifiConnectionBuilder::build).
+    #[cfg(feature = "serde")]
+    pub fn from_json(json: &str) -> Result<Self, WifiConfigError> {
+        crate::json::from_json(json)
+    }
+
+    /// Writes the connection as a pretty-printed JSON profile; see
+    /// [`from_json`](Self::from_json).
+    ///
+    /// Secrets are written as they are, including passwords.
+    ///
+    /// Requires the `serde` feature.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        crate::json::to_json(self)
+    }
+
+    /// Async variant of [`connect`](Self::connect); see
+    /// [`connect_async`](crate::connect_async).
+    ///
+    /// Requires the `tokio` feature and must be called from within a tokio runtime.
+    #[cfg(feature = "tokio")]
+    pub async fn connect_async(self) -> Result<ActivationHandle, WifiConfigError> {
+        crate::connect_async(&self.ssid, &self.options).await
+    }
+}
+
+/// Builder for [`WifiConnection`].
+///
+/// Each setter corresponds to a [`ConnectOptions`] field and is documented there.
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct WifiConnectionBuilder {
+    ssid: Vec<u8>,
+    options: ConnectOptions,
+}
+
+/// Builder for [`ConnectOptions`], which is `#[non_exhaustive]` and so can't be written
+/// as a struct literal outside this crate.
+///
+/// It has the setters of [`WifiConnectionBuilder`]. [`ConnectOptions::builder`] starts
+/// from the defaults, like `..Default::default()` would, and
+/// `ConnectOptionsBuilder::from(options)` from other options, like `..options`:
+///
+/// ```
+/// use wifi_config::{ConnectOptions, ConnectOptionsBuilder};
+///
+/// let options = ConnectOptions::builder()
+///     .password("supersecret123")
+///     .hidden(true)
+///     .build();
+/// let duplicate = ConnectOptionsBuilder::from(options.clone())
+///     .allow_duplicates(true)
+///     .build();
+/// assert!(duplicate.hidden && duplicate.allow_duplicates);
+/// ```
+#[derive(Debug, Clone, Default)]
+#[must_use]
+pub struct ConnectOptionsBuilder {
+    options: ConnectOptions,
+}
+
+impl ConnectOptionsBuilder {
+    /// The options set so far. They are validated together with the SSID when connecting.
+    pub fn build(self) -> ConnectOptions {
+        self.options
+    }
+}
+
+impl From<ConnectOptions> for ConnectOptionsBuilder {
+    fn from(options: ConnectOptions) -> Self {
+        ConnectOptionsBuilder { options }
+    }
+}
+
+/// Gives both builders the same setters, which write the `options` they hold.
+macro_rules! option_setters {
+    ($($setter:item)*) => {
+        impl WifiConnectionBuilder {
+            $($setter)*
+        }
+
+        impl ConnectOptionsBuilder {
+            $($setter)*
+        }
+    };
+}
+
+option_setters! {
+    /// Uses WPA/WPA2 personal with `password`; shorthand for
+    /// `.security(Security::WpaPsk(password))`.
+    pub fn password(self, password: impl Into<Secret>) -> Self {
+        self.security(Security::WpaPsk(password.into()))
+    }
+
+    /// Sets the security scheme and credentials.
+    pub fn security(mut self, security: Security) -> Self {
+        self.options.security = security;
+        self
+    }
+
+    /// Restricts the WPA versions and ciphers, e.g. to [`CipherPolicy::Wpa2Only`].
+    pub fn cipher_policy(mut self, cipher_policy: CipherPolicy) -> Self {
+        self.options.cipher_policy = Some(cipher_policy);
+        self
+    }
+
+    /// Marks the SSID as not broadcast.
+    pub fn hidden(mut self, hidden: bool) -> Self {
+        self.options.hidden = hidden;
+        self
+    }
+
+    /// Selects the Wi-Fi device by interface name.
+    pub fn interface(mut self, interface: impl Into<String>) -> Self {
+        self.options.interface = Some(interface.into());
+        self
+    }
+
+    /// Selects the Wi-Fi device by D-Bus object path.
+    pub fn device_path(mut self, device_path: dbus::Path<'static>) -> Self {
+        self.options.device_path = Some(device_path);
+        self
+    }
+
+    /// Sets how the Wi-Fi device is picked when none is named.
+    pub fn device_selection(mut self, selection: DeviceSelection) -> Self {
+        self.options.device_selection = selection;
+        self
+    }
+
+    /// Sets how long [`WifiConnection::connect_and_wait`] waits for activation.
+    pub fn activation_timeout(mut self, timeout: Duration) -> Self {
+        self.options.activation_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the D-Bus call timeouts.
+    pub fn timeouts(mut self, timeouts: DbusTimeouts) -> Self {
+        self.options.timeouts = timeouts;
+        self
+    }
+
+    /// Sets how device discovery and activation calls are retried.
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.options.retry = retry;
+        self
+    }
+
+    /// Sets where NetworkManager keeps the password; see [`SecretStorage`].
+    pub fn secret_storage(mut self, storage: SecretStorage) -> Self {
+        self.options.secret_storage = storage;
+        self
+    }
+
+    /// Restricts the profile to `user`; call again to allow more users.
+    pub fn permit_user(mut self, user: impl Into<String>) -> Self {
+        self.options.permissions.push(user.into());
+        self
+    }
+
+    /// Puts the connection in the firewalld `zone`.
+    pub fn zone(mut self, zone: impl Into<String>) -> Self {
+        self.options.zone = Some(zone.into());
+        self
+    }
+
+    /// Turns the Wi-Fi radio on if it is switched off in software.
+    pub fn enable_radio(mut self, enable: bool) -> Self {
+        self.options.enable_radio = enable;
+        self
+    }
+
+    /// Runs NetworkManager's connectivity check after [`connect_and_wait`](WifiConnection::connect_and_wait).
+    pub fn verify_connectivity(mut self, verify: bool) -> Self {
+        self.options.verify_connectivity = verify;
+        self
+    }
+
+    /// Keeps the profile when [`connect_and_wait`](WifiConnection::connect_and_wait)
+    /// fails instead of rolling back; see [`ConnectOptions::keep_on_failure`].
+    pub fn keep_on_failure(mut self, keep: bool) -> Self {
+        self.options.keep_on_failure = keep;
+        self
+    }
+
+    /// Refuses to connect below `strength` (0–100); see [`ConnectOptions::min_strength`].
+    pub fn min_strength(mut self, strength: u8) -> Self {
+        self.options.min_strength = Some(strength);
+        self
+    }
+
+    /// Adds a new profile even if one for the SSID is already saved.
+    pub fn allow_duplicates(mut self, allow: bool) -> Self {
+        self.options.allow_duplicates = allow;
+        self
+    }
+
+    /// Re-activates the connection even if the device is already connected to the
+    /// network as asked.
+    pub fn force(mut self, force: bool) -> Self {
+        self.options.force = force;
+        self
+    }
+
+    /// Adds a separate profile, rather than taking over the other one, when a profile
+    /// with the same UUID turns out to exist already.
+    pub fn isolate_profile(mut self, isolate: bool) -> Self {
+        self.options.isolate_profile = isolate;
+        self
+    }
+
+    /// Names the profile instead of after the SSID.
+    pub fn connection_id(mut self, id: impl Into<String>) -> Self {
+        self.options.connection_id = Some(id.into());
+        self
+    }
+
+    /// Gives the profile this UUID, updating the saved profile that has it.
+    pub fn uuid(mut self, uuid: impl Into<String>) -> Self {
+        self.options.uuid = Some(uuid.into());
+        self
+    }
+
+    /// Derives the profile's UUID from the SSID, updating the saved profile that has it.
+    pub fn stable_uuid(mut self, stable: bool) -> Self {
+        self.options.stable_uuid = stable;
+        self
+    }
+
+    /// Sets the IPv4 configuration.
+    pub fn ipv4(mut self, ipv4: Ipv4Config) -> Self {
+        self.options.ipv4 = ipv4;
+        self
+    }
+
+    /// Sets the IPv6 configuration.
+    pub fn ipv6(mut self, ipv6: Ipv6Config) -> Self {
+        self.options.ipv6 = ipv6;
+        self
+    }
+
+    /// Sets the hostname and client identifier sent to DHCP servers.
+    pub fn dhcp(mut self, dhcp: DhcpOptions) -> Self {
+        self.options.dhcp = dhcp;
+        self
+    }
+
+    /// Sets the IPv6 privacy extensions.
+    pub fn ipv6_privacy(mut self, privacy: Ipv6Privacy) -> Self {
+        self.options.ipv6_privacy = Some(privacy);
+        self
+    }
+
+    /// Adds a DNS server; may be called repeatedly.
+    pub fn dns(mut self, server: IpAddr) -> Self {
+        self.options.dns.push(server);
+        self
+    }
+
+    /// Adds a DNS search domain; may be called repeatedly.
+    pub fn dns_search(mut self, domain: impl Into<String>) -> Self {
+        self.options.dns_search.push(domain.into());
+        self
+    }
+
+    /// Sets whether NetworkManager may activate the profile on its own.
+    pub fn autoconnect(mut self, autoconnect: bool) -> Self {
+        self.options.autoconnect = Some(autoconnect);
+        self
+    }
+
+    /// Sets the autoconnect priority.
+    pub fn autoconnect_priority(mut self, priority: i32) -> Self {
+        self.options.autoconnect_priority = Some(priority);
+        self
+    }
+
+    /// Marks the connection as metered or not.
+    pub fn metered(mut self, metered: Metered) -> Self {
+        self.options.metered = Some(metered);
+        self
+    }
+
+    /// Sets the operating mode.
+    pub fn mode(mut self, mode: WirelessMode) -> Self {
+        self.options.mode = mode;
+        self
+    }
+
+    /// Locks the connection to a frequency band.
+    pub fn band(mut self, band: Band) -> Self {
+        self.options.band = Some(band);
+        self
+    }
+
+    /// Locks the connection to a channel within the [`band`](Self::band).
+    pub fn channel(mut self, channel: u32) -> Self {
+        self.options.channel = Some(channel);
+        self
+    }
+
+    /// Pins the connection to one access point.
+    pub fn bssid(mut self, bssid: MacAddr) -> Self {
+        self.options.bssid = Some(bssid);
+        self
+    }
+
+    /// Sets the MAC address used on the connection.
+    pub fn mac_policy(mut self, policy: MacPolicy) -> Self {
+        self.options.mac_policy = Some(policy);
+        self
+    }
+
+    /// Sets MAC address randomization while scanning.
+    pub fn scan_mac_randomization(mut self, randomization: ScanMacRandomization) -> Self {
+        self.options.scan_mac_randomization = Some(randomization);
+        self
+    }
+
+    /// Sets Wi-Fi power saving on the connection.
+    pub fn powersave(mut self, powersave: PowerSave) -> Self {
+        self.options.powersave = Some(powersave);
+        self
+    }
+
+    /// Sets the MTU of the connection; `0` makes it automatic.
+    pub fn mtu(mut self, mtu: u32) -> Self {
+        self.options.mtu = Some(mtu);
+        self
+    }
+
+    /// Sets the raw setting `section.key`, e.g. `("802-11-wireless", "ap-isolation", 1)`;
+    /// see [`ConnectOptions::extra_settings`].
+    pub fn extra_setting(
+        mut self,
+        section: impl Into<String>,
+        key: impl Into<String>,
+        value: impl Into<ExtraSettingValue>,
+    ) -> Self {
+        self.options
+            .extra_settings
+            .entry(section.into())
+            .or_default()
+            .insert(key.into(), value.into());
+        self
+    }
+
+    /// Activates on the strongest access point broadcasting the SSID.
+    pub fn pin_strongest_ap(mut self, pin: bool) -> Self {
+        self.options.pin_strongest_ap = pin;
+        self
+    }
+
+    /// Sets the service to connect through.
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.options.backend = backend;
+        self
+    }
+}
+
+impl WifiConnectionBuilder {
+    /// Replaces all options at once, e.g. with ones assembled elsewhere.
+    pub fn options(mut self, options: ConnectOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Validates the configuration without touching D-Bus.
+    ///
+    /// # Errors
+    ///
+    /// - [`WifiConfigError::InvalidSsid`] if the SSID is empty or longer than 32 bytes.
+    /// - [`WifiConfigError::EmptyPassword`] / [`WifiConfigError::InvalidPsk`] if a
+    ///   WPA-PSK passphrase isn't 8 to 63 printable ASCII characters (or 64 hex digits).
+    /// - [`WifiConfigError::InvalidWirelessConfig`] if the band, channel and mode don't fit,
+    ///   the MTU is below 576, or the cipher policy doesn't suit the security scheme.
+    /// - Any other validation error of [`connect`](crate::connect).
+    pub fn build(self) -> Result<WifiConnection, WifiConfigError> {
+        self.options.validate(&self.ssid)?;
+        Ok(WifiConnection {
+            ssid: self.ssid,
+            options: self.options,
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<crate::json::UncheckedConnection> for WifiConnection {
+    type Error = WifiConfigError;
+
+    fn try_from(connection: crate::json::UncheckedConnection) -> Result<Self, Self::Error> {
+        WifiConnection::builder(connection.ssid)
+            .options(connection.options)
+            .build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_options() {
+        let connection = WifiConnection::builder("MySsid")
+            .password("supersecret123")
+            .hidden(true)
+            .interface("wlan0")
+            .dns("10.0.0.53".parse().unwrap())
+            .build()
+            .unwrap();
+
+        assert_eq!(connection.ssid(), b"MySsid");
+        let options = connection.options();
+        assert_eq!(options.security, Security::WpaPsk("supersecret123".into()));
+        assert!(options.hidden);
+        assert_eq!(options.interface.as_deref(), Some("wlan0"));
+        assert_eq!(options.dns, ["10.0.0.53".parse::<IpAddr>().unwrap()]);
+    }
+
+    #[test]
+    fn options_builder_stands_in_for_struct_update() {
+        let base = ConnectOptions {
+            security: Security::WpaPsk("supersecret123".into()),
+            hidden: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            ConnectOptions::builder()
+                .password("supersecret123")
+                .hidden(true)
+                .build(),
+            base
+        );
+        assert_eq!(
+            ConnectOptionsBuilder::from(base.clone())
+                .backend(Backend::Iwd)
+                .pin_strongest_ap(true)
+                .build(),
+            ConnectOptions {
+                backend: Backend::Iwd,
+                pin_strongest_ap: true,
+                ..base
+            }
+        );
+    }
+
+    #[test]
+    fn build_validates_before_connecting() {
+        let too_long = "x".repeat(33);
+        assert!(matches!(
+            WifiConnection::builder(too_long).build(),
+            Err(WifiConfigError::InvalidSsid(_))
+        ));
+        assert!(matches!(
+            WifiConnection::builder("").build(),
+            Err(WifiConfigError::InvalidSsid(_))
+        ));
+        assert!(matches!(
+            WifiConnection::builder("MySsid").password("short").build(),
+            Err(WifiConfigError::InvalidPsk { .. })
+        ));
+        assert!(matches!(
+            WifiConnection::builder("MySsid")
+                .band(Band::A)
+                .channel(6)
+                .build(),
+            Err(WifiConfigError::InvalidWirelessConfig(_))
+        ));
+        assert!(WifiConnection::builder("MySsid").build().is_ok());
+        // Latin-1 "Café": not UTF-8, but a perfectly valid SSID.
+        assert!(WifiConnection::builder(&b"Caf\xe9"[..]).build().is_ok());
+    }
+}
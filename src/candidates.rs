@@ -0,0 +1,279 @@
+use std::fmt;
+
+use crate::{
+    scan, start_connect, AccessPointInfo, ActivationHandle, ConnectOptions, Security,
+    WifiConfigError, WifiConnection,
+};
+
+/// One network [`connect_first_available`] may connect to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkCandidate {
+    /// The network's SSID.
+    pub ssid: Vec<u8>,
+    /// How to connect to it. [`ConnectOptions::activation_timeout`] bounds the attempt on
+    /// this network.
+    pub options: ConnectOptions,
+}
+
+impl NetworkCandidate {
+    /// A candidate for `ssid` with `security` and the default options otherwise.
+    pub fn new(ssid: impl AsRef<[u8]>, security: Security) -> Self {
+        NetworkCandidate {
+            ssid: ssid.as_ref().to_vec(),
+            options: ConnectOptions {
+                security,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Reads candidates from a JSON array of profiles, each in the format of
+    /// [`WifiConnection::from_json`].
+    ///
+    /// # Errors
+    ///
+    /// - [`WifiConfigError::InvalidConfigFile`] if `json` isn't an array of valid
+    ///   profiles; the reason names the first invalid one by its position.
+    #[cfg(feature = "serde")]
+    pub fn list_from_json(json: &str) -> Result<Vec<Self>, WifiConfigError> {
+        crate::json::candidates_from_json(json)
+    }
+}
+
+impl From<WifiConnection> for NetworkCandidate {
+    fn from(connection: WifiConnection) -> Self {
+        NetworkCandidate {
+            ssid: connection.ssid().to_vec(),
+            options: connection.options().clone(),
+        }
+    }
+}
+
+/// The network [`connect_first_available`] connected to.
+#[derive(Debug)]
+pub struct ConnectedNetwork {
+    /// Position of the network in the candidate list.
+    pub index: usize,
+    /// The network's SSID.
+    pub ssid: Vec<u8>,
+    /// The profile and active connection, as returned by [`connect`](crate::connect).
+    pub handle: ActivationHandle,
+    /// Why the candidates tried before it (or skipped) didn't work, in the order they
+    /// were considered.
+    pub failures: Vec<CandidateFailure>,
+}
+
+/// Why [`connect_first_available`] didn't connect to a candidate; carried by
+/// [`ConnectedNetwork::failures`] and [`WifiConfigError::NoNetworkConnected`].
+#[derive(Debug)]
+pub struct CandidateFailure {
+    /// Position of the network in the candidate list.
+    pub index: usize,
+    /// The network's SSID.
+    pub ssid: Vec<u8>,
+    /// [`WifiConfigError::NetworkNotFound`] if the network wasn't in the scan results,
+    /// otherwise why the attempt failed.
+    pub error: WifiConfigError,
+}
+
+impl fmt::Display for CandidateFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "'{}': {}",
+            String::from_utf8_lossy(&self.ssid),
+            self.error
+        )
+    }
+}
+
+/// Connects to the best of several networks, e.g. a depot network and a field hotspot.
+///
+/// # Behavior
+///
+/// - Validates every candidate; an invalid one is skipped.
+/// - Scans once on the default Wi-Fi device. Candidates not in the results are skipped,
+///   except hidden ones, which never show up in scans.
+/// - Tries the visible candidates by signal strength, strongest first (in list order
+///   when equally strong), then the hidden ones in list order.
+/// - Each attempt works like [`start_connect`] and waits up to the candidate's
+///   [`activation_timeout`](ConnectOptions::activation_timeout). A failed attempt is
+///   [cancelled](crate::PendingConnection::cancel), which also deletes the profile it
+///   created, before the next candidate is tried.
+///
+/// # Errors
+///
+/// - [`WifiConfigError::NoNetworkConnected`] if no candidate connected, with the reason
+///   for each one.
+/// - The errors of [`scan_networks`](crate::scan_networks) if the scan fails.
+///
+/// # Example
+///
+/// ```no_run
+/// use wifi_config::{NetworkCandidate, Security};
+///
+/// let candidates = [
+///     NetworkCandidate::new("Depot", Security::WpaPsk("supersecret123".into())),
+///     NetworkCandidate::new("FieldHotspot", Security::Wpa3Sae("supersecret456".into())),
+/// ];
+/// let connected = wifi_config::connect_first_available(&candidates)?;
+/// println!("connected to {}", String::from_utf8_lossy(&connected.ssid));
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn connect_first_available(
+    candidates: &[NetworkCandidate],
+) -> Result<ConnectedNetwork, WifiConfigError> {
+    let mut failures = Vec::new();
+    let mut valid = Vec::new();
+    for (index, candidate) in candidates.iter().enumerate() {
+        match candidate.options.validate(&candidate.ssid) {
+            Ok(()) => valid.push(index),
+            Err(error) => failures.push(failure(index, candidate, error)),
+        }
+    }
+    let access_points = if valid.is_empty() {
+        Vec::new()
+    } else {
+        scan::scan_networks(true)?
+    };
+    let (attempts, absent) = order_candidates(candidates, &valid, &access_points);
+    failures.extend(absent);
+
+    for index in attempts {
+        let candidate = &candidates[index];
+        let name = String::from_utf8_lossy(&candidate.ssid);
+        log::info!("trying candidate {} ('{}')", index, name);
+        match try_candidate(candidate) {
+            Ok(handle) => {
+                return Ok(ConnectedNetwork {
+                    index,
+                    ssid: candidate.ssid.clone(),
+                    handle,
+                    failures,
+                })
+            }
+            Err(error) => {
+                log::info!("candidate '{}' failed: {}", name, error);
+                failures.push(failure(index, candidate, error));
+            }
+        }
+    }
+    Err(WifiConfigError::NoNetworkConnected(failures))
+}
+
+/// Connects to `candidate` and waits for it, cancelling the attempt if it fails.
+fn try_candidate(candidate: &NetworkCandidate) -> Result<ActivationHandle, WifiConfigError> {
+    let pending = start_connect(&candidate.ssid, &candidate.options)?;
+    match pending.wait(candidate.options.activation_timeout()) {
+        Ok(()) => Ok(pending.handle().clone()),
+        Err(error) => {
+            if let Err(e) = pending.cancel() {
+                log::warn!(
+                    "can't clean up after '{}': {}",
+                    String::from_utf8_lossy(&candidate.ssid),
+                    e
+                );
+            }
+            Err(error)
+        }
+    }
+}
+
+/// Orders the `valid` candidates for trying, and fails the ones not in `access_points`.
+///
+/// Returns the indices to try, visible ones by descending signal strength, then hidden
+/// ones; and the failures of the absent ones.
+fn order_candidates(
+    candidates: &[NetworkCandidate],
+    valid: &[usize],
+    access_points: &[AccessPointInfo],
+) -> (Vec<usize>, Vec<CandidateFailure>) {
+    let mut visible = Vec::new();
+    let mut hidden = Vec::new();
+    let mut absent = Vec::new();
+    for &index in valid {
+        let candidate = &candidates[index];
+        let strength = access_points
+            .iter()
+            .filter(|access_point| access_point.ssid == candidate.ssid)
+            .map(|access_point| access_point.strength)
+            .max();
+        match strength {
+            Some(strength) => visible.push((index, strength)),
+            None if candidate.options.hidden => hidden.push(index),
+            None => {
+                let name = String::from_utf8_lossy(&candidate.ssid).into_owned();
+                absent.push(failure(
+                    index,
+                    candidate,
+                    WifiConfigError::NetworkNotFound(name),
+                ));
+            }
+        }
+    }
+    // Stable, so equally strong candidates keep their list order.
+    visible.sort_by_key(|&(_, strength)| std::cmp::Reverse(strength));
+    let attempts = visible.into_iter().map(|(index, _)| index).chain(hidden);
+    (attempts.collect(), absent)
+}
+
+fn failure(index: usize, candidate: &NetworkCandidate, error: WifiConfigError) -> CandidateFailure {
+    CandidateFailure {
+        index,
+        ssid: candidate.ssid.clone(),
+        error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ApSecurity;
+
+    fn access_point(ssid: &str, strength: u8) -> AccessPointInfo {
+        AccessPointInfo {
+            path: dbus::Path::new("/org/freedesktop/NetworkManager/AccessPoint/1").unwrap(),
+            ssid: ssid.as_bytes().to_vec(),
+            ssid_lossy: ssid.into(),
+            strength,
+            frequency: 2412,
+            max_bitrate: 54_000,
+            hw_address: "02:00:00:00:00:01".into(),
+            flags: 0,
+            wpa_flags: 0,
+            rsn_flags: 0,
+            security: ApSecurity::Open,
+        }
+    }
+
+    #[test]
+    fn orders_by_signal_and_skips_absent_networks() {
+        let mut hidden = NetworkCandidate::new("Hidden", Security::Open);
+        hidden.options.hidden = true;
+        let candidates = [
+            NetworkCandidate::new("Depot", Security::Open),
+            NetworkCandidate::new("Absent", Security::Open),
+            hidden,
+            NetworkCandidate::new("Field", Security::Open),
+            NetworkCandidate::new("Van", Security::Open),
+        ];
+        let access_points = [
+            access_point("Depot", 40),
+            access_point("Field", 20),
+            access_point("Field", 75),
+            access_point("Van", 40),
+        ];
+        let (attempts, absent) = order_candidates(&candidates, &[0, 1, 2, 3, 4], &access_points);
+        assert_eq!(attempts, [3, 0, 4, 2]);
+        assert_eq!(absent.len(), 1);
+        assert_eq!(absent[0].index, 1);
+        assert!(matches!(
+            absent[0].error,
+            WifiConfigError::NetworkNotFound(_)
+        ));
+        assert_eq!(
+            absent[0].to_string(),
+            "'Absent': network 'Absent' is not in range"
+        );
+    }
+}
@@ -0,0 +1,946 @@
+//! The NetworkManager calls made by the blocking code, behind a trait so the logic
+//! around them can be tested without a system bus.
+
+use dbus::arg::PropMap;
+use dbus::blocking::{stdintf::org_freedesktop_dbus::Properties, Connection};
+use std::cell::Cell;
+
+use crate::error::nm_error;
+use crate::nm::{
+    ACCESS_POINT_INTERFACE, ACTIVE_CONNECTION_INTERFACE, DEVICE_INTERFACE, DHCP4_CONFIG_INTERFACE,
+    NM_BUS_NAME, NM_INTERFACE, NM_PATH, NO_OBJECT_PATH, SETTINGS_CONNECTION_INTERFACE,
+    SETTINGS_INTERFACE, SETTINGS_PATH, WIRELESS_INTERFACE,
+};
+use crate::retry::retry;
+use crate::secret;
+use crate::settings::ConnectionSettings;
+use crate::{Connectivity, DbusTimeouts, NmVersion, RetryPolicy, WifiConfigError};
+
+/// One NetworkManager D-Bus call per method, with its result decoded and its error
+/// mapped to the matching [`WifiConfigError`] variant.
+///
+/// Everything deciding *which* calls to make (device selection, updating or adding a
+/// profile, ...) lives outside, on top of `&dyn NmClient`.
+pub(crate) trait NmClient {
+    /// `GetDevices` on the manager object.
+    fn get_devices(&self) -> Result<Vec<dbus::Path<'static>>, WifiConfigError>;
+
+    /// All `org.freedesktop.NetworkManager.Device` properties of `device`.
+    fn device_properties(&self, device: &dbus::Path) -> Result<PropMap, WifiConfigError>;
+
+    /// All `org.freedesktop.NetworkManager.Device.Wireless` properties of `device`.
+    fn wireless_properties(&self, device: &dbus::Path) -> Result<PropMap, WifiConfigError>;
+
+    /// `Version` of the manager object.
+    fn version(&self) -> Result<NmVersion, WifiConfigError>;
+
+    /// `WirelessEnabled` and `WirelessHardwareEnabled` of the manager object.
+    fn wireless_enabled(&self) -> Result<(bool, bool), WifiConfigError>;
+
+    /// Sets `WirelessEnabled` of the manager object, i.e. turns the Wi-Fi radio on or off.
+    fn set_wireless_enabled(&self, enabled: bool) -> Result<(), WifiConfigError>;
+
+    /// `CheckConnectivity` on the manager object, falling back to its `Connectivity`
+    /// property if NetworkManager refuses the call.
+    fn check_connectivity(&self) -> Result<Connectivity, WifiConfigError>;
+
+    /// `Options` of the `Dhcp4Config` object of `device`; `None` while it has none.
+    fn dhcp4_options(&self, device: &dbus::Path) -> Result<Option<PropMap>, WifiConfigError>;
+
+    /// `GetAllAccessPoints` on the Wi-Fi device `device`.
+    fn access_points(
+        &self,
+        device: &dbus::Path,
+    ) -> Result<Vec<dbus::Path<'static>>, WifiConfigError>;
+
+    /// All `org.freedesktop.NetworkManager.AccessPoint` properties of `access_point`.
+    fn access_point_properties(
+        &self,
+        access_point: &dbus::Path,
+    ) -> Result<PropMap, WifiConfigError>;
+
+    /// `RequestScan` on the Wi-Fi device `device`, waiting for the scan to finish; see
+    /// [`request_scan`](crate::request_scan).
+    fn request_scan(&self, device: &dbus::Path) -> Result<(), WifiConfigError>;
+
+    /// `Connection` of the active connection `active`: the saved profile it runs.
+    fn active_connection_profile(
+        &self,
+        active: &dbus::Path,
+    ) -> Result<dbus::Path<'static>, WifiConfigError>;
+
+    /// All `org.freedesktop.NetworkManager.Connection.Active` properties of `active`.
+    fn active_connection_properties(&self, active: &dbus::Path)
+        -> Result<PropMap, WifiConfigError>;
+
+    /// `ListConnections` on the settings service.
+    fn list_connections(&self) -> Result<Vec<dbus::Path<'static>>, WifiConfigError>;
+
+    /// `GetConnectionByUuid` on the settings service.
+    fn connection_by_uuid(&self, uuid: &str) -> Result<dbus::Path<'static>, WifiConfigError>;
+
+    /// `GetSettings` (without secrets) of the saved profile `connection`.
+    fn get_settings(&self, connection: &dbus::Path) -> Result<ConnectionSettings, WifiConfigError>;
+
+    /// `GetSecrets` of the `setting` section of the saved profile `connection`.
+    fn get_secrets(
+        &self,
+        connection: &dbus::Path,
+        setting: &str,
+    ) -> Result<ConnectionSettings, WifiConfigError>;
+
+    /// `Update` of the saved profile `connection`, replacing all its settings.
+    fn update(
+        &self,
+        connection: &dbus::Path,
+        settings: ConnectionSettings,
+    ) -> Result<(), WifiConfigError>;
+
+    /// `AddConnection`: saves a profile without activating it and returns its path.
+    fn add_connection(
+        &self,
+        settings: ConnectionSettings,
+    ) -> Result<dbus::Path<'static>, WifiConfigError>;
+
+    /// `ActivateConnection`; returns the path of the active connection.
+    fn activate_connection(
+        &self,
+        connection: &dbus::Path,
+        device: &dbus::Path,
+        specific_object: &dbus::Path,
+    ) -> Result<dbus::Path<'static>, WifiConfigError>;
+
+    /// `AddAndActivateConnection`; returns the paths of the new profile and of its
+    /// active connection.
+    fn add_and_activate(
+        &self,
+        settings: ConnectionSettings,
+        device: &dbus::Path,
+        specific_object: &dbus::Path,
+    ) -> Result<(dbus::Path<'static>, dbus::Path<'static>), WifiConfigError>;
+
+    /// `Delete` of the saved profile `connection`.
+    fn delete_connection(&self, connection: &dbus::Path) -> Result<(), WifiConfigError>;
+}
+
+/// [`NmClient`] over a blocking system bus connection.
+///
+/// Queries use `timeouts.query`; calls that save or activate a profile use
+/// `timeouts.activate`. Device discovery and activation calls are retried as `retry`
+/// allows. The NetworkManager version is read once.
+pub(crate) struct DbusNmClient<'a> {
+    conn: &'a Connection,
+    timeouts: DbusTimeouts,
+    retry: RetryPolicy,
+    version: Cell<Option<NmVersion>>,
+}
+
+impl<'a> DbusNmClient<'a> {
+    pub(crate) fn new(conn: &'a Connection, timeouts: DbusTimeouts) -> Self {
+        DbusNmClient {
+            conn,
+            timeouts,
+            retry: RetryPolicy::default(),
+            version: Cell::new(None),
+        }
+    }
+
+    /// Retries device discovery and activation calls as `retry` allows.
+    pub(crate) fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Takes `version` as NetworkManager's version instead of reading it.
+    pub(crate) fn with_version(self, version: NmVersion) -> Self {
+        self.version.set(Some(version));
+        self
+    }
+}
+
+impl NmClient for DbusNmClient<'_> {
+    fn get_devices(&self) -> Result<Vec<dbus::Path<'static>>, WifiConfigError> {
+        let proxy = self
+            .conn
+            .with_proxy(NM_BUS_NAME, NM_PATH, self.timeouts.query);
+        let (devices,): (Vec<dbus::Path<'static>>,) = retry(&self.retry, "GetDevices", || {
+            proxy.method_call(NM_INTERFACE, "GetDevices", ())
+        })
+        .map_err(nm_error(WifiConfigError::DeviceQueryFailed))?;
+        Ok(devices)
+    }
+
+    fn device_properties(&self, device: &dbus::Path) -> Result<PropMap, WifiConfigError> {
+        let proxy = self
+            .conn
+            .with_proxy(NM_BUS_NAME, device, self.timeouts.query);
+        retry(&self.retry, "GetAll", || proxy.get_all(DEVICE_INTERFACE))
+            .map_err(nm_error(WifiConfigError::DeviceQueryFailed))
+    }
+
+    fn wireless_properties(&self, device: &dbus::Path) -> Result<PropMap, WifiConfigError> {
+        let proxy = self
+            .conn
+            .with_proxy(NM_BUS_NAME, device, self.timeouts.query);
+        retry(&self.retry, "GetAll Wireless", || {
+            proxy.get_all(WIRELESS_INTERFACE)
+        })
+        .map_err(nm_error(WifiConfigError::DeviceQueryFailed))
+    }
+
+    fn version(&self) -> Result<NmVersion, WifiConfigError> {
+        if let Some(version) = self.version.get() {
+            return Ok(version);
+        }
+        let version: String = self
+            .conn
+            .with_proxy(NM_BUS_NAME, NM_PATH, self.timeouts.query)
+            .get(NM_INTERFACE, "Version")
+            .map_err(nm_error(WifiConfigError::DeviceQueryFailed))?;
+        let parsed = NmVersion::parse(&version).ok_or_else(|| {
+            WifiConfigError::DeviceQueryFailed(dbus::Error::new_custom(
+                "org.freedesktop.DBus.Error.InvalidArgs",
+                &format!("unexpected NetworkManager version '{}'", version),
+            ))
+        })?;
+        log::debug!("NetworkManager {}", parsed);
+        self.version.set(Some(parsed));
+        Ok(parsed)
+    }
+
+    fn wireless_enabled(&self) -> Result<(bool, bool), WifiConfigError> {
+        let proxy = self
+            .conn
+            .with_proxy(NM_BUS_NAME, NM_PATH, self.timeouts.query);
+        let enabled = proxy
+            .get(NM_INTERFACE, "WirelessEnabled")
+            .map_err(nm_error(WifiConfigError::DeviceQueryFailed))?;
+        let hardware_enabled = proxy
+            .get(NM_INTERFACE, "WirelessHardwareEnabled")
+            .map_err(nm_error(WifiConfigError::DeviceQueryFailed))?;
+        Ok((enabled, hardware_enabled))
+    }
+
+    fn set_wireless_enabled(&self, enabled: bool) -> Result<(), WifiConfigError> {
+        self.conn
+            .with_proxy(NM_BUS_NAME, NM_PATH, self.timeouts.query)
+            .set(NM_INTERFACE, "WirelessEnabled", enabled)
+            .map_err(nm_error(WifiConfigError::activation_failed))
+    }
+
+    fn check_connectivity(&self) -> Result<Connectivity, WifiConfigError> {
+        // The call only returns once the check's HTTP request has finished.
+        let proxy = self
+            .conn
+            .with_proxy(NM_BUS_NAME, NM_PATH, self.timeouts.activate);
+        let result: Result<(u32,), dbus::Error> =
+            proxy.method_call(NM_INTERFACE, "CheckConnectivity", ());
+        let state = match result.map_err(nm_error(WifiConfigError::DeviceQueryFailed)) {
+            Ok((state,)) => state,
+            Err(e @ WifiConfigError::NetworkManagerUnavailable(_)) => return Err(e),
+            Err(e) => {
+                log::debug!("{}; reading the Connectivity property instead", e);
+                proxy
+                    .get(NM_INTERFACE, "Connectivity")
+                    .map_err(nm_error(WifiConfigError::DeviceQueryFailed))?
+            }
+        };
+        Ok(Connectivity::from(state))
+    }
+
+    fn access_points(
+        &self,
+        device: &dbus::Path,
+    ) -> Result<Vec<dbus::Path<'static>>, WifiConfigError> {
+        let proxy = self
+            .conn
+            .with_proxy(NM_BUS_NAME, device, self.timeouts.query);
+        let (access_points,): (Vec<dbus::Path<'static>>,) = proxy
+            .method_call(WIRELESS_INTERFACE, "GetAllAccessPoints", ())
+            .map_err(nm_error(WifiConfigError::ScanFailed))?;
+        Ok(access_points)
+    }
+
+    fn access_point_properties(
+        &self,
+        access_point: &dbus::Path,
+    ) -> Result<PropMap, WifiConfigError> {
+        self.conn
+            .with_proxy(NM_BUS_NAME, access_point, self.timeouts.query)
+            .get_all(ACCESS_POINT_INTERFACE)
+            .map_err(nm_error(WifiConfigError::ScanFailed))
+    }
+
+    fn dhcp4_options(&self, device: &dbus::Path) -> Result<Option<PropMap>, WifiConfigError> {
+        let dhcp4_config: dbus::Path<'static> = self
+            .conn
+            .with_proxy(NM_BUS_NAME, device, self.timeouts.query)
+            .get(DEVICE_INTERFACE, "Dhcp4Config")
+            .map_err(nm_error(WifiConfigError::DeviceQueryFailed))?;
+        if &*dhcp4_config == NO_OBJECT_PATH {
+            return Ok(None);
+        }
+        self.conn
+            .with_proxy(NM_BUS_NAME, &dhcp4_config, self.timeouts.query)
+            .get(DHCP4_CONFIG_INTERFACE, "Options")
+            .map(Some)
+            .map_err(nm_error(WifiConfigError::DeviceQueryFailed))
+    }
+
+    fn request_scan(&self, device: &dbus::Path) -> Result<(), WifiConfigError> {
+        crate::scan::rescan(
+            self.conn,
+            self.timeouts,
+            device,
+            true,
+            crate::scan::SCAN_TIMEOUT,
+        )
+    }
+
+    fn active_connection_profile(
+        &self,
+        active: &dbus::Path,
+    ) -> Result<dbus::Path<'static>, WifiConfigError> {
+        self.conn
+            .with_proxy(NM_BUS_NAME, active, self.timeouts.query)
+            .get(ACTIVE_CONNECTION_INTERFACE, "Connection")
+            .map_err(nm_error(WifiConfigError::DeviceQueryFailed))
+    }
+
+    fn active_connection_properties(
+        &self,
+        active: &dbus::Path,
+    ) -> Result<PropMap, WifiConfigError> {
+        self.conn
+            .with_proxy(NM_BUS_NAME, active, self.timeouts.query)
+            .get_all(ACTIVE_CONNECTION_INTERFACE)
+            .map_err(nm_error(WifiConfigError::DeviceQueryFailed))
+    }
+
+    fn list_connections(&self) -> Result<Vec<dbus::Path<'static>>, WifiConfigError> {
+        let proxy = self
+            .conn
+            .with_proxy(NM_BUS_NAME, SETTINGS_PATH, self.timeouts.query);
+        let (connections,): (Vec<dbus::Path<'static>>,) = proxy
+            .method_call(SETTINGS_INTERFACE, "ListConnections", ())
+            .map_err(nm_error(WifiConfigError::SettingsQueryFailed))?;
+        Ok(connections)
+    }
+
+    fn connection_by_uuid(&self, uuid: &str) -> Result<dbus::Path<'static>, WifiConfigError> {
+        let proxy = self
+            .conn
+            .with_proxy(NM_BUS_NAME, SETTINGS_PATH, self.timeouts.query);
+        let (connection,): (dbus::Path<'static>,) = proxy
+            .method_call(SETTINGS_INTERFACE, "GetConnectionByUuid", (uuid,))
+            .map_err(nm_error(WifiConfigError::SettingsQueryFailed))?;
+        Ok(connection)
+    }
+
+    fn get_settings(&self, connection: &dbus::Path) -> Result<ConnectionSettings, WifiConfigError> {
+        let proxy = self
+            .conn
+            .with_proxy(NM_BUS_NAME, connection, self.timeouts.query);
+        let (settings,): (ConnectionSettings,) = proxy
+            .method_call(SETTINGS_CONNECTION_INTERFACE, "GetSettings", ())
+            .map_err(nm_error(WifiConfigError::SettingsQueryFailed))?;
+        Ok(settings)
+    }
+
+    fn get_secrets(
+        &self,
+        connection: &dbus::Path,
+        setting: &str,
+    ) -> Result<ConnectionSettings, WifiConfigError> {
+        let proxy = self
+            .conn
+            .with_proxy(NM_BUS_NAME, connection, self.timeouts.query);
+        let (secrets,): (ConnectionSettings,) = proxy
+            .method_call(SETTINGS_CONNECTION_INTERFACE, "GetSecrets", (setting,))
+            .map_err(nm_error(WifiConfigError::SettingsQueryFailed))?;
+        Ok(secrets)
+    }
+
+    fn update(
+        &self,
+        connection: &dbus::Path,
+        mut settings: ConnectionSettings,
+    ) -> Result<(), WifiConfigError> {
+        let updated = self
+            .conn
+            .with_proxy(NM_BUS_NAME, connection, self.timeouts.activate)
+            .method_call(SETTINGS_CONNECTION_INTERFACE, "Update", (&settings,));
+        secret::wipe_settings(&mut settings);
+        updated.map_err(nm_error(WifiConfigError::activation_failed))
+    }
+
+    fn add_connection(
+        &self,
+        mut settings: ConnectionSettings,
+    ) -> Result<dbus::Path<'static>, WifiConfigError> {
+        let proxy = self
+            .conn
+            .with_proxy(NM_BUS_NAME, SETTINGS_PATH, self.timeouts.activate);
+        let added: Result<(dbus::Path<'static>,), _> =
+            proxy.method_call(SETTINGS_INTERFACE, "AddConnection", (&settings,));
+        secret::wipe_settings(&mut settings);
+        let (path,) = added.map_err(nm_error(WifiConfigError::activation_failed))?;
+        Ok(path)
+    }
+
+    fn activate_connection(
+        &self,
+        connection: &dbus::Path,
+        device: &dbus::Path,
+        specific_object: &dbus::Path,
+    ) -> Result<dbus::Path<'static>, WifiConfigError> {
+        let proxy = self
+            .conn
+            .with_proxy(NM_BUS_NAME, NM_PATH, self.timeouts.activate);
+        let (active_path,): (dbus::Path<'static>,) =
+            retry(&self.retry, "ActivateConnection", || {
+                proxy.method_call(
+                    NM_INTERFACE,
+                    "ActivateConnection",
+                    (connection, device, specific_object),
+                )
+            })
+            .map_err(nm_error(WifiConfigError::activation_failed))?;
+        Ok(active_path)
+    }
+
+    fn add_and_activate(
+        &self,
+        mut settings: ConnectionSettings,
+        device: &dbus::Path,
+        specific_object: &dbus::Path,
+    ) -> Result<(dbus::Path<'static>, dbus::Path<'static>), WifiConfigError> {
+        let proxy = self
+            .conn
+            .with_proxy(NM_BUS_NAME, NM_PATH, self.timeouts.activate);
+        let added = retry(&self.retry, "AddAndActivateConnection", || {
+            proxy.method_call(
+                NM_INTERFACE,
+                "AddAndActivateConnection",
+                (&settings, device, specific_object),
+            )
+        });
+        secret::wipe_settings(&mut settings);
+        added.map_err(nm_error(WifiConfigError::activation_failed))
+    }
+
+    fn delete_connection(&self, connection: &dbus::Path) -> Result<(), WifiConfigError> {
+        self.conn
+            .with_proxy(NM_BUS_NAME, connection, self.timeouts.query)
+            .method_call(SETTINGS_CONNECTION_INTERFACE, "Delete", ())
+            .map_err(nm_error(WifiConfigError::DeleteFailed))
+    }
+}
+
+/// An in-memory [`NmClient`] for unit tests.
+///
+/// Serves canned devices, access points and saved profiles, records every call in
+/// `calls` (`"AddAndActivateConnection /org/freedesktop/NetworkManager/Devices/3"`, ...)
+/// and keeps the settings it was sent, so tests can check both the decisions and the
+/// exact values that would go over the bus.
+#[cfg(test)]
+pub(crate) mod mock {
+    use dbus::arg::{PropMap, Variant};
+    use std::cell::{Cell, RefCell};
+
+    use super::NmClient;
+    use crate::settings::ConnectionSettings;
+    use crate::{Connectivity, NmVersion, WifiConfigError};
+
+    /// A canned device: its path, `Device` and `Device.Wireless` properties.
+    pub(crate) struct MockDevice {
+        pub(crate) path: dbus::Path<'static>,
+        pub(crate) properties: PropMap,
+        pub(crate) wireless_properties: PropMap,
+        pub(crate) access_points: Vec<(dbus::Path<'static>, PropMap)>,
+        /// `Options` of its `Dhcp4Config`; `None` for no DHCP configuration.
+        pub(crate) dhcp4_options: Option<PropMap>,
+    }
+
+    impl MockDevice {
+        /// A device of NetworkManager type `device_type` (`2` is Wi-Fi), numbered `index`.
+        pub(crate) fn new(index: u32, interface: &str, device_type: u32) -> Self {
+            let mut properties = PropMap::new();
+            properties.insert("Interface".into(), Variant(Box::new(interface.to_string())));
+            properties.insert("DeviceType".into(), Variant(Box::new(device_type)));
+            properties.insert("State".into(), Variant(Box::new(30u32)));
+            properties.insert("Managed".into(), Variant(Box::new(true)));
+            properties.insert("Mtu".into(), Variant(Box::new(1500u32)));
+            let mut wireless_properties = PropMap::new();
+            wireless_properties.insert(
+                "PermHwAddress".into(),
+                Variant(Box::new(String::from("00:11:22:33:44:55"))),
+            );
+            // CCMP, WPA, RSN and AP on both bands.
+            wireless_properties.insert("WirelessCapabilities".into(), Variant(Box::new(0x778u32)));
+            MockDevice {
+                path: device_path(index),
+                properties,
+                wireless_properties,
+                access_points: Vec::new(),
+                dhcp4_options: None,
+            }
+        }
+
+        /// Makes an access point broadcasting `ssid` visible to the device.
+        pub(crate) fn with_access_point(mut self, ssid: &[u8], rsn_flags: u32) -> Self {
+            let mut properties = PropMap::new();
+            properties.insert("Ssid".into(), Variant(Box::new(ssid.to_vec())));
+            properties.insert("Strength".into(), Variant(Box::new(70u8)));
+            properties.insert("Frequency".into(), Variant(Box::new(2437u32)));
+            properties.insert("Flags".into(), Variant(Box::new(1u32)));
+            properties.insert("WpaFlags".into(), Variant(Box::new(0u32)));
+            properties.insert("RsnFlags".into(), Variant(Box::new(rsn_flags)));
+            properties.insert(
+                "HwAddress".into(),
+                Variant(Box::new(String::from("AA:BB:CC:DD:EE:FF"))),
+            );
+            let path = format!(
+                "/org/freedesktop/NetworkManager/AccessPoint/{}",
+                self.access_points.len() + 1
+            );
+            self.access_points
+                .push((dbus::Path::new(path).unwrap(), properties));
+            self
+        }
+    }
+
+    /// Path of NetworkManager's device number `index`.
+    pub(crate) fn device_path(index: u32) -> dbus::Path<'static> {
+        dbus::Path::new(format!("/org/freedesktop/NetworkManager/Devices/{}", index)).unwrap()
+    }
+
+    #[derive(Default)]
+    pub(crate) struct MockNmClient {
+        pub(crate) devices: Vec<MockDevice>,
+        /// Saved profiles; `AddConnection` and `AddAndActivateConnection` append to it and
+        /// `Update` replaces entries.
+        pub(crate) connections: RefCell<Vec<(dbus::Path<'static>, ConnectionSettings)>>,
+        /// Active connections with the saved profile each runs, for `Connection`.
+        pub(crate) active_profiles: Vec<(dbus::Path<'static>, dbus::Path<'static>)>,
+        /// Returned by `AddAndActivateConnection` and `ActivateConnection` instead of
+        /// succeeding.
+        pub(crate) activation_error: Option<String>,
+        /// The Wi-Fi radio is switched off in software (`WirelessEnabled` is `false`).
+        pub(crate) wireless_disabled: Cell<bool>,
+        /// The Wi-Fi radio is blocked by a hardware switch.
+        pub(crate) wireless_hardware_disabled: bool,
+        /// `NMConnectivityState` returned by `CheckConnectivity`.
+        pub(crate) connectivity: u32,
+        /// Returned by `Version`; `None` reads as 1.46.0.
+        pub(crate) version: Option<NmVersion>,
+        /// `GetSecrets` fails with `PermissionDenied`, as for a caller that may not read
+        /// the secrets.
+        pub(crate) secrets_denied: bool,
+        /// Saved profiles `ListConnections` leaves out, like one another tool saves right
+        /// after the listing; adding a profile with the same UUID still fails.
+        pub(crate) unlisted: Vec<dbus::Path<'static>>,
+        /// Saved profiles `Delete` refuses with `PermissionDenied`.
+        pub(crate) undeletable: Vec<dbus::Path<'static>>,
+        /// Access points that go out of range once an activation is pinned to them: the
+        /// activation fails and `GetAllAccessPoints` leaves them out from then on.
+        pub(crate) vanishing: Vec<dbus::Path<'static>>,
+        /// The `vanishing` access points that are gone.
+        pub(crate) vanished: RefCell<Vec<dbus::Path<'static>>>,
+        pub(crate) calls: RefCell<Vec<String>>,
+    }
+
+    impl MockNmClient {
+        pub(crate) fn with_devices(devices: Vec<MockDevice>) -> Self {
+            MockNmClient {
+                devices,
+                ..Default::default()
+            }
+        }
+
+        /// Adds a saved profile with `connection.uuid` set to `uuid`.
+        pub(crate) fn save(
+            &self,
+            mut settings: ConnectionSettings,
+            uuid: &str,
+        ) -> dbus::Path<'static> {
+            settings
+                .entry("connection".into())
+                .or_default()
+                .entry("uuid".into())
+                .or_insert_with(|| Variant(Box::new(uuid.to_string())));
+            let mut connections = self.connections.borrow_mut();
+            let path = dbus::Path::new(format!(
+                "/org/freedesktop/NetworkManager/Settings/{}",
+                connections.len() + 1
+            ))
+            .unwrap();
+            connections.push((path.clone(), settings));
+            path
+        }
+
+        /// The recorded calls, e.g. `["GetDevices", "AddAndActivateConnection ..."]`.
+        pub(crate) fn calls(&self) -> Vec<String> {
+            self.calls.borrow().clone()
+        }
+
+        /// The calls made, without their arguments.
+        pub(crate) fn methods(&self) -> Vec<String> {
+            self.calls()
+                .iter()
+                .map(|call| call.split(' ').next().unwrap().to_string())
+                .collect()
+        }
+
+        fn record(&self, call: String) {
+            self.calls.borrow_mut().push(call);
+        }
+
+        fn device(&self, path: &dbus::Path) -> Result<&MockDevice, WifiConfigError> {
+            self.devices
+                .iter()
+                .find(|device| &device.path == path)
+                .ok_or_else(|| {
+                    WifiConfigError::DeviceQueryFailed(dbus::Error::new_custom(
+                        "org.freedesktop.DBus.Error.UnknownObject",
+                        "No such object path",
+                    ))
+                })
+        }
+
+        /// Fails the activation as asked, or if it is pinned to a vanishing access point.
+        fn activation_result(&self, specific_object: &dbus::Path) -> Result<(), WifiConfigError> {
+            if self
+                .vanishing
+                .contains(&specific_object.clone().into_static())
+            {
+                self.vanished
+                    .borrow_mut()
+                    .push(specific_object.clone().into_static());
+                return Err(WifiConfigError::ActivationFailed(format!(
+                    "The access point {} was not found \
+                     (org.freedesktop.NetworkManager.Device.SpecificObjectNotFound)",
+                    specific_object
+                )));
+            }
+            match &self.activation_error {
+                Some(reason) => Err(WifiConfigError::ActivationFailed(reason.clone())),
+                None => Ok(()),
+            }
+        }
+    }
+
+    impl NmClient for MockNmClient {
+        fn get_devices(&self) -> Result<Vec<dbus::Path<'static>>, WifiConfigError> {
+            self.record("GetDevices".into());
+            Ok(self
+                .devices
+                .iter()
+                .map(|device| device.path.clone())
+                .collect())
+        }
+
+        fn device_properties(&self, device: &dbus::Path) -> Result<PropMap, WifiConfigError> {
+            self.record(format!("GetAll {}", device));
+            let properties = &self.device(device)?.properties;
+            Ok(properties
+                .iter()
+                .map(|(key, value)| (key.clone(), Variant(value.0.box_clone())))
+                .collect())
+        }
+
+        fn wireless_properties(&self, device: &dbus::Path) -> Result<PropMap, WifiConfigError> {
+            self.record(format!("GetAllWireless {}", device));
+            let properties = &self.device(device)?.wireless_properties;
+            Ok(properties
+                .iter()
+                .map(|(key, value)| (key.clone(), Variant(value.0.box_clone())))
+                .collect())
+        }
+
+        fn version(&self) -> Result<NmVersion, WifiConfigError> {
+            self.record("Version".into());
+            Ok(self.version.unwrap_or(NmVersion::new(1, 46, 0)))
+        }
+
+        fn wireless_enabled(&self) -> Result<(bool, bool), WifiConfigError> {
+            self.record("WirelessEnabled".into());
+            Ok((
+                !self.wireless_disabled.get(),
+                !self.wireless_hardware_disabled,
+            ))
+        }
+
+        fn set_wireless_enabled(&self, enabled: bool) -> Result<(), WifiConfigError> {
+            self.record(format!("SetWirelessEnabled {}", enabled));
+            self.wireless_disabled.set(!enabled);
+            Ok(())
+        }
+
+        fn check_connectivity(&self) -> Result<Connectivity, WifiConfigError> {
+            self.record("CheckConnectivity".into());
+            Ok(Connectivity::from(self.connectivity))
+        }
+
+        fn access_points(
+            &self,
+            device: &dbus::Path,
+        ) -> Result<Vec<dbus::Path<'static>>, WifiConfigError> {
+            self.record(format!("GetAllAccessPoints {}", device));
+            let vanished = self.vanished.borrow();
+            Ok(self
+                .device(device)?
+                .access_points
+                .iter()
+                .map(|(path, _)| path.clone())
+                .filter(|path| !vanished.contains(path))
+                .collect())
+        }
+
+        fn access_point_properties(
+            &self,
+            access_point: &dbus::Path,
+        ) -> Result<PropMap, WifiConfigError> {
+            self.record(format!("GetAll {}", access_point));
+            let (_, properties) = self
+                .devices
+                .iter()
+                .flat_map(|device| &device.access_points)
+                .find(|(path, _)| path == access_point)
+                .expect("unknown access point");
+            Ok(properties
+                .iter()
+                .map(|(key, value)| (key.clone(), Variant(value.0.box_clone())))
+                .collect())
+        }
+
+        fn dhcp4_options(&self, device: &dbus::Path) -> Result<Option<PropMap>, WifiConfigError> {
+            self.record(format!("Dhcp4Options {}", device));
+            Ok(self.device(device)?.dhcp4_options.as_ref().map(|options| {
+                options
+                    .iter()
+                    .map(|(key, value)| (key.clone(), Variant(value.0.box_clone())))
+                    .collect()
+            }))
+        }
+
+        fn request_scan(&self, device: &dbus::Path) -> Result<(), WifiConfigError> {
+            self.record(format!("RequestScan {}", device));
+            Ok(())
+        }
+
+        fn active_connection_profile(
+            &self,
+            active: &dbus::Path,
+        ) -> Result<dbus::Path<'static>, WifiConfigError> {
+            self.record(format!("Connection {}", active));
+            let (_, profile) = self
+                .active_profiles
+                .iter()
+                .find(|(path, _)| path == active)
+                .expect("unknown active connection");
+            Ok(profile.clone())
+        }
+
+        fn active_connection_properties(
+            &self,
+            active: &dbus::Path,
+        ) -> Result<PropMap, WifiConfigError> {
+            self.record(format!("GetAllActive {}", active));
+            let (_, profile) = self
+                .active_profiles
+                .iter()
+                .find(|(path, _)| path == active)
+                .expect("unknown active connection");
+            let connections = self.connections.borrow();
+            let settings = connections
+                .iter()
+                .find(|(path, _)| path == profile)
+                .map(|(_, settings)| settings);
+            let uuid = settings
+                .map(crate::profiles::connection_uuid)
+                .unwrap_or_default();
+            let id = settings
+                .and_then(|settings| settings.get("connection"))
+                .and_then(|connection| dbus::arg::prop_cast::<String>(connection, "id"))
+                .cloned()
+                .unwrap_or_default();
+            let mut properties = PropMap::new();
+            properties.insert("Connection".into(), Variant(Box::new(profile.clone())));
+            properties.insert("Id".into(), Variant(Box::new(id)));
+            properties.insert("Uuid".into(), Variant(Box::new(uuid)));
+            // NM_ACTIVE_CONNECTION_STATE_ACTIVATED
+            properties.insert("State".into(), Variant(Box::new(2u32)));
+            Ok(properties)
+        }
+
+        fn list_connections(&self) -> Result<Vec<dbus::Path<'static>>, WifiConfigError> {
+            self.record("ListConnections".into());
+            Ok(self
+                .connections
+                .borrow()
+                .iter()
+                .map(|(path, _)| path.clone())
+                .filter(|path| !self.unlisted.contains(path))
+                .collect())
+        }
+
+        fn connection_by_uuid(&self, uuid: &str) -> Result<dbus::Path<'static>, WifiConfigError> {
+            self.record(format!("GetConnectionByUuid {}", uuid));
+            self.connections
+                .borrow()
+                .iter()
+                .find(|(_, settings)| crate::profiles::connection_uuid(settings) == uuid)
+                .map(|(path, _)| path.clone())
+                .ok_or_else(|| {
+                    WifiConfigError::SettingsQueryFailed(dbus::Error::new_custom(
+                        "org.freedesktop.NetworkManager.Settings.InvalidConnection",
+                        "No connection with the UUID was found.",
+                    ))
+                })
+        }
+
+        fn get_settings(
+            &self,
+            connection: &dbus::Path,
+        ) -> Result<ConnectionSettings, WifiConfigError> {
+            self.record(format!("GetSettings {}", connection));
+            let connections = self.connections.borrow();
+            let (_, settings) = connections
+                .iter()
+                .find(|(path, _)| path == connection)
+                .expect("unknown connection");
+            Ok(clone_settings(settings))
+        }
+
+        fn get_secrets(
+            &self,
+            connection: &dbus::Path,
+            setting: &str,
+        ) -> Result<ConnectionSettings, WifiConfigError> {
+            self.record(format!("GetSecrets {} {}", connection, setting));
+            if self.secrets_denied {
+                return Err(WifiConfigError::PermissionDenied(dbus::Error::new_custom(
+                    "org.freedesktop.NetworkManager.Settings.PermissionDenied",
+                    "Not authorized to get secrets",
+                )));
+            }
+            let connections = self.connections.borrow();
+            let (_, settings) = connections
+                .iter()
+                .find(|(path, _)| path == connection)
+                .expect("unknown connection");
+            // The mock keeps the secrets along with the other settings.
+            let mut secrets = clone_settings(settings);
+            secrets.retain(|name, _| name == setting);
+            Ok(secrets)
+        }
+
+        fn update(
+            &self,
+            connection: &dbus::Path,
+            settings: ConnectionSettings,
+        ) -> Result<(), WifiConfigError> {
+            self.record(format!("Update {}", connection));
+            let mut connections = self.connections.borrow_mut();
+            let entry = connections
+                .iter_mut()
+                .find(|(path, _)| path == connection)
+                .expect("unknown connection");
+            entry.1 = settings;
+            Ok(())
+        }
+
+        fn add_connection(
+            &self,
+            settings: ConnectionSettings,
+        ) -> Result<dbus::Path<'static>, WifiConfigError> {
+            self.record("AddConnection".into());
+            Ok(self.save(settings, "generated-uuid"))
+        }
+
+        fn activate_connection(
+            &self,
+            connection: &dbus::Path,
+            device: &dbus::Path,
+            specific_object: &dbus::Path,
+        ) -> Result<dbus::Path<'static>, WifiConfigError> {
+            self.record(format!(
+                "ActivateConnection {} {}{}",
+                connection,
+                device,
+                pin(specific_object)
+            ));
+            self.activation_result(specific_object)?;
+            Ok(dbus::Path::new("/org/freedesktop/NetworkManager/ActiveConnection/1").unwrap())
+        }
+
+        fn add_and_activate(
+            &self,
+            settings: ConnectionSettings,
+            device: &dbus::Path,
+            specific_object: &dbus::Path,
+        ) -> Result<(dbus::Path<'static>, dbus::Path<'static>), WifiConfigError> {
+            self.record(format!(
+                "AddAndActivateConnection {}{}",
+                device,
+                pin(specific_object)
+            ));
+            self.activation_result(specific_object)?;
+            let uuid = crate::profiles::connection_uuid(&settings);
+            let taken = self
+                .connections
+                .borrow()
+                .iter()
+                .any(|(_, saved)| crate::profiles::connection_uuid(saved) == uuid);
+            if !uuid.is_empty() && taken {
+                return Err(WifiConfigError::ProfileExists(dbus::Error::new_custom(
+                    "org.freedesktop.NetworkManager.Settings.UuidExists",
+                    "A connection with this UUID already exists",
+                )));
+            }
+            let settings_path = self.save(settings, "generated-uuid");
+            let active_path =
+                dbus::Path::new("/org/freedesktop/NetworkManager/ActiveConnection/1").unwrap();
+            Ok((settings_path, active_path))
+        }
+
+        fn delete_connection(&self, connection: &dbus::Path) -> Result<(), WifiConfigError> {
+            self.record(format!("Delete {}", connection));
+            if self.undeletable.iter().any(|path| path == connection) {
+                return Err(WifiConfigError::DeleteFailed(dbus::Error::new_custom(
+                    "org.freedesktop.NetworkManager.Settings.Connection.PermissionDenied",
+                    "Insufficient privileges",
+                )));
+            }
+            self.connections
+                .borrow_mut()
+                .retain(|(path, _)| path != connection);
+            Ok(())
+        }
+    }
+
+    /// ` <specific object>` for a recorded activation pinned to an access point, nothing
+    /// for `/`.
+    fn pin(specific_object: &dbus::Path) -> String {
+        if &**specific_object == "/" {
+            String::new()
+        } else {
+            format!(" {}", specific_object)
+        }
+    }
+
+    /// Deep-copies settings; `Variant<Box<dyn RefArg>>` isn't `Clone`.
+    pub(crate) fn clone_settings(settings: &ConnectionSettings) -> ConnectionSettings {
+        settings
+            .iter()
+            .map(|(name, section)| {
+                let section = section
+                    .iter()
+                    .map(|(key, value)| (key.clone(), Variant(value.0.box_clone())))
+                    .collect();
+                (name.clone(), section)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,456 @@
+use dbus::blocking::Connection;
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+#[cfg(feature = "serde")]
+use std::path::Path;
+
+use crate::client::{DbusNmClient, NmClient};
+use crate::profiles;
+use crate::settings;
+use crate::version;
+use crate::{Backend, ConnectOptions, ProfileChange, WifiConfigError};
+#[cfg(feature = "serde")]
+use crate::{Metered, Security};
+
+/// Top level of a config file: any number of `[[network]]` tables.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default)]
+    network: Vec<NetworkEntry>,
+}
+
+/// One `[[network]]` table.
+///
+/// Deliberately not `Debug`, so the password can't end up in a log line.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NetworkEntry {
+    ssid: String,
+    password: Option<String>,
+    security: Option<EntrySecurity>,
+    #[serde(default)]
+    hidden: bool,
+    priority: Option<i32>,
+    autoconnect: Option<bool>,
+    metered: Option<bool>,
+    id: Option<String>,
+    uuid: Option<String>,
+    #[serde(default, rename = "stable-uuid")]
+    stable_uuid: bool,
+}
+
+/// The `security` key; defaults to `wpa-psk` with a password and `open` without.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum EntrySecurity {
+    Open,
+    WpaPsk,
+    Wpa3Sae,
+    Wpa3Transition,
+    Owe,
+}
+
+#[cfg(feature = "serde")]
+impl NetworkEntry {
+    /// Builds and validates the options for this entry.
+    fn into_options(self) -> Result<ConnectOptions, WifiConfigError> {
+        let security = match (self.security, self.password) {
+            (Some(EntrySecurity::Open | EntrySecurity::Owe), Some(_)) => {
+                return Err(WifiConfigError::InvalidConfigFile(format!(
+                    "network '{}': open and OWE networks take no password",
+                    self.ssid
+                )))
+            }
+            (Some(EntrySecurity::Open), None) | (None, None) => Security::Open,
+            (Some(EntrySecurity::Owe), None) => Security::Owe,
+            (Some(EntrySecurity::WpaPsk) | None, password) => {
+                Security::WpaPsk(password.unwrap_or_default().into())
+            }
+            (Some(EntrySecurity::Wpa3Sae), password) => {
+                Security::Wpa3Sae(password.unwrap_or_default().into())
+            }
+            (Some(EntrySecurity::Wpa3Transition), password) => {
+                Security::Wpa3Transition(password.unwrap_or_default().into())
+            }
+        };
+        let options = ConnectOptions {
+            security,
+            hidden: self.hidden,
+            autoconnect: self.autoconnect,
+            autoconnect_priority: self.priority,
+            metered: self
+                .metered
+                .map(|metered| if metered { Metered::Yes } else { Metered::No }),
+            connection_id: self.id,
+            uuid: self.uuid,
+            stable_uuid: self.stable_uuid,
+            ..Default::default()
+        };
+        options.validate(self.ssid.as_bytes())?;
+        Ok(options)
+    }
+}
+
+/// A profile saved by [`save_connection`] or `apply_config_file`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SavedProfile {
+    /// D-Bus object path of the profile (`Settings.Connection`).
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::nm::serialize_path"))]
+    pub settings_path: dbus::Path<'static>,
+    /// Profile UUID (`connection.uuid`).
+    pub uuid: String,
+    /// Whether the profile was newly created or an existing one was updated.
+    pub change: ProfileChange,
+}
+
+/// What [`apply_config_file`] did with one `[[network]]` entry.
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+pub struct NetworkResult {
+    /// The entry's SSID.
+    pub ssid: String,
+    /// The saved profile, or why this entry couldn't be applied.
+    pub result: Result<SavedProfile, WifiConfigError>,
+}
+
+/// The SSID of an entry with its validated options, or why they are invalid.
+#[cfg(feature = "serde")]
+type ParsedNetwork = (String, Result<ConnectOptions, WifiConfigError>);
+
+/// Parses a config file into the SSID and validated options of each entry.
+///
+/// Unknown keys and type errors fail the whole file; an entry with invalid values only
+/// fails itself.
+#[cfg(feature = "serde")]
+fn parse_config(contents: &str) -> Result<Vec<ParsedNetwork>, WifiConfigError> {
+    let config: ConfigFile = toml::from_str(contents).map_err(|e| {
+        // Only the message and line: `Display` would quote the offending line, which may
+        // hold a password.
+        let message = e.message().trim_end();
+        WifiConfigError::InvalidConfigFile(match e.span() {
+            Some(span) => {
+                let line = contents[..span.start].matches('\n').count() + 1;
+                format!("line {}: {}", line, message)
+            }
+            None => message.to_string(),
+        })
+    })?;
+    Ok(config
+        .network
+        .into_iter()
+        .map(|entry| (entry.ssid.clone(), entry.into_options()))
+        .collect())
+}
+
+/// Saves the profile for `ssid`, updating a saved one if there is one (unless
+/// `options.allow_duplicates` is set), without activating it.
+fn save_profile(
+    client: &dyn NmClient,
+    ssid: &[u8],
+    options: &ConnectOptions,
+) -> Result<SavedProfile, WifiConfigError> {
+    let mut new = settings::build_connection_settings(ssid, options);
+    version::fit_settings(client, &mut new)?;
+    log::debug!(
+        "settings for {}: {}",
+        String::from_utf8_lossy(ssid),
+        settings::setting_keys(&new)
+    );
+    let saved = if options.allow_duplicates {
+        None
+    } else {
+        profiles::find_saved_connection(client, ssid, options.profile_uuid(ssid).as_deref())?
+    };
+    match saved {
+        Some((settings_path, existing)) => {
+            let uuid = profiles::connection_uuid(&existing);
+            let rename = options.connection_id.is_some();
+            let change =
+                profiles::update_connection(client, &settings_path, existing, new, rename)?;
+            if change != ProfileChange::Unchanged {
+                log::info!("updated profile {}", settings_path);
+            }
+            Ok(SavedProfile {
+                settings_path,
+                uuid,
+                change,
+            })
+        }
+        None => {
+            let uuid = settings::assign_uuid(&mut new);
+            let settings_path = client.add_connection(new)?;
+            log::info!("added profile {}", settings_path);
+            Ok(SavedProfile {
+                settings_path,
+                uuid,
+                change: ProfileChange::Created,
+            })
+        }
+    }
+}
+
+/// Saves a profile for `ssid` without activating it, for NetworkManager to autoconnect to
+/// once the network is in range.
+///
+/// # Behavior
+///
+/// - Validates `options` like [`connect`](crate::connect) does.
+/// - Updates the saved profile for the SSID with `Update` if there is one (unless
+///   `options.allow_duplicates` is set), or adds a new one with `AddConnection`.
+/// - Doesn't look for a Wi-Fi device, so it also works while the adapter is missing,
+///   e.g. in a chroot or while provisioning over Ethernet. `options.interface`, if set,
+///   only ends up in the profile.
+///
+/// # Errors
+///
+/// - The validation errors of [`connect`](crate::connect).
+/// - [`WifiConfigError::UnsupportedByBackend`] with a backend other than
+///   [`Backend::NetworkManager`].
+/// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+/// - [`WifiConfigError::NetworkManagerUnavailable`] if NetworkManager isn't running.
+/// - [`WifiConfigError::InvalidSettings`] / [`WifiConfigError::ActivationFailed`] if
+///   NetworkManager rejects the profile.
+///
+/// # Example
+///
+/// ```no_run
+/// use wifi_config::{ConnectOptions, Security};
+///
+/// let options = ConnectOptions::builder()
+///     .security(Security::WpaPsk("supersecret123".into()))
+///     .build();
+/// let profile = wifi_config::save_connection("MyHomeWiFi", &options)?;
+/// println!("saved {} at {}", profile.uuid, profile.settings_path);
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn save_connection(
+    ssid: impl AsRef<[u8]>,
+    options: &ConnectOptions,
+) -> Result<SavedProfile, WifiConfigError> {
+    let ssid = ssid.as_ref();
+    options.validate(ssid)?;
+    if options.backend != Backend::NetworkManager {
+        return Err(WifiConfigError::UnsupportedByBackend(
+            "saving profiles without activating them needs NetworkManager".into(),
+        ));
+    }
+    let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+    let client = DbusNmClient::new(&conn, options.timeouts).with_retry(options.retry);
+    save_profile(&client, ssid, options)
+}
+
+/// Saves a profile for every network described in a TOML config file.
+///
+/// The file lists networks as `[[network]]` tables:
+///
+/// ```toml
+/// [[network]]
+/// ssid = "Office"
+/// password = "supersecret123"
+/// hidden = true
+/// priority = 10
+///
+/// [[network]]
+/// ssid = "Guest"
+/// ```
+///
+/// Besides `ssid`, an entry may set `password`, `security` (`open`, `wpa-psk`,
+/// `wpa3-sae`, `wpa3-transition` or `owe`; defaults to `wpa-psk` with a password and
+/// `open` without), `hidden`, `priority`, `autoconnect` and `metered`.
+///
+/// # Behavior
+///
+/// - Rejects the whole file on unknown keys, so a typo like `pasword` can't silently
+///   produce an open network.
+/// - Validates each entry like [`connect`](crate::connect) does.
+/// - Updates the first saved profile for the SSID with `Update`, or adds a new one with
+///   `AddConnection`. Profiles are not activated; NetworkManager autoconnects to them
+///   when they are in range.
+/// - A failing entry doesn't stop the others; every entry gets a [`NetworkResult`], in
+///   file order.
+///
+/// # Errors
+///
+/// - [`WifiConfigError::ConfigFileRead`] if the file can't be read.
+/// - [`WifiConfigError::InvalidConfigFile`] if it isn't valid TOML or has unknown keys.
+/// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+/// - [`WifiConfigError::NetworkManagerUnavailable`] if NetworkManager isn't running.
+///
+/// Needs the `serde` feature.
+///
+/// # Example
+///
+/// ```no_run
+/// for network in wifi_config::apply_config_file("/etc/wifi-config/networks.toml")? {
+///     match network.result {
+///         Ok(profile) => println!("{}: {:?}", network.ssid, profile.change),
+///         Err(e) => eprintln!("{}: {}", network.ssid, e),
+///     }
+/// }
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+#[cfg(feature = "serde")]
+pub fn apply_config_file(path: impl AsRef<Path>) -> Result<Vec<NetworkResult>, WifiConfigError> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| WifiConfigError::ConfigFileRead(path.to_path_buf(), e))?;
+    let networks = parse_config(&contents)?;
+
+    let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+    Ok(networks
+        .into_iter()
+        .map(|(ssid, options)| {
+            let result = options.and_then(|options| {
+                let client = DbusNmClient::new(&conn, options.timeouts).with_retry(options.retry);
+                save_profile(&client, ssid.as_bytes(), &options)
+            });
+            NetworkResult { ssid, result }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::MockNmClient;
+    use crate::Security;
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn parses_networks() {
+        let networks = parse_config(
+            r#"
+            [[network]]
+            ssid = "Office"
+            password = "supersecret123"
+            hidden = true
+            priority = 10
+
+            [[network]]
+            ssid = "Guest"
+
+            [[network]]
+            ssid = "Lobby"
+            security = "owe"
+            metered = true
+            "#,
+        )
+        .unwrap();
+
+        let [(office, Ok(office_options)), (guest, Ok(guest_options)), (lobby, Ok(lobby_options))] =
+            networks.as_slice()
+        else {
+            panic!("expected three valid networks");
+        };
+        assert_eq!(office, "Office");
+        assert_eq!(
+            office_options.security,
+            Security::WpaPsk("supersecret123".into())
+        );
+        assert!(office_options.hidden);
+        assert_eq!(office_options.autoconnect_priority, Some(10));
+        assert_eq!(guest, "Guest");
+        assert_eq!(guest_options.security, Security::Open);
+        assert_eq!(lobby, "Lobby");
+        assert_eq!(lobby_options.security, Security::Owe);
+        assert_eq!(lobby_options.metered, Some(Metered::Yes));
+
+        assert!(parse_config("").unwrap().is_empty());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn unknown_keys_fail_the_whole_file() {
+        let result = parse_config(
+            r#"
+            [[network]]
+            ssid = "Office"
+            pasword = "supersecret123"
+            "#,
+        );
+        let Err(WifiConfigError::InvalidConfigFile(reason)) = result else {
+            panic!("expected InvalidConfigFile");
+        };
+        assert!(reason.starts_with("line 4:"), "{}", reason);
+        assert!(reason.contains("pasword"), "{}", reason);
+        // The offending line is not quoted, so the password doesn't leak.
+        assert!(!reason.contains("supersecret123"), "{}", reason);
+
+        assert!(matches!(
+            parse_config("[[networks]]\nssid = \"Office\""),
+            Err(WifiConfigError::InvalidConfigFile(_))
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn invalid_entries_fail_on_their_own() {
+        let networks = parse_config(
+            r#"
+            [[network]]
+            ssid = "Office"
+            password = "short"
+
+            [[network]]
+            ssid = "Guest"
+            security = "open"
+            password = "supersecret123"
+
+            [[network]]
+            ssid = "Home"
+            password = "supersecret123"
+            "#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            networks[0].1,
+            Err(WifiConfigError::InvalidPsk { .. })
+        ));
+        assert!(matches!(
+            networks[1].1,
+            Err(WifiConfigError::InvalidConfigFile(_))
+        ));
+        assert!(networks[2].1.is_ok());
+    }
+
+    #[test]
+    fn saves_without_a_device() {
+        let client = MockNmClient::with_devices(Vec::new());
+        let options = ConnectOptions {
+            security: Security::WpaPsk("supersecret123".into()),
+            ..Default::default()
+        };
+        let profile = save_profile(&client, b"Office", &options).unwrap();
+        assert_eq!(profile.change, ProfileChange::Created);
+        assert_eq!(
+            client.methods(),
+            ["Version", "ListConnections", "AddConnection"]
+        );
+
+        let profile = save_profile(&client, b"Office", &options).unwrap();
+        assert_eq!(profile.change, ProfileChange::Unchanged);
+        let renamed = ConnectOptions {
+            connection_id: Some("Office Wi-Fi".into()),
+            ..options.clone()
+        };
+        let profile = save_profile(&client, b"Office", &renamed).unwrap();
+        assert_eq!(profile.change, ProfileChange::Updated);
+        assert_eq!(
+            client.methods().iter().filter(|m| *m == "Update").count(),
+            1
+        );
+        let duplicate = ConnectOptions {
+            allow_duplicates: true,
+            ..options
+        };
+        let profile = save_profile(&client, b"Office", &duplicate).unwrap();
+        assert_eq!(profile.change, ProfileChange::Created);
+        assert_eq!(client.connections.borrow().len(), 2);
+    }
+}
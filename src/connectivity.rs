@@ -0,0 +1,164 @@
+use dbus::arg::{prop_cast, PropMap};
+
+use crate::client::NmClient;
+use crate::{WifiConfigError, WifiManager};
+
+/// The DHCP option with the captive portal's login page (RFC 8910, option 114), as
+/// NetworkManager names it in `DHCP4Config.Options`.
+const CAPTIVE_PORTAL_OPTION: &str = "captive_portal";
+
+/// Whether this host reaches the internet, as found by NetworkManager's connectivity
+/// check (an HTTP request to a known URL, see `NetworkManager.conf(5)`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Connectivity {
+    /// Not known: the check is disabled, or hasn't run yet.
+    Unknown,
+    /// Not connected to any network.
+    None,
+    /// Behind a captive portal that wants a login (or payment) first.
+    Portal,
+    /// Connected, but the check URL isn't reachable, e.g. because the uplink is dead.
+    Limited,
+    /// Online.
+    Full,
+}
+
+impl Connectivity {
+    /// Whether the internet is reachable.
+    pub fn is_online(self) -> bool {
+        self == Connectivity::Full
+    }
+
+    /// Whether a captive portal wants a login first.
+    pub fn is_portal(self) -> bool {
+        self == Connectivity::Portal
+    }
+}
+
+impl From<u32> for Connectivity {
+    /// Maps an `NMConnectivityState` value; unknown values map to `Unknown`.
+    fn from(value: u32) -> Self {
+        match value {
+            1 => Connectivity::None,
+            2 => Connectivity::Portal,
+            3 => Connectivity::Limited,
+            4 => Connectivity::Full,
+            _ => Connectivity::Unknown,
+        }
+    }
+}
+
+/// Asks NetworkManager to check connectivity now and returns the result.
+///
+/// # Behavior
+///
+/// - Calls `CheckConnectivity` on `org.freedesktop.NetworkManager`, which blocks until the
+///   check has finished.
+/// - If NetworkManager refuses, e.g. because checking is disabled, reads the manager's
+///   `Connectivity` property instead.
+///
+/// With checking disabled, the result is [`Connectivity::Unknown`]; it is never guessed
+/// from the device state.
+///
+/// # Errors
+///
+/// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+/// - [`WifiConfigError::NetworkManagerUnavailable`] if NetworkManager isn't running.
+/// - [`WifiConfigError::DeviceQueryFailed`] if neither the call nor the property answers.
+///
+/// # Example
+///
+/// ```no_run
+/// use wifi_config::Connectivity;
+///
+/// if wifi_config::check_connectivity()? == Connectivity::Portal {
+///     println!("log in to the captive portal first");
+/// }
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn check_connectivity() -> Result<Connectivity, WifiConfigError> {
+    WifiManager::new()?.check_connectivity()
+}
+
+/// The connectivity of a device from its `properties`: the better of its
+/// `Ip4Connectivity` and `Ip6Connectivity` (NetworkManager 1.16+), which is how
+/// NetworkManager combines them for the global state. `Unknown` if it has neither.
+pub(crate) fn device_connectivity(properties: &PropMap) -> Connectivity {
+    ["Ip4Connectivity", "Ip6Connectivity"]
+        .into_iter()
+        .filter_map(|property| prop_cast::<u32>(properties, property).copied())
+        .max()
+        .map_or(Connectivity::Unknown, Connectivity::from)
+}
+
+/// The captive portal's login page announced over DHCP on the device at `device_path`,
+/// if the network sent one and NetworkManager reports it.
+///
+/// Best effort: a failure to read it is logged and treated as no URL.
+pub(crate) fn portal_url(client: &dyn NmClient, device_path: &dbus::Path) -> Option<String> {
+    match client.dhcp4_options(device_path) {
+        Ok(options) => options.as_ref().and_then(portal_url_from_options),
+        Err(e) => {
+            log::debug!("can't read the DHCP options of {}: {}", device_path, e);
+            None
+        }
+    }
+}
+
+/// The `captive_portal` entry of `DHCP4Config.Options`, unless it is empty or RFC 8910's
+/// "no portal" URN.
+fn portal_url_from_options(options: &PropMap) -> Option<String> {
+    prop_cast::<String>(options, CAPTIVE_PORTAL_OPTION)
+        .filter(|url| !url.is_empty() && *url != "urn:ietf:params:capport:unrestricted")
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_nm_connectivity_states() {
+        let states: Vec<Connectivity> = (0..=5).map(Connectivity::from).collect();
+        assert_eq!(
+            states,
+            [
+                Connectivity::Unknown,
+                Connectivity::None,
+                Connectivity::Portal,
+                Connectivity::Limited,
+                Connectivity::Full,
+                Connectivity::Unknown,
+            ]
+        );
+        assert!(Connectivity::Full.is_online());
+        assert!(!Connectivity::Portal.is_online());
+        assert!(Connectivity::Portal.is_portal());
+    }
+
+    #[test]
+    fn reads_the_portal_url_from_dhcp_options() {
+        let options = |url: &str| {
+            let mut options = PropMap::new();
+            options.insert(
+                CAPTIVE_PORTAL_OPTION.into(),
+                dbus::arg::Variant(Box::new(url.to_string())),
+            );
+            options
+        };
+        assert_eq!(
+            portal_url_from_options(&options("https://portal.example.com/login")).as_deref(),
+            Some("https://portal.example.com/login")
+        );
+        assert_eq!(
+            portal_url_from_options(&options("urn:ietf:params:capport:unrestricted")),
+            None
+        );
+        assert_eq!(portal_url_from_options(&PropMap::new()), None);
+    }
+}
@@ -0,0 +1,753 @@
+use dbus::arg::{prop_cast, PropMap};
+use dbus::blocking::{stdintf::org_freedesktop_dbus::Properties, Connection};
+use std::fmt;
+
+use crate::client::NmClient;
+use crate::error::nm_error;
+use crate::nm::{DEVICE_INTERFACE, DEVICE_TYPE_WIFI, NM_BUS_NAME};
+use crate::{Band, DbusTimeouts, WifiConfigError, WifiManager};
+
+/// State of a NetworkManager device (`NMDeviceState`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum DeviceState {
+    /// The state is unknown.
+    Unknown,
+    /// The device is not managed by NetworkManager.
+    Unmanaged,
+    /// The device can't be used (e.g. radio disabled or firmware missing).
+    Unavailable,
+    /// The device is usable but not connected.
+    Disconnected,
+    /// Preparing the connection.
+    Prepare,
+    /// Connecting to the network.
+    Config,
+    /// Waiting for secrets (e.g. a password).
+    NeedAuth,
+    /// Requesting IP configuration.
+    IpConfig,
+    /// Checking whether further action is required for the IP configuration.
+    IpCheck,
+    /// Waiting for a secondary connection (e.g. a VPN).
+    Secondaries,
+    /// The device is connected.
+    Activated,
+    /// The connection is being torn down.
+    Deactivating,
+    /// The connection attempt failed.
+    Failed,
+}
+
+impl From<u32> for DeviceState {
+    fn from(state: u32) -> Self {
+        match state {
+            10 => DeviceState::Unmanaged,
+            20 => DeviceState::Unavailable,
+            30 => DeviceState::Disconnected,
+            40 => DeviceState::Prepare,
+            50 => DeviceState::Config,
+            60 => DeviceState::NeedAuth,
+            70 => DeviceState::IpConfig,
+            80 => DeviceState::IpCheck,
+            90 => DeviceState::Secondaries,
+            100 => DeviceState::Activated,
+            110 => DeviceState::Deactivating,
+            120 => DeviceState::Failed,
+            _ => DeviceState::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for DeviceState {
+    /// The state's name as `nmcli` spells it, e.g. `need-auth` or `ip-config`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DeviceState::Unknown => "unknown",
+            DeviceState::Unmanaged => "unmanaged",
+            DeviceState::Unavailable => "unavailable",
+            DeviceState::Disconnected => "disconnected",
+            DeviceState::Prepare => "prepare",
+            DeviceState::Config => "config",
+            DeviceState::NeedAuth => "need-auth",
+            DeviceState::IpConfig => "ip-config",
+            DeviceState::IpCheck => "ip-check",
+            DeviceState::Secondaries => "secondaries",
+            DeviceState::Activated => "activated",
+            DeviceState::Deactivating => "deactivating",
+            DeviceState::Failed => "failed",
+        })
+    }
+}
+
+/// Reason for a device state change (`NMDeviceStateReason`).
+///
+/// Only the reasons relevant to Wi-Fi are named; everything else is reported as
+/// [`DeviceStateReason::Other`] with the raw code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum DeviceStateReason {
+    /// No reason given.
+    None,
+    /// Unknown error.
+    Unknown,
+    /// The device became managed.
+    NowManaged,
+    /// The device became unmanaged.
+    NowUnmanaged,
+    /// The device could not be readied for configuration.
+    ConfigFailed,
+    /// IP configuration could not be reserved (no available address, timeout, etc.).
+    IpConfigUnavailable,
+    /// The IP configuration is no longer valid.
+    IpConfigExpired,
+    /// Secrets were required but not provided (typically a wrong or missing password).
+    NoSecrets,
+    /// The 802.1X supplicant disconnected (typically a wrong password).
+    SupplicantDisconnect,
+    /// Configuring the 802.1X supplicant failed.
+    SupplicantConfigFailed,
+    /// The 802.1X supplicant failed.
+    SupplicantFailed,
+    /// The 802.1X supplicant took too long to authenticate.
+    SupplicantTimeout,
+    /// The DHCP client failed to start.
+    DhcpStartFailed,
+    /// The DHCP client reported an error.
+    DhcpError,
+    /// The DHCP client failed.
+    DhcpFailed,
+    /// The shared connection service failed to start.
+    SharedStartFailed,
+    /// The shared connection service failed.
+    SharedFailed,
+    /// Necessary firmware for the device may be missing.
+    FirmwareMissing,
+    /// The device was removed.
+    Removed,
+    /// NetworkManager went to sleep.
+    Sleeping,
+    /// The device's active connection was removed.
+    ConnectionRemoved,
+    /// Disconnected at the user's or a client's request.
+    UserRequested,
+    /// A dependency of the connection failed.
+    DependencyFailed,
+    /// The requested SSID could not be found.
+    SsidNotFound,
+    /// The connection was replaced by a new activation.
+    NewActivation,
+    /// The IP address is already in use on the network.
+    IpAddressDuplicate,
+    /// Any other reason code.
+    Other(u32),
+}
+
+impl DeviceStateReason {
+    /// Returns `true` for the supplicant failures that indicate rejected credentials.
+    pub fn is_auth_failure(&self) -> bool {
+        matches!(
+            self,
+            DeviceStateReason::NoSecrets | DeviceStateReason::SupplicantDisconnect
+        )
+    }
+}
+
+impl From<u32> for DeviceStateReason {
+    fn from(reason: u32) -> Self {
+        match reason {
+            0 => DeviceStateReason::None,
+            1 => DeviceStateReason::Unknown,
+            2 => DeviceStateReason::NowManaged,
+            3 => DeviceStateReason::NowUnmanaged,
+            4 => DeviceStateReason::ConfigFailed,
+            5 => DeviceStateReason::IpConfigUnavailable,
+            6 => DeviceStateReason::IpConfigExpired,
+            7 => DeviceStateReason::NoSecrets,
+            8 => DeviceStateReason::SupplicantDisconnect,
+            9 => DeviceStateReason::SupplicantConfigFailed,
+            10 => DeviceStateReason::SupplicantFailed,
+            11 => DeviceStateReason::SupplicantTimeout,
+            15 => DeviceStateReason::DhcpStartFailed,
+            16 => DeviceStateReason::DhcpError,
+            17 => DeviceStateReason::DhcpFailed,
+            18 => DeviceStateReason::SharedStartFailed,
+            19 => DeviceStateReason::SharedFailed,
+            35 => DeviceStateReason::FirmwareMissing,
+            36 => DeviceStateReason::Removed,
+            37 => DeviceStateReason::Sleeping,
+            38 => DeviceStateReason::ConnectionRemoved,
+            39 => DeviceStateReason::UserRequested,
+            50 => DeviceStateReason::DependencyFailed,
+            53 => DeviceStateReason::SsidNotFound,
+            60 => DeviceStateReason::NewActivation,
+            64 => DeviceStateReason::IpAddressDuplicate,
+            other => DeviceStateReason::Other(other),
+        }
+    }
+}
+
+impl fmt::Display for DeviceStateReason {
+    /// A short description, e.g. `secrets were required but not provided`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            DeviceStateReason::None => "no reason given",
+            DeviceStateReason::Unknown => "unknown error",
+            DeviceStateReason::NowManaged => "the device became managed",
+            DeviceStateReason::NowUnmanaged => "the device became unmanaged",
+            DeviceStateReason::ConfigFailed => "the device could not be readied",
+            DeviceStateReason::IpConfigUnavailable => "no IP configuration could be obtained",
+            DeviceStateReason::IpConfigExpired => "the IP configuration expired",
+            DeviceStateReason::NoSecrets => "secrets were required but not provided",
+            DeviceStateReason::SupplicantDisconnect => "the supplicant disconnected",
+            DeviceStateReason::SupplicantConfigFailed => "configuring the supplicant failed",
+            DeviceStateReason::SupplicantFailed => "the supplicant failed",
+            DeviceStateReason::SupplicantTimeout => "the supplicant timed out",
+            DeviceStateReason::DhcpStartFailed => "the DHCP client failed to start",
+            DeviceStateReason::DhcpError => "DHCP error",
+            DeviceStateReason::DhcpFailed => "DHCP failed",
+            DeviceStateReason::SharedStartFailed => "the shared connection service failed to start",
+            DeviceStateReason::SharedFailed => "the shared connection service failed",
+            DeviceStateReason::FirmwareMissing => "firmware may be missing",
+            DeviceStateReason::Removed => "the device was removed",
+            DeviceStateReason::Sleeping => "NetworkManager went to sleep",
+            DeviceStateReason::ConnectionRemoved => "the connection was removed",
+            DeviceStateReason::UserRequested => "disconnected on request",
+            DeviceStateReason::DependencyFailed => "a dependency failed",
+            DeviceStateReason::SsidNotFound => "the network was not found",
+            DeviceStateReason::NewActivation => "replaced by a new activation",
+            DeviceStateReason::IpAddressDuplicate => "the IP address is already in use",
+            DeviceStateReason::Other(code) => return write!(f, "reason {}", code),
+        };
+        f.write_str(description)
+    }
+}
+
+/// How [`connect`](crate::connect) picks the Wi-Fi device when neither
+/// [`device_path`](crate::ConnectOptions::device_path) nor
+/// [`interface`](crate::ConnectOptions::interface) names one.
+///
+/// A device is usable unless it is [`Unmanaged`](DeviceState::Unmanaged) or
+/// [`Unavailable`](DeviceState::Unavailable), e.g. a USB adapter that is plugged in but
+/// down. iwd and wpa_supplicant always use their first device.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum DeviceSelection {
+    /// The first Wi-Fi device NetworkManager reports, whatever its state.
+    First,
+    /// The first usable device, preferring one that is disconnected or connected over one
+    /// that is busy activating.
+    #[default]
+    Best,
+    /// Every usable device: the profile is activated on each of them, in the same order
+    /// as with `Best`. Only supported by the NetworkManager backend.
+    All,
+}
+
+/// A Wi-Fi device known to NetworkManager.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct WifiDevice {
+    /// D-Bus object path of the device; can be passed as
+    /// [`ConnectOptions::device_path`](crate::ConnectOptions::device_path).
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::nm::serialize_path"))]
+    pub path: dbus::Path<'static>,
+    /// Interface name, e.g. `wlan0`.
+    pub interface: String,
+    /// Kernel driver name.
+    pub driver: String,
+    /// Permanent MAC address of the adapter.
+    pub perm_hw_address: String,
+    /// Current device state.
+    pub state: DeviceState,
+    /// Whether NetworkManager manages the device.
+    pub managed: bool,
+    /// MTU currently in use on the interface (`Mtu`), e.g. to confirm an
+    /// [`mtu`](crate::ConnectOptions::mtu) override took effect.
+    pub mtu: u32,
+    /// What the adapter supports, e.g. access point mode and the 5 GHz band.
+    pub capabilities: DeviceCapabilities,
+}
+
+/// `NMDeviceWifiCapabilities` bits, in the order of [`DeviceCapabilities`]' fields.
+const WIFI_DEVICE_CAP_CIPHER_WEP40: u32 = 0x1;
+const WIFI_DEVICE_CAP_CIPHER_WEP104: u32 = 0x2;
+const WIFI_DEVICE_CAP_CIPHER_TKIP: u32 = 0x4;
+const WIFI_DEVICE_CAP_CIPHER_CCMP: u32 = 0x8;
+const WIFI_DEVICE_CAP_WPA: u32 = 0x10;
+const WIFI_DEVICE_CAP_RSN: u32 = 0x20;
+const WIFI_DEVICE_CAP_AP: u32 = 0x40;
+const WIFI_DEVICE_CAP_ADHOC: u32 = 0x80;
+const WIFI_DEVICE_CAP_FREQ_VALID: u32 = 0x100;
+const WIFI_DEVICE_CAP_FREQ_2GHZ: u32 = 0x200;
+const WIFI_DEVICE_CAP_FREQ_5GHZ: u32 = 0x400;
+const WIFI_DEVICE_CAP_FREQ_6GHZ: u32 = 0x800;
+const WIFI_DEVICE_CAP_MESH: u32 = 0x1000;
+const WIFI_DEVICE_CAP_IBSS_RSN: u32 = 0x2000;
+
+/// What a Wi-Fi adapter supports, decoded from the `WirelessCapabilities` property of
+/// `org.freedesktop.NetworkManager.Device.Wireless`.
+///
+/// NetworkManager reports the bands but not the individual frequencies an adapter
+/// supports; `iw phy` lists those.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DeviceCapabilities {
+    /// 40-bit WEP.
+    pub wep40: bool,
+    /// 104-bit WEP.
+    pub wep104: bool,
+    /// The TKIP cipher.
+    pub tkip: bool,
+    /// The CCMP (AES) cipher.
+    pub ccmp: bool,
+    /// WPA.
+    pub wpa: bool,
+    /// WPA2/RSN.
+    pub rsn: bool,
+    /// Access point mode, needed for [`create_hotspot`](crate::create_hotspot).
+    pub ap: bool,
+    /// Ad-hoc (IBSS) mode.
+    pub adhoc: bool,
+    /// Whether the driver reports its bands at all; older drivers don't, and the band
+    /// fields are then all `false`.
+    pub bands_known: bool,
+    /// The 2.4 GHz band.
+    pub band_2ghz: bool,
+    /// The 5 GHz band.
+    pub band_5ghz: bool,
+    /// The 6 GHz band.
+    pub band_6ghz: bool,
+    /// 802.11s mesh networking.
+    pub mesh: bool,
+    /// WPA2 in ad-hoc mode.
+    pub ibss_rsn: bool,
+}
+
+impl From<u32> for DeviceCapabilities {
+    fn from(bits: u32) -> Self {
+        let has = |bit: u32| bits & bit != 0;
+        let bands_known = has(WIFI_DEVICE_CAP_FREQ_VALID);
+        DeviceCapabilities {
+            wep40: has(WIFI_DEVICE_CAP_CIPHER_WEP40),
+            wep104: has(WIFI_DEVICE_CAP_CIPHER_WEP104),
+            tkip: has(WIFI_DEVICE_CAP_CIPHER_TKIP),
+            ccmp: has(WIFI_DEVICE_CAP_CIPHER_CCMP),
+            wpa: has(WIFI_DEVICE_CAP_WPA),
+            rsn: has(WIFI_DEVICE_CAP_RSN),
+            ap: has(WIFI_DEVICE_CAP_AP),
+            adhoc: has(WIFI_DEVICE_CAP_ADHOC),
+            bands_known,
+            band_2ghz: bands_known && has(WIFI_DEVICE_CAP_FREQ_2GHZ),
+            band_5ghz: bands_known && has(WIFI_DEVICE_CAP_FREQ_5GHZ),
+            band_6ghz: bands_known && has(WIFI_DEVICE_CAP_FREQ_6GHZ),
+            mesh: has(WIFI_DEVICE_CAP_MESH),
+            ibss_rsn: has(WIFI_DEVICE_CAP_IBSS_RSN),
+        }
+    }
+}
+
+impl DeviceCapabilities {
+    /// Whether the adapter supports `band`; `None` if the driver doesn't say.
+    pub fn supports_band(&self, band: Band) -> Option<bool> {
+        if !self.bands_known {
+            return None;
+        }
+        Some(match band {
+            Band::Bg => self.band_2ghz,
+            Band::A => self.band_5ghz,
+        })
+    }
+}
+
+impl WifiDevice {
+    /// Builds a device from its `Device` and `Device.Wireless` interface properties.
+    pub(crate) fn from_properties(
+        path: dbus::Path<'static>,
+        props: &PropMap,
+        wireless_props: &PropMap,
+    ) -> Self {
+        let perm_hw_address = prop_cast::<String>(wireless_props, "PermHwAddress")
+            .cloned()
+            .unwrap_or_default();
+        let capabilities = prop_cast::<u32>(wireless_props, "WirelessCapabilities")
+            .copied()
+            .unwrap_or(0);
+        WifiDevice::refreshed(path, props, perm_hw_address, capabilities.into())
+    }
+
+    /// Builds a device from fresh `Device` interface properties and the adapter's
+    /// details, which don't change.
+    pub(crate) fn refreshed(
+        path: dbus::Path<'static>,
+        props: &PropMap,
+        perm_hw_address: String,
+        capabilities: DeviceCapabilities,
+    ) -> Self {
+        WifiDevice {
+            path,
+            interface: prop_cast::<String>(props, "Interface")
+                .cloned()
+                .unwrap_or_default(),
+            driver: prop_cast::<String>(props, "Driver")
+                .cloned()
+                .unwrap_or_default(),
+            perm_hw_address,
+            state: DeviceState::from(prop_cast::<u32>(props, "State").copied().unwrap_or(0)),
+            managed: prop_cast::<bool>(props, "Managed")
+                .copied()
+                .unwrap_or(false),
+            mtu: prop_cast::<u32>(props, "Mtu").copied().unwrap_or(0),
+            capabilities,
+        }
+    }
+}
+
+impl WifiDevice {
+    /// Whether [`DeviceSelection::Best`] considers the device at all.
+    fn is_usable(&self) -> bool {
+        !matches!(
+            self.state,
+            DeviceState::Unmanaged | DeviceState::Unavailable
+        )
+    }
+
+    /// Whether the device is neither activating nor deactivating a connection.
+    fn is_settled(&self) -> bool {
+        matches!(
+            self.state,
+            DeviceState::Disconnected | DeviceState::Activated
+        )
+    }
+}
+
+/// Returns `true` if the `Device` interface properties describe a Wi-Fi device.
+pub(crate) fn is_wifi(props: &PropMap) -> bool {
+    prop_cast::<u32>(props, "DeviceType").copied() == Some(DEVICE_TYPE_WIFI)
+}
+
+/// Lists the Wi-Fi devices known to NetworkManager.
+///
+/// # Behavior
+///
+/// - Calls `GetDevices` on NetworkManager.
+/// - Fetches all `org.freedesktop.NetworkManager.Device` properties of each device with a
+///   single `GetAll` call and keeps the devices of type `2` (Wi-Fi).
+/// - Reads `PermHwAddress` and `WirelessCapabilities` from the Wi-Fi specific interface
+///   with another `GetAll`.
+///
+/// # Errors
+///
+/// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+/// - [`WifiConfigError::NetworkManagerUnavailable`] if NetworkManager isn't running.
+/// - [`WifiConfigError::DeviceQueryFailed`] if listing devices or reading their properties fails.
+///
+/// # Example
+///
+/// ```no_run
+/// for device in wifi_config::list_wifi_devices()? {
+///     println!("{} ({}) {:?}", device.interface, device.driver, device.state);
+/// }
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn list_wifi_devices() -> Result<Vec<WifiDevice>, WifiConfigError> {
+    WifiManager::new()?.devices()
+}
+
+/// [`list_wifi_devices`] through `client`.
+pub(crate) fn wifi_devices(client: &dyn NmClient) -> Result<Vec<WifiDevice>, WifiConfigError> {
+    let mut wifi_devices = Vec::new();
+    for device in client.get_devices()? {
+        let props = client.device_properties(&device)?;
+        if !is_wifi(&props) {
+            continue;
+        }
+        let wireless_props = client.wireless_properties(&device)?;
+        wifi_devices.push(WifiDevice::from_properties(device, &props, &wireless_props));
+    }
+    Ok(wifi_devices)
+}
+
+/// D-Bus error NetworkManager returns when disconnecting a device that isn't active.
+const NOT_ACTIVE_ERROR: &str = "org.freedesktop.NetworkManager.Device.NotActive";
+
+/// Result of [`disconnect`] / [`disconnect_interface`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum DisconnectOutcome {
+    /// An active connection was torn down.
+    Disconnected,
+    /// The device had no active connection to begin with.
+    AlreadyDisconnected,
+}
+
+/// Disconnects the first usable Wi-Fi device.
+///
+/// # Behavior
+///
+/// - Locates the Wi-Fi device the same way [`connect`](crate::connect) does.
+/// - Calls `Disconnect` on `org.freedesktop.NetworkManager.Device`.
+/// - With `disable_autoconnect`, also sets the device's `Autoconnect` property to `false`
+///   so NetworkManager doesn't re-associate on its own until a connection is activated
+///   explicitly, e.g. with [`connect`](crate::connect).
+///
+/// # Errors
+///
+/// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+/// - [`WifiConfigError::NetworkManagerUnavailable`] if NetworkManager isn't running.
+/// - [`WifiConfigError::DeviceQueryFailed`] / [`WifiConfigError::NoWifiDevice`] if the
+///   Wi-Fi device can't be found.
+/// - [`WifiConfigError::DisconnectFailed`] if `Disconnect` fails for any reason other than
+///   the device already being disconnected, or `Autoconnect` can't be set.
+///
+/// # Example
+///
+/// ```no_run
+/// use wifi_config::{disconnect, DisconnectOutcome};
+///
+/// if disconnect(true)? == DisconnectOutcome::AlreadyDisconnected {
+///     println!("Wi-Fi was not connected");
+/// }
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn disconnect(disable_autoconnect: bool) -> Result<DisconnectOutcome, WifiConfigError> {
+    WifiManager::new()?.disconnect(disable_autoconnect)
+}
+
+/// Like [`disconnect`], but for the Wi-Fi device with the given interface name.
+///
+/// # Errors
+///
+/// - [`WifiConfigError::DeviceNotFound`] if no Wi-Fi device is called `interface`.
+/// - Otherwise the same as [`disconnect`].
+pub fn disconnect_interface(
+    interface: &str,
+    disable_autoconnect: bool,
+) -> Result<DisconnectOutcome, WifiConfigError> {
+    WifiManager::new()?
+        .for_interface(interface)
+        .disconnect(disable_autoconnect)
+}
+
+/// [`disconnect`] of the device at `device_path`.
+pub(crate) fn disconnect_path(
+    conn: &Connection,
+    timeouts: DbusTimeouts,
+    device_path: &dbus::Path,
+    disable_autoconnect: bool,
+) -> Result<DisconnectOutcome, WifiConfigError> {
+    let device_proxy = conn.with_proxy(NM_BUS_NAME, device_path, timeouts.query);
+
+    if disable_autoconnect {
+        device_proxy
+            .set(DEVICE_INTERFACE, "Autoconnect", false)
+            .map_err(nm_error(WifiConfigError::DisconnectFailed))?;
+    }
+
+    let result: Result<(), dbus::Error> =
+        device_proxy.method_call(DEVICE_INTERFACE, "Disconnect", ());
+    match result {
+        Ok(()) => Ok(DisconnectOutcome::Disconnected),
+        Err(e) if e.name() == Some(NOT_ACTIVE_ERROR) => Ok(DisconnectOutcome::AlreadyDisconnected),
+        Err(e) => Err(WifiConfigError::DisconnectFailed(e)),
+    }
+}
+
+/// Picks the Wi-Fi device to use out of `devices`.
+///
+/// `device_path` takes precedence over `interface`; with neither set, the device is
+/// picked as with [`DeviceSelection::Best`].
+pub(crate) fn select_wifi_device(
+    devices: Vec<WifiDevice>,
+    device_path: Option<&dbus::Path>,
+    interface: Option<&str>,
+) -> Result<WifiDevice, WifiConfigError> {
+    select_wifi_devices(devices, device_path, interface, DeviceSelection::Best)
+        .map(|mut selected| selected.remove(0))
+}
+
+/// Picks the Wi-Fi devices to use out of `devices`, in the order to activate them.
+///
+/// `device_path` and `interface` select one device regardless of its state, as in
+/// [`select_wifi_device`]; otherwise `selection` decides. The result is never empty.
+pub(crate) fn select_wifi_devices(
+    devices: Vec<WifiDevice>,
+    device_path: Option<&dbus::Path>,
+    interface: Option<&str>,
+    selection: DeviceSelection,
+) -> Result<Vec<WifiDevice>, WifiConfigError> {
+    let mut devices = devices.into_iter();
+    let selected: Vec<WifiDevice> = match (device_path, interface, selection) {
+        (Some(path), _, _) => devices
+            .find(|device| &device.path == path)
+            .into_iter()
+            .collect(),
+        (None, Some(name), _) => devices
+            .find(|device| device.interface == name)
+            .into_iter()
+            .collect(),
+        (None, None, DeviceSelection::First) => devices.next().into_iter().collect(),
+        (None, None, DeviceSelection::Best | DeviceSelection::All) => {
+            let mut usable: Vec<WifiDevice> = devices.filter(WifiDevice::is_usable).collect();
+            // The sort is stable, so NetworkManager's order is kept otherwise.
+            usable.sort_by_key(|device| !device.is_settled());
+            if selection == DeviceSelection::Best {
+                usable.truncate(1);
+            }
+            usable
+        }
+    };
+
+    if selected.is_empty() {
+        return Err(match (device_path, interface) {
+            (Some(path), _) => WifiConfigError::DeviceNotFound(path.to_string()),
+            (None, Some(name)) => WifiConfigError::DeviceNotFound(name.to_string()),
+            (None, None) => WifiConfigError::NoWifiDevice,
+        });
+    }
+    Ok(selected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(path: &str, interface: &str) -> WifiDevice {
+        WifiDevice {
+            path: dbus::Path::new(path.to_string()).unwrap(),
+            interface: interface.into(),
+            driver: "iwlwifi".into(),
+            perm_hw_address: "00:11:22:33:44:55".into(),
+            state: DeviceState::Disconnected,
+            managed: true,
+            mtu: 1500,
+            capabilities: DeviceCapabilities::default(),
+        }
+    }
+
+    fn devices() -> Vec<WifiDevice> {
+        vec![
+            device("/org/freedesktop/NetworkManager/Devices/3", "wlan0"),
+            device("/org/freedesktop/NetworkManager/Devices/4", "wlan1"),
+        ]
+    }
+
+    #[test]
+    fn names_states_and_reasons() {
+        assert_eq!(DeviceState::NeedAuth.to_string(), "need-auth");
+        assert_eq!(DeviceState::from(70).to_string(), "ip-config");
+        assert_eq!(
+            DeviceStateReason::from(7).to_string(),
+            "secrets were required but not provided"
+        );
+        assert_eq!(DeviceStateReason::from(99).to_string(), "reason 99");
+    }
+
+    #[test]
+    fn selects_first_device_by_default() {
+        let selected = select_wifi_device(devices(), None, None).unwrap();
+        assert_eq!(selected.interface, "wlan0");
+    }
+
+    #[test]
+    fn selects_device_by_interface_or_path() {
+        let selected = select_wifi_device(devices(), None, Some("wlan1")).unwrap();
+        assert_eq!(selected.interface, "wlan1");
+
+        let path = dbus::Path::new("/org/freedesktop/NetworkManager/Devices/4").unwrap();
+        let selected = select_wifi_device(devices(), Some(&path), Some("wlan0")).unwrap();
+        assert_eq!(selected.interface, "wlan1");
+    }
+
+    #[test]
+    fn skips_unusable_and_busy_devices() {
+        let mut mixed = devices();
+        mixed[0].state = DeviceState::Unavailable;
+        mixed[1].state = DeviceState::Config;
+        mixed.push(device("/org/freedesktop/NetworkManager/Devices/5", "wlan2"));
+        mixed.push(device("/org/freedesktop/NetworkManager/Devices/6", "wlan3"));
+        mixed[3].state = DeviceState::Unmanaged;
+
+        let interfaces = |selection: DeviceSelection| -> Vec<String> {
+            select_wifi_devices(mixed.clone(), None, None, selection)
+                .unwrap()
+                .into_iter()
+                .map(|device| device.interface)
+                .collect()
+        };
+        assert_eq!(interfaces(DeviceSelection::First), ["wlan0"]);
+        assert_eq!(interfaces(DeviceSelection::Best), ["wlan2"]);
+        assert_eq!(interfaces(DeviceSelection::All), ["wlan2", "wlan1"]);
+
+        // Naming a device overrides the policy.
+        let named = select_wifi_devices(mixed, None, Some("wlan0"), DeviceSelection::All);
+        assert_eq!(named.unwrap()[0].interface, "wlan0");
+
+        let mut down = devices();
+        down[0].state = DeviceState::Unavailable;
+        down[1].state = DeviceState::Unmanaged;
+        assert!(matches!(
+            select_wifi_device(down, None, None),
+            Err(WifiConfigError::NoWifiDevice)
+        ));
+    }
+
+    #[test]
+    fn reports_missing_device() {
+        assert!(matches!(
+            select_wifi_device(Vec::new(), None, None),
+            Err(WifiConfigError::NoWifiDevice)
+        ));
+        match select_wifi_device(devices(), None, Some("wlan7")) {
+            Err(WifiConfigError::DeviceNotFound(name)) => assert_eq!(name, "wlan7"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn decodes_device_state() {
+        assert_eq!(DeviceState::from(100), DeviceState::Activated);
+        assert_eq!(DeviceState::from(120), DeviceState::Failed);
+        assert_eq!(DeviceState::from(7), DeviceState::Unknown);
+    }
+
+    #[test]
+    fn decodes_state_reason() {
+        assert!(DeviceStateReason::from(7).is_auth_failure());
+        assert!(DeviceStateReason::from(8).is_auth_failure());
+        assert!(!DeviceStateReason::from(53).is_auth_failure());
+        assert_eq!(DeviceStateReason::from(53), DeviceStateReason::SsidNotFound);
+        assert_eq!(DeviceStateReason::from(999), DeviceStateReason::Other(999));
+    }
+
+    #[test]
+    fn decodes_capabilities() {
+        // CCMP, RSN, AP and both bands, as an iwlwifi adapter reports them.
+        let capabilities = DeviceCapabilities::from(0x768);
+        assert!(capabilities.ccmp && capabilities.rsn && capabilities.ap);
+        assert!(!capabilities.wpa && !capabilities.adhoc && !capabilities.mesh);
+        assert_eq!(capabilities.supports_band(Band::A), Some(true));
+        assert!(!capabilities.band_6ghz);
+
+        // Band bits are meaningless without FREQ_VALID.
+        let unknown = DeviceCapabilities::from(0x640);
+        assert!(unknown.ap && !unknown.band_5ghz);
+        assert_eq!(unknown.supports_band(Band::Bg), None);
+    }
+}
@@ -0,0 +1,311 @@
+use dbus::arg::{ArgType, RefArg};
+use dbus::blocking::Connection;
+use std::collections::BTreeMap;
+
+use crate::client::{DbusNmClient, NmClient};
+use crate::settings::ConnectionSettings;
+use crate::{backend, profiles, security, settings};
+use crate::{ApSecurity, Backend, ConnectOptions, ProfileChange, WifiConfigError};
+
+/// What secrets are replaced with unless they are asked for.
+pub const MASKED_SECRET: &str = "<hidden>";
+
+/// What [`connect`](crate::connect) would send to NetworkManager; see [`dry_run`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DryRun {
+    /// The devices the profile would be activated on, in order. The first one gets
+    /// `AddAndActivateConnection` (or `ActivateConnection` after an update).
+    pub interfaces: Vec<String>,
+    /// [`Created`](ProfileChange::Created) if a new profile would be added,
+    /// [`Updated`](ProfileChange::Updated) if the saved one at `profile_path` would be
+    /// overwritten, and [`Unchanged`](ProfileChange::Unchanged) if it already matches, as
+    /// `connect` compares them.
+    pub change: ProfileChange,
+    /// D-Bus object path of the saved profile that would be updated.
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "crate::nm::serialize_optional_path")
+    )]
+    pub profile_path: Option<dbus::Path<'static>>,
+    /// The type [`Security::Auto`](crate::Security::Auto) detected.
+    pub detected_security: Option<ApSecurity>,
+    /// Every setting that would be sent, by section and key, in sorted order.
+    ///
+    /// A new profile has no `connection.uuid` yet unless one was given or
+    /// [derived](crate::ConnectOptions::stable_uuid); `connect` generates it right before
+    /// sending.
+    pub settings: BTreeMap<String, BTreeMap<String, SettingValue>>,
+}
+
+/// One setting of a [`DryRun`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SettingValue {
+    /// D-Bus type signature the value would be sent with, e.g. `s`, `u` or `aa{sv}`.
+    pub signature: String,
+    /// The value, or [`MASKED_SECRET`] for a masked secret.
+    pub value: DbusValue,
+}
+
+/// A D-Bus value in a [`SettingValue`]. Serializes as the plain JSON value.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(untagged))]
+pub enum DbusValue {
+    /// `b`.
+    Bool(bool),
+    /// `n`, `i` and `x`.
+    Int(i64),
+    /// `y`, `q`, `u` and `t`.
+    Uint(u64),
+    /// `s`, `o` and `g`.
+    String(String),
+    /// Arrays and structs, e.g. `ay` or `as`.
+    Array(Vec<DbusValue>),
+    /// Dictionaries, e.g. `a{sv}`, keyed by their keys' text.
+    Dict(BTreeMap<String, DbusValue>),
+}
+
+impl DbusValue {
+    fn from_ref_arg(value: &dyn RefArg) -> Self {
+        let items = || value.as_iter().into_iter().flatten();
+        match value.arg_type() {
+            ArgType::Boolean => DbusValue::Bool(value.as_u64() == Some(1)),
+            ArgType::Int16 | ArgType::Int32 | ArgType::Int64 => {
+                DbusValue::Int(value.as_i64().unwrap_or_default())
+            }
+            ArgType::Byte | ArgType::UInt16 | ArgType::UInt32 | ArgType::UInt64 => {
+                DbusValue::Uint(value.as_u64().unwrap_or_default())
+            }
+            ArgType::String | ArgType::ObjectPath | ArgType::Signature => {
+                DbusValue::String(value.as_str().unwrap_or_default().to_string())
+            }
+            ArgType::Variant => items()
+                .next()
+                .map_or(DbusValue::Array(Vec::new()), DbusValue::from_ref_arg),
+            ArgType::Array if value.signature().starts_with("a{") => {
+                // A dict iterates as alternating keys and values.
+                let items: Vec<&dyn RefArg> = items().collect();
+                DbusValue::Dict(
+                    items
+                        .chunks(2)
+                        .filter(|pair| pair.len() == 2)
+                        .map(|pair| (key_text(pair[0]), DbusValue::from_ref_arg(pair[1])))
+                        .collect(),
+                )
+            }
+            ArgType::Array | ArgType::Struct => {
+                DbusValue::Array(items().map(DbusValue::from_ref_arg).collect())
+            }
+            _ => DbusValue::String(format!("{:?}", value)),
+        }
+    }
+}
+
+/// The text of a dictionary key, which D-Bus allows to be any basic type.
+fn key_text(key: &dyn RefArg) -> String {
+    match key.as_str() {
+        Some(text) => text.to_string(),
+        None => key
+            .as_i64()
+            .map(|number| number.to_string())
+            .or_else(|| key.as_u64().map(|number| number.to_string()))
+            .unwrap_or_default(),
+    }
+}
+
+/// Shows what [`connect`](crate::connect) would send for `ssid`, without sending it.
+///
+/// Goes through the same steps as `connect` up to the call that saves the profile:
+/// validates `options`, looks up the device, resolves
+/// [`Security::Auto`](crate::Security::Auto) from the cached scan results, builds the
+/// settings and, if a saved profile would be updated, merges them into it. Nothing is
+/// changed, and the radio is not checked.
+///
+/// Secrets (the PSK, WEP key and 802.1X passwords) read [`MASKED_SECRET`] unless
+/// `show_secrets` is set.
+///
+/// # Errors
+///
+/// - The validation errors of [`connect`](crate::connect).
+/// - [`WifiConfigError::UnsupportedByBackend`] with a backend other than
+///   [`Backend::NetworkManager`].
+/// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+/// - [`WifiConfigError::NoWifiDevice`] / [`WifiConfigError::DeviceNotFound`] if there is
+///   no device to use.
+///
+/// # Example
+///
+/// ```no_run
+/// use wifi_config::{ConnectOptions, Security};
+///
+/// let options = ConnectOptions::builder()
+///     .security(Security::WpaPsk("supersecret123".into()))
+///     .build();
+/// let dry_run = wifi_config::dry_run("MyHomeWiFi", &options, false)?;
+/// for (section, values) in &dry_run.settings {
+///     for (key, value) in values {
+///         println!("{}.{} ({}) = {:?}", section, key, value.signature, value.value);
+///     }
+/// }
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn dry_run(
+    ssid: impl AsRef<[u8]>,
+    options: &ConnectOptions,
+    show_secrets: bool,
+) -> Result<DryRun, WifiConfigError> {
+    let ssid = ssid.as_ref();
+    options.validate(ssid)?;
+    if options.backend != Backend::NetworkManager {
+        return Err(WifiConfigError::UnsupportedByBackend(
+            "dry runs need NetworkManager".into(),
+        ));
+    }
+    let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+    let client = DbusNmClient::new(&conn, options.timeouts).with_retry(options.retry);
+    dry_run_with(&client, ssid, options, show_secrets)
+}
+
+fn dry_run_with(
+    client: &dyn NmClient,
+    ssid: &[u8],
+    options: &ConnectOptions,
+    show_secrets: bool,
+) -> Result<DryRun, WifiConfigError> {
+    let devices = backend::find_devices(client, options)?;
+    let (options, detected_security) =
+        backend::resolve_security(client, &devices[0].path, ssid, options)?;
+    let new = settings::build_connection_settings(ssid, &options);
+
+    let saved = if options.allow_duplicates {
+        None
+    } else {
+        profiles::find_saved_connection(client, ssid, options.profile_uuid(ssid).as_deref())?
+    };
+    let (change, profile_path, sent) = match saved {
+        Some((path, existing)) => {
+            let rename = options.connection_id.is_some();
+            let (merged, change) = profiles::merge_update(client, &path, existing, new, rename);
+            (change, Some(path), merged)
+        }
+        None => (ProfileChange::Created, None, new),
+    };
+    Ok(DryRun {
+        interfaces: devices.into_iter().map(|device| device.interface).collect(),
+        change,
+        profile_path,
+        detected_security,
+        settings: render(&sent, show_secrets),
+    })
+}
+
+/// Converts `connection_settings` into sorted sections, masking the secrets unless
+/// `show_secrets` is set.
+pub(crate) fn render(
+    connection_settings: &ConnectionSettings,
+    show_secrets: bool,
+) -> BTreeMap<String, BTreeMap<String, SettingValue>> {
+    connection_settings
+        .iter()
+        .map(|(name, section)| {
+            let values = section
+                .iter()
+                .map(|(key, value)| {
+                    let is_secret = security::SECRETS
+                        .iter()
+                        .any(|(section, secret, _)| section == name && secret == key);
+                    let value = SettingValue {
+                        signature: value.0.signature().to_string(),
+                        value: if is_secret && !show_secrets {
+                            DbusValue::String(MASKED_SECRET.into())
+                        } else {
+                            DbusValue::from_ref_arg(&*value.0)
+                        },
+                    };
+                    (key.clone(), value)
+                })
+                .collect();
+            (name.clone(), values)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::{MockDevice, MockNmClient};
+    use crate::{Ipv4Config, PowerSave, Security};
+
+    fn psk_options() -> ConnectOptions {
+        ConnectOptions {
+            security: Security::WpaPsk("supersecret123".into()),
+            powersave: Some(PowerSave::Disable),
+            ipv4: Ipv4Config::Manual {
+                address: [192, 168, 1, 50].into(),
+                prefix: 24,
+                gateway: None,
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn renders_the_settings_with_their_signatures_without_sending_them() {
+        let client = MockNmClient::with_devices(vec![MockDevice::new(1, "wlan0", 2)]);
+        let dry_run = dry_run_with(&client, b"Office", &psk_options(), false).unwrap();
+        assert_eq!(dry_run.interfaces, ["wlan0"]);
+        assert_eq!(dry_run.change, ProfileChange::Created);
+
+        let wifi = &dry_run.settings["802-11-wireless"];
+        assert_eq!(wifi["ssid"].signature, "ay");
+        assert_eq!(
+            wifi["ssid"].value,
+            DbusValue::Array(
+                b"Office"
+                    .iter()
+                    .map(|&b| DbusValue::Uint(b.into()))
+                    .collect()
+            )
+        );
+        assert_eq!(wifi["powersave"].signature, "u");
+        assert_eq!(wifi["powersave"].value, DbusValue::Uint(2));
+
+        let address = &dry_run.settings["ipv4"]["address-data"];
+        assert_eq!(address.signature, "aa{sv}");
+        #[cfg(feature = "serde")]
+        assert_eq!(
+            serde_json::to_value(&address.value).unwrap(),
+            serde_json::json!([{ "address": "192.168.1.50", "prefix": 24 }])
+        );
+
+        let psk = &dry_run.settings["802-11-wireless-security"]["psk"];
+        assert_eq!(psk.value, DbusValue::String(MASKED_SECRET.into()));
+        assert!(!client
+            .methods()
+            .iter()
+            .any(|method| method.starts_with("Add")));
+    }
+
+    #[test]
+    fn shows_secrets_and_merges_into_saved_profiles_on_request() {
+        let client = MockNmClient::with_devices(vec![MockDevice::new(1, "wlan0", 2)]);
+        let mut saved = settings::build_connection_settings(b"Office", &psk_options());
+        saved.entry("proxy".into()).or_default();
+        let path = client.save(saved, "0b7cb1a4-5f7c-4d36-9d0e-6a1f3c2e8b90");
+
+        let dry_run = dry_run_with(&client, b"Office", &psk_options(), true).unwrap();
+        assert_eq!(dry_run.change, ProfileChange::Unchanged);
+        assert_eq!(dry_run.profile_path, Some(path));
+        assert!(dry_run.settings.contains_key("proxy"));
+        assert_eq!(
+            dry_run.settings["connection"]["uuid"].value,
+            DbusValue::String("0b7cb1a4-5f7c-4d36-9d0e-6a1f3c2e8b90".into())
+        );
+        assert_eq!(
+            dry_run.settings["802-11-wireless-security"]["psk"].value,
+            DbusValue::String("supersecret123".into())
+        );
+        assert!(!client.methods().iter().any(|method| method == "Update"));
+    }
+}
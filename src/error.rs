@@ -0,0 +1,564 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::{CandidateFailure, DeviceStateReason, NmVersion, Rollback};
+
+/// D-Bus errors meaning nothing owns NetworkManager's bus name.
+const SERVICE_MISSING_ERRORS: &[&str] = &[
+    "org.freedesktop.DBus.Error.ServiceUnknown",
+    "org.freedesktop.DBus.Error.NameHasNoOwner",
+];
+/// D-Bus errors meaning NetworkManager didn't answer within the call's timeout.
+const TIMEOUT_ERRORS: &[&str] = &[
+    "org.freedesktop.DBus.Error.NoReply",
+    "org.freedesktop.DBus.Error.Timeout",
+    "org.freedesktop.DBus.Error.TimedOut",
+];
+/// NetworkManager's errors for a caller polkit doesn't authorize, from the manager, the
+/// settings service and the devices.
+const PERMISSION_DENIED_ERRORS: &[&str] = &[
+    "org.freedesktop.NetworkManager.PermissionDenied",
+    "org.freedesktop.NetworkManager.Settings.PermissionDenied",
+    "org.freedesktop.NetworkManager.Device.PermissionDenied",
+];
+/// NetworkManager's errors for settings it rejects (`NMConnectionError`).
+const INVALID_SETTINGS_ERRORS: &[&str] = &[
+    "org.freedesktop.NetworkManager.Settings.Connection.InvalidProperty",
+    "org.freedesktop.NetworkManager.Settings.Connection.MissingProperty",
+    "org.freedesktop.NetworkManager.Settings.Connection.InvalidSetting",
+    "org.freedesktop.NetworkManager.Settings.Connection.MissingSetting",
+];
+/// NetworkManager's error for adding a profile whose UUID another one already has.
+const PROFILE_EXISTS_ERRORS: &[&str] = &["org.freedesktop.NetworkManager.Settings.UuidExists"];
+
+/// Errors returned by the Wi-Fi configuration functions.
+#[derive(Debug)]
+pub enum WifiConfigError {
+    /// The SSID is empty or longer than 32 bytes; carries the reason.
+    InvalidSsid(String),
+    /// An empty password was given; open networks must be requested explicitly.
+    EmptyPassword,
+    /// The WPA-PSK passphrase is not 8 to 63 printable ASCII characters, nor a raw key of
+    /// 64 hex digits.
+    InvalidPsk {
+        /// What is wrong with the passphrase.
+        reason: String,
+    },
+    /// The enterprise (802.1X) credentials are incomplete; carries the reason.
+    InvalidEnterpriseAuth(String),
+    /// A certificate or key file given for EAP-TLS does not exist.
+    CertificateNotFound(PathBuf),
+    /// The WEP key has an invalid length or characters; carries the reason.
+    InvalidWepKey(String),
+    /// The static IP configuration is invalid; carries the reason.
+    InvalidIpConfig(String),
+    /// The wireless mode, band or channel is invalid or doesn't fit the security scheme;
+    /// carries the reason.
+    InvalidWirelessConfig(String),
+    /// A MAC address (e.g. a BSSID) is malformed; carries the rejected input.
+    InvalidMacAddress(String),
+    /// The hotspot password, band or channel is invalid; carries the reason.
+    InvalidHotspotConfig(String),
+    /// A Wi-Fi QR code payload is malformed; carries the reason.
+    InvalidQrCode(String),
+    /// A profile UUID isn't in the RFC 4122 form `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`;
+    /// carries the rejected input.
+    InvalidUuid(String),
+    /// A user name in [`ConnectOptions::permissions`](crate::ConnectOptions::permissions)
+    /// is empty or contains a colon; carries the rejected name.
+    InvalidUser(String),
+    /// A config file couldn't be read.
+    ConfigFileRead(PathBuf, std::io::Error),
+    /// A config file isn't valid TOML, has unknown keys, or an entry is inconsistent;
+    /// carries the reason.
+    InvalidConfigFile(String),
+    /// The keyring has no password for the SSID, which is carried (lossily decoded).
+    SecretNotFound(String),
+    /// Talking to the keyring (the Secret Service on the session bus) failed, or its
+    /// unlock prompt was dismissed.
+    KeyringFailed(dbus::Error),
+    /// The selected [`Backend`](crate::Backend) can't apply one of the options; carries
+    /// the reason.
+    UnsupportedByBackend(String),
+    /// The running NetworkManager is too old for one of the options; carries what needs
+    /// which version and the version running.
+    UnsupportedByNetworkManager {
+        /// The feature, e.g. `WPA3-SAE`.
+        feature: String,
+        /// The first version that has it.
+        needs: NmVersion,
+        /// The version running.
+        have: NmVersion,
+    },
+    /// A keyfile couldn't be written, or its mode would let other users read it.
+    KeyfileWrite(PathBuf, std::io::Error),
+    /// A keyfile isn't a valid NetworkManager Wi-Fi keyfile; carries the reason.
+    InvalidKeyfile(String),
+    /// Could not open a connection to D-Bus: the system bus, or the one picked with
+    /// [`WifiManager::with_bus`](crate::WifiManager::with_bus).
+    DbusConnection(dbus::Error),
+    /// NetworkManager isn't running (or not installed): nothing owns its bus name.
+    NetworkManagerUnavailable(dbus::Error),
+    /// NetworkManager refused the call because polkit doesn't authorize the caller, e.g.
+    /// outside an active desktop session; run as root or grant the permission.
+    PermissionDenied(dbus::Error),
+    /// NetworkManager rejected a setting of the profile.
+    InvalidSettings {
+        /// The rejected setting as `section.key` (e.g. `802-11-wireless-security.psk`),
+        /// or the section alone, as NetworkManager names it; empty if it names none.
+        property: String,
+        /// NetworkManager's error.
+        error: dbus::Error,
+    },
+    /// NetworkManager refused to add a profile because one with the same UUID exists,
+    /// e.g. one another tool just saved or one the caller can't list.
+    ProfileExists(dbus::Error),
+    /// NetworkManager didn't answer a call within its timeout; see [`DbusTimeouts`](crate::DbusTimeouts).
+    Timeout(dbus::Error),
+    /// Wi-Fi is switched off in software, e.g. from the desktop's network menu or by
+    /// airplane mode; [`ConnectOptions::enable_radio`](crate::ConnectOptions::enable_radio)
+    /// turns it back on.
+    RadioDisabled,
+    /// The Wi-Fi device (named by its interface) is blocked by a hardware switch or key
+    /// (rfkill), which only the user can undo.
+    HardwareRfkill(String),
+    /// NetworkManager did not report any (usable, see
+    /// [`DeviceSelection`](crate::DeviceSelection)) Wi-Fi device.
+    NoWifiDevice,
+    /// No Wi-Fi device with the requested interface name exists.
+    DeviceNotFound(String),
+    /// The SSID (carried lossily decoded) isn't in the scan results: with
+    /// [`Security::Auto`](crate::Security::Auto) without a password there is nothing to
+    /// detect from, and [`connect_first_available`](crate::connect_first_available) skips
+    /// the network.
+    NetworkNotFound(String),
+    /// The network's strongest access point is weaker than
+    /// [`ConnectOptions::min_strength`](crate::ConnectOptions::min_strength); nothing was
+    /// changed.
+    SignalTooWeak {
+        /// Signal strength (0–100) of the strongest access point.
+        found: u8,
+        /// The required strength.
+        required: u8,
+    },
+    /// No saved profile has the SSID or UUID given to
+    /// [`activate_saved`](crate::activate_saved), which is carried (lossily decoded).
+    ProfileNotFound(String),
+    /// The Wi-Fi device (named by its interface) doesn't support access point mode.
+    HotspotUnsupported(String),
+    /// [`connect_wps_pbc`](crate::connect_wps_pbc) without an SSID, and no access point is
+    /// in WPS push-button mode.
+    WpsNotActive,
+    /// [`reconnect`](crate::reconnect) without an SSID, and the Wi-Fi device (named by
+    /// its interface) has no active connection.
+    NothingToReconnect(String),
+    /// Querying NetworkManager for its devices (or their properties) failed.
+    DeviceQueryFailed(dbus::Error),
+    /// Listing access points or reading their properties failed.
+    ScanFailed(dbus::Error),
+    /// Listing saved connection profiles or reading their settings failed.
+    SettingsQueryFailed(dbus::Error),
+    /// NetworkManager refused to delete a saved connection profile.
+    DeleteFailed(dbus::Error),
+    /// Disconnecting the Wi-Fi device (or disabling its autoconnect) failed.
+    DisconnectFailed(dbus::Error),
+    /// NetworkManager refused to deactivate an active connection.
+    DeactivateFailed(dbus::Error),
+    /// Some of the profiles a [`reset_wifi_profiles`](crate::reset_wifi_profiles) went for
+    /// couldn't be deleted; carries their ids. See
+    /// [`ResetReport::failed`](crate::ResetReport::failed) for why.
+    ResetIncomplete(Vec<String>),
+    /// NetworkManager refused `AddAndActivateConnection`; carries NM's rejection reason.
+    ActivationFailed(String),
+    /// The activation started but the network rejected the credentials.
+    AuthenticationFailed(DeviceStateReason),
+    /// The activation started but failed for a reason other than the credentials.
+    ConnectionFailed(DeviceStateReason),
+    /// The activation did not complete within the given time.
+    ActivationTimeout(Duration),
+    /// [`connect_first_available`](crate::connect_first_available) connected to none of
+    /// the networks; carries why, for each of them.
+    NoNetworkConnected(Vec<CandidateFailure>),
+    /// [`connect_and_wait`](crate::connect_and_wait) failed with `error` and undid the
+    /// attempt as `rollback` says; see
+    /// [`ConnectOptions::keep_on_failure`](crate::ConnectOptions::keep_on_failure).
+    RolledBack {
+        /// Why the activation failed.
+        error: Box<WifiConfigError>,
+        /// What was undone.
+        rollback: Rollback,
+    },
+}
+
+impl WifiConfigError {
+    /// The D-Bus error behind this error, with its name (e.g.
+    /// `org.freedesktop.NetworkManager.PermissionDenied`) and message.
+    ///
+    /// `None` for the errors found before or without a D-Bus call, and for
+    /// [`ActivationFailed`](Self::ActivationFailed), which keeps them in its text.
+    pub fn dbus_error(&self) -> Option<&dbus::Error> {
+        match self {
+            WifiConfigError::DbusConnection(e)
+            | WifiConfigError::NetworkManagerUnavailable(e)
+            | WifiConfigError::PermissionDenied(e)
+            | WifiConfigError::InvalidSettings { error: e, .. }
+            | WifiConfigError::ProfileExists(e)
+            | WifiConfigError::Timeout(e)
+            | WifiConfigError::DeviceQueryFailed(e)
+            | WifiConfigError::ScanFailed(e)
+            | WifiConfigError::SettingsQueryFailed(e)
+            | WifiConfigError::DeleteFailed(e)
+            | WifiConfigError::DisconnectFailed(e)
+            | WifiConfigError::DeactivateFailed(e)
+            | WifiConfigError::KeyringFailed(e) => Some(e),
+            WifiConfigError::RolledBack { error, .. } => error.dbus_error(),
+            _ => None,
+        }
+    }
+
+    /// Wraps a failed `AddAndActivateConnection`, keeping NM's message and error name.
+    pub(crate) fn activation_failed(e: dbus::Error) -> Self {
+        let reason = match (e.message(), e.name()) {
+            (Some(message), Some(name)) => format!("{} ({})", message, name),
+            (Some(message), None) => message.to_string(),
+            (None, Some(name)) => name.to_string(),
+            (None, None) => String::from("unknown error"),
+        };
+        WifiConfigError::ActivationFailed(reason)
+    }
+}
+
+/// Maps the error of a call to NetworkManager by its D-Bus name, and with `wrap` if the
+/// name says nothing more specific than that the call failed.
+///
+/// - Nothing owns the bus name: [`WifiConfigError::NetworkManagerUnavailable`].
+/// - polkit doesn't authorize the caller: [`WifiConfigError::PermissionDenied`].
+/// - A setting was rejected: [`WifiConfigError::InvalidSettings`].
+/// - The UUID is taken: [`WifiConfigError::ProfileExists`].
+/// - No answer in time: [`WifiConfigError::Timeout`].
+pub(crate) fn nm_error(
+    wrap: fn(dbus::Error) -> WifiConfigError,
+) -> impl Fn(dbus::Error) -> WifiConfigError {
+    move |e| {
+        let name = e.name().unwrap_or_default();
+        if SERVICE_MISSING_ERRORS.contains(&name) {
+            WifiConfigError::NetworkManagerUnavailable(e)
+        } else if PERMISSION_DENIED_ERRORS.contains(&name) {
+            WifiConfigError::PermissionDenied(e)
+        } else if INVALID_SETTINGS_ERRORS.contains(&name) {
+            WifiConfigError::InvalidSettings {
+                property: invalid_property(e.message().unwrap_or_default()),
+                error: e,
+            }
+        } else if PROFILE_EXISTS_ERRORS.contains(&name) {
+            WifiConfigError::ProfileExists(e)
+        } else if TIMEOUT_ERRORS.contains(&name) {
+            WifiConfigError::Timeout(e)
+        } else {
+            wrap(e)
+        }
+    }
+}
+
+/// The setting named by an `NMConnectionError` message, which NetworkManager prefixes
+/// with it: `802-11-wireless-security.psk: property is invalid`.
+fn invalid_property(message: &str) -> String {
+    match message.split_once(": ") {
+        Some((property, _)) if !property.contains(' ') => property.to_string(),
+        _ => String::new(),
+    }
+}
+
+impl fmt::Display for WifiConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WifiConfigError::InvalidSsid(reason) => write!(f, "invalid SSID: {}", reason),
+            WifiConfigError::EmptyPassword => write!(
+                f,
+                "password must not be empty; connect without a password for open networks"
+            ),
+            WifiConfigError::InvalidPsk { reason } => {
+                write!(f, "invalid WPA passphrase: {}", reason)
+            }
+            WifiConfigError::InvalidEnterpriseAuth(reason) => {
+                write!(f, "invalid enterprise credentials: {}", reason)
+            }
+            WifiConfigError::CertificateNotFound(path) => {
+                write!(f, "certificate or key file not found: {}", path.display())
+            }
+            WifiConfigError::InvalidWepKey(reason) => write!(f, "invalid WEP key: {}", reason),
+            WifiConfigError::InvalidIpConfig(reason) => {
+                write!(f, "invalid IP configuration: {}", reason)
+            }
+            WifiConfigError::InvalidWirelessConfig(reason) => {
+                write!(f, "invalid wireless configuration: {}", reason)
+            }
+            WifiConfigError::InvalidMacAddress(input) => write!(
+                f,
+                "invalid MAC address '{}'; expected the form AA:BB:CC:DD:EE:FF",
+                input
+            ),
+            WifiConfigError::InvalidHotspotConfig(reason) => {
+                write!(f, "invalid hotspot configuration: {}", reason)
+            }
+            WifiConfigError::InvalidQrCode(reason) => {
+                write!(f, "invalid Wi-Fi QR code: {}", reason)
+            }
+            WifiConfigError::InvalidUuid(input) => write!(
+                f,
+                "invalid UUID '{}'; expected the form xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx",
+                input
+            ),
+            WifiConfigError::InvalidUser(user) => write!(
+                f,
+                "invalid user name '{}' for the profile permissions; it must not be empty \
+                 or contain ':'",
+                user
+            ),
+            WifiConfigError::ConfigFileRead(path, e) => {
+                write!(f, "failed to read {}: {}", path.display(), e)
+            }
+            WifiConfigError::InvalidConfigFile(reason) => {
+                write!(f, "invalid config file: {}", reason)
+            }
+            WifiConfigError::SecretNotFound(ssid) => {
+                write!(f, "no password for '{}' in the keyring", ssid)
+            }
+            WifiConfigError::KeyringFailed(e) => write!(f, "keyring access failed: {}", e),
+            WifiConfigError::UnsupportedByBackend(reason) => write!(f, "{}", reason),
+            WifiConfigError::UnsupportedByNetworkManager {
+                feature,
+                needs,
+                have,
+            } => write!(
+                f,
+                "{} needs NetworkManager {} or later; this one is {}",
+                feature, needs, have
+            ),
+            WifiConfigError::KeyfileWrite(path, e) => {
+                write!(f, "failed to write {}: {}", path.display(), e)
+            }
+            WifiConfigError::InvalidKeyfile(reason) => write!(f, "invalid keyfile: {}", reason),
+            WifiConfigError::DbusConnection(e) => {
+                write!(f, "failed to connect to D-Bus: {}", e)
+            }
+            WifiConfigError::NetworkManagerUnavailable(_) => write!(
+                f,
+                "NetworkManager is not running; start it with 'systemctl start NetworkManager'"
+            ),
+            WifiConfigError::PermissionDenied(e) => write!(
+                f,
+                "NetworkManager denied the request ({}); run as root or grant the polkit \
+                 permission",
+                e.message().unwrap_or("not authorized")
+            ),
+            WifiConfigError::InvalidSettings { error, .. } => write!(
+                f,
+                "NetworkManager rejected the settings: {}",
+                error.message().unwrap_or("invalid setting")
+            ),
+            WifiConfigError::ProfileExists(e) => write!(
+                f,
+                "a profile with the same UUID already exists: {}",
+                e.message().unwrap_or("UUID exists")
+            ),
+            WifiConfigError::Timeout(e) => write!(
+                f,
+                "NetworkManager did not answer in time: {}",
+                e.message().unwrap_or("no reply")
+            ),
+            WifiConfigError::RadioDisabled => write!(
+                f,
+                "Wi-Fi is turned off; enable it with 'nmcli radio wifi on'"
+            ),
+            WifiConfigError::HardwareRfkill(interface) => write!(
+                f,
+                "Wi-Fi device '{}' is blocked by a hardware switch (rfkill); flip the Wi-Fi \
+                 switch or key to turn it on",
+                interface
+            ),
+            WifiConfigError::NoWifiDevice => write!(f, "Wi-Fi device not found"),
+            WifiConfigError::DeviceNotFound(interface) => {
+                write!(f, "Wi-Fi device '{}' not found", interface)
+            }
+            WifiConfigError::ProfileNotFound(name) => {
+                write!(f, "no saved profile for '{}'", name)
+            }
+            WifiConfigError::NetworkNotFound(ssid) => {
+                write!(f, "network '{}' is not in range", ssid)
+            }
+            WifiConfigError::SignalTooWeak { found, required } => write!(
+                f,
+                "the signal is too weak: {}%, at least {}% is required",
+                found, required
+            ),
+            WifiConfigError::WpsNotActive => write!(
+                f,
+                "no access point is in WPS push-button mode; press the WPS button first"
+            ),
+            WifiConfigError::NothingToReconnect(interface) => write!(
+                f,
+                "nothing to reconnect: {} has no active connection",
+                interface
+            ),
+            WifiConfigError::HotspotUnsupported(interface) => {
+                write!(
+                    f,
+                    "Wi-Fi device '{}' does not support hotspot mode",
+                    interface
+                )
+            }
+            WifiConfigError::DeviceQueryFailed(e) => {
+                write!(f, "failed to query NetworkManager devices: {}", e)
+            }
+            WifiConfigError::ScanFailed(e) => write!(f, "failed to scan for networks: {}", e),
+            WifiConfigError::SettingsQueryFailed(e) => {
+                write!(f, "failed to query saved connections: {}", e)
+            }
+            WifiConfigError::DeleteFailed(e) => {
+                write!(f, "failed to delete saved connection: {}", e)
+            }
+            WifiConfigError::DisconnectFailed(e) => {
+                write!(f, "failed to disconnect the Wi-Fi device: {}", e)
+            }
+            WifiConfigError::DeactivateFailed(e) => {
+                write!(f, "failed to deactivate the connection: {}", e)
+            }
+            WifiConfigError::ResetIncomplete(ids) => write!(
+                f,
+                "failed to delete {} saved Wi-Fi profile(s): {}",
+                ids.len(),
+                ids.join(", ")
+            ),
+            WifiConfigError::ActivationFailed(reason) => {
+                write!(f, "NetworkManager rejected the connection: {}", reason)
+            }
+            WifiConfigError::AuthenticationFailed(reason) => {
+                write!(
+                    f,
+                    "authentication failed, check the password ({:?})",
+                    reason
+                )
+            }
+            WifiConfigError::ConnectionFailed(reason) => {
+                write!(f, "connection failed ({:?})", reason)
+            }
+            WifiConfigError::ActivationTimeout(timeout) => write!(
+                f,
+                "connection was not activated within {} seconds",
+                timeout.as_secs()
+            ),
+            WifiConfigError::NoNetworkConnected(failures) if failures.is_empty() => {
+                write!(f, "no networks to connect to")
+            }
+            WifiConfigError::NoNetworkConnected(failures) => {
+                write!(f, "none of the networks could be connected: ")?;
+                for (i, failure) in failures.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{}", failure)?;
+                }
+                Ok(())
+            }
+            WifiConfigError::RolledBack { error, rollback } => {
+                write!(f, "{}", error)?;
+                if rollback.profile_deleted {
+                    write!(f, "; the new profile was removed")?;
+                }
+                if rollback.profile_restored {
+                    write!(f, "; the profile's previous settings were restored")?;
+                }
+                match (&rollback.previous_profile, rollback.restored) {
+                    (Some(_), true) => write!(f, "; the previous connection was re-activated"),
+                    (Some(_), false) => write!(f, "; the previous connection couldn't be restored"),
+                    (None, _) => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for WifiConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WifiConfigError::ConfigFileRead(_, e) | WifiConfigError::KeyfileWrite(_, e) => Some(e),
+            WifiConfigError::RolledBack { error, .. } => Some(&**error),
+            _ => self.dbus_error().map(|e| e as _),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_network_manager_gets_its_own_error() {
+        let missing = dbus::Error::new_custom(
+            "org.freedesktop.DBus.Error.ServiceUnknown",
+            "The name org.freedesktop.NetworkManager was not provided by any .service files",
+        );
+        assert!(matches!(
+            nm_error(WifiConfigError::DeviceQueryFailed)(missing),
+            WifiConfigError::NetworkManagerUnavailable(_)
+        ));
+
+        let denied = dbus::Error::new_custom("org.freedesktop.DBus.Error.AccessDenied", "denied");
+        assert!(matches!(
+            nm_error(WifiConfigError::DeviceQueryFailed)(denied),
+            WifiConfigError::DeviceQueryFailed(_)
+        ));
+    }
+
+    #[test]
+    fn classifies_errors_by_their_dbus_name() {
+        let classify = |name: &str, message: &str| {
+            nm_error(WifiConfigError::activation_failed)(dbus::Error::new_custom(name, message))
+        };
+
+        let denied = classify(
+            "org.freedesktop.NetworkManager.PermissionDenied",
+            "Insufficient privileges",
+        );
+        assert!(matches!(denied, WifiConfigError::PermissionDenied(_)));
+        assert_eq!(
+            denied.dbus_error().and_then(|e| e.message()),
+            Some("Insufficient privileges")
+        );
+
+        let invalid = classify(
+            "org.freedesktop.NetworkManager.Settings.Connection.InvalidProperty",
+            "802-11-wireless-security.psk: property is invalid",
+        );
+        let WifiConfigError::InvalidSettings { property, error } = &invalid else {
+            panic!("expected InvalidSettings, got {:?}", invalid);
+        };
+        assert_eq!(property, "802-11-wireless-security.psk");
+        assert_eq!(
+            error.name(),
+            Some("org.freedesktop.NetworkManager.Settings.Connection.InvalidProperty")
+        );
+        assert_eq!(invalid_property("invalid connection"), "");
+
+        assert!(matches!(
+            classify(
+                "org.freedesktop.DBus.Error.NoReply",
+                "Did not receive a reply"
+            ),
+            WifiConfigError::Timeout(_)
+        ));
+        assert!(matches!(
+            classify(
+                "org.freedesktop.NetworkManager.Settings.UuidExists",
+                "A connection with this UUID already exists"
+            ),
+            WifiConfigError::ProfileExists(_)
+        ));
+        assert!(matches!(
+            classify("org.freedesktop.NetworkManager.UnknownConnection", "no such profile"),
+            WifiConfigError::ActivationFailed(reason) if reason.contains("UnknownConnection")
+        ));
+    }
+}
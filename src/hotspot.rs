@@ -0,0 +1,299 @@
+use dbus::arg::{PropMap, Variant};
+use dbus::blocking::Connection;
+
+use crate::client::DbusNmClient;
+use crate::device;
+use crate::settings::{self, ConnectionSettings};
+use crate::version;
+use crate::wireless;
+use crate::{
+    ActivationHandle, Band, CipherPolicy, DbusTimeouts, PowerSave, ProfileChange, Security,
+    WifiConfigError, WifiDevice, WirelessMode,
+};
+
+/// Options for [`create_hotspot`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HotspotOptions {
+    /// Frequency band. `None` lets NetworkManager choose.
+    pub band: Option<Band>,
+    /// Channel within [`band`](Self::band), which must then be set as well.
+    pub channel: Option<u32>,
+    /// Interface name of the Wi-Fi device to use; defaults to the first usable
+    /// Wi-Fi device, as with [`DeviceSelection::Best`](crate::DeviceSelection::Best).
+    pub interface: Option<String>,
+    /// firewalld zone of the hotspot connection (`connection.zone`).
+    pub zone: Option<String>,
+    /// Wi-Fi power saving while the hotspot is up. `None` keeps NetworkManager's default.
+    pub powersave: Option<PowerSave>,
+    /// MTU of the hotspot (`802-11-wireless.mtu`), as with
+    /// [`ConnectOptions::mtu`](crate::ConnectOptions::mtu).
+    pub mtu: Option<u32>,
+    /// Timeouts of the D-Bus calls made to NetworkManager.
+    pub timeouts: DbusTimeouts,
+}
+
+impl HotspotOptions {
+    /// Checks the options and password client-side.
+    fn validate(&self, password: &str) -> Result<(), WifiConfigError> {
+        if !(8..=63).contains(&password.len()) {
+            return Err(WifiConfigError::InvalidHotspotConfig(
+                "WPA-PSK passwords must be 8 to 63 characters long".into(),
+            ));
+        }
+        wireless::validate_band_channel(self.band, self.channel)
+            .and_then(|()| wireless::validate_mtu(self.mtu))
+            .map_err(WifiConfigError::InvalidHotspotConfig)
+    }
+}
+
+/// Builds the settings of a WPA2 access point sharing the host's connection over NAT.
+fn build_hotspot_settings(
+    ssid: &str,
+    password: &str,
+    options: &HotspotOptions,
+) -> ConnectionSettings {
+    let mut connection_settings = ConnectionSettings::new();
+
+    // Profile settings; a temporary AP should never come up on its own.
+    let mut profile_settings = PropMap::new();
+    profile_settings.insert("id".into(), Variant(Box::new(ssid.to_string())));
+    profile_settings.insert(
+        "type".into(),
+        Variant(Box::new(String::from("802-11-wireless"))),
+    );
+    profile_settings.insert("autoconnect".into(), Variant(Box::new(false)));
+    if let Some(zone) = &options.zone {
+        profile_settings.insert("zone".into(), Variant(Box::new(zone.clone())));
+    }
+    connection_settings.insert("connection".into(), profile_settings);
+
+    // Wi-Fi settings
+    let mut wifi_settings = PropMap::new();
+    wifi_settings.insert("ssid".into(), Variant(Box::new(ssid.as_bytes().to_vec())));
+    wifi_settings.insert(
+        "mode".into(),
+        Variant(Box::new(WirelessMode::Ap.as_nm().to_string())),
+    );
+    wireless::apply_band_channel(options.band, options.channel, &mut wifi_settings);
+    if let Some(powersave) = options.powersave {
+        wifi_settings.insert("powersave".into(), Variant(Box::new(powersave.to_nm())));
+    }
+    if let Some(mtu) = options.mtu {
+        wifi_settings.insert("mtu".into(), Variant(Box::new(mtu)));
+    }
+    connection_settings.insert("802-11-wireless".into(), wifi_settings);
+
+    // Wi-Fi security settings, restricted to WPA2/CCMP so clients don't fall back to TKIP.
+    Security::WpaPsk(password.into()).apply(&mut connection_settings);
+    CipherPolicy::Wpa2Only.apply(&mut connection_settings);
+
+    // IP settings: NetworkManager runs DHCP and NAT for the clients.
+    let mut ipv4_settings = PropMap::new();
+    ipv4_settings.insert("method".into(), Variant(Box::new(String::from("shared"))));
+    connection_settings.insert("ipv4".into(), ipv4_settings);
+    let mut ipv6_settings = PropMap::new();
+    ipv6_settings.insert("method".into(), Variant(Box::new(String::from("ignore"))));
+    connection_settings.insert("ipv6".into(), ipv6_settings);
+
+    connection_settings
+}
+
+/// Fails early if `device` can't run the hotspot `options` ask for, rather than letting
+/// NetworkManager time out.
+fn check_capabilities(
+    device: &WifiDevice,
+    options: &HotspotOptions,
+) -> Result<(), WifiConfigError> {
+    if !device.capabilities.ap {
+        return Err(WifiConfigError::HotspotUnsupported(
+            device.interface.clone(),
+        ));
+    }
+    match options.band {
+        Some(band) if device.capabilities.supports_band(band) == Some(false) => {
+            Err(WifiConfigError::InvalidHotspotConfig(format!(
+                "Wi-Fi device '{}' does not support the {} band",
+                device.interface,
+                match band {
+                    Band::Bg => "2.4 GHz",
+                    Band::A => "5 GHz",
+                }
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Brings up a WPA2 access point on the Wi-Fi device.
+///
+/// # Behavior
+///
+/// - Validates the password (8–63 characters) and band/channel client-side.
+/// - Locates the Wi-Fi device given by `options.interface`, or the first usable one, and checks
+///   its [capabilities](crate::DeviceCapabilities) for AP mode and `options.band`.
+/// - Builds `802-11-wireless` with `mode: "ap"` (plus `band`/`channel` if given),
+///   WPA2-PSK security and `ipv4.method: "shared"`, so NetworkManager runs DHCP and NAT
+///   for the clients.
+/// - Calls `AddAndActivateConnection`. The profile has autoconnect disabled.
+///
+/// Returns an [`ActivationHandle`] right away, before the access point is up; get the
+/// address it serves its clients on with [`ip_info`](crate::ip_info). Stop the hotspot
+/// with [`deactivate`](crate::deactivate) and remove its profile with
+/// [`delete`](crate::delete).
+///
+/// # Errors
+///
+/// - [`WifiConfigError::InvalidHotspotConfig`] if the password, band or channel is invalid,
+///   or the device doesn't support the band.
+/// - [`WifiConfigError::HotspotUnsupported`] if the device can't act as an access point.
+/// - [`WifiConfigError::DeviceNotFound`] / [`WifiConfigError::NoWifiDevice`] if the
+///   Wi-Fi device can't be found.
+/// - [`WifiConfigError::ActivationFailed`] if NetworkManager rejects the connection.
+///
+/// # Example
+///
+/// ```no_run
+/// use wifi_config::{create_hotspot, Band, HotspotOptions};
+///
+/// let options = HotspotOptions {
+///     band: Some(Band::Bg),
+///     channel: Some(6),
+///     ..Default::default()
+/// };
+/// let hotspot = create_hotspot("Provisioning", "setup-1234", &options)?;
+/// wifi_config::delete(&hotspot)?;
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn create_hotspot(
+    ssid: &str,
+    password: &str,
+    options: &HotspotOptions,
+) -> Result<ActivationHandle, WifiConfigError> {
+    options.validate(password)?;
+
+    let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+    let client = DbusNmClient::new(&conn, options.timeouts);
+    let device = device::select_wifi_device(
+        device::wifi_devices(&client)?,
+        None,
+        options.interface.as_deref(),
+    )?;
+    check_capabilities(&device, options)?;
+
+    let mut hotspot_settings = build_hotspot_settings(ssid, password, options);
+    version::fit_settings(&client, &mut hotspot_settings)?;
+    let uuid = settings::assign_uuid(&mut hotspot_settings);
+    log::debug!(
+        "hotspot settings for {} on {}: {}",
+        ssid,
+        device.interface,
+        settings::setting_keys(&hotspot_settings)
+    );
+    let (settings_path, active_path) =
+        crate::add_and_activate(&client, &device.path, hotspot_settings)?;
+    log::info!("started hotspot {} ({})", ssid, settings_path);
+    Ok(ActivationHandle {
+        settings_path,
+        active_path,
+        interface: device.interface,
+        additional: Vec::new(),
+        uuid,
+        change: ProfileChange::Created,
+        detected_security: None,
+        connectivity: None,
+        portal_url: None,
+        ip: None,
+        active: None,
+        access_point: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_shared_access_point() {
+        let options = HotspotOptions {
+            band: Some(Band::Bg),
+            channel: Some(6),
+            zone: Some("public".into()),
+            powersave: Some(PowerSave::Disable),
+            mtu: Some(1400),
+            ..Default::default()
+        };
+        let settings = build_hotspot_settings("Provisioning", "setup-1234", &options);
+        assert_eq!(settings["connection"]["zone"].0.as_str(), Some("public"));
+
+        let wifi = &settings["802-11-wireless"];
+        assert_eq!(wifi["mode"].0.as_str(), Some("ap"));
+        assert_eq!(wifi["band"].0.as_str(), Some("bg"));
+        assert_eq!(wifi["channel"].0.as_u64(), Some(6));
+        assert_eq!(wifi["powersave"].0.as_any().downcast_ref::<u32>(), Some(&2));
+        assert_eq!(wifi["mtu"].0.as_any().downcast_ref::<u32>(), Some(&1400));
+        assert_eq!(
+            settings["802-11-wireless-security"]["key-mgmt"].0.as_str(),
+            Some("wpa-psk")
+        );
+        assert_eq!(settings["ipv4"]["method"].0.as_str(), Some("shared"));
+    }
+
+    #[test]
+    fn validates_hotspot_options() {
+        let defaults = HotspotOptions::default();
+        assert!(defaults.validate("setup-1234").is_ok());
+        assert!(defaults.validate("short").is_err());
+
+        let channel_without_band = HotspotOptions {
+            channel: Some(6),
+            ..Default::default()
+        };
+        assert!(channel_without_band.validate("setup-1234").is_err());
+
+        let wrong_band = HotspotOptions {
+            band: Some(Band::A),
+            channel: Some(6),
+            ..Default::default()
+        };
+        assert!(wrong_band.validate("setup-1234").is_err());
+
+        let tiny_mtu = HotspotOptions {
+            mtu: Some(500),
+            ..Default::default()
+        };
+        assert!(matches!(
+            tiny_mtu.validate("setup-1234"),
+            Err(WifiConfigError::InvalidHotspotConfig(_))
+        ));
+    }
+
+    #[test]
+    fn checks_device_capabilities_first() {
+        let mut device = WifiDevice {
+            path: dbus::Path::new("/org/freedesktop/NetworkManager/Devices/3").unwrap(),
+            interface: "wlan0".into(),
+            driver: "iwlwifi".into(),
+            perm_hw_address: "00:11:22:33:44:55".into(),
+            state: crate::DeviceState::Disconnected,
+            managed: true,
+            mtu: 1500,
+            // 2.4 GHz only, no AP mode.
+            capabilities: 0x328.into(),
+        };
+        assert!(matches!(
+            check_capabilities(&device, &HotspotOptions::default()),
+            Err(WifiConfigError::HotspotUnsupported(interface)) if interface == "wlan0"
+        ));
+
+        device.capabilities.ap = true;
+        let five_ghz = HotspotOptions {
+            band: Some(Band::A),
+            ..Default::default()
+        };
+        assert!(matches!(
+            check_capabilities(&device, &five_ghz),
+            Err(WifiConfigError::InvalidHotspotConfig(reason)) if reason.contains("5 GHz")
+        ));
+        assert!(check_capabilities(&device, &HotspotOptions::default()).is_ok());
+    }
+}
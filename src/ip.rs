@@ -0,0 +1,590 @@
+use dbus::arg::{PropMap, Variant};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::settings::ConnectionSettings;
+use crate::WifiConfigError;
+
+/// IPv4 configuration of the connection.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Ipv4Config {
+    /// Let NetworkManager use DHCP. No `ipv4` section is sent.
+    #[default]
+    Auto,
+    /// A fixed address.
+    Manual {
+        /// The address of this host.
+        address: Ipv4Addr,
+        /// Prefix length of the subnet (e.g. `24` for `255.255.255.0`).
+        prefix: u8,
+        /// Default gateway; must lie in the subnet. `None` for no default route.
+        gateway: Option<Ipv4Addr>,
+    },
+}
+
+impl Ipv4Config {
+    /// Checks the address, prefix and gateway client-side.
+    pub(crate) fn validate(&self) -> Result<(), WifiConfigError> {
+        let (address, prefix, gateway) = match self {
+            Ipv4Config::Auto => return Ok(()),
+            Ipv4Config::Manual {
+                address,
+                prefix,
+                gateway,
+            } => (*address, *prefix, *gateway),
+        };
+
+        if !(1..=32).contains(&prefix) {
+            return Err(WifiConfigError::InvalidIpConfig(format!(
+                "prefix /{} is out of range; expected 1 to 32",
+                prefix
+            )));
+        }
+        if address.is_unspecified()
+            || address.is_loopback()
+            || address.is_multicast()
+            || address.is_broadcast()
+        {
+            return Err(WifiConfigError::InvalidIpConfig(format!(
+                "{} can't be used as a host address",
+                address
+            )));
+        }
+
+        let mask = u32::MAX << (32 - u32::from(prefix));
+        let host = u32::from(address) & !mask;
+        // /31 and /32 have no network or broadcast address (RFC 3021).
+        if prefix < 31 && (host == 0 || host == !mask) {
+            return Err(WifiConfigError::InvalidIpConfig(format!(
+                "{} is the network or broadcast address of /{}",
+                address, prefix
+            )));
+        }
+        if let Some(gateway) = gateway {
+            if u32::from(gateway) & mask != u32::from(address) & mask {
+                return Err(WifiConfigError::InvalidIpConfig(format!(
+                    "gateway {} is not in {}/{}",
+                    gateway, address, prefix
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the `ipv4` section for a manual configuration.
+    pub(crate) fn apply(&self, connection_settings: &mut ConnectionSettings) {
+        let Ipv4Config::Manual {
+            address,
+            prefix,
+            gateway,
+        } = self
+        else {
+            return;
+        };
+
+        let mut address_data = PropMap::new();
+        address_data.insert("address".into(), Variant(Box::new(address.to_string())));
+        address_data.insert("prefix".into(), Variant(Box::new(u32::from(*prefix))));
+
+        let mut ipv4_settings = PropMap::new();
+        ipv4_settings.insert("method".into(), Variant(Box::new(String::from("manual"))));
+        ipv4_settings.insert("address-data".into(), Variant(Box::new(vec![address_data])));
+        if let Some(gateway) = gateway {
+            ipv4_settings.insert("gateway".into(), Variant(Box::new(gateway.to_string())));
+        }
+        connection_settings.insert("ipv4".into(), ipv4_settings);
+    }
+}
+
+/// IPv6 configuration of the connection; the counterpart of [`Ipv4Config`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Ipv6Config {
+    /// Use SLAAC/DHCPv6 as NetworkManager sees fit. No `ipv6` section is sent unless
+    /// [`ConnectOptions::ipv6_privacy`](crate::ConnectOptions::ipv6_privacy) is set.
+    #[default]
+    Auto,
+    /// Don't configure IPv6 on this connection (`method: "ignore"`).
+    ///
+    /// `ignore` is understood by every NetworkManager version, unlike `disabled`, which
+    /// needs 1.20 or newer.
+    Ignore,
+    /// A fixed address.
+    Manual {
+        /// The address of this host.
+        address: Ipv6Addr,
+        /// Prefix length of the subnet (e.g. `64`).
+        prefix: u8,
+        /// Default gateway, often a link-local address. `None` for no default route.
+        gateway: Option<Ipv6Addr>,
+    },
+}
+
+/// IPv6 privacy extensions (RFC 4941) setting, sent as `ipv6.ip6-privacy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Ipv6Privacy {
+    /// No temporary addresses.
+    Disabled,
+    /// Generate temporary addresses but prefer the public one.
+    PreferPublic,
+    /// Generate temporary addresses and prefer them for outgoing connections.
+    PreferTemporary,
+}
+
+impl Ipv6Privacy {
+    fn to_nm(self) -> i32 {
+        match self {
+            Ipv6Privacy::Disabled => 0,
+            Ipv6Privacy::PreferPublic => 1,
+            Ipv6Privacy::PreferTemporary => 2,
+        }
+    }
+}
+
+impl Ipv6Config {
+    /// Checks the address, prefix and gateway client-side.
+    pub(crate) fn validate(&self) -> Result<(), WifiConfigError> {
+        let Ipv6Config::Manual {
+            address,
+            prefix,
+            gateway,
+        } = self
+        else {
+            return Ok(());
+        };
+
+        if !(1..=128).contains(prefix) {
+            return Err(WifiConfigError::InvalidIpConfig(format!(
+                "prefix /{} is out of range; expected 1 to 128",
+                prefix
+            )));
+        }
+        if address.is_unspecified() || address.is_loopback() || address.is_multicast() {
+            return Err(WifiConfigError::InvalidIpConfig(format!(
+                "{} can't be used as a host address",
+                address
+            )));
+        }
+        if let Some(gateway) = gateway {
+            if gateway.is_unspecified() || gateway.is_multicast() {
+                return Err(WifiConfigError::InvalidIpConfig(format!(
+                    "{} can't be used as a gateway",
+                    gateway
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the `ipv6` section unless everything is left to NetworkManager.
+    pub(crate) fn apply(
+        &self,
+        privacy: Option<Ipv6Privacy>,
+        connection_settings: &mut ConnectionSettings,
+    ) {
+        let mut ipv6_settings = PropMap::new();
+        match self {
+            Ipv6Config::Auto if privacy.is_none() => return,
+            Ipv6Config::Auto => {
+                ipv6_settings.insert("method".into(), Variant(Box::new(String::from("auto"))));
+            }
+            Ipv6Config::Ignore => {
+                ipv6_settings.insert("method".into(), Variant(Box::new(String::from("ignore"))));
+            }
+            Ipv6Config::Manual {
+                address,
+                prefix,
+                gateway,
+            } => {
+                let mut address_data = PropMap::new();
+                address_data.insert("address".into(), Variant(Box::new(address.to_string())));
+                address_data.insert("prefix".into(), Variant(Box::new(u32::from(*prefix))));
+
+                ipv6_settings.insert("method".into(), Variant(Box::new(String::from("manual"))));
+                ipv6_settings.insert("address-data".into(), Variant(Box::new(vec![address_data])));
+                if let Some(gateway) = gateway {
+                    ipv6_settings.insert("gateway".into(), Variant(Box::new(gateway.to_string())));
+                }
+            }
+        }
+        if let Some(privacy) = privacy {
+            ipv6_settings.insert("ip6-privacy".into(), Variant(Box::new(privacy.to_nm())));
+        }
+        connection_settings.insert("ipv6".into(), ipv6_settings);
+    }
+}
+
+/// What the connection tells DHCP servers about this host, e.g. so a server that
+/// registers hostnames in DNS gets the right one.
+///
+/// Sent in the `ipv4` section, and the hostname options in the `ipv6` section too
+/// while IPv6 is [`Ipv6Config::Auto`]. The defaults leave everything to NetworkManager,
+/// which sends the system hostname.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct DhcpOptions {
+    /// Hostname to send instead of the system's (`dhcp-hostname`), e.g. `sensor-042`
+    /// or `sensor-042.plant.example`; checked against RFC 1123.
+    pub hostname: Option<String>,
+    /// Whether to send a hostname at all (`dhcp-send-hostname`). `None` keeps
+    /// NetworkManager's default, which is to send it.
+    pub send_hostname: Option<bool>,
+    /// DHCPv4 client identifier (`ipv4.dhcp-client-id`): `mac`, `perm-mac`, `duid`,
+    /// `stable`, a colon-separated hex string such as `01:02:00:00:00:00:01`, or any
+    /// other text, which is sent as it is. IPv6 has no equivalent.
+    pub client_id: Option<String>,
+}
+
+impl DhcpOptions {
+    /// Returns `true` if no option is set.
+    pub(crate) fn is_empty(&self) -> bool {
+        *self == DhcpOptions::default()
+    }
+
+    /// Checks the hostname and client identifier client-side, and that IPv4 uses DHCP.
+    pub(crate) fn validate(&self, ipv4: &Ipv4Config) -> Result<(), WifiConfigError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        if *ipv4 != Ipv4Config::Auto {
+            return Err(WifiConfigError::InvalidIpConfig(
+                "DHCP options need DHCP; the IPv4 address is static".into(),
+            ));
+        }
+        if let Some(hostname) = &self.hostname {
+            validate_hostname(hostname)?;
+        }
+        match &self.client_id {
+            Some(id) if id.is_empty() || !id.chars().all(|c| c.is_ascii_graphic()) => {
+                Err(WifiConfigError::InvalidIpConfig(format!(
+                    "'{}' is not a valid DHCP client identifier",
+                    id
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Adds the options to the `ipv4` section, and the hostname options to the `ipv6`
+    /// section with `ipv6` set to [`Ipv6Config::Auto`].
+    ///
+    /// Must run after the `ipv4`/`ipv6` sections were added, like [`apply_dns`].
+    pub(crate) fn apply(&self, ipv6: &Ipv6Config, connection_settings: &mut ConnectionSettings) {
+        if self.is_empty() {
+            return;
+        }
+        let mut families = vec!["ipv4"];
+        if *ipv6 == Ipv6Config::Auto && (self.hostname.is_some() || self.send_hostname.is_some()) {
+            families.push("ipv6");
+        }
+        for family in families {
+            let section = ip_section(connection_settings, family);
+            if let Some(hostname) = &self.hostname {
+                section.insert("dhcp-hostname".into(), Variant(Box::new(hostname.clone())));
+            }
+            if let Some(send) = self.send_hostname {
+                section.insert("dhcp-send-hostname".into(), Variant(Box::new(send)));
+            }
+        }
+        if let Some(id) = &self.client_id {
+            let section = ip_section(connection_settings, "ipv4");
+            section.insert("dhcp-client-id".into(), Variant(Box::new(id.clone())));
+        }
+    }
+}
+
+/// Checks `hostname` against RFC 1123: dot-separated labels of 1 to 63 letters, digits
+/// and hyphens, not starting or ending with a hyphen, 253 characters at most.
+fn validate_hostname(hostname: &str) -> Result<(), WifiConfigError> {
+    let invalid = |reason: &str| {
+        Err(WifiConfigError::InvalidIpConfig(format!(
+            "'{}' is not a valid hostname: {}",
+            hostname, reason
+        )))
+    };
+    if hostname.is_empty() || hostname.len() > 253 {
+        return invalid("it must be 1 to 253 characters long");
+    }
+    for label in hostname.split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return invalid("each label must be 1 to 63 characters long");
+        }
+        if !label
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        {
+            return invalid("only letters, digits and hyphens are allowed");
+        }
+        if label.starts_with('-') || label.ends_with('-') {
+            return invalid("a label can't start or end with a hyphen");
+        }
+    }
+    Ok(())
+}
+
+/// Checks DNS servers and search domains client-side.
+pub(crate) fn validate_dns(
+    servers: &[IpAddr],
+    search: &[String],
+    ipv6: &Ipv6Config,
+) -> Result<(), WifiConfigError> {
+    if let Some(server) = servers
+        .iter()
+        .find(|server| server.is_unspecified() || server.is_multicast())
+    {
+        return Err(WifiConfigError::InvalidIpConfig(format!(
+            "{} can't be used as a DNS server",
+            server
+        )));
+    }
+    if *ipv6 == Ipv6Config::Ignore && servers.iter().any(IpAddr::is_ipv6) {
+        return Err(WifiConfigError::InvalidIpConfig(
+            "IPv6 DNS servers need IPv6 to be enabled".into(),
+        ));
+    }
+    if let Some(domain) = search
+        .iter()
+        .find(|domain| domain.is_empty() || domain.contains(char::is_whitespace))
+    {
+        return Err(WifiConfigError::InvalidIpConfig(format!(
+            "'{}' is not a valid search domain",
+            domain
+        )));
+    }
+    Ok(())
+}
+
+/// Encodes an IPv4 address the way the legacy `ipv4.dns` property expects: a `u32`
+/// whose in-memory bytes are the address in network byte order.
+pub(crate) fn ipv4_dns_value(address: Ipv4Addr) -> u32 {
+    u32::from(address).to_be()
+}
+
+/// Pins DNS servers and search domains, dropping the automatically provided ones.
+///
+/// IPv4 servers go to `ipv4.dns` (`au`), IPv6 servers to `ipv6.dns` (`aay`); both
+/// formats are understood by every NetworkManager version. Search domains go to
+/// `ipv4.dns-search`. `ignore-auto-dns` is set on both families so neither DHCP nor
+/// router advertisements can add other resolvers.
+///
+/// Must run after the `ipv4`/`ipv6` sections were added; missing ones are created with
+/// `method: "auto"`.
+pub(crate) fn apply_dns(
+    servers: &[IpAddr],
+    search: &[String],
+    connection_settings: &mut ConnectionSettings,
+) {
+    if servers.is_empty() && search.is_empty() {
+        return;
+    }
+
+    let mut ipv4_servers = Vec::new();
+    let mut ipv6_servers = Vec::new();
+    for server in servers {
+        match server {
+            IpAddr::V4(address) => ipv4_servers.push(ipv4_dns_value(*address)),
+            IpAddr::V6(address) => ipv6_servers.push(address.octets().to_vec()),
+        }
+    }
+
+    let ipv4_settings = ip_section(connection_settings, "ipv4");
+    ipv4_settings.insert("ignore-auto-dns".into(), Variant(Box::new(true)));
+    if !ipv4_servers.is_empty() {
+        ipv4_settings.insert("dns".into(), Variant(Box::new(ipv4_servers)));
+    }
+    if !search.is_empty() {
+        ipv4_settings.insert("dns-search".into(), Variant(Box::new(search.to_vec())));
+    }
+
+    let ipv6_settings = ip_section(connection_settings, "ipv6");
+    ipv6_settings.insert("ignore-auto-dns".into(), Variant(Box::new(true)));
+    if !ipv6_servers.is_empty() {
+        ipv6_settings.insert("dns".into(), Variant(Box::new(ipv6_servers)));
+    }
+}
+
+/// Returns the `ipv4`/`ipv6` section, inserting `method: "auto"` if there is none yet.
+fn ip_section<'a>(connection_settings: &'a mut ConnectionSettings, name: &str) -> &'a mut PropMap {
+    connection_settings.entry(name.into()).or_insert_with(|| {
+        let mut section = PropMap::new();
+        section.insert("method".into(), Variant(Box::new(String::from("auto"))));
+        section
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manual(address: [u8; 4], prefix: u8, gateway: Option<[u8; 4]>) -> Ipv4Config {
+        Ipv4Config::Manual {
+            address: address.into(),
+            prefix,
+            gateway: gateway.map(Ipv4Addr::from),
+        }
+    }
+
+    #[test]
+    fn validates_manual_ipv4() {
+        assert!(Ipv4Config::Auto.validate().is_ok());
+        assert!(manual([192, 168, 1, 50], 24, Some([192, 168, 1, 1]))
+            .validate()
+            .is_ok());
+        assert!(manual([10, 0, 0, 0], 31, None).validate().is_ok());
+
+        for invalid in [
+            manual([192, 168, 1, 50], 0, None),
+            manual([192, 168, 1, 50], 33, None),
+            manual([0, 0, 0, 0], 24, None),
+            manual([192, 168, 1, 0], 24, None),
+            manual([192, 168, 1, 255], 24, None),
+            manual([192, 168, 1, 50], 24, Some([192, 168, 2, 1])),
+        ] {
+            assert!(
+                matches!(invalid.validate(), Err(WifiConfigError::InvalidIpConfig(_))),
+                "{:?}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn builds_ipv6_section() {
+        let mut settings = ConnectionSettings::new();
+        Ipv6Config::Auto.apply(None, &mut settings);
+        assert!(!settings.contains_key("ipv6"));
+
+        Ipv6Config::Ignore.apply(None, &mut settings);
+        assert_eq!(settings["ipv6"]["method"].0.as_str(), Some("ignore"));
+
+        Ipv6Config::Auto.apply(Some(Ipv6Privacy::PreferTemporary), &mut settings);
+        assert_eq!(settings["ipv6"]["method"].0.as_str(), Some("auto"));
+        assert_eq!(settings["ipv6"]["ip6-privacy"].0.as_i64(), Some(2));
+    }
+
+    #[test]
+    fn encodes_dns_in_network_byte_order() {
+        assert_eq!(
+            ipv4_dns_value(Ipv4Addr::new(10, 0, 0, 53)).to_ne_bytes(),
+            [10, 0, 0, 53]
+        );
+
+        let mut settings = ConnectionSettings::new();
+        apply_dns(
+            &["10.0.0.53".parse().unwrap(), "fd00::53".parse().unwrap()],
+            &["corp.local".into()],
+            &mut settings,
+        );
+        let ipv4 = &settings["ipv4"];
+        assert_eq!(ipv4["method"].0.as_str(), Some("auto"));
+        assert_eq!(ipv4["ignore-auto-dns"].0.as_u64(), Some(1));
+        let dns: Vec<_> = ipv4["dns"].0.as_iter().unwrap().collect();
+        assert_eq!(dns.len(), 1);
+        assert_eq!(
+            (dns[0].as_u64().unwrap() as u32).to_ne_bytes(),
+            [10, 0, 0, 53]
+        );
+        assert!(settings["ipv6"].contains_key("dns"));
+    }
+
+    #[test]
+    fn validates_dns() {
+        let server: IpAddr = "fd00::53".parse().unwrap();
+        assert!(validate_dns(&[server], &[], &Ipv6Config::Auto).is_ok());
+        assert!(validate_dns(&[server], &[], &Ipv6Config::Ignore).is_err());
+        assert!(validate_dns(&[], &["corp local".into()], &Ipv6Config::Auto).is_err());
+    }
+
+    #[test]
+    fn validates_and_builds_dhcp_options() {
+        let hostname = |name: &str| DhcpOptions {
+            hostname: Some(name.into()),
+            ..Default::default()
+        };
+        assert!(hostname("sensor-042").validate(&Ipv4Config::Auto).is_ok());
+        assert!(hostname("sensor-042.plant.example")
+            .validate(&Ipv4Config::Auto)
+            .is_ok());
+        for invalid in [
+            "",
+            "sensor_042",
+            "-sensor",
+            "sensor-",
+            "sensor..plant",
+            "sensor.",
+            &"a".repeat(64),
+        ] {
+            assert!(
+                matches!(
+                    hostname(invalid).validate(&Ipv4Config::Auto),
+                    Err(WifiConfigError::InvalidIpConfig(_))
+                ),
+                "{:?}",
+                invalid
+            );
+        }
+        let static_ip = Ipv4Config::Manual {
+            address: [192, 168, 1, 50].into(),
+            prefix: 24,
+            gateway: None,
+        };
+        assert!(hostname("sensor-042").validate(&static_ip).is_err());
+        assert!(DhcpOptions::default().validate(&static_ip).is_ok());
+        let spaced = DhcpOptions {
+            client_id: Some("my id".into()),
+            ..Default::default()
+        };
+        assert!(spaced.validate(&Ipv4Config::Auto).is_err());
+
+        let dhcp = DhcpOptions {
+            hostname: Some("sensor-042".into()),
+            send_hostname: Some(true),
+            client_id: Some("mac".into()),
+        };
+        let mut settings = ConnectionSettings::new();
+        dhcp.apply(&Ipv6Config::Auto, &mut settings);
+        let ipv4 = &settings["ipv4"];
+        assert_eq!(ipv4["method"].0.as_str(), Some("auto"));
+        assert_eq!(ipv4["dhcp-hostname"].0.as_str(), Some("sensor-042"));
+        assert_eq!(ipv4["dhcp-send-hostname"].0.as_u64(), Some(1));
+        assert_eq!(ipv4["dhcp-client-id"].0.as_str(), Some("mac"));
+        let ipv6 = &settings["ipv6"];
+        assert_eq!(ipv6["dhcp-hostname"].0.as_str(), Some("sensor-042"));
+        assert!(!ipv6.contains_key("dhcp-client-id"));
+
+        let mut settings = ConnectionSettings::new();
+        Ipv6Config::Ignore.apply(None, &mut settings);
+        dhcp.apply(&Ipv6Config::Ignore, &mut settings);
+        assert!(!settings["ipv6"].contains_key("dhcp-hostname"));
+    }
+
+    #[test]
+    fn validates_manual_ipv6() {
+        let manual = |address: &str, prefix| Ipv6Config::Manual {
+            address: address.parse().unwrap(),
+            prefix,
+            gateway: Some("fe80::1".parse().unwrap()),
+        };
+        assert!(manual("2001:db8::50", 64).validate().is_ok());
+        assert!(manual("2001:db8::50", 0).validate().is_err());
+        assert!(manual("2001:db8::50", 129).validate().is_err());
+        assert!(manual("::1", 64).validate().is_err());
+    }
+}
@@ -0,0 +1,255 @@
+//! The addresses, gateways and DNS servers NetworkManager assigned to a connection.
+
+use dbus::arg::{prop_cast, PropMap};
+use dbus::blocking::{stdintf::org_freedesktop_dbus::Properties, Connection};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::error::nm_error;
+use crate::nm::{
+    ACTIVE_CONNECTION_INTERFACE, IP4_CONFIG_INTERFACE, IP6_CONFIG_INTERFACE, NM_BUS_NAME,
+    NO_OBJECT_PATH,
+};
+use crate::{ActivationHandle, DbusTimeouts, WifiConfigError, WifiManager};
+
+/// How long [`connect_and_wait`](crate::connect_and_wait) waits for the addresses of a
+/// connection that is already up: NetworkManager may report `ACTIVATED` before it
+/// exports the IP configuration objects.
+pub(crate) const IP_INFO_WAIT: Duration = Duration::from_secs(2);
+
+/// How often [`wait_for_ip_info`] reads the IP configuration again.
+const IP_INFO_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The IP configuration of an active connection, from its `Ip4Config` and `Ip6Config`
+/// objects; see [`ActivationHandle::ip`] and [`ip_info`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct IpInfo {
+    /// IPv4 addresses with their prefix length (`AddressData`).
+    pub ipv4_addresses: Vec<(Ipv4Addr, u8)>,
+    /// IPv4 default gateway; `None` without one, e.g. on a hotspot.
+    pub ipv4_gateway: Option<Ipv4Addr>,
+    /// IPv6 addresses with their prefix length, link-local ones included.
+    pub ipv6_addresses: Vec<(Ipv6Addr, u8)>,
+    /// IPv6 default gateway.
+    pub ipv6_gateway: Option<Ipv6Addr>,
+    /// DNS servers in use, IPv4 ones first.
+    pub dns: Vec<IpAddr>,
+}
+
+impl IpInfo {
+    /// The address to reach this host at: the first IPv4 address, otherwise the first
+    /// IPv6 address that isn't link-local.
+    pub fn address(&self) -> Option<IpAddr> {
+        let ipv4 = self
+            .ipv4_addresses
+            .first()
+            .map(|&(address, _)| address.into());
+        ipv4.or_else(|| {
+            self.ipv6_addresses
+                .iter()
+                .map(|&(address, _)| address)
+                .find(|address| !is_link_local(address))
+                .map(IpAddr::V6)
+        })
+    }
+}
+
+/// Reads the addresses, gateways and DNS servers of the connection of `handle`, waiting
+/// up to `timeout` for it to get an [address](IpInfo::address).
+///
+/// [`connect_and_wait`](crate::connect_and_wait) already fills in
+/// [`ActivationHandle::ip`]; this is for connections that weren't waited for, e.g. a
+/// hotspot right after [`create_hotspot`](crate::create_hotspot), whose address is the
+/// one it serves its clients on.
+///
+/// Returns whatever is assigned by then, which may be nothing, rather than failing when
+/// `timeout` passes.
+///
+/// # Errors
+///
+/// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+/// - [`WifiConfigError::NetworkManagerUnavailable`] if NetworkManager isn't running.
+/// - [`WifiConfigError::DeviceQueryFailed`] if the connection is no longer active or a
+///   property can't be read.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use wifi_config::{create_hotspot, HotspotOptions};
+///
+/// let hotspot = create_hotspot("Provisioning", "setup-1234", &HotspotOptions::default())?;
+/// let ip = wifi_config::ip_info(&hotspot, Duration::from_secs(30))?;
+/// if let Some(address) = ip.address() {
+///     println!("manage me at http://{}", address);
+/// }
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn ip_info(handle: &ActivationHandle, timeout: Duration) -> Result<IpInfo, WifiConfigError> {
+    WifiManager::new()?.ip_info(handle, timeout)
+}
+
+/// Reads the IP configuration of the active connection at `active_path` until it has an
+/// address or `timeout` passes.
+pub(crate) fn wait_for_ip_info(
+    conn: &Connection,
+    timeouts: DbusTimeouts,
+    active_path: &dbus::Path,
+    timeout: Duration,
+) -> Result<IpInfo, WifiConfigError> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let info = read_ip_info(conn, timeouts, active_path)?;
+        if info.address().is_some() || Instant::now() >= deadline {
+            return Ok(info);
+        }
+        std::thread::sleep(IP_INFO_POLL_INTERVAL);
+    }
+}
+
+/// Reads the IP configuration of the active connection at `active_path`; a family whose
+/// configuration object is still `/` is left empty.
+fn read_ip_info(
+    conn: &Connection,
+    timeouts: DbusTimeouts,
+    active_path: &dbus::Path,
+) -> Result<IpInfo, WifiConfigError> {
+    let nm = PropertyReader {
+        conn,
+        timeout: timeouts.query,
+    };
+    let mut info = IpInfo::default();
+
+    let ip4_config: dbus::Path<'static> =
+        nm.get(active_path, ACTIVE_CONNECTION_INTERFACE, "Ip4Config")?;
+    if &*ip4_config != NO_OBJECT_PATH {
+        let address_data: Vec<PropMap> =
+            nm.get(&ip4_config, IP4_CONFIG_INTERFACE, "AddressData")?;
+        info.ipv4_addresses = addresses(&address_data);
+        let gateway: String = nm.get(&ip4_config, IP4_CONFIG_INTERFACE, "Gateway")?;
+        info.ipv4_gateway = gateway.parse().ok();
+        let nameservers: Vec<PropMap> =
+            nm.get(&ip4_config, IP4_CONFIG_INTERFACE, "NameserverData")?;
+        info.dns.extend(
+            addresses::<Ipv4Addr>(&nameservers)
+                .into_iter()
+                .map(|(address, _)| IpAddr::V4(address)),
+        );
+    }
+
+    let ip6_config: dbus::Path<'static> =
+        nm.get(active_path, ACTIVE_CONNECTION_INTERFACE, "Ip6Config")?;
+    if &*ip6_config != NO_OBJECT_PATH {
+        let address_data: Vec<PropMap> =
+            nm.get(&ip6_config, IP6_CONFIG_INTERFACE, "AddressData")?;
+        info.ipv6_addresses = addresses(&address_data);
+        let gateway: String = nm.get(&ip6_config, IP6_CONFIG_INTERFACE, "Gateway")?;
+        info.ipv6_gateway = gateway.parse().ok();
+        // IP6Config has no `NameserverData`; the servers are raw 16-byte addresses.
+        let nameservers: Vec<Vec<u8>> = nm.get(&ip6_config, IP6_CONFIG_INTERFACE, "Nameservers")?;
+        info.dns.extend(
+            nameservers
+                .iter()
+                .filter_map(|octets| <[u8; 16]>::try_from(octets.as_slice()).ok())
+                .map(|octets| IpAddr::V6(octets.into())),
+        );
+    }
+    Ok(info)
+}
+
+/// Reads properties of NetworkManager's objects over `conn`.
+struct PropertyReader<'a> {
+    conn: &'a Connection,
+    timeout: Duration,
+}
+
+impl PropertyReader<'_> {
+    /// Reads `property` of `interface` on the object at `path`.
+    fn get<R: for<'b> dbus::arg::Get<'b> + 'static>(
+        &self,
+        path: &dbus::Path,
+        interface: &str,
+        property: &str,
+    ) -> Result<R, WifiConfigError> {
+        self.conn
+            .with_proxy(NM_BUS_NAME, path, self.timeout)
+            .get(interface, property)
+            .map_err(nm_error(WifiConfigError::DeviceQueryFailed))
+    }
+}
+
+/// The well-formed entries of an `AddressData` (or `NameserverData`) property; a
+/// missing prefix, as in `NameserverData`, reads as `0`.
+pub(crate) fn addresses<A: FromStr>(address_data: &[PropMap]) -> Vec<(A, u8)> {
+    address_data
+        .iter()
+        .filter_map(|entry| {
+            let address = prop_cast::<String>(entry, "address")?.parse().ok()?;
+            let prefix = match prop_cast::<u32>(entry, "prefix") {
+                Some(&prefix) => u8::try_from(prefix).ok()?,
+                None => 0,
+            };
+            Some((address, prefix))
+        })
+        .collect()
+}
+
+/// Whether `address` is in `fe80::/10`.
+fn is_link_local(address: &Ipv6Addr) -> bool {
+    address.segments()[0] & 0xffc0 == 0xfe80
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dbus::arg::Variant;
+
+    fn entry(address: &str, prefix: Option<u32>) -> PropMap {
+        let mut entry = PropMap::new();
+        entry.insert("address".into(), Variant(Box::new(address.to_string())));
+        if let Some(prefix) = prefix {
+            entry.insert("prefix".into(), Variant(Box::new(prefix)));
+        }
+        entry
+    }
+
+    #[test]
+    fn reads_address_data() {
+        assert_eq!(addresses::<Ipv4Addr>(&[]), []);
+        assert_eq!(
+            addresses::<Ipv4Addr>(&[
+                entry("not an address", Some(24)),
+                entry("192.168.1.50", Some(24)),
+                entry("fe80::1", Some(64)),
+                entry("10.0.0.1", Some(300)),
+            ]),
+            [(Ipv4Addr::new(192, 168, 1, 50), 24)]
+        );
+        assert_eq!(
+            addresses::<Ipv4Addr>(&[entry("192.168.1.1", None)]),
+            [(Ipv4Addr::new(192, 168, 1, 1), 0)]
+        );
+    }
+
+    #[test]
+    fn prefers_ipv4_then_global_ipv6_addresses() {
+        let mut info = IpInfo {
+            ipv6_addresses: vec![
+                ("fe80::1".parse().unwrap(), 64),
+                ("2001:db8::50".parse().unwrap(), 64),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(info.address(), Some("2001:db8::50".parse().unwrap()));
+        info.ipv4_addresses.push((Ipv4Addr::new(10, 42, 0, 1), 24));
+        assert_eq!(
+            info.address(),
+            Some(IpAddr::V4(Ipv4Addr::new(10, 42, 0, 1)))
+        );
+        info.ipv4_addresses.clear();
+        info.ipv6_addresses.remove(1);
+        assert_eq!(info.address(), None);
+    }
+}
@@ -0,0 +1,701 @@
+//! [`Backend::Iwd`](crate::Backend::Iwd): connecting through iwd (`net.connman.iwd`).
+
+use dbus::arg::{prop_cast, PropMap};
+use dbus::blocking::stdintf::org_freedesktop_dbus::{ObjectManager, Properties};
+use dbus::blocking::Connection;
+use dbus::channel::{MatchingReceiver, Sender, Token};
+use dbus::message::{MatchRule, MessageType};
+use dbus::Message;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::backend::{self, WifiBackend};
+use crate::nm::NO_OBJECT_PATH;
+use crate::scan;
+use crate::{
+    AccessPointInfo, ActivationHandle, ApSecurity, ConnectOptions, Connectivity, DbusTimeouts,
+    DeviceState, DeviceStateEvent, DeviceStateReason, ProfileChange, RadioState, Secret, Security,
+    WifiConfigError, WifiStatus,
+};
+
+/// Well-known bus name of iwd.
+pub(crate) const IWD_BUS_NAME: &str = "net.connman.iwd";
+/// Object path of iwd's object manager.
+const IWD_ROOT_PATH: &str = "/";
+/// Object path of the agent manager.
+const AGENT_MANAGER_PATH: &str = "/net/connman/iwd";
+/// Interface of the agent manager (`RegisterAgent`, `UnregisterAgent`).
+const AGENT_MANAGER_INTERFACE: &str = "net.connman.iwd.AgentManager";
+/// Interface our agent implements (`RequestPassphrase`, ...).
+const AGENT_INTERFACE: &str = "net.connman.iwd.Agent";
+/// Interface implemented by every wireless device object.
+const DEVICE_INTERFACE: &str = "net.connman.iwd.Device";
+/// Interface implemented by devices in station (client) mode.
+const STATION_INTERFACE: &str = "net.connman.iwd.Station";
+/// Interface implemented by every network in range.
+const NETWORK_INTERFACE: &str = "net.connman.iwd.Network";
+/// Interface implemented by every saved network.
+const KNOWN_NETWORK_INTERFACE: &str = "net.connman.iwd.KnownNetwork";
+/// Error the agent answers requests with that it has no credentials for.
+const AGENT_CANCELED_ERROR: &str = "net.connman.iwd.Agent.Error.Canceled";
+/// Returned by `Connect` when the connection failed, e.g. on a wrong passphrase.
+const FAILED_ERROR: &str = "net.connman.iwd.Failed";
+/// How long a triggered scan may take before the results are read anyway.
+const SCAN_TIMEOUT: Duration = Duration::from_secs(15);
+/// How often `Scanning` is polled while waiting for a scan.
+const SCAN_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The result of `GetManagedObjects`: every object with its interfaces and properties.
+type ManagedObjects = HashMap<dbus::Path<'static>, HashMap<String, PropMap>>;
+
+/// [`Backend::Iwd`](crate::Backend::Iwd).
+pub(crate) struct IwdBackend;
+
+impl WifiBackend for IwdBackend {
+    /// iwd's `Connect` only returns once the connection is up, so this waits as well.
+    fn connect(
+        &self,
+        ssid: &[u8],
+        options: &ConnectOptions,
+    ) -> Result<ActivationHandle, WifiConfigError> {
+        self.connect_and_wait(ssid, options, &mut |_| {})
+    }
+
+    fn connect_and_wait(
+        &self,
+        ssid: &[u8],
+        options: &ConnectOptions,
+        _progress: &mut dyn FnMut(&DeviceStateEvent),
+    ) -> Result<ActivationHandle, WifiConfigError> {
+        check_supported(options)?;
+        let name = std::str::from_utf8(ssid).map_err(|_| {
+            WifiConfigError::UnsupportedByBackend(
+                "iwd only handles SSIDs that are valid UTF-8".into(),
+            )
+        })?;
+        let timeout = options.timeouts.query;
+        let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+        let station = find_station(
+            &managed_objects(&conn, timeout)?,
+            options.interface.as_deref(),
+        )?;
+
+        let mut network = find_network(&conn, &station.path, name, timeout)?;
+        if network.is_none() {
+            scan(&conn, &station.path, timeout)?;
+            network = find_network(&conn, &station.path, name, timeout)?;
+        }
+        if network.is_none() && !options.hidden {
+            return Err(WifiConfigError::NetworkNotFound(name.to_string()));
+        }
+
+        let passphrase = passphrase(&options.security);
+        let has_passphrase = passphrase.is_some();
+        let known = known_network(&managed_objects(&conn, timeout)?, name);
+
+        let request = || {
+            match &network {
+                Some(network) => {
+                    Message::new_method_call(IWD_BUS_NAME, network, NETWORK_INTERFACE, "Connect")
+                }
+                None => Message::new_method_call(
+                    IWD_BUS_NAME,
+                    &station.path,
+                    STATION_INTERFACE,
+                    "ConnectHiddenNetwork",
+                )
+                .map(|message| message.append1(name)),
+            }
+            .expect("valid names")
+        };
+
+        log::debug!(
+            "connecting to {} through iwd station {}",
+            name,
+            station.path
+        );
+        // A known network is kept until the new passphrase has worked: iwd connects to it
+        // with the passphrase it saved, asks the agent for another one only once the
+        // handshake rejected that, and saves what the agent hands out once it connects.
+        let agent = Agent::register(&conn, passphrase, timeout)?;
+        let mut result = agent.call(request(), options.activation_timeout())?;
+        let retry = match &result {
+            Err(e) => {
+                known.is_some()
+                    && has_passphrase
+                    && e.name() == Some(FAILED_ERROR)
+                    && !agent.requests().passphrase_requested
+            }
+            Ok(_) => false,
+        };
+        if retry {
+            log::info!(
+                "iwd's saved passphrase for {} was rejected; trying the new one",
+                name
+            );
+            result = agent.call(request(), options.activation_timeout())?;
+        }
+        let requests = agent.requests();
+        drop(agent);
+        match result {
+            Ok(_) => {}
+            Err(_) if requests.refused => {
+                return Err(WifiConfigError::AuthenticationFailed(
+                    DeviceStateReason::NoSecrets,
+                ))
+            }
+            Err(e) if e.name() == Some(FAILED_ERROR) && requests.passphrase_requested => {
+                return Err(WifiConfigError::AuthenticationFailed(
+                    DeviceStateReason::SupplicantDisconnect,
+                ))
+            }
+            Err(e) => return Err(WifiConfigError::activation_failed(e)),
+        }
+        let change = match known {
+            None => ProfileChange::Created,
+            Some(_) if requests.passphrase_requested => ProfileChange::Updated,
+            Some(_) => {
+                if has_passphrase {
+                    log::info!("iwd connected to {} with the passphrase it had saved", name);
+                }
+                ProfileChange::Unchanged
+            }
+        };
+
+        let settings_path = known_network(&managed_objects(&conn, timeout)?, name)
+            .or(network)
+            .unwrap_or_else(|| station.path.clone());
+        Ok(ActivationHandle {
+            settings_path,
+            active_path: station.path,
+            interface: station.interface,
+            additional: Vec::new(),
+            uuid: String::new(),
+            change,
+            detected_security: None,
+            connectivity: None,
+            portal_url: None,
+            ip: None,
+            active: None,
+            access_point: None,
+        })
+    }
+
+    fn scan_networks(
+        &self,
+        fresh: bool,
+        timeouts: DbusTimeouts,
+    ) -> Result<Vec<AccessPointInfo>, WifiConfigError> {
+        backend::check_cached_scan("iwd", fresh)?;
+        let timeout = timeouts.query;
+        let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+        let station = find_station(&managed_objects(&conn, timeout)?, None)?;
+        ordered_networks(&conn, &station.path, timeout)?
+            .into_iter()
+            .map(|(path, signal)| {
+                let props = network_properties(&conn, &path, timeout)
+                    .map_err(WifiConfigError::ScanFailed)?;
+                Ok(access_point(path, signal, &props))
+            })
+            .collect()
+    }
+
+    fn current_status(
+        &self,
+        timeouts: DbusTimeouts,
+    ) -> Result<Option<WifiStatus>, WifiConfigError> {
+        let timeout = timeouts.query;
+        let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+        let objects = managed_objects(&conn, timeout)?;
+        let station = find_station(&objects, None)?;
+        let station_props = &objects[&station.path][STATION_INTERFACE];
+        let network_path = match prop_cast::<dbus::Path<'static>>(station_props, "ConnectedNetwork")
+        {
+            Some(path) if &**path != NO_OBJECT_PATH => path.clone(),
+            _ => return Ok(None),
+        };
+        let signal = ordered_networks(&conn, &station.path, timeout)?
+            .into_iter()
+            .find(|(path, _)| *path == network_path)
+            .map_or(i16::MIN, |(_, signal)| signal);
+        let props = network_properties(&conn, &network_path, timeout)
+            .map_err(WifiConfigError::DeviceQueryFailed)?;
+        let ap = access_point(network_path, signal, &props);
+        Ok(Some(WifiStatus {
+            interface: station.interface,
+            state: station_state(
+                prop_cast::<String>(station_props, "State").map_or("", String::as_str),
+            ),
+            connection_id: ap.ssid_lossy.clone(),
+            ssid: ap.ssid,
+            ssid_lossy: ap.ssid_lossy,
+            bssid: None,
+            strength: ap.strength,
+            frequency: 0,
+            ipv4_address: None,
+            mtu: None,
+            radio: RadioState::Enabled,
+            connectivity: Connectivity::Unknown,
+            portal_url: None,
+        }))
+    }
+}
+
+/// Rejects the options iwd can't apply.
+fn check_supported(options: &ConnectOptions) -> Result<(), WifiConfigError> {
+    let raw_key = matches!(
+        &options.security,
+        Security::WpaPsk(password) | Security::Wpa3Transition(password)
+            if password.len() == 64
+    );
+    backend::check_supported(
+        "iwd",
+        options,
+        &[
+            (matches!(options.security, Security::Wep(_)), "WEP networks"),
+            (
+                matches!(options.security, Security::Enterprise(_)),
+                "enterprise networks",
+            ),
+            (raw_key, "raw 64-digit keys"),
+            (options.bssid.is_some(), "BSSID locks"),
+            (
+                options.autoconnect.is_some() || options.autoconnect_priority.is_some(),
+                "autoconnect settings",
+            ),
+        ],
+    )
+}
+
+/// The passphrase the agent hands to iwd, if the network needs one.
+///
+/// iwd detects the security itself, so [`Security::Auto`] needs no scan results here.
+fn passphrase(security: &Security) -> Option<Secret> {
+    match security {
+        Security::WpaPsk(password)
+        | Security::Wpa3Sae(password)
+        | Security::Wpa3Transition(password) => Some(password.clone()),
+        Security::Auto(password) => password.clone(),
+        _ => None,
+    }
+}
+
+/// A device in station mode.
+struct Station {
+    path: dbus::Path<'static>,
+    interface: String,
+}
+
+/// Lists every object iwd exports.
+fn managed_objects(
+    conn: &Connection,
+    timeout: Duration,
+) -> Result<ManagedObjects, WifiConfigError> {
+    conn.with_proxy(IWD_BUS_NAME, IWD_ROOT_PATH, timeout)
+        .get_managed_objects()
+        .map_err(WifiConfigError::DeviceQueryFailed)
+}
+
+/// Returns the station with the given interface name, or the first one.
+fn find_station(
+    objects: &ManagedObjects,
+    interface: Option<&str>,
+) -> Result<Station, WifiConfigError> {
+    let mut stations: Vec<Station> = objects
+        .iter()
+        .filter(|(_, interfaces)| interfaces.contains_key(STATION_INTERFACE))
+        .map(|(path, interfaces)| Station {
+            path: path.clone(),
+            interface: interfaces
+                .get(DEVICE_INTERFACE)
+                .and_then(|props| prop_cast::<String>(props, "Name"))
+                .cloned()
+                .unwrap_or_default(),
+        })
+        .collect();
+    // The object manager reports objects in no particular order.
+    stations.sort_by(|a, b| a.path.cmp(&b.path));
+    match interface {
+        None => stations
+            .into_iter()
+            .next()
+            .ok_or(WifiConfigError::NoWifiDevice),
+        Some(interface) => stations
+            .into_iter()
+            .find(|station| station.interface == interface)
+            .ok_or_else(|| WifiConfigError::DeviceNotFound(interface.to_string())),
+    }
+}
+
+/// Returns the known network called `name`, if iwd saved one.
+fn known_network(objects: &ManagedObjects, name: &str) -> Option<dbus::Path<'static>> {
+    objects.iter().find_map(|(path, interfaces)| {
+        let props = interfaces.get(KNOWN_NETWORK_INTERFACE)?;
+        (prop_cast::<String>(props, "Name")? == name).then(|| path.clone())
+    })
+}
+
+/// The networks in range with their signal strength in 100 * dBm, strongest first.
+fn ordered_networks(
+    conn: &Connection,
+    station: &dbus::Path,
+    timeout: Duration,
+) -> Result<Vec<(dbus::Path<'static>, i16)>, WifiConfigError> {
+    let (networks,): (Vec<(dbus::Path<'static>, i16)>,) = conn
+        .with_proxy(IWD_BUS_NAME, station, timeout)
+        .method_call(STATION_INTERFACE, "GetOrderedNetworks", ())
+        .map_err(WifiConfigError::ScanFailed)?;
+    Ok(networks)
+}
+
+/// Reads the `Network` properties of `path`.
+fn network_properties(
+    conn: &Connection,
+    path: &dbus::Path,
+    timeout: Duration,
+) -> Result<PropMap, dbus::Error> {
+    conn.with_proxy(IWD_BUS_NAME, path, timeout)
+        .get_all(NETWORK_INTERFACE)
+}
+
+/// Returns the network in range called `name`.
+fn find_network(
+    conn: &Connection,
+    station: &dbus::Path,
+    name: &str,
+    timeout: Duration,
+) -> Result<Option<dbus::Path<'static>>, WifiConfigError> {
+    for (path, _) in ordered_networks(conn, station, timeout)? {
+        let props =
+            network_properties(conn, &path, timeout).map_err(WifiConfigError::ScanFailed)?;
+        if prop_cast::<String>(&props, "Name").is_some_and(|n| n == name) {
+            return Ok(Some(path));
+        }
+    }
+    Ok(None)
+}
+
+/// Triggers a scan and waits for it to finish, for at most 15 seconds.
+fn scan(conn: &Connection, station: &dbus::Path, timeout: Duration) -> Result<(), WifiConfigError> {
+    let proxy = conn.with_proxy(IWD_BUS_NAME, station, timeout);
+    // Fails with `Busy` or `InProgress` if a scan is already running, which is as good.
+    let _: Result<(), _> = proxy.method_call(STATION_INTERFACE, "Scan", ());
+    let deadline = Instant::now() + SCAN_TIMEOUT;
+    while Instant::now() < deadline {
+        let scanning: bool = proxy
+            .get(STATION_INTERFACE, "Scanning")
+            .map_err(WifiConfigError::ScanFailed)?;
+        if !scanning {
+            break;
+        }
+        std::thread::sleep(SCAN_POLL_INTERVAL);
+    }
+    Ok(())
+}
+
+/// Builds an access point from a network's properties and its signal in 100 * dBm.
+fn access_point(path: dbus::Path<'static>, signal: i16, props: &PropMap) -> AccessPointInfo {
+    let name = prop_cast::<String>(props, "Name")
+        .cloned()
+        .unwrap_or_default();
+    let security = match prop_cast::<String>(props, "Type").map(String::as_str) {
+        Some("psk") => ApSecurity::WpaPsk,
+        Some("8021x") => ApSecurity::WpaEap,
+        Some("wep") => ApSecurity::Wep,
+        _ => ApSecurity::Open,
+    };
+    AccessPointInfo {
+        path,
+        ssid: name.clone().into_bytes(),
+        ssid_lossy: name,
+        strength: scan::dbm_to_percent(i32::from(signal) / 100),
+        frequency: 0,
+        max_bitrate: 0,
+        hw_address: String::new(),
+        flags: 0,
+        wpa_flags: 0,
+        rsn_flags: 0,
+        security,
+    }
+}
+
+/// Maps a station `State` to the closest NetworkManager device state.
+fn station_state(state: &str) -> DeviceState {
+    match state {
+        "connected" => DeviceState::Activated,
+        "connecting" | "roaming" => DeviceState::Config,
+        "disconnecting" => DeviceState::Deactivating,
+        "disconnected" => DeviceState::Disconnected,
+        _ => DeviceState::Unknown,
+    }
+}
+
+/// What iwd asked the agent for.
+#[derive(Debug, Clone, Copy, Default)]
+struct AgentRequests {
+    /// iwd asked for a passphrase.
+    passphrase_requested: bool,
+    /// The agent had nothing to answer a request with.
+    refused: bool,
+}
+
+/// An agent registered with iwd for as long as it lives, answering passphrase requests.
+struct Agent<'a> {
+    conn: &'a Connection,
+    path: dbus::Path<'static>,
+    token: Token,
+    requests: Arc<Mutex<AgentRequests>>,
+    timeout: Duration,
+}
+
+impl<'a> Agent<'a> {
+    /// Exports the agent and registers it with iwd.
+    fn register(
+        conn: &'a Connection,
+        passphrase: Option<Secret>,
+        timeout: Duration,
+    ) -> Result<Self, WifiConfigError> {
+        let path = dbus::Path::new(format!("/wifi_config/agent{}", std::process::id()))
+            .expect("valid object path");
+        let requests = Arc::new(Mutex::new(AgentRequests::default()));
+        let sink = Arc::clone(&requests);
+        let rule = MatchRule::new_method_call()
+            .with_path(path.clone())
+            .with_interface(AGENT_INTERFACE);
+        let token = conn.start_receive(
+            rule,
+            Box::new(move |call, conn| {
+                let reply = agent_reply(&call, passphrase.as_deref(), &mut sink.lock().unwrap());
+                if conn.send(reply).is_err() {
+                    log::warn!("failed to send the agent reply to iwd");
+                }
+                true
+            }),
+        );
+
+        let registered: Result<(), _> = conn
+            .with_proxy(IWD_BUS_NAME, AGENT_MANAGER_PATH, timeout)
+            .method_call(AGENT_MANAGER_INTERFACE, "RegisterAgent", (&path,));
+        if let Err(e) = registered {
+            conn.stop_receive(token);
+            return Err(WifiConfigError::activation_failed(e));
+        }
+        Ok(Agent {
+            conn,
+            path,
+            token,
+            requests,
+            timeout,
+        })
+    }
+
+    /// What iwd asked for so far.
+    fn requests(&self) -> AgentRequests {
+        *self.requests.lock().unwrap()
+    }
+
+    /// Sends `request` and answers agent requests until its reply arrives.
+    ///
+    /// A blocking method call wouldn't dispatch the agent requests iwd sends before it
+    /// replies, so the reply is waited for by hand.
+    fn call(
+        &self,
+        request: Message,
+        timeout: Duration,
+    ) -> Result<Result<Message, dbus::Error>, WifiConfigError> {
+        let serial = self.conn.send(request).map_err(|()| {
+            WifiConfigError::activation_failed(dbus::Error::new_failed("failed to send request"))
+        })?;
+        let reply = Arc::new(Mutex::new(None));
+        let tokens = [MessageType::MethodReturn, MessageType::Error].map(|msg_type| {
+            let sink = Arc::clone(&reply);
+            let mut rule = MatchRule::new();
+            rule.msg_type = Some(msg_type);
+            self.conn.start_receive(
+                rule,
+                Box::new(move |message: Message, _: &Connection| {
+                    if message.get_reply_serial() != Some(serial) {
+                        return true;
+                    }
+                    *sink.lock().unwrap() = Some(message);
+                    false
+                }),
+            )
+        });
+
+        let deadline = Instant::now() + timeout;
+        let result = loop {
+            if let Some(mut message) = reply.lock().unwrap().take() {
+                let error = message.as_result().err();
+                break Ok(error.map_or(Ok(message), Err));
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break Err(WifiConfigError::ActivationTimeout(timeout));
+            }
+            if let Err(e) = self.conn.process(remaining) {
+                break Err(WifiConfigError::DeviceQueryFailed(e));
+            }
+        };
+        for token in tokens {
+            self.conn.stop_receive(token);
+        }
+        result
+    }
+}
+
+impl Drop for Agent<'_> {
+    fn drop(&mut self) {
+        let _: Result<(), _> = self
+            .conn
+            .with_proxy(IWD_BUS_NAME, AGENT_MANAGER_PATH, self.timeout)
+            .method_call(AGENT_MANAGER_INTERFACE, "UnregisterAgent", (&self.path,));
+        self.conn.stop_receive(self.token);
+    }
+}
+
+/// Answers an agent method call, recording what was asked for.
+///
+/// Only passphrases are handed out; requests for enterprise credentials are refused.
+fn agent_reply(call: &Message, passphrase: Option<&str>, requests: &mut AgentRequests) -> Message {
+    match call.member().as_deref() {
+        Some("RequestPassphrase") => {
+            requests.passphrase_requested = true;
+            match passphrase {
+                Some(passphrase) => call.method_return().append1(passphrase),
+                None => {
+                    requests.refused = true;
+                    canceled(call)
+                }
+            }
+        }
+        Some("Release" | "Cancel") => call.method_return(),
+        _ => {
+            requests.refused = true;
+            canceled(call)
+        }
+    }
+}
+
+/// The error reply refusing an agent request.
+fn canceled(call: &Message) -> Message {
+    let message = CString::new("no credentials for this request").expect("no NUL bytes");
+    call.error(&AGENT_CANCELED_ERROR.into(), &message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dbus::arg::Variant;
+
+    #[test]
+    fn agent_hands_out_the_passphrase() {
+        let mut call = Message::new_method_call(
+            "net.connman.iwd",
+            "/wifi_config/agent",
+            AGENT_INTERFACE,
+            "RequestPassphrase",
+        )
+        .unwrap();
+        // Replies refer to the call's serial, which is set when it is sent.
+        call.set_serial(1);
+
+        let mut requests = AgentRequests::default();
+        let reply = agent_reply(&call, Some("supersecret123"), &mut requests);
+        assert_eq!(reply.msg_type(), MessageType::MethodReturn);
+        assert_eq!(reply.get1::<&str>(), Some("supersecret123"));
+        assert!(requests.passphrase_requested && !requests.refused);
+
+        let mut requests = AgentRequests::default();
+        let reply = agent_reply(&call, None, &mut requests);
+        assert_eq!(reply.msg_type(), MessageType::Error);
+        assert!(requests.refused);
+    }
+
+    #[test]
+    fn finds_stations_and_known_networks() {
+        let object = |entries: &[(&str, &str, &str)]| {
+            let mut interfaces: HashMap<String, PropMap> = HashMap::new();
+            for (interface, key, value) in entries {
+                interfaces
+                    .entry(interface.to_string())
+                    .or_default()
+                    .insert(key.to_string(), Variant(Box::new(value.to_string())));
+            }
+            interfaces
+        };
+        let path = |path: &str| dbus::Path::new(path.to_string()).unwrap();
+        let objects: ManagedObjects = [
+            (
+                path("/net/connman/iwd/0/5"),
+                object(&[
+                    (DEVICE_INTERFACE, "Name", "wlan1"),
+                    (STATION_INTERFACE, "State", "disconnected"),
+                ]),
+            ),
+            (
+                path("/net/connman/iwd/0/4"),
+                object(&[
+                    (DEVICE_INTERFACE, "Name", "wlan0"),
+                    (STATION_INTERFACE, "State", "connected"),
+                ]),
+            ),
+            // An access point mode device has no Station interface.
+            (
+                path("/net/connman/iwd/0/3"),
+                object(&[(DEVICE_INTERFACE, "Name", "ap0")]),
+            ),
+            (
+                path("/net/connman/iwd/4f6666696365_psk"),
+                object(&[(KNOWN_NETWORK_INTERFACE, "Name", "Office")]),
+            ),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(find_station(&objects, None).unwrap().interface, "wlan0");
+        assert_eq!(
+            find_station(&objects, Some("wlan1")).unwrap().path,
+            path("/net/connman/iwd/0/5")
+        );
+        assert!(matches!(
+            find_station(&objects, Some("ap0")),
+            Err(WifiConfigError::DeviceNotFound(_))
+        ));
+        assert_eq!(
+            known_network(&objects, "Office"),
+            Some(path("/net/connman/iwd/4f6666696365_psk"))
+        );
+        assert_eq!(known_network(&objects, "Guest"), None);
+    }
+
+    #[test]
+    fn rejects_options_iwd_cant_apply() {
+        let supported = ConnectOptions {
+            security: Security::Auto(Some("supersecret123".into())),
+            hidden: true,
+            ..Default::default()
+        };
+        assert!(check_supported(&supported).is_ok());
+
+        for security in [
+            Security::Wep("abcde".into()),
+            Security::WpaPsk("ab".repeat(32).into()),
+        ] {
+            let options = ConnectOptions {
+                security,
+                ..Default::default()
+            };
+            assert!(matches!(
+                check_supported(&options),
+                Err(WifiConfigError::UnsupportedByBackend(_))
+            ));
+        }
+        assert!(matches!(
+            IwdBackend.scan_networks(true, DbusTimeouts::default()),
+            Err(WifiConfigError::UnsupportedByBackend(_))
+        ));
+    }
+}
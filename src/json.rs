@@ -0,0 +1,303 @@
+//! JSON profiles: a [`WifiConnection`] in a versioned envelope, and the serde helpers
+//! the config types use with the `serde` feature.
+
+use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize, Serializer};
+use std::time::Duration;
+
+use crate::{ConnectOptions, MacAddr, NetworkCandidate, Secret, WifiConfigError, WifiConnection};
+
+/// Envelope version written by [`to_json`] and the only one [`from_json`] reads.
+pub(crate) const PROFILE_VERSION: u32 = 1;
+
+/// Prefix of a secret that names an environment variable instead of holding the secret.
+const ENV_PREFIX: &str = "env:";
+
+#[derive(Serialize)]
+struct EnvelopeRef<'a> {
+    version: u32,
+    #[serde(flatten)]
+    connection: &'a WifiConnection,
+}
+
+/// Just the version, read before the rest so a newer profile fails with a clear message
+/// rather than whatever field changed shape.
+#[derive(Deserialize)]
+struct Version {
+    version: u32,
+}
+
+/// A [`WifiConnection`] as it appears in JSON, before validation.
+#[derive(Deserialize)]
+pub(crate) struct UncheckedConnection {
+    #[serde(deserialize_with = "ssid::deserialize")]
+    pub(crate) ssid: Vec<u8>,
+    #[serde(default)]
+    pub(crate) options: ConnectOptions,
+}
+
+/// Writes `connection` as a JSON profile; see [`WifiConnection::to_json`].
+pub(crate) fn to_json(connection: &WifiConnection) -> String {
+    let envelope = EnvelopeRef {
+        version: PROFILE_VERSION,
+        connection,
+    };
+    serde_json::to_string_pretty(&envelope).expect("connections always serialize")
+}
+
+/// Reads and validates a JSON profile; see [`WifiConnection::from_json`].
+pub(crate) fn from_json(json: &str) -> Result<WifiConnection, WifiConfigError> {
+    let invalid = |e: serde_json::Error| WifiConfigError::InvalidConfigFile(e.to_string());
+    let Version { version } = serde_json::from_str(json).map_err(invalid)?;
+    if version != PROFILE_VERSION {
+        return Err(WifiConfigError::InvalidConfigFile(format!(
+            "profile version {} is not supported; expected {}",
+            version, PROFILE_VERSION
+        )));
+    }
+    // The version is just an unknown field to `WifiConnection`.
+    serde_json::from_str(json).map_err(invalid)
+}
+
+/// Reads a JSON array of profiles; see [`NetworkCandidate::list_from_json`].
+pub(crate) fn candidates_from_json(json: &str) -> Result<Vec<NetworkCandidate>, WifiConfigError> {
+    let profiles: Vec<serde_json::Value> = serde_json::from_str(json)
+        .map_err(|e| WifiConfigError::InvalidConfigFile(e.to_string()))?;
+    profiles
+        .iter()
+        .enumerate()
+        .map(|(index, profile)| match from_json(&profile.to_string()) {
+            Ok(connection) => Ok(NetworkCandidate::from(connection)),
+            Err(WifiConfigError::InvalidConfigFile(reason)) => Err(
+                WifiConfigError::InvalidConfigFile(format!("candidate {}: {}", index, reason)),
+            ),
+            Err(e) => Err(e),
+        })
+        .collect()
+}
+
+/// Deserializes a secret, reading it from the environment variable `VAR` if it is given
+/// as `"env:VAR"`.
+pub(crate) fn secret<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Secret, D::Error> {
+    resolve_secret(String::deserialize(deserializer)?)
+        .map(Secret::from)
+        .map_err(de::Error::custom)
+}
+
+/// [`secret`] for optional secrets.
+pub(crate) fn optional_secret<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Secret>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|value| resolve_secret(value).map(Secret::from))
+        .transpose()
+        .map_err(de::Error::custom)
+}
+
+fn resolve_secret(value: String) -> Result<String, String> {
+    match value.strip_prefix(ENV_PREFIX) {
+        Some(name) => std::env::var(name)
+            .map_err(|_| format!("environment variable {} is not set or not UTF-8", name)),
+        None => Ok(value),
+    }
+}
+
+/// SSIDs as a string when they are UTF-8, and as an array of bytes otherwise.
+pub(crate) mod ssid {
+    use super::*;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Ssid {
+        Text(String),
+        Bytes(Vec<u8>),
+    }
+
+    pub(crate) fn serialize<S: Serializer>(ssid: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        match std::str::from_utf8(ssid) {
+            Ok(text) => serializer.serialize_str(text),
+            Err(_) => ssid.serialize(serializer),
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<u8>, D::Error> {
+        Ok(match Ssid::deserialize(deserializer)? {
+            Ssid::Text(text) => text.into_bytes(),
+            Ssid::Bytes(bytes) => bytes,
+        })
+    }
+}
+
+/// Durations as a number of seconds, e.g. `2.5`.
+pub(crate) mod duration_secs {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        duration: &Duration,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(duration.as_secs_f64())
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Duration, D::Error> {
+        let secs = f64::deserialize(deserializer)?;
+        Duration::try_from_secs_f64(secs)
+            .map_err(|_| de::Error::custom(format!("{} is not a valid number of seconds", secs)))
+    }
+}
+
+/// [`duration_secs`] for optional durations.
+pub(crate) mod option_duration_secs {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => duration_secs::serialize(duration, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        #[derive(Deserialize)]
+        struct Secs(#[serde(with = "duration_secs")] Duration);
+        Ok(Option::<Secs>::deserialize(deserializer)?.map(|Secs(duration)| duration))
+    }
+}
+
+/// MAC addresses as `"AA:BB:CC:DD:EE:FF"` rather than an array of octets.
+pub(crate) mod mac_octets {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        octets: &[u8; 6],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        MacAddr(*octets).serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<[u8; 6], D::Error> {
+        Ok(MacAddr::deserialize(deserializer)?.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EnterpriseAuth, Ipv4Config, MacPolicy, Security};
+
+    #[test]
+    fn round_trips_a_connection() {
+        let connection = WifiConnection::builder(&b"Caf\xe9"[..])
+            .security(Security::Enterprise(EnterpriseAuth::Peap {
+                identity: "alice".into(),
+                password: "supersecret123".into(),
+                anonymous_identity: None,
+            }))
+            .hidden(true)
+            .ipv4(Ipv4Config::Manual {
+                address: "192.168.1.50".parse().unwrap(),
+                prefix: 24,
+                gateway: Some("192.168.1.1".parse().unwrap()),
+            })
+            .mac_policy(MacPolicy::Explicit([0x02, 0, 0, 0, 0, 0x01]))
+            .activation_timeout(Duration::from_millis(2500))
+            .build()
+            .unwrap();
+
+        let json = to_json(&connection);
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["version"], 1);
+        assert_eq!(value["ssid"], serde_json::json!([67, 97, 102, 233]));
+        assert_eq!(value["options"]["activation_timeout"], 2.5);
+        assert_eq!(
+            value["options"]["mac_policy"]["explicit"],
+            "02:00:00:00:00:01"
+        );
+        assert_eq!(
+            value["options"]["security"]["enterprise"]["peap"]["identity"],
+            "alice"
+        );
+        assert_eq!(from_json(&json).unwrap(), connection);
+    }
+
+    #[test]
+    fn fills_in_defaults_and_reads_secrets_from_the_environment() {
+        std::env::set_var("WIFI_CONFIG_TEST_JSON_PSK", "supersecret123");
+        let connection = from_json(
+            r#"{
+                "version": 1,
+                "ssid": "Office",
+                "options": {
+                    "security": { "wpa_psk": "env:WIFI_CONFIG_TEST_JSON_PSK" },
+                    "autoconnect_priority": 10,
+                    "added_in_a_later_release": true
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(connection.ssid(), b"Office");
+        assert_eq!(
+            connection.options().security,
+            Security::WpaPsk("supersecret123".into())
+        );
+        assert_eq!(connection.options().autoconnect_priority, Some(10));
+        assert!(!connection.options().hidden);
+
+        let unset = from_json(
+            r#"{"version": 1, "ssid": "Office",
+                "options": {"security": {"wpa_psk": "env:WIFI_CONFIG_TEST_JSON_UNSET"}}}"#,
+        );
+        assert!(
+            matches!(unset, Err(WifiConfigError::InvalidConfigFile(reason))
+                if reason.contains("WIFI_CONFIG_TEST_JSON_UNSET is not set"))
+        );
+    }
+
+    #[test]
+    fn rejects_other_versions_and_invalid_connections() {
+        assert!(matches!(from_json(r#"{"version": 2, "ssid": "Office"}"#),
+                Err(WifiConfigError::InvalidConfigFile(reason)) if reason.contains("version 2")));
+        assert!(matches!(
+            from_json(r#"{"ssid": "Office"}"#),
+            Err(WifiConfigError::InvalidConfigFile(_))
+        ));
+        assert!(matches!(
+            from_json(
+                r#"{"version": 1, "ssid": "Office",
+                "options": {"security": {"wpa_psk": "short"}}}"#
+            ),
+            Err(WifiConfigError::InvalidConfigFile(_))
+        ));
+    }
+
+    #[test]
+    fn reads_candidate_lists() {
+        let candidates = candidates_from_json(
+            r#"[
+                {"version": 1, "ssid": "Depot", "options": {"security": {"wpa_psk": "supersecret123"}}},
+                {"version": 1, "ssid": "Field", "options": {"hidden": true}}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[0].ssid, b"Depot");
+        assert!(candidates[1].options.hidden);
+
+        let invalid = candidates_from_json(r#"[{"version": 1, "ssid": "Depot"}, {"version": 2}]"#);
+        assert!(
+            matches!(invalid, Err(WifiConfigError::InvalidConfigFile(reason))
+                if reason.starts_with("candidate 1:"))
+        );
+    }
+}
@@ -0,0 +1,1366 @@
+use dbus::arg::{prop_cast, ArgType, PropMap, RefArg, Variant};
+use dbus::blocking::Connection;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+
+use crate::client::{DbusNmClient, NmClient};
+use crate::settings::{self, ConnectionSettings};
+use crate::{device, ip, profiles, radio, security, version};
+use crate::{
+    ActivationHandle, DbusTimeouts, MacAddr, ProfileChange, SavedProfile, WifiConfigError,
+    WifiConnection,
+};
+
+/// Settings sections in the order NetworkManager writes them, with their keyfile names.
+const SECTIONS: &[(&str, &str)] = &[
+    ("connection", "connection"),
+    ("802-11-wireless", "wifi"),
+    ("802-11-wireless-security", "wifi-security"),
+    ("802-1x", "802-1x"),
+    ("ipv4", "ipv4"),
+    ("ipv6", "ipv6"),
+];
+
+/// Keys NetworkManager writes first in `[connection]`; the rest follow alphabetically.
+const LEADING_CONNECTION_KEYS: &[&str] = &["id", "uuid", "type"];
+
+impl WifiConnection {
+    /// Renders the connection as a NetworkManager keyfile (`.nmconnection`), for
+    /// provisioning systems where no D-Bus is available, e.g. while baking an image.
+    ///
+    /// The settings are the ones [`connect`](Self::connect) would send, in the layout
+    /// `nmcli` writes: `[connection]`, `[wifi]`, `[wifi-security]`, `[802-1x]`, `[ipv4]`
+    /// and `[ipv6]`. SSIDs that aren't printable UTF-8 are written as a list of bytes.
+    ///
+    /// A few things differ from connecting over D-Bus:
+    ///
+    /// - The interface name is saved as `connection.interface-name`, binding the profile
+    ///   to that device.
+    /// - No UUID is written; NetworkManager derives a stable one from the file's path
+    ///   when it loads the keyfile.
+    /// - [`Security::Auto`](crate::Security::Auto) can't scan, so it becomes WPA-PSK with
+    ///   a password and open without one.
+    /// - Secrets NetworkManager doesn't store (see
+    ///   [`SecretStorage`](crate::SecretStorage)) are left out; only their flags are
+    ///   written.
+    /// - The backend, device path and timeouts don't apply.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wifi_config::WifiConnection;
+    ///
+    /// let keyfile = WifiConnection::builder("Office")
+    ///     .password("supersecret123")
+    ///     .build()?
+    ///     .to_keyfile();
+    /// assert!(keyfile.contains("[wifi-security]\nkey-mgmt=wpa-psk\npsk=supersecret123\n"));
+    /// # Ok::<(), wifi_config::WifiConfigError>(())
+    /// ```
+    pub fn to_keyfile(&self) -> String {
+        let mut connection_settings =
+            settings::build_connection_settings(self.ssid(), self.options());
+        if let Some(interface) = &self.options().interface {
+            connection_settings
+                .entry("connection".into())
+                .or_default()
+                .insert(
+                    "interface-name".into(),
+                    Variant(Box::new(interface.clone())),
+                );
+        }
+        render(&connection_settings)
+    }
+
+    /// Writes [`to_keyfile`](Self::to_keyfile) to `path` with the permission bits
+    /// `mode`, replacing the file if it exists.
+    ///
+    /// NetworkManager ignores keyfiles that other users can access, so `mode` must not
+    /// grant any group or other permissions; `0o600` is the usual choice. The file must
+    /// also be owned by root, which is up to the caller. Keyfiles dropped into
+    /// `/etc/NetworkManager/system-connections` are picked up on NetworkManager's next
+    /// start, or after `nmcli connection reload`.
+    ///
+    /// # Errors
+    ///
+    /// [`WifiConfigError::KeyfileWrite`] if `mode` grants group or other permissions, or
+    /// the file can't be written.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use wifi_config::WifiConnection;
+    ///
+    /// WifiConnection::builder("Office")
+    ///     .password("supersecret123")
+    ///     .build()?
+    ///     .write_keyfile("/etc/NetworkManager/system-connections/Office.nmconnection", 0o600)?;
+    /// # Ok::<(), wifi_config::WifiConfigError>(())
+    /// ```
+    pub fn write_keyfile(&self, path: impl AsRef<Path>, mode: u32) -> Result<(), WifiConfigError> {
+        let path = path.as_ref();
+        let write_error = |e| WifiConfigError::KeyfileWrite(path.to_path_buf(), e);
+        if mode & 0o077 != 0 {
+            return Err(write_error(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "mode {:o} lets other users read the password; NetworkManager ignores such keyfiles",
+                    mode
+                ),
+            )));
+        }
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(mode)
+            .open(path)
+            .map_err(write_error)?;
+        // `mode` only applies when the file is created.
+        fs::set_permissions(path, fs::Permissions::from_mode(mode)).map_err(write_error)?;
+        file.write_all(self.to_keyfile().as_bytes())
+            .map_err(write_error)
+    }
+}
+
+/// Renders `connection_settings` in the keyfile format.
+fn render(connection_settings: &ConnectionSettings) -> String {
+    let mut sections = Vec::new();
+    for (name, keyfile_name) in SECTIONS {
+        let section = connection_settings.get(*name);
+        let mut entries = match section {
+            Some(section) => section_entries(name, section),
+            // nmcli always writes the IP sections, even when everything is automatic.
+            None if name.starts_with("ipv") => vec![("method".into(), "auto".into())],
+            None => continue,
+        };
+        entries.sort_by(|(a, _), (b, _)| {
+            let rank = |key: &String| {
+                LEADING_CONNECTION_KEYS
+                    .iter()
+                    .position(|leading| *name == "connection" && leading == key)
+                    .unwrap_or(LEADING_CONNECTION_KEYS.len())
+            };
+            rank(a).cmp(&rank(b)).then_with(|| a.cmp(b))
+        });
+
+        let mut text = format!("[{}]\n", keyfile_name);
+        for (key, value) in entries {
+            text.push_str(&format!("{}={}\n", key, value));
+        }
+        sections.push(text);
+    }
+    sections.join("\n")
+}
+
+/// Whether `key` is a secret whose flags keep it out of the profile.
+fn is_unsaved_secret(name: &str, key: &str, section: &dbus::arg::PropMap) -> bool {
+    security::SECRETS
+        .iter()
+        .any(|(secret_section, secret, flags)| {
+            *secret_section == name
+                && *secret == key
+                && section
+                    .get(*flags)
+                    .and_then(|flags| flags.0.as_u64())
+                    .is_some_and(|flags| flags != 0)
+        })
+}
+
+/// Converts one settings section into keyfile entries.
+fn section_entries(name: &str, section: &dbus::arg::PropMap) -> Vec<(String, String)> {
+    let mut entries = Vec::new();
+    for (key, Variant(value)) in section {
+        if is_unsaved_secret(name, key, section) {
+            continue;
+        }
+        let value = &**value;
+        match (name, key.as_str()) {
+            ("connection", "type") => {
+                let kind = match value.as_str() {
+                    Some("802-11-wireless") => "wifi",
+                    kind => kind.unwrap_or_default(),
+                };
+                entries.push((key.clone(), kind.to_string()));
+            }
+            ("802-11-wireless", "ssid") => entries.push((key.clone(), ssid_value(&bytes(value)))),
+            ("802-11-wireless", "bssid" | "cloned-mac-address") => {
+                let octets: Vec<String> =
+                    bytes(value).iter().map(|b| format!("{:02X}", b)).collect();
+                entries.push((key.clone(), octets.join(":")));
+            }
+            ("802-1x", "ca-cert" | "client-cert" | "private-key") => {
+                // The D-Bus blob is `file://<path>` plus a NUL; keyfiles take the plain path.
+                let blob = bytes(value);
+                let path = blob.strip_prefix(b"file://").unwrap_or(&blob);
+                let path = path.strip_suffix(b"\0").unwrap_or(path);
+                entries.push((key.clone(), escape(&String::from_utf8_lossy(path))));
+            }
+            ("ipv4" | "ipv6", "address-data") => {
+                // The gateway is written after the first address, as `nmcli` does.
+                let gateway = section
+                    .get("gateway")
+                    .and_then(|gateway| gateway.0.as_str());
+                for (i, address) in value.as_iter().into_iter().flatten().enumerate() {
+                    let mut text = address_value(address);
+                    if let (0, Some(gateway)) = (i, gateway) {
+                        text.push(',');
+                        text.push_str(gateway);
+                    }
+                    entries.push((format!("address{}", i + 1), text));
+                }
+            }
+            ("ipv4" | "ipv6", "gateway") if section.contains_key("address-data") => {}
+            ("ipv4", "dns") => {
+                let servers = value.as_iter().into_iter().flatten().map(|server| {
+                    let server = server.as_u64().unwrap_or_default() as u32;
+                    // Stored in network byte order; see `ip::ipv4_dns_value`.
+                    Ipv4Addr::from(u32::from_be(server)).to_string()
+                });
+                entries.push((key.clone(), list(servers)));
+            }
+            ("ipv6", "dns") => {
+                let servers = value.as_iter().into_iter().flatten().map(|server| {
+                    let octets: [u8; 16] = bytes(server).try_into().unwrap_or_default();
+                    Ipv6Addr::from(octets).to_string()
+                });
+                entries.push((key.clone(), list(servers)));
+            }
+            _ => entries.push((key.clone(), plain_value(value))),
+        }
+    }
+    entries
+}
+
+/// Formats a string, boolean, integer or list value.
+fn plain_value(value: &dyn RefArg) -> String {
+    match value.arg_type() {
+        ArgType::String => escape(value.as_str().unwrap_or_default()),
+        ArgType::Boolean => (value.as_u64() == Some(1)).to_string(),
+        ArgType::Array => list(value.as_iter().into_iter().flatten().map(plain_value)),
+        _ => match value.as_i64() {
+            Some(number) => number.to_string(),
+            None => value.as_u64().unwrap_or_default().to_string(),
+        },
+    }
+}
+
+/// Formats an SSID the way NetworkManager does: as text if it is printable UTF-8,
+/// otherwise as the list of its bytes.
+fn ssid_value(ssid: &[u8]) -> String {
+    match std::str::from_utf8(ssid) {
+        Ok(text) if !text.chars().any(char::is_control) => escape(text).replace(';', "\\;"),
+        _ => list(ssid.iter().map(u8::to_string)),
+    }
+}
+
+/// Formats one `address-data` entry as `address/prefix`.
+fn address_value(entry: &dyn RefArg) -> String {
+    // A dict iterates as alternating keys and values.
+    let items: Vec<&dyn RefArg> = entry.as_iter().into_iter().flatten().collect();
+    let field = |name: &str| {
+        items
+            .chunks(2)
+            .find(|pair| pair[0].as_str() == Some(name))
+            .map(|pair| pair[1])
+    };
+    let address = field("address")
+        .and_then(|a| a.as_str())
+        .unwrap_or_default();
+    let prefix = field("prefix").and_then(|p| p.as_u64()).unwrap_or_default();
+    format!("{}/{}", address, prefix)
+}
+
+/// Collects the bytes of an `ay` value.
+fn bytes(value: &dyn RefArg) -> Vec<u8> {
+    value
+        .as_iter()
+        .into_iter()
+        .flatten()
+        .filter_map(|b| b.as_u64())
+        .map(|b| b as u8)
+        .collect()
+}
+
+/// Joins list items, each followed by `;` as in `dns=10.0.0.53;10.0.0.54;`.
+fn list(items: impl Iterator<Item = String>) -> String {
+    items.map(|item| format!("{};", item)).collect()
+}
+
+/// Escapes a string value like GLib's key files: backslashes, line breaks, tabs and a
+/// leading space.
+fn escape(value: &str) -> String {
+    let mut escaped = String::new();
+    for (i, c) in value.chars().enumerate() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            ' ' if i == 0 => escaped.push_str("\\s"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// How the value of a keyfile key is typed on D-Bus.
+#[derive(Debug, Clone, Copy)]
+enum ValueType {
+    Str,
+    Bool,
+    Int,
+    Int64,
+    Uint,
+    Uint64,
+    StrList,
+    /// `aa:bb:cc:dd:ee:ff`, sent as `ay`.
+    Mac,
+    /// A certificate or key path, sent as a `file://` blob.
+    Cert,
+}
+
+/// D-Bus types of the keys read from a keyfile, by section; `ip` stands for both `ipv4`
+/// and `ipv6`. Keys not listed here get a type guessed from their value.
+const KEY_TYPES: &[(&str, &str, ValueType)] = {
+    use ValueType::*;
+    &[
+        ("connection", "id", Str),
+        ("connection", "uuid", Str),
+        ("connection", "interface-name", Str),
+        ("connection", "autoconnect", Bool),
+        ("connection", "autoconnect-priority", Int),
+        ("connection", "autoconnect-retries", Int),
+        ("connection", "auth-retries", Int),
+        ("connection", "metered", Int),
+        ("connection", "permissions", StrList),
+        ("connection", "secondaries", StrList),
+        ("connection", "stable-id", Str),
+        ("connection", "timestamp", Uint64),
+        ("connection", "zone", Str),
+        ("connection", "mdns", Int),
+        ("connection", "llmnr", Int),
+        ("connection", "multi-connect", Int),
+        ("connection", "wait-device-timeout", Int),
+        ("802-11-wireless", "mode", Str),
+        ("802-11-wireless", "band", Str),
+        ("802-11-wireless", "channel", Uint),
+        ("802-11-wireless", "bssid", Mac),
+        ("802-11-wireless", "mac-address", Mac),
+        ("802-11-wireless", "hidden", Bool),
+        ("802-11-wireless", "powersave", Uint),
+        ("802-11-wireless", "mac-address-randomization", Uint),
+        ("802-11-wireless", "mtu", Uint),
+        ("802-11-wireless", "seen-bssids", StrList),
+        ("802-11-wireless", "wake-on-wlan", Uint),
+        ("802-11-wireless-security", "key-mgmt", Str),
+        ("802-11-wireless-security", "auth-alg", Str),
+        ("802-11-wireless-security", "psk", Str),
+        ("802-11-wireless-security", "psk-flags", Uint),
+        ("802-11-wireless-security", "pmf", Int),
+        ("802-11-wireless-security", "proto", StrList),
+        ("802-11-wireless-security", "pairwise", StrList),
+        ("802-11-wireless-security", "group", StrList),
+        ("802-11-wireless-security", "wep-key0", Str),
+        ("802-11-wireless-security", "wep-key1", Str),
+        ("802-11-wireless-security", "wep-key2", Str),
+        ("802-11-wireless-security", "wep-key3", Str),
+        ("802-11-wireless-security", "wep-key-flags", Uint),
+        ("802-11-wireless-security", "wep-key-type", Uint),
+        ("802-11-wireless-security", "wep-tx-keyidx", Uint),
+        ("802-11-wireless-security", "leap-username", Str),
+        ("802-11-wireless-security", "leap-password", Str),
+        ("802-11-wireless-security", "leap-password-flags", Uint),
+        ("802-11-wireless-security", "wps-method", Uint),
+        ("802-11-wireless-security", "fils", Int),
+        ("802-1x", "eap", StrList),
+        ("802-1x", "identity", Str),
+        ("802-1x", "anonymous-identity", Str),
+        ("802-1x", "password", Str),
+        ("802-1x", "password-flags", Uint),
+        ("802-1x", "phase1-peapver", Str),
+        ("802-1x", "phase2-auth", Str),
+        ("802-1x", "phase2-autheap", Str),
+        ("802-1x", "ca-cert", Cert),
+        ("802-1x", "ca-path", Str),
+        ("802-1x", "client-cert", Cert),
+        ("802-1x", "private-key", Cert),
+        ("802-1x", "private-key-password", Str),
+        ("802-1x", "private-key-password-flags", Uint),
+        ("802-1x", "phase2-ca-cert", Cert),
+        ("802-1x", "phase2-client-cert", Cert),
+        ("802-1x", "phase2-private-key", Cert),
+        ("802-1x", "domain-suffix-match", Str),
+        ("802-1x", "domain-match", Str),
+        ("802-1x", "subject-match", Str),
+        ("802-1x", "altsubject-matches", StrList),
+        ("802-1x", "system-ca-certs", Bool),
+        ("802-1x", "auth-timeout", Int),
+        ("802-1x", "optional", Bool),
+        ("ip", "method", Str),
+        ("ip", "gateway", Str),
+        ("ip", "dns-search", StrList),
+        ("ip", "dns-options", StrList),
+        ("ip", "dns-priority", Int),
+        ("ip", "ignore-auto-dns", Bool),
+        ("ip", "ignore-auto-routes", Bool),
+        ("ip", "never-default", Bool),
+        ("ip", "may-fail", Bool),
+        ("ip", "route-metric", Int64),
+        ("ip", "route-table", Uint),
+        ("ip", "dhcp-hostname", Str),
+        ("ip", "dhcp-send-hostname", Bool),
+        ("ip", "dhcp-timeout", Int),
+        ("ip", "dhcp-client-id", Str),
+        ("ip", "dhcp-iaid", Str),
+        ("ip", "dad-timeout", Int),
+        ("ip", "required-timeout", Int),
+        ("ip", "ip6-privacy", Int),
+        ("ip", "addr-gen-mode", Int),
+        ("ip", "token", Str),
+        ("ip", "dhcp-duid", Str),
+        ("ip", "ra-timeout", Int),
+    ]
+};
+
+/// A Wi-Fi profile read from a NetworkManager keyfile, ready to be saved or activated.
+///
+/// Created with [`import_keyfile`] or [`ConnectionConfig::from_keyfile`]. Unlike a
+/// [`WifiConnection`], it holds the profile's settings as they will be sent, including
+/// sections this crate doesn't know about.
+pub struct ConnectionConfig {
+    settings: ConnectionSettings,
+    timeouts: DbusTimeouts,
+}
+
+/// Only shows the id and SSID, so the secrets can't end up in a log line.
+impl fmt::Debug for ConnectionConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectionConfig")
+            .field("id", &self.id())
+            .field("ssid", &String::from_utf8_lossy(self.ssid()))
+            .finish_non_exhaustive()
+    }
+}
+
+impl ConnectionConfig {
+    /// Parses the contents of a keyfile; see [`import_keyfile`].
+    ///
+    /// # Errors
+    ///
+    /// - [`WifiConfigError::InvalidKeyfile`] if the file is malformed, a value doesn't
+    ///   fit its key, or it isn't a Wi-Fi profile with an SSID.
+    pub fn from_keyfile(contents: &str) -> Result<Self, WifiConfigError> {
+        let settings = parse(contents)?;
+        let kind = settings
+            .get("connection")
+            .and_then(|connection| prop_cast::<String>(connection, "type"));
+        match kind.map(String::as_str) {
+            Some("802-11-wireless") => {}
+            Some(kind) => {
+                return Err(WifiConfigError::InvalidKeyfile(format!(
+                "connection.type is '{}'; only Wi-Fi (802-11-wireless) profiles can be imported",
+                kind
+            )))
+            }
+            None => return Err(WifiConfigError::InvalidKeyfile(
+                "connection.type is missing; only Wi-Fi (802-11-wireless) profiles can be imported"
+                    .into(),
+            )),
+        }
+        if profiles::wireless_ssid(&settings).is_none() {
+            return Err(WifiConfigError::InvalidKeyfile(
+                "the [wifi] section has no ssid".into(),
+            ));
+        }
+        Ok(ConnectionConfig {
+            settings,
+            timeouts: DbusTimeouts::default(),
+        })
+    }
+
+    /// The raw SSID bytes.
+    pub fn ssid(&self) -> &[u8] {
+        profiles::wireless_ssid(&self.settings).map_or(&[], Vec::as_slice)
+    }
+
+    /// The profile name (`connection.id`), if the keyfile sets one.
+    pub fn id(&self) -> Option<&str> {
+        self.connection_string("id")
+    }
+
+    /// The profile UUID (`connection.uuid`), if the keyfile sets one.
+    pub fn uuid(&self) -> Option<&str> {
+        self.connection_string("uuid")
+    }
+
+    /// Uses `timeouts` for the calls [`save`](Self::save) and [`connect`](Self::connect)
+    /// make instead of the defaults.
+    pub fn with_timeouts(mut self, timeouts: DbusTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    fn connection_string(&self, key: &str) -> Option<&str> {
+        self.settings
+            .get("connection")
+            .and_then(|connection| prop_cast::<String>(connection, key))
+            .map(String::as_str)
+    }
+
+    /// Saves the profile without activating it.
+    ///
+    /// If the keyfile has a UUID and a saved profile has the same one, that profile is
+    /// replaced with `Update`; otherwise the profile is added with `AddConnection`, with
+    /// a new UUID if the keyfile has none.
+    ///
+    /// # Errors
+    ///
+    /// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+    /// - [`WifiConfigError::InvalidSettings`] / [`WifiConfigError::ActivationFailed`] if
+    ///   NetworkManager rejects the profile.
+    pub fn save(self) -> Result<SavedProfile, WifiConfigError> {
+        let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+        let timeouts = self.timeouts;
+        self.save_with(&DbusNmClient::new(&conn, timeouts))
+    }
+
+    /// Saves the profile like [`save`](Self::save) and activates it, without waiting for
+    /// the connection to come up.
+    ///
+    /// The device is the one named by `connection.interface-name` if the keyfile binds
+    /// the profile to one, the best usable Wi-Fi device otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - [`WifiConfigError::RadioDisabled`] / [`WifiConfigError::HardwareRfkill`] if the
+    ///   Wi-Fi radio is off.
+    /// - [`WifiConfigError::NoWifiDevice`] / [`WifiConfigError::DeviceNotFound`] if there
+    ///   is no device to use.
+    /// - [`WifiConfigError::ActivationFailed`] if NetworkManager rejects the profile.
+    /// - Otherwise the same as [`save`](Self::save).
+    pub fn connect(self) -> Result<ActivationHandle, WifiConfigError> {
+        let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+        let timeouts = self.timeouts;
+        self.connect_with(&DbusNmClient::new(&conn, timeouts))
+    }
+
+    /// The saved profile with the keyfile's UUID, if it has one.
+    fn existing_profile(
+        &self,
+        client: &dyn NmClient,
+    ) -> Result<Option<dbus::Path<'static>>, WifiConfigError> {
+        match self.uuid() {
+            Some(uuid) => Ok(
+                profiles::find_saved_connection(client, self.ssid(), Some(uuid))?
+                    .map(|(path, _)| path),
+            ),
+            None => Ok(None),
+        }
+    }
+
+    fn save_with(mut self, client: &dyn NmClient) -> Result<SavedProfile, WifiConfigError> {
+        version::fit_settings(client, &mut self.settings)?;
+        let uuid = settings::assign_uuid(&mut self.settings);
+        log::debug!(
+            "settings for {}: {}",
+            String::from_utf8_lossy(self.ssid()),
+            settings::setting_keys(&self.settings)
+        );
+        match self.existing_profile(client)? {
+            Some(settings_path) => {
+                client.update(&settings_path, self.settings)?;
+                log::info!("replaced profile {} with the keyfile", settings_path);
+                Ok(SavedProfile {
+                    settings_path,
+                    uuid,
+                    change: ProfileChange::Updated,
+                })
+            }
+            None => {
+                let settings_path = client.add_connection(self.settings)?;
+                log::info!("added profile {} from the keyfile", settings_path);
+                Ok(SavedProfile {
+                    settings_path,
+                    uuid,
+                    change: ProfileChange::Created,
+                })
+            }
+        }
+    }
+
+    fn connect_with(mut self, client: &dyn NmClient) -> Result<ActivationHandle, WifiConfigError> {
+        let interface = self.connection_string("interface-name").map(str::to_string);
+        radio::ensure_radio(client, false, interface.as_deref())?;
+        let device =
+            device::select_wifi_device(device::wifi_devices(client)?, None, interface.as_deref())?;
+        let existing = self.existing_profile(client)?;
+        version::fit_settings(client, &mut self.settings)?;
+        let uuid = settings::assign_uuid(&mut self.settings);
+        let (settings_path, active_path, change) = match existing {
+            Some(settings_path) => {
+                client.update(&settings_path, self.settings)?;
+                let specific_object = dbus::Path::new("/").unwrap();
+                let active_path =
+                    client.activate_connection(&settings_path, &device.path, &specific_object)?;
+                (settings_path, active_path, ProfileChange::Updated)
+            }
+            None => {
+                let (settings_path, active_path) =
+                    crate::add_and_activate(client, &device.path, self.settings)?;
+                (settings_path, active_path, ProfileChange::Created)
+            }
+        };
+        log::info!(
+            "activating profile {} from the keyfile on {}",
+            settings_path,
+            device.interface
+        );
+        Ok(ActivationHandle {
+            settings_path,
+            active_path,
+            interface: device.interface,
+            additional: Vec::new(),
+            uuid,
+            change,
+            detected_security: None,
+            connectivity: None,
+            portal_url: None,
+            ip: None,
+            active: None,
+            access_point: None,
+        })
+    }
+}
+
+/// Reads a NetworkManager keyfile (`.nmconnection`), e.g. one written by other tooling,
+/// to save or activate it over D-Bus with [`ConnectionConfig::save`] or
+/// [`ConnectionConfig::connect`].
+///
+/// # Behavior
+///
+/// - Reads the keyfile's sections under their D-Bus names (`[wifi]` is
+///   `802-11-wireless`, `[wifi-security]` is `802-11-wireless-security`) and converts
+///   each value to its D-Bus type: SSIDs given as a list of bytes, MAC addresses,
+///   `address1=192.168.1.50/24,192.168.1.1` and `route1=...` entries, DNS servers and
+///   certificate paths included.
+/// - Sections and keys this crate doesn't know are passed through, typed as booleans,
+///   integers or strings by how their value looks; NetworkManager may reject a key whose
+///   type was guessed wrong. Route options (`route1_options`) are dropped.
+/// - Like NetworkManager, names the profile after the file (without `.nmconnection`) if
+///   it has no `connection.id`.
+/// - Certificate paths are used as they are, so relative ones resolve against
+///   NetworkManager's working directory rather than the keyfile's.
+///
+/// # Errors
+///
+/// - [`WifiConfigError::ConfigFileRead`] if the file can't be read.
+/// - [`WifiConfigError::InvalidKeyfile`] if the file is malformed, a value doesn't fit
+///   its key, or it isn't a Wi-Fi profile with an SSID.
+///
+/// # Example
+///
+/// ```no_run
+/// let profile = wifi_config::import_keyfile("Office.nmconnection")?.save()?;
+/// println!("saved as {}", profile.uuid);
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn import_keyfile(path: impl AsRef<Path>) -> Result<ConnectionConfig, WifiConfigError> {
+    let path = path.as_ref();
+    let contents = fs::read_to_string(path)
+        .map_err(|e| WifiConfigError::ConfigFileRead(path.to_path_buf(), e))?;
+    let mut config = ConnectionConfig::from_keyfile(&contents)?;
+    if config.id().is_none() {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let id = name
+            .strip_suffix(".nmconnection")
+            .unwrap_or(&name)
+            .to_string();
+        config
+            .settings
+            .entry("connection".into())
+            .or_default()
+            .insert("id".into(), Variant(Box::new(id)));
+    }
+    Ok(config)
+}
+
+/// Parses a keyfile into settings, with each section under its D-Bus name.
+fn parse(contents: &str) -> Result<ConnectionSettings, WifiConfigError> {
+    let mut sections: Vec<(String, Vec<(String, String)>)> = Vec::new();
+    let mut current = None;
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim_start();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line
+            .trim_end()
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            let name = SECTIONS
+                .iter()
+                .find(|(_, keyfile_name)| *keyfile_name == name)
+                .map_or(name, |(name, _)| *name);
+            // A repeated section continues the earlier one, as with GLib.
+            current = match sections.iter().position(|(existing, _)| existing == name) {
+                Some(index) => Some(index),
+                None => {
+                    sections.push((name.to_string(), Vec::new()));
+                    Some(sections.len() - 1)
+                }
+            };
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(WifiConfigError::InvalidKeyfile(format!(
+                "line {}: expected a [section] or key=value",
+                i + 1
+            )));
+        };
+        let Some((_, entries)) = current.map(|index| &mut sections[index]) else {
+            return Err(WifiConfigError::InvalidKeyfile(format!(
+                "line {}: {} is outside of any section",
+                i + 1,
+                key.trim_end()
+            )));
+        };
+        entries.push((key.trim_end().to_string(), value.trim_start().to_string()));
+    }
+
+    let mut connection_settings = ConnectionSettings::new();
+    for (name, entries) in sections {
+        let section = parse_section(&name, &entries)?;
+        connection_settings.insert(name, section);
+    }
+    Ok(connection_settings)
+}
+
+/// Converts the entries of the section `name` into D-Bus values.
+fn parse_section(name: &str, entries: &[(String, String)]) -> Result<PropMap, WifiConfigError> {
+    let is_ip = name == "ipv4" || name == "ipv6";
+    let mut section = PropMap::new();
+    let mut addresses = Vec::new();
+    let mut routes = Vec::new();
+    let mut address_gateway = None;
+    for (key, value) in entries {
+        let invalid = |reason: String| {
+            WifiConfigError::InvalidKeyfile(format!("{}.{}: {}", name, key, reason))
+        };
+        let value: Box<dyn RefArg> = match (name, key.as_str()) {
+            ("connection", "type") => {
+                let kind = match unescape(value).as_str() {
+                    "wifi" => "802-11-wireless".to_string(),
+                    kind => kind.to_string(),
+                };
+                Box::new(kind)
+            }
+            ("802-11-wireless", "ssid") => Box::new(parse_ssid(value)),
+            // The keyfile key holds both the policy words and fixed addresses, which
+            // D-Bus splits into two properties; see `MacPolicy::apply`.
+            ("802-11-wireless", "cloned-mac-address") => match value.parse::<MacAddr>() {
+                Ok(mac) => Box::new(mac.0.to_vec()),
+                Err(_) => {
+                    section.insert(
+                        "assigned-mac-address".into(),
+                        Variant(Box::new(unescape(value))),
+                    );
+                    continue;
+                }
+            },
+            ("ipv4", "dns") => {
+                let servers = split_list(value)
+                    .iter()
+                    .map(|server| server.parse().map(ip::ipv4_dns_value))
+                    .collect::<Result<Vec<u32>, _>>()
+                    .map_err(|e| invalid(e.to_string()))?;
+                Box::new(servers)
+            }
+            ("ipv6", "dns") => {
+                let servers = split_list(value)
+                    .iter()
+                    .map(|server| server.parse().map(|s: Ipv6Addr| s.octets().to_vec()))
+                    .collect::<Result<Vec<Vec<u8>>, _>>()
+                    .map_err(|e| invalid(e.to_string()))?;
+                Box::new(servers)
+            }
+            _ if is_ip && numbered(key, &["address", "addresses"]).is_some() => {
+                let (address, gateway) = parse_address(name, value).map_err(invalid)?;
+                if addresses.is_empty() {
+                    address_gateway = gateway;
+                }
+                addresses.push((numbered(key, &["address", "addresses"]), address));
+                continue;
+            }
+            _ if is_ip && numbered(key, &["route", "routes"]).is_some() => {
+                routes.push((
+                    numbered(key, &["route", "routes"]),
+                    parse_route(name, value).map_err(invalid)?,
+                ));
+                continue;
+            }
+            _ if is_ip && key.starts_with("route") && key.ends_with("_options") => {
+                log::warn!("dropping {}.{}: route options aren't supported", name, key);
+                continue;
+            }
+            _ => {
+                let table_section = if is_ip { "ip" } else { name };
+                let value_type = KEY_TYPES
+                    .iter()
+                    .find(|(section, known, _)| *section == table_section && known == key)
+                    .map(|(_, _, value_type)| *value_type);
+                match value_type {
+                    Some(value_type) => parse_value(value_type, value).map_err(invalid)?,
+                    None => guess_value(value),
+                }
+            }
+        };
+        section.insert(key.clone(), Variant(value));
+    }
+
+    if !addresses.is_empty() {
+        addresses.sort_by_key(|(index, _)| *index);
+        let address_data: Vec<PropMap> =
+            addresses.into_iter().map(|(_, address)| address).collect();
+        section.insert("address-data".into(), Variant(Box::new(address_data)));
+        if let Some(gateway) = address_gateway {
+            section
+                .entry("gateway".into())
+                .or_insert_with(|| Variant(Box::new(gateway)));
+        }
+    }
+    if !routes.is_empty() {
+        routes.sort_by_key(|(index, _)| *index);
+        let route_data: Vec<PropMap> = routes.into_iter().map(|(_, route)| route).collect();
+        section.insert("route-data".into(), Variant(Box::new(route_data)));
+    }
+    Ok(section)
+}
+
+/// The number of a numbered key such as `address2` (0 without a number), if `key` is
+/// one of `prefixes` followed by digits.
+fn numbered(key: &str, prefixes: &[&str]) -> Option<u32> {
+    prefixes.iter().find_map(|prefix| {
+        let number = key.strip_prefix(prefix)?;
+        if number.is_empty() {
+            Some(0)
+        } else if number.bytes().all(|b| b.is_ascii_digit()) {
+            number.parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+/// Converts a value of a known type.
+fn parse_value(value_type: ValueType, value: &str) -> Result<Box<dyn RefArg>, String> {
+    fn number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+        value
+            .trim_end()
+            .parse()
+            .map_err(|_| format!("'{}' is not a valid number", value))
+    }
+    Ok(match value_type {
+        ValueType::Str => Box::new(unescape(value)),
+        ValueType::Bool => match value.trim_end() {
+            "true" | "1" => Box::new(true),
+            "false" | "0" => Box::new(false),
+            _ => return Err(format!("'{}' is not true or false", value)),
+        },
+        ValueType::Int => Box::new(number::<i32>(value)?),
+        ValueType::Int64 => Box::new(number::<i64>(value)?),
+        ValueType::Uint => Box::new(number::<u32>(value)?),
+        ValueType::Uint64 => Box::new(number::<u64>(value)?),
+        ValueType::StrList => Box::new(split_list(value)),
+        ValueType::Mac => Box::new(
+            value
+                .trim_end()
+                .parse::<MacAddr>()
+                .map_err(|e| e.to_string())?
+                .0
+                .to_vec(),
+        ),
+        ValueType::Cert => {
+            let path = unescape(value);
+            if path.starts_with("data:") {
+                return Err("embedded certificates aren't supported; give a path".into());
+            }
+            let path = path.strip_prefix("file://").unwrap_or(&path);
+            let mut blob = b"file://".to_vec();
+            blob.extend_from_slice(path.as_bytes());
+            blob.push(0);
+            Box::new(blob)
+        }
+    })
+}
+
+/// Types a value of an unknown key by how it looks: a boolean, a 32-bit integer, or a
+/// string.
+fn guess_value(value: &str) -> Box<dyn RefArg> {
+    match value {
+        "true" => Box::new(true),
+        "false" => Box::new(false),
+        _ => match value.parse::<i32>() {
+            Ok(number) => Box::new(number),
+            Err(_) => Box::new(unescape(value)),
+        },
+    }
+}
+
+/// Parses an SSID written as text or, the way NetworkManager writes SSIDs that aren't
+/// printable UTF-8, as a list of bytes (`67;97;102;233;`).
+fn parse_ssid(value: &str) -> Vec<u8> {
+    let bytes: Option<Vec<u8>> = value
+        .strip_suffix(';')
+        .unwrap_or(value)
+        .split(';')
+        .map(|byte| byte.parse().ok())
+        .collect();
+    match bytes {
+        Some(bytes) if value.contains(';') => bytes,
+        _ => unescape(value).into_bytes(),
+    }
+}
+
+/// Parses `address/prefix[,gateway]` into an `address-data` entry and the gateway.
+fn parse_address(family: &str, value: &str) -> Result<(PropMap, Option<String>), String> {
+    let (address, gateway) = match value.split_once(',') {
+        Some((address, gateway)) => (address, Some(gateway.trim().to_string())),
+        None => (value, None),
+    };
+    let (address, prefix) = parse_prefixed(family, address)?;
+    let mut entry = PropMap::new();
+    entry.insert("address".into(), Variant(Box::new(address)));
+    entry.insert("prefix".into(), Variant(Box::new(prefix)));
+    Ok((entry, gateway))
+}
+
+/// Parses `dest/prefix[,next-hop[,metric]]` into a `route-data` entry.
+fn parse_route(family: &str, value: &str) -> Result<PropMap, String> {
+    let mut parts = value.split(',').map(str::trim);
+    let (dest, prefix) = parse_prefixed(family, parts.next().unwrap_or_default())?;
+    let mut entry = PropMap::new();
+    entry.insert("dest".into(), Variant(Box::new(dest)));
+    entry.insert("prefix".into(), Variant(Box::new(prefix)));
+    if let Some(next_hop) = parts.next().filter(|hop| !hop.is_empty()) {
+        let next_hop: IpAddr = next_hop
+            .parse()
+            .map_err(|_| format!("'{}' is not an IP address", next_hop))?;
+        if !next_hop.is_unspecified() {
+            entry.insert("next-hop".into(), Variant(Box::new(next_hop.to_string())));
+        }
+    }
+    if let Some(metric) = parts.next() {
+        let metric: u32 = metric
+            .parse()
+            .map_err(|_| format!("'{}' is not a valid metric", metric))?;
+        entry.insert("metric".into(), Variant(Box::new(metric)));
+    }
+    Ok(entry)
+}
+
+/// Parses `address[/prefix]` of the IP `family`; the prefix defaults to 24 for IPv4 and
+/// 64 for IPv6, as in NetworkManager.
+fn parse_prefixed(family: &str, value: &str) -> Result<(String, u32), String> {
+    let (address, prefix) = match value.trim().split_once('/') {
+        Some((address, prefix)) => (address, Some(prefix)),
+        None => (value.trim(), None),
+    };
+    let (parsed, max_prefix, default_prefix) = match family {
+        "ipv4" => (address.parse::<Ipv4Addr>().map(IpAddr::from), 32, 24),
+        _ => (address.parse::<Ipv6Addr>().map(IpAddr::from), 128, 64),
+    };
+    let address = parsed.map_err(|_| format!("'{}' is not an {} address", address, family))?;
+    let prefix = match prefix {
+        Some(prefix) => prefix
+            .parse()
+            .ok()
+            .filter(|prefix| *prefix <= max_prefix)
+            .ok_or_else(|| format!("'{}' is not a valid prefix length", prefix))?,
+        None => default_prefix,
+    };
+    Ok((address.to_string(), prefix))
+}
+
+/// Splits a list value such as `10.0.0.53;10.0.0.54;` at the unescaped `;`.
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut item = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                item.push(c);
+                item.extend(chars.next());
+            }
+            ';' => items.push(unescape(&std::mem::take(&mut item))),
+            c => item.push(c),
+        }
+    }
+    if !item.trim().is_empty() {
+        items.push(unescape(&item));
+    }
+    items
+}
+
+/// Reverses [`escape`], and also unescapes `\;` in list items.
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some('t') => unescaped.push('\t'),
+            Some(c @ ('\\' | ';')) => unescaped.push(c),
+            Some(c) => {
+                unescaped.push('\\');
+                unescaped.push(c);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::{MockDevice, MockNmClient};
+    use crate::{EnterpriseAuth, Ipv4Config, Ipv6Config, Metered, SecretStorage, Security};
+
+    // The expected files are what `nmcli connection add type wifi ...` writes for the
+    // same settings, minus the UUID and the defaults NetworkManager fills in on its own
+    // (`addr-gen-mode`, `[proxy]`).
+
+    #[test]
+    fn renders_wpa_psk_like_nmcli() {
+        let keyfile = WifiConnection::builder("Office")
+            .password("supersecret123")
+            .build()
+            .unwrap()
+            .to_keyfile();
+        assert_eq!(
+            keyfile,
+            "[connection]\n\
+             id=Office\n\
+             type=wifi\n\
+             \n\
+             [wifi]\n\
+             mode=infrastructure\n\
+             ssid=Office\n\
+             \n\
+             [wifi-security]\n\
+             key-mgmt=wpa-psk\n\
+             psk=supersecret123\n\
+             \n\
+             [ipv4]\n\
+             method=auto\n\
+             \n\
+             [ipv6]\n\
+             method=auto\n"
+        );
+    }
+
+    #[test]
+    fn renders_static_ip_dns_and_profile_options() {
+        let keyfile = WifiConnection::builder("Kiosk")
+            .password("supersecret123")
+            .hidden(true)
+            .interface("wlan0")
+            .autoconnect(false)
+            .autoconnect_priority(10)
+            .metered(Metered::Yes)
+            .bssid("00:11:22:aa:bb:cc".parse().unwrap())
+            .ipv4(Ipv4Config::Manual {
+                address: [192, 168, 1, 50].into(),
+                prefix: 24,
+                gateway: Some([192, 168, 1, 1].into()),
+            })
+            .ipv6(Ipv6Config::Ignore)
+            .dns("10.0.0.53".parse().unwrap())
+            .dns_search("corp.example")
+            .build()
+            .unwrap()
+            .to_keyfile();
+        assert_eq!(
+            keyfile,
+            "[connection]\n\
+             id=Kiosk\n\
+             type=wifi\n\
+             autoconnect=false\n\
+             autoconnect-priority=10\n\
+             interface-name=wlan0\n\
+             metered=1\n\
+             \n\
+             [wifi]\n\
+             bssid=00:11:22:AA:BB:CC\n\
+             hidden=true\n\
+             mode=infrastructure\n\
+             ssid=Kiosk\n\
+             \n\
+             [wifi-security]\n\
+             key-mgmt=wpa-psk\n\
+             psk=supersecret123\n\
+             \n\
+             [ipv4]\n\
+             address1=192.168.1.50/24,192.168.1.1\n\
+             dns=10.0.0.53;\n\
+             dns-search=corp.example;\n\
+             ignore-auto-dns=true\n\
+             method=manual\n\
+             \n\
+             [ipv6]\n\
+             ignore-auto-dns=true\n\
+             method=ignore\n"
+        );
+    }
+
+    #[test]
+    fn renders_enterprise_credentials() {
+        let keyfile = WifiConnection::builder("Campus")
+            .security(Security::Enterprise(EnterpriseAuth::Peap {
+                identity: "alice".into(),
+                password: "hunter22".into(),
+                anonymous_identity: None,
+            }))
+            .build()
+            .unwrap()
+            .to_keyfile();
+        assert!(keyfile.contains(
+            "[802-1x]\n\
+             eap=peap;\n\
+             identity=alice\n\
+             password=hunter22\n\
+             phase2-auth=mschapv2\n"
+        ));
+        assert!(keyfile.contains("[wifi-security]\nkey-mgmt=wpa-eap\n"));
+    }
+
+    #[test]
+    fn writes_unprintable_ssids_as_bytes() {
+        // Latin-1 "Café".
+        assert_eq!(ssid_value(b"Caf\xe9"), "67;97;102;233;");
+        assert_eq!(ssid_value(b"Tab\there"), "84;97;98;9;104;101;114;101;");
+        assert_eq!(ssid_value("Café;Bar".as_bytes()), "Café\\;Bar");
+        assert_eq!(ssid_value(b" Lobby"), "\\sLobby");
+    }
+
+    #[test]
+    fn leaves_out_unsaved_secrets() {
+        let keyfile = WifiConnection::builder("Kiosk")
+            .password("supersecret123")
+            .secret_storage(SecretStorage::NotSaved)
+            .permit_user("kiosk")
+            .build()
+            .unwrap()
+            .to_keyfile();
+        assert!(keyfile.contains("[wifi-security]\nkey-mgmt=wpa-psk\npsk-flags=2\n\n"));
+        assert!(!keyfile.contains("supersecret123"));
+        assert!(keyfile.contains("permissions=user:kiosk:;\n"));
+    }
+
+    #[test]
+    fn open_network_has_no_security_section() {
+        let keyfile = WifiConnection::builder("Guest")
+            .build()
+            .unwrap()
+            .to_keyfile();
+        assert!(!keyfile.contains("[wifi-security]"));
+    }
+
+    #[test]
+    fn imports_what_it_exports() {
+        let dir = std::env::temp_dir();
+        let client_cert = dir.join("wifi-config-test-import.pem");
+        let private_key = dir.join("wifi-config-test-import.key");
+        fs::write(&client_cert, "").unwrap();
+        fs::write(&private_key, "").unwrap();
+        let connections = [
+            WifiConnection::builder("Kiosk")
+                .password("supersecret123")
+                .hidden(true)
+                .interface("wlan0")
+                .autoconnect_priority(10)
+                .metered(Metered::Yes)
+                .bssid("00:11:22:aa:bb:cc".parse().unwrap())
+                .mac_policy(crate::MacPolicy::Stable)
+                .ipv4(Ipv4Config::Manual {
+                    address: [192, 168, 1, 50].into(),
+                    prefix: 24,
+                    gateway: Some([192, 168, 1, 1].into()),
+                })
+                .dns("10.0.0.53".parse().unwrap())
+                .dns("fd00::53".parse().unwrap())
+                .dns_search("corp.example")
+                .permit_user("kiosk")
+                .build()
+                .unwrap(),
+            WifiConnection::builder(&b"Caf\xe9"[..])
+                .security(Security::Enterprise(EnterpriseAuth::Tls {
+                    identity: "alice".into(),
+                    ca_cert: crate::CaCertificate::DangerouslySkipVerification,
+                    client_cert: client_cert.clone(),
+                    private_key: private_key.clone(),
+                    private_key_password: Some("hunter22".into()),
+                }))
+                .build()
+                .unwrap(),
+        ];
+        for connection in connections {
+            let keyfile = connection.to_keyfile();
+            let imported = ConnectionConfig::from_keyfile(&keyfile).unwrap();
+            assert_eq!(imported.ssid(), connection.ssid());
+            assert_eq!(render(&imported.settings), keyfile);
+        }
+        fs::remove_file(client_cert).unwrap();
+        fs::remove_file(private_key).unwrap();
+    }
+
+    #[test]
+    fn imports_byte_ssids_and_unknown_sections() {
+        let imported = ConnectionConfig::from_keyfile(
+            "# written by the provisioning tool\n\
+             [connection]\n\
+             id=Caf\\s\n\
+             type=802-11-wireless\n\
+             \n\
+             [wifi]\n\
+             ssid=67;97;102;233;\n\
+             cloned-mac-address=02:00:00:00:00:01\n\
+             \n\
+             [ipv4]\n\
+             method=manual\n\
+             address1=10.1.0.5/16\n\
+             route1=10.2.0.0/16,10.1.0.1,50\n\
+             \n\
+             [ethtool]\n\
+             feature-tso=false\n\
+             ring-rx=512\n\
+             \n\
+             [user]\n\
+             org.example.site=Berlin City\n",
+        )
+        .unwrap();
+        assert_eq!(imported.ssid(), b"Caf\xe9");
+        assert_eq!(imported.id(), Some("Caf "));
+        assert_eq!(imported.uuid(), None);
+
+        let settings = &imported.settings;
+        assert_eq!(
+            prop_cast::<Vec<u8>>(&settings["802-11-wireless"], "cloned-mac-address"),
+            Some(&vec![2, 0, 0, 0, 0, 1])
+        );
+        assert_eq!(
+            render(settings)
+                .lines()
+                .find(|line| line.starts_with("address1")),
+            Some("address1=10.1.0.5/16")
+        );
+        let route = &settings["ipv4"]["route-data"].0;
+        let route: Vec<&dyn RefArg> = route
+            .as_iter()
+            .unwrap()
+            .next()
+            .unwrap()
+            .as_iter()
+            .unwrap()
+            .collect();
+        assert!(route.chunks(2).any(
+            |pair| pair[0].as_str() == Some("next-hop") && pair[1].as_str() == Some("10.1.0.1")
+        ));
+        assert_eq!(
+            prop_cast::<bool>(&settings["ethtool"], "feature-tso"),
+            Some(&false)
+        );
+        assert_eq!(
+            prop_cast::<i32>(&settings["ethtool"], "ring-rx"),
+            Some(&512)
+        );
+        assert_eq!(
+            prop_cast::<String>(&settings["user"], "org.example.site").map(String::as_str),
+            Some("Berlin City")
+        );
+    }
+
+    #[test]
+    fn rejects_keyfiles_that_arent_wifi_profiles() {
+        let reason = |keyfile: &str| match ConnectionConfig::from_keyfile(keyfile) {
+            Err(WifiConfigError::InvalidKeyfile(reason)) => reason,
+            other => panic!("expected an invalid keyfile, got {:?}", other),
+        };
+        assert!(reason("[connection]\nid=Wired\ntype=ethernet\n").contains("'ethernet'"));
+        assert!(reason("[wifi]\nssid=Office\n").contains("connection.type is missing"));
+        assert!(reason("[connection]\ntype=wifi\n").contains("no ssid"));
+        assert!(reason("[connection]\ntype=wifi\nautoconnect\n").starts_with("line 3"));
+        assert!(reason("[connection]\ntype=wifi\nautoconnect=maybe\n")
+            .starts_with("connection.autoconnect"));
+    }
+
+    #[test]
+    fn saves_new_profiles_and_replaces_ones_with_the_same_uuid() {
+        let client = MockNmClient::with_devices(vec![MockDevice::new(1, "wlan0", 2)]);
+        let keyfile = |password: &str| {
+            format!(
+                "[connection]\nid=Office\nuuid=0b7cb1a4-5f7c-4d36-9d0e-6a1f3c2e8b90\ntype=wifi\n\
+                 [wifi]\nssid=Office\n[wifi-security]\nkey-mgmt=wpa-psk\npsk={}\n",
+                password
+            )
+        };
+        let saved = ConnectionConfig::from_keyfile(&keyfile("supersecret123"))
+            .unwrap()
+            .save_with(&client)
+            .unwrap();
+        assert_eq!(saved.change, ProfileChange::Created);
+        assert_eq!(saved.uuid, "0b7cb1a4-5f7c-4d36-9d0e-6a1f3c2e8b90");
+
+        let handle = ConnectionConfig::from_keyfile(&keyfile("newpassword456"))
+            .unwrap()
+            .connect_with(&client)
+            .unwrap();
+        assert_eq!(handle.change, ProfileChange::Updated);
+        assert_eq!(handle.settings_path, saved.settings_path);
+        assert_eq!(handle.interface, "wlan0");
+        assert!(client
+            .calls()
+            .contains(&format!("Update {}", saved.settings_path)));
+        assert_eq!(client.methods().last().unwrap(), "ActivateConnection");
+        let connections = client.connections.borrow();
+        assert_eq!(connections.len(), 1);
+        let security = &connections[0].1["802-11-wireless-security"];
+        assert_eq!(
+            prop_cast::<String>(security, "psk").map(String::as_str),
+            Some("newpassword456")
+        );
+    }
+
+    #[test]
+    fn writes_keyfile_with_private_permissions() {
+        let path = std::env::temp_dir().join("wifi-config-test-Office.nmconnection");
+        let connection = WifiConnection::builder("Office")
+            .password("supersecret123")
+            .build()
+            .unwrap();
+        connection.write_keyfile(&path, 0o600).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), connection.to_keyfile());
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        assert!(matches!(
+            connection.write_keyfile(&path, 0o644),
+            Err(WifiConfigError::KeyfileWrite(..))
+        ));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,263 @@
+//! Wi-Fi passwords in the desktop keyring (GNOME Keyring, KWallet, KeePassXC, ...),
+//! through the Secret Service D-Bus API on the session bus.
+
+use dbus::arg::{PropMap, RefArg, Variant};
+use dbus::blocking::Connection;
+use dbus::message::MatchRule;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+use crate::nm::{DBUS_TIMEOUT, NO_OBJECT_PATH};
+use crate::{ActivationHandle, Secret, WifiConfigError, WifiConnection};
+
+/// Well-known bus name of the Secret Service.
+const SECRETS_BUS_NAME: &str = "org.freedesktop.secrets";
+/// Object path of the Secret Service.
+const SECRETS_PATH: &str = "/org/freedesktop/secrets";
+/// The user's default collection, usually the login keyring.
+const DEFAULT_COLLECTION_PATH: &str = "/org/freedesktop/secrets/aliases/default";
+const SERVICE_INTERFACE: &str = "org.freedesktop.Secret.Service";
+const COLLECTION_INTERFACE: &str = "org.freedesktop.Secret.Collection";
+const ITEM_INTERFACE: &str = "org.freedesktop.Secret.Item";
+const PROMPT_INTERFACE: &str = "org.freedesktop.Secret.Prompt";
+
+/// Value of the `service` attribute of every item this crate stores.
+pub const KEYRING_SERVICE: &str = "wifi-config";
+
+/// How long the user has to answer an unlock prompt.
+const PROMPT_TIMEOUT: Duration = Duration::from_secs(120);
+/// How often the bus is checked for the prompt's answer.
+const PROMPT_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// A Secret Service `Secret` as received: session, parameters, value and content type.
+type SecretStruct = (dbus::Path<'static>, Vec<u8>, Vec<u8>, String);
+
+/// Looks up the password stored for `ssid` in the keyring.
+///
+/// Items are found by their attributes: `service` = [`KEYRING_SERVICE`] and `ssid` = the
+/// SSID, or `ssid-hex` = its bytes as lowercase hex digits for SSIDs that aren't valid
+/// UTF-8. [`store_secret`] sets them; other programs may store items under the same
+/// attributes (e.g. `secret-tool store --label=Office service wifi-config ssid Office`).
+///
+/// If the item's collection is locked, the Secret Service prompts the user to unlock it,
+/// and this function waits up to two minutes for the answer.
+///
+/// The password is never logged, and every buffer it passes through on the way to the
+/// returned [`Secret`] is wiped.
+///
+/// # Errors
+///
+/// - [`WifiConfigError::SecretNotFound`] if no item has these attributes.
+/// - [`WifiConfigError::KeyringFailed`] if the session bus or the Secret Service can't be
+///   reached, the unlock prompt is dismissed, or the secret isn't valid UTF-8.
+///
+/// # Example
+///
+/// ```no_run
+/// let password = wifi_config::lookup_secret("Office")?;
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn lookup_secret(ssid: impl AsRef<[u8]>) -> Result<Secret, WifiConfigError> {
+    let ssid = ssid.as_ref();
+    let conn = Connection::new_session().map_err(WifiConfigError::KeyringFailed)?;
+    let service = conn.with_proxy(SECRETS_BUS_NAME, SECRETS_PATH, DBUS_TIMEOUT);
+    let search = || -> Result<(Vec<dbus::Path<'static>>, Vec<dbus::Path<'static>>), _> {
+        service
+            .method_call(SERVICE_INTERFACE, "SearchItems", (attributes(ssid),))
+            .map_err(WifiConfigError::KeyringFailed)
+    };
+
+    let (mut unlocked, locked) = search()?;
+    if unlocked.is_empty() && !locked.is_empty() {
+        let (_, prompt): (Vec<dbus::Path<'static>>, dbus::Path<'static>) = service
+            .method_call(SERVICE_INTERFACE, "Unlock", (locked,))
+            .map_err(WifiConfigError::KeyringFailed)?;
+        complete_prompt(&conn, &prompt)?;
+        unlocked = search()?.0;
+    }
+    let Some(item) = unlocked.first() else {
+        return Err(WifiConfigError::SecretNotFound(
+            String::from_utf8_lossy(ssid).into_owned(),
+        ));
+    };
+
+    let session = open_session(&conn)?;
+    let ((_, _, value, _),): (SecretStruct,) = conn
+        .with_proxy(SECRETS_BUS_NAME, item, DBUS_TIMEOUT)
+        .method_call(ITEM_INTERFACE, "GetSecret", (session,))
+        .map_err(WifiConfigError::KeyringFailed)?;
+    log::debug!(
+        "found the password for {} in the keyring",
+        String::from_utf8_lossy(ssid)
+    );
+    let value = Zeroizing::new(value);
+    let password = std::str::from_utf8(&value).map_err(|_| {
+        WifiConfigError::KeyringFailed(dbus::Error::new_custom(
+            "org.freedesktop.DBus.Error.InvalidArgs",
+            "the stored secret isn't valid UTF-8",
+        ))
+    })?;
+    Ok(Secret::from(password))
+}
+
+/// Stores `password` for `ssid` in the default collection, replacing an item with the
+/// same attributes; see [`lookup_secret`] for the attributes.
+///
+/// Meant to be called once a connection with a manually entered password has succeeded,
+/// so later runs can use [`connect_with_keyring`].
+///
+/// # Errors
+///
+/// - [`WifiConfigError::KeyringFailed`] if the session bus or the Secret Service can't be
+///   reached, or the collection stays locked.
+pub fn store_secret(ssid: impl AsRef<[u8]>, password: &str) -> Result<(), WifiConfigError> {
+    let ssid = ssid.as_ref();
+    let conn = Connection::new_session().map_err(WifiConfigError::KeyringFailed)?;
+    let session = open_session(&conn)?;
+
+    let mut properties = PropMap::new();
+    properties.insert(
+        "org.freedesktop.Secret.Item.Label".into(),
+        Variant(Box::new(format!(
+            "Wi-Fi password for {}",
+            String::from_utf8_lossy(ssid)
+        ))),
+    );
+    properties.insert(
+        "org.freedesktop.Secret.Item.Attributes".into(),
+        Variant(Box::new(attributes(ssid))),
+    );
+    let value = Zeroizing::new(password.as_bytes().to_vec());
+    let secret = (session, Vec::<u8>::new(), &value[..], "text/plain");
+    let (_, prompt): (dbus::Path<'static>, dbus::Path<'static>) = conn
+        .with_proxy(SECRETS_BUS_NAME, DEFAULT_COLLECTION_PATH, DBUS_TIMEOUT)
+        .method_call(
+            COLLECTION_INTERFACE,
+            "CreateItem",
+            (properties, secret, true),
+        )
+        .map_err(WifiConfigError::KeyringFailed)?;
+    complete_prompt(&conn, &prompt)?;
+    log::info!(
+        "stored the password for {} in the keyring",
+        String::from_utf8_lossy(ssid)
+    );
+    Ok(())
+}
+
+/// Connects to the WPA-PSK network `ssid` with the password stored in the keyring.
+///
+/// Same as [`send_wifi_to_network_manager`](crate::send_wifi_to_network_manager) with the
+/// password from [`lookup_secret`].
+///
+/// # Errors
+///
+/// - Same as [`lookup_secret`] and
+///   [`send_wifi_to_network_manager`](crate::send_wifi_to_network_manager).
+///
+/// # Example
+///
+/// ```no_run
+/// wifi_config::store_secret("Office", "supersecret123")?;
+/// wifi_config::connect_with_keyring("Office")?;
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn connect_with_keyring(ssid: impl AsRef<[u8]>) -> Result<ActivationHandle, WifiConfigError> {
+    let ssid = ssid.as_ref();
+    let password = lookup_secret(ssid)?;
+    WifiConnection::builder(ssid)
+        .password(password)
+        .build()?
+        .connect()
+}
+
+/// The attributes identifying the item for `ssid`; see [`lookup_secret`].
+fn attributes(ssid: &[u8]) -> HashMap<String, String> {
+    let mut attributes = HashMap::new();
+    attributes.insert("service".to_string(), KEYRING_SERVICE.to_string());
+    match std::str::from_utf8(ssid) {
+        Ok(ssid) => attributes.insert("ssid".to_string(), ssid.to_string()),
+        Err(_) => attributes.insert(
+            "ssid-hex".to_string(),
+            ssid.iter().map(|byte| format!("{:02x}", byte)).collect(),
+        ),
+    };
+    attributes
+}
+
+/// Opens an unencrypted session. The secret only travels over the local bus.
+fn open_session(conn: &Connection) -> Result<dbus::Path<'static>, WifiConfigError> {
+    let (_, session): (Variant<Box<dyn RefArg>>, dbus::Path<'static>) = conn
+        .with_proxy(SECRETS_BUS_NAME, SECRETS_PATH, DBUS_TIMEOUT)
+        .method_call(
+            SERVICE_INTERFACE,
+            "OpenSession",
+            ("plain", Variant(String::new())),
+        )
+        .map_err(WifiConfigError::KeyringFailed)?;
+    Ok(session)
+}
+
+/// Shows the Secret Service `prompt`, if there is one, and waits until the user answers.
+fn complete_prompt(conn: &Connection, prompt: &dbus::Path) -> Result<(), WifiConfigError> {
+    if &**prompt == NO_OBJECT_PATH {
+        return Ok(());
+    }
+    let answer = Arc::new(Mutex::new(None));
+    let answered = answer.clone();
+    let rule = MatchRule::new_signal(PROMPT_INTERFACE, "Completed")
+        .with_path(prompt.clone().into_static());
+    let token = conn
+        .add_match(
+            rule,
+            move |(dismissed, _): (bool, Variant<Box<dyn RefArg>>), _, _| {
+                *answered.lock().unwrap() = Some(dismissed);
+                false
+            },
+        )
+        .map_err(WifiConfigError::KeyringFailed)?;
+    conn.with_proxy(SECRETS_BUS_NAME, prompt, DBUS_TIMEOUT)
+        .method_call::<(), _, _, _>(PROMPT_INTERFACE, "Prompt", ("",))
+        .map_err(WifiConfigError::KeyringFailed)?;
+
+    let deadline = Instant::now() + PROMPT_TIMEOUT;
+    loop {
+        match *answer.lock().unwrap() {
+            Some(false) => return Ok(()),
+            Some(true) => {
+                return Err(WifiConfigError::KeyringFailed(dbus::Error::new_custom(
+                    "org.freedesktop.Secret.Error.IsLocked",
+                    "the keyring prompt was dismissed",
+                )))
+            }
+            None => {}
+        }
+        if Instant::now() >= deadline {
+            let _ = conn.remove_match(token);
+            return Err(WifiConfigError::KeyringFailed(dbus::Error::new_custom(
+                "org.freedesktop.DBus.Error.Timeout",
+                "the keyring prompt wasn't answered in time",
+            )));
+        }
+        conn.process(PROMPT_POLL_INTERVAL)
+            .map_err(WifiConfigError::KeyringFailed)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifies_items_by_service_and_ssid() {
+        let utf8 = attributes(b"Office");
+        assert_eq!(utf8["service"], "wifi-config");
+        assert_eq!(utf8["ssid"], "Office");
+
+        let latin1 = attributes(b"Caf\xe9");
+        assert_eq!(latin1["ssid-hex"], "436166e9");
+        assert!(!latin1.contains_key("ssid"));
+    }
+}
@@ -1,8 +1,111 @@
-use dbus::{
-    arg::Variant,
-    blocking::{stdintf::org_freedesktop_dbus::Properties, Connection},
+mod activation;
+mod backend;
+mod builder;
+mod candidates;
+mod client;
+mod config_file;
+mod connectivity;
+mod device;
+mod dry_run;
+mod error;
+mod hotspot;
+mod ip;
+mod ip_info;
+mod iwd;
+#[cfg(feature = "serde")]
+mod json;
+mod keyfile;
+#[cfg(feature = "keyring")]
+mod keyring;
+mod manager;
+mod nm;
+#[cfg(feature = "tokio")]
+mod nonblocking;
+mod options;
+mod pending;
+mod profiles;
+mod qr;
+mod radio;
+mod reconnect;
+mod retry;
+mod scan;
+mod secret;
+mod security;
+mod settings;
+mod status;
+mod version;
+mod watch;
+mod wireless;
+mod wpa_supplicant;
+mod wps;
+
+pub use activation::{
+    deactivate, wait_for_activation, ActivationHandle, ActivationOutcome, DeviceActivation,
+    Rollback,
+};
+pub use backend::Backend;
+pub use builder::{ConnectOptionsBuilder, WifiConnection, WifiConnectionBuilder};
+pub use candidates::{
+    connect_first_available, CandidateFailure, ConnectedNetwork, NetworkCandidate,
+};
+#[cfg(feature = "serde")]
+pub use config_file::{apply_config_file, NetworkResult};
+pub use config_file::{save_connection, SavedProfile};
+pub use connectivity::{check_connectivity, Connectivity};
+pub use device::{
+    disconnect, disconnect_interface, list_wifi_devices, DeviceCapabilities, DeviceSelection,
+    DeviceState, DeviceStateReason, DisconnectOutcome, WifiDevice,
+};
+pub use dry_run::{dry_run, DbusValue, DryRun, SettingValue, MASKED_SECRET};
+pub use error::WifiConfigError;
+pub use hotspot::{create_hotspot, HotspotOptions};
+pub use ip::{DhcpOptions, Ipv4Config, Ipv6Config, Ipv6Privacy};
+pub use ip_info::{ip_info, IpInfo};
+pub use keyfile::{import_keyfile, ConnectionConfig};
+#[cfg(feature = "keyring")]
+pub use keyring::{connect_with_keyring, lookup_secret, store_secret, KEYRING_SERVICE};
+pub use manager::{BusSelection, WifiManager};
+pub use nm::DbusTimeouts;
+#[cfg(feature = "tokio")]
+pub use nonblocking::{
+    connect_async, connect_with_security_async, send_open_wifi_to_network_manager_async,
+    send_wifi_to_network_manager_async,
+};
+pub use options::{ConnectOptions, Metered};
+pub use pending::{start_connect, PendingConnection};
+pub use profiles::{
+    activate_saved, delete, forget_network, list_saved_connections,
+    list_saved_connections_with_warnings, reset_wifi_profiles, stable_uuid_for,
+    stable_uuid_for_interface, ProfileChange, ProfileSecurity, ProfileWarning, ResetFailure,
+    ResetReport, SavedWifiProfile, STABLE_UUID_NAMESPACE,
+};
+pub use qr::WifiQr;
+pub use radio::{radio_state, RadioState};
+pub use reconnect::reconnect;
+pub use retry::RetryPolicy;
+pub use scan::{
+    active_access_point, is_network_available, request_scan, scan_networks, signal_strength,
+    AccessPointInfo, ApSecurity, PinnedAccessPoint,
+};
+pub use secret::Secret;
+pub use security::{
+    CaCertificate, Cipher, CipherPolicy, EnterpriseAuth, SecretStorage, Security, WpaProto,
 };
-use std::{collections::HashMap, time::Duration};
+pub use settings::ExtraSettingValue;
+#[allow(deprecated)]
+pub use status::current_status;
+pub use status::{active_connection, ActiveConnectionInfo, ActiveConnectionState, WifiStatus};
+pub use version::{nm_version, NmVersion};
+pub use watch::{
+    watch_device_states, watch_wifi, DeviceStateEvent, DeviceStateWatcher, EventWatcher, StopToken,
+    WifiEvent, WifiWatcher,
+};
+pub use wireless::{
+    frequency_to_channel, Band, MacAddr, MacPolicy, PowerSave, ScanMacRandomization, WirelessMode,
+};
+pub use wps::connect_wps_pbc;
+
+use client::NmClient;
 
 /// Sends Wi-Fi configuration to **NetworkManager** via the D-Bus system bus.
 ///
@@ -18,87 +121,296 @@ use std::{collections::HashMap, time::Duration};
 /// - Builds a connection settings dictionary compatible with NetworkManager:
 ///   - `802-11-wireless` (SSID, mode)
 ///   - `802-11-wireless-security` (WPA-PSK with the given password)
-/// - Calls `AddAndActivateConnection` to tell NetworkManager to connect.
+/// - If a profile for `ssid` is already saved, calls `Update` on it and then
+///   `ActivateConnection`; otherwise calls `AddAndActivateConnection` to tell
+///   NetworkManager to connect.
+/// - Skips the `Update` if the saved profile already matches, comparing the stored
+///   password read with `GetSecrets`. If that can't be read, it updates anyway.
+///
+/// Returns an [`ActivationHandle`] with the paths and UUID of the profile, which also
+/// tells whether it was [created](ProfileChange::Created),
+/// [updated](ProfileChange::Updated), [updated without checking](ProfileChange::UpdatedUnverified)
+/// or [left as it was](ProfileChange::Unchanged). Pass it to [`deactivate`] or [`delete`] to undo
+/// the connection.
 ///
 /// # Errors
 ///
-/// - If no Wi-Fi device is found, the function prints an error to stderr and returns.
-/// - If D-Bus calls fail, the error is printed to stderr.
+/// - [`WifiConfigError::InvalidSsid`] if `ssid` is empty or longer than 32 bytes.
+/// - [`WifiConfigError::EmptyPassword`] if `password` is empty; use
+///   [`send_open_wifi_to_network_manager`] for open networks.
+/// - [`WifiConfigError::InvalidPsk`] if `password` isn't 8 to 63 printable ASCII
+///   characters or a 64-digit hex key.
+/// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+/// - [`WifiConfigError::NetworkManagerUnavailable`] if NetworkManager isn't running.
+/// - [`WifiConfigError::DeviceQueryFailed`] if listing devices or reading their type fails.
+/// - [`WifiConfigError::NoWifiDevice`] if no Wi-Fi device is found.
+/// - [`WifiConfigError::SettingsQueryFailed`] if the saved profiles can't be listed.
+/// - [`WifiConfigError::ActivationFailed`] if NetworkManager rejects the connection.
 ///
 /// # Example
 ///
 /// ```no_run
 /// use wifi_config::send_wifi_to_network_manager;
 ///
-/// fn main() {
-///     send_wifi_to_network_manager("MyHomeWiFi", "supersecret123");
+/// fn main() -> Result<(), wifi_config::WifiConfigError> {
+///     send_wifi_to_network_manager("MyHomeWiFi", "supersecret123")?;
+///     Ok(())
 /// }
 /// ```
+///
+/// Sends Wi-Fi parameters to NetworkManager for connection. Use [`WifiConnection::builder`]
+/// for anything beyond SSID and password.
+pub fn send_wifi_to_network_manager(
+    ssid: &str,
+    password: &str,
+) -> Result<ActivationHandle, WifiConfigError> {
+    WifiConnection::builder(ssid)
+        .password(password)
+        .build()?
+        .connect()
+}
 
-/// Sends Wi-Fi parameters to NetworkManager for connection
-pub fn send_wifi_to_network_manager(ssid: &str, password: &str) {
-    let conn = Connection::new_system().unwrap();
-    let proxy = conn.with_proxy(
-        "org.freedesktop.NetworkManager",
-        "/org/freedesktop/NetworkManager",
-        Duration::from_secs(10),
-    );
-    let connection_path = dbus::Path::new("/").unwrap();
-    let (devices,): (Vec<dbus::Path>,) = proxy
-        .method_call("org.freedesktop.NetworkManager", "GetDevices", ())
-        .unwrap();
-
-    let mut wifi_device_path: Option<dbus::Path> = None;
-
-    for device in devices {
-        let device_proxy = conn.with_proxy(
-            "org.freedesktop.NetworkManager",
-            &device,
-            Duration::from_secs(10),
-        );
-        let device_type: u32 = device_proxy
-            .get("org.freedesktop.NetworkManager.Device", "DeviceType")
-            .unwrap();
-
-        // type 2 means Wi-Fi
-        if device_type == 2 {
-            wifi_device_path = Some(device);
-            break;
-        }
-    }
+/// Connects to an open (unencrypted) Wi-Fi network.
+///
+/// Works like [`send_wifi_to_network_manager`], but omits the `802-11-wireless-security`
+/// section entirely so NetworkManager treats the network as open.
+///
+/// # Errors
+///
+/// Same as [`send_wifi_to_network_manager`], except that no password is validated.
+///
+/// # Example
+///
+/// ```no_run
+/// wifi_config::send_open_wifi_to_network_manager("CoffeeShopGuest")?;
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn send_open_wifi_to_network_manager(ssid: &str) -> Result<ActivationHandle, WifiConfigError> {
+    connect_with_security(ssid, &Security::Open)
+}
 
-    if wifi_device_path.is_none() {
-        eprintln!("Wi-Fi device not found.");
-        return;
-    }
+/// Connects to a WPA/WPA2-Enterprise (802.1X) network.
+///
+/// Builds `key-mgmt: wpa-eap` plus an `802-1x` section from `auth`, then locates the
+/// Wi-Fi device and activates the connection like [`send_wifi_to_network_manager`].
+///
+/// # Errors
+///
+/// - [`WifiConfigError::InvalidEnterpriseAuth`] / [`WifiConfigError::EmptyPassword`] if
+///   the credentials are incomplete.
+/// - [`WifiConfigError::CertificateNotFound`] if an EAP-TLS certificate or key path
+///   doesn't exist; this is checked before any D-Bus call.
+/// - [`WifiConfigError::ActivationFailed`] carrying NetworkManager's rejection reason.
+/// - Otherwise the same as [`send_wifi_to_network_manager`].
+///
+/// # Example
+///
+/// ```no_run
+/// use wifi_config::{send_enterprise_wifi, EnterpriseAuth};
+///
+/// send_enterprise_wifi(
+///     "CorpNet",
+///     EnterpriseAuth::Peap {
+///         identity: "alice".into(),
+///         password: "hunter2".into(),
+///         anonymous_identity: Some("anonymous".into()),
+///     },
+/// )?;
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn send_enterprise_wifi(
+    ssid: &str,
+    auth: EnterpriseAuth,
+) -> Result<ActivationHandle, WifiConfigError> {
+    connect_with_security(ssid, &Security::Enterprise(auth))
+}
 
-    let device_path = wifi_device_path.unwrap();
-    // Wi-Fi configuration structure
-    let mut connection_settings: HashMap<&str, HashMap<&str, Variant<Box<dyn dbus::arg::RefArg>>>> =
-        HashMap::new();
+/// Connects to a Wi-Fi network using the given [`Security`] scheme.
+///
+/// This is the general form of [`send_wifi_to_network_manager`] (WPA-PSK) and
+/// [`send_open_wifi_to_network_manager`] (open), and also covers WPA3-SAE and
+/// WPA2/WPA3 transition mode.
+///
+/// # Errors
+///
+/// Same as [`send_wifi_to_network_manager`]. Credentials are validated according to the
+/// selected scheme before any D-Bus call is made.
+///
+/// # Example
+///
+/// ```no_run
+/// use wifi_config::{connect_with_security, Security};
+///
+/// connect_with_security("MyWpa3Net", &Security::Wpa3Sae("supersecret123".into()))?;
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn connect_with_security(
+    ssid: impl AsRef<[u8]>,
+    security: &Security,
+) -> Result<ActivationHandle, WifiConfigError> {
+    connect(
+        ssid,
+        &ConnectOptions {
+            security: security.clone(),
+            ..Default::default()
+        },
+    )
+}
 
-    // Wi-Fi settings
-    let mut wifi_settings: HashMap<&str, Variant<Box<dyn dbus::arg::RefArg>>> = HashMap::new();
-    wifi_settings.insert("ssid", Variant(Box::new(ssid.as_bytes().to_vec())));
-    wifi_settings.insert("mode", Variant(Box::new(String::from("infrastructure"))));
-    connection_settings.insert("802-11-wireless", wifi_settings);
+/// Connects to a Wi-Fi network with the given [`ConnectOptions`].
+///
+/// All other connect functions are shorthands for this one.
+///
+/// `ssid` is taken as raw bytes, so names that aren't valid UTF-8 (e.g. Latin-1) can be
+/// passed as `&[u8]`; a `&str` works as well. Only the 32-byte limit is checked.
+///
+/// # Behavior
+///
+/// - Validates `options` client-side.
+/// - Checks the manager's `WirelessEnabled` and `WirelessHardwareEnabled` properties, and
+///   with `options.enable_radio` turns a software-disabled radio on.
+/// - Locates the Wi-Fi device given by `options.device_path` or `options.interface`, or
+///   picks one (or with [`DeviceSelection::All`] every usable one) as
+///   `options.device_selection` says.
+/// - Builds the settings dictionary (`802-11-wireless`, plus security sections as needed;
+///   `hidden` is set for non-broadcasting SSIDs).
+/// - Updates and activates the saved profile for `ssid`, or calls
+///   `AddAndActivateConnection` if there is none or `options.allow_duplicates` is set.
+///
+/// With [`Backend::WpaSupplicant`] in `options.backend`, the network is added to
+/// wpa_supplicant and selected instead; see [`Backend`].
+///
+/// # Errors
+///
+/// - [`WifiConfigError::RadioDisabled`] if Wi-Fi is switched off in software and
+///   `options.enable_radio` isn't set.
+/// - [`WifiConfigError::HardwareRfkill`] if Wi-Fi is blocked by a hardware switch.
+/// - [`WifiConfigError::DeviceNotFound`] if `options.device_path` / `options.interface`
+///   names no Wi-Fi device.
+/// - [`WifiConfigError::SignalTooWeak`] / [`WifiConfigError::NetworkNotFound`] if
+///   `options.min_strength` is set and the network is too weak or not in range.
+/// - [`WifiConfigError::UnsupportedByBackend`] if `options.backend` can't apply one of
+///   the options.
+/// - Otherwise the same as [`send_wifi_to_network_manager`] and [`send_enterprise_wifi`].
+///
+/// # Example
+///
+/// ```no_run
+/// use wifi_config::{connect, ConnectOptions, Security};
+///
+/// let options = ConnectOptions::builder()
+///     .security(Security::WpaPsk("supersecret123".into()))
+///     .hidden(true)
+///     .build();
+/// connect("MyHiddenNet", &options)?;
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn connect(
+    ssid: impl AsRef<[u8]>,
+    options: &ConnectOptions,
+) -> Result<ActivationHandle, WifiConfigError> {
+    let ssid = ssid.as_ref();
+    options.validate(ssid)?;
+    options.backend.implementation().connect(ssid, options)
+}
 
-    // Wi-Fi security settings
-    let mut wifi_security: HashMap<&str, Variant<Box<dyn dbus::arg::RefArg>>> = HashMap::new();
-    wifi_security.insert("key-mgmt", Variant(Box::new(String::from("wpa-psk"))));
-    wifi_security.insert("psk", Variant(Box::new(String::from(password))));
-    connection_settings.insert("802-11-wireless-security", wifi_security);
+/// Like [`connect`], but blocks until the connection is actually up.
+///
+/// `AddAndActivateConnection` succeeding only means NetworkManager accepted the profile.
+/// This function subscribes to the device's `StateChanged` signal before activating and
+/// then waits until it reaches `ACTIVATED`, or drops to `FAILED`/`DISCONNECTED`, for at
+/// most [`ConnectOptions::activation_timeout`] (30 seconds by default, 60 for hidden
+/// networks). See [`wait_for_activation`] to attach a waiter after calling [`connect`].
+///
+/// Once the connection is up, its addresses, gateways and DNS servers are reported in
+/// [`ActivationHandle::ip`], waiting a moment for NetworkManager to publish them.
+///
+/// With `options.verify_connectivity`, then runs NetworkManager's connectivity check and
+/// reports its result in [`ActivationHandle::connectivity`]; see [`check_connectivity`].
+///
+/// If the connection doesn't come up, the profile it created is deleted and the
+/// connection that was active on the device before is re-activated, unless
+/// `options.keep_on_failure` is set; the error is then wrapped in
+/// [`WifiConfigError::RolledBack`].
+///
+/// With [`Backend::WpaSupplicant`], waits for the interface's `State` property to become
+/// `completed` instead.
+///
+/// # Errors
+///
+/// - [`WifiConfigError::AuthenticationFailed`] if the supplicant rejected the credentials
+///   (state reasons `NO_SECRETS` / `SUPPLICANT_DISCONNECT`).
+/// - [`WifiConfigError::ConnectionFailed`] if activation failed for another reason.
+/// - [`WifiConfigError::ActivationTimeout`] if the timeout passed first.
+/// - [`WifiConfigError::RolledBack`] wrapping one of these three after a rollback.
+/// - Otherwise the same as [`connect`].
+///
+/// # Example
+///
+/// ```no_run
+/// use wifi_config::{connect_and_wait, ConnectOptions, Security};
+///
+/// let options = ConnectOptions::builder()
+///     .security(Security::WpaPsk("supersecret123".into()))
+///     .build();
+/// connect_and_wait("MyHomeWiFi", &options)?;
+/// println!("online");
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn connect_and_wait(
+    ssid: impl AsRef<[u8]>,
+    options: &ConnectOptions,
+) -> Result<ActivationHandle, WifiConfigError> {
+    connect_and_wait_with_progress(ssid, options, |_| {})
+}
 
-    let result: Result<(), _> = proxy.method_call(
-        "org.freedesktop.NetworkManager",
-        "AddAndActivateConnection",
-        (connection_settings, device_path, connection_path),
-    );
+/// Like [`connect_and_wait`], calling `on_state_change` with every state change of the
+/// device while waiting, e.g. to show `prepare → config → ip-config → activated`.
+///
+/// [`DeviceState`] and [`DeviceStateReason`] display as short human-readable names. Only
+/// NetworkManager reports state changes; with the other backends `on_state_change` is
+/// never called.
+///
+/// # Errors
+///
+/// - Same as [`connect_and_wait`].
+///
+/// # Example
+///
+/// ```no_run
+/// use wifi_config::{connect_and_wait_with_progress, ConnectOptions, Security};
+///
+/// let options = ConnectOptions::builder()
+///     .security(Security::WpaPsk("supersecret123".into()))
+///     .build();
+/// connect_and_wait_with_progress("MyHomeWiFi", &options, |event| {
+///     println!("{} -> {}", event.old, event.new);
+/// })?;
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn connect_and_wait_with_progress(
+    ssid: impl AsRef<[u8]>,
+    options: &ConnectOptions,
+    mut on_state_change: impl FnMut(&DeviceStateEvent),
+) -> Result<ActivationHandle, WifiConfigError> {
+    let ssid = ssid.as_ref();
+    options.validate(ssid)?;
+    options
+        .backend
+        .implementation()
+        .connect_and_wait(ssid, options, &mut on_state_change)
+}
 
-    match result {
-        Ok(_) => println!("Wi-Fi configuration successfully sent."),
-        Err(e) => eprintln!("Failed to configure Wi-Fi: {}", e),
-    }
+/// Calls `AddAndActivateConnection` with `connection_settings` on `device_path`.
+///
+/// Returns the paths of the new profile and of its active connection.
+pub(crate) fn add_and_activate(
+    client: &dyn NmClient,
+    device_path: &dbus::Path,
+    connection_settings: settings::ConnectionSettings,
+) -> Result<(dbus::Path<'static>, dbus::Path<'static>), WifiConfigError> {
+    let connection_path = dbus::Path::new("/").unwrap();
+    client.add_and_activate(connection_settings, device_path, &connection_path)
 }
 
 //test
@@ -108,6 +420,9 @@ mod tests {
 
     #[test]
     fn test_check() {
-        assert!(true);
+        assert_eq!(
+            WifiConfigError::NoWifiDevice.to_string(),
+            "Wi-Fi device not found"
+        );
     }
 }
@@ -1,28 +1,2036 @@
-use wifi_config::send_wifi_to_network_manager;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use serde::Serialize;
+use serde_json::json;
+use wifi_config::{
+    activate_saved, apply_config_file, connect_and_wait, connect_and_wait_with_progress,
+    connect_wps_pbc, create_hotspot, disconnect, disconnect_interface, forget_network,
+    import_keyfile, ip_info, list_saved_connections_with_warnings, list_wifi_devices, radio_state,
+    reconnect, reset_wifi_profiles, save_connection, watch_wifi, ActivationHandle,
+    ActiveConnectionInfo, Backend, Band, CipherPolicy, ConnectOptions, Connectivity, DbusTimeouts,
+    DeviceCapabilities, DeviceSelection, DeviceState, DeviceStateEvent, DhcpOptions,
+    DisconnectOutcome, DryRun, HotspotOptions, IpInfo, Ipv4Config, Ipv6Config, MacAddr, MacPolicy,
+    Metered, PowerSave, ProfileChange, RadioState, RetryPolicy, SecretStorage, Security,
+    WifiConfigError, WifiConnection, WifiEvent, WifiManager, WifiQr,
+};
 
-/// Simple CLI wrapper for the `wifi_configurator` library.
+use std::io::BufRead;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+
+/// Set from `--json`: results go to stdout and errors to stderr as single JSON objects.
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Environment variable read when no password is given on the command line.
+const PASSWORD_ENV: &str = "WIFI_CONFIG_PASSWORD";
+
+/// How long `hotspot` waits for the access point to get the address it serves on.
+const HOTSPOT_ADDRESS_WAIT: Duration = Duration::from_secs(15);
+
+/// Configure Wi-Fi connections through NetworkManager.
 ///
-/// Usage:
-/// ```sh
-/// wifi-config <SSID> <PASSWORD>
-/// ```
+/// `wifi-config <SSID> [PASSWORD]` without a subcommand is shorthand for
+/// `wifi-config connect <SSID> [PASSWORD]`.
+#[derive(Debug, Parser)]
+#[command(name = "wifi-config", version)]
+struct Cli {
+    /// Print the result, or the error, as a single JSON object.
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Connect to a network and wait until the connection is up.
+    Connect(Box<ConnectArgs>),
+    /// Activate a saved profile as it is, without supplying the password again.
+    Activate {
+        /// SSID or UUID of the saved profile.
+        #[arg(value_name = "SSID|UUID")]
+        profile: String,
+    },
+    /// Take the Wi-Fi connection down and bring it up again, waiting until it is up.
+    Reconnect {
+        /// SSID or UUID of the saved profile to re-activate; defaults to the one that is
+        /// active now.
+        #[arg(value_name = "SSID|UUID")]
+        profile: Option<String>,
+    },
+    /// List the access points visible to the Wi-Fi device.
+    Scan {
+        #[command(flatten)]
+        backend: BackendArgs,
+        /// Scan with this Wi-Fi device instead of the first one.
+        #[arg(long, value_name = "NAME")]
+        interface: Option<String>,
+        /// Scan now and wait for the results instead of listing the cached ones.
+        #[arg(long)]
+        rescan: bool,
+    },
+    /// Show what the Wi-Fi device is connected to.
+    Status {
+        #[command(flatten)]
+        backend: BackendArgs,
+        /// Show this Wi-Fi device instead of the first one.
+        #[arg(long, value_name = "NAME")]
+        interface: Option<String>,
+    },
+    /// List the saved Wi-Fi profiles.
+    List,
+    /// List the Wi-Fi devices and what they support, e.g. hotspots and the 5 GHz band.
+    Devices,
+    /// Delete all saved profiles for a network.
+    Forget {
+        /// SSID of the network.
+        ssid: String,
+    },
+    /// Delete every saved Wi-Fi profile, e.g. for a factory reset; other profiles are
+    /// kept.
+    Reset {
+        /// Confirm deleting them; refused without it.
+        #[arg(long)]
+        yes: bool,
+        /// Only list the profiles that would be deleted.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Disconnect the Wi-Fi device.
+    Disconnect {
+        /// Use this Wi-Fi device instead of the first one.
+        #[arg(long, value_name = "NAME")]
+        interface: Option<String>,
+        /// Keep NetworkManager from reconnecting on its own.
+        #[arg(long)]
+        no_autoconnect: bool,
+    },
+    /// Start a WPA2 access point sharing this host's connection.
+    Hotspot(HotspotArgs),
+    /// Connect by WPS push-button: press the router's WPS button, then run this.
+    Wps {
+        /// SSID of the network; defaults to the access point whose button was pressed.
+        ssid: Option<String>,
+    },
+    /// Connect to the strongest of several networks that works, trying them in turn.
+    ConnectAny {
+        /// JSON array of profiles in the format of `connect --from-json`.
+        #[arg(long, value_name = "PATH")]
+        from_json: PathBuf,
+    },
+    /// Save a profile for a network, then activate it unless --no-activate is given.
+    Add(Box<AddArgs>),
+    /// Save a profile for every network in a TOML config file.
+    Apply {
+        /// Path of the config file.
+        file: PathBuf,
+    },
+    /// Print a NetworkManager keyfile (.nmconnection) for a network, without D-Bus.
+    ExportKeyfile(Box<ExportKeyfileArgs>),
+    /// Save a NetworkManager keyfile (.nmconnection) written elsewhere as a profile.
+    ImportKeyfile {
+        /// Path of the keyfile.
+        file: PathBuf,
+        /// Also activate the profile.
+        #[arg(long)]
+        activate: bool,
+    },
+    /// Print the Wi-Fi device's state changes, access point changes and signal strength
+    /// as they happen, until interrupted.
+    ///
+    /// Every line starts with the UTC time. With --json, every event is printed as one
+    /// JSON object per line, with the Unix time in `time`. NetworkManager restarting is
+    /// reported, and the device is watched again once it is back.
+    Watch {
+        /// Watch this Wi-Fi device instead of the first one.
+        #[arg(long, value_name = "NAME")]
+        interface: Option<String>,
+    },
+}
+
+#[derive(Debug, Args)]
+struct AddArgs {
+    #[command(flatten)]
+    network: NetworkArgs,
+    /// Add a new profile even if one for the SSID is saved.
+    #[arg(long)]
+    allow_duplicates: bool,
+    /// Only save the profile, for NetworkManager to autoconnect to later; works without
+    /// a Wi-Fi device.
+    #[arg(long)]
+    no_activate: bool,
+}
+
+#[derive(Debug, Args)]
+struct ConnectArgs {
+    #[command(flatten)]
+    network: NetworkArgs,
+    /// Add a new profile even if one for the SSID is saved.
+    #[arg(long)]
+    allow_duplicates: bool,
+    /// Re-activate the connection even if already connected to the network as asked.
+    #[arg(long)]
+    force: bool,
+    /// If a profile with the same UUID turns up that another tool saved, add a separate
+    /// one instead of taking that one over.
+    #[arg(long)]
+    isolate_profile: bool,
+    /// Turn Wi-Fi on first if it is switched off in software.
+    #[arg(long)]
+    enable_radio: bool,
+    /// Once connected, check whether the internet is reachable (captive portals, dead
+    /// uplinks) and report the result.
+    #[arg(long)]
+    check_connectivity: bool,
+    /// Keep the new profile if the connection doesn't come up, instead of removing it and
+    /// re-activating the previous connection.
+    #[arg(long)]
+    keep_on_failure: bool,
+    /// Don't connect if the network's signal strength is below this percentage.
+    #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(0..=100))]
+    min_strength: Option<u8>,
+    /// Scan first and connect through the strongest access point broadcasting the SSID,
+    /// instead of the one NetworkManager picks.
+    #[arg(long)]
+    pin_strongest_ap: bool,
+    /// Print the device's state changes while connecting.
+    #[arg(short, long)]
+    verbose: bool,
+    /// Print the settings that would be sent to NetworkManager instead of connecting.
+    #[arg(long, conflicts_with_all = ["verbose", "check_connectivity", "enable_radio"])]
+    dry_run: bool,
+    /// Show the secrets in the --dry-run output instead of masking them.
+    #[arg(long, requires = "dry_run")]
+    show_secrets: bool,
+    /// Take the SSID and all network options from a JSON profile instead; secrets
+    /// written as "env:VAR" are read from the environment variable VAR.
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["ssid", "ssid_option", "ssid_hex", "qr", "password_arg"],
+    )]
+    from_json: Option<PathBuf>,
+    /// Without a password, use the one stored in the keyring; with one, store it there
+    /// once connected.
+    #[cfg(feature = "keyring")]
+    #[arg(long)]
+    use_keyring: bool,
+    /// Timeout of each call to NetworkManager, in seconds.
+    #[arg(long, value_name = "SECS", value_parser = parse_timeout)]
+    timeout: Option<DbusTimeouts>,
+    /// Retry finding the device and activating up to N times while NetworkManager is
+    /// unreachable or times out, e.g. right after boot.
+    #[arg(long, value_name = "N")]
+    retries: Option<u32>,
+    #[command(flatten)]
+    backend: BackendArgs,
+}
+
+#[derive(Debug, Args)]
+struct ExportKeyfileArgs {
+    #[command(flatten)]
+    network: NetworkArgs,
+    /// Write the keyfile to this path, readable only by its owner, instead of stdout.
+    #[arg(long, short, value_name = "PATH")]
+    output: Option<PathBuf>,
+}
+
+/// The network and profile settings shared by `connect` and `export-keyfile`.
+#[derive(Debug, Args)]
+struct NetworkArgs {
+    /// SSID of the network; omit with --ssid or --ssid-hex.
+    #[arg(value_name = "SSID")]
+    ssid: Option<String>,
+    /// WPA passphrase, same as --password. Without a password (and without
+    /// WIFI_CONFIG_PASSWORD) the network is open.
+    #[arg(value_name = "PASSWORD")]
+    password_arg: Option<String>,
+    #[command(flatten)]
+    password: PasswordArgs,
+    /// Give the SSID as an option, e.g. for names starting with a dash:
+    /// --ssid '-Guest 5GHz'. Whitespace is kept as it is.
+    #[arg(
+        long = "ssid",
+        value_name = "SSID",
+        allow_hyphen_values = true,
+        conflicts_with = "ssid_hex"
+    )]
+    ssid_option: Option<String>,
+    /// Give the SSID as hex bytes, for names that aren't UTF-8.
+    #[arg(long, value_name = "HEX", value_parser = parse_hex)]
+    ssid_hex: Option<HexBytes>,
+    /// Take the SSID, security and password from a Wi-Fi QR code payload,
+    /// e.g. 'WIFI:T:WPA;S:Office;P:supersecret123;;'.
+    #[arg(
+        long,
+        value_name = "PAYLOAD",
+        value_parser = parse_qr,
+        conflicts_with_all = [
+            "ssid", "ssid_option", "ssid_hex", "password_arg", "password", "password_stdin",
+            "ask", "owe", "auto",
+        ],
+    )]
+    qr: Option<WifiQr>,
+    /// The SSID is not broadcast.
+    #[arg(long)]
+    hidden: bool,
+    /// Enhanced Open (OWE) network; takes no password.
+    #[arg(long, conflicts_with = "auto")]
+    owe: bool,
+    /// Detect the security type from the scan results.
+    #[arg(long)]
+    auto: bool,
+    /// Use this Wi-Fi device instead of picking one by --devices.
+    #[arg(long, value_name = "NAME")]
+    interface: Option<String>,
+    /// Which Wi-Fi devices to use: the first one, the best usable one (default) or all
+    /// usable ones.
+    #[arg(long, value_name = "first|best|all", value_parser = parse_device_selection)]
+    devices: Option<DeviceSelection>,
+    /// Static IPv4 address instead of DHCP.
+    #[arg(long, value_name = "ADDRESS/PREFIX", value_parser = parse_ipv4)]
+    ip: Option<(Ipv4Addr, u8)>,
+    /// IPv4 default gateway, used with --ip.
+    #[arg(long, value_name = "ADDRESS", requires = "ip")]
+    gateway: Option<Ipv4Addr>,
+    /// Don't configure IPv6 on this connection.
+    #[arg(long)]
+    no_ipv6: bool,
+    /// DNS server to use instead of DHCP's; repeatable.
+    #[arg(long, value_name = "ADDRESS")]
+    dns: Vec<IpAddr>,
+    /// DNS search domain; repeatable.
+    #[arg(long, value_name = "DOMAIN")]
+    dns_search: Vec<String>,
+    /// Hostname to send to the DHCP server instead of this host's.
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["ip", "no_send_hostname"])]
+    dhcp_hostname: Option<String>,
+    /// Don't send a hostname to the DHCP server.
+    #[arg(long, conflicts_with = "ip")]
+    no_send_hostname: bool,
+    /// DHCP client identifier: mac, perm-mac, duid, stable or a hex string.
+    #[arg(long, value_name = "ID", conflicts_with = "ip")]
+    dhcp_client_id: Option<String>,
+    /// Only use the profile when activated explicitly.
+    #[arg(long)]
+    no_autoconnect: bool,
+    /// Autoconnect priority; higher wins over other profiles.
+    #[arg(long, value_name = "N", allow_negative_numbers = true)]
+    priority: Option<i32>,
+    /// Mark the connection as metered.
+    #[arg(long)]
+    metered: bool,
+    /// Where NetworkManager keeps the password: in the profile (system), with the
+    /// user's secret agent (agent) or nowhere (not-saved).
+    #[arg(long, value_name = "STORAGE", value_parser = parse_secret_storage)]
+    secret_storage: Option<SecretStorage>,
+    /// Only let this user see and activate the profile; repeatable.
+    #[arg(long, value_name = "NAME")]
+    user: Vec<String>,
+    /// Name of the profile; defaults to the SSID.
+    #[arg(long, value_name = "NAME")]
+    id: Option<String>,
+    /// UUID of the profile: update the saved one with this UUID, or add one under it.
+    #[arg(long, value_name = "UUID")]
+    uuid: Option<String>,
+    /// Derive the profile's UUID from the SSID (and --interface), so re-running the same
+    /// command updates one profile.
+    #[arg(long, conflicts_with = "uuid")]
+    stable_uuid: bool,
+    /// firewalld zone of the connection, e.g. trusted or public.
+    #[arg(long, value_name = "ZONE")]
+    zone: Option<String>,
+    /// Only associate with the access point with this BSSID.
+    #[arg(long, value_name = "MAC", value_parser = parse_mac)]
+    bssid: Option<MacAddr>,
+    /// Lock the connection to 5 GHz (a) or 2.4 GHz (bg).
+    #[arg(long, value_name = "a|bg", value_parser = parse_band)]
+    band: Option<Band>,
+    /// Lock the connection to a channel within --band.
+    #[arg(long, value_name = "N")]
+    channel: Option<u32>,
+    /// random, stable, preserve or a MAC address to use.
+    #[arg(long, value_name = "POLICY", value_parser = parse_mac_policy)]
+    mac: Option<MacPolicy>,
+    /// Wi-Fi power saving: default, ignore, disable or enable.
+    #[arg(long, value_name = "MODE", value_parser = parse_powersave)]
+    powersave: Option<PowerSave>,
+    /// MTU of the connection, at least 576; 0 for automatic.
+    #[arg(long, value_name = "BYTES")]
+    mtu: Option<u32>,
+    /// Refuse WPA1 and TKIP: only WPA2 (RSN) with CCMP.
+    #[arg(long)]
+    wpa2_only: bool,
+}
+
+#[derive(Debug, Args)]
+struct HotspotArgs {
+    /// SSID to broadcast.
+    ssid: String,
+    /// WPA2 passphrase, 8 to 63 characters; falls back to WIFI_CONFIG_PASSWORD.
+    #[arg(value_name = "PASSWORD")]
+    password_arg: Option<String>,
+    #[command(flatten)]
+    password: PasswordArgs,
+    /// 5 GHz (a) or 2.4 GHz (bg).
+    #[arg(long, value_name = "a|bg", value_parser = parse_band)]
+    band: Option<Band>,
+    /// Channel within --band.
+    #[arg(long, value_name = "N")]
+    channel: Option<u32>,
+    /// Use this Wi-Fi device instead of the first one.
+    #[arg(long, value_name = "NAME")]
+    interface: Option<String>,
+    /// firewalld zone of the hotspot connection.
+    #[arg(long, value_name = "ZONE")]
+    zone: Option<String>,
+    /// Wi-Fi power saving: default, ignore, disable or enable.
+    #[arg(long, value_name = "MODE", value_parser = parse_powersave)]
+    powersave: Option<PowerSave>,
+    /// MTU of the hotspot, at least 576; 0 for automatic.
+    #[arg(long, value_name = "BYTES")]
+    mtu: Option<u32>,
+    /// Timeout of each call to NetworkManager, in seconds.
+    #[arg(long, value_name = "SECS", value_parser = parse_timeout)]
+    timeout: Option<DbusTimeouts>,
+}
+
+/// Selects the service `connect`, `scan` and `status` talk to.
+#[derive(Debug, Args)]
+struct BackendArgs {
+    /// networkmanager, iwd or wpa_supplicant; defaults to whichever is running.
+    #[arg(long, value_name = "BACKEND", value_parser = parse_backend)]
+    backend: Option<Backend>,
+}
+
+impl BackendArgs {
+    /// Returns the given backend, or detects the running one.
+    fn resolve(self) -> Backend {
+        self.backend
+            .unwrap_or_else(|| Backend::detect().unwrap_or_else(|e| fail(&e)))
+    }
+}
+
+/// Bytes given as hex digits. clap would take a `Vec<u8>` field for a list of values,
+/// one byte each, rather than a single argument.
+type HexBytes = Vec<u8>;
+
+/// Ways to pass the password other than the positional argument.
 ///
-/// - Initializes logger
-/// - Reads SSID and password from CLI args
-/// - Calls [`send_wifi_to_network_manager`]
+/// Anything on the command line is visible to other users in `/proc/<pid>/cmdline`, so
+/// prefer `--password-stdin`, `--ask` or the `WIFI_CONFIG_PASSWORD` environment variable.
+#[derive(Debug, Args)]
+struct PasswordArgs {
+    /// WPA passphrase.
+    #[arg(long, value_name = "PASSWORD", conflicts_with = "password_arg")]
+    password: Option<String>,
+    /// Read the passphrase from the first line of stdin.
+    #[arg(long, conflicts_with_all = ["password", "password_arg"])]
+    password_stdin: bool,
+    /// Prompt for the passphrase without echoing it.
+    #[arg(long, conflicts_with_all = ["password", "password_arg", "password_stdin"])]
+    ask: bool,
+}
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+impl PasswordArgs {
+    /// Whether one of the password flags was given.
+    fn is_given(&self) -> bool {
+        self.password.is_some() || self.password_stdin || self.ask
+    }
+
+    /// Returns the password from `positional` or a flag; `None` if neither was given.
+    fn explicit(self, positional: Option<String>) -> Option<String> {
+        if self.password_stdin {
+            let mut line = String::new();
+            match std::io::stdin().lock().read_line(&mut line) {
+                Ok(0) => exit_with("no password on stdin"),
+                Ok(_) => Some(line.trim_end_matches(['\n', '\r']).to_string()),
+                Err(e) => exit_with(&format!("can't read the password from stdin: {}", e)),
+            }
+        } else if self.ask {
+            let password = rpassword::prompt_password("Password: ")
+                .unwrap_or_else(|e| exit_with(&format!("can't read the password: {}", e)));
+            Some(password)
+        } else {
+            positional.or(self.password)
+        }
+    }
+}
+
+/// Returns the password from `WIFI_CONFIG_PASSWORD`, if it is set and not empty.
+fn password_from_env() -> Option<String> {
+    std::env::var(PASSWORD_ENV)
+        .ok()
+        .filter(|password| !password.is_empty())
+}
+
+/// CLI wrapper for the `wifi_config` library.
+///
+/// - Initializes logger
+/// - Parses the subcommand with clap; arguments that don't start with a subcommand are
+///   treated as `connect`, so `wifi-config <SSID> <PASSWORD>` keeps working
+/// - Takes the password from the positional argument, `--password`, `--password-stdin` or
+///   `--ask`, and otherwise from `WIFI_CONFIG_PASSWORD`
+/// - `connect --qr` takes SSID, security and password from a Wi-Fi QR code payload
+/// - `connect` calls [`connect_and_wait`], so the exit code reflects whether the
+///   connection came up; `connect --dry-run` prints what [`wifi_config::dry_run`] would
+///   send instead
+/// - `connect`, `scan` and `status` go through the backend given by `--backend`, or the
+///   one [`Backend::detect`] finds
+/// - `connect-any` tries the profiles of a JSON file with [`connect_first_available`]
+/// - `add` saves the profile with [`save_connection`], then activates it with
+///   [`activate_saved`] unless given `--no-activate`
+/// - `list`, `forget`, `disconnect`, `reconnect` and `hotspot` each call the library
+///   function of the same name; `apply` calls [`apply_config_file`]; `devices` prints
+///   [`list_wifi_devices`] with each device's [`DeviceCapabilities`]
+/// - `export-keyfile` renders the profile with [`WifiConnection::to_keyfile`], or writes
+///   it with [`WifiConnection::write_keyfile`] given `--output`
+/// - `import-keyfile` reads a keyfile with [`import_keyfile`] and saves it, or activates
+///   it given `--activate`
+/// - `--json` prints the result as one JSON object, and errors as
+///   `{"error": {"kind": ..., "message": ...}}` on stderr
+/// - Exits with a non-zero code (see [`exit_code`]) if configuration fails
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init();
-    let args: Vec<String> = std::env::args().collect();
 
-    if args.len() != 3 {
-        eprintln!("Usage: wifi-config <SSID> <PASSWORD>");
+    let args: Vec<String> = std::env::args().collect();
+    // Decided before parsing so that usage errors are reported as JSON as well.
+    JSON_OUTPUT.store(args.iter().any(|arg| arg == "--json"), Ordering::Relaxed);
+    let cli = Cli::try_parse_from(with_default_subcommand(args)).unwrap_or_else(|e| {
+        // Keep 0 for --help / --version and 1 for usage errors, as before clap.
+        if !e.use_stderr() {
+            let _ = e.print();
+            std::process::exit(0);
+        }
+        if json_output() {
+            let rendered = e.render().to_string();
+            let message = rendered.lines().next().unwrap_or_default();
+            exit_with(message.strip_prefix("error: ").unwrap_or(message));
+        }
+        let _ = e.print();
         std::process::exit(1);
-    }
+    });
+    JSON_OUTPUT.store(cli.json, Ordering::Relaxed);
 
-    let ssid = &args[1];
-    let password = &args[2];
-    send_wifi_to_network_manager(ssid, password);
+    match cli.command {
+        Command::Connect(args) => run_connect(*args),
+        Command::Activate { profile } => {
+            let handle = activate_saved(&profile).unwrap_or_else(|e| fail(&e));
+            if json_output() {
+                print_json(&handle);
+            } else {
+                println!("Activating the saved profile '{}'.", profile);
+            }
+        }
+        Command::Reconnect { profile } => {
+            let handle = reconnect(profile.as_deref()).unwrap_or_else(|e| fail(&e));
+            if json_output() {
+                print_json(&handle);
+            } else {
+                println!("Reconnected on {} ({}).", handle.interface, handle.uuid);
+            }
+        }
+        Command::Scan {
+            backend,
+            interface,
+            rescan,
+        } => print_access_points(backend.resolve(), interface.as_deref(), rescan),
+        Command::Status { backend, interface } => {
+            print_status(backend.resolve(), interface.as_deref())
+        }
+        Command::List => print_saved_connections(),
+        Command::Devices => print_devices(),
+        Command::Forget { ssid } => {
+            let removed = forget_network(&ssid).unwrap_or_else(|e| fail(&e));
+            if json_output() {
+                print_json(&json!({ "ssid": ssid, "removed": removed }));
+            } else {
+                println!("Removed {} saved profile(s) for '{}'.", removed, ssid);
+            }
+        }
+        Command::Reset { yes, dry_run } => run_reset(yes, dry_run),
+        Command::Disconnect {
+            interface,
+            no_autoconnect,
+        } => {
+            let outcome = match &interface {
+                Some(interface) => disconnect_interface(interface, no_autoconnect),
+                None => disconnect(no_autoconnect),
+            };
+            match outcome.unwrap_or_else(|e| fail(&e)) {
+                outcome if json_output() => print_json(&json!({ "outcome": outcome })),
+                DisconnectOutcome::Disconnected => println!("Disconnected."),
+                DisconnectOutcome::AlreadyDisconnected => println!("Already disconnected."),
+            }
+        }
+        Command::Hotspot(args) => run_hotspot(args),
+        Command::Wps { ssid } => {
+            let handle = connect_wps_pbc(ssid.as_deref()).unwrap_or_else(|e| fail(&e));
+            if json_output() {
+                print_json(&handle);
+            } else {
+                println!("Connected with WPS on {}.", handle.interface);
+            }
+        }
+        Command::ConnectAny { from_json } => run_connect_any(&from_json),
+        Command::Add(args) => run_add(*args),
+        Command::Apply { file } => run_apply(&file),
+        Command::ExportKeyfile(args) => run_export_keyfile(*args),
+        Command::ImportKeyfile { file, activate } => run_import_keyfile(&file, activate),
+        Command::Watch { interface } => run_watch(interface.as_deref()),
+    }
     Ok(())
 }
+
+/// Inserts `connect` after the program name unless the arguments already start with a
+/// subcommand or a top-level flag such as `--help`. A leading `--json` is skipped.
+fn with_default_subcommand(mut args: Vec<String>) -> Vec<String> {
+    let Some(first) = args.iter().skip(1).find(|arg| *arg != "--json") else {
+        return args;
+    };
+    let command = Cli::command();
+    let is_subcommand = first == "help"
+        || command
+            .get_subcommands()
+            .any(|subcommand| subcommand.get_name() == first);
+    let is_top_level_flag = matches!(first.as_str(), "-h" | "--help" | "-V" | "--version");
+    if !is_subcommand && !is_top_level_flag {
+        args.insert(1, "connect".into());
+    }
+    args
+}
+
+/// Handles `connect`.
+fn run_connect(args: ConnectArgs) {
+    let profile = args.from_json.as_deref().map(read_json_profile);
+    let (ssid, mut options) = match profile {
+        // The profile names its backend; only an explicit --backend overrides it.
+        Some((ssid, mut options)) => {
+            if let Some(backend) = args.backend.backend {
+                options.backend = backend;
+            }
+            (ssid, options)
+        }
+        None => {
+            let (ssid, mut options) = network_options(args.network);
+            options.backend = args.backend.resolve();
+            (ssid, options)
+        }
+    };
+    options.allow_duplicates |= args.allow_duplicates;
+    options.force |= args.force;
+    options.isolate_profile |= args.isolate_profile;
+    options.enable_radio |= args.enable_radio;
+    options.verify_connectivity |= args.check_connectivity;
+    options.keep_on_failure |= args.keep_on_failure;
+    options.pin_strongest_ap |= args.pin_strongest_ap;
+    if let Some(strength) = args.min_strength {
+        options.min_strength = Some(strength);
+    }
+    if let Some(timeouts) = args.timeout {
+        options.timeouts = timeouts;
+    }
+    if let Some(retries) = args.retries {
+        options.retry = RetryPolicy::retries(retries);
+    }
+
+    #[cfg(feature = "keyring")]
+    let store_password = match &options.security {
+        _ if !args.use_keyring => None,
+        Security::Open => {
+            let password = wifi_config::lookup_secret(&ssid).unwrap_or_else(|e| fail(&e));
+            options.security = Security::WpaPsk(password);
+            None
+        }
+        Security::WpaPsk(password) => Some(password.clone()),
+        _ => None,
+    };
+
+    if args.dry_run {
+        match wifi_config::dry_run(&ssid, &options, args.show_secrets) {
+            Ok(dry_run) => print_dry_run(&ssid, &dry_run),
+            Err(e) => fail(&e),
+        }
+        return;
+    }
+
+    let name = String::from_utf8_lossy(&ssid);
+    let result = if args.verbose {
+        connect_and_wait_with_progress(&ssid, &options, print_state_change)
+    } else {
+        connect_and_wait(&ssid, &options)
+    };
+    #[cfg(feature = "keyring")]
+    if let (Ok(_), Some(password)) = (&result, store_password) {
+        if let Err(e) = wifi_config::store_secret(&ssid, &password) {
+            eprintln!("warning: the password was not stored in the keyring: {}", e);
+        }
+    }
+    match result {
+        Ok(handle) if json_output() => print_json(&ProfileResult {
+            ssid: &name,
+            // `connect_and_wait` only returns once the device is up.
+            state: Some(DeviceState::Activated),
+            handle: &handle,
+        }),
+        Ok(handle) => {
+            let interfaces: Vec<&str> = std::iter::once(handle.interface.as_str())
+                .chain(
+                    handle
+                        .additional
+                        .iter()
+                        .map(|other| other.interface.as_str()),
+                )
+                .filter(|interface| !interface.is_empty())
+                .collect();
+            let connected = if handle.change == ProfileChange::AlreadyConnected {
+                "Already connected"
+            } else {
+                "Connected"
+            };
+            if interfaces.is_empty() {
+                println!("{} to '{}'.", connected, name);
+            } else {
+                println!("{} to '{}' on {}.", connected, name, interfaces.join(", "));
+            }
+            if let Some(ip) = &handle.ip {
+                print_ip_info(ip);
+            }
+            if let Some(security) = handle.detected_security {
+                println!("Detected {:?} security.", security);
+            }
+            match &handle.access_point {
+                Some(ap) if ap.pinned => {
+                    println!("Pinned to access point {} ({}%).", ap.bssid, ap.strength)
+                }
+                Some(ap) => println!(
+                    "Access point {} ({}%) went away; NetworkManager picked another.",
+                    ap.bssid, ap.strength
+                ),
+                None => {}
+            }
+            match handle.connectivity {
+                Some(Connectivity::Full) => println!("The internet is reachable."),
+                Some(Connectivity::Portal) => match &handle.portal_url {
+                    Some(url) => println!("Captive portal detected; log in at {}", url),
+                    None => println!("Captive portal detected; log in first."),
+                },
+                Some(Connectivity::Limited | Connectivity::None) => {
+                    println!("Connected, but the internet is not reachable.")
+                }
+                Some(Connectivity::Unknown) => {
+                    println!("Connectivity unknown; NetworkManager's check may be disabled.")
+                }
+                None => {}
+            }
+            match handle.change {
+                ProfileChange::Created => println!("Created a new profile for '{}'.", name),
+                ProfileChange::Updated => println!("Updated the saved profile for '{}'.", name),
+                ProfileChange::UpdatedUnverified => println!(
+                    "Updated the saved profile for '{}'; its password couldn't be read to \
+                     tell whether that was needed.",
+                    name
+                ),
+                ProfileChange::Unchanged => {
+                    println!("The saved profile for '{}' already matched.", name)
+                }
+                ProfileChange::AdoptedExisting => println!(
+                    "A profile for '{}' with the same UUID already existed; updated it.",
+                    name
+                ),
+                ProfileChange::CreatedSeparate => println!(
+                    "A profile for '{}' with the same UUID already existed; created '{} \
+                     (wifi-config)' next to it.",
+                    name, name
+                ),
+                ProfileChange::AlreadyConnected => {}
+            }
+        }
+        Err(e) => fail(&e),
+    }
+}
+
+/// Prints the address, gateways and DNS servers of a connection that is up.
+fn print_ip_info(ip: &IpInfo) {
+    if let Some(address) = ip.address() {
+        println!("Address: {}", address);
+    }
+    let gateways: Vec<String> = ip
+        .ipv4_gateway
+        .map(IpAddr::V4)
+        .into_iter()
+        .chain(ip.ipv6_gateway.map(IpAddr::V6))
+        .map(|gateway| gateway.to_string())
+        .collect();
+    if !gateways.is_empty() {
+        println!("Gateway: {}", gateways.join(", "));
+    }
+    if !ip.dns.is_empty() {
+        let dns: Vec<String> = ip.dns.iter().map(IpAddr::to_string).collect();
+        println!("DNS: {}", dns.join(", "));
+    }
+}
+
+/// Handles `export-keyfile`.
+fn run_export_keyfile(args: ExportKeyfileArgs) {
+    let (ssid, options) = network_options(args.network);
+    let connection = WifiConnection::builder(ssid)
+        .options(options)
+        .build()
+        .unwrap_or_else(|e| fail(&e));
+    match &args.output {
+        Some(path) => {
+            connection
+                .write_keyfile(path, 0o600)
+                .unwrap_or_else(|e| fail(&e));
+            if json_output() {
+                print_json(&json!({ "path": path }));
+            } else {
+                println!("Wrote {}.", path.display());
+            }
+        }
+        None if json_output() => print_json(&json!({ "keyfile": connection.to_keyfile() })),
+        None => print!("{}", connection.to_keyfile()),
+    }
+}
+
+/// Handles `add`.
+fn run_add(args: AddArgs) {
+    let (ssid, mut options) = network_options(args.network);
+    options.allow_duplicates = args.allow_duplicates;
+    let name = String::from_utf8_lossy(&ssid).into_owned();
+    let profile = save_connection(&ssid, &options).unwrap_or_else(|e| fail(&e));
+    if args.no_activate {
+        if json_output() {
+            print_json(&profile);
+        } else {
+            let change = match profile.change {
+                ProfileChange::Created | ProfileChange::CreatedSeparate => "Saved",
+                ProfileChange::Updated
+                | ProfileChange::UpdatedUnverified
+                | ProfileChange::AdoptedExisting => "Updated",
+                ProfileChange::Unchanged | ProfileChange::AlreadyConnected => "Kept",
+            };
+            println!("{} the profile for '{}' ({}).", change, name, profile.uuid);
+        }
+        return;
+    }
+    let handle = activate_saved(&profile.uuid).unwrap_or_else(|e| fail(&e));
+    if json_output() {
+        print_json(&handle);
+    } else {
+        println!("Activating '{}' on {}.", name, handle.interface);
+    }
+}
+
+/// Handles `import-keyfile`.
+fn run_import_keyfile(file: &Path, activate: bool) {
+    let config = import_keyfile(file).unwrap_or_else(|e| fail(&e));
+    let name = String::from_utf8_lossy(config.ssid()).into_owned();
+    if activate {
+        let handle = config.connect().unwrap_or_else(|e| fail(&e));
+        if json_output() {
+            print_json(&handle);
+        } else {
+            println!("Activating '{}' on {}.", name, handle.interface);
+        }
+    } else {
+        let profile = config.save().unwrap_or_else(|e| fail(&e));
+        if json_output() {
+            print_json(&profile);
+        } else {
+            let change = match profile.change {
+                ProfileChange::Created | ProfileChange::CreatedSeparate => "Saved",
+                ProfileChange::Updated
+                | ProfileChange::UpdatedUnverified
+                | ProfileChange::Unchanged
+                | ProfileChange::AdoptedExisting
+                | ProfileChange::AlreadyConnected => "Replaced",
+            };
+            println!("{} the profile for '{}' ({}).", change, name, profile.uuid);
+        }
+    }
+}
+
+/// Builds the SSID and options given by the network arguments.
+fn network_options(args: NetworkArgs) -> (Vec<u8>, ConnectOptions) {
+    let (ssid, security, hidden) = match args.qr {
+        Some(qr) => (qr.ssid, qr.security, qr.hidden || args.hidden),
+        None => {
+            let (ssid, security) = connect_credentials(
+                args.ssid_hex.or(args.ssid_option.map(String::into_bytes)),
+                args.ssid,
+                args.password_arg,
+                args.password,
+                args.owe,
+                args.auto,
+            );
+            (ssid, security, args.hidden)
+        }
+    };
+
+    let mut options = ConnectOptions::default();
+    options.security = security;
+    options.hidden = hidden;
+    options.interface = args.interface;
+    options.device_selection = args.devices.unwrap_or_default();
+    options.dns = args.dns;
+    options.dns_search = args.dns_search;
+    options.dhcp = DhcpOptions {
+        hostname: args.dhcp_hostname,
+        send_hostname: args.no_send_hostname.then_some(false),
+        client_id: args.dhcp_client_id,
+    };
+    options.autoconnect_priority = args.priority;
+    options.band = args.band;
+    options.channel = args.channel;
+    options.bssid = args.bssid;
+    options.mac_policy = args.mac;
+    options.powersave = args.powersave;
+    options.mtu = args.mtu;
+    options.cipher_policy = args.wpa2_only.then_some(CipherPolicy::Wpa2Only);
+    options.secret_storage = args.secret_storage.unwrap_or_default();
+    options.permissions = args.user;
+    options.connection_id = args.id;
+    options.uuid = args.uuid;
+    options.stable_uuid = args.stable_uuid;
+    options.zone = args.zone;
+    if let Some((address, prefix)) = args.ip {
+        options.ipv4 = Ipv4Config::Manual {
+            address,
+            prefix,
+            gateway: args.gateway,
+        };
+    }
+    if args.no_ipv6 {
+        options.ipv6 = Ipv6Config::Ignore;
+    }
+    if args.no_autoconnect {
+        options.autoconnect = Some(false);
+    }
+    if args.metered {
+        options.metered = Some(Metered::Yes);
+    }
+    (ssid, options)
+}
+
+/// Handles `connect-any`.
+fn run_connect_any(path: &Path) {
+    let json = std::fs::read_to_string(path)
+        .map_err(|e| WifiConfigError::ConfigFileRead(path.to_path_buf(), e))
+        .unwrap_or_else(|e| fail(&e));
+    let candidates =
+        wifi_config::NetworkCandidate::list_from_json(&json).unwrap_or_else(|e| fail(&e));
+    let connected = wifi_config::connect_first_available(&candidates).unwrap_or_else(|e| fail(&e));
+    let name = String::from_utf8_lossy(&connected.ssid);
+    if json_output() {
+        let failures: Vec<_> = connected
+            .failures
+            .iter()
+            .map(|failure| {
+                json!({
+                    "ssid": String::from_utf8_lossy(&failure.ssid),
+                    "kind": error_kind(&failure.error),
+                    "message": failure.error.to_string(),
+                })
+            })
+            .collect();
+        print_json(&json!({
+            "ssid": name,
+            "index": connected.index,
+            "handle": connected.handle,
+            "failures": failures,
+        }));
+    } else {
+        for failure in &connected.failures {
+            println!("Skipped {}", failure);
+        }
+        println!("Connected to '{}' on {}.", name, connected.handle.interface);
+    }
+}
+
+/// Reads the SSID and options of `connect --from-json`.
+fn read_json_profile(path: &Path) -> (Vec<u8>, ConnectOptions) {
+    let json = std::fs::read_to_string(path)
+        .map_err(|e| WifiConfigError::ConfigFileRead(path.to_path_buf(), e))
+        .unwrap_or_else(|e| fail(&e));
+    let connection = WifiConnection::from_json(&json).unwrap_or_else(|e| fail(&e));
+    (connection.ssid().to_vec(), connection.options().clone())
+}
+
+/// Determines the SSID and security of `connect` and `export-keyfile` from the positional arguments and
+/// password flags.
+fn connect_credentials(
+    ssid_option: Option<Vec<u8>>,
+    ssid: Option<String>,
+    password_arg: Option<String>,
+    password_args: PasswordArgs,
+    owe: bool,
+    auto: bool,
+) -> (Vec<u8>, Security) {
+    // With --ssid or --ssid-hex the only positional argument is the password. The SSID
+    // is passed on byte for byte, surrounding whitespace included.
+    let (ssid, password) = match (ssid_option, ssid, password_arg) {
+        (Some(ssid), password, None) => (ssid, password),
+        (None, Some(ssid), password) => (ssid.into_bytes(), password),
+        _ => usage_error(
+            "expected an SSID, --ssid or --ssid-hex followed by an optional password, or --qr",
+        ),
+    };
+    // clap only sees the conflict when the password is in the PASSWORD slot.
+    if password.is_some() && password_args.is_given() {
+        usage_error("the password was given more than once");
+    }
+    let password = match password_args.explicit(password) {
+        // Enhanced Open takes no password, so a globally set variable doesn't apply.
+        None if !owe => password_from_env(),
+        password => password,
+    };
+
+    let security = match password {
+        // Enhanced Open has no credentials.
+        Some(_) if owe => usage_error("--owe networks take no password"),
+        _ if owe => Security::Owe,
+        password if auto => Security::Auto(password.map(Into::into)),
+        Some(password) => Security::WpaPsk(password.into()),
+        None => Security::Open,
+    };
+    (ssid, security)
+}
+
+/// Handles `hotspot`.
+fn run_hotspot(args: HotspotArgs) {
+    let mut options = HotspotOptions {
+        band: args.band,
+        channel: args.channel,
+        interface: args.interface,
+        zone: args.zone,
+        powersave: args.powersave,
+        mtu: args.mtu,
+        ..Default::default()
+    };
+    if let Some(timeouts) = args.timeout {
+        options.timeouts = timeouts;
+    }
+    let password = args
+        .password
+        .explicit(args.password_arg)
+        .or_else(password_from_env)
+        .unwrap_or_else(|| exit_with("hotspot needs a password"));
+    let mut handle = create_hotspot(&args.ssid, &password, &options).unwrap_or_else(|e| fail(&e));
+    match ip_info(&handle, HOTSPOT_ADDRESS_WAIT) {
+        Ok(ip) => handle.ip = Some(ip),
+        Err(e) => eprintln!("warning: can't read the hotspot's address: {}", e),
+    }
+    if json_output() {
+        print_json(&ProfileResult {
+            ssid: &args.ssid,
+            state: None,
+            handle: &handle,
+        });
+    } else {
+        match handle.ip.as_ref().and_then(IpInfo::address) {
+            Some(address) => println!(
+                "Hotspot '{}' started; clients reach this host at {}.",
+                args.ssid, address
+            ),
+            None => println!("Hotspot '{}' started.", args.ssid),
+        }
+    }
+}
+
+/// Prints a state change for `connect --verbose` on stderr, so `--json` output stays
+/// clean.
+fn print_state_change(event: &DeviceStateEvent) {
+    match event.new {
+        DeviceState::Failed => eprintln!("{} → {} ({})", event.old, event.new, event.reason),
+        _ => eprintln!("{} → {}", event.old, event.new),
+    }
+}
+
+/// Prints the result of `connect --dry-run`: the settings by section, one key per line.
+fn print_dry_run(ssid: &[u8], dry_run: &DryRun) {
+    if json_output() {
+        print_json(dry_run);
+        return;
+    }
+    let name = String::from_utf8_lossy(ssid);
+    let interfaces = dry_run.interfaces.join(", ");
+    match &dry_run.profile_path {
+        Some(path) => println!(
+            "Would update profile {} for '{}' on {}.",
+            path, name, interfaces
+        ),
+        None => println!("Would add a profile for '{}' on {}.", name, interfaces),
+    }
+    if let Some(security) = dry_run.detected_security {
+        println!("Detected {:?} security.", security);
+    }
+    for (section, values) in &dry_run.settings {
+        println!("[{}]", section);
+        for (key, value) in values {
+            let shown = serde_json::to_string(&value.value).expect("values always serialize");
+            println!("  {} ({}) = {}", key, value.signature, shown);
+        }
+    }
+}
+
+/// JSON output of `connect` and `hotspot`.
+#[derive(Serialize)]
+struct ProfileResult<'a> {
+    ssid: &'a str,
+    /// Device state once the command returned; `None` if it didn't wait for activation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state: Option<DeviceState>,
+    #[serde(flatten)]
+    handle: &'a ActivationHandle,
+}
+
+/// Handles `apply`: reports every network, then exits with the first failure's exit code.
+fn run_apply(file: &Path) {
+    let networks = apply_config_file(file).unwrap_or_else(|e| fail(&e));
+    let first_failure = networks
+        .iter()
+        .find_map(|network| network.result.as_ref().err());
+
+    if json_output() {
+        let networks: Vec<_> = networks
+            .iter()
+            .map(|network| match &network.result {
+                Ok(profile) => json!({ "ssid": network.ssid, "profile": profile }),
+                Err(e) => json!({
+                    "ssid": network.ssid,
+                    "error": { "kind": error_kind(e), "message": e.to_string() },
+                }),
+            })
+            .collect();
+        print_json(&json!({ "networks": networks }));
+    } else {
+        for network in &networks {
+            match &network.result {
+                Ok(profile) => match profile.change {
+                    ProfileChange::Created | ProfileChange::CreatedSeparate => {
+                        println!("{}: created a new profile", network.ssid)
+                    }
+                    ProfileChange::Updated
+                    | ProfileChange::UpdatedUnverified
+                    | ProfileChange::AdoptedExisting => {
+                        println!("{}: updated the saved profile", network.ssid)
+                    }
+                    ProfileChange::Unchanged | ProfileChange::AlreadyConnected => {
+                        println!("{}: left the saved profile as it is", network.ssid)
+                    }
+                },
+                Err(e) => eprintln!("{}: {}", network.ssid, e),
+            }
+        }
+    }
+    if let Some(error) = first_failure {
+        std::process::exit(exit_code(error));
+    }
+}
+
+/// Handles `reset`: reports what was deleted, then exits with code 5 if any
+/// profile couldn't be.
+fn run_reset(yes: bool, dry_run: bool) {
+    if !yes && !dry_run {
+        exit_with("refusing to delete every saved Wi-Fi profile without --yes");
+    }
+    let report = reset_wifi_profiles(dry_run).unwrap_or_else(|e| fail(&e));
+    if json_output() {
+        print_json(&report);
+    } else if dry_run {
+        for id in &report.deleted {
+            println!("would delete {}", id);
+        }
+        println!("Would delete {} Wi-Fi profile(s).", report.deleted.len());
+    } else {
+        for failure in &report.failed {
+            eprintln!("{}: {}", failure.id, failure.reason);
+        }
+        println!(
+            "Deleted {} Wi-Fi profile(s), {} failed.",
+            report.deleted.len(),
+            report.failed.len()
+        );
+    }
+    if !report.failed.is_empty() {
+        let ids = report
+            .failed
+            .into_iter()
+            .map(|failure| failure.id)
+            .collect();
+        fail(&WifiConfigError::ResetIncomplete(ids));
+    }
+}
+
+/// Parses the value of `--mac`.
+fn parse_mac_policy(value: &str) -> Result<MacPolicy, String> {
+    match value {
+        "random" => Ok(MacPolicy::Random),
+        "stable" => Ok(MacPolicy::Stable),
+        "preserve" => Ok(MacPolicy::Preserve),
+        mac => Ok(MacPolicy::Explicit(parse_mac(mac)?.0)),
+    }
+}
+
+/// Parses the value of `--powersave`.
+fn parse_powersave(value: &str) -> Result<PowerSave, String> {
+    match value {
+        "default" => Ok(PowerSave::Default),
+        "ignore" => Ok(PowerSave::Ignore),
+        "disable" => Ok(PowerSave::Disable),
+        "enable" => Ok(PowerSave::Enable),
+        _ => Err("expected 'default', 'ignore', 'disable' or 'enable'".into()),
+    }
+}
+
+/// Parses a MAC address such as `AA:BB:CC:DD:EE:FF`.
+fn parse_mac(value: &str) -> Result<MacAddr, String> {
+    value.parse().map_err(|e: WifiConfigError| e.to_string())
+}
+
+/// Parses the value of `--ssid-hex`, e.g. `4d79e29da4`.
+fn parse_hex(hex: &str) -> Result<Vec<u8>, String> {
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .filter(|pair| pair.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+        })
+        .collect::<Option<Vec<u8>>>();
+    bytes.ok_or_else(|| "expected an even number of hex digits".into())
+}
+
+/// Parses the value of `--qr`.
+fn parse_qr(value: &str) -> Result<WifiQr, String> {
+    WifiQr::parse(value).map_err(|e| e.to_string())
+}
+
+/// Parses the value of `--devices`.
+fn parse_device_selection(value: &str) -> Result<DeviceSelection, String> {
+    match value {
+        "first" => Ok(DeviceSelection::First),
+        "best" => Ok(DeviceSelection::Best),
+        "all" => Ok(DeviceSelection::All),
+        _ => Err("expected 'first', 'best' or 'all'".into()),
+    }
+}
+
+/// Parses the value of `--band`.
+fn parse_band(value: &str) -> Result<Band, String> {
+    match value {
+        "a" => Ok(Band::A),
+        "bg" => Ok(Band::Bg),
+        _ => Err("expected 'a' (5 GHz) or 'bg' (2.4 GHz)".into()),
+    }
+}
+
+/// Parses the value of `--secret-storage`.
+fn parse_secret_storage(value: &str) -> Result<SecretStorage, String> {
+    match value {
+        "system" => Ok(SecretStorage::System),
+        "agent" => Ok(SecretStorage::AgentOwned),
+        "not-saved" => Ok(SecretStorage::NotSaved),
+        _ => Err("expected 'system', 'agent' or 'not-saved'".into()),
+    }
+}
+
+/// Parses the value of `--backend`.
+fn parse_backend(value: &str) -> Result<Backend, String> {
+    match value {
+        "networkmanager" => Ok(Backend::NetworkManager),
+        "iwd" => Ok(Backend::Iwd),
+        "wpa_supplicant" => Ok(Backend::WpaSupplicant),
+        _ => Err("expected 'networkmanager', 'iwd' or 'wpa_supplicant'".into()),
+    }
+}
+
+/// Parses the value of `--timeout`, which applies to queries and activation calls alike.
+fn parse_timeout(value: &str) -> Result<DbusTimeouts, String> {
+    match value.parse::<u64>() {
+        Ok(secs) if secs > 0 => Ok(DbusTimeouts::uniform(Duration::from_secs(secs))),
+        _ => Err("expected a positive number of seconds".into()),
+    }
+}
+
+/// Parses the value of `--ip`.
+///
+/// Only the syntax is checked here; the library validates the values.
+fn parse_ipv4(value: &str) -> Result<(Ipv4Addr, u8), String> {
+    value
+        .split_once('/')
+        .and_then(|(address, prefix)| Some((address.parse().ok()?, prefix.parse().ok()?)))
+        .ok_or_else(|| "expected ADDRESS/PREFIX, e.g. 192.168.1.50/24".into())
+}
+
+/// Prints `rows` under `header`, padding every column but the last to its widest cell.
+fn print_table<const N: usize>(header: [&str; N], rows: &[[String; N]]) {
+    let header = header.map(String::from);
+    let mut widths = [0; N];
+    for row in std::iter::once(&header).chain(rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in std::iter::once(&header).chain(rows) {
+        let mut line = String::new();
+        for (i, (cell, width)) in row.iter().zip(widths).enumerate() {
+            if i + 1 == N {
+                line.push_str(cell);
+            } else {
+                line.push_str(&format!("{:<width$}  ", cell, width = width));
+            }
+        }
+        println!("{}", line);
+    }
+}
+
+/// A manager pinned to the Wi-Fi device `interface`, for `scan` and `status`; only
+/// NetworkManager's devices can be picked by name.
+fn interface_manager(backend: Backend, interface: &str) -> WifiManager {
+    if backend != Backend::NetworkManager {
+        fail(&WifiConfigError::UnsupportedByBackend(
+            "--interface needs NetworkManager; the other backends use their first device".into(),
+        ));
+    }
+    WifiManager::new()
+        .map(|manager| manager.for_interface(interface))
+        .unwrap_or_else(|e| fail(&e))
+}
+
+/// Prints the visible access points as a table, strongest first.
+fn print_access_points(backend: Backend, interface: Option<&str>, rescan: bool) {
+    let access_points = match interface {
+        Some(interface) => interface_manager(backend, interface).scan(rescan),
+        None => backend.scan_networks(rescan),
+    };
+    let mut access_points = access_points.unwrap_or_else(|e| fail(&e));
+    access_points.sort_by_key(|ap| std::cmp::Reverse(ap.strength));
+    if json_output() {
+        print_json(&json!({ "access_points": access_points }));
+        return;
+    }
+
+    let rows: Vec<[String; 5]> = access_points
+        .iter()
+        .map(|ap| {
+            [
+                ap.ssid_lossy.clone(),
+                ap.hw_address.clone(),
+                format!("{}%", ap.strength),
+                format!("{} MHz", ap.frequency),
+                format!("{:?}", ap.security),
+            ]
+        })
+        .collect();
+    print_table(["SSID", "BSSID", "SIGNAL", "FREQUENCY", "SECURITY"], &rows);
+}
+
+/// Prints the saved Wi-Fi profiles as a table; skipped profiles go to stderr.
+fn print_saved_connections() {
+    let (profiles, warnings) = list_saved_connections_with_warnings().unwrap_or_else(|e| fail(&e));
+    if json_output() {
+        print_json(&json!({ "profiles": profiles, "warnings": warnings }));
+        return;
+    }
+
+    let rows: Vec<[String; 4]> = profiles
+        .iter()
+        .map(|profile| {
+            [
+                profile.id.clone(),
+                profile.ssid_lossy.clone(),
+                format!("{:?}", profile.security),
+                if profile.autoconnect { "yes" } else { "no" }.to_string(),
+            ]
+        })
+        .collect();
+    print_table(["NAME", "SSID", "SECURITY", "AUTOCONNECT"], &rows);
+
+    for warning in warnings {
+        eprintln!("warning: skipped {}: {}", warning.path, warning.reason);
+    }
+}
+
+/// Prints the Wi-Fi devices with their capabilities, as a table or a single JSON object.
+fn print_devices() {
+    let devices = list_wifi_devices().unwrap_or_else(|e| fail(&e));
+    if json_output() {
+        print_json(&json!({ "devices": devices }));
+        return;
+    }
+
+    let rows: Vec<[String; 6]> = devices
+        .iter()
+        .map(|device| {
+            [
+                device.interface.clone(),
+                device.driver.clone(),
+                device.state.to_string(),
+                device.perm_hw_address.clone(),
+                device.mtu.to_string(),
+                capability_names(&device.capabilities),
+            ]
+        })
+        .collect();
+    print_table(
+        ["DEVICE", "DRIVER", "STATE", "MAC", "MTU", "CAPABILITIES"],
+        &rows,
+    );
+}
+
+/// `capabilities` as a list like `ap, 2.4 GHz, 5 GHz`; the ciphers are left out.
+fn capability_names(capabilities: &DeviceCapabilities) -> String {
+    let bands = if capabilities.bands_known {
+        vec![
+            (capabilities.band_2ghz, "2.4 GHz"),
+            (capabilities.band_5ghz, "5 GHz"),
+            (capabilities.band_6ghz, "6 GHz"),
+        ]
+    } else {
+        vec![(true, "bands unknown")]
+    };
+    let names: Vec<&str> = [
+        (capabilities.ap, "ap"),
+        (capabilities.adhoc, "adhoc"),
+        (capabilities.mesh, "mesh"),
+    ]
+    .into_iter()
+    .chain(bands)
+    .filter_map(|(supported, name)| supported.then_some(name))
+    .collect();
+    if names.is_empty() {
+        "-".into()
+    } else {
+        names.join(", ")
+    }
+}
+
+/// JSON output of `status`: `{"connected": false}` (plus `radio` with NetworkManager), or
+/// `true` plus the [`ActiveConnectionInfo`] fields, which include `radio`.
+#[derive(Serialize)]
+struct StatusResult<'a> {
+    connected: bool,
+    #[serde(flatten)]
+    active: Option<&'a ActiveConnectionInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    radio: Option<RadioState>,
+}
+
+/// Prints the current connection, as `key: value` lines or a single JSON object.
+fn print_status(backend: Backend, interface: Option<&str>) {
+    let active = match interface {
+        Some(interface) => interface_manager(backend, interface).active_connection(),
+        None => backend.active_connection(),
+    };
+    let active = active.unwrap_or_else(|e| fail(&e));
+    // Without a connection, say whether that's because Wi-Fi is off.
+    let radio = match (&active, backend) {
+        (None, Backend::NetworkManager) => Some(radio_state().unwrap_or_else(|e| fail(&e))),
+        _ => None,
+    };
+    if json_output() {
+        print_json(&StatusResult {
+            connected: active.is_some(),
+            active: active.as_ref(),
+            radio,
+        });
+        return;
+    }
+    let Some(active) = active else {
+        match radio {
+            Some(RadioState::SoftBlocked) => {
+                println!("not connected: Wi-Fi is turned off (try --enable-radio)")
+            }
+            Some(RadioState::HardBlocked) => {
+                println!("not connected: Wi-Fi is blocked; flip the Wi-Fi switch or key")
+            }
+            _ => println!("not connected"),
+        }
+        return;
+    };
+
+    let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".into());
+    println!("interface:  {}", active.interface);
+    println!("state:      {:?}", active.state);
+    println!("connection: {}", active.connection_id);
+    println!("ssid:       {}", String::from_utf8_lossy(&active.ssid));
+    if !active.uuid.is_empty() {
+        println!("uuid:       {}", active.uuid);
+    }
+    println!(
+        "bssid:      {}",
+        or_dash(active.bssid.map(|bssid| bssid.to_string()))
+    );
+    println!(
+        "signal:     {}",
+        or_dash(active.strength.map(|strength| format!("{}%", strength)))
+    );
+    println!(
+        "ipv4:       {}",
+        or_dash(active.ip4.map(|ip4| ip4.to_string()))
+    );
+    if let Some(mtu) = active.mtu {
+        println!("mtu:        {}", mtu);
+    }
+    match active.radio {
+        RadioState::Enabled => println!("radio:      on"),
+        RadioState::SoftBlocked => println!("radio:      off"),
+        RadioState::HardBlocked => println!("radio:      blocked"),
+    }
+    match active.connectivity {
+        Connectivity::Portal => match &active.portal_url {
+            Some(url) => println!("internet:   captive portal detected; log in at {}", url),
+            None => println!("internet:   captive portal detected"),
+        },
+        Connectivity::Full => println!("internet:   reachable"),
+        Connectivity::Limited | Connectivity::None => println!("internet:   not reachable"),
+        Connectivity::Unknown => println!("internet:   -"),
+    }
+    // iwd and wpa_supplicant don't say since when the connection is up.
+    if backend == Backend::NetworkManager {
+        let up = SystemTime::now()
+            .duration_since(active.started_at)
+            .unwrap_or_default();
+        println!("up:         {}s", up.as_secs());
+    }
+}
+
+/// Handles `watch`.
+fn run_watch(interface: Option<&str>) {
+    let watcher = watch_wifi(interface).unwrap_or_else(|e| fail(&e));
+    for event in watcher {
+        let since_epoch = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        if json_output() {
+            #[derive(Serialize)]
+            struct Line<'a> {
+                time: f64,
+                #[serde(flatten)]
+                event: &'a WifiEvent,
+            }
+            print_json(&Line {
+                time: since_epoch.as_millis() as f64 / 1000.0,
+                event: &event,
+            });
+        } else {
+            println!("{} {}", time_of_day(since_epoch), describe_event(&event));
+        }
+    }
+}
+
+/// `HH:MM:SS.mmm` in UTC, for `since_epoch` after the Unix epoch.
+fn time_of_day(since_epoch: Duration) -> String {
+    let seconds = since_epoch.as_secs() % 86_400;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// One line of `watch` output for `event`.
+fn describe_event(event: &WifiEvent) -> String {
+    match event {
+        WifiEvent::StateChanged {
+            interface,
+            old,
+            new,
+            reason,
+        } => format!("{}: {} → {} ({})", interface, old, new, reason),
+        WifiEvent::AccessPointChanged {
+            interface,
+            access_point: Some(ap),
+        } => format!(
+            "{}: access point {} ({}, {} MHz, {}%)",
+            interface, ap.ssid_lossy, ap.hw_address, ap.frequency, ap.strength
+        ),
+        WifiEvent::AccessPointChanged {
+            interface,
+            access_point: None,
+        } => format!("{}: no access point", interface),
+        WifiEvent::StrengthChanged {
+            interface,
+            strength,
+        } => format!("{}: signal {}%", interface, strength),
+        WifiEvent::NetworkManagerStopped => "NetworkManager stopped".into(),
+        WifiEvent::NetworkManagerStarted => "NetworkManager started".into(),
+    }
+}
+
+/// Whether `--json` was given.
+fn json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// Prints `value` to stdout as a single line of JSON.
+fn print_json(value: &impl Serialize) {
+    println!(
+        "{}",
+        serde_json::to_string(value).expect("CLI output always serializes")
+    );
+}
+
+/// Prints an error to stderr, as `{"error": {"kind": ..., "message": ...}}` with `--json`,
+/// and exits with `code`.
+fn exit_with_error(kind: &str, message: &str, code: i32) -> ! {
+    if json_output() {
+        let error = json!({ "error": { "kind": kind, "message": message } });
+        eprintln!("{}", error);
+    } else {
+        eprintln!("{}", message);
+    }
+    std::process::exit(code);
+}
+
+/// Prints `error` and exits with its exit code.
+fn fail(error: &WifiConfigError) -> ! {
+    let mut message = error.to_string();
+    if let WifiConfigError::DeviceNotFound(_) = error {
+        message.push_str(&available_interfaces());
+    }
+    exit_with_error(error_kind(error), &message, exit_code(error));
+}
+
+/// The hint for a device that wasn't found: `; available Wi-Fi interfaces: wlan0, wlan1`,
+/// or nothing if the devices can't be listed either.
+fn available_interfaces() -> String {
+    match list_wifi_devices() {
+        Ok(devices) if devices.is_empty() => "; there are no Wi-Fi interfaces".into(),
+        Ok(devices) => {
+            let names: Vec<&str> = devices.iter().map(|d| d.interface.as_str()).collect();
+            format!("; available Wi-Fi interfaces: {}", names.join(", "))
+        }
+        Err(_) => String::new(),
+    }
+}
+
+/// Prints `message` and exits with code 1, like a validation error.
+fn exit_with(message: &str) -> ! {
+    exit_with_error("usage", message, 1);
+}
+
+/// Reports a `connect` usage error that clap can't detect on its own and exits with code 1.
+fn usage_error(message: &str) -> ! {
+    if json_output() {
+        exit_with(message);
+    }
+    let mut command = Cli::command();
+    command.build();
+    let _ = command
+        .find_subcommand_mut("connect")
+        .expect("connect is a subcommand")
+        .error(clap::error::ErrorKind::ArgumentConflict, message)
+        .print();
+    std::process::exit(1);
+}
+
+/// Maps a library error to the process exit code.
+///
+/// Scripts branch on these codes, so they are part of the CLI's interface: the README
+/// lists them and `exit_codes_are_stable` pins them, and changing one is a breaking
+/// change. Usage errors exit with 1, like invalid input.
+fn exit_code(error: &WifiConfigError) -> i32 {
+    match error {
+        WifiConfigError::InvalidSsid(_)
+        | WifiConfigError::EmptyPassword
+        | WifiConfigError::InvalidPsk { .. }
+        | WifiConfigError::InvalidEnterpriseAuth(_)
+        | WifiConfigError::CertificateNotFound(_)
+        | WifiConfigError::InvalidWepKey(_)
+        | WifiConfigError::InvalidIpConfig(_)
+        | WifiConfigError::InvalidWirelessConfig(_)
+        | WifiConfigError::InvalidMacAddress(_)
+        | WifiConfigError::InvalidHotspotConfig(_)
+        | WifiConfigError::InvalidQrCode(_)
+        | WifiConfigError::InvalidUuid(_)
+        | WifiConfigError::InvalidUser(_)
+        | WifiConfigError::ConfigFileRead(..)
+        | WifiConfigError::InvalidConfigFile(_)
+        | WifiConfigError::UnsupportedByBackend(_)
+        | WifiConfigError::UnsupportedByNetworkManager { .. }
+        | WifiConfigError::KeyfileWrite(..)
+        | WifiConfigError::InvalidKeyfile(_) => 1,
+        WifiConfigError::DbusConnection(_) => 2,
+        WifiConfigError::NetworkNotFound(_)
+        | WifiConfigError::SignalTooWeak { .. }
+        | WifiConfigError::ProfileNotFound(_)
+        | WifiConfigError::SecretNotFound(_)
+        | WifiConfigError::WpsNotActive
+        | WifiConfigError::NothingToReconnect(_) => 3,
+        WifiConfigError::DeviceQueryFailed(_)
+        | WifiConfigError::ScanFailed(_)
+        | WifiConfigError::SettingsQueryFailed(_)
+        | WifiConfigError::KeyringFailed(_) => 4,
+        WifiConfigError::ActivationFailed(_)
+        | WifiConfigError::InvalidSettings { .. }
+        | WifiConfigError::ProfileExists(_)
+        | WifiConfigError::DeleteFailed(_)
+        | WifiConfigError::DisconnectFailed(_)
+        | WifiConfigError::DeactivateFailed(_)
+        | WifiConfigError::ResetIncomplete(_) => 5,
+        WifiConfigError::AuthenticationFailed(_) => 6,
+        WifiConfigError::ActivationTimeout(_) => 7,
+        WifiConfigError::ConnectionFailed(_) => 8,
+        WifiConfigError::NoNetworkConnected(_) => 11,
+        WifiConfigError::PermissionDenied(_) => 12,
+        WifiConfigError::Timeout(_) => 13,
+        // Reported as the failure that caused the rollback.
+        WifiConfigError::RolledBack { error, .. } => exit_code(error),
+        WifiConfigError::NetworkManagerUnavailable(_) => 9,
+        WifiConfigError::RadioDisabled | WifiConfigError::HardwareRfkill(_) => 10,
+        WifiConfigError::NoWifiDevice
+        | WifiConfigError::DeviceNotFound(_)
+        | WifiConfigError::HotspotUnsupported(_) => 14,
+    }
+}
+
+/// Names a library error for the `kind` field of `--json` errors.
+fn error_kind(error: &WifiConfigError) -> &'static str {
+    match error {
+        WifiConfigError::InvalidSsid(_) => "invalid_ssid",
+        WifiConfigError::EmptyPassword => "empty_password",
+        WifiConfigError::InvalidPsk { .. } => "invalid_psk",
+        WifiConfigError::InvalidEnterpriseAuth(_) => "invalid_enterprise_auth",
+        WifiConfigError::CertificateNotFound(_) => "certificate_not_found",
+        WifiConfigError::InvalidWepKey(_) => "invalid_wep_key",
+        WifiConfigError::InvalidIpConfig(_) => "invalid_ip_config",
+        WifiConfigError::InvalidWirelessConfig(_) => "invalid_wireless_config",
+        WifiConfigError::InvalidMacAddress(_) => "invalid_mac_address",
+        WifiConfigError::InvalidHotspotConfig(_) => "invalid_hotspot_config",
+        WifiConfigError::InvalidQrCode(_) => "invalid_qr_code",
+        WifiConfigError::InvalidUuid(_) => "invalid_uuid",
+        WifiConfigError::InvalidUser(_) => "invalid_user",
+        WifiConfigError::ConfigFileRead(..) => "config_file_read",
+        WifiConfigError::InvalidConfigFile(_) => "invalid_config_file",
+        WifiConfigError::UnsupportedByBackend(_) => "unsupported_by_backend",
+        WifiConfigError::UnsupportedByNetworkManager { .. } => "unsupported_by_network_manager",
+        WifiConfigError::KeyfileWrite(..) => "keyfile_write",
+        WifiConfigError::InvalidKeyfile(_) => "invalid_keyfile",
+        WifiConfigError::DbusConnection(_) => "dbus_connection",
+        WifiConfigError::NetworkManagerUnavailable(_) => "network_manager_unavailable",
+        WifiConfigError::RadioDisabled => "radio_disabled",
+        WifiConfigError::SecretNotFound(_) => "secret_not_found",
+        WifiConfigError::KeyringFailed(_) => "keyring_failed",
+        WifiConfigError::HardwareRfkill(_) => "hardware_rfkill",
+        WifiConfigError::NoWifiDevice => "no_wifi_device",
+        WifiConfigError::DeviceNotFound(_) => "device_not_found",
+        WifiConfigError::NetworkNotFound(_) => "network_not_found",
+        WifiConfigError::SignalTooWeak { .. } => "signal_too_weak",
+        WifiConfigError::ProfileNotFound(_) => "profile_not_found",
+        WifiConfigError::HotspotUnsupported(_) => "hotspot_unsupported",
+        WifiConfigError::WpsNotActive => "wps_not_active",
+        WifiConfigError::NothingToReconnect(_) => "nothing_to_reconnect",
+        WifiConfigError::DeviceQueryFailed(_) => "device_query_failed",
+        WifiConfigError::ScanFailed(_) => "scan_failed",
+        WifiConfigError::SettingsQueryFailed(_) => "settings_query_failed",
+        WifiConfigError::ActivationFailed(_) => "activation_failed",
+        WifiConfigError::DeleteFailed(_) => "delete_failed",
+        WifiConfigError::DisconnectFailed(_) => "disconnect_failed",
+        WifiConfigError::DeactivateFailed(_) => "deactivate_failed",
+        WifiConfigError::ResetIncomplete(_) => "reset_incomplete",
+        WifiConfigError::AuthenticationFailed(_) => "authentication_failed",
+        WifiConfigError::ActivationTimeout(_) => "activation_timeout",
+        WifiConfigError::ConnectionFailed(_) => "connection_failed",
+        WifiConfigError::NoNetworkConnected(_) => "no_network_connected",
+        WifiConfigError::PermissionDenied(_) => "permission_denied",
+        WifiConfigError::InvalidSettings { .. } => "invalid_settings",
+        WifiConfigError::ProfileExists(_) => "profile_exists",
+        WifiConfigError::Timeout(_) => "timeout",
+        WifiConfigError::RolledBack { error, .. } => error_kind(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wifi_config::NmVersion;
+
+    fn parse(args: &[&str]) -> Command {
+        let args = std::iter::once("wifi-config").chain(args.iter().copied());
+        Cli::try_parse_from(with_default_subcommand(args.map(String::from).collect()))
+            .unwrap()
+            .command
+    }
+
+    #[test]
+    fn cli_definition_is_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn bare_ssid_is_an_alias_for_connect() {
+        let Command::Connect(args) = parse(&["--hidden", "MySsid", "supersecret123"]) else {
+            panic!("expected connect");
+        };
+        assert_eq!(args.network.ssid.as_deref(), Some("MySsid"));
+        assert_eq!(args.network.password_arg.as_deref(), Some("supersecret123"));
+        assert!(args.network.hidden);
+
+        let Command::Connect(args) = parse(&["--ssid-hex", "436166e9", "supersecret123"]) else {
+            panic!("expected connect");
+        };
+        assert_eq!(args.network.ssid_hex.as_deref(), Some(&b"Caf\xe9"[..]));
+
+        let Command::Connect(args) = parse(&["--retries", "3", "MySsid"]) else {
+            panic!("expected connect");
+        };
+        assert_eq!(args.retries, Some(3));
+
+        assert!(matches!(
+            parse(&["forget", "MySsid"]),
+            Command::Forget { .. }
+        ));
+        assert!(matches!(
+            parse(&["reset", "--yes"]),
+            Command::Reset {
+                yes: true,
+                dry_run: false
+            }
+        ));
+        assert!(matches!(
+            parse(&["watch", "--interface", "wlan0"]),
+            Command::Watch { interface: Some(_) }
+        ));
+        assert!(matches!(
+            parse(&["scan"]),
+            Command::Scan {
+                backend: BackendArgs { backend: None },
+                interface: None,
+                rescan: false
+            }
+        ));
+        assert!(matches!(
+            parse(&["scan", "--rescan", "--interface", "wlan1"]),
+            Command::Scan { rescan: true, interface: Some(name), .. } if name == "wlan1"
+        ));
+        assert!(matches!(
+            parse(&["status", "--backend", "iwd"]),
+            Command::Status {
+                backend: BackendArgs {
+                    backend: Some(Backend::Iwd)
+                },
+                interface: None
+            }
+        ));
+        assert!(matches!(
+            parse(&["status", "--interface", "wlan1"]),
+            Command::Status { interface: Some(name), .. } if name == "wlan1"
+        ));
+    }
+
+    #[test]
+    fn from_json_replaces_the_ssid() {
+        let Command::Connect(args) = parse(&["connect", "--from-json", "office.json", "-v"]) else {
+            panic!("expected connect");
+        };
+        assert_eq!(args.from_json.as_deref(), Some(Path::new("office.json")));
+        assert!(args.network.ssid.is_none());
+
+        let args = [
+            "wifi-config",
+            "connect",
+            "--from-json",
+            "office.json",
+            "MySsid",
+        ];
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn add_takes_the_network_arguments() {
+        let Command::Add(args) = parse(&["add", "MySsid", "--password", "x", "--no-activate"])
+        else {
+            panic!("expected add");
+        };
+        assert_eq!(args.network.ssid.as_deref(), Some("MySsid"));
+        assert!(args.no_activate);
+    }
+
+    #[test]
+    fn show_secrets_needs_dry_run() {
+        let Command::Connect(args) = parse(&["connect", "--dry-run", "--show-secrets", "MySsid"])
+        else {
+            panic!("expected connect");
+        };
+        assert!(args.dry_run && args.show_secrets);
+        assert!(
+            Cli::try_parse_from(["wifi-config", "connect", "--show-secrets", "MySsid"]).is_err()
+        );
+    }
+
+    #[test]
+    fn password_sources_conflict() {
+        let try_parse = |args: &[&str]| {
+            let args = std::iter::once("wifi-config").chain(args.iter().copied());
+            Cli::try_parse_from(with_default_subcommand(args.map(String::from).collect()))
+        };
+        assert!(try_parse(&["MySsid", "--password-stdin"]).is_ok());
+        assert!(try_parse(&["MySsid", "--password", "x", "--password-stdin"]).is_err());
+        assert!(try_parse(&["MySsid", "supersecret123", "--ask"]).is_err());
+        assert!(try_parse(&["hotspot", "Provisioning", "setup-1234", "--password-stdin"]).is_err());
+    }
+
+    #[test]
+    fn watch_lines_start_with_the_utc_time() {
+        assert_eq!(time_of_day(Duration::from_millis(0)), "00:00:00.000");
+        // 2026-10-15 13:05:09.042 UTC.
+        assert_eq!(
+            time_of_day(Duration::from_millis(1_792_069_509_042)),
+            "13:05:09.042"
+        );
+        let event = WifiEvent::StrengthChanged {
+            interface: "wlan0".into(),
+            strength: 64,
+        };
+        assert_eq!(describe_event(&event), "wlan0: signal 64%");
+    }
+
+    #[test]
+    fn awkward_ssids_reach_the_settings_untouched() {
+        let ssid_of = |args: &[&str]| {
+            let Command::Connect(args) = parse(args) else {
+                panic!("expected connect");
+            };
+            let (ssid, options) = network_options(args.network);
+            let connection = WifiConnection::builder(ssid.clone())
+                .options(options)
+                .build()
+                .unwrap();
+            let saved = wifi_config::ConnectionConfig::from_keyfile(&connection.to_keyfile());
+            assert_eq!(saved.unwrap().ssid(), ssid);
+            ssid
+        };
+        assert_eq!(
+            ssid_of(&["--ssid", "-Guest 5GHz", "supersecret123"]),
+            b"-Guest 5GHz"
+        );
+        assert_eq!(ssid_of(&["connect", "--ssid=-Guest 5GHz"]), b"-Guest 5GHz");
+        assert_eq!(
+            ssid_of(&["connect", "--", "-Guest 5GHz", "supersecret123"]),
+            b"-Guest 5GHz"
+        );
+        assert_eq!(ssid_of(&["--", "-Guest 5GHz"]), b"-Guest 5GHz");
+        assert_eq!(ssid_of(&["Office "]), b"Office ");
+        assert_eq!(ssid_of(&["--ssid", " Lobby\t"]), b" Lobby\t");
+        let longest = "x".repeat(32);
+        assert_eq!(ssid_of(&["--ssid", &longest]), longest.as_bytes());
+        assert_eq!(ssid_of(&["☕ Café 🚀"]), "☕ Café 🚀".as_bytes());
+        assert_eq!(ssid_of(&["--ssid-hex", "2d477565737420"]), b"-Guest ");
+        assert_eq!(ssid_of(&["--ssid-hex", "436166e9"]), b"Caf\xe9");
+
+        let try_parse = |args: &[&str]| {
+            let args = std::iter::once("wifi-config").chain(args.iter().copied());
+            Cli::try_parse_from(with_default_subcommand(args.map(String::from).collect()))
+        };
+        assert!(try_parse(&["--ssid", "Office", "--ssid-hex", "4f6666696365"]).is_err());
+        assert!(try_parse(&["--ssid", "Office", "--qr", "WIFI:S:Office;;"]).is_err());
+    }
+
+    #[test]
+    fn exit_codes_are_stable() {
+        // Scripts branch on these; changing one is a breaking change (see the README).
+        let dbus = || dbus::Error::new_custom("org.freedesktop.DBus.Error.Failed", "failed");
+        let io = || std::io::Error::from(std::io::ErrorKind::NotFound);
+        let reason = wifi_config::DeviceStateReason::from(0);
+        let codes = [
+            (WifiConfigError::InvalidSsid(String::new()), 1),
+            (WifiConfigError::EmptyPassword, 1),
+            (
+                WifiConfigError::InvalidPsk {
+                    reason: String::new(),
+                },
+                1,
+            ),
+            (WifiConfigError::InvalidEnterpriseAuth(String::new()), 1),
+            (WifiConfigError::CertificateNotFound(PathBuf::new()), 1),
+            (WifiConfigError::InvalidWepKey(String::new()), 1),
+            (WifiConfigError::InvalidIpConfig(String::new()), 1),
+            (WifiConfigError::InvalidWirelessConfig(String::new()), 1),
+            (WifiConfigError::InvalidMacAddress(String::new()), 1),
+            (WifiConfigError::InvalidHotspotConfig(String::new()), 1),
+            (WifiConfigError::InvalidQrCode(String::new()), 1),
+            (WifiConfigError::InvalidUuid(String::new()), 1),
+            (WifiConfigError::InvalidUser(String::new()), 1),
+            (WifiConfigError::ConfigFileRead(PathBuf::new(), io()), 1),
+            (WifiConfigError::InvalidConfigFile(String::new()), 1),
+            (WifiConfigError::UnsupportedByBackend(String::new()), 1),
+            (
+                WifiConfigError::UnsupportedByNetworkManager {
+                    feature: String::new(),
+                    needs: NmVersion::new(1, 16, 0),
+                    have: NmVersion::new(1, 14, 0),
+                },
+                1,
+            ),
+            (WifiConfigError::KeyfileWrite(PathBuf::new(), io()), 1),
+            (WifiConfigError::InvalidKeyfile(String::new()), 1),
+            (WifiConfigError::DbusConnection(dbus()), 2),
+            (WifiConfigError::NetworkNotFound(String::new()), 3),
+            (
+                WifiConfigError::SignalTooWeak {
+                    found: 10,
+                    required: 50,
+                },
+                3,
+            ),
+            (WifiConfigError::ProfileNotFound(String::new()), 3),
+            (WifiConfigError::SecretNotFound(String::new()), 3),
+            (WifiConfigError::WpsNotActive, 3),
+            (WifiConfigError::NothingToReconnect(String::new()), 3),
+            (WifiConfigError::DeviceQueryFailed(dbus()), 4),
+            (WifiConfigError::ScanFailed(dbus()), 4),
+            (WifiConfigError::SettingsQueryFailed(dbus()), 4),
+            (WifiConfigError::KeyringFailed(dbus()), 4),
+            (WifiConfigError::ActivationFailed(String::new()), 5),
+            (
+                WifiConfigError::InvalidSettings {
+                    property: String::new(),
+                    error: dbus(),
+                },
+                5,
+            ),
+            (WifiConfigError::ProfileExists(dbus()), 5),
+            (WifiConfigError::DeleteFailed(dbus()), 5),
+            (WifiConfigError::DisconnectFailed(dbus()), 5),
+            (WifiConfigError::DeactivateFailed(dbus()), 5),
+            (WifiConfigError::ResetIncomplete(Vec::new()), 5),
+            (WifiConfigError::AuthenticationFailed(reason), 6),
+            (
+                WifiConfigError::ActivationTimeout(Duration::from_secs(30)),
+                7,
+            ),
+            (WifiConfigError::ConnectionFailed(reason), 8),
+            (WifiConfigError::NetworkManagerUnavailable(dbus()), 9),
+            (WifiConfigError::RadioDisabled, 10),
+            (WifiConfigError::HardwareRfkill(String::new()), 10),
+            (WifiConfigError::NoNetworkConnected(Vec::new()), 11),
+            (WifiConfigError::PermissionDenied(dbus()), 12),
+            (WifiConfigError::Timeout(dbus()), 13),
+            (WifiConfigError::NoWifiDevice, 14),
+            (WifiConfigError::DeviceNotFound(String::new()), 14),
+            (WifiConfigError::HotspotUnsupported(String::new()), 14),
+            (
+                WifiConfigError::RolledBack {
+                    error: Box::new(WifiConfigError::AuthenticationFailed(reason)),
+                    rollback: wifi_config::Rollback {
+                        profile_deleted: true,
+                        profile_restored: false,
+                        previous_profile: None,
+                        restored: false,
+                    },
+                },
+                6,
+            ),
+        ];
+        for (error, code) in &codes {
+            assert_eq!(exit_code(error), *code, "{:?}", error);
+        }
+    }
+}
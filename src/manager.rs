@@ -0,0 +1,691 @@
+use dbus::blocking::Connection;
+use dbus::channel::Channel;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use crate::client::{DbusNmClient, NmClient};
+use crate::{activation, backend, device, ip_info, profiles, radio, scan, status};
+use crate::{
+    AccessPointInfo, ActivationHandle, ActiveConnectionInfo, Backend, ConnectOptions, Connectivity,
+    DbusTimeouts, DeviceSelection, DisconnectOutcome, IpInfo, NmVersion, ProfileWarning,
+    RadioState, ResetReport, SavedWifiProfile, WifiConfigError, WifiDevice, WifiStatus,
+};
+
+/// D-Bus errors meaning a device object no longer exists, e.g. because its adapter was
+/// unplugged. NetworkManager's GDBus answers calls on removed objects with
+/// `UnknownMethod` rather than `UnknownObject`.
+const DEVICE_GONE_ERRORS: &[&str] = &[
+    "org.freedesktop.DBus.Error.UnknownObject",
+    "org.freedesktop.DBus.Error.UnknownMethod",
+];
+
+/// The D-Bus bus a [`WifiManager`] talks to NetworkManager on.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum BusSelection {
+    /// The system bus, where NetworkManager runs. libdbus takes its address from
+    /// `DBUS_SYSTEM_BUS_ADDRESS` if set, e.g. for a socket bind-mounted into a container
+    /// at a nonstandard path.
+    #[default]
+    System,
+    /// The session bus of the user, e.g. for a mock NetworkManager started there.
+    Session,
+    /// The bus at a D-Bus address such as `unix:path=/tmp/test-bus`, e.g. a private
+    /// `dbus-daemon` started for a test.
+    Address(String),
+}
+
+impl BusSelection {
+    /// Opens a connection to the bus.
+    fn open(&self) -> Result<Connection, dbus::Error> {
+        match self {
+            BusSelection::System => Connection::new_system(),
+            BusSelection::Session => Connection::new_session(),
+            BusSelection::Address(address) => {
+                let mut channel = Channel::open_private(address)?;
+                // Says `Hello`, without which the bus daemon answers nothing.
+                channel.register()?;
+                Ok(Connection::from(channel))
+            }
+        }
+    }
+}
+
+/// A NetworkManager client that keeps its system bus connection and Wi-Fi device between
+/// calls.
+///
+/// The free functions ([`connect`](crate::connect), [`scan_networks`](crate::scan_networks),
+/// [`active_connection`](crate::active_connection), [`disconnect`](crate::disconnect)) each
+/// open a new connection and walk every device to find the Wi-Fi one. Create a manager
+/// once instead when calling them repeatedly, e.g. polling the status every few seconds.
+///
+/// The device is picked as with [`DeviceSelection::Best`] on first use and then kept;
+/// every call only re-reads its properties. If the device disappears (its object path
+/// answers `UnknownObject`, e.g. after the adapter was unplugged), the devices are
+/// enumerated again and the best one is used from then on.
+///
+/// [`for_interface`](Self::for_interface) pins the manager to one adapter instead;
+/// every call then fails with [`WifiConfigError::DeviceNotFound`] while it is missing.
+///
+/// [`with_bus`](Self::with_bus) talks to NetworkManager on another bus, e.g. a mock on a
+/// private test bus. [`connect_and_wait`](Self::connect_and_wait) opens a connection of
+/// its own to that bus, so waiting for the activation doesn't hold up the other calls.
+///
+/// [`with_timeouts`](Self::with_timeouts) sets the timeouts of the manager's calls; the
+/// free functions use [`DbusTimeouts::default`]. [`connect`](Self::connect) takes them
+/// from its options instead, like every connect function.
+///
+/// The manager is `Send + Sync`, so one can be shared between threads, e.g. in an
+/// [`Arc`](std::sync::Arc) by a status poller and a command handler. Each call holds the
+/// bus connection for its duration, so calls from several threads run one after the
+/// other rather than interleaving their D-Bus messages; only `connect_and_wait` waits for
+/// an activation to complete, on its own connection, so a `connect` holds up a concurrent
+/// `status` only briefly.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use wifi_config::WifiManager;
+///
+/// let manager = WifiManager::new()?;
+/// loop {
+///     match manager.active_connection()? {
+///         Some(active) => println!("{} ({:?})", active.interface, active.strength),
+///         None => println!("not connected"),
+///     }
+///     std::thread::sleep(Duration::from_secs(5));
+/// }
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub struct WifiManager {
+    /// Locked for the whole of each call; always before `device`.
+    conn: Mutex<Connection>,
+    /// The bus `conn` is on, for the connections of [`connect_and_wait`](Self::connect_and_wait).
+    bus: BusSelection,
+    /// Interface name of the only device to use, from [`for_interface`](Self::for_interface).
+    interface: Option<String>,
+    device: Mutex<Option<WifiDevice>>,
+    /// Read on first use, like the device.
+    version: Mutex<Option<NmVersion>>,
+    timeouts: DbusTimeouts,
+}
+
+impl WifiManager {
+    /// Connects to the system bus; the Wi-Fi device is looked up on first use.
+    ///
+    /// # Errors
+    ///
+    /// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+    pub fn new() -> Result<Self, WifiConfigError> {
+        WifiManager::with_bus(BusSelection::System)
+    }
+
+    /// Like [`new`](Self::new), but connects to `bus`.
+    ///
+    /// Every method of the manager talks to NetworkManager on that bus, including
+    /// [`connect_and_wait`](Self::connect_and_wait) and a manager pinned with
+    /// [`for_interface`](Self::for_interface). The free functions, such as
+    /// [`connect_and_wait`](crate::connect_and_wait), keep using the system bus.
+    ///
+    /// # Errors
+    ///
+    /// - [`WifiConfigError::DbusConnection`] if the bus can't be reached.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use wifi_config::{BusSelection, WifiManager};
+    ///
+    /// let bus = BusSelection::Address("unix:path=/tmp/test-bus".into());
+    /// let manager = WifiManager::with_bus(bus)?;
+    /// println!("{:?}", manager.active_connection()?);
+    /// # Ok::<(), wifi_config::WifiConfigError>(())
+    /// ```
+    pub fn with_bus(bus: BusSelection) -> Result<Self, WifiConfigError> {
+        Ok(WifiManager {
+            conn: Mutex::new(bus.open().map_err(WifiConfigError::DbusConnection)?),
+            bus,
+            interface: None,
+            device: Mutex::new(None),
+            version: Mutex::new(None),
+            timeouts: DbusTimeouts::default(),
+        })
+    }
+
+    /// Always uses the Wi-Fi device called `interface`, e.g. `wlan1`, whatever its state,
+    /// on the bus this manager was created for.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// let manager = wifi_config::WifiManager::new()?.for_interface("wlan1");
+    /// for ap in manager.scan(false)? {
+    ///     println!("{} {}%", ap.ssid_lossy, ap.strength);
+    /// }
+    /// # Ok::<(), wifi_config::WifiConfigError>(())
+    /// ```
+    pub fn for_interface(mut self, interface: impl Into<String>) -> Self {
+        self.interface = Some(interface.into());
+        // A device picked before belongs to no interface in particular.
+        self.device = Mutex::new(None);
+        self
+    }
+
+    /// Uses `timeouts` for this manager's calls to NetworkManager instead of the defaults.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use wifi_config::{DbusTimeouts, WifiManager};
+    ///
+    /// // Fail fast in a UI rather than hang for the default 10 seconds.
+    /// let timeouts = DbusTimeouts::uniform(Duration::from_secs(2));
+    /// let manager = WifiManager::new()?.with_timeouts(timeouts);
+    /// println!("{:?}", manager.active_connection()?);
+    /// # Ok::<(), wifi_config::WifiConfigError>(())
+    /// ```
+    pub fn with_timeouts(mut self, timeouts: DbusTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// The Wi-Fi device this manager uses, with its current state.
+    ///
+    /// # Errors
+    ///
+    /// - [`WifiConfigError::NetworkManagerUnavailable`] if NetworkManager isn't running.
+    /// - [`WifiConfigError::DeviceQueryFailed`] / [`WifiConfigError::NoWifiDevice`] if the
+    ///   Wi-Fi device can't be found.
+    /// - [`WifiConfigError::DeviceNotFound`] if the manager is pinned to an interface that
+    ///   doesn't exist.
+    pub fn device(&self) -> Result<WifiDevice, WifiConfigError> {
+        let conn = self.conn();
+        self.cached_device(&self.client(&conn))
+    }
+
+    /// The version of NetworkManager, read once and then kept.
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`nm_version`](crate::nm_version).
+    pub fn nm_version(&self) -> Result<NmVersion, WifiConfigError> {
+        let conn = self.conn();
+        self.cached_version(&self.client(&conn))
+    }
+
+    /// Like [`connect`](crate::connect), over this manager's connection.
+    ///
+    /// The cached device is used unless `options` pick one themselves, through
+    /// `device_path`, `interface` or a [`DeviceSelection`] other than `Best`. With a
+    /// backend other than NetworkManager, this is the same as [`connect`](crate::connect).
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`connect`](crate::connect).
+    pub fn connect(
+        &self,
+        ssid: impl AsRef<[u8]>,
+        options: &ConnectOptions,
+    ) -> Result<ActivationHandle, WifiConfigError> {
+        let ssid = ssid.as_ref();
+        options.validate(ssid)?;
+        match options.backend {
+            Backend::NetworkManager => self.connect_validated(ssid, options),
+            other => other.implementation().connect(ssid, options),
+        }
+    }
+
+    /// Like [`connect_and_wait`](crate::connect_and_wait), on this manager's bus and
+    /// device.
+    ///
+    /// Opens a second connection to the bus for the duration of the call, so the other
+    /// methods can be called while it waits. The devices are looked up again rather than
+    /// taken from the cache, on the interface of [`for_interface`](Self::for_interface)
+    /// unless `options` pick one themselves. With a backend other than NetworkManager,
+    /// this is the same as [`connect_and_wait`](crate::connect_and_wait).
+    ///
+    /// # Errors
+    ///
+    /// - [`WifiConfigError::DbusConnection`] if the bus can't be reached.
+    /// - Otherwise the same as [`connect_and_wait`](crate::connect_and_wait).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use wifi_config::{BusSelection, ConnectOptions, Security, WifiManager};
+    ///
+    /// let manager = WifiManager::with_bus(BusSelection::Session)?;
+    /// let options = ConnectOptions::builder()
+    ///     .security(Security::WpaPsk("supersecret123".into()))
+    ///     .build();
+    /// let handle = manager.connect_and_wait("MyHomeWiFi", &options)?;
+    /// println!("up on {}", handle.interface);
+    /// # Ok::<(), wifi_config::WifiConfigError>(())
+    /// ```
+    pub fn connect_and_wait(
+        &self,
+        ssid: impl AsRef<[u8]>,
+        options: &ConnectOptions,
+    ) -> Result<ActivationHandle, WifiConfigError> {
+        let ssid = ssid.as_ref();
+        options.validate(ssid)?;
+        if options.backend != Backend::NetworkManager {
+            return options
+                .backend
+                .implementation()
+                .connect_and_wait(ssid, options, &mut |_| {});
+        }
+        let pinned;
+        let options = match &self.interface {
+            Some(interface) if !picks_device(options) => {
+                pinned = ConnectOptions {
+                    interface: Some(interface.clone()),
+                    ..options.clone()
+                };
+                &pinned
+            }
+            _ => options,
+        };
+        let conn = self.bus.open().map_err(WifiConfigError::DbusConnection)?;
+        backend::connect_and_wait_on(&conn, ssid, options, &mut |_| {})
+    }
+
+    /// Like [`scan_networks`](crate::scan_networks), on the cached device.
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`scan_networks`](crate::scan_networks).
+    pub fn scan(&self, fresh: bool) -> Result<Vec<AccessPointInfo>, WifiConfigError> {
+        let conn = self.conn();
+        let client = self.client(&conn);
+        let device = self.cached_device(&client)?;
+        scan::scan_device(&conn, self.timeouts, &client, &device.path, fresh)
+    }
+
+    /// Like [`current_status`](crate::current_status), for the cached device.
+    ///
+    /// Deprecated: [`active_connection`](Self::active_connection) reports the connection
+    /// in the same shape [`connect_and_wait`](crate::connect_and_wait) does.
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`current_status`](crate::current_status).
+    #[deprecated(note = "use `WifiManager::active_connection`")]
+    pub fn status(&self) -> Result<Option<WifiStatus>, WifiConfigError> {
+        let conn = self.conn();
+        let device = self.cached_device(&self.client(&conn))?;
+        status::device_status(&conn, self.timeouts, device)
+    }
+
+    /// Like [`active_connection`](crate::active_connection), for the cached device.
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`active_connection`](crate::active_connection).
+    pub fn active_connection(&self) -> Result<Option<ActiveConnectionInfo>, WifiConfigError> {
+        let conn = self.conn();
+        let device = self.cached_device(&self.client(&conn))?;
+        status::device_active_connection(&conn, self.timeouts, &device)
+    }
+
+    /// Like [`radio_state`](crate::radio_state), over this manager's connection.
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`radio_state`](crate::radio_state).
+    pub fn radio_state(&self) -> Result<RadioState, WifiConfigError> {
+        let conn = self.conn();
+        radio::read_radio_state(&self.client(&conn))
+    }
+
+    /// Like [`disconnect`](crate::disconnect), for the cached device.
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`disconnect`](crate::disconnect).
+    pub fn disconnect(
+        &self,
+        disable_autoconnect: bool,
+    ) -> Result<DisconnectOutcome, WifiConfigError> {
+        let conn = self.conn();
+        let device = self.cached_device(&self.client(&conn))?;
+        device::disconnect_path(&conn, self.timeouts, &device.path, disable_autoconnect)
+    }
+
+    /// Like [`list_wifi_devices`](crate::list_wifi_devices), over this manager's
+    /// connection.
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`list_wifi_devices`](crate::list_wifi_devices).
+    pub fn devices(&self) -> Result<Vec<WifiDevice>, WifiConfigError> {
+        let conn = self.conn();
+        device::wifi_devices(&self.client(&conn))
+    }
+
+    /// Like [`request_scan`](crate::request_scan), on the cached device.
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`request_scan`](crate::request_scan).
+    pub fn request_scan(&self, wait: bool, timeout: Duration) -> Result<(), WifiConfigError> {
+        let conn = self.conn();
+        let device = self.cached_device(&self.client(&conn))?;
+        scan::rescan(&conn, self.timeouts, &device.path, wait, timeout)
+    }
+
+    /// Like [`signal_strength`](crate::signal_strength), on the cached device.
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`signal_strength`](crate::signal_strength).
+    pub fn signal_strength(
+        &self,
+        ssid: impl AsRef<[u8]>,
+        max_age: Option<Duration>,
+    ) -> Result<Option<u8>, WifiConfigError> {
+        let conn = self.conn();
+        let client = self.client(&conn);
+        let device = self.cached_device(&client)?;
+        scan::device_signal_strength(
+            &conn,
+            self.timeouts,
+            &client,
+            &device.path,
+            ssid.as_ref(),
+            max_age,
+        )
+    }
+
+    /// Like [`is_network_available`](crate::is_network_available), on the cached device.
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`is_network_available`](crate::is_network_available).
+    pub fn is_network_available(
+        &self,
+        ssid: impl AsRef<[u8]>,
+        fresh: bool,
+    ) -> Result<Option<u8>, WifiConfigError> {
+        Ok(scan::best_strength(&self.scan(fresh)?, ssid.as_ref()))
+    }
+
+    /// Like [`active_access_point`](crate::active_access_point), of the cached device.
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`active_access_point`](crate::active_access_point).
+    pub fn active_access_point(&self) -> Result<Option<AccessPointInfo>, WifiConfigError> {
+        let conn = self.conn();
+        let client = self.client(&conn);
+        let device = self.cached_device(&client)?;
+        scan::device_access_point(&conn, self.timeouts, &client, &device.path)
+    }
+
+    /// Like [`check_connectivity`](crate::check_connectivity), over this manager's
+    /// connection.
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`check_connectivity`](crate::check_connectivity).
+    pub fn check_connectivity(&self) -> Result<Connectivity, WifiConfigError> {
+        let conn = self.conn();
+        self.client(&conn).check_connectivity()
+    }
+
+    /// Like [`ip_info`](crate::ip_info), over this manager's connection.
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`ip_info`](crate::ip_info).
+    pub fn ip_info(
+        &self,
+        handle: &ActivationHandle,
+        timeout: Duration,
+    ) -> Result<IpInfo, WifiConfigError> {
+        let conn = self.conn();
+        ip_info::wait_for_ip_info(&conn, self.timeouts, &handle.active_path, timeout)
+    }
+
+    /// Like [`deactivate`](crate::deactivate), over this manager's connection.
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`deactivate`](crate::deactivate).
+    pub fn deactivate(
+        &self,
+        handle: &ActivationHandle,
+    ) -> Result<DisconnectOutcome, WifiConfigError> {
+        let conn = self.conn();
+        activation::deactivate_on(&conn, handle, self.timeouts)
+    }
+
+    /// Like [`list_saved_connections_with_warnings`](crate::list_saved_connections_with_warnings),
+    /// over this manager's connection.
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`list_saved_connections_with_warnings`](crate::list_saved_connections_with_warnings).
+    pub fn list_saved_connections_with_warnings(
+        &self,
+    ) -> Result<(Vec<SavedWifiProfile>, Vec<ProfileWarning>), WifiConfigError> {
+        let conn = self.conn();
+        profiles::saved_connections(&self.client(&conn))
+    }
+
+    /// Like [`activate_saved`](crate::activate_saved), on this manager's interface if it
+    /// is pinned to one.
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`activate_saved`](crate::activate_saved).
+    pub fn activate_saved(
+        &self,
+        ssid_or_uuid: impl AsRef<[u8]>,
+    ) -> Result<ActivationHandle, WifiConfigError> {
+        let conn = self.conn();
+        profiles::activate(
+            &self.client(&conn),
+            ssid_or_uuid.as_ref(),
+            self.interface.as_deref(),
+        )
+    }
+
+    /// Like [`forget_network`](crate::forget_network), over this manager's connection.
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`forget_network`](crate::forget_network).
+    pub fn forget_network(&self, ssid: impl AsRef<[u8]>) -> Result<usize, WifiConfigError> {
+        let conn = self.conn();
+        profiles::forget(&self.client(&conn), ssid.as_ref())
+    }
+
+    /// Like [`delete`](crate::delete), over this manager's connection.
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`delete`](crate::delete).
+    pub fn delete(&self, handle: &ActivationHandle) -> Result<(), WifiConfigError> {
+        let conn = self.conn();
+        self.client(&conn).delete_connection(&handle.settings_path)
+    }
+
+    /// Like [`reset_wifi_profiles`](crate::reset_wifi_profiles), over this manager's
+    /// connection.
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`reset_wifi_profiles`](crate::reset_wifi_profiles).
+    pub fn reset_wifi_profiles(&self, dry_run: bool) -> Result<ResetReport, WifiConfigError> {
+        let conn = self.conn();
+        profiles::reset(&self.client(&conn), dry_run)
+    }
+
+    /// [`connect`](Self::connect) with NetworkManager, for options that already passed
+    /// [`ConnectOptions::validate`].
+    pub(crate) fn connect_validated(
+        &self,
+        ssid: &[u8],
+        options: &ConnectOptions,
+    ) -> Result<ActivationHandle, WifiConfigError> {
+        let conn = self.conn();
+        let client = DbusNmClient::new(&conn, options.timeouts).with_retry(options.retry);
+        let version = self.cached_version(&client)?;
+        let client = client.with_version(version);
+        let interface = options.interface.as_deref().or(self.interface.as_deref());
+        radio::ensure_radio(&client, options.enable_radio, interface)?;
+        let devices = if picks_device(options) {
+            backend::find_devices(&client, options)?
+        } else {
+            vec![self.cached_device(&client)?]
+        };
+        backend::connect_on(&client, &devices, ssid, options)
+    }
+
+    fn cached_device(&self, client: &dyn NmClient) -> Result<WifiDevice, WifiConfigError> {
+        cached_device(&self.device, client, self.interface.as_deref())
+    }
+
+    fn cached_version(&self, client: &dyn NmClient) -> Result<NmVersion, WifiConfigError> {
+        let mut cache = self.version.lock().unwrap_or_else(PoisonError::into_inner);
+        match *cache {
+            Some(version) => Ok(version),
+            None => Ok(*cache.insert(client.version()?)),
+        }
+    }
+
+    /// A client over `conn` with this manager's timeouts.
+    fn client<'a>(&self, conn: &'a Connection) -> DbusNmClient<'a> {
+        DbusNmClient::new(conn, self.timeouts)
+    }
+
+    /// The bus connection, for one call; other threads' calls wait until it is released.
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        // A panic in another thread's call leaves nothing half-done worth refusing over.
+        self.conn.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Returns the device in `cache` with fresh properties, looking the best one (or the one
+/// called `interface`) up again if the cache is empty or its device is gone.
+fn cached_device(
+    cache: &Mutex<Option<WifiDevice>>,
+    client: &dyn NmClient,
+    interface: Option<&str>,
+) -> Result<WifiDevice, WifiConfigError> {
+    let cache = || cache.lock().unwrap_or_else(PoisonError::into_inner);
+    let cached = cache().clone();
+    if let Some(cached) = cached {
+        match client.device_properties(&cached.path) {
+            Ok(props) => {
+                let device = WifiDevice::refreshed(
+                    cached.path,
+                    &props,
+                    cached.perm_hw_address,
+                    cached.capabilities,
+                );
+                *cache() = Some(device.clone());
+                return Ok(device);
+            }
+            Err(e) if is_device_gone(&e) => {
+                log::info!(
+                    "Wi-Fi device {} is gone; looking for another one",
+                    cached.interface
+                );
+                *cache() = None;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    let device = device::select_wifi_device(device::wifi_devices(client)?, None, interface)?;
+    log::debug!("using Wi-Fi device {} ({})", device.path, device.interface);
+    *cache() = Some(device.clone());
+    Ok(device)
+}
+
+/// Whether `options` pick the device themselves rather than leave it to the manager.
+fn picks_device(options: &ConnectOptions) -> bool {
+    options.device_path.is_some()
+        || options.interface.is_some()
+        || options.device_selection != DeviceSelection::Best
+}
+
+/// Whether `e` says the device object queried doesn't exist (anymore).
+fn is_device_gone(e: &WifiConfigError) -> bool {
+    match e {
+        WifiConfigError::DeviceQueryFailed(e) => e
+            .name()
+            .is_some_and(|name| DEVICE_GONE_ERRORS.contains(&name)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::{device_path, MockDevice, MockNmClient};
+
+    #[test]
+    fn can_be_shared_between_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<WifiManager>();
+    }
+
+    #[test]
+    fn reuses_the_device_until_it_is_gone() {
+        let cache = Mutex::new(None);
+        let client = MockNmClient::with_devices(vec![
+            MockDevice::new(1, "wlan0", 2),
+            MockDevice::new(2, "wlan1", 2),
+        ]);
+        assert_eq!(
+            cached_device(&cache, &client, None).unwrap().interface,
+            "wlan0"
+        );
+        assert_eq!(
+            cached_device(&cache, &client, None).unwrap().interface,
+            "wlan0"
+        );
+        assert_eq!(
+            client
+                .methods()
+                .iter()
+                .filter(|m| *m == "GetDevices")
+                .count(),
+            1
+        );
+        assert_eq!(
+            client.calls().last().unwrap(),
+            &format!("GetAll {}", device_path(1))
+        );
+
+        // wlan0 was unplugged.
+        let client = MockNmClient::with_devices(vec![MockDevice::new(2, "wlan1", 2)]);
+        assert_eq!(
+            cached_device(&cache, &client, None).unwrap().interface,
+            "wlan1"
+        );
+        assert_eq!(client.methods()[..2], ["GetAll", "GetDevices"]);
+        assert_eq!(cache.lock().unwrap().as_ref().unwrap().path, device_path(2));
+    }
+
+    #[test]
+    fn sticks_to_the_pinned_interface() {
+        let cache = Mutex::new(None);
+        let client = MockNmClient::with_devices(vec![
+            MockDevice::new(1, "wlan0", 2),
+            MockDevice::new(2, "wlan1", 2),
+        ]);
+        let device = cached_device(&cache, &client, Some("wlan1")).unwrap();
+        assert_eq!(device.path, device_path(2));
+
+        // wlan1 was unplugged; wlan0 doesn't stand in for it.
+        let client = MockNmClient::with_devices(vec![MockDevice::new(1, "wlan0", 2)]);
+        assert!(matches!(
+            cached_device(&cache, &client, Some("wlan1")),
+            Err(WifiConfigError::DeviceNotFound(interface)) if interface == "wlan1"
+        ));
+    }
+}
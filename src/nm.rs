@@ -0,0 +1,105 @@
+//! NetworkManager D-Bus names and timeouts.
+
+use std::time::Duration;
+
+/// Well-known bus name of NetworkManager.
+pub(crate) const NM_BUS_NAME: &str = "org.freedesktop.NetworkManager";
+/// Object path of the NetworkManager manager object.
+pub(crate) const NM_PATH: &str = "/org/freedesktop/NetworkManager";
+/// Interface of the manager object (`GetDevices`, `AddAndActivateConnection`, ...).
+pub(crate) const NM_INTERFACE: &str = "org.freedesktop.NetworkManager";
+/// Interface implemented by every device object.
+pub(crate) const DEVICE_INTERFACE: &str = "org.freedesktop.NetworkManager.Device";
+/// Interface implemented by Wi-Fi device objects.
+pub(crate) const WIRELESS_INTERFACE: &str = "org.freedesktop.NetworkManager.Device.Wireless";
+/// Interface implemented by access point objects.
+pub(crate) const ACCESS_POINT_INTERFACE: &str = "org.freedesktop.NetworkManager.AccessPoint";
+/// Interface implemented by active connection objects.
+pub(crate) const ACTIVE_CONNECTION_INTERFACE: &str =
+    "org.freedesktop.NetworkManager.Connection.Active";
+/// Interface implemented by IPv4 configuration objects.
+pub(crate) const IP4_CONFIG_INTERFACE: &str = "org.freedesktop.NetworkManager.IP4Config";
+/// Interface implemented by IPv6 configuration objects.
+pub(crate) const IP6_CONFIG_INTERFACE: &str = "org.freedesktop.NetworkManager.IP6Config";
+/// Interface implemented by DHCPv4 configuration objects.
+pub(crate) const DHCP4_CONFIG_INTERFACE: &str = "org.freedesktop.NetworkManager.DHCP4Config";
+/// Object path NetworkManager uses for "no object" in object-valued properties.
+pub(crate) const NO_OBJECT_PATH: &str = "/";
+/// Object path of the settings service holding saved connection profiles.
+pub(crate) const SETTINGS_PATH: &str = "/org/freedesktop/NetworkManager/Settings";
+/// Interface of the settings service (`ListConnections`, ...).
+pub(crate) const SETTINGS_INTERFACE: &str = "org.freedesktop.NetworkManager.Settings";
+/// Interface implemented by every saved connection profile.
+pub(crate) const SETTINGS_CONNECTION_INTERFACE: &str =
+    "org.freedesktop.NetworkManager.Settings.Connection";
+
+/// `DeviceType` value NetworkManager uses for Wi-Fi devices.
+pub(crate) const DEVICE_TYPE_WIFI: u32 = 2;
+
+/// Default timeout of D-Bus calls that only read or change state.
+pub(crate) const DBUS_TIMEOUT: Duration = Duration::from_secs(10);
+/// Default timeout of calls that add, update or activate a profile, which NetworkManager
+/// only answers after writing it to disk.
+pub(crate) const DBUS_ACTIVATE_TIMEOUT: Duration = Duration::from_secs(25);
+
+/// Timeouts of the D-Bus calls made to NetworkManager.
+///
+/// Listing devices, access points and profiles is usually answered within milliseconds,
+/// while adding and activating a profile can take much longer on a loaded system, so the
+/// two are configured separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct DbusTimeouts {
+    /// Timeout of queries (`GetDevices`, `GetAll`, `ListConnections`, `GetSettings`, ...).
+    /// Defaults to 10 seconds.
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::duration_secs"))]
+    pub query: Duration,
+    /// Timeout of `AddAndActivateConnection`, `Update` and `ActivateConnection`.
+    /// Defaults to 25 seconds.
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::duration_secs"))]
+    pub activate: Duration,
+}
+
+impl DbusTimeouts {
+    /// Uses `timeout` for every call.
+    pub fn uniform(timeout: Duration) -> Self {
+        DbusTimeouts {
+            query: timeout,
+            activate: timeout,
+        }
+    }
+}
+
+impl Default for DbusTimeouts {
+    fn default() -> Self {
+        DbusTimeouts {
+            query: DBUS_TIMEOUT,
+            activate: DBUS_ACTIVATE_TIMEOUT,
+        }
+    }
+}
+
+/// Serializes a D-Bus object path as a plain string, for `#[serde(serialize_with)]`.
+#[cfg(feature = "serde")]
+pub(crate) fn serialize_path<S: serde::Serializer>(
+    path: &dbus::Path<'_>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(path)
+}
+
+/// [`serialize_path`] for optional paths, which serialize as `null` when unset.
+#[cfg(feature = "serde")]
+pub(crate) fn serialize_optional_path<S: serde::Serializer>(
+    path: &Option<dbus::Path<'_>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match path {
+        Some(path) => serializer.serialize_some(&**path),
+        None => serializer.serialize_none(),
+    }
+}
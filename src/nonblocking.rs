@@ -0,0 +1,109 @@
+//! The async API, behind the `tokio` feature.
+//!
+//! Every function here hands its blocking counterpart to
+//! [`tokio::task::spawn_blocking`] and awaits the result. The `dbus` crate's blocking
+//! connection is the only one the library uses, so rather than a second, async code
+//! path that would have to track every option and backend, the async API runs the very
+//! same code on tokio's blocking thread pool: the calling task is never parked on a
+//! D-Bus reply, and both APIs send the same settings and return the same errors. The
+//! price is one pool thread per call in flight. Panics in the blocking call are
+//! resumed in the caller.
+
+use crate::{ActivationHandle, ConnectOptions, Security, WifiConfigError, WifiConnection};
+
+/// Async variant of [`send_wifi_to_network_manager`](crate::send_wifi_to_network_manager).
+///
+/// Runs on tokio's blocking thread pool like [`connect_async`], so it doesn't stall
+/// other tasks and sends exactly the settings the blocking version does.
+///
+/// Requires the `tokio` feature and must be called from within a tokio runtime.
+///
+/// # Errors
+///
+/// Same as [`send_wifi_to_network_manager`](crate::send_wifi_to_network_manager).
+///
+/// # Example
+///
+/// ```no_run
+/// use wifi_config::send_wifi_to_network_manager_async;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), wifi_config::WifiConfigError> {
+///     send_wifi_to_network_manager_async("MyHomeWiFi", "supersecret123").await?;
+///     Ok(())
+/// }
+/// ```
+pub async fn send_wifi_to_network_manager_async(
+    ssid: &str,
+    password: &str,
+) -> Result<ActivationHandle, WifiConfigError> {
+    WifiConnection::builder(ssid)
+        .password(password)
+        .build()?
+        .connect_async()
+        .await
+}
+
+/// Async variant of [`send_open_wifi_to_network_manager`](crate::send_open_wifi_to_network_manager).
+///
+/// Requires the `tokio` feature and must be called from within a tokio runtime.
+pub async fn send_open_wifi_to_network_manager_async(
+    ssid: &str,
+) -> Result<ActivationHandle, WifiConfigError> {
+    connect_with_security_async(ssid, &Security::Open).await
+}
+
+/// Async variant of [`connect_with_security`](crate::connect_with_security).
+///
+/// Requires the `tokio` feature and must be called from within a tokio runtime.
+pub async fn connect_with_security_async(
+    ssid: impl AsRef<[u8]>,
+    security: &Security,
+) -> Result<ActivationHandle, WifiConfigError> {
+    let options = ConnectOptions {
+        security: security.clone(),
+        ..Default::default()
+    };
+    connect_async(ssid, &options).await
+}
+
+/// Async variant of [`connect`](crate::connect).
+///
+/// Runs the blocking [`connect`](crate::connect) on tokio's blocking thread pool, so
+/// device enumeration and `AddAndActivateConnection` don't stall other tasks and the
+/// async API makes exactly the same decisions as the blocking one: every backend and
+/// option is supported.
+///
+/// Requires the `tokio` feature and must be called from within a tokio runtime.
+///
+/// # Errors
+///
+/// Same as [`connect`](crate::connect).
+///
+/// # Panics
+///
+/// Resumes a panic of the blocking call.
+pub async fn connect_async(
+    ssid: impl AsRef<[u8]>,
+    options: &ConnectOptions,
+) -> Result<ActivationHandle, WifiConfigError> {
+    let ssid = ssid.as_ref().to_vec();
+    let options = options.clone();
+    match tokio::task::spawn_blocking(move || crate::connect(&ssid, &options)).await {
+        Ok(result) => result,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send<T: Send>(_: T) {}
+
+    #[test]
+    fn future_is_send() {
+        // Must be spawnable on a multi-threaded runtime.
+        assert_send(send_wifi_to_network_manager_async("ssid", "password"));
+    }
+}
@@ -0,0 +1,401 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use crate::activation::{DEFAULT_ACTIVATION_TIMEOUT, DEFAULT_HIDDEN_ACTIVATION_TIMEOUT};
+use crate::ip;
+use crate::wireless;
+use crate::{
+    Backend, Band, CipherPolicy, DbusTimeouts, DeviceSelection, DhcpOptions, ExtraSettingValue,
+    Ipv4Config, Ipv6Config, Ipv6Privacy, MacAddr, MacPolicy, PowerSave, RetryPolicy,
+    ScanMacRandomization, SecretStorage, Security, WifiConfigError, WirelessMode,
+};
+
+/// Whether the connection is metered (`connection.metered`).
+///
+/// Applications such as update managers hold back background traffic on metered
+/// connections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Metered {
+    /// Let NetworkManager guess, e.g. from the DHCP vendor option of tethering phones.
+    Unknown,
+    /// The connection is metered.
+    Yes,
+    /// The connection is not metered.
+    No,
+}
+
+impl Metered {
+    /// The `NMMetered` value sent to NetworkManager.
+    pub(crate) fn to_nm(self) -> i32 {
+        match self {
+            Metered::Unknown => 0,
+            Metered::Yes => 1,
+            Metered::No => 2,
+        }
+    }
+}
+
+/// Options for [`connect`](crate::connect).
+///
+/// Every field has a default, so only the interesting ones need to be set, through
+/// [`ConnectOptions::builder`] or on a default value:
+///
+/// ```
+/// use wifi_config::{ConnectOptions, Security};
+///
+/// let options = ConnectOptions::builder()
+///     .security(Security::WpaPsk("supersecret123".into()))
+///     .hidden(true)
+///     .build();
+///
+/// let mut same = ConnectOptions::default();
+/// same.security = Security::WpaPsk("supersecret123".into());
+/// same.hidden = true;
+/// assert_eq!(options, same);
+/// ```
+///
+/// The struct is `#[non_exhaustive]`, so new options can be added as fields without
+/// breaking callers; outside this crate that rules out struct literals, including
+/// `..Default::default()`, which [`ConnectOptionsBuilder`](crate::ConnectOptionsBuilder)
+/// stands in for.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct ConnectOptions {
+    /// Security scheme and credentials. Defaults to [`Security::Open`].
+    pub security: Security,
+    /// WPA versions and ciphers to allow, e.g. [`CipherPolicy::Wpa2Only`] to refuse WPA1
+    /// and TKIP. `None` lets NetworkManager negotiate.
+    pub cipher_policy: Option<CipherPolicy>,
+    /// The network doesn't broadcast its SSID.
+    ///
+    /// Sets `802-11-wireless.hidden` so NetworkManager probes for the SSID instead of
+    /// waiting for a beacon that never comes.
+    pub hidden: bool,
+    /// Interface name (e.g. `wlan1`) of the Wi-Fi device to use.
+    ///
+    /// Defaults to `None`, meaning the device is picked by
+    /// [`device_selection`](Self::device_selection).
+    pub interface: Option<String>,
+    /// D-Bus object path of the Wi-Fi device to use, as returned by
+    /// [`list_wifi_devices`](crate::list_wifi_devices). Takes precedence over `interface`.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub device_path: Option<dbus::Path<'static>>,
+    /// How the Wi-Fi device is picked when neither `interface` nor `device_path` is set.
+    /// Defaults to [`DeviceSelection::Best`].
+    pub device_selection: DeviceSelection,
+    /// How long [`connect_and_wait`](crate::connect_and_wait) waits for the connection
+    /// to activate.
+    ///
+    /// Defaults to 30 seconds, or 60 seconds for hidden networks.
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::option_duration_secs"))]
+    pub activation_timeout: Option<Duration>,
+    /// Turn the Wi-Fi radio on if it is switched off in software, instead of failing with
+    /// [`WifiConfigError::RadioDisabled`], and wait up to 10 seconds for the device to
+    /// become available. A hardware switch (rfkill) always fails with
+    /// [`WifiConfigError::HardwareRfkill`].
+    pub enable_radio: bool,
+    /// Have [`connect_and_wait`](crate::connect_and_wait) run NetworkManager's
+    /// connectivity check once the connection is up and report the result in
+    /// [`ActivationHandle::connectivity`](crate::ActivationHandle::connectivity), to tell
+    /// captive portals and dead uplinks from being online.
+    pub verify_connectivity: bool,
+    /// Keep the profile when [`connect_and_wait`](crate::connect_and_wait) fails.
+    ///
+    /// By default a failed or timed-out activation through NetworkManager is rolled back:
+    /// the profile it created is deleted, so autoconnect doesn't keep retrying it, a saved
+    /// profile it updated gets its previous settings and secrets back, and the connection
+    /// that was active on the device before is re-activated. See
+    /// [`WifiConfigError::RolledBack`].
+    pub keep_on_failure: bool,
+    /// Don't connect unless the network's strongest access point has at least this
+    /// signal strength (0–100), failing with [`WifiConfigError::SignalTooWeak`] before a
+    /// profile is created. NetworkManager's 40 is about -80 dBm.
+    ///
+    /// Checked against NetworkManager's cached scan results; call
+    /// [`request_scan`](crate::request_scan) first for fresh ones. A network missing from
+    /// them fails with [`WifiConfigError::NetworkNotFound`], unless it is
+    /// [`hidden`](Self::hidden): hidden networks often don't show up in scans, so they are
+    /// only gated when they do. `None`, the default, connects whatever the signal.
+    pub min_strength: Option<u8>,
+    /// Pin the connection to the strongest access point broadcasting the SSID, instead
+    /// of letting NetworkManager pick one, e.g. on a campus where many access points
+    /// share it and a distant one sometimes wins.
+    ///
+    /// Requests a scan first and waits up to 15 seconds for it (keeping the cached
+    /// results if NetworkManager doesn't allow one or it fails), then passes the access
+    /// point with the highest signal strength, among those with
+    /// [`bssid`](Self::bssid) if that is set, as the activation's specific object.
+    /// Unlike [`bssid`](Self::bssid) nothing is saved in the profile, so NetworkManager
+    /// still roams and autoconnects freely later. If no access point broadcasts the
+    /// SSID, or the picked one disappears before the activation completes, the
+    /// connection is activated without a pin. The result is reported in
+    /// [`ActivationHandle::access_point`](crate::ActivationHandle::access_point). Only
+    /// the first device is pinned with [`DeviceSelection::All`].
+    pub pin_strongest_ap: bool,
+    /// Always add a new profile, even if one for the same SSID is already saved.
+    ///
+    /// By default a saved profile with a matching SSID (or [`uuid`](Self::uuid)) is
+    /// updated and re-activated instead, so repeated provisioning doesn't pile up
+    /// duplicates.
+    pub allow_duplicates: bool,
+    /// Re-activate the connection even if the device is already connected to the
+    /// network as asked.
+    ///
+    /// By default, when the only device used is associated with `ssid` through a profile
+    /// with the same UUID (if one is [given](Self::uuid)) that updating wouldn't change,
+    /// every setting and secret matching, NetworkManager is left alone and the handle
+    /// reports
+    /// [`ProfileChange::AlreadyConnected`](crate::ProfileChange::AlreadyConnected), so
+    /// running the same provisioning on every boot doesn't drop open sessions. If the
+    /// stored secrets can't be read to compare, the profile is updated and re-activated as
+    /// usual. With [`allow_duplicates`](Self::allow_duplicates), and with iwd and
+    /// wpa_supplicant, the connection is always re-activated.
+    pub force: bool,
+    /// Add a separate profile when NetworkManager refuses the new one because a profile
+    /// with the same UUID exists that the lookup for a saved profile didn't see, e.g.
+    /// one another tool saved meanwhile, or one restricted to other users.
+    ///
+    /// By default that profile is updated and activated instead
+    /// ([`ProfileChange::AdoptedExisting`](crate::ProfileChange::AdoptedExisting)). With
+    /// this set, the new profile is added under a fresh UUID and the id
+    /// `"<id> (wifi-config)"`, so the other tool's profile is left alone
+    /// ([`ProfileChange::CreatedSeparate`](crate::ProfileChange::CreatedSeparate)).
+    pub isolate_profile: bool,
+    /// Name of the profile (`connection.id`), as shown by `nmcli connection show`.
+    ///
+    /// `None` names new profiles after the SSID and keeps the name of a saved one.
+    pub connection_id: Option<String>,
+    /// UUID of the profile (`connection.uuid`), e.g. to give a fleet of devices the same
+    /// identifier for a network.
+    ///
+    /// When set, the saved profile with this UUID is updated instead of the one matching
+    /// the SSID, or a new one is added under it. `None` generates a random UUID for new
+    /// profiles. Either way it ends up in [`ActivationHandle::uuid`](crate::ActivationHandle::uuid).
+    pub uuid: Option<String>,
+    /// Derive the profile's UUID from the SSID instead of generating a random one, so
+    /// re-running the same provisioning converges on one profile.
+    ///
+    /// The UUID is [`stable_uuid_for`](crate::stable_uuid_for) the SSID, or
+    /// [`stable_uuid_for_interface`](crate::stable_uuid_for_interface) if
+    /// [`interface`](Self::interface) is set. It is then used like [`uuid`](Self::uuid):
+    /// the saved profile with it is updated, whatever its SSID or name, or a new one is
+    /// added under it. A profile for the SSID saved under another UUID is left alone. An
+    /// explicit [`uuid`](Self::uuid) takes precedence.
+    pub stable_uuid: bool,
+    /// IPv4 configuration. Defaults to [`Ipv4Config::Auto`] (DHCP).
+    pub ipv4: Ipv4Config,
+    /// IPv6 configuration. Defaults to [`Ipv6Config::Auto`].
+    pub ipv6: Ipv6Config,
+    /// IPv6 privacy extensions. `None` keeps NetworkManager's global default.
+    ///
+    /// Only has an effect with [`Ipv6Config::Auto`].
+    pub ipv6_privacy: Option<Ipv6Privacy>,
+    /// Hostname and client identifier sent to DHCP servers; only with
+    /// [`Ipv4Config::Auto`].
+    pub dhcp: DhcpOptions,
+    /// DNS servers to use instead of the ones provided by DHCP or router advertisements.
+    pub dns: Vec<IpAddr>,
+    /// DNS search domains (e.g. `corp.local`).
+    ///
+    /// Setting either this or [`dns`](Self::dns) makes NetworkManager ignore
+    /// automatically provided DNS settings.
+    pub dns_search: Vec<String>,
+    /// Whether NetworkManager may activate the profile on its own (`connection.autoconnect`).
+    ///
+    /// `None` keeps NetworkManager's default, which is to autoconnect.
+    pub autoconnect: Option<bool>,
+    /// Priority among profiles that may autoconnect (`connection.autoconnect-priority`);
+    /// higher wins. `None` keeps NetworkManager's default of `0`.
+    pub autoconnect_priority: Option<i32>,
+    /// Marks the connection as metered or not. `None` keeps NetworkManager's default
+    /// ([`Metered::Unknown`]).
+    pub metered: Option<Metered>,
+    /// Where NetworkManager keeps the password. Defaults to [`SecretStorage::System`],
+    /// i.e. in the profile on disk.
+    pub secret_storage: SecretStorage,
+    /// Users allowed to see and activate the profile (`connection.permissions`, as
+    /// `user:<name>:`). Empty, the default, leaves it to everyone.
+    ///
+    /// Names must be non-empty and free of colons. With
+    /// [`SecretStorage::AgentOwned`](crate::SecretStorage::AgentOwned) the password stays
+    /// with the user's secret agent as well. Updating a saved profile without
+    /// permissions keeps the ones it has.
+    pub permissions: Vec<String>,
+    /// firewalld zone of the connection (`connection.zone`), e.g. `"trusted"`. `None`
+    /// leaves the device in the default zone.
+    pub zone: Option<String>,
+    /// Operating mode. Defaults to [`WirelessMode::Infrastructure`].
+    pub mode: WirelessMode,
+    /// Frequency band (`802-11-wireless.band`), e.g. [`Band::A`] to stay off 2.4 GHz.
+    /// `None` allows any band.
+    pub band: Option<Band>,
+    /// Channel within [`band`](Self::band), which must then be set as well; channels
+    /// outside the band (e.g. 6 with [`Band::A`]) are rejected before any D-Bus call.
+    pub channel: Option<u32>,
+    /// Only associate with the access point with this BSSID (`802-11-wireless.bssid`).
+    ///
+    /// Use [`AccessPointInfo::bssid`](crate::AccessPointInfo::bssid) to pick one from
+    /// [`scan_networks`](crate::scan_networks).
+    pub bssid: Option<MacAddr>,
+    /// MAC address to use on this connection. `None` keeps NetworkManager's default.
+    pub mac_policy: Option<MacPolicy>,
+    /// MAC address randomization while scanning. `None` keeps NetworkManager's default.
+    pub scan_mac_randomization: Option<ScanMacRandomization>,
+    /// Wi-Fi power saving on the connection. `None` keeps NetworkManager's default.
+    pub powersave: Option<PowerSave>,
+    /// MTU of the connection (`802-11-wireless.mtu`). `Some(0)` makes it automatic,
+    /// values below 576 are rejected; `None` keeps NetworkManager's default.
+    pub mtu: Option<u32>,
+    /// Raw settings by section and key, e.g. `802-11-wireless` → `ap-isolation`, for
+    /// whatever NetworkManager supports that these options don't.
+    ///
+    /// They are merged into the generated settings last, so they can replace generated
+    /// values too (which is logged as a warning). Nothing checks them here: a wrong name
+    /// or type is only rejected by NetworkManager, and a wrong value may well be accepted
+    /// and break the profile. Saved profiles keep extra sections when they are updated.
+    pub extra_settings: HashMap<String, HashMap<String, ExtraSettingValue>>,
+    /// Timeouts of the D-Bus calls made to NetworkManager.
+    pub timeouts: DbusTimeouts,
+    /// Retries of device discovery and activation calls that failed for a transient
+    /// reason, e.g. while NetworkManager is still starting. Defaults to none.
+    pub retry: RetryPolicy,
+    /// The service to connect through. Defaults to [`Backend::NetworkManager`].
+    pub backend: Backend,
+}
+
+impl ConnectOptions {
+    /// Starts building options from the defaults; see
+    /// [`ConnectOptionsBuilder`](crate::ConnectOptionsBuilder).
+    pub fn builder() -> crate::ConnectOptionsBuilder {
+        crate::ConnectOptionsBuilder::default()
+    }
+
+    /// Checks `ssid` and the options client-side, before anything is sent over D-Bus.
+    pub(crate) fn validate(&self, ssid: &[u8]) -> Result<(), WifiConfigError> {
+        wireless::validate_ssid(ssid)?;
+        self.security.validate()?;
+        if let Some(cipher_policy) = &self.cipher_policy {
+            cipher_policy.validate(&self.security)?;
+        }
+        self.mode
+            .validate(&self.security, self.band, self.channel)?;
+        wireless::validate_mtu(self.mtu).map_err(WifiConfigError::InvalidWirelessConfig)?;
+        self.ipv4.validate()?;
+        self.ipv6.validate()?;
+        ip::validate_dns(&self.dns, &self.dns_search, &self.ipv6)?;
+        self.dhcp.validate(&self.ipv4)?;
+        if let Some(user) = self
+            .permissions
+            .iter()
+            .find(|user| user.is_empty() || user.contains(':'))
+        {
+            return Err(WifiConfigError::InvalidUser(user.clone()));
+        }
+        match &self.uuid {
+            Some(uuid) if !is_uuid(uuid) => Err(WifiConfigError::InvalidUuid(uuid.clone())),
+            _ => Ok(()),
+        }
+    }
+
+    /// The UUID to save the profile for `ssid` under and to look the saved one up by:
+    /// [`uuid`](Self::uuid), or the derived one with [`stable_uuid`](Self::stable_uuid).
+    pub(crate) fn profile_uuid(&self, ssid: &[u8]) -> Option<String> {
+        match (&self.uuid, &self.interface) {
+            (Some(uuid), _) => Some(uuid.clone()),
+            (None, _) if !self.stable_uuid => None,
+            (None, Some(interface)) => {
+                Some(crate::stable_uuid_for_interface(ssid, interface).to_string())
+            }
+            (None, None) => Some(crate::stable_uuid_for(ssid).to_string()),
+        }
+    }
+
+    /// The activation timeout, falling back to the default for (hidden) networks.
+    pub(crate) fn activation_timeout(&self) -> Duration {
+        self.activation_timeout.unwrap_or(if self.hidden {
+            DEFAULT_HIDDEN_ACTIVATION_TIMEOUT
+        } else {
+            DEFAULT_ACTIVATION_TIMEOUT
+        })
+    }
+}
+
+/// Whether `value` is a UUID in the hyphenated RFC 4122 form NetworkManager expects.
+pub(crate) fn is_uuid(value: &str) -> bool {
+    value.len() == 36 && uuid::Uuid::try_parse(value).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_malformed_uuids() {
+        let with_uuid = |uuid: &str| ConnectOptions {
+            uuid: Some(uuid.into()),
+            ..Default::default()
+        };
+        assert!(with_uuid("3f2b8c1e-5a4d-4e6f-9b7a-1c2d3e4f5a6b")
+            .validate(b"Office")
+            .is_ok());
+        for uuid in [
+            "",
+            "office",
+            "3f2b8c1e5a4d4e6f9b7a1c2d3e4f5a6b",
+            "{3f2b8c1e-5a4d-4e6f-9b7a-1c2d3e4f5a6b}",
+            "3f2b8c1e-5a4d-4e6f-9b7a-1c2d3e4f5a6g",
+        ] {
+            assert!(matches!(
+                with_uuid(uuid).validate(b"Office"),
+                Err(WifiConfigError::InvalidUuid(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_user_names_that_break_the_permission_format() {
+        let permitting = |user: &str| ConnectOptions {
+            permissions: vec!["kiosk".into(), user.into()],
+            ..Default::default()
+        };
+        assert!(permitting("student-042").validate(b"Lab").is_ok());
+        for user in ["", "student:042", ":"] {
+            assert!(matches!(
+                permitting(user).validate(b"Lab"),
+                Err(WifiConfigError::InvalidUser(rejected)) if rejected == user
+            ));
+        }
+    }
+
+    #[test]
+    fn hidden_networks_wait_longer() {
+        let visible = ConnectOptions::default();
+        let hidden = ConnectOptions {
+            hidden: true,
+            ..Default::default()
+        };
+        assert_eq!(visible.activation_timeout(), DEFAULT_ACTIVATION_TIMEOUT);
+        assert_eq!(
+            hidden.activation_timeout(),
+            DEFAULT_HIDDEN_ACTIVATION_TIMEOUT
+        );
+
+        let explicit = ConnectOptions {
+            hidden: true,
+            activation_timeout: Some(Duration::from_secs(5)),
+            ..Default::default()
+        };
+        assert_eq!(explicit.activation_timeout(), Duration::from_secs(5));
+    }
+}
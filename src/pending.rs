@@ -0,0 +1,225 @@
+use dbus::blocking::{stdintf::org_freedesktop_dbus::Properties, Connection};
+use std::time::Duration;
+
+use crate::activation::{self, StateChangeWatcher};
+use crate::client::{DbusNmClient, NmClient};
+use crate::error::nm_error;
+use crate::nm::{ACTIVE_CONNECTION_INTERFACE, DEVICE_INTERFACE, NM_BUS_NAME, NO_OBJECT_PATH};
+use crate::{
+    ActivationHandle, Backend, ConnectOptions, DbusTimeouts, DeviceState, DeviceStateReason,
+    ProfileChange, WifiConfigError, WifiManager,
+};
+
+/// `NMActiveConnectionState`: the connection is fully up.
+const ACTIVE_CONNECTION_ACTIVATED: u32 = 2;
+
+/// A connection attempt started by [`start_connect`], which can be waited for or
+/// cancelled.
+///
+/// The handle is `Send` and `Sync` and keeps no bus connection: start the attempt on the
+/// UI thread, [`wait`](Self::wait) for it on a worker thread, and [`cancel`](Self::cancel)
+/// it from the UI thread through an `Arc` while the worker is still waiting; the wait
+/// then returns with [`WifiConfigError::ConnectionFailed`].
+///
+/// Dropping the handle without calling `wait` or `cancel` leaves the attempt running:
+/// NetworkManager finishes (or fails) the activation on its own, and the profile stays.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// use wifi_config::{ConnectOptions, Security};
+///
+/// let options = ConnectOptions::builder()
+///     .security(Security::WpaPsk("supersecret123".into()))
+///     .build();
+/// let pending = Arc::new(wifi_config::start_connect("MyHomeWiFi", &options)?);
+/// let worker = {
+///     let pending = Arc::clone(&pending);
+///     std::thread::spawn(move || pending.wait(Duration::from_secs(30)))
+/// };
+/// // The user clicked "Cancel":
+/// pending.cancel()?;
+/// assert!(worker.join().unwrap().is_err());
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingConnection {
+    handle: ActivationHandle,
+    device_path: dbus::Path<'static>,
+    /// From the options the attempt was started with.
+    timeouts: DbusTimeouts,
+}
+
+impl PendingConnection {
+    /// The profile and active connection of the attempt.
+    pub fn handle(&self) -> &ActivationHandle {
+        &self.handle
+    }
+
+    /// D-Bus object path of the device the attempt runs on.
+    pub fn device_path(&self) -> &dbus::Path<'static> {
+        &self.device_path
+    }
+
+    /// Waits up to `timeout` for the connection to come up.
+    ///
+    /// Returns right away if it is already up.
+    ///
+    /// # Errors
+    ///
+    /// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+    /// - [`WifiConfigError::DeviceQueryFailed`] if subscribing or reading the device fails.
+    /// - [`WifiConfigError::AuthenticationFailed`] if the network rejected the credentials.
+    /// - [`WifiConfigError::ConnectionFailed`] if the activation failed for another reason,
+    ///   including [`cancel`](Self::cancel).
+    /// - [`WifiConfigError::ActivationTimeout`] if the timeout passed first; the attempt
+    ///   keeps running.
+    pub fn wait(&self, timeout: Duration) -> Result<(), WifiConfigError> {
+        let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+        // Subscribe first so no state change between checking and waiting is missed.
+        let watcher = StateChangeWatcher::new(&conn, &self.device_path)?;
+        match active_connection_state(&conn, self.timeouts, &self.handle.active_path) {
+            Some(ACTIVE_CONNECTION_ACTIVATED) => return Ok(()),
+            Some(_) => {}
+            // The active connection is gone: the attempt already failed or was cancelled.
+            None => {
+                let reason = device_state_reason(&conn, self.timeouts, &self.device_path)?;
+                return Err(activation::activation_error(reason));
+            }
+        }
+        let state = device_state(&conn, self.timeouts, &self.device_path)?;
+        let outcome = watcher.wait(timeout, activation::is_activating(state))?;
+        if outcome.is_activated() {
+            Ok(())
+        } else {
+            Err(activation::activation_error(outcome.reason))
+        }
+    }
+
+    /// The current state of the device the attempt runs on, e.g.
+    /// [`DeviceState::Config`] while associating.
+    ///
+    /// # Errors
+    ///
+    /// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+    /// - [`WifiConfigError::DeviceQueryFailed`] if the device's state can't be read.
+    pub fn poll_state(&self) -> Result<DeviceState, WifiConfigError> {
+        let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+        device_state(&conn, self.timeouts, &self.device_path)
+    }
+
+    /// Stops the attempt: deactivates the connection and, if [`start_connect`] created
+    /// the profile, deletes it, so nothing is left behind.
+    ///
+    /// An updated profile is kept (with the new settings), since it existed before.
+    /// Cancelling an attempt that already finished tears the connection down as well.
+    ///
+    /// # Errors
+    ///
+    /// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+    /// - [`WifiConfigError::DeactivateFailed`] if NetworkManager refuses to deactivate.
+    /// - [`WifiConfigError::DeleteFailed`] if the profile can't be deleted.
+    pub fn cancel(&self) -> Result<(), WifiConfigError> {
+        log::info!("cancelling the activation of {}", self.handle.settings_path);
+        let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+        activation::deactivate_on(&conn, &self.handle, self.timeouts)?;
+        if matches!(
+            self.handle.change,
+            ProfileChange::Created | ProfileChange::CreatedSeparate
+        ) {
+            DbusNmClient::new(&conn, self.timeouts)
+                .delete_connection(&self.handle.settings_path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Starts connecting to `ssid` and returns without waiting for the connection to come up.
+///
+/// Works like [`connect`](crate::connect), but returns a [`PendingConnection`] to wait
+/// for or cancel the attempt.
+///
+/// # Errors
+///
+/// - [`WifiConfigError::UnsupportedByBackend`] with a backend other than
+///   [`Backend::NetworkManager`].
+/// - [`WifiConfigError::DeviceQueryFailed`] if the active connection's device can't be
+///   read.
+/// - Otherwise the same as [`connect`](crate::connect).
+pub fn start_connect(
+    ssid: impl AsRef<[u8]>,
+    options: &ConnectOptions,
+) -> Result<PendingConnection, WifiConfigError> {
+    let ssid = ssid.as_ref();
+    options.validate(ssid)?;
+    if options.backend != Backend::NetworkManager {
+        return Err(WifiConfigError::UnsupportedByBackend(
+            "cancellable connections need NetworkManager".into(),
+        ));
+    }
+    let handle = WifiManager::new()?.connect_validated(ssid, options)?;
+    let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+    let devices: Vec<dbus::Path<'static>> = conn
+        .with_proxy(NM_BUS_NAME, &handle.active_path, options.timeouts.query)
+        .get(ACTIVE_CONNECTION_INTERFACE, "Devices")
+        .map_err(nm_error(WifiConfigError::DeviceQueryFailed))?;
+    let device_path = devices
+        .into_iter()
+        .find(|path| &**path != NO_OBJECT_PATH)
+        .ok_or(WifiConfigError::NoWifiDevice)?;
+    Ok(PendingConnection {
+        handle,
+        device_path,
+        timeouts: options.timeouts,
+    })
+}
+
+/// The `NMActiveConnectionState` of `active_path`; `None` once the object is gone.
+fn active_connection_state(
+    conn: &Connection,
+    timeouts: DbusTimeouts,
+    active_path: &dbus::Path,
+) -> Option<u32> {
+    conn.with_proxy(NM_BUS_NAME, active_path, timeouts.query)
+        .get(ACTIVE_CONNECTION_INTERFACE, "State")
+        .ok()
+}
+
+/// The current state of the device at `device_path`.
+fn device_state(
+    conn: &Connection,
+    timeouts: DbusTimeouts,
+    device_path: &dbus::Path,
+) -> Result<DeviceState, WifiConfigError> {
+    let state: u32 = conn
+        .with_proxy(NM_BUS_NAME, device_path, timeouts.query)
+        .get(DEVICE_INTERFACE, "State")
+        .map_err(nm_error(WifiConfigError::DeviceQueryFailed))?;
+    Ok(DeviceState::from(state))
+}
+
+/// Why the device at `device_path` last changed state.
+fn device_state_reason(
+    conn: &Connection,
+    timeouts: DbusTimeouts,
+    device_path: &dbus::Path,
+) -> Result<DeviceStateReason, WifiConfigError> {
+    let (_, reason): (u32, u32) = conn
+        .with_proxy(NM_BUS_NAME, device_path, timeouts.query)
+        .get(DEVICE_INTERFACE, "StateReason")
+        .map_err(nm_error(WifiConfigError::DeviceQueryFailed))?;
+    Ok(DeviceStateReason::from(reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_be_waited_for_on_another_thread() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<PendingConnection>();
+    }
+}
@@ -0,0 +1,1060 @@
+use dbus::arg::prop_cast;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+use crate::client::NmClient;
+use crate::device;
+use crate::dry_run::{self, DbusValue, SettingValue};
+use crate::options::is_uuid;
+use crate::settings::{self, ConnectionSettings, MANAGED_SECTIONS};
+use crate::{
+    secret, security, version, ActivationHandle, ConnectOptions, WifiConfigError, WifiManager,
+};
+
+/// What [`connect`](crate::connect) did with the saved connection profiles; see
+/// [`ActivationHandle::change`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ProfileChange {
+    /// A new profile was added with `AddAndActivateConnection`.
+    Created,
+    /// A saved profile for the same SSID was updated and re-activated.
+    Updated,
+    /// A saved profile for the same SSID was updated and re-activated without knowing
+    /// whether anything changed, since NetworkManager didn't hand out its secrets to
+    /// compare, e.g. to an unprivileged caller.
+    UpdatedUnverified,
+    /// A saved profile was activated as it is: by [`activate_saved`], or because it
+    /// already matched the request, secrets included, so `Update` was skipped.
+    Unchanged,
+    /// Adding the profile failed because one with the same UUID existed, unseen so far;
+    /// that one was updated and activated instead. See
+    /// [`ConnectOptions::isolate_profile`].
+    AdoptedExisting,
+    /// Adding the profile failed because one with the same UUID existed, unseen so far;
+    /// a separate profile was added next to it under a fresh UUID and the id
+    /// `"<id> (wifi-config)"`. See [`ConnectOptions::isolate_profile`].
+    CreatedSeparate,
+    /// The device already was connected to the network with the profile and password
+    /// asked for, so nothing was changed or re-activated; see
+    /// [`ConnectOptions::force`](crate::ConnectOptions::force).
+    AlreadyConnected,
+}
+
+/// Security scheme of a saved profile, derived from its `key-mgmt` setting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ProfileSecurity {
+    /// No `802-11-wireless-security` section.
+    Open,
+    /// Static WEP (`none`) or dynamic WEP (`ieee8021x`).
+    Wep,
+    /// WPA/WPA2 personal (`wpa-psk`).
+    WpaPsk,
+    /// WPA/WPA2 enterprise (`wpa-eap`).
+    WpaEap,
+    /// WPA3 personal (`sae`).
+    Sae,
+    /// Enhanced Open (`owe`).
+    Owe,
+    /// Any other `key-mgmt` value, kept verbatim.
+    Other(String),
+}
+
+impl ProfileSecurity {
+    fn from_settings(settings: &ConnectionSettings) -> Self {
+        let key_mgmt = match settings
+            .get("802-11-wireless-security")
+            .and_then(|security| prop_cast::<String>(security, "key-mgmt"))
+        {
+            Some(key_mgmt) => key_mgmt,
+            None => return ProfileSecurity::Open,
+        };
+        match key_mgmt.as_str() {
+            "none" | "ieee8021x" => ProfileSecurity::Wep,
+            "wpa-psk" => ProfileSecurity::WpaPsk,
+            "wpa-eap" => ProfileSecurity::WpaEap,
+            "sae" => ProfileSecurity::Sae,
+            "owe" => ProfileSecurity::Owe,
+            other => ProfileSecurity::Other(other.to_string()),
+        }
+    }
+}
+
+/// A Wi-Fi connection profile saved in NetworkManager.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SavedWifiProfile {
+    /// D-Bus object path of the profile.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::nm::serialize_path"))]
+    pub path: dbus::Path<'static>,
+    /// Human-readable profile name (`connection.id`).
+    pub id: String,
+    /// Profile UUID (`connection.uuid`).
+    pub uuid: String,
+    /// Raw SSID bytes.
+    pub ssid: Vec<u8>,
+    /// SSID decoded as UTF-8, with invalid sequences replaced.
+    pub ssid_lossy: String,
+    /// Security scheme derived from `key-mgmt`.
+    pub security: ProfileSecurity,
+    /// Whether NetworkManager connects to this network automatically.
+    pub autoconnect: bool,
+}
+
+impl SavedWifiProfile {
+    /// Parses the settings of one profile.
+    ///
+    /// Returns `Ok(None)` for non-Wi-Fi profiles and `Err` with a reason for Wi-Fi profiles
+    /// missing required settings.
+    fn from_settings(
+        path: dbus::Path<'static>,
+        settings: &ConnectionSettings,
+    ) -> Result<Option<Self>, String> {
+        if !settings.contains_key("802-11-wireless") {
+            return Ok(None);
+        }
+        let ssid = wireless_ssid(settings)
+            .cloned()
+            .ok_or("missing 802-11-wireless.ssid")?;
+        let connection = settings
+            .get("connection")
+            .ok_or("missing connection section")?;
+        let id = prop_cast::<String>(connection, "id")
+            .cloned()
+            .ok_or("missing connection.id")?;
+        let uuid = prop_cast::<String>(connection, "uuid")
+            .cloned()
+            .ok_or("missing connection.uuid")?;
+
+        Ok(Some(SavedWifiProfile {
+            path,
+            id,
+            uuid,
+            ssid_lossy: String::from_utf8_lossy(&ssid).into_owned(),
+            ssid,
+            security: ProfileSecurity::from_settings(settings),
+            // NetworkManager omits properties that have their default value.
+            autoconnect: prop_cast::<bool>(connection, "autoconnect")
+                .copied()
+                .unwrap_or(true),
+        }))
+    }
+}
+
+/// A saved profile that [`list_saved_connections_with_warnings`] had to skip.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ProfileWarning {
+    /// D-Bus object path of the skipped profile.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::nm::serialize_path"))]
+    pub path: dbus::Path<'static>,
+    /// Why it was skipped.
+    pub reason: String,
+}
+
+/// What [`reset_wifi_profiles`] deleted, or would delete on a dry run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ResetReport {
+    /// Whether this was a dry run, which deletes nothing.
+    pub dry_run: bool,
+    /// `connection.id`s of the Wi-Fi profiles deleted, or that would be deleted.
+    pub deleted: Vec<String>,
+    /// The profiles that couldn't be deleted, or read to tell whether they are Wi-Fi.
+    pub failed: Vec<ResetFailure>,
+}
+
+/// A profile [`reset_wifi_profiles`] couldn't remove.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ResetFailure {
+    /// `connection.id` of the profile; its path if it couldn't be read.
+    pub id: String,
+    /// D-Bus object path of the profile.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::nm::serialize_path"))]
+    pub path: dbus::Path<'static>,
+    /// Why it couldn't be removed, e.g. NetworkManager refusing to delete a system
+    /// profile.
+    pub reason: String,
+}
+
+/// Returns the raw SSID of a profile's `802-11-wireless` section, if it has one.
+pub(crate) fn wireless_ssid(settings: &ConnectionSettings) -> Option<&Vec<u8>> {
+    settings
+        .get("802-11-wireless")
+        .and_then(|wifi| prop_cast::<Vec<u8>>(wifi, "ssid"))
+}
+
+/// Whether `active`, the saved profile at `connection` running on a device associated
+/// with an access point broadcasting `ssid`, already is the connection `options` ask for.
+///
+/// Besides the SSID, and the UUID if `options` pick one, the profile has to be one
+/// [`merge_update`] leaves [`ProfileChange::Unchanged`], every setting and secret
+/// matching. A secret NetworkManager doesn't hand out to compare isn't known to match,
+/// so the profile goes through the normal update instead.
+pub(crate) fn is_requested_connection(
+    client: &dyn NmClient,
+    connection: &dbus::Path,
+    ssid: &[u8],
+    options: &ConnectOptions,
+    active: ConnectionSettings,
+) -> Result<bool, WifiConfigError> {
+    if wireless_ssid(&active).map(Vec::as_slice) != Some(ssid) {
+        return Ok(false);
+    }
+    if let Some(uuid) = options.profile_uuid(ssid) {
+        if connection_uuid(&active) != uuid {
+            return Ok(false);
+        }
+    }
+    let nm_version = client.version()?;
+    let mut requested = settings::build_connection_settings(ssid, options);
+    version::adapt_settings(&mut requested, nm_version);
+    let rename = options.connection_id.is_some();
+    let (mut merged, change) = merge_update(client, connection, active, requested, rename);
+    secret::wipe_settings(&mut merged);
+    Ok(change == ProfileChange::Unchanged)
+}
+
+/// The sections of `settings` holding a secret, each once.
+pub(crate) fn secret_sections(settings: &ConnectionSettings) -> Vec<&'static str> {
+    let mut sections: Vec<&'static str> = security::SECRETS
+        .iter()
+        .filter(|(section, key, _)| settings.get(*section).is_some_and(|s| s.contains_key(*key)))
+        .map(|(section, _, _)| *section)
+        .collect();
+    sections.dedup();
+    sections
+}
+
+/// Returns the profile's `connection.uuid`, or an empty string if it has none.
+pub(crate) fn connection_uuid(settings: &ConnectionSettings) -> String {
+    settings
+        .get("connection")
+        .and_then(|connection| prop_cast::<String>(connection, "uuid"))
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Namespace of the UUIDs derived by [`stable_uuid_for`] and
+/// [`stable_uuid_for_interface`]: `b7c3e1d2-6f4a-4e8b-9a15-3d2c7f60e8a4`.
+///
+/// Tooling outside this crate can predict a profile's UUID as the version 5 UUID of the
+/// SSID bytes in this namespace, e.g. `uuid.uuid5(namespace, "Office")` in Python for the
+/// UTF-8 SSID `Office`.
+pub const STABLE_UUID_NAMESPACE: Uuid = Uuid::from_u128(0xb7c3e1d2_6f4a_4e8b_9a15_3d2c7f60e8a4);
+
+/// The UUID [`ConnectOptions::stable_uuid`](crate::ConnectOptions::stable_uuid) saves the
+/// profile for `ssid` under: the version 5 UUID of the SSID bytes in
+/// [`STABLE_UUID_NAMESPACE`].
+///
+/// The same SSID always gives the same UUID, on every device and across releases.
+///
+/// # Example
+///
+/// ```
+/// let uuid = wifi_config::stable_uuid_for("Office");
+/// assert_eq!(uuid, wifi_config::stable_uuid_for(b"Office"));
+/// assert_ne!(uuid, wifi_config::stable_uuid_for("Office 5G"));
+/// ```
+pub fn stable_uuid_for(ssid: impl AsRef<[u8]>) -> Uuid {
+    Uuid::new_v5(&STABLE_UUID_NAMESPACE, ssid.as_ref())
+}
+
+/// [`stable_uuid_for`] for a profile pinned to `interface`, so the profiles for the same
+/// SSID on two devices don't share a UUID.
+///
+/// The name hashed is the SSID bytes, a NUL byte and the interface name.
+pub fn stable_uuid_for_interface(ssid: impl AsRef<[u8]>, interface: &str) -> Uuid {
+    let mut name = ssid.as_ref().to_vec();
+    name.push(0);
+    name.extend_from_slice(interface.as_bytes());
+    Uuid::new_v5(&STABLE_UUID_NAMESPACE, &name)
+}
+
+/// Returns `true` if the profile is a Wi-Fi profile for exactly these SSID bytes.
+pub(crate) fn matches_ssid(settings: &ConnectionSettings, ssid: &[u8]) -> bool {
+    wireless_ssid(settings).is_some_and(|saved| saved.as_slice() == ssid)
+}
+
+/// Returns `true` if the profile is the one to update: the one with UUID `uuid` if set,
+/// otherwise a Wi-Fi profile for `ssid`.
+pub(crate) fn matches_profile(
+    settings: &ConnectionSettings,
+    ssid: &[u8],
+    uuid: Option<&str>,
+) -> bool {
+    match uuid {
+        Some(uuid) => connection_uuid(settings) == uuid,
+        None => matches_ssid(settings, ssid),
+    }
+}
+
+/// Replaces the sections this crate manages in `existing` with those from `new`.
+///
+/// Everything else is kept, since `Update` replaces the whole profile. The `connection`
+/// section is merged key by key so the UUID survives, and so does the id if there is one
+/// unless `rename` is set; so are the other sections of `new`, which come from
+/// [`ConnectOptions::extra_settings`].
+pub(crate) fn merge_settings(
+    mut existing: ConnectionSettings,
+    mut new: ConnectionSettings,
+    rename: bool,
+) -> ConnectionSettings {
+    for section in MANAGED_SECTIONS {
+        existing.remove(*section);
+        if let Some(values) = new.remove(*section) {
+            existing.insert(section.to_string(), values);
+        }
+    }
+    if let Some(new_connection) = new.remove("connection") {
+        let connection = existing.entry("connection".into()).or_default();
+        for (key, value) in new_connection {
+            if key == "id" && !rename && connection.contains_key("id") {
+                continue;
+            }
+            connection.insert(key, value);
+        }
+    }
+    // Sections only extra settings write, merged like `connection`.
+    for (section, values) in new {
+        existing.entry(section).or_default().extend(values);
+    }
+    existing
+}
+
+/// Finds the saved profile with UUID `uuid` if set, otherwise the first one whose SSID
+/// equals `ssid`.
+///
+/// Returns its path together with its current (secret-less) settings.
+pub(crate) fn find_saved_connection(
+    client: &dyn NmClient,
+    ssid: &[u8],
+    uuid: Option<&str>,
+) -> Result<Option<(dbus::Path<'static>, ConnectionSettings)>, WifiConfigError> {
+    for path in client.list_connections()? {
+        let settings = client.get_settings(&path)?;
+        if matches_profile(&settings, ssid, uuid) {
+            return Ok(Some((path, settings)));
+        }
+    }
+    Ok(None)
+}
+
+/// Lists the Wi-Fi profiles saved in NetworkManager.
+///
+/// Like [`list_saved_connections_with_warnings`], but drops the warnings about skipped
+/// profiles.
+///
+/// # Example
+///
+/// ```no_run
+/// for profile in wifi_config::list_saved_connections()? {
+///     println!("{} ({}) {:?}", profile.id, profile.ssid_lossy, profile.security);
+/// }
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn list_saved_connections() -> Result<Vec<SavedWifiProfile>, WifiConfigError> {
+    list_saved_connections_with_warnings().map(|(profiles, _warnings)| profiles)
+}
+
+/// Lists the Wi-Fi profiles saved in NetworkManager, reporting skipped ones.
+///
+/// # Behavior
+///
+/// - Calls `ListConnections` on `org.freedesktop.NetworkManager.Settings`.
+/// - Reads each profile with `GetSettings` and keeps those with an `802-11-wireless`
+///   section; ethernet, bridge and other profiles are skipped silently.
+/// - A Wi-Fi profile that can't be read or lacks its SSID, id or UUID doesn't abort the
+///   listing; it is reported as a [`ProfileWarning`] instead.
+///
+/// # Errors
+///
+/// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+/// - [`WifiConfigError::NetworkManagerUnavailable`] if NetworkManager isn't running.
+/// - [`WifiConfigError::SettingsQueryFailed`] if the profiles can't be listed.
+pub fn list_saved_connections_with_warnings(
+) -> Result<(Vec<SavedWifiProfile>, Vec<ProfileWarning>), WifiConfigError> {
+    WifiManager::new()?.list_saved_connections_with_warnings()
+}
+
+/// [`list_saved_connections_with_warnings`] through `client`.
+pub(crate) fn saved_connections(
+    client: &dyn NmClient,
+) -> Result<(Vec<SavedWifiProfile>, Vec<ProfileWarning>), WifiConfigError> {
+    let mut profiles = Vec::new();
+    let mut warnings = Vec::new();
+    for path in client.list_connections()? {
+        let parsed = client
+            .get_settings(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|settings| SavedWifiProfile::from_settings(path.clone(), &settings));
+        match parsed {
+            Ok(Some(profile)) => profiles.push(profile),
+            Ok(None) => {}
+            Err(reason) => warnings.push(ProfileWarning { path, reason }),
+        }
+    }
+    Ok((profiles, warnings))
+}
+
+/// Deletes every saved profile whose SSID equals `ssid`.
+///
+/// # Behavior
+///
+/// - Calls `ListConnections` on `org.freedesktop.NetworkManager.Settings`.
+/// - Reads each profile with `GetSettings` and compares the raw `802-11-wireless.ssid`
+///   bytes, so non-ASCII (and non-UTF-8) names match exactly.
+/// - Calls `Delete` on every match.
+///
+/// Returns how many profiles were deleted; `0` if none was saved.
+///
+/// # Errors
+///
+/// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+/// - [`WifiConfigError::NetworkManagerUnavailable`] if NetworkManager isn't running.
+/// - [`WifiConfigError::SettingsQueryFailed`] if the profiles can't be listed or read.
+/// - [`WifiConfigError::DeleteFailed`] if NetworkManager refuses to delete a profile;
+///   profiles deleted before that stay deleted.
+///
+/// # Example
+///
+/// ```no_run
+/// let removed = wifi_config::forget_network("MyHomeWiFi")?;
+/// println!("removed {} profile(s)", removed);
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn forget_network(ssid: impl AsRef<[u8]>) -> Result<usize, WifiConfigError> {
+    WifiManager::new()?.forget_network(ssid)
+}
+
+/// [`forget_network`] through `client`.
+pub(crate) fn forget(client: &dyn NmClient, ssid: &[u8]) -> Result<usize, WifiConfigError> {
+    let mut removed = 0;
+    for path in client.list_connections()? {
+        let settings = client.get_settings(&path)?;
+        if matches_ssid(&settings, ssid) {
+            client.delete_connection(&path)?;
+            log::info!("deleted profile {}", path);
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Deletes every saved Wi-Fi profile, e.g. to wipe the Wi-Fi credentials on a factory
+/// reset.
+///
+/// # Behavior
+///
+/// - Calls `ListConnections` on `org.freedesktop.NetworkManager.Settings` and reads
+///   each profile with `GetSettings`.
+/// - Calls `Delete` on every profile whose `connection.type` is `802-11-wireless`,
+///   hotspots included. Ethernet, VPN, bridge and other profiles are left alone.
+/// - A profile that can't be deleted, or read, doesn't stop the others from being
+///   deleted; it is reported in [`ResetReport::failed`]. An unreadable profile is never
+///   deleted, as it may not be a Wi-Fi one.
+/// - With `dry_run`, deletes nothing and reports the profiles it would delete.
+///
+/// # Errors
+///
+/// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+/// - [`WifiConfigError::NetworkManagerUnavailable`] if NetworkManager isn't running.
+/// - [`WifiConfigError::SettingsQueryFailed`] if the profiles can't be listed.
+///
+/// # Example
+///
+/// ```no_run
+/// let report = wifi_config::reset_wifi_profiles(false)?;
+/// println!("deleted {}, failed {}", report.deleted.len(), report.failed.len());
+/// for failure in &report.failed {
+///     eprintln!("kept {}: {}", failure.id, failure.reason);
+/// }
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn reset_wifi_profiles(dry_run: bool) -> Result<ResetReport, WifiConfigError> {
+    WifiManager::new()?.reset_wifi_profiles(dry_run)
+}
+
+/// [`reset_wifi_profiles`] through `client`.
+pub(crate) fn reset(client: &dyn NmClient, dry_run: bool) -> Result<ResetReport, WifiConfigError> {
+    let mut report = ResetReport {
+        dry_run,
+        ..Default::default()
+    };
+    for path in client.list_connections()? {
+        let settings = match client.get_settings(&path) {
+            Ok(settings) => settings,
+            Err(e) => {
+                report.failed.push(ResetFailure {
+                    id: path.to_string(),
+                    path,
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let connection = settings.get("connection");
+        let kind = connection.and_then(|connection| prop_cast::<String>(connection, "type"));
+        if kind.map(String::as_str) != Some("802-11-wireless") {
+            continue;
+        }
+        let id = connection
+            .and_then(|connection| prop_cast::<String>(connection, "id"))
+            .cloned()
+            .unwrap_or_else(|| path.to_string());
+        if dry_run {
+            report.deleted.push(id);
+            continue;
+        }
+        match client.delete_connection(&path) {
+            Ok(()) => {
+                log::info!("deleted profile {} ({})", id, path);
+                report.deleted.push(id);
+            }
+            Err(e) => {
+                log::warn!("can't delete profile {} ({}): {}", id, path, e);
+                report.failed.push(ResetFailure {
+                    id,
+                    path,
+                    reason: e.to_string(),
+                });
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Activates a saved profile on the first usable Wi-Fi device, without changing it.
+///
+/// Useful for profiles provisioned ahead of time, e.g. by a keyfile written at image
+/// build time: no password has to be supplied again.
+///
+/// # Behavior
+///
+/// - If `ssid_or_uuid` is a UUID (`xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`), picks the
+///   profile with that `connection.uuid`. Otherwise picks the first Wi-Fi profile whose
+///   raw SSID bytes equal `ssid_or_uuid`, like [`forget_network`].
+/// - Calls `ActivateConnection` with the profile and the device, and `/` as specific
+///   object so NetworkManager picks the access point.
+///
+/// Returns once NetworkManager has accepted the activation, with
+/// [`ProfileChange::Unchanged`] as change; use [`wait_for_activation`](crate::wait_for_activation)
+/// to wait for the connection to come up.
+///
+/// # Errors
+///
+/// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+/// - [`WifiConfigError::NetworkManagerUnavailable`] if NetworkManager isn't running.
+/// - [`WifiConfigError::DeviceQueryFailed`] / [`WifiConfigError::NoWifiDevice`] if the Wi-Fi device can't be found.
+/// - [`WifiConfigError::SettingsQueryFailed`] if the profiles can't be listed or read.
+/// - [`WifiConfigError::ProfileNotFound`] if no saved profile matches.
+/// - [`WifiConfigError::ActivationFailed`] if NetworkManager refuses the activation.
+///
+/// # Example
+///
+/// ```no_run
+/// let handle = wifi_config::activate_saved("Office")?;
+/// println!("activating {}", handle.uuid);
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn activate_saved(ssid_or_uuid: impl AsRef<[u8]>) -> Result<ActivationHandle, WifiConfigError> {
+    WifiManager::new()?.activate_saved(ssid_or_uuid)
+}
+
+/// Finds the saved profile [`activate_saved`] picks for `ssid_or_uuid`.
+///
+/// Returns its path together with its current (secret-less) settings.
+pub(crate) fn find_profile(
+    client: &dyn NmClient,
+    ssid_or_uuid: &[u8],
+) -> Result<(dbus::Path<'static>, ConnectionSettings), WifiConfigError> {
+    let uuid = std::str::from_utf8(ssid_or_uuid)
+        .ok()
+        .filter(|value| is_uuid(value));
+    find_saved_connection(client, ssid_or_uuid, uuid)?.ok_or_else(|| {
+        WifiConfigError::ProfileNotFound(String::from_utf8_lossy(ssid_or_uuid).into_owned())
+    })
+}
+
+/// [`activate_saved`] through `client`, on the device called `interface` if given.
+pub(crate) fn activate(
+    client: &dyn NmClient,
+    ssid_or_uuid: &[u8],
+    interface: Option<&str>,
+) -> Result<ActivationHandle, WifiConfigError> {
+    let (settings_path, settings) = find_profile(client, ssid_or_uuid)?;
+    let device = device::select_wifi_device(device::wifi_devices(client)?, None, interface)?;
+    let specific_object = dbus::Path::new("/").unwrap();
+    let active_path = client.activate_connection(&settings_path, &device.path, &specific_object)?;
+    log::info!(
+        "activated profile {} on {}",
+        settings_path,
+        device.interface
+    );
+    Ok(ActivationHandle {
+        settings_path,
+        active_path,
+        interface: device.interface,
+        additional: Vec::new(),
+        uuid: connection_uuid(&settings),
+        change: ProfileChange::Unchanged,
+        detected_security: None,
+        connectivity: None,
+        portal_url: None,
+        ip: None,
+        active: None,
+        access_point: None,
+    })
+}
+
+/// Deletes the profile created or updated by [`connect`](crate::connect).
+///
+/// Calls `Delete` on [`ActivationHandle::settings_path`]; NetworkManager deactivates the
+/// connection first if it is still up. Other profiles for the same SSID are left alone,
+/// unlike with [`forget_network`].
+///
+/// # Errors
+///
+/// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+/// - [`WifiConfigError::NetworkManagerUnavailable`] if NetworkManager isn't running.
+/// - [`WifiConfigError::DeleteFailed`] if NetworkManager refuses, e.g. because the
+///   profile was already deleted.
+///
+/// # Example
+///
+/// ```no_run
+/// let handle = wifi_config::send_wifi_to_network_manager("MyHomeWiFi", "supersecret123")?;
+/// wifi_config::delete(&handle)?;
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn delete(handle: &ActivationHandle) -> Result<(), WifiConfigError> {
+    WifiManager::new()?.delete(handle)
+}
+
+/// Overwrites the sections this crate manages in a saved profile with `new` settings,
+/// and with `rename` its id as well, unless that wouldn't change anything.
+///
+/// Skipping `Update` keeps NetworkManager from bumping the profile's timestamp and
+/// reconnecting; see [`merge_update`] for how the change is told.
+pub(crate) fn update_connection(
+    client: &dyn NmClient,
+    connection_path: &dbus::Path,
+    existing: ConnectionSettings,
+    new: ConnectionSettings,
+    rename: bool,
+) -> Result<ProfileChange, WifiConfigError> {
+    let (merged, change) = merge_update(client, connection_path, existing, new, rename);
+    if change == ProfileChange::Unchanged {
+        log::info!(
+            "profile {} already matches; not rewriting it",
+            connection_path
+        );
+    } else {
+        client.update(connection_path, merged)?;
+    }
+    Ok(change)
+}
+
+/// Overwrites a saved profile with `new` settings, unless it already matches, and
+/// activates it on `device_path`, on the access point `specific_object` unless that
+/// is `/`.
+///
+/// Returns what happened to the profile and the path of the new active connection.
+pub(crate) fn update_and_activate(
+    client: &dyn NmClient,
+    connection_path: &dbus::Path,
+    existing: ConnectionSettings,
+    new: ConnectionSettings,
+    rename: bool,
+    device_path: &dbus::Path,
+    specific_object: &dbus::Path,
+) -> Result<(ProfileChange, dbus::Path<'static>), WifiConfigError> {
+    let change = update_connection(client, connection_path, existing, new, rename)?;
+    let active_path = client.activate_connection(connection_path, device_path, specific_object)?;
+    Ok((change, active_path))
+}
+
+/// Settings as [rendered](dry_run::render) for comparing them, by section and key.
+type SettingValues = BTreeMap<String, BTreeMap<String, SettingValue>>;
+
+/// Merges `new` into `existing`, the saved profile at `connection`, like
+/// [`merge_settings`] does, and tells whether writing the result would change it.
+///
+/// The change is [`ProfileChange::Unchanged`] if every setting and secret already
+/// matches, [`ProfileChange::Updated`] if something differs, and
+/// [`ProfileChange::UpdatedUnverified`] if the settings match but the stored secrets
+/// can't be read to compare: `GetSecrets` fails, e.g. with `PermissionDenied`, or a
+/// secret isn't kept by NetworkManager.
+pub(crate) fn merge_update(
+    client: &dyn NmClient,
+    connection: &dbus::Path,
+    existing: ConnectionSettings,
+    new: ConnectionSettings,
+    rename: bool,
+) -> (ConnectionSettings, ProfileChange) {
+    let mut saved = dry_run::render(&existing, true);
+    let merged = merge_settings(existing, new, rename);
+    let mut wanted = dry_run::render(&merged, true);
+
+    // `GetSettings` leaves the secrets out; they are compared on their own.
+    let mut wanted_secrets = Vec::new();
+    for (section, key, _) in security::SECRETS {
+        if let Some(values) = saved.get_mut(*section) {
+            values.remove(*key);
+        }
+        if let Some(value) = wanted
+            .get_mut(*section)
+            .and_then(|values| values.remove(*key))
+        {
+            wanted_secrets.push((*section, *key, value));
+        }
+    }
+    if !same_settings(&saved, &wanted) {
+        return (merged, ProfileChange::Updated);
+    }
+
+    let mut stored = ConnectionSettings::new();
+    for section in secret_sections(&merged) {
+        match client.get_secrets(connection, section) {
+            Ok(secrets) => stored.extend(secrets),
+            Err(e) => {
+                log::info!(
+                    "can't read the {} secrets of {} to compare them, updating it anyway: {}",
+                    section,
+                    connection,
+                    e
+                );
+                return (merged, ProfileChange::UpdatedUnverified);
+            }
+        }
+    }
+    let rendered = dry_run::render(&stored, true);
+    secret::wipe_settings(&mut stored);
+    let stored = rendered;
+    let mut change = ProfileChange::Unchanged;
+    for (section, key, value) in wanted_secrets {
+        match stored.get(section).and_then(|values| values.get(key)) {
+            Some(stored) if *stored == value => {}
+            Some(_) => return (merged, ProfileChange::Updated),
+            None => change = ProfileChange::UpdatedUnverified,
+        }
+    }
+    (merged, change)
+}
+
+/// Whether `a` and `b` hold the same settings.
+///
+/// A setting only one of them has counts as matching if it is one NetworkManager fills
+/// in when it saves a profile: an empty list, dictionary or string, or `method=auto`.
+fn same_settings(a: &SettingValues, b: &SettingValues) -> bool {
+    let empty = BTreeMap::new();
+    a.keys().chain(b.keys()).all(|section| {
+        let a = a.get(section).unwrap_or(&empty);
+        let b = b.get(section).unwrap_or(&empty);
+        a.keys()
+            .chain(b.keys())
+            .all(|key| match (a.get(key), b.get(key)) {
+                (Some(a), Some(b)) => a == b,
+                (Some(value), None) | (None, Some(value)) => is_filled_in(key, &value.value),
+                (None, None) => true,
+            })
+    })
+}
+
+/// Whether `value` of `key` is a default NetworkManager writes into saved profiles.
+fn is_filled_in(key: &str, value: &DbusValue) -> bool {
+    match value {
+        DbusValue::Array(items) => items.is_empty(),
+        DbusValue::Dict(entries) => entries.is_empty(),
+        DbusValue::String(text) => text.is_empty() || (key == "method" && text == "auto"),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::{MockDevice, MockNmClient};
+    use crate::settings::build_connection_settings;
+    use crate::{ConnectOptions, Security};
+    use dbus::arg::{PropMap, Variant};
+
+    fn saved_profile() -> ConnectionSettings {
+        let mut settings = build_connection_settings(
+            b"MyHomeWiFi",
+            &ConnectOptions {
+                security: Security::WpaPsk("oldpassword".into()),
+                hidden: true,
+                ..Default::default()
+            },
+        );
+        let mut connection = PropMap::new();
+        connection.insert("id".into(), Variant(Box::new(String::from("MyHomeWiFi"))));
+        settings.insert("connection".into(), connection);
+        settings
+    }
+
+    #[test]
+    fn reads_wireless_ssid() {
+        assert_eq!(
+            wireless_ssid(&saved_profile()).map(Vec::as_slice),
+            Some(&b"MyHomeWiFi"[..])
+        );
+        assert_eq!(wireless_ssid(&ConnectionSettings::new()), None);
+    }
+
+    #[test]
+    fn reads_connection_uuid() {
+        let mut settings = saved_profile();
+        assert_eq!(connection_uuid(&settings), "");
+        settings
+            .get_mut("connection")
+            .unwrap()
+            .insert("uuid".into(), Variant(Box::new(String::from("1234"))));
+        assert_eq!(connection_uuid(&settings), "1234");
+    }
+
+    #[test]
+    fn derives_stable_uuids() {
+        // Pinned: external tooling predicts these from STABLE_UUID_NAMESPACE.
+        assert_eq!(
+            stable_uuid_for("Office").to_string(),
+            "a8cd9fb6-fedf-5c5c-914a-4dd189d60fef"
+        );
+        assert_eq!(
+            stable_uuid_for_interface("Office", "wlan1").to_string(),
+            "349ea201-d91a-5384-a13e-d5d58dddfae4"
+        );
+    }
+
+    #[test]
+    fn parses_saved_profile() {
+        let path = dbus::Path::new("/org/freedesktop/NetworkManager/Settings/1").unwrap();
+        let mut settings = saved_profile();
+        let connection = settings.get_mut("connection").unwrap();
+        connection.insert("uuid".into(), Variant(Box::new(String::from("1234"))));
+        connection.insert("autoconnect".into(), Variant(Box::new(false)));
+
+        let profile = SavedWifiProfile::from_settings(path.clone(), &settings)
+            .unwrap()
+            .unwrap();
+        assert_eq!(profile.id, "MyHomeWiFi");
+        assert_eq!(profile.uuid, "1234");
+        assert_eq!(profile.ssid, b"MyHomeWiFi");
+        assert_eq!(profile.security, ProfileSecurity::WpaPsk);
+        assert!(!profile.autoconnect);
+
+        // Missing UUID is malformed, a profile without a Wi-Fi section is skipped.
+        assert!(SavedWifiProfile::from_settings(path.clone(), &saved_profile()).is_err());
+        settings.remove("802-11-wireless");
+        assert_eq!(SavedWifiProfile::from_settings(path, &settings), Ok(None));
+    }
+
+    #[test]
+    fn matches_ssid_on_raw_bytes() {
+        let settings = build_connection_settings("Café".as_bytes(), &ConnectOptions::default());
+        assert!(matches_ssid(&settings, "Café".as_bytes()));
+        assert!(!matches_ssid(&settings, b"Caf"));
+        assert!(!matches_ssid(&settings, b"Cafe"));
+    }
+
+    #[test]
+    fn merge_replaces_managed_sections_only() {
+        let mut existing = saved_profile();
+        let connection = existing.get_mut("connection").unwrap();
+        connection.insert("id".into(), Variant(Box::new(String::from("Home"))));
+        connection.insert("uuid".into(), Variant(Box::new(String::from("1234"))));
+        let mut proxy = PropMap::new();
+        proxy.insert("method".into(), Variant(Box::new(1i32)));
+        existing.insert("proxy".into(), proxy);
+        let new = build_connection_settings(
+            b"MyHomeWiFi",
+            &ConnectOptions {
+                autoconnect: Some(false),
+                extra_settings: [(
+                    "proxy".to_string(),
+                    [("pac-url".to_string(), "http://wpad/wpad.dat".into())].into(),
+                )]
+                .into(),
+                ..Default::default()
+            },
+        );
+        let merged = merge_settings(existing, new, false);
+
+        // Sections from extra settings are merged key by key.
+        assert_eq!(merged["proxy"]["method"].0.as_i64(), Some(1));
+        assert_eq!(
+            merged["proxy"]["pac-url"].0.as_str(),
+            Some("http://wpad/wpad.dat")
+        );
+
+        // The profile keeps its identity but picks up the new connection options.
+        assert_eq!(merged["connection"]["id"].0.as_str(), Some("Home"));
+        assert_eq!(merged["connection"]["uuid"].0.as_str(), Some("1234"));
+        assert_eq!(merged["connection"]["autoconnect"].0.as_u64(), Some(0));
+        // Switching to an open network drops the old security section.
+        assert!(!merged.contains_key("802-11-wireless-security"));
+        assert!(!merged["802-11-wireless"].contains_key("hidden"));
+    }
+
+    #[test]
+    fn merge_keeps_permissions_unless_new_ones_are_given() {
+        let permitted = |users: &[&str]| ConnectOptions {
+            permissions: users.iter().map(|user| user.to_string()).collect(),
+            ..Default::default()
+        };
+        let permissions = |settings: &ConnectionSettings| {
+            prop_cast::<Vec<String>>(&settings["connection"], "permissions").cloned()
+        };
+        let existing = || build_connection_settings(b"MyHomeWiFi", &permitted(&["kiosk"]));
+
+        let merged = merge_settings(
+            existing(),
+            build_connection_settings(b"MyHomeWiFi", &ConnectOptions::default()),
+            false,
+        );
+        assert_eq!(permissions(&merged), Some(vec!["user:kiosk:".to_string()]));
+
+        let merged = merge_settings(
+            existing(),
+            build_connection_settings(b"MyHomeWiFi", &permitted(&["alice", "bob"])),
+            false,
+        );
+        assert_eq!(
+            permissions(&merged),
+            Some(vec!["user:alice:".to_string(), "user:bob:".to_string()])
+        );
+    }
+
+    #[test]
+    fn merge_update_ignores_what_network_manager_fills_in() {
+        let options = ConnectOptions {
+            security: Security::WpaPsk("supersecret123".into()),
+            ..Default::default()
+        };
+        // What `GetSettings` returns for a profile saved with `options`.
+        let saved = || {
+            let mut settings = build_connection_settings(b"Office", &options);
+            let mut ipv4 = PropMap::new();
+            ipv4.insert("method".into(), Variant(Box::new(String::from("auto"))));
+            ipv4.insert("dns".into(), Variant(Box::new(Vec::<u32>::new())));
+            settings.insert("ipv4".into(), ipv4);
+            settings
+        };
+        let client = MockNmClient::default();
+        let path = client.save(saved(), "1");
+        let change = |options: &ConnectOptions| {
+            let existing = client.get_settings(&path).unwrap();
+            let new = build_connection_settings(b"Office", options);
+            merge_update(&client, &path, existing, new, false).1
+        };
+
+        assert_eq!(change(&options), ProfileChange::Unchanged);
+        let hidden = ConnectOptions {
+            hidden: true,
+            ..options.clone()
+        };
+        assert_eq!(change(&hidden), ProfileChange::Updated);
+        let other_password = ConnectOptions {
+            security: Security::WpaPsk("anothersecret".into()),
+            ..Default::default()
+        };
+        assert_eq!(change(&other_password), ProfileChange::Updated);
+    }
+
+    #[test]
+    fn forget_deletes_only_matching_profiles() {
+        let client = MockNmClient::default();
+        let home = client.save(saved_profile(), "1");
+        client.save(
+            build_connection_settings(b"Office", &ConnectOptions::default()),
+            "2",
+        );
+        let home_again = client.save(saved_profile(), "3");
+
+        assert_eq!(forget(&client, b"MyHomeWiFi").unwrap(), 2);
+        let calls = client.calls();
+        assert!(calls.contains(&format!("Delete {}", home)));
+        assert!(calls.contains(&format!("Delete {}", home_again)));
+        assert_eq!(client.connections.borrow().len(), 1);
+    }
+
+    #[test]
+    fn reset_deletes_only_wifi_profiles() {
+        let mut client = MockNmClient::default();
+        let home = client.save(
+            build_connection_settings(b"MyHomeWiFi", &ConnectOptions::default()),
+            "1",
+        );
+        let mut ethernet = ConnectionSettings::new();
+        let mut connection = PropMap::new();
+        connection.insert("id".into(), Variant(Box::new(String::from("Wired"))));
+        connection.insert(
+            "type".into(),
+            Variant(Box::new(String::from("802-3-ethernet"))),
+        );
+        ethernet.insert("connection".into(), connection);
+        let wired = client.save(ethernet, "2");
+        let office = client.save(
+            build_connection_settings(b"Office", &ConnectOptions::default()),
+            "3",
+        );
+        client.undeletable.push(office.clone());
+
+        let report = reset(&client, true).unwrap();
+        assert!(report.dry_run);
+        assert_eq!(report.deleted, ["MyHomeWiFi", "Office"]);
+        assert!(report.failed.is_empty());
+        assert!(!client.methods().contains(&"Delete".into()));
+
+        let report = reset(&client, false).unwrap();
+        assert_eq!(report.deleted, ["MyHomeWiFi"]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].id, "Office");
+        assert_eq!(report.failed[0].path, office);
+        assert!(report.failed[0].reason.contains("Insufficient privileges"));
+        let calls = client.calls();
+        assert!(calls.contains(&format!("Delete {}", home)));
+        assert!(!calls.contains(&format!("Delete {}", wired)));
+        let left: Vec<_> = client
+            .connections
+            .borrow()
+            .iter()
+            .map(|(path, _)| path.clone())
+            .collect();
+        assert_eq!(left, [wired, office]);
+    }
+
+    #[test]
+    fn activates_saved_profile_by_ssid_or_uuid() {
+        let client = MockNmClient::with_devices(vec![MockDevice::new(2, "wlan0", 2)]);
+        let home = client.save(saved_profile(), "0b7cb1a4-5f7c-4d36-9d0e-6a1f3c2e8b90");
+        let office = client.save(
+            build_connection_settings(b"Office", &ConnectOptions::default()),
+            "6f1e2d3c-4b5a-4978-8695-a4b3c2d1e0f9",
+        );
+
+        let handle = activate(&client, b"MyHomeWiFi", None).unwrap();
+        assert_eq!(handle.settings_path, home);
+        assert_eq!(handle.change, ProfileChange::Unchanged);
+        let handle = activate(&client, b"6f1e2d3c-4b5a-4978-8695-a4b3c2d1e0f9", None).unwrap();
+        assert_eq!(handle.settings_path, office);
+        assert_eq!(handle.uuid, "6f1e2d3c-4b5a-4978-8695-a4b3c2d1e0f9");
+        assert!(!client.methods().contains(&"Update".into()));
+
+        assert!(matches!(
+            activate(&client, b"Guest", None),
+            Err(WifiConfigError::ProfileNotFound(name)) if name == "Guest"
+        ));
+    }
+}
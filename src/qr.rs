@@ -0,0 +1,280 @@
+use std::str::FromStr;
+
+use crate::{Security, WifiConfigError, WifiConnection, WifiConnectionBuilder};
+
+/// A network described by a Wi-Fi QR code, as printed on router stickers and shown by
+/// phones when sharing a network.
+///
+/// The payload has the form `WIFI:T:WPA;S:MySsid;P:supersecret123;H:true;;`:
+///
+/// - `T` is the security type: `WPA`, `SAE`, `WEP` or `nopass`. Without `T` the network
+///   is open.
+/// - `S` is the SSID. A quoted value (`S:"CAFE"`) is taken as text; an unquoted value
+///   made of an even number of hex digits is the hex-encoded SSID, as in the ZXing
+///   specification. Any other value is taken as text.
+/// - `P` is the password; surrounding quotes are removed.
+/// - `H:true` marks the SSID as hidden.
+///
+/// `\;`, `\:`, `\,`, `\"` and `\\` escape the special characters in values. Fields can
+/// come in any order, and unknown fields are ignored.
+///
+/// Convert it into a [`WifiConnectionBuilder`] to connect:
+///
+/// ```no_run
+/// use wifi_config::{WifiConnectionBuilder, WifiQr};
+///
+/// let qr = WifiQr::parse("WIFI:T:WPA;S:Office;P:supersecret123;;")?;
+/// WifiConnectionBuilder::from(qr).build()?.connect()?;
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WifiQr {
+    /// Raw SSID bytes.
+    pub ssid: Vec<u8>,
+    /// Security scheme and password.
+    pub security: Security,
+    /// Whether the SSID is hidden (`H:true`).
+    pub hidden: bool,
+}
+
+impl WifiQr {
+    /// Parses a `WIFI:` QR code payload.
+    ///
+    /// Only the syntax is checked here; the SSID and password are validated when the
+    /// connection is built.
+    ///
+    /// # Errors
+    ///
+    /// [`WifiConfigError::InvalidQrCode`] if the payload doesn't start with `WIFI:`, has no
+    /// SSID, has an unknown `T` value, or has no password for a secured network.
+    pub fn parse(payload: &str) -> Result<Self, WifiConfigError> {
+        let invalid = |reason: &str| WifiConfigError::InvalidQrCode(reason.to_string());
+        let fields = payload
+            .strip_prefix("WIFI:")
+            .ok_or_else(|| invalid("expected the payload to start with 'WIFI:'"))?;
+
+        let mut ssid = None;
+        let mut password = None;
+        let mut security_type = None;
+        let mut hidden = false;
+        for field in split_fields(fields) {
+            if field.is_empty() {
+                continue;
+            }
+            let Some(colon) = field.iter().position(|&(c, escaped)| c == ':' && !escaped) else {
+                return Err(invalid("expected fields of the form KEY:VALUE"));
+            };
+            let key: String = field[..colon].iter().map(|&(c, _)| c).collect();
+            let value = &field[colon + 1..];
+            match key.as_str() {
+                "S" => ssid = Some(decode_ssid(value)),
+                "P" => password = Some(unquote(value)),
+                "T" => security_type = Some(unquote(value)),
+                "H" => hidden = unquote(value).eq_ignore_ascii_case("true"),
+                _ => {}
+            }
+        }
+
+        let ssid = ssid.ok_or_else(|| invalid("missing SSID (S field)"))?;
+        let password = password.filter(|password| !password.is_empty());
+        let security = match (security_type.as_deref(), password) {
+            (None | Some(""), _) => Security::Open,
+            (Some(t), _) if t.eq_ignore_ascii_case("nopass") => Security::Open,
+            (Some(_), None) => return Err(invalid("missing password (P field)")),
+            (Some(t), Some(password)) if t.eq_ignore_ascii_case("WPA") => {
+                Security::WpaPsk(password.into())
+            }
+            (Some(t), Some(password)) if t.eq_ignore_ascii_case("SAE") => {
+                Security::Wpa3Sae(password.into())
+            }
+            (Some(t), Some(password)) if t.eq_ignore_ascii_case("WEP") => {
+                Security::Wep(password.into())
+            }
+            (Some(t), Some(_)) => {
+                return Err(WifiConfigError::InvalidQrCode(format!(
+                    "unknown security type '{}'",
+                    t
+                )))
+            }
+        };
+        Ok(WifiQr {
+            ssid,
+            security,
+            hidden,
+        })
+    }
+}
+
+impl FromStr for WifiQr {
+    type Err = WifiConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        WifiQr::parse(s)
+    }
+}
+
+impl From<WifiQr> for WifiConnectionBuilder {
+    fn from(qr: WifiQr) -> Self {
+        WifiConnection::builder(qr.ssid)
+            .security(qr.security)
+            .hidden(qr.hidden)
+    }
+}
+
+/// A character of a field, and whether it was preceded by a backslash.
+type Escaped = (char, bool);
+
+/// Splits the fields at unescaped `;`, resolving backslash escapes.
+fn split_fields(fields: &str) -> Vec<Vec<Escaped>> {
+    let mut split = vec![Vec::new()];
+    let mut chars = fields.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            // A trailing lone backslash is kept as is.
+            '\\' => {
+                let escaped = chars.next().map_or(('\\', false), |next| (next, true));
+                split.last_mut().expect("never empty").push(escaped);
+            }
+            ';' => split.push(Vec::new()),
+            c => split.last_mut().expect("never empty").push((c, false)),
+        }
+    }
+    split
+}
+
+/// Returns the value without its surrounding unescaped double quotes, if it has them.
+fn strip_quotes(value: &[Escaped]) -> Option<&[Escaped]> {
+    match value {
+        [('"', false), inner @ .., ('"', false)] => Some(inner),
+        _ => None,
+    }
+}
+
+/// Collects the value into a string, removing surrounding quotes.
+fn unquote(value: &[Escaped]) -> String {
+    strip_quotes(value)
+        .unwrap_or(value)
+        .iter()
+        .map(|&(c, _)| c)
+        .collect()
+}
+
+/// Decodes the `S` field: quoted values are text, unquoted hex digits are the SSID bytes.
+fn decode_ssid(value: &[Escaped]) -> Vec<u8> {
+    if let Some(text) = strip_quotes(value) {
+        return text
+            .iter()
+            .map(|&(c, _)| c)
+            .collect::<String>()
+            .into_bytes();
+    }
+    let text: String = value.iter().map(|&(c, _)| c).collect();
+    let is_hex = !text.is_empty()
+        && text.len().is_multiple_of(2)
+        && value
+            .iter()
+            .all(|&(c, escaped)| !escaped && c.is_ascii_hexdigit());
+    if !is_hex {
+        return text.into_bytes();
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).expect("checked to be hex"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_fields_in_any_order() {
+        let qr = WifiQr::parse("WIFI:T:WPA;S:Office;P:supersecret123;H:true;;").unwrap();
+        assert_eq!(qr.ssid, b"Office");
+        assert_eq!(qr.security, Security::WpaPsk("supersecret123".into()));
+        assert!(qr.hidden);
+
+        let qr = WifiQr::parse("WIFI:P:supersecret123;S:Office;T:SAE;;").unwrap();
+        assert_eq!(qr.security, Security::Wpa3Sae("supersecret123".into()));
+        assert!(!qr.hidden);
+
+        let qr = WifiQr::parse("WIFI:S:Guest;T:nopass;;").unwrap();
+        assert_eq!(qr.security, Security::Open);
+        // No T field at all is an open network as well, and the final ';' is optional.
+        let qr = WifiQr::parse("WIFI:S:Guest").unwrap();
+        assert_eq!(qr.security, Security::Open);
+        let qr = WifiQr::parse("WIFI:T:WEP;S:Legacy;P:abcde;I:unknown;;").unwrap();
+        assert_eq!(qr.security, Security::Wep("abcde".into()));
+    }
+
+    #[test]
+    fn resolves_escapes() {
+        let qr = WifiQr::parse(r#"WIFI:T:WPA;S:My\;Net\:5G;P:pa\\ss\,wo\"rd\;;;"#).unwrap();
+        assert_eq!(qr.ssid, b"My;Net:5G");
+        assert_eq!(qr.security, Security::WpaPsk(r#"pa\ss,wo"rd;"#.into()));
+
+        // An unescaped ':' after the key belongs to the value.
+        let qr = WifiQr::parse("WIFI:T:WPA;S:Office;P:a:b:c:d:e;;").unwrap();
+        assert_eq!(qr.security, Security::WpaPsk("a:b:c:d:e".into()));
+
+        // A trailing backslash has nothing to escape.
+        let qr = WifiQr::parse(r"WIFI:S:Office\").unwrap();
+        assert_eq!(qr.ssid, br"Office\");
+    }
+
+    #[test]
+    fn decodes_hex_and_quoted_ssids() {
+        // Latin-1 "Café", which isn't UTF-8.
+        let qr = WifiQr::parse("WIFI:S:436166e9;;").unwrap();
+        assert_eq!(qr.ssid, b"Caf\xe9");
+        // Quotes keep a name that looks like hex as text.
+        let qr = WifiQr::parse(r#"WIFI:S:"CAFE";;"#).unwrap();
+        assert_eq!(qr.ssid, b"CAFE");
+        // Odd length, or an escaped character, isn't hex.
+        let qr = WifiQr::parse("WIFI:S:ABC;;").unwrap();
+        assert_eq!(qr.ssid, b"ABC");
+        let qr = WifiQr::parse(r"WIFI:S:AB\CD;;").unwrap();
+        assert_eq!(qr.ssid, b"ABCD");
+        // Quotes around the password are removed; an escaped quote stays.
+        let qr = WifiQr::parse(r#"WIFI:T:WPA;S:Office;P:"supersecret123";;"#).unwrap();
+        assert_eq!(qr.security, Security::WpaPsk("supersecret123".into()));
+        let qr = WifiQr::parse(r#"WIFI:T:WPA;S:Office;P:\"supersecret123\";;"#).unwrap();
+        assert_eq!(qr.security, Security::WpaPsk(r#""supersecret123""#.into()));
+    }
+
+    #[test]
+    fn rejects_malformed_payloads() {
+        for payload in [
+            "",
+            "T:WPA;S:Office;P:supersecret123;;",
+            "WIFI:T:WPA;P:supersecret123;;",
+            "WIFI:T:WPA;S:Office;;",
+            "WIFI:T:WPA;S:Office;P:;;",
+            "WIFI:T:WPA2-EAP;S:Office;P:supersecret123;;",
+            "WIFI:S:Office;garbage;;",
+        ] {
+            assert!(
+                matches!(
+                    WifiQr::parse(payload),
+                    Err(WifiConfigError::InvalidQrCode(_))
+                ),
+                "{}",
+                payload
+            );
+        }
+    }
+
+    #[test]
+    fn converts_into_a_builder() {
+        let qr: WifiQr = "WIFI:T:WPA;S:Office;P:supersecret123;H:true;;"
+            .parse()
+            .unwrap();
+        let connection = WifiConnectionBuilder::from(qr).build().unwrap();
+        assert_eq!(connection.ssid(), b"Office");
+        assert_eq!(
+            connection.options().security,
+            Security::WpaPsk("supersecret123".into())
+        );
+        assert!(connection.options().hidden);
+    }
+}
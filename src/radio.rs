@@ -0,0 +1,193 @@
+//! The manager's Wi-Fi radio switch, checked (and optionally flipped) before connecting.
+
+use dbus::blocking::Connection;
+use std::time::{Duration, Instant};
+
+use crate::client::{DbusNmClient, NmClient};
+use crate::{device, DbusTimeouts, DeviceState, WifiConfigError, WifiDevice};
+
+/// How long to wait for a Wi-Fi device to come up after turning the radio on.
+pub(crate) const RADIO_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often the device states are polled meanwhile.
+pub(crate) const RADIO_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Whether the Wi-Fi radio is on, as reported by [`radio_state`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum RadioState {
+    /// Wi-Fi is on.
+    Enabled,
+    /// Wi-Fi is switched off in software, e.g. from the desktop's network menu or by
+    /// airplane mode; [`ConnectOptions::enable_radio`](crate::ConnectOptions::enable_radio)
+    /// turns it back on.
+    SoftBlocked,
+    /// Wi-Fi is blocked by a hardware switch or key (rfkill), which only the user can
+    /// undo.
+    HardBlocked,
+}
+
+impl RadioState {
+    /// The state from the manager's `WirelessEnabled` and `WirelessHardwareEnabled`
+    /// properties; a hardware block wins.
+    pub(crate) fn from_switches(enabled: bool, hardware_enabled: bool) -> Self {
+        match (enabled, hardware_enabled) {
+            (_, false) => RadioState::HardBlocked,
+            (false, true) => RadioState::SoftBlocked,
+            (true, true) => RadioState::Enabled,
+        }
+    }
+}
+
+/// Reports whether the Wi-Fi radio is on, e.g. to tell the user to flip the Wi-Fi switch
+/// when [`active_connection`](crate::active_connection) returns `None`.
+///
+/// # Errors
+///
+/// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+/// - [`WifiConfigError::NetworkManagerUnavailable`] if NetworkManager isn't running.
+/// - [`WifiConfigError::DeviceQueryFailed`] if the manager's properties can't be read.
+///
+/// # Example
+///
+/// ```no_run
+/// use wifi_config::RadioState;
+///
+/// if wifi_config::radio_state()? == RadioState::HardBlocked {
+///     println!("flip the Wi-Fi switch on the side of your laptop");
+/// }
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn radio_state() -> Result<RadioState, WifiConfigError> {
+    let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+    read_radio_state(&DbusNmClient::new(&conn, DbusTimeouts::default()))
+}
+
+/// [`radio_state`] through `client`.
+pub(crate) fn read_radio_state(client: &dyn NmClient) -> Result<RadioState, WifiConfigError> {
+    let (enabled, hardware_enabled) = client.wireless_enabled()?;
+    Ok(RadioState::from_switches(enabled, hardware_enabled))
+}
+
+/// Decides what to do about a radio in `state` before connecting on one of `devices`.
+///
+/// Returns whether the radio has to be turned on, which is only allowed with `enable`. A
+/// hardware block always fails with [`WifiConfigError::HardwareRfkill`], naming
+/// `interface` if given, or else the first unavailable Wi-Fi device.
+pub(crate) fn check_radio(
+    state: RadioState,
+    enable: bool,
+    devices: &[WifiDevice],
+    interface: Option<&str>,
+) -> Result<bool, WifiConfigError> {
+    match state {
+        RadioState::Enabled => Ok(false),
+        RadioState::SoftBlocked if enable => Ok(true),
+        RadioState::SoftBlocked => Err(WifiConfigError::RadioDisabled),
+        RadioState::HardBlocked => {
+            let blocked = interface.map(str::to_string).or_else(|| {
+                devices
+                    .iter()
+                    .find(|device| device.state == DeviceState::Unavailable)
+                    .or(devices.first())
+                    .map(|device| device.interface.clone())
+            });
+            Err(blocked.map_or(
+                WifiConfigError::NoWifiDevice,
+                WifiConfigError::HardwareRfkill,
+            ))
+        }
+    }
+}
+
+/// Fails unless the Wi-Fi radio is on; with `enable`, turns a software-blocked radio on
+/// and waits up to [`RADIO_TIMEOUT`] for a Wi-Fi device to leave
+/// [`DeviceState::Unavailable`]. `interface` is the device to connect on, if chosen.
+pub(crate) fn ensure_radio(
+    client: &dyn NmClient,
+    enable: bool,
+    interface: Option<&str>,
+) -> Result<(), WifiConfigError> {
+    let state = read_radio_state(client)?;
+    if state == RadioState::Enabled {
+        return Ok(());
+    }
+    let mut devices = device::wifi_devices(client)?;
+    if !check_radio(state, enable, &devices, interface)? {
+        return Ok(());
+    }
+    log::info!("turning the Wi-Fi radio on");
+    client.set_wireless_enabled(true)?;
+
+    let deadline = Instant::now() + RADIO_TIMEOUT;
+    loop {
+        devices = device::wifi_devices(client)?;
+        if devices
+            .iter()
+            .any(|device| device.state != DeviceState::Unavailable)
+        {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            log::warn!(
+                "no Wi-Fi device became available within {:?} of turning the radio on",
+                RADIO_TIMEOUT
+            );
+            return Ok(());
+        }
+        std::thread::sleep(RADIO_POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::{MockDevice, MockNmClient};
+    use std::cell::Cell;
+
+    fn unavailable(index: u32, interface: &str) -> MockDevice {
+        let mut device = MockDevice::new(index, interface, 2);
+        device
+            .properties
+            .insert("State".into(), dbus::arg::Variant(Box::new(20u32)));
+        device
+    }
+
+    #[test]
+    fn reports_a_hardware_block_with_the_interface() {
+        let client = MockNmClient {
+            wireless_hardware_disabled: true,
+            ..MockNmClient::with_devices(vec![
+                MockDevice::new(1, "eth0", 1),
+                unavailable(2, "wlan0"),
+            ])
+        };
+        assert_eq!(read_radio_state(&client).unwrap(), RadioState::HardBlocked);
+        match ensure_radio(&client, true, None) {
+            Err(WifiConfigError::HardwareRfkill(interface)) => assert_eq!(interface, "wlan0"),
+            other => panic!("expected HardwareRfkill, got {:?}", other),
+        }
+        assert!(!client.methods().contains(&"SetWirelessEnabled".to_string()));
+    }
+
+    #[test]
+    fn refuses_a_soft_block_unless_asked_to_enable_the_radio() {
+        let client = MockNmClient {
+            wireless_disabled: Cell::new(true),
+            ..MockNmClient::with_devices(vec![MockDevice::new(1, "wlan0", 2)])
+        };
+        assert!(matches!(
+            ensure_radio(&client, false, None),
+            Err(WifiConfigError::RadioDisabled)
+        ));
+
+        ensure_radio(&client, true, None).unwrap();
+        assert!(client
+            .calls()
+            .contains(&"SetWirelessEnabled true".to_string()));
+        assert!(!client.wireless_disabled.get());
+    }
+}
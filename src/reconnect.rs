@@ -0,0 +1,198 @@
+//! Bouncing the Wi-Fi: taking a saved profile down and bringing it up again.
+
+use dbus::arg::prop_cast;
+use dbus::blocking::{stdintf::org_freedesktop_dbus::Properties, Connection};
+use std::time::Duration;
+
+use crate::activation::{
+    self, StateChangeWatcher, DEFAULT_ACTIVATION_TIMEOUT, DEFAULT_HIDDEN_ACTIVATION_TIMEOUT,
+};
+use crate::client::{DbusNmClient, NmClient};
+use crate::error::nm_error;
+use crate::nm::{ACTIVE_CONNECTION_INTERFACE, DEVICE_INTERFACE, NM_BUS_NAME, NO_OBJECT_PATH};
+use crate::profiles::{self, connection_uuid};
+use crate::settings::ConnectionSettings;
+use crate::{device, radio};
+use crate::{ActivationHandle, DbusTimeouts, ProfileChange, WifiConfigError};
+
+/// Re-activates a saved profile on the Wi-Fi device and waits until it is up again.
+///
+/// # Behavior
+///
+/// - Fails if the Wi-Fi radio is off, and uses the first usable Wi-Fi device.
+/// - With `ssid_or_uuid`, picks the saved profile like
+///   [`activate_saved`](crate::activate_saved) does and activates it; NetworkManager
+///   takes down whatever is active on the device first, including that same profile.
+/// - With `None`, takes the profile active on the device, disconnects the device and
+///   activates the profile again.
+/// - Waits for the device to reach `ACTIVATED`, up to 30 seconds (60 for hidden
+///   networks).
+///
+/// # Errors
+///
+/// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+/// - [`WifiConfigError::RadioDisabled`] / [`WifiConfigError::HardwareRfkill`] if the
+///   Wi-Fi radio is off.
+/// - [`WifiConfigError::NoWifiDevice`] if no Wi-Fi device is found.
+/// - [`WifiConfigError::ProfileNotFound`] if no saved profile matches `ssid_or_uuid`.
+/// - [`WifiConfigError::NothingToReconnect`] if `ssid_or_uuid` is `None` and nothing is
+///   active on the device.
+/// - [`WifiConfigError::DisconnectFailed`] if the device can't be disconnected.
+/// - [`WifiConfigError::ActivationFailed`] if NetworkManager refuses the activation.
+/// - [`WifiConfigError::ActivationTimeout`] if the device didn't come back in time.
+/// - [`WifiConfigError::AuthenticationFailed`] / [`WifiConfigError::ConnectionFailed`]
+///   if the activation failed.
+///
+/// # Example
+///
+/// ```no_run
+/// let handle = wifi_config::reconnect(None)?;
+/// println!("reconnected {} on {}", handle.uuid, handle.interface);
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn reconnect(ssid_or_uuid: Option<&str>) -> Result<ActivationHandle, WifiConfigError> {
+    let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+    let timeouts = DbusTimeouts::default();
+    let client = DbusNmClient::new(&conn, timeouts);
+    radio::ensure_radio(&client, false, None)?;
+    let device = device::select_wifi_device(device::wifi_devices(&client)?, None, None)?;
+    let active_profile = match ssid_or_uuid {
+        Some(_) => None,
+        None => active_profile(&conn, timeouts, &device.path)?,
+    };
+    let (settings_path, settings) =
+        pick_profile(&client, ssid_or_uuid, active_profile, &device.interface)?;
+
+    // Subscribe first so no state change between activation and waiting is missed.
+    let watcher = StateChangeWatcher::new(&conn, &device.path)?;
+    if ssid_or_uuid.is_none() {
+        device::disconnect_path(&conn, timeouts, &device.path, false)?;
+        log::info!("disconnected {}", device.interface);
+    }
+    let specific_object = dbus::Path::new(NO_OBJECT_PATH).unwrap();
+    let active_path = client.activate_connection(&settings_path, &device.path, &specific_object)?;
+    log::info!(
+        "re-activating profile {} on {}",
+        settings_path,
+        device.interface
+    );
+    let outcome = watcher.wait(activation_timeout(&settings), false)?;
+    if !outcome.is_activated() {
+        return Err(activation::activation_error(outcome.reason));
+    }
+    Ok(ActivationHandle {
+        settings_path,
+        active_path,
+        interface: device.interface,
+        additional: Vec::new(),
+        uuid: connection_uuid(&settings),
+        change: ProfileChange::Unchanged,
+        detected_security: None,
+        connectivity: None,
+        portal_url: None,
+        ip: None,
+        active: None,
+        access_point: None,
+    })
+}
+
+/// The profile of the connection active on `device_path`, if any.
+fn active_profile(
+    conn: &Connection,
+    timeouts: DbusTimeouts,
+    device_path: &dbus::Path,
+) -> Result<Option<dbus::Path<'static>>, WifiConfigError> {
+    let active_path: dbus::Path<'static> = conn
+        .with_proxy(NM_BUS_NAME, device_path, timeouts.query)
+        .get(DEVICE_INTERFACE, "ActiveConnection")
+        .map_err(nm_error(WifiConfigError::DeviceQueryFailed))?;
+    if &*active_path == NO_OBJECT_PATH {
+        return Ok(None);
+    }
+    conn.with_proxy(NM_BUS_NAME, &active_path, timeouts.query)
+        .get(ACTIVE_CONNECTION_INTERFACE, "Connection")
+        .map(Some)
+        .map_err(nm_error(WifiConfigError::DeviceQueryFailed))
+}
+
+/// The profile to re-activate: the saved one for `ssid_or_uuid` if given, otherwise
+/// `active_profile`, the one active on `interface`.
+fn pick_profile(
+    client: &dyn NmClient,
+    ssid_or_uuid: Option<&str>,
+    active_profile: Option<dbus::Path<'static>>,
+    interface: &str,
+) -> Result<(dbus::Path<'static>, ConnectionSettings), WifiConfigError> {
+    match (ssid_or_uuid, active_profile) {
+        (Some(name), _) => profiles::find_profile(client, name.as_bytes()),
+        (None, Some(path)) => {
+            let settings = client.get_settings(&path)?;
+            Ok((path, settings))
+        }
+        (None, None) => Err(WifiConfigError::NothingToReconnect(interface.into())),
+    }
+}
+
+/// How long to wait for the profile with `settings` to come up.
+fn activation_timeout(settings: &ConnectionSettings) -> Duration {
+    let hidden = settings
+        .get("802-11-wireless")
+        .and_then(|wifi| prop_cast::<bool>(wifi, "hidden"))
+        .copied()
+        .unwrap_or(false);
+    if hidden {
+        DEFAULT_HIDDEN_ACTIVATION_TIMEOUT
+    } else {
+        DEFAULT_ACTIVATION_TIMEOUT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::{MockDevice, MockNmClient};
+    use crate::settings::build_connection_settings;
+    use crate::ConnectOptions;
+
+    #[test]
+    fn picks_the_named_or_the_active_profile() {
+        let client = MockNmClient::with_devices(vec![MockDevice::new(1, "wlan0", 2)]);
+        let hidden = ConnectOptions {
+            hidden: true,
+            ..Default::default()
+        };
+        let office = client.save(
+            build_connection_settings(b"Office", &hidden),
+            "0b7cb1a4-5f7c-4d36-9d0e-6a1f3c2e8b90",
+        );
+        let home = client.save(
+            build_connection_settings(b"Home", &ConnectOptions::default()),
+            "6f1e2d3c-4b5a-4978-8695-a4b3c2d1e0f9",
+        );
+
+        let (path, settings) =
+            pick_profile(&client, Some("Office"), Some(home.clone()), "wlan0").unwrap();
+        assert_eq!(path, office);
+        assert_eq!(
+            activation_timeout(&settings),
+            DEFAULT_HIDDEN_ACTIVATION_TIMEOUT
+        );
+        let (path, settings) = pick_profile(&client, None, Some(home.clone()), "wlan0").unwrap();
+        assert_eq!(path, home);
+        assert_eq!(activation_timeout(&settings), DEFAULT_ACTIVATION_TIMEOUT);
+
+        assert!(matches!(
+            pick_profile(&client, Some("Cafe"), None, "wlan0"),
+            Err(WifiConfigError::ProfileNotFound(name)) if name == "Cafe"
+        ));
+        let nothing = pick_profile(&client, None, None, "wlan0").unwrap_err();
+        assert!(
+            matches!(&nothing, WifiConfigError::NothingToReconnect(interface)
+            if interface == "wlan0")
+        );
+        assert_eq!(
+            nothing.to_string(),
+            "nothing to reconnect: wlan0 has no active connection"
+        );
+    }
+}
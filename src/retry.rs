@@ -0,0 +1,197 @@
+use std::time::Duration;
+
+/// D-Bus errors NetworkManager returns while it is still starting up or overloaded.
+const TRANSIENT_ERRORS: &[&str] = &[
+    "org.freedesktop.DBus.Error.ServiceUnknown",
+    "org.freedesktop.DBus.Error.NoReply",
+    "org.freedesktop.DBus.Error.Timeout",
+    "org.freedesktop.DBus.Error.TimedOut",
+    "org.freedesktop.DBus.Error.LimitsExceeded",
+];
+
+/// How often device discovery and activation calls are retried.
+///
+/// Only failures that go away on their own are retried: NetworkManager not (yet) owning
+/// its bus name, a call timing out, or the bus' limits being exceeded. Errors
+/// NetworkManager answers with (invalid settings, missing secrets, ...) fail right away.
+///
+/// The first retry waits `backoff`; every further one waits twice as long as the one
+/// before, up to `max_backoff`.
+///
+/// A timed out `AddAndActivateConnection` may still have saved the profile, so a retry
+/// can leave a duplicate behind.
+///
+/// ```
+/// use std::time::Duration;
+/// use wifi_config::{ConnectOptions, RetryPolicy};
+///
+/// let options = ConnectOptions::builder()
+///     .retry(RetryPolicy {
+///         attempts: 5,
+///         backoff: Duration::from_secs(1),
+///         max_backoff: Duration::from_secs(10),
+///     })
+///     .build();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct RetryPolicy {
+    /// Total number of attempts per call, including the first one. Defaults to 1, i.e.
+    /// no retries.
+    pub attempts: u32,
+    /// Wait before the first retry. Defaults to 500 milliseconds.
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::duration_secs"))]
+    pub backoff: Duration,
+    /// Upper bound of the wait between two attempts. Defaults to 8 seconds.
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::duration_secs"))]
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Retries each call up to `retries` times, with the default backoff.
+    pub fn retries(retries: u32) -> Self {
+        RetryPolicy {
+            attempts: retries.saturating_add(1),
+            ..Default::default()
+        }
+    }
+
+    /// The wait before retry number `retry` (starting at 1).
+    pub(crate) fn delay(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: 1,
+            backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(8),
+        }
+    }
+}
+
+/// Whether `e` is worth retrying; see [`RetryPolicy`].
+pub(crate) fn is_transient(e: &dbus::Error) -> bool {
+    e.name()
+        .is_some_and(|name| TRANSIENT_ERRORS.contains(&name))
+}
+
+/// Runs the D-Bus call `method` until it succeeds, fails for good, or `policy` runs out
+/// of attempts.
+pub(crate) fn retry<T>(
+    policy: &RetryPolicy,
+    method: &str,
+    mut call: impl FnMut() -> Result<T, dbus::Error>,
+) -> Result<T, dbus::Error> {
+    let mut attempt = 1;
+    loop {
+        match call() {
+            Err(e) => match next_delay(policy, method, attempt, &e) {
+                Some(delay) => std::thread::sleep(delay),
+                None => return Err(e),
+            },
+            result => return result,
+        }
+        attempt += 1;
+    }
+}
+
+/// The wait before retrying after attempt number `attempt` failed with `e`, or `None` to
+/// give up.
+fn next_delay(
+    policy: &RetryPolicy,
+    method: &str,
+    attempt: u32,
+    e: &dbus::Error,
+) -> Option<Duration> {
+    if attempt >= policy.attempts || !is_transient(e) {
+        return None;
+    }
+    let delay = policy.delay(attempt);
+    log::warn!(
+        "{} failed on attempt {} of {}: {}; retrying in {:?}",
+        method,
+        attempt,
+        policy.attempts,
+        e,
+        delay
+    );
+    Some(delay)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn error(name: &str) -> dbus::Error {
+        dbus::Error::new_custom(name, "failed")
+    }
+
+    fn policy(attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            attempts,
+            backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn retries_transient_errors_until_success() {
+        let calls = Cell::new(0);
+        let result = retry(&policy(3), "GetDevices", || {
+            calls.set(calls.get() + 1);
+            match calls.get() {
+                1 => Err(error("org.freedesktop.DBus.Error.ServiceUnknown")),
+                2 => Err(error("org.freedesktop.DBus.Error.NoReply")),
+                _ => Ok(7),
+            }
+        });
+        assert_eq!(result.unwrap(), 7);
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn gives_up_after_the_last_attempt() {
+        let calls = Cell::new(0);
+        let result: Result<(), _> = retry(&policy(2), "GetDevices", || {
+            calls.set(calls.get() + 1);
+            Err(error("org.freedesktop.DBus.Error.LimitsExceeded"))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn fails_right_away_on_other_errors() {
+        let calls = Cell::new(0);
+        let result: Result<(), _> = retry(&policy(5), "AddAndActivateConnection", || {
+            calls.set(calls.get() + 1);
+            Err(error(
+                "org.freedesktop.NetworkManager.Settings.InvalidSetting",
+            ))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let policy = RetryPolicy {
+            attempts: 10,
+            backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+        };
+        let delays: Vec<u64> = (1..=5).map(|retry| policy.delay(retry).as_secs()).collect();
+        assert_eq!(delays, [1, 2, 4, 5, 5]);
+        assert_eq!(RetryPolicy::retries(3).attempts, 4);
+        assert_eq!(RetryPolicy::default().attempts, 1);
+    }
+}
@@ -0,0 +1,750 @@
+use dbus::arg::{prop_cast, PropMap};
+use dbus::blocking::{stdintf::org_freedesktop_dbus::Properties, Connection};
+use std::time::{Duration, Instant};
+
+use crate::client::NmClient;
+use crate::error::nm_error;
+use crate::nm::{NM_BUS_NAME, NO_OBJECT_PATH, WIRELESS_INTERFACE};
+use crate::wireless;
+use crate::{
+    ConnectOptions, DbusTimeouts, MacAddr, Secret, Security, WifiConfigError, WifiManager,
+};
+
+/// How long [`scan_networks`] and [`signal_strength`] wait for a requested scan to finish.
+pub(crate) const SCAN_TIMEOUT: Duration = Duration::from_secs(15);
+/// Error NetworkManager answers `RequestScan` with while the device can't scan, e.g. while
+/// it is connecting or has scanned moments ago.
+const SCAN_NOT_ALLOWED: &str = "org.freedesktop.NetworkManager.Device.Wireless.ScanNotAllowed";
+/// How often `LastScan` is polled while waiting for a scan.
+const SCAN_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// `NM80211ApFlags`: the AP requires some form of encryption.
+const AP_FLAGS_PRIVACY: u32 = 0x1;
+/// `NM80211ApSecurityFlags`: pre-shared key authentication.
+const AP_SEC_KEY_MGMT_PSK: u32 = 0x100;
+/// `NM80211ApSecurityFlags`: 802.1X authentication.
+const AP_SEC_KEY_MGMT_802_1X: u32 = 0x200;
+/// `NM80211ApSecurityFlags`: WPA3 simultaneous authentication of equals.
+const AP_SEC_KEY_MGMT_SAE: u32 = 0x400;
+/// `NM80211ApSecurityFlags`: Opportunistic Wireless Encryption.
+const AP_SEC_KEY_MGMT_OWE: u32 = 0x800;
+/// `NM80211ApSecurityFlags`: open BSS of an OWE transition-mode pair.
+const AP_SEC_KEY_MGMT_OWE_TM: u32 = 0x1000;
+
+/// Security scheme advertised by an access point, decoded from its flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ApSecurity {
+    /// No encryption.
+    Open,
+    /// Legacy WEP.
+    Wep,
+    /// WPA/WPA2 personal (pre-shared key).
+    WpaPsk,
+    /// WPA/WPA2 enterprise (802.1X).
+    WpaEap,
+    /// WPA3 personal.
+    Sae,
+    /// Opportunistic Wireless Encryption (Enhanced Open); connect with
+    /// [`Security::Owe`](crate::Security::Owe).
+    Owe,
+}
+
+impl ApSecurity {
+    /// Decodes the `Flags`, `WpaFlags` and `RsnFlags` properties of an access point.
+    ///
+    /// The strongest advertised scheme wins, so a WPA2/WPA3 transition AP reports [`ApSecurity::Sae`].
+    pub(crate) fn from_flags(flags: u32, wpa_flags: u32, rsn_flags: u32) -> Self {
+        let key_mgmt = wpa_flags | rsn_flags;
+        if key_mgmt & AP_SEC_KEY_MGMT_SAE != 0 {
+            ApSecurity::Sae
+        } else if key_mgmt & AP_SEC_KEY_MGMT_802_1X != 0 {
+            ApSecurity::WpaEap
+        } else if key_mgmt & AP_SEC_KEY_MGMT_PSK != 0 {
+            ApSecurity::WpaPsk
+        } else if key_mgmt & (AP_SEC_KEY_MGMT_OWE | AP_SEC_KEY_MGMT_OWE_TM) != 0 {
+            ApSecurity::Owe
+        } else if flags & AP_FLAGS_PRIVACY != 0 {
+            ApSecurity::Wep
+        } else {
+            ApSecurity::Open
+        }
+    }
+}
+
+/// Converts a signal level in dBm to a strength in percent, the way NetworkManager does:
+/// -100 dBm and below is 0 %, -50 dBm and above is 100 %.
+pub(crate) fn dbm_to_percent(dbm: i32) -> u8 {
+    (2 * (dbm + 100)).clamp(0, 100) as u8
+}
+
+/// An access point visible to the Wi-Fi device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct AccessPointInfo {
+    /// D-Bus object path of the access point.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::nm::serialize_path"))]
+    pub path: dbus::Path<'static>,
+    /// Raw SSID bytes.
+    pub ssid: Vec<u8>,
+    /// SSID decoded as UTF-8, with invalid sequences replaced.
+    pub ssid_lossy: String,
+    /// Signal strength in percent (0–100).
+    pub strength: u8,
+    /// Frequency in MHz.
+    pub frequency: u32,
+    /// Highest bitrate the access point supports, in kbit/s; 0 if not reported.
+    pub max_bitrate: u32,
+    /// BSSID in `AA:BB:CC:DD:EE:FF` form.
+    pub hw_address: String,
+    /// Raw `Flags` property.
+    pub flags: u32,
+    /// Raw `WpaFlags` property.
+    pub wpa_flags: u32,
+    /// Raw `RsnFlags` property.
+    pub rsn_flags: u32,
+    /// Security scheme decoded from the flags.
+    pub security: ApSecurity,
+}
+
+impl AccessPointInfo {
+    /// The BSSID parsed from [`hw_address`](Self::hw_address), ready to be passed as
+    /// [`ConnectOptions::bssid`](crate::ConnectOptions::bssid).
+    ///
+    /// `None` if NetworkManager reported a malformed address.
+    pub fn bssid(&self) -> Option<MacAddr> {
+        self.hw_address.parse().ok()
+    }
+
+    /// The channel number of [`frequency`](Self::frequency); see
+    /// [`frequency_to_channel`](crate::frequency_to_channel).
+    pub fn channel(&self) -> Option<u32> {
+        wireless::frequency_to_channel(self.frequency)
+    }
+
+    /// Builds an access point from its `AccessPoint` interface properties.
+    pub(crate) fn from_properties(path: dbus::Path<'static>, props: &PropMap) -> Self {
+        let ssid = prop_cast::<Vec<u8>>(props, "Ssid")
+            .cloned()
+            .unwrap_or_default();
+        let flags = prop_cast::<u32>(props, "Flags").copied().unwrap_or(0);
+        let wpa_flags = prop_cast::<u32>(props, "WpaFlags").copied().unwrap_or(0);
+        let rsn_flags = prop_cast::<u32>(props, "RsnFlags").copied().unwrap_or(0);
+
+        AccessPointInfo {
+            path,
+            ssid_lossy: String::from_utf8_lossy(&ssid).into_owned(),
+            ssid,
+            strength: prop_cast::<u8>(props, "Strength").copied().unwrap_or(0),
+            frequency: prop_cast::<u32>(props, "Frequency").copied().unwrap_or(0),
+            max_bitrate: prop_cast::<u32>(props, "MaxBitrate").copied().unwrap_or(0),
+            hw_address: prop_cast::<String>(props, "HwAddress")
+                .cloned()
+                .unwrap_or_default(),
+            flags,
+            wpa_flags,
+            rsn_flags,
+            security: ApSecurity::from_flags(flags, wpa_flags, rsn_flags),
+        }
+    }
+}
+
+/// The access point [`ConnectOptions::pin_strongest_ap`] pinned a connection to, as
+/// reported in [`ActivationHandle::access_point`](crate::ActivationHandle::access_point).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PinnedAccessPoint {
+    /// D-Bus object path of the access point, passed as the activation's specific object.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::nm::serialize_path"))]
+    pub path: dbus::Path<'static>,
+    /// BSSID of the access point.
+    pub bssid: MacAddr,
+    /// Its signal strength (0–100) in the scan it was picked from.
+    pub strength: u8,
+    /// Whether the connection was activated on it. `false` if it disappeared before the
+    /// activation completed and NetworkManager was left to pick the access point.
+    pub pinned: bool,
+}
+
+/// Lists the access points currently visible to the first usable Wi-Fi device.
+///
+/// # Behavior
+///
+/// - Locates the Wi-Fi device the same way [`send_wifi_to_network_manager`](crate::send_wifi_to_network_manager) does.
+/// - Calls `GetAllAccessPoints` on `org.freedesktop.NetworkManager.Device.Wireless`.
+/// - Reads `Ssid`, `Strength`, `Frequency`, `Flags`, `WpaFlags`, `RsnFlags` and `HwAddress` of each AP.
+///
+/// Every BSSID is returned, so an SSID served by several APs appears once per AP.
+///
+/// Without `fresh` the list is NetworkManager's cached scan result, which can be minutes
+/// old. With `fresh` a new scan is requested first and waited for (up to 15 seconds), as
+/// with [`request_scan`]; if NetworkManager doesn't allow one right now, the cached list
+/// is returned.
+///
+/// # Errors
+///
+/// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+/// - [`WifiConfigError::NetworkManagerUnavailable`] if NetworkManager isn't running.
+/// - [`WifiConfigError::DeviceQueryFailed`] / [`WifiConfigError::NoWifiDevice`] if the Wi-Fi device can't be found.
+/// - [`WifiConfigError::ScanFailed`] if the access points can't be listed or read, or a
+///   `fresh` scan fails or doesn't finish in time.
+///
+/// # Example
+///
+/// ```no_run
+/// for ap in wifi_config::scan_networks(true)? {
+///     println!("{} {}% {:?}", ap.ssid_lossy, ap.strength, ap.security);
+/// }
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn scan_networks(fresh: bool) -> Result<Vec<AccessPointInfo>, WifiConfigError> {
+    WifiManager::new()?.scan(fresh)
+}
+
+/// [`scan_networks`] on the device at `device_path`.
+pub(crate) fn scan_device(
+    conn: &Connection,
+    timeouts: DbusTimeouts,
+    client: &dyn NmClient,
+    device_path: &dbus::Path,
+    fresh: bool,
+) -> Result<Vec<AccessPointInfo>, WifiConfigError> {
+    if fresh {
+        rescan(conn, timeouts, device_path, true, SCAN_TIMEOUT)?;
+    }
+    access_points(client, device_path)
+}
+
+/// Asks the first usable Wi-Fi device to scan for networks.
+///
+/// # Behavior
+///
+/// - Calls `RequestScan` with no options on `org.freedesktop.NetworkManager.Device.Wireless`.
+/// - With `wait`, polls the device's `LastScan` until it advances, i.e. the scan finished
+///   and [`scan_networks`] returns its results. Otherwise returns right away.
+/// - If NetworkManager doesn't allow a scan right now (`ScanNotAllowed`, e.g. while the
+///   device is connecting), logs a warning and returns `Ok`, leaving the cached results.
+///
+/// # Errors
+///
+/// - Same as [`scan_networks`]. [`WifiConfigError::ScanFailed`] is also returned if
+///   NetworkManager refuses the scan for another reason, or with `wait` if the scan
+///   doesn't finish within `timeout`.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// wifi_config::request_scan(true, Duration::from_secs(10))?;
+/// let access_points = wifi_config::scan_networks(false)?;
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn request_scan(wait: bool, timeout: Duration) -> Result<(), WifiConfigError> {
+    WifiManager::new()?.request_scan(wait, timeout)
+}
+
+/// Returns the best signal strength (0–100) among the access points serving `ssid`.
+///
+/// # Behavior
+///
+/// - Locates the first usable Wi-Fi device like [`scan_networks`].
+/// - If `max_age` is given and the device's last scan (`LastScan`) is older than that, or
+///   it never scanned, calls `RequestScan` and waits up to 15 seconds for the scan to
+///   finish. NetworkManager's cached list can otherwise be minutes old.
+/// - Compares the raw SSID bytes of every access point and returns the highest
+///   `Strength` among the matching BSSIDs.
+///
+/// Returns `Ok(None)` if no access point serves `ssid`.
+///
+/// # Errors
+///
+/// - Same as [`request_scan`].
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// match wifi_config::signal_strength("MyHomeWiFi", Some(Duration::from_secs(30)))? {
+///     Some(strength) => println!("{}%", strength),
+///     None => println!("not in range"),
+/// }
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn signal_strength(
+    ssid: impl AsRef<[u8]>,
+    max_age: Option<Duration>,
+) -> Result<Option<u8>, WifiConfigError> {
+    WifiManager::new()?.signal_strength(ssid, max_age)
+}
+
+/// [`signal_strength`] on the device at `device_path`.
+pub(crate) fn device_signal_strength(
+    conn: &Connection,
+    timeouts: DbusTimeouts,
+    client: &dyn NmClient,
+    device_path: &dbus::Path,
+    ssid: &[u8],
+    max_age: Option<Duration>,
+) -> Result<Option<u8>, WifiConfigError> {
+    if let Some(max_age) = max_age {
+        let device_proxy = conn.with_proxy(NM_BUS_NAME, device_path, timeouts.query);
+        let last_scan: i64 = device_proxy
+            .get(WIRELESS_INTERFACE, "LastScan")
+            .map_err(nm_error(WifiConfigError::ScanFailed))?;
+        if scan_is_stale(last_scan, boot_time_ms(), max_age) {
+            rescan(conn, timeouts, device_path, true, SCAN_TIMEOUT)?;
+        }
+    }
+
+    Ok(best_strength(&access_points(client, device_path)?, ssid))
+}
+
+/// Whether `ssid` is visible right now, as the signal strength (0–100) of its strongest
+/// access point.
+///
+/// Returns `Ok(None)` if no access point serves `ssid`, compared as raw bytes, so callers
+/// can gate on a minimum too: NetworkManager's strength 60 is about -70 dBm.
+///
+/// Lists the cached access points of the first usable Wi-Fi device, like
+/// [`scan_networks`]; with `fresh`, scans first and waits for the results. Use
+/// [`signal_strength`] to rescan only when the cached results are too old.
+///
+/// # Errors
+///
+/// - Same as [`scan_networks`].
+///
+/// # Example
+///
+/// ```no_run
+/// match wifi_config::is_network_available("MyHomeWiFi", true)? {
+///     Some(strength) if strength >= 60 => println!("in range"),
+///     Some(_) => println!("too weak"),
+///     None => println!("not in range"),
+/// }
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn is_network_available(
+    ssid: impl AsRef<[u8]>,
+    fresh: bool,
+) -> Result<Option<u8>, WifiConfigError> {
+    WifiManager::new()?.is_network_available(ssid, fresh)
+}
+
+/// Details of the access point the first usable Wi-Fi device is associated with, e.g. its
+/// BSSID, [`channel`](AccessPointInfo::channel) and
+/// [`max_bitrate`](AccessPointInfo::max_bitrate).
+///
+/// Reads the device's `ActiveAccessPoint` property and then the access point's
+/// properties, like [`scan_networks`] does for every access point.
+///
+/// Returns `Ok(None)` if the device isn't associated with one.
+///
+/// # Errors
+///
+/// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+/// - [`WifiConfigError::NetworkManagerUnavailable`] if NetworkManager isn't running.
+/// - [`WifiConfigError::DeviceQueryFailed`] / [`WifiConfigError::NoWifiDevice`] if the
+///   Wi-Fi device can't be found or read.
+/// - [`WifiConfigError::ScanFailed`] if the access point's properties can't be read.
+///
+/// # Example
+///
+/// ```no_run
+/// if let Some(ap) = wifi_config::active_access_point()? {
+///     println!(
+///         "{} on channel {:?}, {} Mbit/s, {}%",
+///         ap.hw_address,
+///         ap.channel(),
+///         ap.max_bitrate / 1000,
+///         ap.strength
+///     );
+/// }
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn active_access_point() -> Result<Option<AccessPointInfo>, WifiConfigError> {
+    WifiManager::new()?.active_access_point()
+}
+
+/// [`active_access_point`] of the device at `device_path`.
+pub(crate) fn device_access_point(
+    conn: &Connection,
+    timeouts: DbusTimeouts,
+    client: &dyn NmClient,
+    device_path: &dbus::Path,
+) -> Result<Option<AccessPointInfo>, WifiConfigError> {
+    let access_point: dbus::Path<'static> = conn
+        .with_proxy(NM_BUS_NAME, device_path, timeouts.query)
+        .get(WIRELESS_INTERFACE, "ActiveAccessPoint")
+        .map_err(nm_error(WifiConfigError::DeviceQueryFailed))?;
+    if &*access_point == NO_OBJECT_PATH {
+        return Ok(None);
+    }
+    let props = client.access_point_properties(&access_point)?;
+    Ok(Some(AccessPointInfo::from_properties(access_point, &props)))
+}
+
+/// Calls `RequestScan` and, with `wait`, polls `LastScan` until it moves past its value
+/// from before the request; see [`request_scan`].
+pub(crate) fn rescan(
+    conn: &Connection,
+    timeouts: DbusTimeouts,
+    device_path: &dbus::Path,
+    wait: bool,
+    timeout: Duration,
+) -> Result<(), WifiConfigError> {
+    let device_proxy = conn.with_proxy(NM_BUS_NAME, device_path, timeouts.query);
+    let last_scan: i64 = device_proxy
+        .get(WIRELESS_INTERFACE, "LastScan")
+        .map_err(nm_error(WifiConfigError::ScanFailed))?;
+    let result: Result<(), _> =
+        device_proxy.method_call(WIRELESS_INTERFACE, "RequestScan", (PropMap::new(),));
+    match result {
+        Err(e) if e.name() == Some(SCAN_NOT_ALLOWED) => {
+            log::warn!(
+                "NetworkManager did not allow a scan ({}); using the cached results",
+                e.message().unwrap_or("no reason given")
+            );
+            return Ok(());
+        }
+        result => result.map_err(nm_error(WifiConfigError::ScanFailed))?,
+    }
+    if !wait {
+        return Ok(());
+    }
+
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        std::thread::sleep(SCAN_POLL_INTERVAL);
+        let current: i64 = device_proxy
+            .get(WIRELESS_INTERFACE, "LastScan")
+            .map_err(nm_error(WifiConfigError::ScanFailed))?;
+        if current > last_scan {
+            return Ok(());
+        }
+    }
+    Err(WifiConfigError::ScanFailed(dbus::Error::new_custom(
+        "org.freedesktop.DBus.Error.Timeout",
+        "the requested scan did not finish in time",
+    )))
+}
+
+/// Returns `true` if a scan at `last_scan` ms (`-1` for never) is older than `max_age` at
+/// `now` ms, both on the `CLOCK_BOOTTIME` scale NetworkManager uses.
+fn scan_is_stale(last_scan: i64, now: Option<i64>, max_age: Duration) -> bool {
+    match now {
+        Some(now) if last_scan >= 0 => now.saturating_sub(last_scan) > max_age.as_millis() as i64,
+        // Never scanned, or no clock to compare against: rescan to be safe.
+        _ => true,
+    }
+}
+
+/// Milliseconds since boot, including suspend, read from `/proc/uptime`.
+fn boot_time_ms() -> Option<i64> {
+    let uptime = std::fs::read_to_string("/proc/uptime").ok()?;
+    let secs: f64 = uptime.split_whitespace().next()?.parse().ok()?;
+    Some((secs * 1000.0) as i64)
+}
+
+/// Returns the highest `Strength` among the access points broadcasting `ssid`.
+pub(crate) fn best_strength(access_points: &[AccessPointInfo], ssid: &[u8]) -> Option<u8> {
+    access_points
+        .iter()
+        .filter(|ap| ap.ssid == ssid)
+        .map(|ap| ap.strength)
+        .max()
+}
+
+/// The strongest access point broadcasting `ssid` with a readable BSSID, only
+/// considering `bssid` if one is given.
+pub(crate) fn strongest_access_point<'a>(
+    access_points: &'a [AccessPointInfo],
+    ssid: &[u8],
+    bssid: Option<MacAddr>,
+) -> Option<(&'a AccessPointInfo, MacAddr)> {
+    access_points
+        .iter()
+        .filter(|ap| ap.ssid == ssid)
+        .filter_map(|ap| Some((ap, ap.bssid()?)))
+        .filter(|(_, found)| bssid.is_none_or(|bssid| bssid == *found))
+        .max_by_key(|(ap, _)| ap.strength)
+}
+
+/// Fails if `ssid` is weaker than `options.min_strength` in `access_points`, or missing
+/// from them without being hidden.
+pub(crate) fn check_strength(
+    options: &ConnectOptions,
+    access_points: &[AccessPointInfo],
+    ssid: &[u8],
+) -> Result<(), WifiConfigError> {
+    let Some(required) = options.min_strength else {
+        return Ok(());
+    };
+    match best_strength(access_points, ssid) {
+        Some(found) if found < required => Err(WifiConfigError::SignalTooWeak { found, required }),
+        Some(_) => Ok(()),
+        None if options.hidden => Ok(()),
+        None => Err(WifiConfigError::NetworkNotFound(
+            String::from_utf8_lossy(ssid).into_owned(),
+        )),
+    }
+}
+
+/// Resolves [`Security::Auto`] for `ssid` from the visible access points.
+///
+/// Uses the strongest AP serving `ssid` and returns the concrete scheme together with the
+/// detected AP security, which is `None` if the SSID wasn't visible and WPA-PSK was assumed.
+pub(crate) fn detect_security(
+    password: Option<&str>,
+    access_points: &[AccessPointInfo],
+    ssid: &[u8],
+) -> Result<(Security, Option<ApSecurity>), WifiConfigError> {
+    let password = || {
+        password
+            .map(Secret::from)
+            .ok_or(WifiConfigError::EmptyPassword)
+    };
+    let Some(ap) = access_points
+        .iter()
+        .filter(|ap| ap.ssid == ssid)
+        .max_by_key(|ap| ap.strength)
+    else {
+        return match password() {
+            Ok(password) => Ok((Security::WpaPsk(password), None)),
+            Err(_) => Err(WifiConfigError::NetworkNotFound(
+                String::from_utf8_lossy(ssid).into_owned(),
+            )),
+        };
+    };
+
+    let security = match ap.security {
+        ApSecurity::Open => Security::Open,
+        ApSecurity::Owe => Security::Owe,
+        ApSecurity::Wep => Security::Wep(password()?),
+        ApSecurity::WpaPsk => Security::WpaPsk(password()?),
+        // Transition-mode APs advertise both; PSK clients must still be able to join.
+        ApSecurity::Sae if (ap.wpa_flags | ap.rsn_flags) & AP_SEC_KEY_MGMT_PSK != 0 => {
+            Security::Wpa3Transition(password()?)
+        }
+        ApSecurity::Sae => Security::Wpa3Sae(password()?),
+        ApSecurity::WpaEap => {
+            return Err(WifiConfigError::InvalidEnterpriseAuth(
+                "the network uses 802.1X; pass Security::Enterprise credentials".into(),
+            ))
+        }
+    };
+    Ok((security, Some(ap.security)))
+}
+
+/// Returns `options` with [`Security::Auto`] replaced by the scheme detected for `ssid`,
+/// plus the detected AP security; see [`detect_security`].
+///
+/// The detected scheme is validated, so e.g. a too short WPA2 passphrase is still caught
+/// before any settings are sent.
+pub(crate) fn resolve_auto_security(
+    options: &ConnectOptions,
+    access_points: &[AccessPointInfo],
+    ssid: &[u8],
+) -> Result<(ConnectOptions, Option<ApSecurity>), WifiConfigError> {
+    let password = match &options.security {
+        Security::Auto(password) => password.as_deref(),
+        _ => return Ok((options.clone(), None)),
+    };
+    let (security, detected) = detect_security(password, access_points, ssid)?;
+    security.validate()?;
+    Ok((
+        ConnectOptions {
+            security,
+            ..options.clone()
+        },
+        detected,
+    ))
+}
+
+/// Lists the access points visible to the device at `device_path`.
+pub(crate) fn access_points(
+    client: &dyn NmClient,
+    device_path: &dbus::Path,
+) -> Result<Vec<AccessPointInfo>, WifiConfigError> {
+    client
+        .access_points(device_path)?
+        .into_iter()
+        .map(|ap_path| {
+            let props = client.access_point_properties(&ap_path)?;
+            Ok(AccessPointInfo::from_properties(ap_path, &props))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dbus::arg::Variant;
+
+    #[test]
+    fn converts_dbm_to_percent() {
+        assert_eq!(dbm_to_percent(-110), 0);
+        assert_eq!(dbm_to_percent(-75), 50);
+        assert_eq!(dbm_to_percent(-40), 100);
+    }
+
+    #[test]
+    fn decodes_security_flags() {
+        assert_eq!(ApSecurity::from_flags(0, 0, 0), ApSecurity::Open);
+        assert_eq!(
+            ApSecurity::from_flags(AP_FLAGS_PRIVACY, 0, 0),
+            ApSecurity::Wep
+        );
+        assert_eq!(
+            ApSecurity::from_flags(AP_FLAGS_PRIVACY, 0x188, 0x188),
+            ApSecurity::WpaPsk
+        );
+        assert_eq!(
+            ApSecurity::from_flags(AP_FLAGS_PRIVACY, 0, 0x288),
+            ApSecurity::WpaEap
+        );
+        // WPA2/WPA3 transition mode advertises both PSK and SAE.
+        assert_eq!(
+            ApSecurity::from_flags(AP_FLAGS_PRIVACY, 0, 0x588),
+            ApSecurity::Sae
+        );
+        assert_eq!(
+            ApSecurity::from_flags(AP_FLAGS_PRIVACY, 0, 0x888),
+            ApSecurity::Owe
+        );
+        // The open half of an OWE transition pair advertises OWE_TM without privacy.
+        assert_eq!(ApSecurity::from_flags(0, 0, 0x1000), ApSecurity::Owe);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serializes_for_json_output() {
+        let mut props = PropMap::new();
+        props.insert("Ssid".into(), Variant(Box::new(b"MySsid".to_vec())));
+        props.insert("RsnFlags".into(), Variant(Box::new(0x188u32)));
+        props.insert(
+            "HwAddress".into(),
+            Variant(Box::new(String::from("AA:BB:CC:DD:EE:FF"))),
+        );
+        let path = dbus::Path::new("/org/freedesktop/NetworkManager/AccessPoint/7").unwrap();
+        let ap = AccessPointInfo::from_properties(path, &props);
+
+        let json = serde_json::to_value(&ap).unwrap();
+        assert_eq!(
+            json["path"],
+            "/org/freedesktop/NetworkManager/AccessPoint/7"
+        );
+        assert_eq!(json["ssid_lossy"], "MySsid");
+        assert_eq!(json["hw_address"], "AA:BB:CC:DD:EE:FF");
+        assert_eq!(json["security"], "wpa_psk");
+    }
+
+    #[test]
+    fn picks_strongest_matching_bssid() {
+        let ap = |ssid: &[u8], strength: u8| {
+            let mut props = PropMap::new();
+            props.insert("Ssid".into(), Variant(Box::new(ssid.to_vec())));
+            props.insert("Strength".into(), Variant(Box::new(strength)));
+            AccessPointInfo::from_properties(dbus::Path::new("/").unwrap(), &props)
+        };
+        let aps = [ap(b"Home", 40), ap(b"Other", 90), ap(b"Home", 72)];
+
+        assert_eq!(best_strength(&aps, b"Home"), Some(72));
+        assert_eq!(best_strength(&aps, b"Cafe"), None);
+
+        let gated = |min_strength: u8, hidden: bool| ConnectOptions {
+            min_strength: Some(min_strength),
+            hidden,
+            ..Default::default()
+        };
+        assert!(check_strength(&gated(72, false), &aps, b"Home").is_ok());
+        assert!(matches!(
+            check_strength(&gated(80, false), &aps, b"Home"),
+            Err(WifiConfigError::SignalTooWeak {
+                found: 72,
+                required: 80
+            })
+        ));
+        assert!(matches!(
+            check_strength(&gated(40, false), &aps, b"Cafe"),
+            Err(WifiConfigError::NetworkNotFound(_))
+        ));
+        assert!(check_strength(&gated(40, true), &aps, b"Cafe").is_ok());
+        assert!(check_strength(&gated(100, true), &aps, b"Home").is_err());
+    }
+
+    #[test]
+    fn detects_security_from_flags() {
+        let ap = |flags: u32, rsn_flags: u32| {
+            let mut props = PropMap::new();
+            props.insert("Ssid".into(), Variant(Box::new(b"Net".to_vec())));
+            props.insert("Flags".into(), Variant(Box::new(flags)));
+            props.insert("RsnFlags".into(), Variant(Box::new(rsn_flags)));
+            AccessPointInfo::from_properties(dbus::Path::new("/").unwrap(), &props)
+        };
+        let detect =
+            |ap: AccessPointInfo, password: Option<&str>| detect_security(password, &[ap], b"Net");
+
+        assert_eq!(
+            detect(ap(0, 0), None).unwrap(),
+            (Security::Open, Some(ApSecurity::Open))
+        );
+        assert_eq!(
+            detect(ap(AP_FLAGS_PRIVACY, 0x188), Some("password")).unwrap(),
+            (
+                Security::WpaPsk("password".into()),
+                Some(ApSecurity::WpaPsk)
+            )
+        );
+        assert_eq!(
+            detect(ap(AP_FLAGS_PRIVACY, 0x488), Some("password"))
+                .unwrap()
+                .0,
+            Security::Wpa3Sae("password".into())
+        );
+        assert_eq!(
+            detect(ap(AP_FLAGS_PRIVACY, 0x588), Some("password"))
+                .unwrap()
+                .0,
+            Security::Wpa3Transition("password".into())
+        );
+        assert_eq!(
+            detect(ap(AP_FLAGS_PRIVACY, 0x888), None).unwrap().0,
+            Security::Owe
+        );
+        assert!(matches!(
+            detect(ap(AP_FLAGS_PRIVACY, 0x188), None),
+            Err(WifiConfigError::EmptyPassword)
+        ));
+        assert!(matches!(
+            detect(ap(AP_FLAGS_PRIVACY, 0x288), Some("password")),
+            Err(WifiConfigError::InvalidEnterpriseAuth(_))
+        ));
+    }
+
+    #[test]
+    fn falls_back_to_psk_when_not_visible() {
+        assert_eq!(
+            detect_security(Some("password"), &[], b"Hidden").unwrap(),
+            (Security::WpaPsk("password".into()), None)
+        );
+        assert!(matches!(
+            detect_security(None, &[], b"Hidden"),
+            Err(WifiConfigError::NetworkNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn detects_stale_scans() {
+        let max_age = Duration::from_secs(30);
+        assert!(!scan_is_stale(100_000, Some(110_000), max_age));
+        assert!(scan_is_stale(100_000, Some(140_000), max_age));
+        assert!(scan_is_stale(-1, Some(140_000), max_age));
+        assert!(scan_is_stale(100_000, None, max_age));
+    }
+}
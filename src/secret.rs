@@ -0,0 +1,146 @@
+//! Passwords that are wiped from memory when dropped and never show up in `Debug` output.
+
+use dbus::arg::PropMap;
+use std::fmt;
+use std::ops::Deref;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::dry_run::MASKED_SECRET;
+use crate::settings::ConnectionSettings;
+
+/// A password, passphrase or key, as held by [`Security`](crate::Security) and
+/// [`EnterpriseAuth`](crate::EnterpriseAuth).
+///
+/// Its buffer is overwritten with zeroes by [`zeroize`] when it is dropped, and it prints as
+/// `Secret(<hidden>)`, so options can be logged with `{:?}`. Created from a `String`
+/// (taking over its buffer) or a `&str`, and read through [`expose`](Self::expose) or
+/// as a `&str`.
+///
+/// This only covers the copies this crate holds: the D-Bus library copies the secret
+/// into the message it sends, and a `String` it was built from may have left earlier
+/// copies behind when it grew.
+///
+/// # Example
+///
+/// ```
+/// use wifi_config::Secret;
+///
+/// let psk = Secret::from("supersecret123");
+/// assert_eq!(psk.expose(), "supersecret123");
+/// assert_eq!(format!("{:?}", psk), "Secret(<hidden>)");
+/// ```
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret(Zeroizing<String>);
+
+impl Secret {
+    /// The secret in the clear.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for Secret {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Secret(Zeroizing::new(value))
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Secret::from(value.to_string())
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret({})", MASKED_SECRET)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Secret {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Secret {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Secret::from)
+    }
+}
+
+/// Wipes the secrets in `settings` (see [`SECRETS`](crate::security::SECRETS)) once
+/// they have been sent or are no longer needed.
+pub(crate) fn wipe_settings(settings: &mut ConnectionSettings) {
+    for (section, key, _) in crate::security::SECRETS {
+        if let Some(section) = settings.get_mut(*section) {
+            wipe_properties(section, &[key]);
+        }
+    }
+}
+
+/// Wipes the string or byte array values of `keys` in `properties`, zeroing their whole
+/// buffers and leaving them empty.
+pub(crate) fn wipe_properties(properties: &mut PropMap, keys: &[&str]) {
+    for key in keys {
+        let Some(value) = properties.get_mut(*key) else {
+            continue;
+        };
+        let value = value.0.as_any_mut();
+        if let Some(value) = value.downcast_mut::<String>() {
+            value.zeroize();
+        } else if let Some(value) = value.downcast_mut::<Vec<u8>>() {
+            value.zeroize();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::build_connection_settings;
+    use crate::{ConnectOptions, Security};
+    use dbus::arg::{prop_cast, Variant};
+
+    #[test]
+    fn debug_output_hides_the_secret() {
+        let options = ConnectOptions {
+            security: Security::WpaPsk("supersecret123".into()),
+            ..Default::default()
+        };
+        let debug = format!("{:?}", options);
+        assert!(debug.contains("WpaPsk(Secret(<hidden>))"));
+        assert!(!debug.contains("supersecret123"));
+    }
+
+    #[test]
+    fn wipes_the_secrets_in_settings() {
+        let options = ConnectOptions {
+            security: Security::WpaPsk("supersecret123".into()),
+            ..Default::default()
+        };
+        let mut settings = build_connection_settings(b"Office", &options);
+        wipe_settings(&mut settings);
+        let section = &settings["802-11-wireless-security"];
+        assert_eq!(prop_cast::<String>(section, "psk").unwrap(), "");
+        assert_eq!(prop_cast::<String>(section, "key-mgmt").unwrap(), "wpa-psk");
+
+        let mut properties = PropMap::new();
+        properties.insert("psk".into(), Variant(Box::new(b"supersecret123".to_vec())));
+        wipe_properties(&mut properties, &["psk"]);
+        assert_eq!(
+            prop_cast::<Vec<u8>>(&properties, "psk").unwrap(),
+            &Vec::<u8>::new()
+        );
+    }
+}
@@ -0,0 +1,823 @@
+use dbus::arg::{PropMap, Variant};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use crate::settings::ConnectionSettings;
+use crate::{Secret, WifiConfigError};
+
+/// `802-11-wireless-security.pmf` value letting the supplicant decide (optional PMF).
+const PMF_OPTIONAL: i32 = 2;
+/// `802-11-wireless-security.wep-key-type` value for a literal (ASCII or hex) key.
+const WEP_KEY_TYPE_KEY: u32 = 1;
+
+/// Secrets this crate sends, as `(section, key, flags key)`.
+pub(crate) const SECRETS: &[(&str, &str, &str)] = &[
+    ("802-11-wireless-security", "psk", "psk-flags"),
+    ("802-11-wireless-security", "wep-key0", "wep-key-flags"),
+    ("802-1x", "password", "password-flags"),
+    (
+        "802-1x",
+        "private-key-password",
+        "private-key-password-flags",
+    ),
+];
+
+/// Where NetworkManager keeps the network's secrets (`psk-flags` and the other
+/// `*-flags` settings).
+///
+/// The secrets are sent along with the settings either way, so the first activation
+/// doesn't depend on this. With [`AgentOwned`](Self::AgentOwned) or
+/// [`NotSaved`](Self::NotSaved), though, NetworkManager asks a secret agent of the
+/// logged-in user (e.g. GNOME Shell, or `nmcli --ask`) for them on every later
+/// activation, including autoconnect and reconnects after a drop. Without an agent,
+/// those fail with
+/// [`DeviceStateReason::NoSecrets`](crate::DeviceStateReason::NoSecrets).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum SecretStorage {
+    /// The profile stores the secrets, readable only by root (`0`).
+    #[default]
+    System,
+    /// The user's secret agent stores and hands out the secrets (`1`).
+    AgentOwned,
+    /// Nobody stores the secrets (`2`); nothing is written to
+    /// `/etc/NetworkManager/system-connections`.
+    NotSaved,
+}
+
+impl SecretStorage {
+    /// The `NMSettingSecretFlags` value sent to NetworkManager.
+    pub(crate) fn to_nm(self) -> u32 {
+        match self {
+            SecretStorage::System => 0,
+            SecretStorage::AgentOwned => 1,
+            SecretStorage::NotSaved => 2,
+        }
+    }
+
+    /// Sets the flags of every secret in `settings`.
+    pub(crate) fn apply(self, settings: &mut ConnectionSettings) {
+        if self == SecretStorage::System {
+            return;
+        }
+        for (section, key, flags_key) in SECRETS {
+            if let Some(section) = settings.get_mut(*section) {
+                if section.contains_key(*key) {
+                    section.insert(flags_key.to_string(), Variant(Box::new(self.to_nm())));
+                }
+            }
+        }
+    }
+}
+
+/// Security scheme used when connecting to a network.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Security {
+    /// Open network; no `802-11-wireless-security` section is sent.
+    #[default]
+    Open,
+    /// WPA/WPA2 personal (`key-mgmt: wpa-psk`) with the given passphrase.
+    WpaPsk(#[cfg_attr(feature = "serde", serde(deserialize_with = "crate::json::secret"))] Secret),
+    /// WPA3 personal (`key-mgmt: sae`) with the given password.
+    Wpa3Sae(#[cfg_attr(feature = "serde", serde(deserialize_with = "crate::json::secret"))] Secret),
+    /// WPA2/WPA3 transition mode for mixed-mode APs.
+    ///
+    /// Sent as `key-mgmt: wpa-psk` with optional protected management frames, which lets
+    /// NetworkManager's supplicant negotiate SAE when the AP offers it and fall back to PSK
+    /// otherwise.
+    Wpa3Transition(
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "crate::json::secret"))] Secret,
+    ),
+    /// WPA/WPA2 enterprise (`key-mgmt: wpa-eap`) with an `802-1x` section.
+    Enterprise(EnterpriseAuth),
+    /// Opportunistic Wireless Encryption, a.k.a. Enhanced Open (`key-mgmt: owe`).
+    ///
+    /// Encrypts traffic on networks without a password, as found in cafés and airports.
+    /// There are no credentials to give.
+    Owe,
+    /// Detect the scheme from the access point's advertised flags, using the given
+    /// password if the network needs one.
+    ///
+    /// Before connecting, the target SSID is looked up in NetworkManager's cached scan
+    /// results. Open, OWE, WEP, WPA-PSK, WPA3-SAE and WPA2/WPA3 transition networks are
+    /// recognized; 802.1X networks need [`Security::Enterprise`]. If the SSID isn't visible
+    /// (e.g. a hidden network), WPA-PSK is assumed when a password was given.
+    ///
+    /// The detected type is reported in
+    /// [`ActivationHandle::detected_security`](crate::ActivationHandle::detected_security).
+    Auto(
+        #[cfg_attr(
+            feature = "serde",
+            serde(deserialize_with = "crate::json::optional_secret")
+        )]
+        Option<Secret>,
+    ),
+    /// Legacy WEP (`key-mgmt: none`) with the given key.
+    ///
+    /// The key format is detected from its length: 5 or 13 characters are an ASCII key,
+    /// 10 or 26 characters are a hex key. Any other length is rejected.
+    Wep(#[cfg_attr(feature = "serde", serde(deserialize_with = "crate::json::secret"))] Secret),
+}
+
+/// 802.1X authentication method for [`Security::Enterprise`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum EnterpriseAuth {
+    /// PEAP with MSCHAPv2 inner authentication.
+    Peap {
+        /// Identity (username) sent inside the tunnel.
+        identity: String,
+        /// Password for MSCHAPv2.
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "crate::json::secret"))]
+        password: Secret,
+        /// Optional outer identity sent in the clear before the tunnel is established.
+        anonymous_identity: Option<String>,
+    },
+    /// EAP-TLS with a client certificate and private key.
+    Tls {
+        /// Identity presented to the authentication server.
+        identity: String,
+        /// How the server certificate is verified.
+        ca_cert: CaCertificate,
+        /// Path to the client certificate (PEM or DER).
+        client_cert: PathBuf,
+        /// Path to the client private key.
+        private_key: PathBuf,
+        /// Password protecting the private key, if it is encrypted.
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, deserialize_with = "crate::json::optional_secret")
+        )]
+        private_key_password: Option<Secret>,
+    },
+}
+
+/// Server certificate verification for [`EnterpriseAuth::Tls`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum CaCertificate {
+    /// Verify the server against the CA certificate at this path.
+    Path(PathBuf),
+    /// **Insecure:** do not verify the authentication server at all.
+    ///
+    /// Anyone able to impersonate the network can then capture the client's identity.
+    /// Only use this for testing; it must be chosen explicitly.
+    DangerouslySkipVerification,
+}
+
+impl EnterpriseAuth {
+    fn validate(&self) -> Result<(), WifiConfigError> {
+        match self {
+            EnterpriseAuth::Peap {
+                identity, password, ..
+            } => {
+                if identity.is_empty() {
+                    return Err(WifiConfigError::InvalidEnterpriseAuth(
+                        "identity must not be empty".into(),
+                    ));
+                }
+                if password.is_empty() {
+                    return Err(WifiConfigError::EmptyPassword);
+                }
+                Ok(())
+            }
+            EnterpriseAuth::Tls {
+                identity,
+                ca_cert,
+                client_cert,
+                private_key,
+                ..
+            } => {
+                if identity.is_empty() {
+                    return Err(WifiConfigError::InvalidEnterpriseAuth(
+                        "identity must not be empty".into(),
+                    ));
+                }
+                if let CaCertificate::Path(ca_cert) = ca_cert {
+                    check_file_exists(ca_cert)?;
+                }
+                check_file_exists(client_cert)?;
+                check_file_exists(private_key)
+            }
+        }
+    }
+
+    /// Builds the `802-1x` section.
+    fn settings_section(&self) -> PropMap {
+        let mut section = PropMap::new();
+        match self {
+            EnterpriseAuth::Peap {
+                identity,
+                password,
+                anonymous_identity,
+            } => {
+                section.insert("eap".into(), Variant(Box::new(vec![String::from("peap")])));
+                section.insert(
+                    "phase2-auth".into(),
+                    Variant(Box::new(String::from("mschapv2"))),
+                );
+                section.insert("identity".into(), Variant(Box::new(identity.clone())));
+                section.insert("password".into(), Variant(Box::new(password.to_string())));
+                if let Some(anonymous_identity) = anonymous_identity {
+                    section.insert(
+                        "anonymous-identity".into(),
+                        Variant(Box::new(anonymous_identity.clone())),
+                    );
+                }
+            }
+            EnterpriseAuth::Tls {
+                identity,
+                ca_cert,
+                client_cert,
+                private_key,
+                private_key_password,
+            } => {
+                section.insert("eap".into(), Variant(Box::new(vec![String::from("tls")])));
+                section.insert("identity".into(), Variant(Box::new(identity.clone())));
+                if let CaCertificate::Path(ca_cert) = ca_cert {
+                    section.insert("ca-cert".into(), Variant(Box::new(cert_path_blob(ca_cert))));
+                }
+                section.insert(
+                    "client-cert".into(),
+                    Variant(Box::new(cert_path_blob(client_cert))),
+                );
+                section.insert(
+                    "private-key".into(),
+                    Variant(Box::new(cert_path_blob(private_key))),
+                );
+                if let Some(private_key_password) = private_key_password {
+                    section.insert(
+                        "private-key-password".into(),
+                        Variant(Box::new(private_key_password.to_string())),
+                    );
+                }
+            }
+        }
+        section
+    }
+}
+
+/// Accepts 8 to 63 printable ASCII characters, or a raw 64-digit hex PSK.
+///
+/// NetworkManager tells the two apart by length, so a raw key is sent unchanged as `psk`.
+fn validate_passphrase(password: &str) -> Result<(), WifiConfigError> {
+    match password.len() {
+        0 => Err(WifiConfigError::EmptyPassword),
+        64 if password.chars().all(|c| c.is_ascii_hexdigit()) => Ok(()),
+        64 => Err(WifiConfigError::InvalidPsk {
+            reason: "64-character keys must be hex digits".into(),
+        }),
+        8..=63
+            if password
+                .chars()
+                .all(|c| c.is_ascii() && !c.is_ascii_control()) =>
+        {
+            Ok(())
+        }
+        8..=63 => Err(WifiConfigError::InvalidPsk {
+            reason: "passphrases must only contain printable ASCII characters".into(),
+        }),
+        len => Err(WifiConfigError::InvalidPsk {
+            reason: format!(
+                "passphrase is {} characters long; expected 8 to 63, or 64 hex digits",
+                len
+            ),
+        }),
+    }
+}
+
+/// Accepts 5/13-character ASCII keys and 10/26-digit hex keys.
+fn validate_wep_key(key: &str) -> Result<(), WifiConfigError> {
+    match key.len() {
+        5 | 13 if key.chars().all(|c| c.is_ascii() && !c.is_ascii_control()) => Ok(()),
+        5 | 13 => Err(WifiConfigError::InvalidWepKey(
+            "ASCII keys must only contain printable ASCII characters".into(),
+        )),
+        10 | 26 if key.chars().all(|c| c.is_ascii_hexdigit()) => Ok(()),
+        10 | 26 => Err(WifiConfigError::InvalidWepKey(
+            "hex keys must only contain the digits 0-9 and a-f".into(),
+        )),
+        len => Err(WifiConfigError::InvalidWepKey(format!(
+            "key is {} characters long; expected 5 or 13 ASCII characters or 10 or 26 hex digits",
+            len
+        ))),
+    }
+}
+
+/// Fails with [`WifiConfigError::CertificateNotFound`] unless `path` is an existing file.
+fn check_file_exists(path: &Path) -> Result<(), WifiConfigError> {
+    if path.is_file() {
+        Ok(())
+    } else {
+        Err(WifiConfigError::CertificateNotFound(path.to_path_buf()))
+    }
+}
+
+/// Encodes a certificate or key path with NetworkManager's path scheme: the bytes of
+/// `file://<absolute path>` followed by a trailing NUL.
+fn cert_path_blob(path: &Path) -> Vec<u8> {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let mut blob = b"file://".to_vec();
+    blob.extend_from_slice(path.as_os_str().as_bytes());
+    blob.push(0);
+    blob
+}
+
+impl Security {
+    /// Checks the credentials client-side, before anything is sent over D-Bus.
+    ///
+    /// The password rules depend on the scheme: SAE accepts passwords shorter than the
+    /// WPA2 passphrase minimum, so only the emptiness check is shared. Transition mode may
+    /// fall back to PSK and therefore follows the PSK rules.
+    pub(crate) fn validate(&self) -> Result<(), WifiConfigError> {
+        match self {
+            Security::Open | Security::Owe => Ok(()),
+            Security::WpaPsk(password) | Security::Wpa3Transition(password) => {
+                validate_passphrase(password)
+            }
+            Security::Wpa3Sae(password) => {
+                if password.is_empty() {
+                    Err(WifiConfigError::EmptyPassword)
+                } else {
+                    Ok(())
+                }
+            }
+            Security::Enterprise(auth) => auth.validate(),
+            Security::Wep(key) => validate_wep_key(key),
+            // The password is checked against the scheme once it has been detected.
+            Security::Auto(Some(password)) if password.is_empty() => {
+                Err(WifiConfigError::EmptyPassword)
+            }
+            Security::Auto(_) => Ok(()),
+        }
+    }
+
+    /// Adds the `802-11-wireless-security` section (and `802-1x` for enterprise) to
+    /// `settings`. Nothing is added for open networks.
+    ///
+    /// [`Security::Auto`] is normally resolved before; if not, it is applied like the
+    /// fallback for invisible networks: WPA-PSK with a password, open without.
+    pub(crate) fn apply(&self, settings: &mut ConnectionSettings) {
+        let mut section = PropMap::new();
+        match self {
+            Security::Open | Security::Auto(None) => return,
+            Security::Auto(Some(password)) => {
+                return Security::WpaPsk(password.clone()).apply(settings);
+            }
+            Security::WpaPsk(password) => {
+                section.insert(
+                    "key-mgmt".into(),
+                    Variant(Box::new(String::from("wpa-psk"))),
+                );
+                section.insert("psk".into(), Variant(Box::new(password.to_string())));
+            }
+            Security::Wpa3Sae(password) => {
+                section.insert("key-mgmt".into(), Variant(Box::new(String::from("sae"))));
+                section.insert("psk".into(), Variant(Box::new(password.to_string())));
+            }
+            Security::Wpa3Transition(password) => {
+                section.insert(
+                    "key-mgmt".into(),
+                    Variant(Box::new(String::from("wpa-psk"))),
+                );
+                section.insert("psk".into(), Variant(Box::new(password.to_string())));
+                section.insert("pmf".into(), Variant(Box::new(PMF_OPTIONAL)));
+            }
+            Security::Enterprise(auth) => {
+                section.insert(
+                    "key-mgmt".into(),
+                    Variant(Box::new(String::from("wpa-eap"))),
+                );
+                settings.insert("802-1x".into(), auth.settings_section());
+            }
+            Security::Owe => {
+                section.insert("key-mgmt".into(), Variant(Box::new(String::from("owe"))));
+            }
+            Security::Wep(key) => {
+                section.insert("key-mgmt".into(), Variant(Box::new(String::from("none"))));
+                section.insert("wep-key0".into(), Variant(Box::new(key.to_string())));
+                section.insert("wep-key-type".into(), Variant(Box::new(WEP_KEY_TYPE_KEY)));
+                section.insert("wep-tx-keyidx".into(), Variant(Box::new(0u32)));
+            }
+        }
+        settings.insert("802-11-wireless-security".into(), section);
+    }
+}
+
+/// WPA protocol version (`802-11-wireless-security.proto`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum WpaProto {
+    /// The original WPA (`wpa`).
+    Wpa,
+    /// WPA2 and later (`rsn`).
+    Rsn,
+}
+
+impl WpaProto {
+    fn as_nm(self) -> &'static str {
+        match self {
+            WpaProto::Wpa => "wpa",
+            WpaProto::Rsn => "rsn",
+        }
+    }
+}
+
+/// Encryption cipher (`802-11-wireless-security.pairwise` and `group`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Cipher {
+    /// TKIP, the RC4-based cipher of the original WPA (`tkip`).
+    Tkip,
+    /// CCMP, i.e. AES (`ccmp`).
+    Ccmp,
+}
+
+impl Cipher {
+    fn as_nm(self) -> &'static str {
+        match self {
+            Cipher::Tkip => "tkip",
+            Cipher::Ccmp => "ccmp",
+        }
+    }
+}
+
+/// Which WPA versions and ciphers a connection may negotiate, for
+/// [`ConnectOptions::cipher_policy`](crate::ConnectOptions::cipher_policy).
+///
+/// Without one, NetworkManager takes whatever the access point offers, TKIP and WPA1
+/// included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum CipherPolicy {
+    /// WPA2 (RSN) with CCMP for unicast and group traffic; access points that only
+    /// offer WPA1 or TKIP are refused.
+    Wpa2Only,
+    /// Exactly these protocols and ciphers; an empty list leaves that key to
+    /// NetworkManager.
+    Custom {
+        /// Allowed protocol versions (`proto`).
+        proto: Vec<WpaProto>,
+        /// Allowed unicast ciphers (`pairwise`).
+        pairwise: Vec<Cipher>,
+        /// Allowed group (broadcast) ciphers (`group`).
+        group: Vec<Cipher>,
+    },
+}
+
+impl CipherPolicy {
+    /// The `proto`, `pairwise` and `group` lists of the policy.
+    fn lists(&self) -> (&[WpaProto], &[Cipher], &[Cipher]) {
+        match self {
+            CipherPolicy::Wpa2Only => (&[WpaProto::Rsn], &[Cipher::Ccmp], &[Cipher::Ccmp]),
+            CipherPolicy::Custom {
+                proto,
+                pairwise,
+                group,
+            } => (proto, pairwise, group),
+        }
+    }
+
+    /// Checks that the policy can apply to `security`: it needs a WPA scheme, and WPA3
+    /// needs RSN and CCMP.
+    pub(crate) fn validate(&self, security: &Security) -> Result<(), WifiConfigError> {
+        let (proto, pairwise, group) = self.lists();
+        let incoherent = |reason: &str| Err(WifiConfigError::InvalidWirelessConfig(reason.into()));
+        match security {
+            Security::WpaPsk(_) | Security::Enterprise(_) => Ok(()),
+            Security::Wpa3Sae(_) => {
+                if proto.contains(&WpaProto::Wpa) {
+                    incoherent("WPA3 networks only use RSN, not WPA")
+                } else if pairwise.contains(&Cipher::Tkip) || group.contains(&Cipher::Tkip) {
+                    incoherent("WPA3 networks don't allow TKIP")
+                } else {
+                    Ok(())
+                }
+            }
+            // The WPA3 side of a transition network needs RSN and CCMP.
+            Security::Wpa3Transition(_) => {
+                if !proto.is_empty() && !proto.contains(&WpaProto::Rsn) {
+                    incoherent("WPA2/WPA3 transition mode needs RSN")
+                } else if !pairwise.is_empty() && !pairwise.contains(&Cipher::Ccmp) {
+                    incoherent("WPA2/WPA3 transition mode needs CCMP")
+                } else {
+                    Ok(())
+                }
+            }
+            Security::Open | Security::Owe | Security::Wep(_) | Security::Auto(_) => {
+                incoherent("cipher policies need WPA-PSK, WPA3 or enterprise security")
+            }
+        }
+    }
+
+    /// Writes the policy into the `802-11-wireless-security` section of `settings`,
+    /// as the string arrays NetworkManager expects.
+    pub(crate) fn apply(&self, settings: &mut ConnectionSettings) {
+        let Some(section) = settings.get_mut("802-11-wireless-security") else {
+            return;
+        };
+        let (proto, pairwise, group) = self.lists();
+        let proto: Vec<String> = proto.iter().map(|p| p.as_nm().to_string()).collect();
+        let ciphers = |ciphers: &[Cipher]| -> Vec<String> {
+            ciphers.iter().map(|c| c.as_nm().to_string()).collect()
+        };
+        for (key, values) in [
+            ("proto", proto),
+            ("pairwise", ciphers(pairwise)),
+            ("group", ciphers(group)),
+        ] {
+            // A wrongly typed or empty array would be ignored without a word.
+            if !values.is_empty() {
+                section.insert(key.into(), Variant(Box::new(values)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn applied(security: &Security) -> ConnectionSettings {
+        let mut settings = ConnectionSettings::new();
+        security.apply(&mut settings);
+        settings
+    }
+
+    #[test]
+    fn sae_uses_sae_key_mgmt() {
+        let settings = applied(&Security::Wpa3Sae("pass".into()));
+        let section = &settings["802-11-wireless-security"];
+        assert_eq!(section["key-mgmt"].0.as_str(), Some("sae"));
+        assert_eq!(section["psk"].0.as_str(), Some("pass"));
+    }
+
+    #[test]
+    fn transition_mode_allows_optional_pmf() {
+        let settings = applied(&Security::Wpa3Transition("password".into()));
+        let section = &settings["802-11-wireless-security"];
+        assert_eq!(section["key-mgmt"].0.as_str(), Some("wpa-psk"));
+        assert_eq!(section["pmf"].0.as_i64(), Some(PMF_OPTIONAL as i64));
+    }
+
+    #[test]
+    fn peap_builds_802_1x_section() {
+        let settings = applied(&Security::Enterprise(EnterpriseAuth::Peap {
+            identity: "alice".into(),
+            password: "hunter2".into(),
+            anonymous_identity: None,
+        }));
+        assert_eq!(
+            settings["802-11-wireless-security"]["key-mgmt"].0.as_str(),
+            Some("wpa-eap")
+        );
+        let eap = &settings["802-1x"];
+        let methods: Vec<&str> = eap["eap"]
+            .0
+            .as_iter()
+            .unwrap()
+            .map(|m| m.as_str().unwrap())
+            .collect();
+        assert_eq!(methods, ["peap"]);
+        assert_eq!(eap["phase2-auth"].0.as_str(), Some("mschapv2"));
+        assert_eq!(eap["identity"].0.as_str(), Some("alice"));
+        assert!(!eap.contains_key("anonymous-identity"));
+    }
+
+    #[test]
+    fn tls_encodes_cert_paths() {
+        let dir = std::env::temp_dir();
+        let cert = dir.join("wifi-config-test-client.pem");
+        std::fs::write(&cert, "cert").unwrap();
+        let cert = cert.canonicalize().unwrap();
+
+        let security = Security::Enterprise(EnterpriseAuth::Tls {
+            identity: "device-42".into(),
+            ca_cert: CaCertificate::DangerouslySkipVerification,
+            client_cert: cert.clone(),
+            private_key: cert.clone(),
+            private_key_password: Some("keypass".into()),
+        });
+        assert!(security.validate().is_ok());
+
+        let settings = applied(&security);
+        let eap = &settings["802-1x"];
+        let blob: Vec<u8> = eap["client-cert"]
+            .0
+            .as_iter()
+            .unwrap()
+            .map(|b| b.as_u64().unwrap() as u8)
+            .collect();
+        let mut expected = format!("file://{}", cert.display()).into_bytes();
+        expected.push(0);
+        assert_eq!(blob, expected);
+        assert!(!eap.contains_key("ca-cert"));
+        assert_eq!(eap["private-key-password"].0.as_str(), Some("keypass"));
+    }
+
+    #[test]
+    fn tls_rejects_missing_files() {
+        let missing = PathBuf::from("/nonexistent/wifi-config/client.pem");
+        let security = Security::Enterprise(EnterpriseAuth::Tls {
+            identity: "device-42".into(),
+            ca_cert: CaCertificate::Path(missing.clone()),
+            client_cert: missing.clone(),
+            private_key: missing.clone(),
+            private_key_password: None,
+        });
+        match security.validate() {
+            Err(WifiConfigError::CertificateNotFound(path)) => assert_eq!(path, missing),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn cipher_policies_send_string_arrays() {
+        let mut settings = applied(&Security::WpaPsk("supersecret123".into()));
+        CipherPolicy::Wpa2Only.apply(&mut settings);
+        let section = &settings["802-11-wireless-security"];
+        for key in ["proto", "pairwise", "group"] {
+            assert_eq!(section[key].0.signature().to_string(), "as");
+        }
+        let strings = |key: &str| {
+            section[key]
+                .0
+                .as_any()
+                .downcast_ref::<Vec<String>>()
+                .cloned()
+        };
+        assert_eq!(strings("proto"), Some(vec!["rsn".to_string()]));
+        assert_eq!(strings("pairwise"), Some(vec!["ccmp".to_string()]));
+        assert_eq!(strings("group"), Some(vec!["ccmp".to_string()]));
+
+        let mut settings = applied(&Security::WpaPsk("supersecret123".into()));
+        let pairwise_only = CipherPolicy::Custom {
+            proto: Vec::new(),
+            pairwise: vec![Cipher::Ccmp, Cipher::Tkip],
+            group: Vec::new(),
+        };
+        pairwise_only.apply(&mut settings);
+        let section = &settings["802-11-wireless-security"];
+        assert!(!section.contains_key("proto") && !section.contains_key("group"));
+        assert_eq!(
+            section["pairwise"].0.as_any().downcast_ref::<Vec<String>>(),
+            Some(&vec!["ccmp".to_string(), "tkip".to_string()])
+        );
+    }
+
+    #[test]
+    fn validates_cipher_policies_against_the_security() {
+        let wpa2_only = CipherPolicy::Wpa2Only;
+        assert!(wpa2_only.validate(&Security::WpaPsk("pass".into())).is_ok());
+        assert!(wpa2_only
+            .validate(&Security::Wpa3Sae("pass".into()))
+            .is_ok());
+        assert!(wpa2_only
+            .validate(&Security::Wpa3Transition("pass".into()))
+            .is_ok());
+        for security in [Security::Open, Security::Owe, Security::Auto(None)] {
+            assert!(matches!(
+                wpa2_only.validate(&security),
+                Err(WifiConfigError::InvalidWirelessConfig(_))
+            ));
+        }
+
+        let tkip = CipherPolicy::Custom {
+            proto: vec![WpaProto::Rsn],
+            pairwise: vec![Cipher::Ccmp],
+            group: vec![Cipher::Tkip],
+        };
+        assert!(tkip.validate(&Security::WpaPsk("pass".into())).is_ok());
+        assert!(tkip
+            .validate(&Security::Wpa3Transition("pass".into()))
+            .is_ok());
+        assert!(tkip.validate(&Security::Wpa3Sae("pass".into())).is_err());
+
+        let wpa1_only = CipherPolicy::Custom {
+            proto: vec![WpaProto::Wpa],
+            pairwise: Vec::new(),
+            group: Vec::new(),
+        };
+        assert!(wpa1_only
+            .validate(&Security::Wpa3Sae("pass".into()))
+            .is_err());
+        assert!(wpa1_only
+            .validate(&Security::Wpa3Transition("pass".into()))
+            .is_err());
+    }
+
+    #[test]
+    fn wep_sets_static_key() {
+        let settings = applied(&Security::Wep("0123456789".into()));
+        let section = &settings["802-11-wireless-security"];
+        assert_eq!(section["key-mgmt"].0.as_str(), Some("none"));
+        assert_eq!(section["wep-key0"].0.as_str(), Some("0123456789"));
+        assert_eq!(
+            section["wep-key-type"].0.as_u64(),
+            Some(WEP_KEY_TYPE_KEY as u64)
+        );
+    }
+
+    #[test]
+    fn validates_wep_key_lengths() {
+        for key in [
+            "abcde",
+            "abcdefghijklm",
+            "0123456789",
+            "0123456789abcdef0123456789",
+        ] {
+            assert!(Security::Wep(key.into()).validate().is_ok(), "{}", key);
+        }
+        for key in [
+            "",
+            "abcd",
+            "abcdef",
+            "012345678g",
+            "0123456789abcdef01234567zz",
+        ] {
+            assert!(
+                matches!(
+                    Security::Wep(key.into()).validate(),
+                    Err(WifiConfigError::InvalidWepKey(_))
+                ),
+                "{}",
+                key
+            );
+        }
+    }
+
+    #[test]
+    fn owe_has_no_credentials() {
+        assert!(Security::Owe.validate().is_ok());
+        let settings = applied(&Security::Owe);
+        let security = &settings["802-11-wireless-security"];
+        assert_eq!(security["key-mgmt"].0.as_str(), Some("owe"));
+        assert!(!security.contains_key("psk"));
+        assert!(!settings.contains_key("802-1x"));
+    }
+
+    #[test]
+    fn rejects_empty_password() {
+        assert!(matches!(
+            Security::WpaPsk(Secret::default()).validate(),
+            Err(WifiConfigError::EmptyPassword)
+        ));
+        assert!(Security::Open.validate().is_ok());
+    }
+
+    #[test]
+    fn short_sae_password_is_accepted() {
+        assert!(Security::Wpa3Sae("abc".into()).validate().is_ok());
+        assert!(matches!(
+            Security::Wpa3Sae(Secret::default()).validate(),
+            Err(WifiConfigError::EmptyPassword)
+        ));
+    }
+
+    #[test]
+    fn validates_passphrase_length() {
+        for password in ["12345678", &"x".repeat(63), &"a".repeat(64)] {
+            assert!(Security::WpaPsk(password.into()).validate().is_ok());
+        }
+        for password in ["1234567", &"x".repeat(64), &"x".repeat(65), "pässword"] {
+            assert!(
+                matches!(
+                    Security::WpaPsk(password.into()).validate(),
+                    Err(WifiConfigError::InvalidPsk { .. })
+                ),
+                "{}",
+                password
+            );
+        }
+        assert!(Security::Wpa3Transition("short".into()).validate().is_err());
+    }
+}
@@ -0,0 +1,512 @@
+use dbus::arg::{PropMap, RefArg, Variant};
+use std::collections::HashMap;
+
+use crate::ip;
+use crate::wireless;
+use crate::{ConnectOptions, DeviceSelection};
+
+/// Full connection settings as sent to `AddAndActivateConnection`, keyed by section
+/// name (e.g. `802-11-wireless`).
+pub(crate) type ConnectionSettings = HashMap<String, PropMap>;
+
+/// Sections written by [`build_connection_settings`]; updating a saved profile replaces
+/// exactly these and keeps the rest.
+///
+/// The `connection` section isn't listed: it carries the profile's UUID, so it is
+/// merged key by key instead.
+pub(crate) const MANAGED_SECTIONS: &[&str] = &[
+    "802-11-wireless",
+    "802-11-wireless-security",
+    "802-1x",
+    "ipv4",
+    "ipv6",
+];
+
+/// A raw value for [`ConnectOptions::extra_settings`], in the D-Bus type NetworkManager
+/// expects for the key; see the `nm-settings-dbus` manual for which one that is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ExtraSettingValue {
+    /// `s`, e.g. `connection.zone`.
+    String(String),
+    /// `u`, e.g. `802-11-wireless.mtu`.
+    U32(u32),
+    /// `i`, e.g. `802-11-wireless.ap-isolation`.
+    I32(i32),
+    /// `b`, e.g. `802-11-wireless.hidden`.
+    Bool(bool),
+    /// `ay`, e.g. `802-11-wireless.ssid`.
+    Bytes(Vec<u8>),
+    /// `as`, e.g. `802-11-wireless.seen-bssids`.
+    Strings(Vec<String>),
+    /// `aa{sv}`, e.g. `ipv4.address-data`.
+    Dicts(Vec<HashMap<String, ExtraSettingValue>>),
+}
+
+impl ExtraSettingValue {
+    fn to_ref_arg(&self) -> Box<dyn RefArg> {
+        match self {
+            ExtraSettingValue::String(value) => Box::new(value.clone()),
+            ExtraSettingValue::U32(value) => Box::new(*value),
+            ExtraSettingValue::I32(value) => Box::new(*value),
+            ExtraSettingValue::Bool(value) => Box::new(*value),
+            ExtraSettingValue::Bytes(value) => Box::new(value.clone()),
+            ExtraSettingValue::Strings(value) => Box::new(value.clone()),
+            ExtraSettingValue::Dicts(dicts) => Box::new(
+                dicts
+                    .iter()
+                    .map(|dict| {
+                        dict.iter()
+                            .map(|(key, value)| (key.clone(), Variant(value.to_ref_arg())))
+                            .collect::<PropMap>()
+                    })
+                    .collect::<Vec<PropMap>>(),
+            ),
+        }
+    }
+}
+
+impl From<&str> for ExtraSettingValue {
+    fn from(value: &str) -> Self {
+        ExtraSettingValue::String(value.into())
+    }
+}
+
+impl From<String> for ExtraSettingValue {
+    fn from(value: String) -> Self {
+        ExtraSettingValue::String(value)
+    }
+}
+
+impl From<u32> for ExtraSettingValue {
+    fn from(value: u32) -> Self {
+        ExtraSettingValue::U32(value)
+    }
+}
+
+impl From<i32> for ExtraSettingValue {
+    fn from(value: i32) -> Self {
+        ExtraSettingValue::I32(value)
+    }
+}
+
+impl From<bool> for ExtraSettingValue {
+    fn from(value: bool) -> Self {
+        ExtraSettingValue::Bool(value)
+    }
+}
+
+impl From<Vec<u8>> for ExtraSettingValue {
+    fn from(value: Vec<u8>) -> Self {
+        ExtraSettingValue::Bytes(value)
+    }
+}
+
+impl From<Vec<String>> for ExtraSettingValue {
+    fn from(value: Vec<String>) -> Self {
+        ExtraSettingValue::Strings(value)
+    }
+}
+
+/// The `section.key` names set in `settings`, sorted, for logging what is sent without
+/// the values (the PSK among them).
+pub(crate) fn setting_keys(settings: &ConnectionSettings) -> String {
+    let mut keys: Vec<String> = settings
+        .iter()
+        .flat_map(|(section, props)| props.keys().map(move |key| format!("{}.{}", section, key)))
+        .collect();
+    keys.sort();
+    keys.join(", ")
+}
+
+/// Returns the profile's `connection.uuid`, first generating a random (v4) one if there
+/// is none, so a new profile's UUID is known without reading it back.
+pub(crate) fn assign_uuid(settings: &mut ConnectionSettings) -> String {
+    let connection = settings.entry("connection".into()).or_default();
+    if let Some(uuid) = connection.get("uuid").and_then(|uuid| uuid.0.as_str()) {
+        return uuid.to_string();
+    }
+    let uuid = uuid::Uuid::new_v4().to_string();
+    connection.insert("uuid".into(), Variant(Box::new(uuid.clone())));
+    uuid
+}
+
+/// Builds the NetworkManager settings dictionary for a network in `options.mode`
+/// (infrastructure by default).
+///
+/// The `connection` section names the profile (after the SSID unless
+/// `options.connection_id` is set) and carries the UUID if one was given, as well as the
+/// autoconnect, metered, permission and firewall zone options.
+///
+/// The `802-11-wireless-security` section is derived from `options.security`; for
+/// [`Security::Open`](crate::Security::Open) it is omitted and the network is treated as open.
+///
+/// An `ipv4` section is only added for [`Ipv4Config::Manual`](crate::Ipv4Config::Manual);
+/// otherwise NetworkManager uses DHCP. The `ipv6` section likewise is only added when
+/// something other than NetworkManager's defaults (including custom DNS) is requested.
+pub(crate) fn build_connection_settings(
+    ssid: &[u8],
+    options: &ConnectOptions,
+) -> ConnectionSettings {
+    let mut connection_settings = ConnectionSettings::new();
+
+    // Profile settings
+    let mut profile_settings = PropMap::new();
+    let id = match &options.connection_id {
+        Some(id) => id.clone(),
+        None => String::from_utf8_lossy(ssid).into_owned(),
+    };
+    profile_settings.insert("id".into(), Variant(Box::new(id)));
+    if let Some(uuid) = options.profile_uuid(ssid) {
+        profile_settings.insert("uuid".into(), Variant(Box::new(uuid)));
+    }
+    profile_settings.insert(
+        "type".into(),
+        Variant(Box::new(String::from("802-11-wireless"))),
+    );
+    if let Some(autoconnect) = options.autoconnect {
+        profile_settings.insert("autoconnect".into(), Variant(Box::new(autoconnect)));
+    }
+    if let Some(priority) = options.autoconnect_priority {
+        profile_settings.insert("autoconnect-priority".into(), Variant(Box::new(priority)));
+    }
+    if let Some(metered) = options.metered {
+        profile_settings.insert("metered".into(), Variant(Box::new(metered.to_nm())));
+    }
+    if !options.permissions.is_empty() {
+        let permissions: Vec<String> = options
+            .permissions
+            .iter()
+            .map(|user| format!("user:{}:", user))
+            .collect();
+        profile_settings.insert("permissions".into(), Variant(Box::new(permissions)));
+    }
+    if let Some(zone) = &options.zone {
+        profile_settings.insert("zone".into(), Variant(Box::new(zone.clone())));
+    }
+    if options.device_selection == DeviceSelection::All {
+        // NM_SETTING_CONNECTION_MULTI_CONNECT_MULTIPLE: active on several devices at once.
+        profile_settings.insert("multi-connect".into(), Variant(Box::new(3i32)));
+    }
+    connection_settings.insert("connection".into(), profile_settings);
+
+    // Wi-Fi settings
+    let mut wifi_settings = PropMap::new();
+    wifi_settings.insert("ssid".into(), Variant(Box::new(ssid.to_vec())));
+    wifi_settings.insert(
+        "mode".into(),
+        Variant(Box::new(options.mode.as_nm().to_string())),
+    );
+    wireless::apply_band_channel(options.band, options.channel, &mut wifi_settings);
+    if let Some(bssid) = options.bssid {
+        wifi_settings.insert("bssid".into(), Variant(Box::new(bssid.0.to_vec())));
+    }
+    if let Some(mac_policy) = options.mac_policy {
+        mac_policy.apply(&mut wifi_settings);
+    }
+    if let Some(randomization) = options.scan_mac_randomization {
+        wifi_settings.insert(
+            "mac-address-randomization".into(),
+            Variant(Box::new(randomization.to_nm())),
+        );
+    }
+    if let Some(powersave) = options.powersave {
+        wifi_settings.insert("powersave".into(), Variant(Box::new(powersave.to_nm())));
+    }
+    if let Some(mtu) = options.mtu {
+        wifi_settings.insert("mtu".into(), Variant(Box::new(mtu)));
+    }
+    if options.hidden {
+        wifi_settings.insert("hidden".into(), Variant(Box::new(true)));
+    }
+    connection_settings.insert("802-11-wireless".into(), wifi_settings);
+
+    // Wi-Fi security settings
+    options.security.apply(&mut connection_settings);
+    if let Some(cipher_policy) = &options.cipher_policy {
+        cipher_policy.apply(&mut connection_settings);
+    }
+    options.secret_storage.apply(&mut connection_settings);
+
+    // IP settings
+    options.ipv4.apply(&mut connection_settings);
+    options
+        .ipv6
+        .apply(options.ipv6_privacy, &mut connection_settings);
+    ip::apply_dns(&options.dns, &options.dns_search, &mut connection_settings);
+    options.dhcp.apply(&options.ipv6, &mut connection_settings);
+
+    // The caller's raw settings go last, over anything generated above.
+    for (section, values) in &options.extra_settings {
+        let props = connection_settings.entry(section.clone()).or_default();
+        for (key, value) in values {
+            if props
+                .insert(key.clone(), Variant(value.to_ref_arg()))
+                .is_some()
+            {
+                log::warn!(
+                    "extra setting {}.{} replaces the value generated for it",
+                    section,
+                    key
+                );
+            }
+        }
+    }
+
+    connection_settings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Band, Ipv4Config, Metered, SecretStorage, Security, WirelessMode};
+    use dbus::arg::RefArg;
+
+    fn with_security(security: Security) -> ConnectOptions {
+        ConnectOptions {
+            security,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn builds_wpa_psk_settings() {
+        let settings = build_connection_settings(
+            b"MyHomeWiFi",
+            &with_security(Security::WpaPsk("supersecret123".into())),
+        );
+
+        let wifi = &settings["802-11-wireless"];
+        let ssid: Vec<u8> = wifi["ssid"]
+            .0
+            .as_iter()
+            .unwrap()
+            .map(|b| b.as_u64().unwrap() as u8)
+            .collect();
+        assert_eq!(ssid, b"MyHomeWiFi");
+        assert_eq!(wifi["mode"].0.as_str(), Some("infrastructure"));
+
+        let security = &settings["802-11-wireless-security"];
+        assert_eq!(security["key-mgmt"].0.as_str(), Some("wpa-psk"));
+        assert_eq!(security["psk"].0.as_str(), Some("supersecret123"));
+    }
+
+    #[test]
+    fn non_utf8_ssid_is_sent_as_is() {
+        // Latin-1 "Café".
+        let settings = build_connection_settings(b"Caf\xe9", &ConnectOptions::default());
+        let ssid: Vec<u8> = settings["802-11-wireless"]["ssid"]
+            .0
+            .as_iter()
+            .unwrap()
+            .map(|b| b.as_u64().unwrap() as u8)
+            .collect();
+        assert_eq!(ssid, b"Caf\xe9");
+        assert_eq!(settings["connection"]["id"].0.as_str(), Some("Caf\u{fffd}"));
+    }
+
+    #[test]
+    fn raw_hex_psk_is_sent_unchanged() {
+        let raw_key = "0123456789abcdefABCDEF0123456789abcdef0123456789abcdef0123456789";
+        let options = with_security(Security::WpaPsk(raw_key.into()));
+        assert!(options.validate(b"MyHomeWiFi").is_ok());
+
+        let settings = build_connection_settings(b"MyHomeWiFi", &options);
+        assert_eq!(
+            settings["802-11-wireless-security"]["psk"].0.as_str(),
+            Some(raw_key)
+        );
+    }
+
+    #[test]
+    fn connection_section_carries_autoconnect() {
+        let settings = build_connection_settings(b"Kiosk", &ConnectOptions::default());
+        let connection = &settings["connection"];
+        assert_eq!(connection["id"].0.as_str(), Some("Kiosk"));
+        assert_eq!(connection["type"].0.as_str(), Some("802-11-wireless"));
+        assert!(!connection.contains_key("autoconnect"));
+
+        let options = ConnectOptions {
+            autoconnect: Some(false),
+            autoconnect_priority: Some(10),
+            metered: Some(Metered::Yes),
+            ..Default::default()
+        };
+        let settings = build_connection_settings(b"Kiosk", &options);
+        let connection = &settings["connection"];
+        assert_eq!(connection["id"].0.as_str(), Some("Kiosk"));
+        assert_eq!(connection["autoconnect"].0.as_u64(), Some(0));
+        assert_eq!(connection["autoconnect-priority"].0.as_i64(), Some(10));
+        assert_eq!(connection["metered"].0.as_i64(), Some(1));
+    }
+
+    #[test]
+    fn adhoc_mode_sets_band_and_channel() {
+        let options = ConnectOptions {
+            mode: WirelessMode::Adhoc,
+            band: Some(Band::Bg),
+            channel: Some(11),
+            ..Default::default()
+        };
+        let settings = build_connection_settings(b"FieldMesh", &options);
+        let wifi = &settings["802-11-wireless"];
+        assert_eq!(wifi["mode"].0.as_str(), Some("adhoc"));
+        assert_eq!(wifi["band"].0.as_str(), Some("bg"));
+        assert_eq!(wifi["channel"].0.as_u64(), Some(11));
+    }
+
+    #[test]
+    fn open_network_has_no_security_section() {
+        let settings = build_connection_settings(b"Guest", &ConnectOptions::default());
+        assert!(settings.contains_key("802-11-wireless"));
+        assert!(!settings.contains_key("802-11-wireless-security"));
+    }
+
+    #[test]
+    fn manual_ipv4_adds_address_data() {
+        let options = ConnectOptions {
+            ipv4: Ipv4Config::Manual {
+                address: [192, 168, 1, 50].into(),
+                prefix: 24,
+                gateway: Some([192, 168, 1, 1].into()),
+            },
+            ..Default::default()
+        };
+        let settings = build_connection_settings(b"Office", &options);
+        let ipv4 = &settings["ipv4"];
+        assert_eq!(ipv4["method"].0.as_str(), Some("manual"));
+        assert_eq!(ipv4["gateway"].0.as_str(), Some("192.168.1.1"));
+
+        let address_data: Vec<_> = ipv4["address-data"].0.as_iter().unwrap().collect();
+        assert_eq!(address_data.len(), 1);
+        // A dict iterates as alternating keys and values.
+        let entries: Vec<_> = address_data[0].as_iter().unwrap().collect();
+        let address = entries
+            .iter()
+            .position(|e| e.as_str() == Some("address"))
+            .unwrap();
+        assert_eq!(entries[address + 1].as_str(), Some("192.168.1.50"));
+
+        let settings = build_connection_settings(b"Office", &ConnectOptions::default());
+        assert!(!settings.contains_key("ipv4"));
+    }
+
+    #[test]
+    fn hidden_network_sets_hidden_flag() {
+        let options = ConnectOptions {
+            hidden: true,
+            ..Default::default()
+        };
+        let settings = build_connection_settings(b"Hidden", &options);
+        assert_eq!(settings["802-11-wireless"]["hidden"].0.as_u64(), Some(1));
+
+        let settings = build_connection_settings(b"Visible", &ConnectOptions::default());
+        assert!(!settings["802-11-wireless"].contains_key("hidden"));
+    }
+
+    #[test]
+    fn bssid_is_sent_as_bytes() {
+        let options = ConnectOptions {
+            bssid: Some("00:11:22:aa:bb:cc".parse().unwrap()),
+            ..Default::default()
+        };
+        let settings = build_connection_settings(b"Office", &options);
+        let bssid: Vec<u8> = settings["802-11-wireless"]["bssid"]
+            .0
+            .as_iter()
+            .unwrap()
+            .map(|b| b.as_u64().unwrap() as u8)
+            .collect();
+        assert_eq!(bssid, [0x00, 0x11, 0x22, 0xaa, 0xbb, 0xcc]);
+    }
+
+    #[test]
+    fn secret_storage_sets_flags_next_to_the_secrets() {
+        let options = ConnectOptions {
+            secret_storage: SecretStorage::NotSaved,
+            permissions: vec!["kiosk".into()],
+            ..with_security(Security::WpaPsk("supersecret123".into()))
+        };
+        let settings = build_connection_settings(b"Kiosk", &options);
+        let security = &settings["802-11-wireless-security"];
+        // The PSK is still sent, for this activation.
+        assert_eq!(security["psk"].0.as_str(), Some("supersecret123"));
+        assert_eq!(security["psk-flags"].0.signature().to_string(), "u");
+        assert_eq!(security["psk-flags"].0.as_u64(), Some(2));
+        let permissions: Vec<&str> = settings["connection"]["permissions"]
+            .0
+            .as_iter()
+            .unwrap()
+            .map(|user| user.as_str().unwrap())
+            .collect();
+        assert_eq!(permissions, ["user:kiosk:"]);
+
+        let settings = build_connection_settings(
+            b"Office",
+            &with_security(Security::WpaPsk("supersecret123".into())),
+        );
+        assert!(!settings["802-11-wireless-security"].contains_key("psk-flags"));
+        assert!(!settings["connection"].contains_key("permissions"));
+    }
+
+    #[test]
+    fn zone_joins_the_connection_section() {
+        let options = ConnectOptions {
+            zone: Some("trusted".into()),
+            autoconnect: Some(false),
+            ..Default::default()
+        };
+        let settings = build_connection_settings(b"Office", &options);
+        let connection = &settings["connection"];
+        assert_eq!(connection["zone"].0.as_str(), Some("trusted"));
+        assert_eq!(connection["id"].0.as_str(), Some("Office"));
+        assert_eq!(connection["autoconnect"].0.as_u64(), Some(0));
+    }
+
+    #[test]
+    fn extra_settings_go_in_last_with_their_types() {
+        let address = [
+            ("address".to_string(), "192.168.1.50".into()),
+            ("prefix".to_string(), 24u32.into()),
+        ];
+        let connection = crate::WifiConnection::builder("Office")
+            .mtu(1400)
+            .extra_setting("802-11-wireless", "ap-isolation", 1)
+            .extra_setting("802-11-wireless", "mtu", 1280u32)
+            .extra_setting("802-11-wireless", "seen-bssids", vec![String::from("x")])
+            .extra_setting("connection", "llmnr", -1)
+            .extra_setting("ipv4", "may-fail", false)
+            .extra_setting(
+                "ipv4",
+                "address-data",
+                ExtraSettingValue::Dicts(vec![address.into()]),
+            )
+            .extra_setting("proxy", "pac-script", b"function".to_vec())
+            .build()
+            .unwrap();
+        let settings = build_connection_settings(b"Office", connection.options());
+
+        let signature = |section: &str, key: &str| settings[section][key].0.signature();
+        assert_eq!(signature("802-11-wireless", "ap-isolation"), "i");
+        assert_eq!(signature("802-11-wireless", "seen-bssids"), "as");
+        assert_eq!(signature("ipv4", "may-fail"), "b");
+        assert_eq!(signature("ipv4", "address-data"), "aa{sv}");
+        assert_eq!(signature("proxy", "pac-script"), "ay");
+        // They replace generated values and leave the rest alone.
+        assert_eq!(settings["802-11-wireless"]["mtu"].0.as_u64(), Some(1280));
+        assert_eq!(settings["connection"]["llmnr"].0.as_i64(), Some(-1));
+        assert_eq!(settings["connection"]["id"].0.as_str(), Some("Office"));
+        // A dict iterates as alternating keys and values.
+        let entry = settings["ipv4"]["address-data"].0.as_iter().unwrap().next();
+        let entry: Vec<&dyn RefArg> = entry.unwrap().as_iter().unwrap().collect();
+        let prefix = entry
+            .chunks(2)
+            .find(|pair| pair[0].as_str() == Some("prefix"))
+            .and_then(|pair| pair[1].as_u64());
+        assert_eq!(prefix, Some(24));
+    }
+}
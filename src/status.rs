@@ -0,0 +1,520 @@
+use dbus::arg::{prop_cast, PropMap};
+use dbus::blocking::{stdintf::org_freedesktop_dbus::Properties, Connection};
+use std::net::Ipv4Addr;
+use std::time::{Duration, SystemTime};
+
+use crate::client::{DbusNmClient, NmClient};
+use crate::error::nm_error;
+use crate::nm::{
+    ACCESS_POINT_INTERFACE, ACTIVE_CONNECTION_INTERFACE, DEVICE_INTERFACE, IP4_CONFIG_INTERFACE,
+    NM_BUS_NAME, NO_OBJECT_PATH, WIRELESS_INTERFACE,
+};
+use crate::radio::{self, RadioState};
+use crate::{connectivity, ip_info, Connectivity};
+use crate::{
+    AccessPointInfo, DbusTimeouts, DeviceState, MacAddr, WifiConfigError, WifiDevice, WifiManager,
+};
+
+/// What the Wi-Fi device is connected to right now, as reported by the deprecated
+/// [`current_status`]; [`ActiveConnectionInfo`] is the status shape to use.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct WifiStatus {
+    /// Interface name of the Wi-Fi device, e.g. `wlan0`.
+    pub interface: String,
+    /// Current device state; `Activated` once the connection is fully up.
+    pub state: DeviceState,
+    /// Name (`connection.id`) of the active profile.
+    pub connection_id: String,
+    /// Raw SSID bytes of the associated access point.
+    pub ssid: Vec<u8>,
+    /// SSID decoded as UTF-8, with invalid sequences replaced.
+    pub ssid_lossy: String,
+    /// BSSID of the associated access point; `None` if NetworkManager reported a
+    /// malformed address.
+    pub bssid: Option<MacAddr>,
+    /// Signal strength in percent (0–100).
+    pub strength: u8,
+    /// Frequency in MHz.
+    pub frequency: u32,
+    /// First IPv4 address and its prefix length; `None` until DHCP (or the static
+    /// configuration) has completed.
+    pub ipv4_address: Option<(Ipv4Addr, u8)>,
+    /// MTU in use on the interface; `None` with iwd and wpa_supplicant, which don't
+    /// report it.
+    pub mtu: Option<u32>,
+    /// Whether the Wi-Fi radio is on. Always [`RadioState::Enabled`] with iwd and
+    /// wpa_supplicant, which don't report it; see [`radio_state`](crate::radio_state) for
+    /// when there is no connection to report.
+    pub radio: RadioState,
+    /// Whether the internet is reachable over this device, from NetworkManager's last
+    /// connectivity check; [`Connectivity::Unknown`] if the check is disabled, and with
+    /// iwd and wpa_supplicant.
+    pub connectivity: Connectivity,
+    /// With [`Connectivity::Portal`], the login page of the captive portal, if the network
+    /// announced it over DHCP (RFC 8910) and NetworkManager reports it.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub portal_url: Option<String>,
+}
+
+/// State of an active connection (`NMActiveConnectionState`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ActiveConnectionState {
+    /// The state is unknown.
+    Unknown,
+    /// The connection is being brought up.
+    Activating,
+    /// The connection is up.
+    Activated,
+    /// The connection is being torn down.
+    Deactivating,
+    /// The connection is down.
+    Deactivated,
+}
+
+impl From<u32> for ActiveConnectionState {
+    fn from(state: u32) -> Self {
+        match state {
+            1 => ActiveConnectionState::Activating,
+            2 => ActiveConnectionState::Activated,
+            3 => ActiveConnectionState::Deactivating,
+            4 => ActiveConnectionState::Deactivated,
+            _ => ActiveConnectionState::Unknown,
+        }
+    }
+}
+
+/// The connection up on a Wi-Fi device, in one shape for logs and telemetry: from
+/// [`ActivationHandle::active`](crate::ActivationHandle::active) after
+/// [`connect_and_wait`](crate::connect_and_wait), and from [`active_connection`] and
+/// [`Backend::active_connection`](crate::Backend::active_connection) when polling.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ActiveConnectionInfo {
+    /// Raw SSID bytes, of the associated access point or else of the profile.
+    pub ssid: Vec<u8>,
+    /// Interface name of the Wi-Fi device, e.g. `wlan0`.
+    pub interface: String,
+    /// UUID of the profile (`connection.uuid`); empty with iwd and wpa_supplicant, which
+    /// have no profile UUIDs.
+    pub uuid: String,
+    /// Name (`connection.id`) of the profile; the network name with iwd and
+    /// wpa_supplicant.
+    pub connection_id: String,
+    /// State of the active connection.
+    pub state: ActiveConnectionState,
+    /// First IPv4 address; `None` until DHCP (or the static configuration) has completed.
+    pub ip4: Option<Ipv4Addr>,
+    /// BSSID of the associated access point.
+    pub bssid: Option<MacAddr>,
+    /// Signal strength of the associated access point in percent (0–100).
+    pub strength: Option<u8>,
+    /// MTU in use on the interface; `None` with iwd and wpa_supplicant, which don't
+    /// report it.
+    pub mtu: Option<u32>,
+    /// Whether the Wi-Fi radio is on. Always [`RadioState::Enabled`] with iwd and
+    /// wpa_supplicant, which don't report it.
+    pub radio: RadioState,
+    /// Whether the internet is reachable over the device, from NetworkManager's last
+    /// connectivity check; [`Connectivity::Unknown`] if the check is disabled, and with
+    /// iwd and wpa_supplicant.
+    pub connectivity: Connectivity,
+    /// With [`Connectivity::Portal`], the login page of the captive portal, if the network
+    /// announced it over DHCP (RFC 8910) and NetworkManager reports it.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub portal_url: Option<String>,
+    /// When the connection came up: when [`connect_and_wait`](crate::connect_and_wait)
+    /// saw it activated, otherwise the profile's `connection.timestamp`, which
+    /// NetworkManager sets on activation and may refresh while the connection stays up.
+    /// With iwd and wpa_supplicant, which don't report it, when the status was read.
+    pub started_at: SystemTime,
+}
+
+impl ActiveConnectionInfo {
+    /// The connection `status` reports, for the services that only give a [`WifiStatus`].
+    pub(crate) fn from_status(status: WifiStatus) -> Self {
+        let state = match status.state {
+            DeviceState::Prepare
+            | DeviceState::Config
+            | DeviceState::NeedAuth
+            | DeviceState::IpConfig
+            | DeviceState::IpCheck
+            | DeviceState::Secondaries => ActiveConnectionState::Activating,
+            DeviceState::Activated => ActiveConnectionState::Activated,
+            DeviceState::Deactivating => ActiveConnectionState::Deactivating,
+            DeviceState::Disconnected | DeviceState::Failed => ActiveConnectionState::Deactivated,
+            _ => ActiveConnectionState::Unknown,
+        };
+        ActiveConnectionInfo {
+            ssid: status.ssid,
+            interface: status.interface,
+            uuid: String::new(),
+            connection_id: status.connection_id,
+            state,
+            ip4: status.ipv4_address.map(|(address, _)| address),
+            bssid: status.bssid,
+            strength: Some(status.strength),
+            mtu: status.mtu,
+            radio: status.radio,
+            connectivity: status.connectivity,
+            portal_url: status.portal_url,
+            started_at: SystemTime::now(),
+        }
+    }
+}
+
+/// Reports the connection up on the first usable Wi-Fi device, as
+/// [`connect_and_wait`](crate::connect_and_wait) does in
+/// [`ActivationHandle::active`](crate::ActivationHandle::active).
+///
+/// This is the status to log or ship, the same shape after connecting and when polling;
+/// [`Backend::active_connection`](crate::Backend::active_connection) reports it through
+/// iwd and wpa_supplicant too.
+///
+/// Returns `Ok(None)` if nothing is active on the device.
+///
+/// # Errors
+///
+/// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+/// - [`WifiConfigError::NetworkManagerUnavailable`] if NetworkManager isn't running.
+/// - [`WifiConfigError::NoWifiDevice`] if no Wi-Fi device is found.
+/// - [`WifiConfigError::DeviceQueryFailed`] if a property can't be read.
+///
+/// # Example
+///
+/// ```no_run
+/// if let Some(active) = wifi_config::active_connection()? {
+///     println!("{} up since {:?}", active.uuid, active.started_at);
+/// }
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn active_connection() -> Result<Option<ActiveConnectionInfo>, WifiConfigError> {
+    WifiManager::new()?.active_connection()
+}
+
+/// [`active_connection`] of `device`.
+pub(crate) fn device_active_connection(
+    conn: &Connection,
+    timeouts: DbusTimeouts,
+    device: &WifiDevice,
+) -> Result<Option<ActiveConnectionInfo>, WifiConfigError> {
+    let client = DbusNmClient::new(conn, timeouts);
+    let active_path = match prop_cast::<dbus::Path<'static>>(
+        &client.device_properties(&device.path)?,
+        "ActiveConnection",
+    ) {
+        Some(path) if &**path != NO_OBJECT_PATH => path.clone(),
+        _ => return Ok(None),
+    };
+    let ip = ip_info::wait_for_ip_info(conn, timeouts, &active_path, Duration::ZERO)?;
+    let ip4 = ip.ipv4_addresses.first().map(|&(address, _)| address);
+    read_active_connection(&client, device, &active_path, ip4, None).map(Some)
+}
+
+/// Reads the active connection at `active_path` on `device`, with `ip4` as its address,
+/// and the device's MTU and connectivity and the radio state along with it.
+///
+/// `started_at` is when it was seen coming up, if it was; otherwise the profile's
+/// timestamp is used, or the current time if it has none.
+pub(crate) fn read_active_connection(
+    client: &dyn NmClient,
+    device: &WifiDevice,
+    active_path: &dbus::Path,
+    ip4: Option<Ipv4Addr>,
+    started_at: Option<SystemTime>,
+) -> Result<ActiveConnectionInfo, WifiConfigError> {
+    let active = client.active_connection_properties(active_path)?;
+    let uuid = prop_cast::<String>(&active, "Uuid")
+        .cloned()
+        .unwrap_or_default();
+    let connection_id = prop_cast::<String>(&active, "Id")
+        .cloned()
+        .unwrap_or_default();
+    let state = prop_cast::<u32>(&active, "State").copied().unwrap_or(0);
+    let profile = match prop_cast::<dbus::Path<'static>>(&active, "Connection") {
+        Some(path) => Some(client.get_settings(path)?),
+        None => None,
+    };
+
+    let access_point = prop_cast::<dbus::Path<'static>>(
+        &client.wireless_properties(&device.path)?,
+        "ActiveAccessPoint",
+    )
+    .filter(|path| &***path != NO_OBJECT_PATH)
+    .cloned();
+    let ap = match access_point {
+        Some(path) => {
+            let props = client.access_point_properties(&path)?;
+            Some(AccessPointInfo::from_properties(path, &props))
+        }
+        None => None,
+    };
+
+    let ssid = match (&ap, &profile) {
+        (Some(ap), _) if !ap.ssid.is_empty() => ap.ssid.clone(),
+        (_, Some(profile)) => crate::profiles::wireless_ssid(profile)
+            .cloned()
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    };
+    let device_props = client.device_properties(&device.path)?;
+    let connectivity = connectivity::device_connectivity(&device_props);
+    let portal_url = if connectivity.is_portal() {
+        connectivity::portal_url(client, &device.path)
+    } else {
+        None
+    };
+    let timestamp = profile
+        .as_ref()
+        .and_then(|profile| profile.get("connection"))
+        .and_then(|connection| prop_cast::<u64>(connection, "timestamp"))
+        .filter(|&&seconds| seconds > 0)
+        .map(|&seconds| SystemTime::UNIX_EPOCH + Duration::from_secs(seconds));
+    Ok(ActiveConnectionInfo {
+        ssid,
+        interface: device.interface.clone(),
+        uuid,
+        connection_id,
+        state: state.into(),
+        ip4,
+        bssid: ap.as_ref().and_then(AccessPointInfo::bssid),
+        strength: ap.as_ref().map(|ap| ap.strength),
+        mtu: prop_cast::<u32>(&device_props, "Mtu").copied(),
+        radio: radio::read_radio_state(client)?,
+        connectivity,
+        portal_url,
+        started_at: started_at.or(timestamp).unwrap_or_else(SystemTime::now),
+    })
+}
+
+/// Reports what the first usable Wi-Fi device is connected to.
+///
+/// Deprecated: [`active_connection`] reports the connection in the same
+/// [`ActiveConnectionInfo`] shape [`connect_and_wait`](crate::connect_and_wait) does.
+///
+/// # Behavior
+///
+/// - Locates the Wi-Fi device the same way [`connect`](crate::connect) does.
+/// - Reads its `ActiveConnection` and `ActiveAccessPoint` properties, then the access
+///   point's `Ssid`, `HwAddress`, `Strength` and `Frequency` and the active connection's `Id`.
+/// - Reads `AddressData` from the device's `Ip4Config` object, if it has one, and the
+///   device's `Mtu`.
+/// - Reads the device's `Ip4Connectivity` and `Ip6Connectivity`, and behind a captive
+///   portal the portal's URL from its `Dhcp4Config` options.
+///
+/// Returns `Ok(None)` if the device exists but isn't associated with a network.
+///
+/// # Errors
+///
+/// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+/// - [`WifiConfigError::NetworkManagerUnavailable`] if NetworkManager isn't running.
+/// - [`WifiConfigError::NoWifiDevice`] if no Wi-Fi device is found.
+/// - [`WifiConfigError::DeviceQueryFailed`] if a property can't be read.
+///
+/// # Example
+///
+/// ```no_run
+/// # #![allow(deprecated)]
+/// match wifi_config::current_status()? {
+///     Some(status) => println!("{} ({}%)", status.ssid_lossy, status.strength),
+///     None => println!("not connected"),
+/// }
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+#[deprecated(note = "use `active_connection`, which reports the same shape as `connect_and_wait`")]
+#[allow(deprecated)]
+pub fn current_status() -> Result<Option<WifiStatus>, WifiConfigError> {
+    WifiManager::new()?.status()
+}
+
+/// [`current_status`] of `device`.
+pub(crate) fn device_status(
+    conn: &Connection,
+    timeouts: DbusTimeouts,
+    device: WifiDevice,
+) -> Result<Option<WifiStatus>, WifiConfigError> {
+    let device_proxy = conn.with_proxy(NM_BUS_NAME, &device.path, timeouts.query);
+    let active_connection: dbus::Path<'static> = device_proxy
+        .get(DEVICE_INTERFACE, "ActiveConnection")
+        .map_err(nm_error(WifiConfigError::DeviceQueryFailed))?;
+    let access_point: dbus::Path<'static> = device_proxy
+        .get(WIRELESS_INTERFACE, "ActiveAccessPoint")
+        .map_err(nm_error(WifiConfigError::DeviceQueryFailed))?;
+    if &*active_connection == NO_OBJECT_PATH || &*access_point == NO_OBJECT_PATH {
+        return Ok(None);
+    }
+
+    let ap_props = conn
+        .with_proxy(NM_BUS_NAME, &access_point, timeouts.query)
+        .get_all(ACCESS_POINT_INTERFACE)
+        .map_err(nm_error(WifiConfigError::DeviceQueryFailed))?;
+    let ap = AccessPointInfo::from_properties(access_point, &ap_props);
+
+    let connection_id: String = conn
+        .with_proxy(NM_BUS_NAME, &active_connection, timeouts.query)
+        .get(ACTIVE_CONNECTION_INTERFACE, "Id")
+        .map_err(nm_error(WifiConfigError::DeviceQueryFailed))?;
+
+    let ip4_config: dbus::Path<'static> = device_proxy
+        .get(DEVICE_INTERFACE, "Ip4Config")
+        .map_err(nm_error(WifiConfigError::DeviceQueryFailed))?;
+    let ipv4_address = if &*ip4_config == NO_OBJECT_PATH {
+        None
+    } else {
+        let address_data: Vec<PropMap> = conn
+            .with_proxy(NM_BUS_NAME, &ip4_config, timeouts.query)
+            .get(IP4_CONFIG_INTERFACE, "AddressData")
+            .map_err(nm_error(WifiConfigError::DeviceQueryFailed))?;
+        ip_info::addresses(&address_data).first().copied()
+    };
+
+    let client = DbusNmClient::new(conn, timeouts);
+    let connectivity = connectivity::device_connectivity(&client.device_properties(&device.path)?);
+    let portal_url = if connectivity.is_portal() {
+        connectivity::portal_url(&client, &device.path)
+    } else {
+        None
+    };
+
+    Ok(Some(WifiStatus {
+        interface: device.interface,
+        state: device.state,
+        connection_id,
+        bssid: ap.bssid(),
+        ssid: ap.ssid,
+        ssid_lossy: ap.ssid_lossy,
+        strength: ap.strength,
+        frequency: ap.frequency,
+        ipv4_address,
+        mtu: Some(device.mtu),
+        radio: radio::read_radio_state(&client)?,
+        connectivity,
+        portal_url,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::{MockDevice, MockNmClient};
+    use crate::settings::build_connection_settings;
+    use crate::{device, ConnectOptions};
+    use dbus::arg::Variant;
+
+    #[test]
+    fn reads_the_active_connection_from_the_access_point_or_the_profile() {
+        let active = dbus::Path::new("/org/freedesktop/NetworkManager/ActiveConnection/3").unwrap();
+        let mut device = MockDevice::new(1, "wlan0", 2).with_access_point(b"Office", 0x188);
+        device.wireless_properties.insert(
+            "ActiveAccessPoint".into(),
+            Variant(Box::new(device.access_points[0].0.clone())),
+        );
+        let mut client = MockNmClient::with_devices(vec![device]);
+        let mut settings = build_connection_settings(b"Office", &ConnectOptions::default());
+        settings
+            .get_mut("connection")
+            .unwrap()
+            .insert("timestamp".into(), Variant(Box::new(1_700_000_000u64)));
+        let profile = client.save(settings, "0b7cb1a4-5f7c-4d36-9d0e-6a1f3c2e8b90");
+        client.active_profiles.push((active.clone(), profile));
+        let wlan0 = device::wifi_devices(&client).unwrap().remove(0);
+
+        let address = Ipv4Addr::new(192, 168, 1, 50);
+        let info = read_active_connection(&client, &wlan0, &active, Some(address), None).unwrap();
+        assert_eq!(
+            info,
+            ActiveConnectionInfo {
+                ssid: b"Office".to_vec(),
+                interface: "wlan0".into(),
+                uuid: "0b7cb1a4-5f7c-4d36-9d0e-6a1f3c2e8b90".into(),
+                connection_id: "Office".into(),
+                state: ActiveConnectionState::Activated,
+                ip4: Some(address),
+                bssid: "AA:BB:CC:DD:EE:FF".parse().ok(),
+                strength: Some(70),
+                mtu: Some(1500),
+                radio: RadioState::Enabled,
+                connectivity: Connectivity::Unknown,
+                portal_url: None,
+                started_at: SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            }
+        );
+
+        // Without an access point the SSID comes from the profile.
+        client.devices[0].wireless_properties.insert(
+            "ActiveAccessPoint".into(),
+            Variant(Box::new(dbus::Path::from("/"))),
+        );
+        let now = SystemTime::now();
+        let info = read_active_connection(&client, &wlan0, &active, None, Some(now)).unwrap();
+        assert_eq!(info.ssid, b"Office");
+        assert_eq!((info.bssid, info.strength), (None, None));
+        assert_eq!(info.started_at, now);
+    }
+
+    #[test]
+    fn reports_a_captive_portal_on_the_active_connection() {
+        let active = dbus::Path::new("/org/freedesktop/NetworkManager/ActiveConnection/3").unwrap();
+        let mut device = MockDevice::new(1, "wlan0", 2).with_access_point(b"Cafe", 0);
+        // NM_CONNECTIVITY_PORTAL over IPv4, nothing yet over IPv6.
+        device
+            .properties
+            .insert("Ip4Connectivity".into(), Variant(Box::new(2u32)));
+        device
+            .properties
+            .insert("Ip6Connectivity".into(), Variant(Box::new(1u32)));
+        let mut options = PropMap::new();
+        options.insert(
+            "captive_portal".into(),
+            Variant(Box::new(String::from("https://portal.example.com/login"))),
+        );
+        device.dhcp4_options = Some(options);
+        let mut client = MockNmClient::with_devices(vec![device]);
+        let settings = build_connection_settings(b"Cafe", &ConnectOptions::default());
+        let profile = client.save(settings, "5d0f1e2a-3b4c-4d5e-8f60-718293a4b5c6");
+        client.active_profiles.push((active.clone(), profile));
+        let wlan0 = device::wifi_devices(&client).unwrap().remove(0);
+
+        let info = read_active_connection(&client, &wlan0, &active, None, None).unwrap();
+        assert_eq!(info.connectivity, Connectivity::Portal);
+        assert_eq!(
+            info.portal_url.as_deref(),
+            Some("https://portal.example.com/login")
+        );
+        assert_eq!(info.radio, RadioState::Enabled);
+    }
+
+    #[test]
+    fn reports_the_other_services_in_the_same_shape() {
+        let status = WifiStatus {
+            interface: "wlan0".into(),
+            state: DeviceState::Config,
+            connection_id: "Office".into(),
+            ssid: b"Office".to_vec(),
+            ssid_lossy: "Office".into(),
+            bssid: None,
+            strength: 64,
+            frequency: 0,
+            ipv4_address: None,
+            mtu: None,
+            radio: RadioState::Enabled,
+            connectivity: Connectivity::Unknown,
+            portal_url: None,
+        };
+        let info = ActiveConnectionInfo::from_status(status);
+        assert_eq!(info.ssid, b"Office");
+        assert_eq!(info.interface, "wlan0");
+        assert_eq!(info.uuid, "");
+        assert_eq!(info.connection_id, "Office");
+        assert_eq!(info.state, ActiveConnectionState::Activating);
+        assert_eq!(
+            (info.ip4, info.bssid, info.strength),
+            (None, None, Some(64))
+        );
+    }
+}
@@ -0,0 +1,272 @@
+//! The running NetworkManager's version, and the settings that depend on it.
+
+use dbus::arg::{prop_cast, PropMap, Variant};
+use dbus::blocking::Connection;
+use std::fmt;
+use std::net::Ipv4Addr;
+
+use crate::client::{DbusNmClient, NmClient};
+use crate::ip::ipv4_dns_value;
+use crate::settings::ConnectionSettings;
+use crate::{DbusTimeouts, WifiConfigError};
+
+/// The first version taking `ipv4.address-data` and `ipv4.gateway`; older ones only
+/// know the `addresses` array of `(address, prefix, gateway)` triples.
+const ADDRESS_DATA: NmVersion = NmVersion::new(1, 0, 0);
+
+/// Features `settings` use and the version NetworkManager gained them in.
+///
+/// Older versions don't reject the settings for them but ignore them or fail the
+/// activation without saying why, so they are refused before anything is sent.
+fn requirements(settings: &ConnectionSettings) -> Vec<(&'static str, NmVersion)> {
+    let value = |section: &str, key: &str| {
+        settings
+            .get(section)
+            .and_then(|section| prop_cast::<String>(section, key))
+            .map(String::as_str)
+    };
+    let mut requirements = Vec::new();
+    match value("802-11-wireless-security", "key-mgmt") {
+        Some("sae") => requirements.push(("WPA3-SAE", NmVersion::new(1, 16, 0))),
+        Some("owe") => requirements.push(("OWE (Enhanced Open)", NmVersion::new(1, 24, 0))),
+        _ => {}
+    }
+    // Unlike IPv4, there is no fallback to the legacy `addresses` encoding.
+    if value("ipv6", "method") == Some("manual") {
+        requirements.push(("static IPv6 addresses", ADDRESS_DATA));
+    }
+    requirements
+}
+
+/// A NetworkManager version, as in its `Version` property; ordered so versions can be
+/// compared.
+///
+/// # Example
+///
+/// ```no_run
+/// use wifi_config::NmVersion;
+///
+/// let version = wifi_config::nm_version()?;
+/// if version >= NmVersion::new(1, 16, 0) {
+///     println!("NetworkManager {} can do WPA3", version);
+/// }
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct NmVersion {
+    /// Major version, `1` for every release since 2014.
+    pub major: u32,
+    /// Minor version; odd ones are development snapshots.
+    pub minor: u32,
+    /// Micro (patch) version.
+    pub micro: u32,
+}
+
+impl NmVersion {
+    /// The version `major.minor.micro`.
+    pub const fn new(major: u32, minor: u32, micro: u32) -> Self {
+        NmVersion {
+            major,
+            minor,
+            micro,
+        }
+    }
+
+    /// Parses a `Version` property such as `1.46.0` or `1.47.2-dev`: the leading
+    /// numbers of up to three dot-separated parts, a missing part reading as `0`.
+    pub(crate) fn parse(version: &str) -> Option<Self> {
+        let mut parts = version.split('.').map(|part| {
+            let digits = part
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(part.len());
+            part[..digits].parse::<u32>().ok()
+        });
+        let major = parts.next()??;
+        let minor = parts.next().unwrap_or(Some(0))?;
+        let micro = parts.next().flatten().unwrap_or(0);
+        Some(NmVersion::new(major, minor, micro))
+    }
+}
+
+impl fmt::Display for NmVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.micro)
+    }
+}
+
+/// Reads the version of the running NetworkManager.
+///
+/// [`WifiManager::nm_version`](crate::WifiManager::nm_version) reads it once per
+/// manager instead.
+///
+/// # Errors
+///
+/// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+/// - [`WifiConfigError::NetworkManagerUnavailable`] if NetworkManager isn't running.
+/// - [`WifiConfigError::DeviceQueryFailed`] if the property can't be read or parsed.
+pub fn nm_version() -> Result<NmVersion, WifiConfigError> {
+    let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+    DbusNmClient::new(&conn, DbusTimeouts::default()).version()
+}
+
+/// Fails with [`WifiConfigError::UnsupportedByNetworkManager`] if `settings` use
+/// something NetworkManager `have` can't do.
+pub(crate) fn check_supported(
+    settings: &ConnectionSettings,
+    have: NmVersion,
+) -> Result<(), WifiConfigError> {
+    match requirements(settings)
+        .into_iter()
+        .find(|&(_, needs)| have < needs)
+    {
+        Some((feature, needs)) => Err(WifiConfigError::UnsupportedByNetworkManager {
+            feature: feature.into(),
+            needs,
+            have,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Rewrites `settings` into the encoding NetworkManager `version` understands.
+pub(crate) fn adapt_settings(settings: &mut ConnectionSettings, version: NmVersion) {
+    if version >= ADDRESS_DATA {
+        return;
+    }
+    let Some(ipv4) = settings.get_mut("ipv4") else {
+        return;
+    };
+    let Some(address_data) = ipv4.remove("address-data") else {
+        return;
+    };
+    let addresses = address_data
+        .0
+        .as_any()
+        .downcast_ref::<Vec<PropMap>>()
+        .and_then(|addresses| {
+            addresses
+                .iter()
+                .map(|address| {
+                    let ip: Ipv4Addr = prop_cast::<String>(address, "address")?.parse().ok()?;
+                    Some((ip, *prop_cast::<u32>(address, "prefix")?))
+                })
+                .collect::<Option<Vec<_>>>()
+        });
+    let Some(addresses) = addresses else {
+        log::warn!("can't read ipv4.address-data; sending it as it is");
+        ipv4.insert("address-data".into(), address_data);
+        return;
+    };
+    let gateway = ipv4
+        .remove("gateway")
+        .and_then(|gateway| gateway.0.as_str()?.parse::<Ipv4Addr>().ok());
+    // `aau`: address, prefix and gateway (`0` for none), addresses in network order. The
+    // gateway goes with the first address.
+    let triples: Vec<Vec<u32>> = addresses
+        .into_iter()
+        .enumerate()
+        .map(|(i, (address, prefix))| {
+            let gateway = gateway.filter(|_| i == 0);
+            vec![
+                ipv4_dns_value(address),
+                prefix,
+                gateway.map_or(0, ipv4_dns_value),
+            ]
+        })
+        .collect();
+    ipv4.insert("addresses".into(), Variant(Box::new(triples)));
+}
+
+/// Checks `settings` against the version of the NetworkManager behind `client` and
+/// rewrites them for it; see [`check_supported`] and [`adapt_settings`].
+pub(crate) fn fit_settings(
+    client: &dyn NmClient,
+    settings: &mut ConnectionSettings,
+) -> Result<(), WifiConfigError> {
+    let version = client.version()?;
+    check_supported(settings, version)?;
+    adapt_settings(settings, version);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::build_connection_settings;
+    use crate::{ConnectOptions, Ipv4Config, Security};
+    use dbus::arg::RefArg;
+
+    #[test]
+    fn parses_and_orders_versions() {
+        assert_eq!(NmVersion::parse("1.46.0"), Some(NmVersion::new(1, 46, 0)));
+        assert_eq!(
+            NmVersion::parse("1.47.2-dev"),
+            Some(NmVersion::new(1, 47, 2))
+        );
+        assert_eq!(NmVersion::parse("1.22"), Some(NmVersion::new(1, 22, 0)));
+        assert_eq!(NmVersion::parse("0.9.10.0"), Some(NmVersion::new(0, 9, 10)));
+        assert_eq!(NmVersion::parse(""), None);
+        assert_eq!(NmVersion::parse("unknown"), None);
+        assert!(NmVersion::new(1, 9, 0) < NmVersion::new(1, 16, 0));
+        assert!(NmVersion::new(1, 16, 1) > NmVersion::new(1, 16, 0));
+        assert_eq!(NmVersion::new(1, 46, 0).to_string(), "1.46.0");
+    }
+
+    #[test]
+    fn refuses_features_the_version_lacks() {
+        let sae = build_connection_settings(
+            b"Office",
+            &ConnectOptions {
+                security: Security::Wpa3Sae("supersecret123".into()),
+                ..Default::default()
+            },
+        );
+        assert!(check_supported(&sae, NmVersion::new(1, 16, 0)).is_ok());
+        let e = check_supported(&sae, NmVersion::new(1, 14, 2)).unwrap_err();
+        assert!(matches!(
+            &e,
+            WifiConfigError::UnsupportedByNetworkManager { needs, have, .. }
+                if *needs == NmVersion::new(1, 16, 0) && *have == NmVersion::new(1, 14, 2)
+        ));
+        assert_eq!(
+            e.to_string(),
+            "WPA3-SAE needs NetworkManager 1.16.0 or later; this one is 1.14.2"
+        );
+        let open = build_connection_settings(b"Cafe", &ConnectOptions::default());
+        assert!(check_supported(&open, NmVersion::new(0, 9, 8)).is_ok());
+    }
+
+    #[test]
+    fn sends_legacy_addresses_to_old_versions() {
+        let options = ConnectOptions {
+            ipv4: Ipv4Config::Manual {
+                address: Ipv4Addr::new(192, 168, 1, 50),
+                prefix: 24,
+                gateway: Some(Ipv4Addr::new(192, 168, 1, 1)),
+            },
+            ..Default::default()
+        };
+        let mut settings = build_connection_settings(b"Lab", &options);
+        adapt_settings(&mut settings, NmVersion::new(1, 46, 0));
+        assert!(settings["ipv4"].contains_key("address-data"));
+
+        adapt_settings(&mut settings, NmVersion::new(0, 9, 10));
+        let ipv4 = &settings["ipv4"];
+        assert!(!ipv4.contains_key("address-data") && !ipv4.contains_key("gateway"));
+        assert_eq!(ipv4["addresses"].0.signature().to_string(), "aau");
+        let addresses = ipv4["addresses"]
+            .0
+            .as_any()
+            .downcast_ref::<Vec<Vec<u32>>>()
+            .unwrap();
+        assert_eq!(
+            addresses,
+            &[vec![
+                u32::from_ne_bytes([192, 168, 1, 50]),
+                24,
+                u32::from_ne_bytes([192, 168, 1, 1]),
+            ]]
+        );
+    }
+}
@@ -0,0 +1,506 @@
+use dbus::arg::{prop_cast, PropMap};
+use dbus::blocking::Connection;
+use dbus::message::MatchRule;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::client::{DbusNmClient, NmClient};
+use crate::device;
+use crate::nm::{
+    ACCESS_POINT_INTERFACE, DEVICE_INTERFACE, NM_BUS_NAME, NM_INTERFACE, NM_PATH, NO_OBJECT_PATH,
+    WIRELESS_INTERFACE,
+};
+use crate::{AccessPointInfo, DbusTimeouts, DeviceState, DeviceStateReason, WifiConfigError};
+
+/// How long the watcher thread blocks on the bus before checking whether to stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// One `StateChanged` signal of a device, e.g. `Config` → `IpConfig` while connecting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DeviceStateEvent {
+    /// State the device left.
+    pub old: DeviceState,
+    /// State the device entered.
+    pub new: DeviceState,
+    /// Why the state changed.
+    pub reason: DeviceStateReason,
+}
+
+/// Something that happened to the Wi-Fi device or NetworkManager; see [`watch_wifi`].
+///
+/// Serialized with an `event` field naming the variant, e.g.
+/// `{"event":"strength_changed","interface":"wlan0","strength":64}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(tag = "event", rename_all = "snake_case")
+)]
+pub enum WifiEvent {
+    /// The device changed state, as in [`DeviceStateEvent`].
+    StateChanged {
+        /// Interface name of the device.
+        interface: String,
+        /// State the device left.
+        old: DeviceState,
+        /// State the device entered.
+        new: DeviceState,
+        /// Why the state changed.
+        reason: DeviceStateReason,
+    },
+    /// The device associated with another access point, e.g. after roaming, or with
+    /// none (`None`) after losing the network. `access_point` is `None` as well if the
+    /// new access point couldn't be read.
+    AccessPointChanged {
+        /// Interface name of the device.
+        interface: String,
+        /// The access point now in use.
+        access_point: Option<AccessPointInfo>,
+    },
+    /// The signal strength of the access point in use changed.
+    StrengthChanged {
+        /// Interface name of the device.
+        interface: String,
+        /// Signal strength in percent (0–100).
+        strength: u8,
+    },
+    /// NetworkManager left the bus; nothing is reported until it is back.
+    NetworkManagerStopped,
+    /// NetworkManager is back on the bus, and the device is watched again once it shows
+    /// up.
+    NetworkManagerStarted,
+}
+
+/// Stops an [`EventWatcher`] from another thread.
+///
+/// The watcher's iterator ends once the background thread has noticed, within a fraction
+/// of a second.
+#[derive(Debug, Clone)]
+pub struct StopToken(Arc<AtomicBool>);
+
+impl StopToken {
+    /// Asks the watcher to stop.
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Events of a device as they happen; see [`watch_device_states`] and [`watch_wifi`].
+///
+/// A background thread with its own system bus connection receives the signals, so the
+/// caller doesn't need to dispatch D-Bus messages. Iterating blocks until the next event
+/// and ends when the watcher is stopped through its [`StopToken`] or the bus connection
+/// is lost. Dropping the watcher stops the thread as well.
+#[derive(Debug)]
+pub struct EventWatcher<T> {
+    events: Receiver<T>,
+    stop: StopToken,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// The state changes of one device; see [`watch_device_states`].
+pub type DeviceStateWatcher = EventWatcher<DeviceStateEvent>;
+
+/// The [`WifiEvent`]s of the Wi-Fi device; see [`watch_wifi`].
+pub type WifiWatcher = EventWatcher<WifiEvent>;
+
+impl<T> EventWatcher<T> {
+    /// A token that stops this watcher, e.g. from a UI thread or a signal handler.
+    pub fn stop_token(&self) -> StopToken {
+        self.stop.clone()
+    }
+
+    /// Waits up to `timeout` for the next event.
+    ///
+    /// Returns `None` if none arrived in time or the watcher has stopped.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<T> {
+        self.events.recv_timeout(timeout).ok()
+    }
+}
+
+impl<T> Iterator for EventWatcher<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.events.recv().ok()
+    }
+}
+
+impl<T> Drop for EventWatcher<T> {
+    fn drop(&mut self) {
+        self.stop.stop();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Watches the `StateChanged` signals of the device at `device_path`, e.g. a
+/// [`WifiDevice::path`](crate::WifiDevice::path).
+///
+/// Events that arrive while the caller isn't iterating are queued.
+///
+/// # Errors
+///
+/// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+/// - [`WifiConfigError::DeviceQueryFailed`] if subscribing to the signal fails.
+///
+/// # Example
+///
+/// ```no_run
+/// let device = wifi_config::list_wifi_devices()?.remove(0);
+/// for event in wifi_config::watch_device_states(&device.path)? {
+///     println!("{:?} -> {:?} ({:?})", event.old, event.new, event.reason);
+/// }
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn watch_device_states(
+    device_path: &dbus::Path,
+) -> Result<DeviceStateWatcher, WifiConfigError> {
+    let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+    let (sender, events) = mpsc::channel();
+    let rule = MatchRule::new_signal(DEVICE_INTERFACE, "StateChanged")
+        .with_path(device_path.clone().into_static());
+    conn.add_match(rule, move |(new, old, reason): (u32, u32, u32), _, _| {
+        let event = DeviceStateEvent {
+            old: DeviceState::from(old),
+            new: DeviceState::from(new),
+            reason: DeviceStateReason::from(reason),
+        };
+        log::debug!("device state {:?} -> {:?}", event.old, event.new);
+        // Keep the match only as long as someone is listening.
+        sender.send(event).is_ok()
+    })
+    .map_err(WifiConfigError::DeviceQueryFailed)?;
+
+    let stop = StopToken(Arc::new(AtomicBool::new(false)));
+    let thread_stop = stop.clone();
+    let thread = std::thread::spawn(move || {
+        while !thread_stop.is_stopped() {
+            if let Err(e) = conn.process(POLL_INTERVAL) {
+                log::warn!("stopped watching device states: {}", e);
+                break;
+            }
+        }
+    });
+    Ok(EventWatcher {
+        events,
+        stop,
+        thread: Some(thread),
+    })
+}
+
+/// Watches the Wi-Fi device named `interface`, or the first one: its state changes, the
+/// access point it is associated with and that access point's signal strength.
+///
+/// When NetworkManager restarts, the watcher reports
+/// [`NetworkManagerStopped`](WifiEvent::NetworkManagerStopped) and
+/// [`NetworkManagerStarted`](WifiEvent::NetworkManagerStarted) and picks the device up
+/// again, by its interface name, once NetworkManager exports it.
+///
+/// # Errors
+///
+/// - [`WifiConfigError::DbusConnection`] if the system D-Bus can't be reached.
+/// - [`WifiConfigError::NetworkManagerUnavailable`] if NetworkManager isn't running.
+/// - [`WifiConfigError::NoWifiDevice`] / [`WifiConfigError::DeviceNotFound`] if there is
+///   no Wi-Fi device, or none named `interface`.
+/// - [`WifiConfigError::DeviceQueryFailed`] if the device can't be read or subscribing
+///   to the signals fails.
+///
+/// # Example
+///
+/// ```no_run
+/// use wifi_config::WifiEvent;
+///
+/// for event in wifi_config::watch_wifi(Some("wlan0"))? {
+///     if let WifiEvent::StrengthChanged { strength, .. } = event {
+///         println!("signal {}%", strength);
+///     }
+/// }
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn watch_wifi(interface: Option<&str>) -> Result<WifiWatcher, WifiConfigError> {
+    let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+    let mut tracker = Tracker {
+        interface: interface.map(str::to_string),
+        device: None,
+        access_point: None,
+    };
+    let device = {
+        let client = DbusNmClient::new(&conn, DbusTimeouts::default());
+        device::select_wifi_device(device::wifi_devices(&client)?, None, interface)?
+    };
+    tracker.device = Some((device.path, device.interface));
+    tracker.access_point = tracker.active_access_point(&conn);
+
+    let (signal_sender, signals) = mpsc::channel();
+    subscribe(&conn, &signal_sender)?;
+    let (sender, events) = mpsc::channel();
+    let stop = StopToken(Arc::new(AtomicBool::new(false)));
+    let thread_stop = stop.clone();
+    let thread = std::thread::spawn(move || {
+        while !thread_stop.is_stopped() {
+            if let Err(e) = conn.process(POLL_INTERVAL) {
+                log::warn!("stopped watching the Wi-Fi device: {}", e);
+                break;
+            }
+            for signal in signals.try_iter() {
+                for event in tracker.handle(&conn, signal) {
+                    if sender.send(event).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    });
+    Ok(EventWatcher {
+        events,
+        stop,
+        thread: Some(thread),
+    })
+}
+
+/// A signal [`watch_wifi`] reacts to, with just the arguments it needs: the callbacks
+/// pass them on to the watcher thread, which reads whatever else it needs from the bus.
+enum Signal {
+    /// NetworkManager's bus name got a new owner, or none if `owner` is empty.
+    NameOwner {
+        owner: String,
+    },
+    DeviceAdded,
+    DeviceRemoved(dbus::Path<'static>),
+    StateChanged {
+        device: dbus::Path<'static>,
+        event: DeviceStateEvent,
+    },
+    /// `PropertiesChanged` of an object, with the changed properties the watcher uses.
+    Properties {
+        path: dbus::Path<'static>,
+        active_access_point: Option<dbus::Path<'static>>,
+        strength: Option<u8>,
+    },
+}
+
+/// Subscribes `conn` to the signals of [`Signal`], which it sends to `signals`.
+///
+/// The matches aren't restricted to NetworkManager's current unique name, so they keep
+/// working when it restarts.
+fn subscribe(conn: &Connection, signals: &Sender<Signal>) -> Result<(), WifiConfigError> {
+    let sender = signals.clone();
+    let rule = MatchRule::new_signal("org.freedesktop.DBus", "NameOwnerChanged")
+        .with_sender("org.freedesktop.DBus");
+    conn.add_match(
+        rule,
+        move |(name, _, owner): (String, String, String), _, _| {
+            name != NM_BUS_NAME || sender.send(Signal::NameOwner { owner }).is_ok()
+        },
+    )
+    .map_err(WifiConfigError::DeviceQueryFailed)?;
+
+    let sender = signals.clone();
+    let rule = MatchRule::new_signal(NM_INTERFACE, "DeviceAdded").with_path(NM_PATH);
+    conn.add_match(rule, move |(_,): (dbus::Path<'static>,), _, _| {
+        sender.send(Signal::DeviceAdded).is_ok()
+    })
+    .map_err(WifiConfigError::DeviceQueryFailed)?;
+
+    let sender = signals.clone();
+    let rule = MatchRule::new_signal(NM_INTERFACE, "DeviceRemoved").with_path(NM_PATH);
+    conn.add_match(rule, move |(device,): (dbus::Path<'static>,), _, _| {
+        sender.send(Signal::DeviceRemoved(device)).is_ok()
+    })
+    .map_err(WifiConfigError::DeviceQueryFailed)?;
+
+    let sender = signals.clone();
+    let rule =
+        MatchRule::new_signal(DEVICE_INTERFACE, "StateChanged").with_namespaced_path(NM_PATH);
+    conn.add_match(
+        rule,
+        move |(new, old, reason): (u32, u32, u32), _, message| {
+            let Some(device) = message.path() else {
+                return true;
+            };
+            let event = DeviceStateEvent {
+                old: DeviceState::from(old),
+                new: DeviceState::from(new),
+                reason: DeviceStateReason::from(reason),
+            };
+            let device = device.into_static();
+            sender.send(Signal::StateChanged { device, event }).is_ok()
+        },
+    )
+    .map_err(WifiConfigError::DeviceQueryFailed)?;
+
+    let sender = signals.clone();
+    let rule = MatchRule::new_signal("org.freedesktop.DBus.Properties", "PropertiesChanged")
+        .with_namespaced_path(NM_PATH);
+    conn.add_match(
+        rule,
+        move |(interface, changed, _): (String, PropMap, Vec<String>), _, message| {
+            let Some(path) = message.path() else {
+                return true;
+            };
+            let signal = Signal::Properties {
+                path: path.into_static(),
+                active_access_point: (interface == WIRELESS_INTERFACE)
+                    .then(|| prop_cast::<dbus::Path<'static>>(&changed, "ActiveAccessPoint"))
+                    .flatten()
+                    .cloned(),
+                strength: (interface == ACCESS_POINT_INTERFACE)
+                    .then(|| prop_cast::<u8>(&changed, "Strength"))
+                    .flatten()
+                    .copied(),
+            };
+            sender.send(signal).is_ok()
+        },
+    )
+    .map_err(WifiConfigError::DeviceQueryFailed)?;
+    Ok(())
+}
+
+/// What [`watch_wifi`]'s thread knows about the device it watches.
+struct Tracker {
+    /// The interface asked for; the first Wi-Fi device if `None`.
+    interface: Option<String>,
+    /// Path and interface name of the device, while NetworkManager exports it.
+    device: Option<(dbus::Path<'static>, String)>,
+    /// The access point the device is associated with.
+    access_point: Option<dbus::Path<'static>>,
+}
+
+impl Tracker {
+    /// The events `signal` amounts to.
+    fn handle(&mut self, conn: &Connection, signal: Signal) -> Vec<WifiEvent> {
+        match signal {
+            Signal::NameOwner { owner } if owner.is_empty() => {
+                log::info!("NetworkManager left the bus");
+                self.device = None;
+                self.access_point = None;
+                vec![WifiEvent::NetworkManagerStopped]
+            }
+            Signal::NameOwner { .. } => {
+                log::info!("NetworkManager is back on the bus");
+                let mut events = vec![WifiEvent::NetworkManagerStarted];
+                events.extend(self.find_device(conn));
+                events
+            }
+            Signal::DeviceAdded if self.device.is_none() => self.find_device(conn),
+            Signal::DeviceAdded => Vec::new(),
+            Signal::DeviceRemoved(path) => {
+                if self
+                    .device
+                    .as_ref()
+                    .is_some_and(|(device, _)| *device == path)
+                {
+                    log::info!("the watched Wi-Fi device {} went away", path);
+                    self.device = None;
+                    self.access_point = None;
+                }
+                Vec::new()
+            }
+            Signal::StateChanged { device, event } => match &self.device {
+                Some((path, interface)) if *path == device => {
+                    vec![WifiEvent::StateChanged {
+                        interface: interface.clone(),
+                        old: event.old,
+                        new: event.new,
+                        reason: event.reason,
+                    }]
+                }
+                _ => Vec::new(),
+            },
+            Signal::Properties {
+                path,
+                active_access_point,
+                strength,
+            } => {
+                let Some((device, interface)) = &self.device else {
+                    return Vec::new();
+                };
+                let mut events = Vec::new();
+                if let Some(access_point) = active_access_point.filter(|_| *device == path) {
+                    let access_point = Some(access_point).filter(|path| &**path != NO_OBJECT_PATH);
+                    if access_point != self.access_point {
+                        events.push(WifiEvent::AccessPointChanged {
+                            interface: interface.clone(),
+                            access_point: access_point
+                                .as_ref()
+                                .and_then(|path| access_point_info(conn, path)),
+                        });
+                        self.access_point = access_point;
+                    }
+                }
+                if let Some(strength) = strength.filter(|_| self.access_point == Some(path)) {
+                    events.push(WifiEvent::StrengthChanged {
+                        interface: interface.clone(),
+                        strength,
+                    });
+                }
+                events
+            }
+        }
+    }
+
+    /// Looks the device up again, e.g. after NetworkManager restarted; reports the
+    /// access point it is already associated with, if any.
+    fn find_device(&mut self, conn: &Connection) -> Vec<WifiEvent> {
+        let client = DbusNmClient::new(conn, DbusTimeouts::default());
+        let found = device::wifi_devices(&client).and_then(|devices| {
+            device::select_wifi_device(devices, None, self.interface.as_deref())
+        });
+        let device = match found {
+            Ok(device) => device,
+            Err(e) => {
+                log::debug!("the Wi-Fi device isn't back yet: {}", e);
+                return Vec::new();
+            }
+        };
+        log::info!("watching {} again", device.interface);
+        let interface = device.interface.clone();
+        self.device = Some((device.path, device.interface));
+        self.access_point = self.active_access_point(conn);
+        match &self.access_point {
+            Some(path) => vec![WifiEvent::AccessPointChanged {
+                interface,
+                access_point: access_point_info(conn, path),
+            }],
+            None => Vec::new(),
+        }
+    }
+
+    /// The access point the device is associated with, read from the bus.
+    fn active_access_point(&self, conn: &Connection) -> Option<dbus::Path<'static>> {
+        let (device, _) = self.device.as_ref()?;
+        let client = DbusNmClient::new(conn, DbusTimeouts::default());
+        match client.wireless_properties(device) {
+            Ok(props) => prop_cast::<dbus::Path<'static>>(&props, "ActiveAccessPoint")
+                .filter(|path| &***path != NO_OBJECT_PATH)
+                .cloned(),
+            Err(e) => {
+                log::warn!("can't read the access point of {}: {}", device, e);
+                None
+            }
+        }
+    }
+}
+
+/// The properties of the access point at `path`; `None`, with a warning, if it can't be
+/// read, e.g. because it went out of range in the meantime.
+fn access_point_info(conn: &Connection, path: &dbus::Path<'static>) -> Option<AccessPointInfo> {
+    let client = DbusNmClient::new(conn, DbusTimeouts::default());
+    match client.access_point_properties(path) {
+        Ok(props) => Some(AccessPointInfo::from_properties(path.clone(), &props)),
+        Err(e) => {
+            log::warn!("can't read access point {}: {}", path, e);
+            None
+        }
+    }
+}
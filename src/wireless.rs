@@ -0,0 +1,472 @@
+use dbus::arg::{PropMap, Variant};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{Security, WifiConfigError};
+
+/// Operating mode of the Wi-Fi device for a connection (`802-11-wireless.mode`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum WirelessMode {
+    /// Join an access point.
+    #[default]
+    Infrastructure,
+    /// Join or start an ad-hoc (IBSS) cell; needs a fixed band and channel.
+    Adhoc,
+    /// Act as an access point. See also [`create_hotspot`](crate::create_hotspot).
+    Ap,
+}
+
+impl WirelessMode {
+    pub(crate) fn as_nm(self) -> &'static str {
+        match self {
+            WirelessMode::Infrastructure => "infrastructure",
+            WirelessMode::Adhoc => "adhoc",
+            WirelessMode::Ap => "ap",
+        }
+    }
+
+    /// Rejects combinations NetworkManager doesn't accept.
+    ///
+    /// IBSS cells only support open networks, WEP and WPA-PSK; WPA3, OWE and 802.1X need
+    /// an access point. An access point can't authenticate clients via 802.1X itself.
+    pub(crate) fn validate(
+        self,
+        security: &Security,
+        band: Option<Band>,
+        channel: Option<u32>,
+    ) -> Result<(), WifiConfigError> {
+        validate_band_channel(band, channel).map_err(WifiConfigError::InvalidWirelessConfig)?;
+        match (self, security) {
+            (WirelessMode::Adhoc | WirelessMode::Ap, Security::Auto(_)) => {
+                Err(WifiConfigError::InvalidWirelessConfig(
+                    "security can only be detected when joining an access point".into(),
+                ))
+            }
+            (
+                WirelessMode::Adhoc,
+                Security::Wpa3Sae(_)
+                | Security::Wpa3Transition(_)
+                | Security::Enterprise(_)
+                | Security::Owe,
+            ) => Err(WifiConfigError::InvalidWirelessConfig(
+                "ad-hoc networks only support open, WEP and WPA-PSK security".into(),
+            )),
+            (WirelessMode::Adhoc, _) if band.is_none() || channel.is_none() => {
+                Err(WifiConfigError::InvalidWirelessConfig(
+                    "ad-hoc networks need a fixed band and channel".into(),
+                ))
+            }
+            (WirelessMode::Ap, Security::Enterprise(_)) => {
+                Err(WifiConfigError::InvalidWirelessConfig(
+                    "access points don't support enterprise security".into(),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Frequency band (`802-11-wireless.band`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Band {
+    /// 2.4 GHz (`bg`), channels 1–14.
+    Bg,
+    /// 5 GHz (`a`).
+    A,
+}
+
+impl Band {
+    pub(crate) fn as_nm(self) -> &'static str {
+        match self {
+            Band::Bg => "bg",
+            Band::A => "a",
+        }
+    }
+}
+
+/// The channel number of a frequency in MHz, in the 2.4, 5 or 6 GHz band.
+///
+/// `None` for frequencies that aren't a channel's center frequency, e.g. the 0 that the
+/// iwd backend reports.
+///
+/// ```
+/// use wifi_config::frequency_to_channel;
+///
+/// assert_eq!(frequency_to_channel(2437), Some(6));
+/// assert_eq!(frequency_to_channel(5180), Some(36));
+/// assert_eq!(frequency_to_channel(5955), Some(1));
+/// ```
+pub fn frequency_to_channel(frequency: u32) -> Option<u32> {
+    let (base, channels) = match frequency {
+        2484 => return Some(14),
+        // The only 6 GHz channel off the 5955 MHz + 5 MHz * n grid.
+        5935 => return Some(2),
+        2412..=2472 => (2407, 1..=13),
+        5160..=5885 => (5000, 32..=177),
+        5955..=7115 => (5950, 1..=233),
+        _ => return None,
+    };
+    let offset = frequency - base;
+    Some(offset / 5).filter(|channel| offset.is_multiple_of(5) && channels.contains(channel))
+}
+
+/// Checks that the SSID is 1 to 32 bytes long, as 802.11 requires. The bytes themselves
+/// may be anything; SSIDs need not be UTF-8.
+pub(crate) fn validate_ssid(ssid: &[u8]) -> Result<(), WifiConfigError> {
+    match ssid.len() {
+        0 => Err(WifiConfigError::InvalidSsid(
+            "SSID must not be empty".into(),
+        )),
+        1..=32 => Ok(()),
+        len => Err(WifiConfigError::InvalidSsid(format!(
+            "SSID is {} bytes long; at most 32 are allowed",
+            len
+        ))),
+    }
+}
+
+/// Checks that a channel comes with a band and lies within it; returns the reason if not.
+pub(crate) fn validate_band_channel(
+    band: Option<Band>,
+    channel: Option<u32>,
+) -> Result<(), String> {
+    match (band, channel) {
+        (None, Some(_)) => Err("a channel requires a band".into()),
+        (Some(Band::Bg), Some(channel)) if !(1..=14).contains(&channel) => {
+            Err(format!("channel {} is not a 2.4 GHz channel", channel))
+        }
+        (Some(Band::A), Some(channel)) if !(32..=177).contains(&channel) => {
+            Err(format!("channel {} is not a 5 GHz channel", channel))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The smallest MTU an IPv4 host must accept (RFC 791); smaller overrides are rejected.
+pub(crate) const MIN_MTU: u32 = 576;
+
+/// Checks an `802-11-wireless.mtu` override; `0` leaves the MTU automatic.
+pub(crate) fn validate_mtu(mtu: Option<u32>) -> Result<(), String> {
+    match mtu {
+        Some(mtu) if mtu != 0 && mtu < MIN_MTU => Err(format!(
+            "an MTU of {} is below the minimum of {}; use 0 for automatic",
+            mtu, MIN_MTU
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// MAC address the device uses on this connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum MacPolicy {
+    /// A new random MAC address on every activation.
+    Random,
+    /// A random MAC address that stays the same for this connection on this host.
+    Stable,
+    /// Keep whatever MAC address the device currently has.
+    Preserve,
+    /// Use this MAC address.
+    Explicit(#[cfg_attr(feature = "serde", serde(with = "crate::json::mac_octets"))] [u8; 6]),
+}
+
+impl MacPolicy {
+    /// Writes the policy into an `802-11-wireless` section.
+    ///
+    /// NetworkManager is strict about the types here: the policy words go into the string
+    /// property `assigned-mac-address`, while `cloned-mac-address` only takes a byte array.
+    pub(crate) fn apply(self, wifi_settings: &mut PropMap) {
+        let word = match self {
+            MacPolicy::Random => "random",
+            MacPolicy::Stable => "stable",
+            MacPolicy::Preserve => "preserve",
+            MacPolicy::Explicit(octets) => {
+                wifi_settings.insert(
+                    "cloned-mac-address".into(),
+                    Variant(Box::new(octets.to_vec())),
+                );
+                return;
+            }
+        };
+        wifi_settings.insert(
+            "assigned-mac-address".into(),
+            Variant(Box::new(word.to_string())),
+        );
+    }
+}
+
+/// MAC address randomization while scanning (`802-11-wireless.mac-address-randomization`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ScanMacRandomization {
+    /// Follow NetworkManager's global configuration.
+    Default,
+    /// Always scan with the real MAC address.
+    Never,
+    /// Always scan with a random MAC address.
+    Always,
+}
+
+impl ScanMacRandomization {
+    pub(crate) fn to_nm(self) -> u32 {
+        match self {
+            ScanMacRandomization::Default => 0,
+            ScanMacRandomization::Never => 1,
+            ScanMacRandomization::Always => 2,
+        }
+    }
+}
+
+/// Wi-Fi power saving on the connection (`802-11-wireless.powersave`).
+///
+/// Some drivers' power saving delays incoming frames by 100 ms and more; latency-sensitive
+/// devices want [`PowerSave::Disable`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum PowerSave {
+    /// Follow NetworkManager's global configuration.
+    Default,
+    /// Leave the driver's setting alone.
+    Ignore,
+    /// Turn power saving off.
+    Disable,
+    /// Turn power saving on.
+    Enable,
+}
+
+impl PowerSave {
+    /// The `NMSettingWirelessPowersave` value sent to NetworkManager.
+    ///
+    /// The property is a `u32`; NetworkManager drops a value of any other type without an
+    /// error.
+    pub(crate) fn to_nm(self) -> u32 {
+        match self {
+            PowerSave::Default => 0,
+            PowerSave::Ignore => 1,
+            PowerSave::Disable => 2,
+            PowerSave::Enable => 3,
+        }
+    }
+}
+
+/// Writes `band` and `channel` into an `802-11-wireless` section.
+///
+/// Shared by client and access point profiles so both lock the band the same way.
+pub(crate) fn apply_band_channel(
+    band: Option<Band>,
+    channel: Option<u32>,
+    wifi_settings: &mut PropMap,
+) {
+    if let Some(band) = band {
+        wifi_settings.insert("band".into(), Variant(Box::new(band.as_nm().to_string())));
+    }
+    if let Some(channel) = channel {
+        wifi_settings.insert("channel".into(), Variant(Box::new(channel)));
+    }
+}
+
+/// A MAC address such as an access point's BSSID.
+///
+/// Parses from `AA:BB:CC:DD:EE:FF` (case-insensitive) and displays in upper case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MacAddr(pub [u8; 6]);
+
+impl From<[u8; 6]> for MacAddr {
+    fn from(octets: [u8; 6]) -> Self {
+        MacAddr(octets)
+    }
+}
+
+impl FromStr for MacAddr {
+    type Err = WifiConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || WifiConfigError::InvalidMacAddress(s.to_string());
+        let mut octets = [0; 6];
+        let mut parts = s.split(':');
+        for octet in &mut octets {
+            let part = parts.next().ok_or_else(invalid)?;
+            // `from_str_radix` alone would also accept a sign, e.g. `+a`.
+            if part.len() != 2 || !part.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(invalid());
+            }
+            *octet = u8::from_str_radix(part, 16).map_err(|_| invalid())?;
+        }
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(MacAddr(octets))
+    }
+}
+
+/// Serializes as the [`Display`](fmt::Display) form, e.g. `"AA:BB:CC:DD:EE:FF"`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for MacAddr {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Deserializes from the form [`FromStr`] parses.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for MacAddr {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <String as serde::Deserialize>::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl fmt::Display for MacAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(
+            f,
+            "{:02X}:{:02X}:{:02X}:{:02X}:{:02X}:{:02X}",
+            a, b, c, d, e, g
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_mode_combinations() {
+        let psk = Security::WpaPsk("supersecret123".into());
+        let sae = Security::Wpa3Sae("supersecret123".into());
+        let fixed = (Some(Band::Bg), Some(1));
+
+        assert!(WirelessMode::Infrastructure
+            .validate(&sae, None, None)
+            .is_ok());
+        assert!(WirelessMode::Adhoc.validate(&psk, fixed.0, fixed.1).is_ok());
+        assert!(WirelessMode::Adhoc
+            .validate(&Security::Open, fixed.0, fixed.1)
+            .is_ok());
+        assert!(WirelessMode::Adhoc
+            .validate(&sae, fixed.0, fixed.1)
+            .is_err());
+        assert!(WirelessMode::Adhoc.validate(&psk, None, None).is_err());
+        assert!(WirelessMode::Ap.validate(&sae, None, None).is_ok());
+    }
+
+    #[test]
+    fn parses_and_normalizes_mac_addresses() {
+        let mac: MacAddr = "aa:bb:cc:0d:ee:ff".parse().unwrap();
+        assert_eq!(mac, MacAddr([0xaa, 0xbb, 0xcc, 0x0d, 0xee, 0xff]));
+        assert_eq!(mac.to_string(), "AA:BB:CC:0D:EE:FF");
+        #[cfg(feature = "serde")]
+        assert_eq!(
+            serde_json::to_string(&mac).unwrap(),
+            "\"AA:BB:CC:0D:EE:FF\""
+        );
+
+        for invalid in [
+            "",
+            "aa:bb:cc:dd:ee",
+            "aa:bb:cc:dd:ee:ff:00",
+            "a:bb:cc:dd:ee:ff",
+            "gg:bb:cc:dd:ee:ff",
+            "+a:bb:cc:dd:ee:ff",
+        ] {
+            assert!(
+                matches!(
+                    invalid.parse::<MacAddr>(),
+                    Err(WifiConfigError::InvalidMacAddress(_))
+                ),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn mac_policy_uses_the_right_property_types() {
+        let mut wifi_settings = PropMap::new();
+        MacPolicy::Stable.apply(&mut wifi_settings);
+        assert_eq!(
+            wifi_settings["assigned-mac-address"].0.as_str(),
+            Some("stable")
+        );
+        assert!(!wifi_settings.contains_key("cloned-mac-address"));
+
+        let mut wifi_settings = PropMap::new();
+        MacPolicy::Explicit([0x02, 0, 0, 0, 0, 0x01]).apply(&mut wifi_settings);
+        let cloned: Vec<u8> = wifi_settings["cloned-mac-address"]
+            .0
+            .as_iter()
+            .unwrap()
+            .map(|b| b.as_u64().unwrap() as u8)
+            .collect();
+        assert_eq!(cloned, [0x02, 0, 0, 0, 0, 0x01]);
+    }
+
+    #[test]
+    fn converts_frequencies_to_channels() {
+        let channels: Vec<Option<u32>> = [2412, 2472, 2484, 5180, 5825, 5935, 6415, 7115, 2413, 0]
+            .into_iter()
+            .map(frequency_to_channel)
+            .collect();
+        assert_eq!(
+            channels,
+            [
+                Some(1),
+                Some(13),
+                Some(14),
+                Some(36),
+                Some(165),
+                Some(2),
+                Some(93),
+                Some(233),
+                None,
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn validates_band_channel() {
+        assert!(validate_band_channel(None, None).is_ok());
+        assert!(validate_band_channel(Some(Band::A), Some(36)).is_ok());
+        assert!(validate_band_channel(None, Some(6)).is_err());
+        assert!(validate_band_channel(Some(Band::A), Some(6)).is_err());
+        assert!(validate_band_channel(Some(Band::Bg), Some(36)).is_err());
+    }
+
+    #[test]
+    fn validates_mtu() {
+        assert!(validate_mtu(None).is_ok());
+        assert!(validate_mtu(Some(0)).is_ok());
+        assert!(validate_mtu(Some(MIN_MTU)).is_ok());
+        assert!(validate_mtu(Some(1500)).is_ok());
+        assert_eq!(
+            validate_mtu(Some(575)).unwrap_err(),
+            "an MTU of 575 is below the minimum of 576; use 0 for automatic"
+        );
+        assert!(validate_mtu(Some(1)).is_err());
+    }
+}
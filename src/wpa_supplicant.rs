@@ -0,0 +1,731 @@
+//! [`Backend::WpaSupplicant`](crate::Backend::WpaSupplicant): connecting through
+//! wpa_supplicant's own D-Bus API, for systems that don't run NetworkManager.
+
+use dbus::arg::{prop_cast, PropMap, RefArg, Variant};
+use dbus::blocking::{stdintf::org_freedesktop_dbus::Properties, Connection};
+use dbus::channel::Token;
+use dbus::message::MatchRule;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::backend::{self, WifiBackend};
+use crate::nm::NO_OBJECT_PATH;
+use crate::{scan, secret};
+use crate::{
+    AccessPointInfo, ActivationHandle, ApSecurity, CaCertificate, ConnectOptions, Connectivity,
+    DbusTimeouts, DeviceState, DeviceStateEvent, DeviceStateReason, EnterpriseAuth, MacAddr,
+    ProfileChange, RadioState, Security, WifiConfigError, WifiStatus,
+};
+
+/// Well-known bus name of wpa_supplicant.
+pub(crate) const WPA_BUS_NAME: &str = "fi.w1.wpa_supplicant1";
+/// Object path of the wpa_supplicant root object.
+const WPA_PATH: &str = "/fi/w1/wpa_supplicant1";
+/// Interface of the root object (`GetInterface`, `CreateInterface`, ...).
+const WPA_INTERFACE: &str = "fi.w1.wpa_supplicant1";
+/// Interface implemented by every interface (network device) object.
+const WPA_IFACE_INTERFACE: &str = "fi.w1.wpa_supplicant1.Interface";
+/// Interface implemented by every BSS (access point) object.
+const WPA_BSS_INTERFACE: &str = "fi.w1.wpa_supplicant1.BSS";
+/// Interface implemented by every configured network object.
+const WPA_NETWORK_INTERFACE: &str = "fi.w1.wpa_supplicant1.Network";
+/// Returned by `GetInterface` when wpa_supplicant doesn't control the interface yet.
+const INTERFACE_UNKNOWN_ERROR: &str = "fi.w1.wpa_supplicant1.InterfaceUnknown";
+/// Interface of the standard `PropertiesChanged` signal.
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+
+/// [`Backend::WpaSupplicant`](crate::Backend::WpaSupplicant).
+pub(crate) struct WpaSupplicantBackend;
+
+impl WifiBackend for WpaSupplicantBackend {
+    fn connect(
+        &self,
+        ssid: &[u8],
+        options: &ConnectOptions,
+    ) -> Result<ActivationHandle, WifiConfigError> {
+        check_supported(options)?;
+        let network = network_properties(ssid, options)?;
+        let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+        let interface_path = find_interface(&conn, options)?;
+        let (network_path, change) = add_network(&conn, &interface_path, ssid, network, options)?;
+        select_network(&conn, &interface_path, &network_path, options)?;
+        handle(
+            &conn,
+            options.timeouts.query,
+            interface_path,
+            network_path,
+            change,
+        )
+    }
+
+    fn connect_and_wait(
+        &self,
+        ssid: &[u8],
+        options: &ConnectOptions,
+        _progress: &mut dyn FnMut(&DeviceStateEvent),
+    ) -> Result<ActivationHandle, WifiConfigError> {
+        check_supported(options)?;
+        let network = network_properties(ssid, options)?;
+        let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+        let interface_path = find_interface(&conn, options)?;
+        let (network_path, change) = add_network(&conn, &interface_path, ssid, network, options)?;
+        // Subscribe first so no state change between selecting and waiting is missed.
+        let watcher = StateWatcher::new(&conn, &interface_path)?;
+        select_network(&conn, &interface_path, &network_path, options)?;
+        watcher.wait(options.activation_timeout())?;
+        handle(
+            &conn,
+            options.timeouts.query,
+            interface_path,
+            network_path,
+            change,
+        )
+    }
+
+    fn scan_networks(
+        &self,
+        fresh: bool,
+        timeouts: DbusTimeouts,
+    ) -> Result<Vec<AccessPointInfo>, WifiConfigError> {
+        backend::check_cached_scan("wpa_supplicant", fresh)?;
+        let timeout = timeouts.query;
+        let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+        let interface_path = first_interface(&conn, timeout)?;
+        let bss_paths: Vec<dbus::Path<'static>> = conn
+            .with_proxy(WPA_BUS_NAME, &interface_path, timeout)
+            .get(WPA_IFACE_INTERFACE, "BSSs")
+            .map_err(WifiConfigError::ScanFailed)?;
+        bss_paths
+            .into_iter()
+            .map(|path| {
+                let props = conn
+                    .with_proxy(WPA_BUS_NAME, &path, timeout)
+                    .get_all(WPA_BSS_INTERFACE)
+                    .map_err(WifiConfigError::ScanFailed)?;
+                Ok(access_point(path, &props))
+            })
+            .collect()
+    }
+
+    fn current_status(
+        &self,
+        timeouts: DbusTimeouts,
+    ) -> Result<Option<WifiStatus>, WifiConfigError> {
+        let timeout = timeouts.query;
+        let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+        let interface_path = first_interface(&conn, timeout)?;
+        let props = conn
+            .with_proxy(WPA_BUS_NAME, &interface_path, timeout)
+            .get_all(WPA_IFACE_INTERFACE)
+            .map_err(WifiConfigError::DeviceQueryFailed)?;
+        let bss_path = match prop_cast::<dbus::Path<'static>>(&props, "CurrentBSS") {
+            Some(path) if &**path != NO_OBJECT_PATH => path.clone(),
+            _ => return Ok(None),
+        };
+        let bss_props = conn
+            .with_proxy(WPA_BUS_NAME, &bss_path, timeout)
+            .get_all(WPA_BSS_INTERFACE)
+            .map_err(WifiConfigError::DeviceQueryFailed)?;
+        let ap = access_point(bss_path, &bss_props);
+        Ok(Some(WifiStatus {
+            interface: prop_cast::<String>(&props, "Ifname")
+                .cloned()
+                .unwrap_or_default(),
+            state: device_state(prop_cast::<String>(&props, "State").map_or("", String::as_str)),
+            connection_id: ap.ssid_lossy.clone(),
+            bssid: ap.bssid(),
+            ssid: ap.ssid,
+            ssid_lossy: ap.ssid_lossy,
+            strength: ap.strength,
+            frequency: ap.frequency,
+            ipv4_address: None,
+            mtu: None,
+            radio: RadioState::Enabled,
+            connectivity: Connectivity::Unknown,
+            portal_url: None,
+        }))
+    }
+}
+
+/// wpa_supplicant has no profile UUIDs, so the handle only carries the object paths and
+/// the interface name.
+fn handle(
+    conn: &Connection,
+    timeout: Duration,
+    interface_path: dbus::Path<'static>,
+    network_path: dbus::Path<'static>,
+    change: ProfileChange,
+) -> Result<ActivationHandle, WifiConfigError> {
+    let interface: String = conn
+        .with_proxy(WPA_BUS_NAME, &interface_path, timeout)
+        .get(WPA_IFACE_INTERFACE, "Ifname")
+        .map_err(WifiConfigError::DeviceQueryFailed)?;
+    Ok(ActivationHandle {
+        settings_path: network_path,
+        active_path: interface_path,
+        interface,
+        additional: Vec::new(),
+        uuid: String::new(),
+        change,
+        detected_security: None,
+        connectivity: None,
+        portal_url: None,
+        ip: None,
+        active: None,
+        access_point: None,
+    })
+}
+
+/// Rejects the options wpa_supplicant can't apply.
+fn check_supported(options: &ConnectOptions) -> Result<(), WifiConfigError> {
+    backend::check_supported(
+        "wpa_supplicant",
+        options,
+        &[
+            (
+                matches!(options.security, Security::Auto(_)),
+                "automatic security detection",
+            ),
+            (options.autoconnect == Some(false), "disabling autoconnect"),
+        ],
+    )
+}
+
+/// The `AddNetwork` properties holding secrets.
+const NETWORK_SECRETS: &[&str] = &["psk", "wep_key0", "password", "private_key_passwd"];
+
+/// Builds the `AddNetwork` properties.
+///
+/// wpa_supplicant quotes string values itself (except for keywords such as `key_mgmt`)
+/// and hex-encodes byte arrays, so raw keys and the SSID are sent as bytes.
+fn network_properties(ssid: &[u8], options: &ConnectOptions) -> Result<PropMap, WifiConfigError> {
+    let mut network = PropMap::new();
+    let mut insert = |key: &str, value: Box<dyn RefArg>| {
+        network.insert(key.into(), Variant(value));
+    };
+    insert("ssid", Box::new(ssid.to_vec()));
+    if options.hidden {
+        insert("scan_ssid", Box::new(1i32));
+    }
+    if let Some(bssid) = options.bssid {
+        insert("bssid", Box::new(bssid.to_string()));
+    }
+    if let Some(priority) = options.autoconnect_priority {
+        insert("priority", Box::new(priority));
+    }
+
+    match &options.security {
+        Security::Open => insert("key_mgmt", Box::new(String::from("NONE"))),
+        Security::WpaPsk(password) => {
+            insert("key_mgmt", Box::new(String::from("WPA-PSK")));
+            insert("psk", passphrase(password));
+        }
+        Security::Wpa3Sae(password) => {
+            insert("key_mgmt", Box::new(String::from("SAE")));
+            insert("psk", Box::new(password.to_string()));
+            insert("ieee80211w", Box::new(2i32));
+        }
+        Security::Wpa3Transition(password) => {
+            insert("key_mgmt", Box::new(String::from("WPA-PSK SAE")));
+            insert("psk", passphrase(password));
+            insert("ieee80211w", Box::new(1i32));
+        }
+        Security::Owe => {
+            insert("key_mgmt", Box::new(String::from("OWE")));
+            insert("ieee80211w", Box::new(2i32));
+        }
+        Security::Wep(key) => {
+            insert("key_mgmt", Box::new(String::from("NONE")));
+            insert(
+                "wep_key0",
+                match hex_bytes(key) {
+                    Some(bytes) if key.len() == 10 || key.len() == 26 => Box::new(bytes),
+                    _ => Box::new(key.to_string()),
+                },
+            );
+            insert("wep_tx_keyidx", Box::new(0i32));
+        }
+        Security::Enterprise(auth) => {
+            insert("key_mgmt", Box::new(String::from("WPA-EAP")));
+            match auth {
+                EnterpriseAuth::Peap {
+                    identity,
+                    password,
+                    anonymous_identity,
+                } => {
+                    insert("eap", Box::new(String::from("PEAP")));
+                    insert("phase2", Box::new(String::from("auth=MSCHAPV2")));
+                    insert("identity", Box::new(identity.clone()));
+                    insert("password", Box::new(password.to_string()));
+                    if let Some(anonymous_identity) = anonymous_identity {
+                        insert("anonymous_identity", Box::new(anonymous_identity.clone()));
+                    }
+                }
+                EnterpriseAuth::Tls {
+                    identity,
+                    ca_cert,
+                    client_cert,
+                    private_key,
+                    private_key_password,
+                } => {
+                    insert("eap", Box::new(String::from("TLS")));
+                    insert("identity", Box::new(identity.clone()));
+                    if let CaCertificate::Path(ca_cert) = ca_cert {
+                        insert("ca_cert", Box::new(config_path(ca_cert)?));
+                    }
+                    insert("client_cert", Box::new(config_path(client_cert)?));
+                    insert("private_key", Box::new(config_path(private_key)?));
+                    if let Some(private_key_password) = private_key_password {
+                        insert(
+                            "private_key_passwd",
+                            Box::new(private_key_password.to_string()),
+                        );
+                    }
+                }
+            }
+        }
+        Security::Auto(_) => unreachable!("rejected by check_supported"),
+    }
+    Ok(network)
+}
+
+/// A WPA passphrase, or the bytes of a raw 64-digit hex PSK.
+fn passphrase(password: &str) -> Box<dyn RefArg> {
+    match hex_bytes(password) {
+        Some(key) if password.len() == 64 => Box::new(key),
+        _ => Box::new(password.to_string()),
+    }
+}
+
+/// The absolute path of a certificate or key file, as wpa_supplicant only takes UTF-8.
+fn config_path(path: &std::path::Path) -> Result<String, WifiConfigError> {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    path.to_str().map(str::to_string).ok_or_else(|| {
+        WifiConfigError::UnsupportedByBackend(format!(
+            "wpa_supplicant can't read certificate paths that aren't UTF-8: {}",
+            path.display()
+        ))
+    })
+}
+
+/// Decodes an even number of hex digits; `None` for anything else.
+fn hex_bytes(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// Decodes the `ssid` of a configured network: a quoted string, or hex digits for SSIDs
+/// that aren't printable.
+fn config_ssid(value: &str) -> Option<Vec<u8>> {
+    match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(text) => Some(text.as_bytes().to_vec()),
+        None => hex_bytes(value),
+    }
+}
+
+/// Returns the interface object for `options.interface`, asking wpa_supplicant to take
+/// control of it if it doesn't yet, or the first interface it controls.
+fn find_interface(
+    conn: &Connection,
+    options: &ConnectOptions,
+) -> Result<dbus::Path<'static>, WifiConfigError> {
+    let Some(interface) = options.interface.as_deref() else {
+        return first_interface(conn, options.timeouts.query);
+    };
+    let proxy = conn.with_proxy(WPA_BUS_NAME, WPA_PATH, options.timeouts.query);
+
+    let result: Result<(dbus::Path<'static>,), _> =
+        proxy.method_call(WPA_INTERFACE, "GetInterface", (interface,));
+    match result {
+        Ok((path,)) => Ok(path),
+        Err(e) if e.name() == Some(INTERFACE_UNKNOWN_ERROR) => {
+            let mut args = PropMap::new();
+            args.insert(
+                "Ifname".into(),
+                Variant(Box::new(interface.to_string()) as Box<dyn RefArg>),
+            );
+            let (path,): (dbus::Path<'static>,) = proxy
+                .method_call(WPA_INTERFACE, "CreateInterface", (args,))
+                .map_err(|_| WifiConfigError::DeviceNotFound(interface.to_string()))?;
+            Ok(path)
+        }
+        Err(e) => Err(WifiConfigError::DeviceQueryFailed(e)),
+    }
+}
+
+/// Returns the first interface wpa_supplicant controls.
+fn first_interface(
+    conn: &Connection,
+    timeout: Duration,
+) -> Result<dbus::Path<'static>, WifiConfigError> {
+    let interfaces: Vec<dbus::Path<'static>> = conn
+        .with_proxy(WPA_BUS_NAME, WPA_PATH, timeout)
+        .get(WPA_INTERFACE, "Interfaces")
+        .map_err(WifiConfigError::DeviceQueryFailed)?;
+    interfaces
+        .into_iter()
+        .next()
+        .ok_or(WifiConfigError::NoWifiDevice)
+}
+
+/// Builds an access point from its `BSS` interface properties.
+fn access_point(path: dbus::Path<'static>, props: &PropMap) -> AccessPointInfo {
+    let ssid = prop_cast::<Vec<u8>>(props, "SSID")
+        .cloned()
+        .unwrap_or_default();
+    let hw_address = match prop_cast::<Vec<u8>>(props, "BSSID").map(Vec::as_slice) {
+        Some(&[a, b, c, d, e, f]) => MacAddr([a, b, c, d, e, f]).to_string(),
+        _ => String::new(),
+    };
+    let key_mgmt: Vec<String> = ["RSN", "WPA"]
+        .iter()
+        .flat_map(|key| key_mgmt(props, key))
+        .collect();
+    let privacy = prop_cast::<bool>(props, "Privacy")
+        .copied()
+        .unwrap_or(false);
+    AccessPointInfo {
+        path,
+        ssid_lossy: String::from_utf8_lossy(&ssid).into_owned(),
+        ssid,
+        strength: scan::dbm_to_percent(
+            prop_cast::<i16>(props, "Signal")
+                .copied()
+                .unwrap_or(i16::MIN) as i32,
+        ),
+        frequency: prop_cast::<u16>(props, "Frequency").copied().unwrap_or(0) as u32,
+        // `Rates` is in bit/s.
+        max_bitrate: prop_cast::<Vec<u32>>(props, "Rates")
+            .and_then(|rates| rates.iter().max())
+            .map_or(0, |rate| rate / 1000),
+        hw_address,
+        flags: 0,
+        wpa_flags: 0,
+        rsn_flags: 0,
+        security: security_from_key_mgmt(&key_mgmt, privacy),
+    }
+}
+
+/// Reads the `KeyMgmt` strings (`wpa-psk`, `sae`, ...) of the `RSN` or `WPA` property.
+fn key_mgmt(props: &PropMap, key: &str) -> Vec<String> {
+    let Some(mut entries) = props.get(key).and_then(|dict| dict.0.as_iter()) else {
+        return Vec::new();
+    };
+    while let (Some(name), Some(value)) = (entries.next(), entries.next()) {
+        if name.as_str() == Some("KeyMgmt") {
+            return strings(value);
+        }
+    }
+    Vec::new()
+}
+
+/// Collects the strings in a (possibly nested) array or variant.
+fn strings(arg: &dyn RefArg) -> Vec<String> {
+    match (arg.as_str(), arg.as_iter()) {
+        (Some(s), _) => vec![s.to_string()],
+        (None, Some(items)) => items.flat_map(strings).collect(),
+        (None, None) => Vec::new(),
+    }
+}
+
+/// Decodes the advertised key management suites; the strongest one wins, as in
+/// [`ApSecurity::from_flags`].
+fn security_from_key_mgmt(key_mgmt: &[String], privacy: bool) -> ApSecurity {
+    let offers = |suite: &str| key_mgmt.iter().any(|k| k.contains(suite));
+    if offers("sae") {
+        ApSecurity::Sae
+    } else if offers("eap") {
+        ApSecurity::WpaEap
+    } else if offers("psk") {
+        ApSecurity::WpaPsk
+    } else if offers("owe") {
+        ApSecurity::Owe
+    } else if privacy {
+        ApSecurity::Wep
+    } else {
+        ApSecurity::Open
+    }
+}
+
+/// Maps an interface `State` to the closest NetworkManager device state.
+fn device_state(state: &str) -> DeviceState {
+    match state {
+        "completed" => DeviceState::Activated,
+        "authenticating" | "associating" | "associated" | "4way_handshake" | "group_handshake" => {
+            DeviceState::Config
+        }
+        "disconnected" | "inactive" | "scanning" => DeviceState::Disconnected,
+        "interface_disabled" => DeviceState::Unavailable,
+        _ => DeviceState::Unknown,
+    }
+}
+
+/// Adds the network to the interface.
+///
+/// Unless `options.allow_duplicates` is set, networks already configured for `ssid` are
+/// removed first, so repeated provisioning doesn't pile them up; the change is then
+/// reported as [`ProfileChange::Updated`].
+fn add_network(
+    conn: &Connection,
+    interface_path: &dbus::Path,
+    ssid: &[u8],
+    mut network: PropMap,
+    options: &ConnectOptions,
+) -> Result<(dbus::Path<'static>, ProfileChange), WifiConfigError> {
+    let proxy = conn.with_proxy(WPA_BUS_NAME, interface_path, options.timeouts.query);
+    let mut change = ProfileChange::Created;
+    if !options.allow_duplicates {
+        let networks: Vec<dbus::Path<'static>> = proxy
+            .get(WPA_IFACE_INTERFACE, "Networks")
+            .map_err(WifiConfigError::SettingsQueryFailed)?;
+        for network_path in networks {
+            let properties: PropMap = conn
+                .with_proxy(WPA_BUS_NAME, &network_path, options.timeouts.query)
+                .get(WPA_NETWORK_INTERFACE, "Properties")
+                .map_err(WifiConfigError::SettingsQueryFailed)?;
+            let configured = prop_cast::<String>(&properties, "ssid").and_then(|v| config_ssid(v));
+            if configured.as_deref() == Some(ssid) {
+                proxy
+                    .method_call::<(), _, _, _>(
+                        WPA_IFACE_INTERFACE,
+                        "RemoveNetwork",
+                        (&network_path,),
+                    )
+                    .map_err(WifiConfigError::DeleteFailed)?;
+                log::info!("removed network {}", network_path);
+                change = ProfileChange::Updated;
+            }
+        }
+    }
+
+    let mut keys: Vec<&str> = network.keys().map(String::as_str).collect();
+    keys.sort_unstable();
+    log::debug!(
+        "network properties for {}: {}",
+        String::from_utf8_lossy(ssid),
+        keys.join(", ")
+    );
+    let proxy = conn.with_proxy(WPA_BUS_NAME, interface_path, options.timeouts.activate);
+    let added: Result<(dbus::Path<'static>,), _> =
+        proxy.method_call(WPA_IFACE_INTERFACE, "AddNetwork", (&network,));
+    secret::wipe_properties(&mut network, NETWORK_SECRETS);
+    let (network_path,) = added.map_err(WifiConfigError::activation_failed)?;
+    Ok((network_path, change))
+}
+
+/// Makes the interface associate with the network (`SelectNetwork`).
+fn select_network(
+    conn: &Connection,
+    interface_path: &dbus::Path,
+    network_path: &dbus::Path,
+    options: &ConnectOptions,
+) -> Result<(), WifiConfigError> {
+    log::info!("selecting network {} on {}", network_path, interface_path);
+    conn.with_proxy(WPA_BUS_NAME, interface_path, options.timeouts.activate)
+        .method_call(WPA_IFACE_INTERFACE, "SelectNetwork", (network_path,))
+        .map_err(WifiConfigError::activation_failed)
+}
+
+/// Returns `true` for the key exchange states, which are only reached once the access
+/// point accepted the association.
+fn is_handshaking(state: &str) -> bool {
+    matches!(state, "4way_handshake" | "group_handshake")
+}
+
+/// Collects the `State` changes of one interface for as long as it lives.
+struct StateWatcher<'a> {
+    conn: &'a Connection,
+    token: Token,
+    states: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl<'a> StateWatcher<'a> {
+    /// Subscribes to `PropertiesChanged` on `interface_path`.
+    fn new(conn: &'a Connection, interface_path: &dbus::Path) -> Result<Self, WifiConfigError> {
+        let states = Arc::new(Mutex::new(VecDeque::new()));
+        let sink = Arc::clone(&states);
+        let rule = MatchRule::new_signal(PROPERTIES_INTERFACE, "PropertiesChanged")
+            .with_path(interface_path.clone().into_static());
+        let token = conn
+            .add_match(
+                rule,
+                move |(interface, changed, _): (String, PropMap, Vec<String>), _, _| {
+                    if interface == WPA_IFACE_INTERFACE {
+                        if let Some(state) = prop_cast::<String>(&changed, "State") {
+                            sink.lock().unwrap().push_back(state.clone());
+                        }
+                    }
+                    true
+                },
+            )
+            .map_err(WifiConfigError::DeviceQueryFailed)?;
+        Ok(StateWatcher {
+            conn,
+            token,
+            states,
+        })
+    }
+
+    /// Processes signals until the interface reaches `completed` or `timeout` passes.
+    ///
+    /// wpa_supplicant retries failed associations on its own, so a disconnect only ends
+    /// the wait once the key exchange was reached, which means the password was wrong.
+    fn wait(&self, timeout: Duration) -> Result<(), WifiConfigError> {
+        let deadline = Instant::now() + timeout;
+        let mut handshaking = false;
+        loop {
+            while let Some(state) = self.states.lock().unwrap().pop_front() {
+                match state.as_str() {
+                    "completed" => return Ok(()),
+                    "disconnected" | "inactive" if handshaking => {
+                        return Err(WifiConfigError::AuthenticationFailed(
+                            DeviceStateReason::SupplicantDisconnect,
+                        ))
+                    }
+                    "interface_disabled" => {
+                        return Err(WifiConfigError::ConnectionFailed(
+                            DeviceStateReason::SupplicantFailed,
+                        ))
+                    }
+                    state => handshaking |= is_handshaking(state),
+                }
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(WifiConfigError::ActivationTimeout(timeout));
+            }
+            self.conn
+                .process(remaining)
+                .map_err(WifiConfigError::DeviceQueryFailed)?;
+        }
+    }
+}
+
+impl Drop for StateWatcher<'_> {
+    fn drop(&mut self) {
+        let _ = self.conn.remove_match(self.token);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_network_properties() {
+        let options = ConnectOptions {
+            security: Security::WpaPsk("supersecret123".into()),
+            hidden: true,
+            autoconnect_priority: Some(5),
+            ..Default::default()
+        };
+        let network = network_properties(b"Office", &options).unwrap();
+        assert_eq!(
+            prop_cast::<Vec<u8>>(&network, "ssid").map(Vec::as_slice),
+            Some(&b"Office"[..])
+        );
+        assert_eq!(
+            prop_cast::<String>(&network, "key_mgmt").map(String::as_str),
+            Some("WPA-PSK")
+        );
+        // Sent unquoted; wpa_supplicant adds the quotes.
+        assert_eq!(
+            prop_cast::<String>(&network, "psk").map(String::as_str),
+            Some("supersecret123")
+        );
+        assert_eq!(prop_cast::<i32>(&network, "scan_ssid"), Some(&1));
+        assert_eq!(prop_cast::<i32>(&network, "priority"), Some(&5));
+
+        // A raw key goes as bytes, which wpa_supplicant writes back as unquoted hex.
+        let options = ConnectOptions {
+            security: Security::WpaPsk("ab".repeat(32).into()),
+            ..Default::default()
+        };
+        let network = network_properties(b"Office", &options).unwrap();
+        assert_eq!(
+            prop_cast::<Vec<u8>>(&network, "psk").map(Vec::len),
+            Some(32)
+        );
+    }
+
+    #[test]
+    fn rejects_options_that_need_network_manager() {
+        let supported = ConnectOptions {
+            security: Security::Owe,
+            bssid: Some("AA:BB:CC:DD:EE:FF".parse().unwrap()),
+            ..Default::default()
+        };
+        assert!(check_supported(&supported).is_ok());
+
+        for options in [
+            ConnectOptions {
+                dns: vec!["10.0.0.53".parse().unwrap()],
+                ..Default::default()
+            },
+            ConnectOptions {
+                security: Security::Auto(None),
+                ..Default::default()
+            },
+            ConnectOptions {
+                autoconnect: Some(false),
+                ..Default::default()
+            },
+        ] {
+            assert!(matches!(
+                check_supported(&options),
+                Err(WifiConfigError::UnsupportedByBackend(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn decodes_bss_properties() {
+        let mut rsn = PropMap::new();
+        rsn.insert(
+            "KeyMgmt".into(),
+            Variant(Box::new(vec![String::from("wpa-psk"), String::from("sae")])),
+        );
+        rsn.insert(
+            "Pairwise".into(),
+            Variant(Box::new(vec![String::from("ccmp")])),
+        );
+        let mut props = PropMap::new();
+        props.insert("SSID".into(), Variant(Box::new(b"Office".to_vec())));
+        props.insert(
+            "BSSID".into(),
+            Variant(Box::new(vec![0xaau8, 0xbb, 0xcc, 0xdd, 0xee, 0xff])),
+        );
+        props.insert("Signal".into(), Variant(Box::new(-60i16)));
+        props.insert("Frequency".into(), Variant(Box::new(5180u16)));
+        props.insert("Privacy".into(), Variant(Box::new(true)));
+        props.insert("RSN".into(), Variant(Box::new(rsn)));
+
+        let ap = access_point(dbus::Path::new("/bss/0").unwrap(), &props);
+        assert_eq!(ap.ssid, b"Office");
+        assert_eq!(ap.hw_address, "AA:BB:CC:DD:EE:FF");
+        assert_eq!(ap.strength, 80);
+        assert_eq!(ap.frequency, 5180);
+        assert_eq!(ap.security, ApSecurity::Sae);
+
+        assert_eq!(security_from_key_mgmt(&[], true), ApSecurity::Wep);
+        assert_eq!(security_from_key_mgmt(&[], false), ApSecurity::Open);
+        assert_eq!(
+            security_from_key_mgmt(&["wpa-eap".into()], true),
+            ApSecurity::WpaEap
+        );
+    }
+
+    #[test]
+    fn decodes_configured_ssids() {
+        assert_eq!(config_ssid("\"Office\"").as_deref(), Some(&b"Office"[..]));
+        assert_eq!(config_ssid("436166e9").as_deref(), Some(&b"Caf\xe9"[..]));
+        assert_eq!(config_ssid("436"), None);
+    }
+}
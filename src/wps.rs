@@ -0,0 +1,247 @@
+//! Connections set up by pressing the router's WPS button (Wi-Fi Protected Setup,
+//! push-button configuration).
+
+use dbus::arg::{PropMap, Variant};
+use dbus::blocking::Connection;
+use std::time::Duration;
+
+use crate::activation::{self, StateChangeWatcher};
+use crate::client::{DbusNmClient, NmClient};
+use crate::settings::{self, ConnectionSettings};
+use crate::{device, radio, scan, wireless};
+use crate::{
+    AccessPointInfo, ActivationHandle, DbusTimeouts, ProfileChange, WifiConfigError, WifiDevice,
+    WirelessMode,
+};
+
+/// How long to wait for the WPS handshake and the activation after it. The router keeps
+/// the push-button session open for two minutes.
+const WPS_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// `NMSettingWirelessSecurityWpsMethod`: push-button configuration.
+const WPS_METHOD_PBC: u32 = 0x4;
+/// `NM80211ApFlags`: the AP's WPS push button was pressed.
+const AP_FLAGS_WPS_PBC: u32 = 0x4;
+
+/// Connects to a WPA-PSK network by WPS push-button, without knowing its password.
+///
+/// Press the WPS button on the router first. `ssid` names the network; with `None`, the
+/// access point whose push button was pressed is looked up in a fresh scan.
+///
+/// # Behavior
+///
+/// - Fails if the Wi-Fi radio is off, and uses the first usable Wi-Fi device.
+/// - Builds a profile with `802-11-wireless-security.key-mgmt: "wpa-psk"`, `wps-method`
+///   set to push-button and no PSK, and calls `AddAndActivateConnection`.
+///   NetworkManager runs the WPS handshake while it looks for the missing PSK, and saves
+///   the PSK the router hands out in the profile.
+/// - Waits up to two minutes for the activation to finish.
+///
+/// If the activation fails or times out, the new profile is deleted again, so no profile
+/// without a password is left behind.
+///
+/// # Errors
+///
+/// - [`WifiConfigError::InvalidSsid`] if `ssid` is empty or longer than 32 bytes.
+/// - [`WifiConfigError::RadioDisabled`] / [`WifiConfigError::HardwareRfkill`] if the
+///   Wi-Fi radio is off.
+/// - [`WifiConfigError::NoWifiDevice`] if no Wi-Fi device is found.
+/// - [`WifiConfigError::WpsNotActive`] if `ssid` is `None` and no access point is in
+///   push-button mode.
+/// - [`WifiConfigError::ActivationFailed`] if NetworkManager rejects the connection.
+/// - [`WifiConfigError::ActivationTimeout`] if the handshake didn't finish in time.
+/// - [`WifiConfigError::AuthenticationFailed`] / [`WifiConfigError::ConnectionFailed`]
+///   if the activation failed.
+///
+/// # Example
+///
+/// ```no_run
+/// println!("press the WPS button on the router");
+/// let handle = wifi_config::connect_wps_pbc(None)?;
+/// println!("connected on {}", handle.interface);
+/// # Ok::<(), wifi_config::WifiConfigError>(())
+/// ```
+pub fn connect_wps_pbc(ssid: Option<&str>) -> Result<ActivationHandle, WifiConfigError> {
+    if let Some(ssid) = ssid {
+        wireless::validate_ssid(ssid.as_bytes())?;
+    }
+    let conn = Connection::new_system().map_err(WifiConfigError::DbusConnection)?;
+    let timeouts = DbusTimeouts::default();
+    let client = DbusNmClient::new(&conn, timeouts);
+    radio::ensure_radio(&client, false, None)?;
+    let device = device::select_wifi_device(device::wifi_devices(&client)?, None, None)?;
+    let ssid = match ssid {
+        Some(ssid) => ssid.as_bytes().to_vec(),
+        None => {
+            let access_points = scan::scan_device(&conn, timeouts, &client, &device.path, true)?;
+            pbc_access_point(&access_points)?.ssid.clone()
+        }
+    };
+
+    // Subscribe first so no state change between activation and waiting is missed.
+    let watcher = StateChangeWatcher::new(&conn, &device.path)?;
+    let handle = provision(&client, &device, &ssid)?;
+    let result = watcher.wait(WPS_TIMEOUT, false).and_then(|outcome| {
+        if outcome.is_activated() {
+            Ok(())
+        } else {
+            Err(activation::activation_error(outcome.reason))
+        }
+    });
+    discard_on_failure(&client, &handle, result)?;
+    Ok(handle)
+}
+
+/// The access point whose WPS push button was pressed.
+fn pbc_access_point(
+    access_points: &[AccessPointInfo],
+) -> Result<&AccessPointInfo, WifiConfigError> {
+    access_points
+        .iter()
+        .find(|ap| ap.flags & AP_FLAGS_WPS_PBC != 0)
+        .ok_or(WifiConfigError::WpsNotActive)
+}
+
+/// Builds the settings of a WPA-PSK profile whose PSK comes from WPS push-button.
+fn build_wps_settings(ssid: &[u8]) -> ConnectionSettings {
+    let mut connection_settings = ConnectionSettings::new();
+
+    let mut profile_settings = PropMap::new();
+    profile_settings.insert(
+        "id".into(),
+        Variant(Box::new(String::from_utf8_lossy(ssid).into_owned())),
+    );
+    profile_settings.insert(
+        "type".into(),
+        Variant(Box::new(String::from("802-11-wireless"))),
+    );
+    connection_settings.insert("connection".into(), profile_settings);
+
+    let mut wifi_settings = PropMap::new();
+    wifi_settings.insert("ssid".into(), Variant(Box::new(ssid.to_vec())));
+    wifi_settings.insert(
+        "mode".into(),
+        Variant(Box::new(WirelessMode::Infrastructure.as_nm().to_string())),
+    );
+    connection_settings.insert("802-11-wireless".into(), wifi_settings);
+
+    let mut security_settings = PropMap::new();
+    security_settings.insert(
+        "key-mgmt".into(),
+        Variant(Box::new(String::from("wpa-psk"))),
+    );
+    security_settings.insert("wps-method".into(), Variant(Box::new(WPS_METHOD_PBC)));
+    connection_settings.insert("802-11-wireless-security".into(), security_settings);
+
+    connection_settings
+}
+
+/// Adds the WPS profile for `ssid` and activates it on `device`.
+fn provision(
+    client: &dyn NmClient,
+    device: &WifiDevice,
+    ssid: &[u8],
+) -> Result<ActivationHandle, WifiConfigError> {
+    let mut wps_settings = build_wps_settings(ssid);
+    let uuid = settings::assign_uuid(&mut wps_settings);
+    let (settings_path, active_path) = crate::add_and_activate(client, &device.path, wps_settings)?;
+    log::info!(
+        "added WPS profile {} for {}; waiting for the handshake",
+        settings_path,
+        String::from_utf8_lossy(ssid)
+    );
+    Ok(ActivationHandle {
+        settings_path,
+        active_path,
+        interface: device.interface.clone(),
+        additional: Vec::new(),
+        uuid,
+        change: ProfileChange::Created,
+        detected_security: None,
+        connectivity: None,
+        portal_url: None,
+        ip: None,
+        active: None,
+        access_point: None,
+    })
+}
+
+/// Deletes the profile of `handle` if the activation `result` is an error, then returns
+/// that error.
+fn discard_on_failure(
+    client: &dyn NmClient,
+    handle: &ActivationHandle,
+    result: Result<(), WifiConfigError>,
+) -> Result<(), WifiConfigError> {
+    if result.is_err() {
+        log::info!("WPS failed; deleting profile {}", handle.settings_path);
+        if let Err(e) = client.delete_connection(&handle.settings_path) {
+            log::warn!(
+                "failed to delete WPS profile {}: {}",
+                handle.settings_path,
+                e
+            );
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::{MockDevice, MockNmClient};
+    use dbus::arg::RefArg;
+
+    #[test]
+    fn builds_push_button_profile_without_psk() {
+        let settings = build_wps_settings(b"Office");
+        let security = &settings["802-11-wireless-security"];
+        assert_eq!(security["key-mgmt"].0.as_str(), Some("wpa-psk"));
+        assert_eq!(
+            security["wps-method"].0.as_any().downcast_ref::<u32>(),
+            Some(&WPS_METHOD_PBC)
+        );
+        assert!(!security.contains_key("psk"));
+        assert_eq!(
+            settings["802-11-wireless"]["mode"].0.as_str(),
+            Some("infrastructure")
+        );
+    }
+
+    #[test]
+    fn picks_the_access_point_in_push_button_mode() {
+        let mut device = MockDevice::new(1, "wlan0", 2)
+            .with_access_point(b"Neighbour", 0x188)
+            .with_access_point(b"Office", 0x188);
+        device.access_points[1]
+            .1
+            .insert("Flags".into(), Variant(Box::new(1 | AP_FLAGS_WPS_PBC)));
+        let client = MockNmClient::with_devices(vec![device]);
+
+        let access_points = scan::access_points(&client, &client.devices[0].path).unwrap();
+        assert_eq!(pbc_access_point(&access_points).unwrap().ssid, b"Office");
+        assert!(matches!(
+            pbc_access_point(&access_points[..1]),
+            Err(WifiConfigError::WpsNotActive)
+        ));
+    }
+
+    #[test]
+    fn deletes_the_profile_when_wps_times_out() {
+        let client = MockNmClient::with_devices(vec![MockDevice::new(1, "wlan0", 2)]);
+        let device = device::wifi_devices(&client).unwrap().remove(0);
+        let handle = provision(&client, &device, b"Office").unwrap();
+        assert_eq!(client.connections.borrow().len(), 1);
+
+        let result = discard_on_failure(
+            &client,
+            &handle,
+            Err(WifiConfigError::ActivationTimeout(WPS_TIMEOUT)),
+        );
+        assert!(matches!(result, Err(WifiConfigError::ActivationTimeout(_))));
+        assert!(client.connections.borrow().is_empty());
+        assert!(client
+            .calls()
+            .contains(&format!("Delete {}", handle.settings_path)));
+    }
+}
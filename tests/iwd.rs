@@ -0,0 +1,42 @@
+//! `connect` through iwd against a mock on a private bus; skipped without `dbus-daemon`.
+
+mod support;
+
+use support::iwd::{MockIwd, SSID};
+use wifi_config::{Backend, ConnectOptions, ProfileChange, Security, WifiConfigError};
+
+fn options(passphrase: &str) -> ConnectOptions {
+    ConnectOptions::builder()
+        .security(Security::WpaPsk(passphrase.into()))
+        .backend(Backend::Iwd)
+        .build()
+}
+
+#[test]
+fn keeps_the_known_network_when_the_new_passphrase_fails() {
+    let Some(_bus) = support::private_bus() else {
+        return;
+    };
+    let iwd = MockIwd::start("newpassword1", true);
+    match wifi_config::connect(SSID, &options("mistyped123")) {
+        Err(WifiConfigError::AuthenticationFailed(_)) => {}
+        other => panic!("expected AuthenticationFailed, got {:?}", other),
+    }
+    // The saved passphrase was tried first, then the new one.
+    assert_eq!(iwd.connect_calls(), 2);
+    assert_eq!(iwd.handed_out(), ["mistyped123"]);
+    assert!(!iwd.forgotten());
+}
+
+#[test]
+fn replaces_a_rejected_passphrase_without_forgetting() {
+    let Some(_bus) = support::private_bus() else {
+        return;
+    };
+    let iwd = MockIwd::start("newpassword1", true);
+    let handle = wifi_config::connect(SSID, &options("newpassword1")).unwrap();
+    assert_eq!(handle.interface, "wlan0");
+    assert_eq!(handle.change, ProfileChange::Updated);
+    assert_eq!(iwd.handed_out(), ["newpassword1"]);
+    assert!(!iwd.forgotten());
+}
@@ -0,0 +1,376 @@
+//! `connect` against a mock NetworkManager on a private bus; skipped without `dbus-daemon`.
+
+mod support;
+
+use serde_json::json;
+use std::time::Duration;
+use support::{values, MockDevice, MockNetworkManager, Settings, NM_BUS_NAME};
+use wifi_config::{
+    BusSelection, ConnectOptions, DbusTimeouts, Ipv4Config, ProfileChange, RadioState, Security,
+    WifiConfigError, WifiEvent,
+};
+
+/// Connects to `ssid` with `options` on a mock with an ethernet and a Wi-Fi device, and
+/// returns the settings it sent.
+fn connect(ssid: &str, options: &ConnectOptions) -> Option<Settings> {
+    let _bus = support::private_bus()?;
+    let nm = MockNetworkManager::start(
+        NM_BUS_NAME,
+        vec![MockDevice::ethernet("eth0"), MockDevice::wifi("wlan0")],
+    );
+    let handle = wifi_config::connect(ssid, options).unwrap();
+    assert_eq!(&*handle.settings_path, support::PROFILE_PATH);
+    assert_eq!(&*handle.active_path, support::ACTIVE_PATH);
+    assert_eq!(handle.interface, "wlan0");
+    assert_eq!(handle.change, ProfileChange::Created);
+
+    let calls = nm.add_and_activate_calls();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].device, MockDevice::path(1));
+    assert_eq!(calls[0].specific_object, "/");
+    let mut settings = calls[0].settings.clone();
+    // The UUID is random; it is the one in the handle.
+    let uuid = settings
+        .get_mut("connection")
+        .unwrap()
+        .remove("uuid")
+        .unwrap();
+    assert_eq!(uuid.signature, "s");
+    assert_eq!(uuid.value, json!(handle.uuid));
+    Some(settings)
+}
+
+fn section_names(settings: &Settings) -> Vec<&str> {
+    settings.keys().map(String::as_str).collect()
+}
+
+#[test]
+fn sends_wpa_psk_settings() {
+    let options = ConnectOptions::builder()
+        .security(Security::WpaPsk("supersecret123".into()))
+        .build();
+    let Some(settings) = connect("Office", &options) else {
+        return;
+    };
+    assert_eq!(
+        section_names(&settings),
+        ["802-11-wireless", "802-11-wireless-security", "connection"]
+    );
+    assert_eq!(
+        values(&settings["connection"]),
+        json!({ "id": "Office", "type": "802-11-wireless" })
+    );
+    assert_eq!(
+        values(&settings["802-11-wireless"]),
+        json!({ "mode": "infrastructure", "ssid": b"Office" })
+    );
+    assert_eq!(
+        support::signatures(&settings["802-11-wireless"]),
+        [("mode", "s"), ("ssid", "ay")].into()
+    );
+    assert_eq!(
+        values(&settings["802-11-wireless-security"]),
+        json!({ "key-mgmt": "wpa-psk", "psk": "supersecret123" })
+    );
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn connect_async_sends_what_connect_sends() {
+    let Some(_bus) = support::private_bus() else {
+        return;
+    };
+    let nm = MockNetworkManager::start(NM_BUS_NAME, vec![MockDevice::wifi("wlan0")]);
+    let options = ConnectOptions::builder()
+        .security(Security::WpaPsk("supersecret123".into()))
+        .build();
+    let handle = wifi_config::connect_async("Office", &options)
+        .await
+        .unwrap();
+    assert_eq!(&*handle.active_path, support::ACTIVE_PATH);
+    assert_eq!(handle.interface, "wlan0");
+
+    let calls = nm.add_and_activate_calls();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].device, MockDevice::path(0));
+    assert_eq!(
+        values(&calls[0].settings["802-11-wireless-security"]),
+        json!({ "key-mgmt": "wpa-psk", "psk": "supersecret123" })
+    );
+}
+
+#[test]
+fn sends_hidden_networks_as_hidden() {
+    let options = ConnectOptions::builder()
+        .security(Security::WpaPsk("supersecret123".into()))
+        .hidden(true)
+        .build();
+    let Some(settings) = connect("Hidden", &options) else {
+        return;
+    };
+    assert_eq!(
+        values(&settings["802-11-wireless"]),
+        json!({ "hidden": true, "mode": "infrastructure", "ssid": b"Hidden" })
+    );
+    assert_eq!(settings["802-11-wireless"]["hidden"].signature, "b");
+}
+
+#[test]
+fn sends_open_networks_without_a_security_section() {
+    let Some(settings) = connect("Cafe", &ConnectOptions::default()) else {
+        return;
+    };
+    assert_eq!(section_names(&settings), ["802-11-wireless", "connection"]);
+    assert_eq!(
+        values(&settings["802-11-wireless"]),
+        json!({ "mode": "infrastructure", "ssid": b"Cafe" })
+    );
+}
+
+#[test]
+fn sends_static_addresses() {
+    let options = ConnectOptions::builder()
+        .ipv4(Ipv4Config::Manual {
+            address: [192, 168, 1, 50].into(),
+            prefix: 24,
+            gateway: Some([192, 168, 1, 1].into()),
+        })
+        .build();
+    let Some(settings) = connect("Lab", &options) else {
+        return;
+    };
+    assert_eq!(
+        section_names(&settings),
+        ["802-11-wireless", "connection", "ipv4"]
+    );
+    assert_eq!(
+        values(&settings["ipv4"]),
+        json!({
+            "method": "manual",
+            "address-data": [{ "address": "192.168.1.50", "prefix": 24 }],
+            "gateway": "192.168.1.1",
+        })
+    );
+    assert_eq!(
+        support::signatures(&settings["ipv4"]),
+        [
+            ("address-data", "aa{sv}"),
+            ("gateway", "s"),
+            ("method", "s")
+        ]
+        .into()
+    );
+}
+
+#[test]
+fn propagates_error_replies() {
+    let Some(_bus) = support::private_bus() else {
+        return;
+    };
+    let options = ConnectOptions::builder()
+        .security(Security::WpaPsk("supersecret123".into()))
+        .build();
+    assert!(matches!(
+        wifi_config::connect("Office", &options),
+        Err(WifiConfigError::NetworkManagerUnavailable(_))
+    ));
+
+    let nm = MockNetworkManager::start(NM_BUS_NAME, vec![MockDevice::wifi("wlan0")]);
+    nm.fail_add_and_activate(
+        "org.freedesktop.NetworkManager.PermissionDenied",
+        "Insufficient privileges",
+    );
+    match wifi_config::connect("Office", &options) {
+        Err(e @ WifiConfigError::PermissionDenied(_)) => {
+            let error = e.dbus_error().unwrap();
+            assert_eq!(
+                error.name(),
+                Some("org.freedesktop.NetworkManager.PermissionDenied")
+            );
+            assert_eq!(error.message(), Some("Insufficient privileges"));
+        }
+        other => panic!("expected PermissionDenied, got {:?}", other),
+    }
+
+    nm.fail_add_and_activate(
+        "org.freedesktop.NetworkManager.Settings.Connection.InvalidProperty",
+        "802-11-wireless-security.psk: property is invalid",
+    );
+    match wifi_config::connect("Office", &options) {
+        Err(WifiConfigError::InvalidSettings { property, .. }) => {
+            assert_eq!(property, "802-11-wireless-security.psk")
+        }
+        other => panic!("expected InvalidSettings, got {:?}", other),
+    }
+
+    nm.fail_add_and_activate("org.freedesktop.NetworkManager.Failed", "no reason");
+    assert!(matches!(
+        wifi_config::connect("Office", &options),
+        Err(WifiConfigError::ActivationFailed(reason))
+            if reason == "no reason (org.freedesktop.NetworkManager.Failed)"
+    ));
+    assert!(nm.add_and_activate_calls().is_empty());
+}
+
+#[test]
+fn lists_devices_with_their_capabilities() {
+    let Some(_bus) = support::private_bus() else {
+        return;
+    };
+    let ap_less = MockDevice {
+        capabilities: 0x328,
+        ..MockDevice::wifi("wlan1")
+    };
+    let _nm = MockNetworkManager::start(
+        NM_BUS_NAME,
+        vec![
+            MockDevice::wifi("wlan0"),
+            MockDevice::ethernet("eth0"),
+            ap_less,
+        ],
+    );
+    let devices = wifi_config::list_wifi_devices().unwrap();
+    let interfaces: Vec<&str> = devices.iter().map(|d| d.interface.as_str()).collect();
+    assert_eq!(interfaces, ["wlan0", "wlan1"]);
+    assert_eq!(devices[0].perm_hw_address, "02:00:00:00:00:01");
+    assert_eq!(devices[0].mtu, 1500);
+    assert!(devices[0].capabilities.ap && devices[0].capabilities.band_5ghz);
+    assert!(!devices[1].capabilities.ap && !devices[1].capabilities.band_5ghz);
+
+    let hotspot = wifi_config::HotspotOptions {
+        interface: Some("wlan1".into()),
+        ..Default::default()
+    };
+    assert!(matches!(
+        wifi_config::create_hotspot("Provisioning", "setup-1234", &hotspot),
+        Err(WifiConfigError::HotspotUnsupported(interface)) if interface == "wlan1"
+    ));
+}
+
+#[test]
+fn watch_carries_on_when_network_manager_restarts() {
+    let Some(_bus) = support::private_bus() else {
+        return;
+    };
+    let nm = MockNetworkManager::start(NM_BUS_NAME, vec![MockDevice::wifi("wlan0")]);
+    assert!(matches!(
+        wifi_config::watch_wifi(Some("wlan1")),
+        Err(WifiConfigError::DeviceNotFound(interface)) if interface == "wlan1"
+    ));
+    let watcher = wifi_config::watch_wifi(Some("wlan0")).unwrap();
+
+    drop(nm);
+    assert_eq!(
+        watcher.recv_timeout(Duration::from_secs(5)),
+        Some(WifiEvent::NetworkManagerStopped)
+    );
+    let _nm = MockNetworkManager::start(NM_BUS_NAME, vec![MockDevice::wifi("wlan0")]);
+    assert_eq!(
+        watcher.recv_timeout(Duration::from_secs(5)),
+        Some(WifiEvent::NetworkManagerStarted)
+    );
+    assert_eq!(watcher.recv_timeout(Duration::from_millis(300)), None);
+}
+
+#[test]
+fn one_manager_serves_several_threads() {
+    let Some(_bus) = support::private_bus() else {
+        return;
+    };
+    let _nm = MockNetworkManager::start(
+        NM_BUS_NAME,
+        vec![MockDevice::ethernet("eth0"), MockDevice::wifi("wlan0")],
+    );
+    let manager = wifi_config::WifiManager::new().unwrap();
+    std::thread::scope(|scope| {
+        for thread in 0..8 {
+            let manager = &manager;
+            scope.spawn(move || {
+                for i in 0..25 {
+                    match (thread + i) % 3 {
+                        0 => assert_eq!(manager.active_connection().unwrap(), None),
+                        1 => assert_eq!(manager.device().unwrap().interface, "wlan0"),
+                        _ => assert_eq!(manager.radio_state().unwrap(), RadioState::Enabled),
+                    }
+                }
+            });
+        }
+    });
+}
+
+#[test]
+fn manager_talks_to_the_bus_it_was_given() {
+    let Some(bus) = support::private_bus() else {
+        return;
+    };
+    let _nm = MockNetworkManager::start(NM_BUS_NAME, vec![MockDevice::wifi("wlan0")]);
+    let nowhere = BusSelection::Address("unix:path=/nonexistent/bus".into());
+    assert!(matches!(
+        wifi_config::WifiManager::with_bus(nowhere),
+        Err(WifiConfigError::DbusConnection(_))
+    ));
+    let manager =
+        wifi_config::WifiManager::with_bus(BusSelection::Address(bus.address.clone())).unwrap();
+    assert_eq!(manager.device().unwrap().interface, "wlan0");
+    assert_eq!(manager.active_connection().unwrap(), None);
+}
+
+#[test]
+fn manager_connects_and_waits_on_the_bus_it_was_given() {
+    let Some(bus) = support::private_bus() else {
+        return;
+    };
+    let nm = MockNetworkManager::start(
+        NM_BUS_NAME,
+        vec![MockDevice::wifi("wlan0"), MockDevice::wifi("wlan1")],
+    );
+    let manager = wifi_config::WifiManager::with_bus(BusSelection::Address(bus.address.clone()))
+        .unwrap()
+        .for_interface("wlan1");
+    let options = ConnectOptions::builder()
+        .security(Security::WpaPsk("supersecret123".into()))
+        .build();
+    let handle = manager.connect_and_wait("Office", &options).unwrap();
+    assert_eq!(&*handle.active_path, support::ACTIVE_PATH);
+    assert_eq!(handle.interface, "wlan1");
+    let calls = nm.add_and_activate_calls();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].device, MockDevice::path(1));
+    // The manager's own connection stayed usable while the call waited on its own.
+    assert_eq!(manager.device().unwrap().interface, "wlan1");
+}
+
+#[test]
+fn manager_calls_give_up_after_its_timeouts() {
+    let Some(bus) = support::private_bus() else {
+        return;
+    };
+    let nm = MockNetworkManager::start(NM_BUS_NAME, vec![MockDevice::wifi("wlan0")]);
+    nm.delay_get_devices(Duration::from_millis(500));
+    let manager = wifi_config::WifiManager::with_bus(BusSelection::Address(bus.address.clone()))
+        .unwrap()
+        .with_timeouts(DbusTimeouts::uniform(Duration::from_millis(100)));
+    assert!(matches!(
+        manager.devices(),
+        Err(WifiConfigError::Timeout(_))
+    ));
+}
+
+#[test]
+fn connect_and_wait_refuses_what_network_manager_is_too_old_for() {
+    let Some(_bus) = support::private_bus() else {
+        return;
+    };
+    let nm = MockNetworkManager::start(NM_BUS_NAME, vec![MockDevice::wifi("wlan0")]);
+    nm.set_version("1.14.2");
+    let options = ConnectOptions::builder()
+        .security(Security::Wpa3Sae("supersecret123".into()))
+        .build();
+    match wifi_config::connect_and_wait("Office", &options) {
+        Err(WifiConfigError::UnsupportedByNetworkManager { feature, .. }) => {
+            assert_eq!(feature, "WPA3-SAE")
+        }
+        other => panic!("expected UnsupportedByNetworkManager, got {:?}", other),
+    }
+    assert!(nm.add_and_activate_calls().is_empty());
+}
@@ -0,0 +1,224 @@
+//! A mock iwd on a [`PrivateBus`](super::PrivateBus), serving one station and one network
+//! in range, `Office`, for [`Backend::Iwd`](wifi_config::Backend::Iwd).
+//!
+//! Like iwd, `Network.Connect` on a known network first tries the passphrase saved for
+//! it, and only asks the registered agent for one after that was rejected.
+
+use dbus::blocking::Connection;
+use dbus::channel::MatchingReceiver;
+use dbus::message::MatchRule;
+use dbus::strings::ErrorName;
+use dbus::MethodErr;
+use dbus_crossroads::Crossroads;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+const IWD_BUS_NAME: &str = "net.connman.iwd";
+const AGENT_MANAGER_PATH: &str = "/net/connman/iwd";
+const STATION_PATH: &str = "/net/connman/iwd/0/3";
+const NETWORK_PATH: &str = "/net/connman/iwd/0/3/4f6666696365_psk";
+const KNOWN_NETWORK_PATH: &str = "/net/connman/iwd/4f6666696365_psk";
+/// The SSID of the network in range.
+pub const SSID: &str = "Office";
+
+#[derive(Default)]
+struct State {
+    /// The passphrase the access point takes.
+    passphrase: String,
+    /// Whether the passphrase saved for the known network was rejected, so the next
+    /// `Connect` asks the agent.
+    ask_passphrase: bool,
+    /// The bus name and object path of the registered agent.
+    agent: Option<(String, dbus::Path<'static>)>,
+    connect_calls: usize,
+    handed_out: Vec<String>,
+    forgotten: bool,
+}
+
+type Shared = Arc<Mutex<State>>;
+
+/// A mock iwd serving until dropped.
+pub struct MockIwd {
+    state: Shared,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockIwd {
+    /// Serves `Office`, whose access point takes `passphrase`; with `known`, iwd has it
+    /// saved with a passphrase that no longer works.
+    pub fn start(passphrase: &str, known: bool) -> Self {
+        let state = Shared::new(Mutex::new(State {
+            passphrase: passphrase.into(),
+            ask_passphrase: !known,
+            ..State::default()
+        }));
+        let stop = Arc::new(AtomicBool::new(false));
+        let conn = Connection::new_system().expect("the private bus is up");
+        conn.request_name(IWD_BUS_NAME, false, false, true)
+            .expect("the bus name is free");
+        let thread = {
+            let state = Arc::clone(&state);
+            let stop = Arc::clone(&stop);
+            std::thread::spawn(move || serve(conn, state, known, &stop))
+        };
+        MockIwd {
+            state,
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// How often `Network.Connect` was called.
+    pub fn connect_calls(&self) -> usize {
+        self.state.lock().unwrap().connect_calls
+    }
+
+    /// The passphrases the agent handed out, in order.
+    pub fn handed_out(&self) -> Vec<String> {
+        self.state.lock().unwrap().handed_out.clone()
+    }
+
+    /// Whether `KnownNetwork.Forget` was called.
+    pub fn forgotten(&self) -> bool {
+        self.state.lock().unwrap().forgotten
+    }
+}
+
+impl Drop for MockIwd {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.join().expect("the mock doesn't panic");
+        }
+    }
+}
+
+fn serve(conn: Connection, state: Shared, known: bool, stop: &AtomicBool) {
+    let mut cr = Crossroads::new();
+
+    let agent_manager = cr.register("net.connman.iwd.AgentManager", |b| {
+        b.method(
+            "RegisterAgent",
+            ("path",),
+            (),
+            |ctx, state: &mut Shared, (path,): (dbus::Path<'static>,)| {
+                let sender = ctx.message().sender().expect("calls have a sender");
+                state.lock().unwrap().agent = Some((sender.to_string(), path));
+                Ok(())
+            },
+        );
+        b.method(
+            "UnregisterAgent",
+            ("path",),
+            (),
+            |_, state: &mut Shared, (_,): (dbus::Path<'static>,)| {
+                state.lock().unwrap().agent = None;
+                Ok(())
+            },
+        );
+    });
+    cr.insert(AGENT_MANAGER_PATH, &[agent_manager], Arc::clone(&state));
+
+    let device = cr.register("net.connman.iwd.Device", |b| {
+        b.property("Name")
+            .get(|_, _: &mut Shared| Ok(String::from("wlan0")));
+    });
+    let station = cr.register("net.connman.iwd.Station", |b| {
+        b.property("State")
+            .get(|_, _: &mut Shared| Ok(String::from("disconnected")));
+        b.property("Scanning").get(|_, _: &mut Shared| Ok(false));
+        b.method(
+            "GetOrderedNetworks",
+            (),
+            ("networks",),
+            |_, _: &mut Shared, ()| Ok((vec![(dbus::Path::from(NETWORK_PATH), -5000i16)],)),
+        );
+        b.method("Scan", (), (), |_, _: &mut Shared, ()| Ok(()));
+    });
+    cr.insert(STATION_PATH, &[device, station], Arc::clone(&state));
+
+    let network = cr.register("net.connman.iwd.Network", |b| {
+        b.property("Name")
+            .get(|_, _: &mut Shared| Ok(String::from(SSID)));
+        b.property("Type")
+            .get(|_, _: &mut Shared| Ok(String::from("psk")));
+        b.property("Connected").get(|_, _: &mut Shared| Ok(false));
+        b.method("Connect", (), (), |_, state: &mut Shared, ()| {
+            connect(state)
+        });
+    });
+    cr.insert(NETWORK_PATH, &[network], Arc::clone(&state));
+
+    if known {
+        let known_network = cr.register("net.connman.iwd.KnownNetwork", |b| {
+            b.property("Name")
+                .get(|_, _: &mut Shared| Ok(String::from(SSID)));
+            b.property("Type")
+                .get(|_, _: &mut Shared| Ok(String::from("psk")));
+            b.method("Forget", (), (), |_, state: &mut Shared, ()| {
+                state.lock().unwrap().forgotten = true;
+                Ok(())
+            });
+        });
+        cr.insert(KNOWN_NETWORK_PATH, &[known_network], Arc::clone(&state));
+    }
+    let object_manager = cr.object_manager();
+    cr.insert("/", &[object_manager], state);
+
+    conn.start_receive(
+        MatchRule::new_method_call(),
+        Box::new(move |message, conn| {
+            let _ = cr.handle_message(message, conn);
+            true
+        }),
+    );
+    while !stop.load(Ordering::Relaxed) {
+        if conn.process(Duration::from_millis(20)).is_err() {
+            break;
+        }
+    }
+}
+
+/// `Network.Connect`: rejects the saved passphrase of a known network, then asks the
+/// agent and connects if it hands out the one the access point takes.
+fn connect(state: &Shared) -> Result<(), MethodErr> {
+    let agent = {
+        let mut state = state.lock().unwrap();
+        state.connect_calls += 1;
+        if !state.ask_passphrase {
+            state.ask_passphrase = true;
+            return Err(failed());
+        }
+        state.agent.clone()
+    };
+    let (agent_name, agent_path) = agent.ok_or_else(|| MethodErr::failed("no agent"))?;
+    // Over a second connection, since this one is busy with the `Connect` call.
+    let conn = Connection::new_system().expect("the private bus is up");
+    let (passphrase,): (String,) = conn
+        .with_proxy(agent_name, agent_path, Duration::from_secs(5))
+        .method_call(
+            "net.connman.iwd.Agent",
+            "RequestPassphrase",
+            (dbus::Path::from(NETWORK_PATH),),
+        )
+        .map_err(|_| MethodErr::from((ErrorName::from("net.connman.iwd.Aborted"), "canceled")))?;
+    let mut state = state.lock().unwrap();
+    let accepted = passphrase == state.passphrase;
+    state.handed_out.push(passphrase);
+    if accepted {
+        Ok(())
+    } else {
+        Err(failed())
+    }
+}
+
+/// The error iwd answers a failed `Connect` with.
+fn failed() -> MethodErr {
+    MethodErr::from((
+        ErrorName::from("net.connman.iwd.Failed"),
+        "operation failed",
+    ))
+}
@@ -0,0 +1,408 @@
+//! A mock NetworkManager on a private message bus, for the integration tests.
+//!
+//! [`private_bus`] starts a `dbus-daemon` and points the library's system bus connection
+//! at it; [`MockNetworkManager`] then claims a bus name on it and serves just enough of
+//! NetworkManager's object tree for [`wifi_config::connect`]: `GetDevices`, the device
+//! properties, `Settings.ListConnections` and `AddAndActivateConnection`, which records
+//! the settings it receives and then reports the device activated.
+//!
+//! [`iwd::MockIwd`] does the same for iwd.
+
+// Every test crate uses only some of the mocks.
+#![allow(dead_code)]
+
+use dbus::arg::{ArgType, PropMap, RefArg};
+use dbus::blocking::Connection;
+use dbus::channel::MatchingReceiver;
+use dbus::message::MatchRule;
+use dbus::strings::ErrorName;
+use dbus::{Message, MethodErr};
+use dbus_crossroads::Crossroads;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+pub mod iwd;
+
+/// NetworkManager's well-known bus name, which the library calls.
+pub const NM_BUS_NAME: &str = "org.freedesktop.NetworkManager";
+
+const NM_PATH: &str = "/org/freedesktop/NetworkManager";
+const SETTINGS_PATH: &str = "/org/freedesktop/NetworkManager/Settings";
+/// What the mock's `AddAndActivateConnection` returns.
+pub const PROFILE_PATH: &str = "/org/freedesktop/NetworkManager/Settings/1";
+pub const ACTIVE_PATH: &str = "/org/freedesktop/NetworkManager/ActiveConnection/1";
+
+/// `NMDeviceState` of a device that is ready to connect.
+const DEVICE_DISCONNECTED: u32 = 30;
+/// `NMDeviceState` of a device whose connection is up.
+const DEVICE_ACTIVATED: u32 = 100;
+
+/// Serializes the tests: the library reads the bus address once per process, so every
+/// test's daemon listens on the same socket.
+static BUS_LOCK: Mutex<()> = Mutex::new(());
+
+/// A `dbus-daemon` the library uses as its system bus, stopped on drop.
+pub struct PrivateBus {
+    daemon: Child,
+    socket: String,
+    /// The bus address, also set as `DBUS_SYSTEM_BUS_ADDRESS`.
+    pub address: String,
+    _lock: MutexGuard<'static, ()>,
+}
+
+/// Starts a private bus for one test, or returns `None` (and says so) if `dbus-daemon`
+/// isn't installed, so the test can skip.
+pub fn private_bus() -> Option<PrivateBus> {
+    let lock = BUS_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let socket = std::env::temp_dir()
+        .join(format!("wifi-config-test-{}", std::process::id()))
+        .display()
+        .to_string();
+    let _ = std::fs::remove_file(&socket);
+    let address = format!("unix:path={}", socket);
+    let spawned = Command::new("dbus-daemon")
+        .args(["--session", "--nofork", "--print-address"])
+        .arg(format!("--address={}", address))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn();
+    let mut daemon = match spawned {
+        Ok(daemon) => daemon,
+        Err(e) => {
+            eprintln!("skipping: can't start dbus-daemon: {}", e);
+            return None;
+        }
+    };
+    // The daemon prints its address once it listens.
+    let mut printed = String::new();
+    let stdout = daemon.stdout.take().expect("stdout is piped");
+    BufReader::new(stdout)
+        .read_line(&mut printed)
+        .expect("dbus-daemon prints its address");
+    assert!(
+        printed.starts_with(&address),
+        "unexpected address {:?}",
+        printed
+    );
+    std::env::set_var("DBUS_SYSTEM_BUS_ADDRESS", &address);
+    Some(PrivateBus {
+        daemon,
+        socket,
+        address,
+        _lock: lock,
+    })
+}
+
+impl Drop for PrivateBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+        let _ = std::fs::remove_file(&self.socket);
+    }
+}
+
+/// A network device of the mock.
+#[derive(Debug, Clone)]
+pub struct MockDevice {
+    pub interface: String,
+    /// `NMDeviceType`; 2 is Wi-Fi, 1 ethernet.
+    pub device_type: u32,
+    pub hw_address: String,
+    /// `NMDeviceWifiCapabilities`.
+    pub capabilities: u32,
+}
+
+impl MockDevice {
+    pub fn wifi(interface: &str) -> Self {
+        MockDevice {
+            interface: interface.into(),
+            device_type: 2,
+            hw_address: "02:00:00:00:00:01".into(),
+            // CCMP, WPA, RSN and AP on both bands.
+            capabilities: 0x778,
+        }
+    }
+
+    pub fn ethernet(interface: &str) -> Self {
+        MockDevice {
+            device_type: 1,
+            ..MockDevice::wifi(interface)
+        }
+    }
+
+    /// Object path of the device at `index` in [`MockNetworkManager::start`]'s list.
+    pub fn path(index: usize) -> String {
+        format!("/org/freedesktop/NetworkManager/Devices/{}", index + 1)
+    }
+}
+
+/// One setting received by the mock: its D-Bus signature and value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Setting {
+    pub signature: String,
+    pub value: Value,
+}
+
+/// Settings received by the mock, by section and key.
+pub type Settings = BTreeMap<String, BTreeMap<String, Setting>>;
+
+/// An `AddAndActivateConnection` call the mock received.
+#[derive(Debug, Clone)]
+pub struct AddAndActivateCall {
+    pub settings: Settings,
+    pub device: String,
+    pub specific_object: String,
+}
+
+#[derive(Default)]
+struct State {
+    version: Option<String>,
+    get_devices_delay: Duration,
+    add_and_activate_error: Option<(ErrorName<'static>, String)>,
+    calls: Vec<AddAndActivateCall>,
+}
+
+type Shared = Arc<Mutex<State>>;
+
+/// A mock NetworkManager serving on a [`PrivateBus`] until dropped.
+pub struct MockNetworkManager {
+    state: Shared,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockNetworkManager {
+    /// Claims `bus_name` on the private bus and serves `devices` under it.
+    pub fn start(bus_name: &str, devices: Vec<MockDevice>) -> Self {
+        let state = Shared::default();
+        let stop = Arc::new(AtomicBool::new(false));
+        let conn = Connection::new_system().expect("the private bus is up");
+        conn.request_name(bus_name, false, false, true)
+            .expect("the bus name is free");
+        let thread = {
+            let state = Arc::clone(&state);
+            let stop = Arc::clone(&stop);
+            std::thread::spawn(move || serve(conn, state, devices, &stop))
+        };
+        MockNetworkManager {
+            state,
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Makes `AddAndActivateConnection` reply with the D-Bus error `name`.
+    pub fn fail_add_and_activate(&self, name: &str, message: &str) {
+        let name = ErrorName::new(name.to_string()).expect("a valid error name");
+        self.state.lock().unwrap().add_and_activate_error = Some((name, message.into()));
+    }
+
+    /// Makes the `Version` property read `version` instead of `1.46.0`.
+    pub fn set_version(&self, version: &str) {
+        self.state.lock().unwrap().version = Some(version.into());
+    }
+
+    /// Makes `GetDevices` answer only after `delay`.
+    pub fn delay_get_devices(&self, delay: Duration) {
+        self.state.lock().unwrap().get_devices_delay = delay;
+    }
+
+    /// The `AddAndActivateConnection` calls received so far.
+    pub fn add_and_activate_calls(&self) -> Vec<AddAndActivateCall> {
+        self.state.lock().unwrap().calls.clone()
+    }
+}
+
+impl Drop for MockNetworkManager {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.join().expect("the mock doesn't panic");
+        }
+    }
+}
+
+fn serve(conn: Connection, state: Shared, devices: Vec<MockDevice>, stop: &AtomicBool) {
+    let mut cr = Crossroads::new();
+    let device_paths: Vec<dbus::Path<'static>> = (0..devices.len())
+        .map(|index| dbus::Path::from(MockDevice::path(index)))
+        .collect();
+
+    let manager = cr.register(NM_BUS_NAME, |b| {
+        let paths = device_paths.clone();
+        b.method(
+            "GetDevices",
+            (),
+            ("devices",),
+            move |_, state: &mut Shared, ()| {
+                let delay = state.lock().unwrap().get_devices_delay;
+                std::thread::sleep(delay);
+                Ok((paths.clone(),))
+            },
+        );
+        b.method(
+            "AddAndActivateConnection",
+            ("connection", "device", "specific_object"),
+            ("path", "active_connection"),
+            |ctx,
+             state: &mut Shared,
+             (settings, device, specific_object): (
+                HashMap<String, PropMap>,
+                dbus::Path<'static>,
+                dbus::Path<'static>,
+            )| {
+                let mut state = state.lock().unwrap();
+                if let Some((name, message)) = &state.add_and_activate_error {
+                    return Err(MethodErr::from((name.clone(), message.clone())));
+                }
+                state.calls.push(AddAndActivateCall {
+                    settings: record(&settings),
+                    device: device.to_string(),
+                    specific_object: specific_object.to_string(),
+                });
+                // Sent after the reply, as NetworkManager's state changes follow it.
+                let activated = Message::signal(
+                    &device,
+                    &"org.freedesktop.NetworkManager.Device".into(),
+                    &"StateChanged".into(),
+                )
+                .append3(DEVICE_ACTIVATED, DEVICE_DISCONNECTED, 0u32);
+                ctx.push_msg(activated);
+                Ok((
+                    dbus::Path::from(PROFILE_PATH),
+                    dbus::Path::from(ACTIVE_PATH),
+                ))
+            },
+        );
+        b.property("WirelessEnabled").get(|_, _| Ok(true));
+        b.property("WirelessHardwareEnabled").get(|_, _| Ok(true));
+        b.property("Version").get(|_, state: &mut Shared| {
+            let version = state.lock().unwrap().version.clone();
+            Ok(version.unwrap_or_else(|| String::from("1.46.0")))
+        });
+    });
+    cr.insert(NM_PATH, &[manager], Arc::clone(&state));
+
+    let settings = cr.register("org.freedesktop.NetworkManager.Settings", |b| {
+        b.method(
+            "ListConnections",
+            (),
+            ("connections",),
+            |_, _: &mut Shared, ()| Ok((Vec::<dbus::Path<'static>>::new(),)),
+        );
+    });
+    cr.insert(SETTINGS_PATH, &[settings], state);
+
+    let device = cr.register("org.freedesktop.NetworkManager.Device", |b| {
+        b.property("DeviceType")
+            .get(|_, device: &mut MockDevice| Ok(device.device_type));
+        b.property("Interface")
+            .get(|_, device: &mut MockDevice| Ok(device.interface.clone()));
+        b.property("Driver")
+            .get(|_, _: &mut MockDevice| Ok(String::from("mock")));
+        b.property("State")
+            .get(|_, _: &mut MockDevice| Ok(DEVICE_DISCONNECTED));
+        b.property("Managed").get(|_, _: &mut MockDevice| Ok(true));
+        b.property("Mtu").get(|_, _: &mut MockDevice| Ok(1500u32));
+        b.property("ActiveConnection")
+            .get(|_, _: &mut MockDevice| Ok(dbus::Path::from("/")));
+    });
+    let wireless = cr.register("org.freedesktop.NetworkManager.Device.Wireless", |b| {
+        b.property("PermHwAddress")
+            .get(|_, device: &mut MockDevice| Ok(device.hw_address.clone()));
+        b.property("WirelessCapabilities")
+            .get(|_, device: &mut MockDevice| Ok(device.capabilities));
+        b.property("ActiveAccessPoint")
+            .get(|_, _: &mut MockDevice| Ok(dbus::Path::from("/")));
+    });
+    for (path, mock_device) in device_paths.into_iter().zip(devices) {
+        cr.insert(path, &[device, wireless], mock_device);
+    }
+
+    conn.start_receive(
+        MatchRule::new_method_call(),
+        Box::new(move |message, conn| {
+            let _ = cr.handle_message(message, conn);
+            true
+        }),
+    );
+    while !stop.load(Ordering::Relaxed) {
+        if conn.process(Duration::from_millis(20)).is_err() {
+            break;
+        }
+    }
+}
+
+/// Converts received settings into comparable values.
+fn record(settings: &HashMap<String, PropMap>) -> Settings {
+    settings
+        .iter()
+        .map(|(name, section)| {
+            let values = section
+                .iter()
+                .map(|(key, value)| {
+                    let setting = Setting {
+                        signature: value.0.signature().to_string(),
+                        value: to_json(&*value.0),
+                    };
+                    (key.clone(), setting)
+                })
+                .collect();
+            (name.clone(), values)
+        })
+        .collect()
+}
+
+fn to_json(value: &dyn RefArg) -> Value {
+    let items = || value.as_iter().into_iter().flatten();
+    match value.arg_type() {
+        ArgType::Boolean => Value::Bool(value.as_u64() == Some(1)),
+        ArgType::Int16 | ArgType::Int32 | ArgType::Int64 => value.as_i64().into(),
+        ArgType::Byte | ArgType::UInt16 | ArgType::UInt32 | ArgType::UInt64 => {
+            value.as_u64().into()
+        }
+        ArgType::String | ArgType::ObjectPath | ArgType::Signature => value.as_str().into(),
+        ArgType::Variant => items().next().map_or(Value::Null, to_json),
+        ArgType::Array if value.signature().starts_with("a{") => {
+            // A dict iterates as alternating keys and values.
+            let items: Vec<&dyn RefArg> = items().collect();
+            Value::Object(
+                items
+                    .chunks(2)
+                    .map(|pair| {
+                        let key = pair[0].as_str().unwrap_or_default().to_string();
+                        (key, to_json(pair[1]))
+                    })
+                    .collect(),
+            )
+        }
+        ArgType::Array | ArgType::Struct => Value::Array(items().map(to_json).collect()),
+        other => panic!("unexpected D-Bus type {:?}", other),
+    }
+}
+
+/// The values of a section, without their signatures.
+pub fn values(section: &BTreeMap<String, Setting>) -> Value {
+    Value::Object(
+        section
+            .iter()
+            .map(|(key, setting)| (key.clone(), setting.value.clone()))
+            .collect(),
+    )
+}
+
+/// The signatures of a section's values.
+pub fn signatures(section: &BTreeMap<String, Setting>) -> BTreeMap<&str, &str> {
+    section
+        .iter()
+        .map(|(key, setting)| (key.as_str(), setting.signature.as_str()))
+        .collect()
+}